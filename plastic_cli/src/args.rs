@@ -0,0 +1,113 @@
+use crate::CliError;
+use std::path::PathBuf;
+
+/// The help text printed by `plastic-test --help` and on usage errors
+pub const USAGE: &str = "\
+USAGE: plastic-test <rom-file> [options]
+
+Options:
+  --frames <n>                 Run at most <n> frames (default: 600)
+  --expect-hash <hex>          Pass only if the hash of the last frame is <hex>
+  --result-protocol blargg     Pass only if the ROM reports success at $6000,
+                               the run stops when the result is ready
+  --input <movie-file>         Play the inputs of a movie recorded with plastic_core
+  --dump-screenshot <png-file> Save the last frame as a PNG image
+  --dump-audio <wav-file>      Save the audio as a stereo 32-bit float WAV file
+  -h, --help                   Print this help
+
+Exit codes: 0 if all the checks passed, 1 if a check failed, 2 on errors";
+
+/// How the ROM reports its result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultProtocol {
+    /// blargg's test ROMs, the status at `$6000` and the text from `$6004`
+    Blargg,
+}
+
+/// The options of a run, see [`USAGE`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub rom: PathBuf,
+    pub frames: u64,
+    pub expect_hash: Option<u64>,
+    pub result_protocol: Option<ResultProtocol>,
+    /// a movie file written by [`Movie::save_to`](plastic_core::movie::Movie::save_to)
+    pub input: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub audio: Option<PathBuf>,
+}
+
+impl Options {
+    /// Run `rom` for 600 frames without any checks
+    pub fn new<P: Into<PathBuf>>(rom: P) -> Self {
+        Self {
+            rom: rom.into(),
+            frames: 600,
+            expect_hash: None,
+            result_protocol: None,
+            input: None,
+            screenshot: None,
+            audio: None,
+        }
+    }
+}
+
+/// Parse the command line arguments, without the program name.
+///
+/// Returns `Ok(None)` if the help was requested.
+pub fn parse_args<I, S>(args: I) -> Result<Option<Options>, CliError>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut args = args.into_iter().map(Into::into);
+    let mut rom = None;
+    let mut options = Options::new(PathBuf::new());
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| CliError::Usage(format!("missing value for `{arg}`")))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--frames" => {
+                let frames = value()?;
+                options.frames = frames
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("invalid frames count `{frames}`")))?;
+            }
+            "--expect-hash" => {
+                let hash = value()?;
+                let digits = hash.strip_prefix("0x").unwrap_or(&hash);
+                options.expect_hash = Some(
+                    u64::from_str_radix(digits, 16)
+                        .map_err(|_| CliError::Usage(format!("invalid frame hash `{hash}`")))?,
+                );
+            }
+            "--result-protocol" => {
+                options.result_protocol = match value()?.as_str() {
+                    "blargg" => Some(ResultProtocol::Blargg),
+                    protocol => {
+                        return Err(CliError::Usage(format!(
+                            "unknown result protocol `{protocol}`"
+                        )))
+                    }
+                };
+            }
+            "--input" => options.input = Some(value()?.into()),
+            "--dump-screenshot" => options.screenshot = Some(value()?.into()),
+            "--dump-audio" => options.audio = Some(value()?.into()),
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option `{arg}`")))
+            }
+            _ if rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{arg}`"))),
+        }
+    }
+
+    options.rom = rom.ok_or_else(|| CliError::Usage("missing the ROM file".to_string()))?;
+
+    Ok(Some(options))
+}
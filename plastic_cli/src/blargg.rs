@@ -0,0 +1,73 @@
+use plastic_core::NES;
+use std::fmt;
+
+/// Written to `$6001-$6003` when the memory at `$6000` holds a result
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+/// The longest text read from `$6004`, the rest of the 8KB PRG RAM
+const MAX_TEXT_LENGTH: u16 = 0x1FFC;
+
+/// The result of a ROM using the protocol of blargg's test ROMs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlarggResult {
+    Passed {
+        text: String,
+    },
+    Failed {
+        code: u8,
+        text: String,
+    },
+    /// The test didn't finish in the frames given
+    TimedOut,
+}
+
+impl BlarggResult {
+    pub fn passed(&self) -> bool {
+        matches!(self, Self::Passed { .. })
+    }
+}
+
+impl fmt::Display for BlarggResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed { text } => write!(f, "passed\n{}", text.trim_end()),
+            Self::Failed { code, text } => {
+                write!(f, "failed with code {code}\n{}", text.trim_end())
+            }
+            Self::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+pub(crate) enum BlarggStatus {
+    /// The signature isn't written yet, or the test is still running
+    Running,
+    /// The test asks to press the reset button
+    ResetRequested,
+    Done(BlarggResult),
+}
+
+pub(crate) fn read_status(nes: &NES) -> BlarggStatus {
+    let peek = |address| nes.peek_memory(address).unwrap_or(0);
+
+    if (0..3).any(|i| peek(0x6001 + i) != SIGNATURE[i as usize]) {
+        return BlarggStatus::Running;
+    }
+
+    match peek(0x6000) {
+        0x80 => BlarggStatus::Running,
+        0x81 => BlarggStatus::ResetRequested,
+        code => {
+            let text = (0..MAX_TEXT_LENGTH)
+                .map(|i| peek(0x6004 + i))
+                .take_while(|&c| c != 0)
+                .map(char::from)
+                .collect();
+
+            BlarggStatus::Done(if code == 0 {
+                BlarggResult::Passed { text }
+            } else {
+                BlarggResult::Failed { code, text }
+            })
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Headless runner of the plastic NES emulator, for running test ROMs in CI.
+//!
+//! The `plastic-test` binary is a thin wrapper over [`parse_args`] and [`run`],
+//! see [`USAGE`] for the options:
+//!
+//! ```text
+//! plastic-test rom.nes --frames 600 --expect-hash 9a3c0e1f25d6b870 --dump-screenshot out.png
+//! plastic-test test.nes --frames 3000 --result-protocol blargg
+//! ```
+
+mod args;
+mod blargg;
+mod output;
+
+pub use args::{parse_args, Options, ResultProtocol, USAGE};
+pub use blargg::BlarggResult;
+
+use blargg::BlarggStatus;
+use plastic_core::movie::Movie;
+use plastic_core::nes_display::frame_hash;
+use plastic_core::{CartridgeError, NESKey, SaveError, NES};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+
+/// The frames to wait before pressing reset when a blargg test asks for it,
+/// the tests need at least 100 milliseconds
+const BLARGG_RESET_DELAY_FRAMES: u64 = 6;
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Rom(CartridgeError),
+    /// Failed to load the movie of `--input`
+    Movie(SaveError),
+    Io(std::io::Error),
+    Png(png::EncodingError),
+}
+
+impl Error for CliError {}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}"),
+            Self::Rom(err) => write!(f, "failed to load the ROM: {err}"),
+            Self::Movie(err) => write!(f, "failed to load the input movie: {err}"),
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Png(err) => write!(f, "failed to write the screenshot: {err}"),
+        }
+    }
+}
+
+impl From<CartridgeError> for CliError {
+    fn from(err: CartridgeError) -> Self {
+        Self::Rom(err)
+    }
+}
+
+impl From<SaveError> for CliError {
+    fn from(err: SaveError) -> Self {
+        Self::Movie(err)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for CliError {
+    fn from(err: png::EncodingError) -> Self {
+        Self::Png(err)
+    }
+}
+
+/// The results of [`run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The frames run, less than [`Options::frames`] if the ROM reported
+    /// its result early
+    pub frames: u64,
+    /// The [`frame_hash`] of the last frame
+    pub frame_hash: u64,
+    /// `None` if [`Options::expect_hash`] is not set
+    pub hash_matches: Option<bool>,
+    /// `None` if [`Options::result_protocol`] is not [`ResultProtocol::Blargg`]
+    pub blargg: Option<BlarggResult>,
+}
+
+impl Report {
+    /// All the requested checks passed, `true` if there are no checks
+    pub fn passed(&self) -> bool {
+        self.hash_matches != Some(false) && self.blargg.as_ref().is_none_or(|r| r.passed())
+    }
+
+    /// The exit code of `plastic-test`, `0` if [`Report::passed`] and `1` otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.passed() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "frames: {}", self.frames)?;
+        write!(f, "frame hash: {:016x}", self.frame_hash)?;
+        match self.hash_matches {
+            Some(true) => write!(f, " (matches)")?,
+            Some(false) => write!(f, " (expected a different hash)")?,
+            None => {}
+        }
+        if let Some(result) = &self.blargg {
+            write!(f, "\nblargg: {result}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the ROM of `options` headless, write the requested dumps and evaluate the checks.
+///
+/// With an `--input` movie, the emulator starts from the first snapshot of the movie,
+/// and no buttons are pressed after the movie ends.
+pub fn run(options: &Options) -> Result<Report, CliError> {
+    let mut nes = NES::new(&options.rom)?;
+
+    let movie = match &options.input {
+        Some(path) => {
+            let movie = Movie::load_from(BufReader::new(File::open(path)?))?;
+            nes.play_movie_from_frame(&movie, 0)?;
+            Some(movie)
+        }
+        None => None,
+    };
+
+    let mut audio = Vec::new();
+    let mut blargg = options.result_protocol.map(|_| BlarggResult::TimedOut);
+    let mut reset_frame = None;
+    let mut frames = 0;
+
+    while frames < options.frames {
+        if let Some(movie) = &movie {
+            let input = movie.inputs().get(frames as usize).copied().unwrap_or(0);
+            for key in NESKey::ALL {
+                nes.set_controller_state(key, input & key as u8 != 0);
+            }
+        }
+
+        nes.clock_for_frame();
+        frames += 1;
+
+        // take the audio even if it is not dumped, otherwise the buffer fills up
+        let samples = nes.audio_buffer();
+        if options.audio.is_some() {
+            audio.extend_from_slice(&samples);
+        }
+
+        if blargg.is_some() {
+            match blargg::read_status(&nes) {
+                BlarggStatus::Running => {}
+                BlarggStatus::ResetRequested => {
+                    let frame = *reset_frame.get_or_insert(frames + BLARGG_RESET_DELAY_FRAMES);
+                    if frames >= frame {
+                        nes.reset();
+                        reset_frame = None;
+                    }
+                }
+                BlarggStatus::Done(result) => {
+                    blargg = Some(result);
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &options.screenshot {
+        output::write_png(path, nes.pixel_buffer())?;
+    }
+    if let Some(path) = &options.audio {
+        output::write_wav(path, &audio)?;
+    }
+
+    let frame_hash = frame_hash(nes.pixel_buffer());
+
+    Ok(Report {
+        frames,
+        frame_hash,
+        hash_matches: options.expect_hash.map(|hash| hash == frame_hash),
+        blargg,
+    })
+}
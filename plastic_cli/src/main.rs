@@ -0,0 +1,27 @@
+use plastic_cli::{parse_args, run, USAGE};
+use std::process::exit;
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            exit(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(report) => {
+            println!("{report}");
+            exit(report.exit_code());
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::CliError;
+use plastic_core::nes_audio::SAMPLE_RATE;
+use plastic_core::nes_display::{TV_HEIGHT, TV_WIDTH};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Save the RGB `pixels` of [`NES::pixel_buffer`](plastic_core::NES::pixel_buffer) as a PNG image
+pub(crate) fn write_png(path: &Path, pixels: &[u8]) -> Result<(), CliError> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        TV_WIDTH as u32,
+        TV_HEIGHT as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+
+    Ok(())
+}
+
+/// Save the stereo `samples` of [`NES::audio_buffer`](plastic_core::NES::audio_buffer)
+/// as a 32-bit float WAV file
+pub(crate) fn write_wav(path: &Path, samples: &[f32]) -> Result<(), CliError> {
+    const CHANNELS: u16 = 2;
+    const SAMPLE_BYTES: u16 = 4;
+    // IEEE float
+    const FORMAT: u16 = 3;
+
+    let data_len = (samples.len() * SAMPLE_BYTES as usize) as u32;
+    let block_align = CHANNELS * SAMPLE_BYTES;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&FORMAT.to_le_bytes())?;
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_BYTES * 8).to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use plastic_cli::{parse_args, run, BlarggResult, CliError, Options, ResultProtocol};
+use plastic_core::movie::Movie;
+use plastic_core::nes_display::{TV_HEIGHT, TV_WIDTH};
+use plastic_core::NES;
+use std::path::PathBuf;
+
+const ROM: &str = "../test_roms/instr_test-v5/rom_singles/01-basics.nes";
+/// Uses MMC1 with PRG RAM, the single test ROMs are NROM without RAM at `$6000`
+const BLARGG_ROM: &str = "../test_roms/instr_test-v5/all_instrs.nes";
+
+fn temp_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// A mapper 10 ROM, which has PRG RAM at `$6000` from power-up, running `prg` from `$8000`
+///
+/// The mapper needs more than 2 PRG banks, so the same bank is repeated
+fn write_rom(name: &str, prg: &[u8]) -> PathBuf {
+    let mut rom = vec![
+        0x4E, 0x45, 0x53, 0x1A, 4, 1, 0xA0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let mut prg_data = vec![0; 0x4000];
+    prg_data[..prg.len()].copy_from_slice(prg);
+    // reset vector
+    prg_data[0x3FFC] = 0x00;
+    prg_data[0x3FFD] = 0x80;
+    for _ in 0..4 {
+        rom.extend_from_slice(&prg_data);
+    }
+    rom.extend_from_slice(&[0; 0x2000]);
+
+    let path = temp_path(name);
+    std::fs::write(&path, rom).unwrap();
+    path
+}
+
+/// Reports a blargg failure with `code` and the text "Bad"
+fn blargg_failing_rom() -> PathBuf {
+    let mut prg = Vec::new();
+    for (i, byte) in [0xDE, 0xB0, 0x61, b'B', b'a', b'd', 0]
+        .into_iter()
+        .enumerate()
+    {
+        // LDA #byte; STA $6001+i
+        prg.extend_from_slice(&[0xA9, byte, 0x8D, 0x01 + i as u8, 0x60]);
+    }
+    // LDA #$03; STA $6000; JMP self
+    let end = 0x8000 + prg.len() as u16 + 5;
+    prg.extend_from_slice(&[0xA9, 0x03, 0x8D, 0x00, 0x60]);
+    prg.extend_from_slice(&[0x4C, end as u8, (end >> 8) as u8]);
+
+    write_rom("blargg_failing.nes", &prg)
+}
+
+/// Polls the controller into `$10` in the NMI handler and uses it as the
+/// backdrop color of the empty background, so the frames depend on the inputs
+fn input_color_rom() -> PathBuf {
+    let mut prg = vec![
+        // LDA #$80; STA $2000; LDA #$0A; STA $2001; JMP self
+        0xA9, 0x80, 0x8D, 0x00, 0x20, 0xA9, 0x0A, 0x8D, 0x01, 0x20, 0x4C, 0x0A, 0x80,
+    ];
+    prg.resize(0x100, 0xEA);
+    prg.extend_from_slice(&[
+        // LDA #1; STA $4016; LDA #0; STA $4016
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+        // LDX #8; read: LDA $4016; LSR A; ROR $10; DEX; BNE read
+        0xA2, 0x08, 0xAD, 0x16, 0x40, 0x4A, 0x66, 0x10, 0xCA, 0xD0, 0xF7,
+        // LDA #$3F; STA $2006; LDA #0; STA $2006
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20,
+        // LDA $10; AND #$3F; STA $2007
+        0xA5, 0x10, 0x29, 0x3F, 0x8D, 0x07, 0x20,
+        // LDA #$3F; STA $2006; LDA #0; STA $2006
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20, // RTI
+        0x40,
+    ]);
+    prg.resize(0x3FFA, 0);
+    // NMI vector
+    prg.extend_from_slice(&[0x00, 0x81]);
+
+    write_rom("input_color.nes", &prg)
+}
+
+#[test]
+fn parse_all_options() {
+    let options = parse_args([
+        "rom.nes",
+        "--frames",
+        "120",
+        "--expect-hash",
+        "0xABC123",
+        "--result-protocol",
+        "blargg",
+        "--input",
+        "movie.bin",
+        "--dump-screenshot",
+        "out.png",
+        "--dump-audio",
+        "out.wav",
+    ])
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        options,
+        Options {
+            rom: "rom.nes".into(),
+            frames: 120,
+            expect_hash: Some(0xABC123),
+            result_protocol: Some(ResultProtocol::Blargg),
+            input: Some("movie.bin".into()),
+            screenshot: Some("out.png".into()),
+            audio: Some("out.wav".into()),
+        }
+    );
+    assert_eq!(parse_args(["--help"]).unwrap(), None);
+}
+
+#[test]
+fn parse_errors() {
+    for args in [
+        &[][..],
+        &["rom.nes", "--frames"],
+        &["rom.nes", "--frames", "many"],
+        &["rom.nes", "--expect-hash", "xyz"],
+        &["rom.nes", "--result-protocol", "other"],
+        &["rom.nes", "--unknown"],
+        &["rom.nes", "other.nes"],
+    ] {
+        assert!(
+            matches!(parse_args(args.iter().copied()), Err(CliError::Usage(_))),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn blargg_passing_rom() {
+    let report = run(&Options {
+        frames: 3000,
+        result_protocol: Some(ResultProtocol::Blargg),
+        ..Options::new(BLARGG_ROM)
+    })
+    .unwrap();
+
+    assert!(
+        matches!(report.blargg, Some(BlarggResult::Passed { .. })),
+        "{report}"
+    );
+    // stopped when the result was ready
+    assert!(report.frames < 3000);
+    assert_eq!(report.exit_code(), 0);
+}
+
+#[test]
+fn blargg_failing_rom_and_timeout() {
+    let options = Options {
+        result_protocol: Some(ResultProtocol::Blargg),
+        ..Options::new(blargg_failing_rom())
+    };
+    let report = run(&options).unwrap();
+    assert_eq!(
+        report.blargg,
+        Some(BlarggResult::Failed {
+            code: 3,
+            text: "Bad".to_string()
+        })
+    );
+    assert_eq!(report.frames, 1);
+    assert_eq!(report.exit_code(), 1);
+
+    // NROM without RAM at `$6000`, the signature is never written
+    let report = run(&Options {
+        frames: 10,
+        result_protocol: Some(ResultProtocol::Blargg),
+        ..Options::new(ROM)
+    })
+    .unwrap();
+    assert_eq!(report.blargg, Some(BlarggResult::TimedOut));
+    assert_eq!(report.frames, 10);
+    assert_eq!(report.exit_code(), 1);
+}
+
+#[test]
+fn expect_frame_hash() {
+    let options = Options {
+        frames: 30,
+        ..Options::new(ROM)
+    };
+    let report = run(&options).unwrap();
+    assert_eq!(report.hash_matches, None);
+    assert_eq!(report.exit_code(), 0);
+
+    let matching = run(&Options {
+        expect_hash: Some(report.frame_hash),
+        ..options.clone()
+    })
+    .unwrap();
+    assert_eq!(matching.hash_matches, Some(true));
+    assert_eq!(matching.exit_code(), 0);
+
+    let different = run(&Options {
+        expect_hash: Some(report.frame_hash ^ 1),
+        ..options
+    })
+    .unwrap();
+    assert_eq!(different.hash_matches, Some(false));
+    assert_eq!(different.exit_code(), 1);
+}
+
+#[test]
+fn dump_screenshot_and_audio() {
+    let screenshot = temp_path("screenshot.png");
+    let audio = temp_path("audio.wav");
+    let options = Options {
+        frames: 20,
+        screenshot: Some(screenshot.clone()),
+        audio: Some(audio.clone()),
+        ..Options::new(ROM)
+    };
+    run(&options).unwrap();
+
+    let mut expected = NES::new(ROM).unwrap();
+    let mut samples = Vec::new();
+    for _ in 0..20 {
+        expected.clock_for_frame();
+        samples.extend(expected.audio_buffer());
+    }
+
+    let decoder = png::Decoder::new(std::fs::File::open(&screenshot).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(
+        (info.width, info.height),
+        (TV_WIDTH as u32, TV_HEIGHT as u32)
+    );
+    assert_eq!(&pixels[..info.buffer_size()], expected.pixel_buffer());
+
+    let wav = std::fs::read(&audio).unwrap();
+    assert_eq!(&wav[..4], b"RIFF");
+    let data = wav[44..]
+        .chunks_exact(4)
+        .map(|sample| f32::from_le_bytes(sample.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(data, samples);
+}
+
+#[test]
+fn input_movie() {
+    let rom = input_color_rom();
+    let mut nes = NES::new(&rom).unwrap();
+    let mut movie = Movie::new(0);
+    for frame in 0..30u8 {
+        nes.record_movie_frame(&mut movie, frame.wrapping_mul(7))
+            .unwrap();
+    }
+    let path = temp_path("input.movie");
+    movie
+        .save_to(std::fs::File::create(&path).unwrap())
+        .unwrap();
+
+    // the movie ends before the run, the last frames are without input
+    for _ in 0..5 {
+        nes.record_movie_frame(&mut Movie::new(0), 0).unwrap();
+    }
+
+    let report = run(&Options {
+        frames: 35,
+        input: Some(path),
+        ..Options::new(&rom)
+    })
+    .unwrap();
+    assert_eq!(
+        report.frame_hash,
+        plastic_core::nes_display::frame_hash(nes.pixel_buffer())
+    );
+
+    // the last frames without input reset the backdrop, so compare before them
+    let with_input = run(&Options {
+        frames: 30,
+        input: Some(temp_path("input.movie")),
+        ..Options::new(&rom)
+    })
+    .unwrap();
+    let without_input = run(&Options {
+        frames: 30,
+        ..Options::new(&rom)
+    })
+    .unwrap();
+    assert_ne!(without_input.frame_hash, with_input.frame_hash);
+}
+
+#[test]
+fn missing_files() {
+    assert!(matches!(
+        run(&Options::new(temp_path("missing.nes"))),
+        Err(CliError::Rom(_))
+    ));
+    assert!(matches!(
+        run(&Options {
+            input: Some(temp_path("missing.movie")),
+            ..Options::new(ROM)
+        }),
+        Err(CliError::Io(_))
+    ));
+}
@@ -0,0 +1,68 @@
+//! Compares the speed of the PPU backends, run with:
+//!
+//! ```sh
+//! cargo run -p plastic_core --release --example ppu_backend_bench -- [rom.nes] [frames]
+//! ```
+//!
+//! Only the PPU work is reduced by the scanline backend, the CPU and APU take the same
+//! time with both, so the speedup is lower for ROMs that keep the rendering disabled.
+use std::time::{Duration, Instant};
+
+use plastic_core::{PpuBackend, NES};
+
+const DEFAULT_ROM: &str = "test_roms/sprite_hit_tests/01.basics.nes";
+/// the backends run in turns of this many frames, so that changes in the machine
+/// load affect both of them
+const FRAMES_PER_TURN: u32 = 10;
+
+fn new_nes(rom: &str, backend: PpuBackend) -> NES {
+    let mut nes = NES::new(rom).expect("failed to load the ROM");
+    nes.set_ppu_backend(backend);
+    nes.reset();
+    nes
+}
+
+fn run(nes: &mut NES, frames: u32) -> Duration {
+    let start = Instant::now();
+    for _ in 0..frames {
+        nes.clock_for_frame();
+        // the audio buffer grows if not taken
+        let _ = nes.audio_buffer();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let rom = args.next().unwrap_or_else(|| DEFAULT_ROM.to_owned());
+    let frames = args
+        .next()
+        .map(|frames| frames.parse().expect("frames must be a number"))
+        .unwrap_or(3000);
+
+    let mut dot_nes = new_nes(&rom, PpuBackend::DotAccurate);
+    let mut scanline_nes = new_nes(&rom, PpuBackend::Scanline);
+    let mut dot = Duration::ZERO;
+    let mut scanline = Duration::ZERO;
+
+    let mut remaining = frames;
+    while remaining > 0 {
+        let turn = remaining.min(FRAMES_PER_TURN);
+        dot += run(&mut dot_nes, turn);
+        scanline += run(&mut scanline_nes, turn);
+        remaining -= turn;
+    }
+
+    for (name, time) in [("dot accurate", dot), ("scanline", scanline)] {
+        println!(
+            "{:<14} {:>8.2?} ({:.0} fps)",
+            name,
+            time,
+            frames as f64 / time.as_secs_f64()
+        );
+    }
+    println!(
+        "speedup: {:.2}x",
+        dot.as_secs_f64() / scanline.as_secs_f64()
+    );
+}
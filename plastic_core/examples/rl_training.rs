@@ -0,0 +1,59 @@
+//! A stub of a training loop using the `rl` helpers, run with:
+//!
+//! ```sh
+//! cargo run -p plastic_core --features rl --example rl_training -- <rom.nes>
+//! ```
+use plastic_core::{rl::RlConfig, NES};
+
+fn main() {
+    let rom = std::env::args()
+        .nth(1)
+        .expect("usage: rl_training <rom.nes>");
+    let mut nes = NES::new(rom).expect("failed to load the ROM");
+
+    nes.set_rl_config(RlConfig {
+        // replace with the addresses of the score of the game
+        ram_addresses: vec![0x07DD, 0x07DE],
+        // replace with the game over condition
+        done_predicate: Some(Box::new(|ram| ram[0x075A] == 0xFF)),
+        skip_intermediate_frames: true,
+        // set to get palette indices instead of RGB, for smaller observations
+        indexed_pixels: false,
+    });
+
+    // skip the boot screens
+    for _ in 0..120 {
+        nes.clock_for_frame();
+    }
+    let start = nes.snapshot().expect("failed to create snapshot");
+
+    // a very simple pseudo random agent
+    let mut seed = 0x1234_5678u32;
+    let mut next_action = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+
+    for episode in 0..3 {
+        nes.rl_reset_to(&start).expect("failed to reset");
+
+        let mut steps = 0;
+        let mut last_ram = Vec::new();
+        while steps < 1000 {
+            let observation = nes.rl_step(next_action(), 4);
+            steps += 1;
+            last_ram = observation.ram;
+
+            // the agent would compute the reward and learn from
+            // `observation.pixels` here
+
+            if observation.done {
+                break;
+            }
+        }
+
+        println!("episode {episode}: {steps} steps, final ram values {last_ram:02X?}");
+    }
+}
@@ -53,6 +53,9 @@ impl Bus for APU2A03 {
         // only the CPU is allowed to write to PPU registers
         if device == Device::Cpu {
             if let Ok(register) = address.try_into() {
+                if let Some(log) = self.register_log.as_mut() {
+                    log.push(address, data);
+                }
                 self.write_register(register, data);
             } else {
                 unreachable!("Bus address mapping should be handled correctly (APU Memory I/O)");
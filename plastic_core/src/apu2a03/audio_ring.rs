@@ -0,0 +1,120 @@
+use super::SAMPLE_RATE;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// The capacity of the ring created by the emulator, one second of stereo samples
+pub const DEFAULT_AUDIO_RING_CAPACITY: usize = SAMPLE_RATE as usize * 2;
+
+/// A fixed capacity ring buffer of stereo `f32` samples, the APU writes into it
+/// while the emulator is running and the audio output reads from it, without
+/// locks or allocations, so it can be used from real-time audio callbacks.
+///
+/// There must be only one producer and one consumer at a time:
+/// - The producer is the thread running the emulator, the samples are written
+///   by [`NES::clock_for_frame`](crate::NES::clock_for_frame) and [`NES::clock`](crate::NES::clock).
+/// - The consumer is the thread calling [`AudioRing::pop_slice`], usually the audio thread.
+///   [`NES::audio_buffer`](crate::NES::audio_buffer) reads from the ring as well,
+///   so it should not be used if another thread is reading.
+///
+/// When the ring is full the new samples are dropped and counted in
+/// [`AudioRing::overrun_samples`].
+pub struct AudioRing {
+    /// the bits of the samples, atomics are used so that reading and writing
+    /// different slots from two threads is safe
+    buffer: Box<[AtomicU32]>,
+    /// total number of samples written, only changed by the producer
+    write_count: AtomicUsize,
+    /// total number of samples read, only changed by the consumer
+    read_count: AtomicUsize,
+    overrun_samples: AtomicU64,
+}
+
+impl AudioRing {
+    /// Create a ring that can hold `capacity` samples, `capacity` is rounded
+    /// down to a multiple of 2 to hold complete stereo frames
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: (0..capacity & !1).map(|_| AtomicU32::new(0)).collect(),
+            write_count: AtomicUsize::new(0),
+            read_count: AtomicUsize::new(0),
+            overrun_samples: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of samples the ring can hold
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The number of samples that can be read now
+    pub fn read_available(&self) -> usize {
+        let read = self.read_count.load(Ordering::Acquire);
+        let write = self.write_count.load(Ordering::Acquire);
+
+        write.wrapping_sub(read)
+    }
+
+    /// Read samples into `out`, returns the number of samples read, which is less
+    /// than `out.len()` if there aren't enough samples.
+    ///
+    /// The samples are stereo (2 channels) interleaved, reading an odd number of
+    /// samples leaves the next read starting from the right channel.
+    pub fn pop_slice(&self, out: &mut [f32]) -> usize {
+        let read = self.read_count.load(Ordering::Relaxed);
+        let write = self.write_count.load(Ordering::Acquire);
+        let count = write.wrapping_sub(read).min(out.len());
+
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            let index = read.wrapping_add(i) % self.buffer.len();
+            *sample = f32::from_bits(self.buffer[index].load(Ordering::Relaxed));
+        }
+
+        // release the slots to the producer after reading them
+        self.read_count
+            .store(read.wrapping_add(count), Ordering::Release);
+
+        count
+    }
+
+    /// The total number of samples dropped because the ring was full
+    pub fn overrun_samples(&self) -> u64 {
+        self.overrun_samples.load(Ordering::Relaxed)
+    }
+
+    /// Write a mono sample to both channels, dropped if there is no space for both
+    pub(crate) fn push_stereo(&self, sample: f32) -> bool {
+        let write = self.write_count.load(Ordering::Relaxed);
+        let read = self.read_count.load(Ordering::Acquire);
+
+        if self.buffer.len() - write.wrapping_sub(read) < 2 {
+            self.overrun_samples.fetch_add(2, Ordering::Relaxed);
+            return false;
+        }
+
+        for i in 0..2 {
+            let index = write.wrapping_add(i) % self.buffer.len();
+            self.buffer[index].store(sample.to_bits(), Ordering::Relaxed);
+        }
+
+        // publish the samples to the consumer after writing them
+        self.write_count
+            .store(write.wrapping_add(2), Ordering::Release);
+
+        true
+    }
+
+    /// Read all the available samples into a new buffer
+    pub(crate) fn pop_all(&self) -> Vec<f32> {
+        let mut buffer = vec![0.; self.read_available()];
+        let count = self.pop_slice(&mut buffer);
+        buffer.truncate(count);
+
+        buffer
+    }
+}
+
+/// An empty ring with no capacity, which drops all samples
+impl Default for AudioRing {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -1,8 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::VecDeque,
-    ops::{Deref, DerefMut},
-};
+use std::ops::{Deref, DerefMut};
 
 pub trait APUChannel: Serialize + for<'de> Deserialize<'de> {
     fn get_output(&mut self) -> f32;
@@ -12,28 +9,6 @@ pub trait TimedAPUChannel: APUChannel {
     fn timer_clock(&mut self);
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct BufferedChannel {
-    buffer: VecDeque<f32>,
-}
-
-impl BufferedChannel {
-    pub fn new() -> Self {
-        Self {
-            buffer: VecDeque::new(),
-        }
-    }
-
-    pub fn recored_sample(&mut self, sample: f32) {
-        self.buffer.push_back(sample);
-        self.buffer.push_back(sample);
-    }
-
-    pub fn take_buffer(&mut self) -> Vec<f32> {
-        self.buffer.drain(..).collect()
-    }
-}
-
 #[derive(Serialize, Deserialize)]
 #[serde(bound = "C: APUChannel")]
 pub struct Dac<C: APUChannel> {
@@ -1,13 +1,33 @@
 use super::super::channel::{APUChannel, TimedAPUChannel};
+use crate::common::Region;
 use serde::{Deserialize, Serialize};
 
 const DMC_PERIOD_RATES_NTSC: [u16; 0x10] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
 ];
 
+const DMC_PERIOD_RATES_PAL: [u16; 0x10] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// A sample started by the game (a write to `$4015` while the DMC was idle),
+/// see [`NES::dmc_sample_log`](crate::NES::dmc_sample_log)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmcSampleEvent {
+    /// The CPU address of the first byte of the sample, `$C000-$FFC0`
+    pub address: u16,
+    /// The length of the sample in bytes
+    pub length: u16,
+    /// The rate index (`$4010` bits 0-3), the index in the period table of the region
+    pub rate_index: u8,
+    /// `true` if the sample restarts when it ends
+    pub looping: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Dmc {
     period: u16,
+    rate_index: u8,
     current_timer: u16,
 
     samples_address: u16,
@@ -28,14 +48,14 @@ pub struct Dmc {
     loop_flag: bool,
 
     irq_enabled_flag: bool,
-    irq_pin_state: bool,
-    is_irq_change_requested: bool,
+    interrupt_flag: bool,
 }
 
 impl Dmc {
     pub fn new() -> Self {
         Self {
             period: 0,
+            rate_index: 0,
             current_timer: 0,
 
             samples_address: 0,
@@ -56,8 +76,7 @@ impl Dmc {
             loop_flag: false,
 
             irq_enabled_flag: false,
-            irq_pin_state: false,
-            is_irq_change_requested: false,
+            interrupt_flag: false,
         }
     }
 
@@ -65,8 +84,7 @@ impl Dmc {
         self.irq_enabled_flag = flag;
 
         if !flag {
-            self.irq_pin_state = false;
-            self.is_irq_change_requested = true;
+            self.interrupt_flag = false;
         }
     }
 
@@ -74,9 +92,14 @@ impl Dmc {
         self.loop_flag = flag;
     }
 
-    pub(crate) fn set_rate_index(&mut self, rate_index: u8) {
+    pub(crate) fn set_rate_index(&mut self, rate_index: u8, region: Region) {
+        let table = match region {
+            Region::Ntsc => &DMC_PERIOD_RATES_NTSC,
+            Region::Pal => &DMC_PERIOD_RATES_PAL,
+        };
+        self.rate_index = rate_index & 0xF;
         // since the table is in CPU clocks, /2 to make it in APU clocks periods
-        self.period = DMC_PERIOD_RATES_NTSC[rate_index as usize & 0xF] / 2;
+        self.period = table[self.rate_index as usize] / 2;
     }
 
     pub(crate) fn set_direct_output_level_load(&mut self, output_level: u8) {
@@ -120,31 +143,38 @@ impl Dmc {
             if self.loop_flag {
                 self.restart_sample();
             } else if self.irq_enabled_flag {
-                self.irq_pin_state = true;
-                self.is_irq_change_requested = true;
+                self.interrupt_flag = true;
             }
         }
     }
 
-    pub(crate) fn sample_remaining_bytes_more_than_0(&self) -> bool {
-        self.samples_remaining_bytes_counter > 0
+    pub(crate) fn get_period(&self) -> u16 {
+        self.period
+    }
+
+    pub(crate) fn output_level(&self) -> u8 {
+        self.output_level
+    }
+
+    pub(crate) fn samples_address_counter(&self) -> u16 {
+        self.samples_address_counter
     }
 
-    pub(crate) fn get_irq_pin_state(&self) -> bool {
-        self.irq_pin_state
+    pub(crate) fn samples_remaining_bytes(&self) -> u16 {
+        self.samples_remaining_bytes_counter
     }
 
-    pub(crate) fn is_irq_change_requested(&self) -> bool {
-        self.is_irq_change_requested
+    pub(crate) fn sample_remaining_bytes_more_than_0(&self) -> bool {
+        self.samples_remaining_bytes_counter > 0
     }
 
-    pub(crate) fn clear_irq_request_pin(&mut self) {
-        self.is_irq_change_requested = false;
+    /// The DMC interrupt flag, the IRQ is asserted as long as it is set
+    pub(crate) fn interrupt_flag(&self) -> bool {
+        self.interrupt_flag
     }
 
     pub(crate) fn clear_interrupt_flag(&mut self) {
-        self.irq_pin_state = false;
-        self.is_irq_change_requested = true;
+        self.interrupt_flag = false;
     }
 
     pub(crate) fn clear_sample_remaining_bytes_and_silence(&mut self) {
@@ -152,6 +182,16 @@ impl Dmc {
         self.silence_on_next_empty = true;
     }
 
+    /// The settings of the sample that plays on the next restart
+    pub(crate) fn sample_event(&self) -> DmcSampleEvent {
+        DmcSampleEvent {
+            address: self.samples_address,
+            length: self.samples_length,
+            rate_index: self.rate_index,
+            looping: self.loop_flag,
+        }
+    }
+
     pub(crate) fn restart_sample(&mut self) {
         self.samples_remaining_bytes_counter = self.samples_length;
         self.samples_address_counter = self.samples_address;
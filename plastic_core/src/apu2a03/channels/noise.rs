@@ -1,12 +1,16 @@
 use super::super::channel::{APUChannel, TimedAPUChannel};
 use super::super::envelope::{EnvelopeGenerator, EnvelopedChannel};
+use crate::common::Region;
 use serde::{Deserialize, Serialize};
 
-/// Table for NTSC only
-const NOISE_PERIODS_TABLE: [u16; 0x10] = [
+const NOISE_PERIODS_TABLE_NTSC: [u16; 0x10] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
 ];
 
+const NOISE_PERIODS_TABLE_PAL: [u16; 0x10] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
 #[derive(Serialize, Deserialize)]
 pub struct NoiseWave {
     period: u16,
@@ -33,8 +37,24 @@ impl NoiseWave {
         }
     }
 
-    pub(crate) fn set_period(&mut self, period_index_index: u8) {
-        self.period = NOISE_PERIODS_TABLE[period_index_index as usize & 0xF];
+    pub(crate) fn set_period(&mut self, period_index_index: u8, region: Region) {
+        let table = match region {
+            Region::Ntsc => &NOISE_PERIODS_TABLE_NTSC,
+            Region::Pal => &NOISE_PERIODS_TABLE_PAL,
+        };
+        self.period = table[period_index_index as usize & 0xF];
+    }
+
+    pub(crate) fn get_period(&self) -> u16 {
+        self.period
+    }
+
+    pub(crate) fn mode_flag(&self) -> bool {
+        self.mode_flag
+    }
+
+    pub(crate) fn volume(&self) -> u8 {
+        self.envelope_generator.volume()
     }
 
     pub(crate) fn set_mode_flag(&mut self, flag: bool) {
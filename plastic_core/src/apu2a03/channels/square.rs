@@ -13,8 +13,12 @@ struct Sweeper {
 
     reload_flag: bool,
 
+    /// The period the sweep moves to on the next update, always computed, even when
+    /// the sweep is disabled, as it also decides if the channel is muted
     target_period: u16,
 
+    /// Pulse 1 negates the change amount with ones' complement (`-c - 1`),
+    /// and pulse 2 with two's complement (`-c`)
     is_square_1: bool,
 }
 
@@ -82,6 +86,18 @@ impl SquarePulse {
             .set_sequence(&DUTY_CYCLE_SEQUENCES[duty_cycle_index as usize & 0x3]);
     }
 
+    /// The index of the current duty cycle sequence
+    pub(crate) fn duty_cycle_index(&self) -> u8 {
+        DUTY_CYCLE_SEQUENCES
+            .iter()
+            .position(|sequence| sequence == self.sequencer.sequence())
+            .unwrap_or(0) as u8
+    }
+
+    pub(crate) fn volume(&self) -> u8 {
+        self.envelope_generator.volume()
+    }
+
     pub(crate) fn get_period(&self) -> u16 {
         self.period
     }
@@ -103,8 +119,18 @@ impl SquarePulse {
         self.sweeper.update_target_period(self.period);
     }
 
+    /// The period the sweep unit will set on its next update
+    pub(crate) fn sweep_target_period(&self) -> u16 {
+        self.sweeper.target_period
+    }
+
     pub(crate) fn clock_sweeper(&mut self) {
-        if self.sweeper.divider_period_counter == 0 && self.sweeper.enabled && !self.muted() {
+        // with a shift count of `0` the period is never updated, but the muting still applies
+        if self.sweeper.divider_period_counter == 0
+            && self.sweeper.enabled
+            && self.sweeper.shift_count != 0
+            && !self.muted()
+        {
             self.set_period(self.sweeper.target_period);
         }
 
@@ -116,6 +142,8 @@ impl SquarePulse {
         }
     }
 
+    /// The channel is silenced when the period is too small, or when the sweep target
+    /// overflows the 11 bits period, regardless of the sweep being enabled
     pub(crate) fn muted(&self) -> bool {
         self.period < 8 || (!self.sweeper.negative && self.sweeper.target_period > 0x7FF)
     }
@@ -44,6 +44,14 @@ impl TriangleWave {
         self.period
     }
 
+    pub(crate) fn linear_counter(&self) -> u8 {
+        self.linear_counter
+    }
+
+    pub(crate) fn muted(&self) -> bool {
+        self.muted
+    }
+
     pub(crate) fn set_period(&mut self, period: u16) {
         self.period = period;
 
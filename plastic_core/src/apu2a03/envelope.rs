@@ -74,10 +74,14 @@ impl EnvelopeGenerator {
 
     /// return the volume 0 - 1
     pub(crate) fn get_current_volume(&mut self) -> f32 {
+        self.volume() as f32
+    }
+
+    pub(crate) fn volume(&self) -> u8 {
         if self.use_constant_volume {
-            self.divider_reload_value as f32
+            self.divider_reload_value
         } else {
-            self.decay_level as f32
+            self.decay_level
         }
     }
 }
@@ -1,41 +1,155 @@
 mod apu2a03_registers;
+mod audio_ring;
 mod channel;
 mod channels;
 mod envelope;
 mod length_counter;
+mod register_log;
 mod sequencer;
+mod snapshot;
+mod tests;
 
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, to_json_value, JsonSavable};
 use crate::common::{
-    interconnection::{APUCPUConnection, CPUIrqProvider},
+    interconnection::APUCPUConnection,
     save_state::{Savable, SaveError},
-    CPU_FREQ,
+    Region,
 };
 use apu2a03_registers::Register;
-use channel::{BufferedChannel, Dac, TimedAPUChannel};
+use channel::{Dac, TimedAPUChannel};
+pub use channels::DmcSampleEvent;
 use channels::{Dmc, NoiseWave, SquarePulse, TriangleWave};
 use envelope::EnvelopedChannel;
 use length_counter::LengthCountedChannel;
+use register_log::RegisterLog;
 use serde::{Deserialize, Serialize};
-use std::cell::Cell;
+use std::{cell::Cell, sync::Arc};
 
-// for performance
-/// The sample rate expected to get from [`NES::audio_buffer`](crate::NES::audio_buffer)
-/// Do note that the audio is mono, i.e. 1 channel
+pub use audio_ring::{AudioRing, DEFAULT_AUDIO_RING_CAPACITY};
+pub use register_log::ApuWrite;
+pub use snapshot::{ApuSnapshot, DmcState, NoiseState, PulseState, TriangleState};
+
+/// The default sample rate of [`NES::audio_buffer`](crate::NES::audio_buffer), it can be
+/// changed with [`NES::set_audio_sample_rate`](crate::NES::set_audio_sample_rate)
 pub const SAMPLE_RATE: u32 = 44100;
 
-// after how many apu clocks a sample should be recorded
-// APU, is clocked on every CPU clock
-const SAMPLES_EVERY_N_APU_CLOCK: f64 = CPU_FREQ / (SAMPLE_RATE as f64);
+/// The CPU cycles at which the frame sequencer steps happen, the last step
+/// is the 4th step in 4-step mode, and the 5th step in 5-step mode
+const FRAME_SEQUENCER_STEPS_NTSC: [u16; 5] = [7457, 14913, 22371, 29829, 37281];
+const FRAME_SEQUENCER_STEPS_PAL: [u16; 5] = [8313, 16627, 24939, 33253, 41565];
+
+/// The maximum number of events kept in the DMC sample log, the games start
+/// samples often, so the log should be cleared regularly
+const MAX_DMC_SAMPLE_EVENTS: usize = 4096;
+
+/// The length of the fade out when pausing the audio and the fade in when
+/// resuming it is `1 / AUDIO_FADES_PER_SECOND` seconds, 5ms
+const AUDIO_FADES_PER_SECOND: u32 = 200;
+
+/// How the APU decides which CPU cycles produce an audio sample
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AudioSampling {
+    /// Sample every `cpu_freq / sample_rate` cycles using a floating point counter,
+    /// the default for interactive use.
+    #[default]
+    Float,
+    /// Sample using an integer counter of the exact ratio between the sample rate and
+    /// the CPU clock, so the number of samples in every frame follows a fixed pattern
+    /// and the total after `n` seconds of emulated time is exactly `sample_rate * n`.
+    /// Used for movies and anything that needs to be reproducible sample for sample.
+    Exact,
+}
+
+/// The outputs of the 5 channels before mixing, the channel levels (`0..=15`, and `0..=127`
+/// for the DMC) scaled down and with the DC offset removed, centered around `0`.
+///
+/// Passed to the tap set with [`NES::set_channel_tap`](crate::NES::set_channel_tap).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChannelSamples {
+    pub pulse1: f32,
+    pub pulse2: f32,
+    pub triangle: f32,
+    pub noise: f32,
+    pub dmc: f32,
+}
+
+impl ChannelSamples {
+    /// Mix the channels into one sample, the same as the audio output
+    pub fn mix(&self) -> f32 {
+        let pulse_out = if self.pulse1 == 0. && self.pulse2 == 0. {
+            0.
+        } else {
+            95.88 / ((8128. / (self.pulse1 + self.pulse2)) + 100.)
+        };
+
+        let tnd_out = if self.triangle == 0. && self.noise == 0. && self.dmc == 0. {
+            0.
+        } else {
+            159.79
+                / ((1. / ((self.triangle / 8227.) + (self.noise / 12241.) + (self.dmc / 22638.)))
+                    + 100.)
+        };
+
+        pulse_out + tnd_out
+    }
+}
+
+/// A function receiving the channel outputs of every audio sample
+pub type ChannelTap = Box<dyn FnMut(ChannelSamples)>;
+
+/// Where the samples go, this is not part of the emulation and is not saved
+struct AudioOutput {
+    /// shared with the NES, which keeps it when the APU is recreated
+    ring: Arc<AudioRing>,
+    paused: bool,
+    /// the last sample written, the start of the fade out
+    last_sample: f32,
+    /// number of samples left to fade in after resuming
+    fade_in_remaining: u32,
+    sampling: AudioSampling,
+    sample_rate: u32,
+    channel_tap: Option<ChannelTap>,
+}
+
+impl AudioOutput {
+    fn fade_samples(&self) -> u32 {
+        self.sample_rate / AUDIO_FADES_PER_SECOND
+    }
+}
+
+impl Default for AudioOutput {
+    fn default() -> Self {
+        Self {
+            ring: Arc::default(),
+            paused: false,
+            last_sample: 0.,
+            fade_in_remaining: 0,
+            sampling: AudioSampling::default(),
+            sample_rate: SAMPLE_RATE,
+            channel_tap: None,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct APU2A03 {
+    region: Region,
+
     square_pulse_1: Dac<LengthCountedChannel<SquarePulse>>,
     square_pulse_2: Dac<LengthCountedChannel<SquarePulse>>,
     triangle: Dac<LengthCountedChannel<TriangleWave>>,
     noise: Dac<LengthCountedChannel<NoiseWave>>,
     dmc: Dac<Dmc>,
 
-    buffered_channel: BufferedChannel,
+    #[serde(skip)]
+    audio_output: AudioOutput,
+    /// not part of the emulation, see [`APU2A03::start_register_log`]
+    #[serde(skip)]
+    register_log: Option<RegisterLog>,
+    /// the samples started since the log was cleared, not part of the emulation
+    #[serde(skip)]
+    dmc_sample_log: Vec<DmcSampleEvent>,
 
     is_4_step_squence_mode_hold_value: bool,
     is_4_step_squence_mode: bool,
@@ -47,17 +161,21 @@ pub struct APU2A03 {
 
     sample_counter: f64,
 
-    offset: f64,
+    /// the counter of [`AudioSampling::Exact`], in units of `1 / cpu_freq_denominator`
+    exact_sample_counter: u64,
 
+    /// the frame interrupt flag, the IRQ output is asserted as long as it is set
     interrupt_flag: Cell<bool>,
-    request_interrupt_flag_change: Cell<bool>,
+    /// the frame counter set `interrupt_flag` in this cycle, the APU is clocked
+    /// before the CPU accesses of the cycle
+    interrupt_flag_set_this_cycle: bool,
 }
 
 impl APU2A03 {
-    pub fn new() -> Self {
-        let buffered_channel = BufferedChannel::new();
-
+    pub fn new(region: Region, audio_ring: Arc<AudioRing>) -> Self {
         Self {
+            region,
+
             square_pulse_1: Dac::new(LengthCountedChannel::new(SquarePulse::new(true))),
             square_pulse_2: Dac::new(LengthCountedChannel::new(SquarePulse::new(false))),
 
@@ -67,7 +185,12 @@ impl APU2A03 {
 
             dmc: Dac::new(Dmc::new()),
 
-            buffered_channel,
+            audio_output: AudioOutput {
+                ring: audio_ring,
+                ..AudioOutput::default()
+            },
+            register_log: None,
+            dmc_sample_log: Vec::new(),
 
             is_4_step_squence_mode_hold_value: false,
             is_4_step_squence_mode: false,
@@ -77,12 +200,12 @@ impl APU2A03 {
 
             sample_counter: 0.,
 
-            offset: 0.,
+            exact_sample_counter: 0,
 
             wait_reset: 0,
 
             interrupt_flag: Cell::new(false),
-            request_interrupt_flag_change: Cell::new(false),
+            interrupt_flag_set_this_cycle: false,
         }
     }
 
@@ -100,11 +223,14 @@ impl APU2A03 {
                 let noise_length_counter = (self.noise.length_counter().counter() != 0) as u8;
 
                 let dmc_active = self.dmc.sample_remaining_bytes_more_than_0() as u8;
-                let dmc_interrupt = self.dmc.get_irq_pin_state() as u8;
+                let dmc_interrupt = self.dmc.interrupt_flag() as u8;
 
                 let frame_interrupt = self.interrupt_flag.get() as u8;
-                self.interrupt_flag.set(false);
-                self.request_interrupt_flag_change.set(true);
+                // if the flag is being set on the same cycle, it reads as set
+                // and is not cleared
+                if !self.interrupt_flag_set_this_cycle {
+                    self.interrupt_flag.set(false);
+                }
 
                 dmc_interrupt << 7
                     | frame_interrupt << 6
@@ -298,7 +424,7 @@ impl APU2A03 {
             }
             Register::Noise3 => {
                 self.noise.channel_mut().set_mode_flag(data & 0x80 != 0);
-                self.noise.channel_mut().set_period(data & 0xF);
+                self.noise.channel_mut().set_period(data & 0xF, self.region);
             }
             Register::Noise4 => {
                 self.noise.length_counter_mut().reload_counter(data >> 3);
@@ -308,7 +434,7 @@ impl APU2A03 {
                 let loop_flag = data & 0x40 != 0;
                 let irq_enabled = data & 0x80 != 0;
 
-                self.dmc.set_rate_index(rate_index);
+                self.dmc.set_rate_index(rate_index, self.region);
                 self.dmc.set_loop_flag(loop_flag);
                 self.dmc.set_irq_enabled_flag(irq_enabled);
             }
@@ -343,6 +469,10 @@ impl APU2A03 {
                     self.dmc.clear_sample_remaining_bytes_and_silence();
                 } else if !self.dmc.sample_remaining_bytes_more_than_0() {
                     self.dmc.restart_sample();
+
+                    if self.dmc_sample_log.len() < MAX_DMC_SAMPLE_EVENTS {
+                        self.dmc_sample_log.push(self.dmc.sample_event());
+                    }
                 }
 
                 self.dmc.clear_interrupt_flag();
@@ -353,7 +483,6 @@ impl APU2A03 {
 
                 if self.interrupt_inhibit_flag {
                     self.interrupt_flag.set(false);
-                    self.request_interrupt_flag_change.set(true);
                 }
 
                 self.wait_reset = if self.cycle % 2 == 0 { 4 } else { 3 };
@@ -377,38 +506,38 @@ impl APU2A03 {
         self.noise.length_counter_mut().decrement();
     }
 
-    fn update_irq_pin(&mut self) {
+    fn set_interrupt_flag(&mut self) {
         if !self.interrupt_inhibit_flag {
             self.interrupt_flag.set(true);
-            self.request_interrupt_flag_change.set(true);
+            self.interrupt_flag_set_this_cycle = true;
         }
     }
 
-    fn get_mixer_output(&mut self) -> f32 {
-        let square_pulse_1 = self.square_pulse_1.dac_output();
-        let square_pulse_2 = self.square_pulse_2.dac_output();
-        let triangle = self.triangle.dac_output();
-        let noise = self.noise.dac_output();
-        let dmc = self.dmc.dac_output();
-
-        let pulse_out = if square_pulse_1 == 0. && square_pulse_2 == 0. {
-            0.
-        } else {
-            95.88 / ((8128. / (square_pulse_1 + square_pulse_2)) + 100.)
-        };
-
-        let tnd_out = if triangle == 0. && noise == 0. && dmc == 0. {
-            0.
-        } else {
-            159.79 / ((1. / ((triangle / 8227.) + (noise / 12241.) + (dmc / 22638.))) + 100.)
-        };
+    /// The IRQ output of the APU, a level that stays asserted while the frame
+    /// interrupt flag or the DMC interrupt flag is set
+    pub(crate) fn irq_level(&self) -> bool {
+        self.interrupt_flag.get() || self.dmc.interrupt_flag()
+    }
 
-        pulse_out + tnd_out
+    fn channel_samples(&mut self) -> ChannelSamples {
+        ChannelSamples {
+            pulse1: self.square_pulse_1.dac_output(),
+            pulse2: self.square_pulse_2.dac_output(),
+            triangle: self.triangle.dac_output(),
+            noise: self.noise.dac_output(),
+            dmc: self.dmc.dac_output(),
+        }
     }
 
     /// clock the APU **at** CPU clock rate, the clocks are handled correctly
     /// as it should be
     pub fn clock(&mut self) {
+        self.interrupt_flag_set_this_cycle = false;
+
+        if let Some(log) = self.register_log.as_mut() {
+            log.clock();
+        }
+
         match self.wait_reset.cmp(&0) {
             std::cmp::Ordering::Less => {}
             std::cmp::Ordering::Equal => {
@@ -425,13 +554,9 @@ impl APU2A03 {
             std::cmp::Ordering::Greater => self.wait_reset -= 1,
         }
 
-        self.sample_counter += 1.;
-        if self.sample_counter >= SAMPLES_EVERY_N_APU_CLOCK {
-            let output = self.get_mixer_output();
-
-            self.buffered_channel.recored_sample(output);
-
-            self.sample_counter -= SAMPLES_EVERY_N_APU_CLOCK;
+        if self.sample_due() {
+            let channels = self.channel_samples();
+            self.output_sample(channels);
         }
 
         // clocked on every CPU cycle
@@ -446,37 +571,42 @@ impl APU2A03 {
 
         self.cycle += 1;
 
+        let steps = match self.region {
+            Region::Ntsc => &FRAME_SEQUENCER_STEPS_NTSC,
+            Region::Pal => &FRAME_SEQUENCER_STEPS_PAL,
+        };
+
         // this is clocked in every CPU cycle, so the numbers are multiplied by 2
         match self.cycle {
-            7457 => {
+            c if c == steps[0] => {
                 self.generate_quarter_frame_clock();
             }
-            14913 => {
+            c if c == steps[1] => {
                 self.generate_quarter_frame_clock();
                 self.generate_half_frame_clock();
             }
-            22371 => {
+            c if c == steps[2] => {
                 self.generate_quarter_frame_clock();
             }
-            29828 if self.is_4_step_squence_mode => {
-                self.update_irq_pin();
+            // the flag is set on two cycles, and a `$4015` read on the second one can't
+            // clear it, so reads see it set on three cycles in a row
+            c if c == steps[3] - 1 && self.is_4_step_squence_mode => {
+                self.set_interrupt_flag();
             }
-            29829 if self.is_4_step_squence_mode => {
+            c if c == steps[3] && self.is_4_step_squence_mode => {
                 self.generate_quarter_frame_clock();
                 self.generate_half_frame_clock();
 
-                self.update_irq_pin();
+                self.set_interrupt_flag();
             }
-            29830 if self.is_4_step_squence_mode => {
-                self.update_irq_pin();
-
+            c if c == steps[3] + 1 && self.is_4_step_squence_mode => {
                 self.cycle = 0;
             }
-            37281 if !self.is_4_step_squence_mode => {
+            c if c == steps[4] && !self.is_4_step_squence_mode => {
                 self.generate_quarter_frame_clock();
                 self.generate_half_frame_clock();
             }
-            37282 if !self.is_4_step_squence_mode => {
+            c if c == steps[4] + 1 && !self.is_4_step_squence_mode => {
                 self.cycle = 0;
             }
             _ => {
@@ -485,35 +615,199 @@ impl APU2A03 {
         }
     }
 
-    /// Take and return the audio buffer as f32 format stereo (2 channels)
-    pub fn take_audio_buffer(&mut self) -> Vec<f32> {
-        self.buffered_channel.take_buffer()
+    pub fn region(&self) -> Region {
+        self.region
     }
-}
 
-impl CPUIrqProvider for APU2A03 {
-    fn is_irq_change_requested(&self) -> bool {
-        let dmc_irq_request = self.dmc.is_irq_change_requested();
+    /// Get the current state of all channels
+    pub fn channel_states(&self) -> ApuSnapshot {
+        let cpu_freq = self.region.cpu_freq();
 
-        self.request_interrupt_flag_change.get() || dmc_irq_request
+        let pulse_state = |pulse: &LengthCountedChannel<SquarePulse>| {
+            let length_counter = pulse.length_counter().counter();
+            let pulse = pulse.channel();
+
+            PulseState {
+                period: pulse.get_period(),
+                // the sequencer has 8 steps, and clocked every APU cycle
+                frequency: cpu_freq / (16. * (pulse.get_period() as f64 + 1.)),
+                duty: pulse.duty_cycle_index(),
+                volume: pulse.volume(),
+                length_counter,
+                sweep_target_period: pulse.sweep_target_period(),
+                audible: length_counter != 0 && pulse.volume() != 0 && !pulse.muted(),
+            }
+        };
+
+        let triangle = {
+            let length_counter = self.triangle.length_counter().counter();
+            let triangle = self.triangle.channel();
+
+            TriangleState {
+                period: triangle.get_period(),
+                // the sequencer has 32 steps, and clocked every CPU cycle
+                frequency: cpu_freq / (32. * (triangle.get_period() as f64 + 1.)),
+                linear_counter: triangle.linear_counter(),
+                length_counter,
+                audible: length_counter != 0 && triangle.linear_counter() != 0 && !triangle.muted(),
+            }
+        };
+
+        let noise = {
+            let length_counter = self.noise.length_counter().counter();
+            let noise = self.noise.channel();
+
+            NoiseState {
+                period: noise.get_period(),
+                frequency: cpu_freq / (2. * (noise.get_period() as f64 + 1.)),
+                short_mode: noise.mode_flag(),
+                volume: noise.volume(),
+                length_counter,
+                audible: length_counter != 0 && noise.volume() != 0,
+            }
+        };
+
+        let dmc = DmcState {
+            period: self.dmc.get_period(),
+            frequency: cpu_freq / (2. * (self.dmc.get_period() as f64 + 1.)),
+            output_level: self.dmc.output_level(),
+            sample_address: self.dmc.samples_address_counter(),
+            remaining_bytes: self.dmc.samples_remaining_bytes(),
+            audible: self.dmc.sample_remaining_bytes_more_than_0(),
+        };
+
+        ApuSnapshot {
+            pulse_1: pulse_state(&self.square_pulse_1),
+            pulse_2: pulse_state(&self.square_pulse_2),
+            triangle,
+            noise,
+            dmc,
+        }
+    }
+
+    /// Advance the sampling counter by one CPU cycle, returns `true` if a sample
+    /// should be produced in this cycle
+    fn sample_due(&mut self) -> bool {
+        match self.audio_output.sampling {
+            AudioSampling::Float => {
+                // after how many apu clocks a sample should be recorded
+                // APU, is clocked on every CPU clock
+                let samples_every_n_apu_clock =
+                    self.region.cpu_freq() / self.audio_output.sample_rate as f64;
+
+                self.sample_counter += 1.;
+                if self.sample_counter >= samples_every_n_apu_clock {
+                    self.sample_counter -= samples_every_n_apu_clock;
+                    true
+                } else {
+                    false
+                }
+            }
+            AudioSampling::Exact => {
+                // each cycle is `1 / cpu_freq` seconds, and a sample is due every
+                // `1 / sample_rate` seconds, scaled by `cpu_freq * denominator`
+                let (numerator, denominator) = self.region.cpu_freq_ratio();
+
+                self.exact_sample_counter += self.audio_output.sample_rate as u64 * denominator;
+                if self.exact_sample_counter >= numerator {
+                    self.exact_sample_counter -= numerator;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Change the way samples are timed, the sampling counters are kept so switching
+    /// back and forth is deterministic
+    pub fn set_audio_sampling(&mut self, sampling: AudioSampling) {
+        self.audio_output.sampling = sampling;
     }
 
-    fn irq_pin_state(&self) -> bool {
-        let dmc_irq = self.dmc.get_irq_pin_state();
+    /// Change the number of samples produced per second of emulated time, the sampling
+    /// counters restart so the first sample at the new rate is a full period away
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.audio_output.sample_rate = sample_rate;
+        self.sample_counter = 0.;
+        self.exact_sample_counter = 0;
+    }
+
+    /// The DMC samples started since the last [`APU2A03::clear_dmc_sample_log`]
+    pub fn dmc_sample_log(&self) -> &[DmcSampleEvent] {
+        &self.dmc_sample_log
+    }
 
-        self.interrupt_flag.get() || dmc_irq
+    pub fn clear_dmc_sample_log(&mut self) {
+        self.dmc_sample_log.clear();
     }
 
-    fn clear_irq_request_pin(&mut self) {
-        self.request_interrupt_flag_change.set(false);
+    /// Replace the function receiving the channel outputs, returning the old one
+    pub fn replace_channel_tap(&mut self, tap: Option<ChannelTap>) -> Option<ChannelTap> {
+        std::mem::replace(&mut self.audio_output.channel_tap, tap)
+    }
 
-        self.dmc.clear_irq_request_pin();
+    /// Replace the ring the samples are written to
+    pub fn set_audio_ring(&mut self, audio_ring: Arc<AudioRing>) {
+        self.audio_output.ring = audio_ring;
+    }
+
+    /// Stop writing samples to the ring, and write a short fade out from the
+    /// last sample to silence, the samples already in the ring are kept
+    pub fn pause_audio(&mut self) {
+        let output = &mut self.audio_output;
+        if output.paused {
+            return;
+        }
+        output.paused = true;
+        output.fade_in_remaining = 0;
+
+        let fade_samples = output.fade_samples();
+        for i in 1..=fade_samples {
+            let gain = 1. - i as f32 / fade_samples as f32;
+            output.ring.push_stereo(output.last_sample * gain);
+        }
+        output.last_sample = 0.;
+    }
+
+    /// Write samples to the ring again, starting with a short fade in
+    pub fn resume_audio(&mut self) {
+        let output = &mut self.audio_output;
+        if !output.paused {
+            return;
+        }
+        output.paused = false;
+        output.fade_in_remaining = output.fade_samples();
+    }
+
+    pub fn is_audio_paused(&self) -> bool {
+        self.audio_output.paused
+    }
+
+    fn output_sample(&mut self, channels: ChannelSamples) {
+        let output = &mut self.audio_output;
+        if output.paused {
+            return;
+        }
+
+        if let Some(tap) = output.channel_tap.as_mut() {
+            tap(channels);
+        }
+
+        let mut sample = channels.mix();
+        if output.fade_in_remaining > 0 {
+            sample *= 1. - output.fade_in_remaining as f32 / (output.fade_samples() + 1) as f32;
+            output.fade_in_remaining -= 1;
+        }
+
+        output.last_sample = sample;
+        output.ring.push_stereo(sample);
     }
 }
 
 impl Default for APU2A03 {
     fn default() -> Self {
-        Self::new()
+        Self::new(Region::default(), Arc::default())
     }
 }
 
@@ -523,12 +817,18 @@ impl APUCPUConnection for APU2A03 {
     }
 
     fn submit_dmc_buffer_byte(&mut self, byte: u8) {
+        if let (Some(log), Some(address)) = (
+            self.register_log.as_mut(),
+            self.dmc.request_dmc_reader_read(),
+        ) {
+            log.push(address, byte);
+        }
         self.dmc.submit_buffer_byte(byte);
     }
 }
 
 impl Savable for APU2A03 {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         bincode::serialize_into(writer, self).map_err(|err| match *err {
             bincode::ErrorKind::Io(err) => SaveError::IoError(err),
             _ => SaveError::SerializationError,
@@ -537,12 +837,42 @@ impl Savable for APU2A03 {
         Ok(())
     }
 
-    fn load<R: std::io::Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
         let state: APU2A03 = bincode::deserialize_from(reader).map_err(|err| match *err {
             bincode::ErrorKind::Io(err) => SaveError::IoError(err),
             _ => SaveError::SerializationError,
         })?;
 
+        self.load_serialized_state(state)
+    }
+}
+
+#[cfg(feature = "state-json")]
+impl JsonSavable for APU2A03 {
+    fn to_json(&self) -> serde_json::Value {
+        to_json_value(self)
+    }
+
+    fn load_json(&mut self, value: serde_json::Value) -> Result<(), SaveError> {
+        self.load_serialized_state(from_json_value(value)?)
+    }
+}
+
+impl APU2A03 {
+    fn load_serialized_state(&mut self, mut state: APU2A03) -> Result<(), SaveError> {
+        // the timing of the whole state depends on the region, so it can't be
+        // loaded into a console of a different region
+        if state.region != self.region {
+            return Err(SaveError::RegionMismatch {
+                found: state.region,
+                expected: self.region,
+            });
+        }
+
+        // the samples are not saved, so keep writing to the same ring
+        state.audio_output = std::mem::take(&mut self.audio_output);
+        state.register_log = self.register_log.take();
+        state.dmc_sample_log = std::mem::take(&mut self.dmc_sample_log);
         let _ = std::mem::replace(self, state);
 
         Ok(())
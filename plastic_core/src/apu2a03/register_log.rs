@@ -0,0 +1,117 @@
+use super::{AudioRing, AudioSampling, APU2A03};
+use crate::common::interconnection::APUCPUConnection;
+use crate::common::{Bus, Device, Region};
+use std::sync::Arc;
+
+/// The size of the ring used by [`APU2A03::render_register_log`], emptied every
+/// [`RENDER_DRAIN_CYCLES`] cycles
+const RENDER_RING_CAPACITY: usize = 4096;
+const RENDER_DRAIN_CYCLES: u64 = 10000;
+
+/// A write to an APU register, recorded by
+/// [`NES::start_apu_register_log`](crate::NES::start_apu_register_log)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApuWrite {
+    /// The number of CPU cycles since the log started
+    pub cycle: u64,
+    /// The register (`$4000-$4017`), or for the sample bytes read by the DMC,
+    /// the address of the byte (`$8000-$FFFF`)
+    pub address: u16,
+    pub data: u8,
+}
+
+impl ApuWrite {
+    /// `true` if this is a sample byte read by the DMC, and not a register write
+    pub fn is_dmc_sample_byte(&self) -> bool {
+        self.address >= 0x8000
+    }
+}
+
+/// The writes recorded since the log started, not part of the emulation
+#[derive(Default)]
+pub(super) struct RegisterLog {
+    cycle: u64,
+    writes: Vec<ApuWrite>,
+}
+
+impl RegisterLog {
+    pub fn clock(&mut self) {
+        self.cycle += 1;
+    }
+
+    pub fn push(&mut self, address: u16, data: u8) {
+        self.writes.push(ApuWrite {
+            cycle: self.cycle,
+            address,
+            data,
+        });
+    }
+}
+
+impl APU2A03 {
+    /// Start recording the register writes, replacing the current log
+    pub fn start_register_log(&mut self) {
+        self.register_log = Some(RegisterLog::default());
+    }
+
+    /// Stop recording the register writes and return them, empty if the log wasn't started
+    pub fn stop_register_log(&mut self) -> Vec<ApuWrite> {
+        self.register_log
+            .take()
+            .map(|log| log.writes)
+            .unwrap_or_default()
+    }
+
+    /// Play a log recorded with [`APU2A03::start_register_log`] through a new APU for
+    /// `cycles` CPU cycles, without a CPU, and return the audio (stereo, like the ring).
+    ///
+    /// The APU starts from the power-on state, so the result is the same as the live
+    /// audio if the log was started at power-on, otherwise the notes that were already
+    /// playing are missing until the game writes their registers again.
+    pub fn render_register_log(
+        region: Region,
+        sampling: AudioSampling,
+        sample_rate: u32,
+        log: &[ApuWrite],
+        cycles: u64,
+    ) -> Vec<f32> {
+        let ring = Arc::new(AudioRing::new(RENDER_RING_CAPACITY));
+        let mut apu = APU2A03::new(region, ring.clone());
+        apu.set_audio_sampling(sampling);
+        apu.set_audio_sample_rate(sample_rate);
+
+        let mut output = Vec::new();
+        let mut writes = log.iter().peekable();
+        for cycle in 0..cycles {
+            // the CPU runs after the APU in a cycle, so the writes logged at `cycle`
+            // happened after `cycle` clocks of the APU
+            while let Some(write) = writes.next_if(|write| write.cycle <= cycle) {
+                if write.is_dmc_sample_byte() {
+                    // the DMC requests the same bytes at the same cycles, as the APU
+                    // is running the same way
+                    if apu.request_dmc_reader_read() == Some(write.address) {
+                        apu.submit_dmc_buffer_byte(write.data);
+                    }
+                } else {
+                    apu.write(write.address, write.data, Device::Cpu);
+                }
+            }
+
+            apu.clock();
+
+            if cycle % RENDER_DRAIN_CYCLES == 0 {
+                drain_ring(&ring, &mut output);
+            }
+        }
+        drain_ring(&ring, &mut output);
+
+        output
+    }
+}
+
+fn drain_ring(ring: &AudioRing, output: &mut Vec<f32>) {
+    let start = output.len();
+    output.resize(start + ring.read_available(), 0.);
+    let read = ring.pop_slice(&mut output[start..]);
+    output.truncate(start + read);
+}
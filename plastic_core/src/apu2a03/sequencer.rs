@@ -24,6 +24,10 @@ impl Sequencer {
         *self.sequence.get(self.position).unwrap_or(&0)
     }
 
+    pub(crate) fn sequence(&self) -> &[u8] {
+        &self.sequence
+    }
+
     fn length(&self) -> usize {
         self.sequence.len()
     }
@@ -0,0 +1,79 @@
+/// State of one of the two pulse (square) channels
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PulseState {
+    /// The timer period, in APU cycles (2 CPU cycles)
+    pub period: u16,
+    /// The frequency of the output tone in Hz
+    pub frequency: f64,
+    /// The duty cycle index, `0` (12.5%), `1` (25%), `2` (50%) or `3` (25% negated)
+    pub duty: u8,
+    /// The current volume (constant or envelope), `0..=15`
+    pub volume: u8,
+    /// The current value of the length counter
+    pub length_counter: u8,
+    /// The period the sweep unit moves to on its next update, the channel is muted
+    /// if this is above `$7FF`, even if the sweep is disabled
+    pub sweep_target_period: u16,
+    /// `true` if the channel is producing sound
+    pub audible: bool,
+}
+
+/// State of the triangle channel
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TriangleState {
+    /// The timer period, in CPU cycles
+    pub period: u16,
+    /// The frequency of the output tone in Hz
+    pub frequency: f64,
+    /// The current value of the linear counter
+    pub linear_counter: u8,
+    /// The current value of the length counter
+    pub length_counter: u8,
+    /// `true` if the channel is producing sound
+    pub audible: bool,
+}
+
+/// State of the noise channel
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NoiseState {
+    /// The timer period, in APU cycles (2 CPU cycles)
+    pub period: u16,
+    /// The rate at which the random bits are generated in Hz
+    pub frequency: f64,
+    /// `true` if the short (93 steps) mode is used
+    pub short_mode: bool,
+    /// The current volume (constant or envelope), `0..=15`
+    pub volume: u8,
+    /// The current value of the length counter
+    pub length_counter: u8,
+    /// `true` if the channel is producing sound
+    pub audible: bool,
+}
+
+/// State of the delta modulation channel (DMC)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DmcState {
+    /// The timer period, in APU cycles (2 CPU cycles)
+    pub period: u16,
+    /// The rate at which the sample bits are played in Hz
+    pub frequency: f64,
+    /// The current output level, `0..=127`
+    pub output_level: u8,
+    /// The address of the next sample byte to be read
+    pub sample_address: u16,
+    /// The number of sample bytes remaining to be read
+    pub remaining_bytes: u16,
+    /// `true` if a sample is being played
+    pub audible: bool,
+}
+
+/// A snapshot of the state of all APU channels, returned from
+/// [`NES::apu_channel_states`](crate::NES::apu_channel_states)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ApuSnapshot {
+    pub pulse_1: PulseState,
+    pub pulse_2: PulseState,
+    pub triangle: TriangleState,
+    pub noise: NoiseState,
+    pub dmc: DmcState,
+}
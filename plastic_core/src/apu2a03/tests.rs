@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod apu_tests {
+    use super::super::{AudioRing, APU2A03};
+    use crate::common::{Bus, Device, Region};
+    use std::sync::Arc;
+
+    fn new_apu(region: Region) -> APU2A03 {
+        APU2A03::new(region, Arc::new(AudioRing::default()))
+    }
+
+    /// play a square wave and return the number of rising edges in one second of audio
+    fn square_wave_edges(region: Region) -> usize {
+        let audio_ring = Arc::new(AudioRing::new(crate::nes_audio::SAMPLE_RATE as usize * 4));
+        let mut apu = APU2A03::new(region, audio_ring.clone());
+
+        apu.write(0x4015, 0x01, Device::Cpu);
+        // 50% duty, halt length counter, constant volume 15
+        apu.write(0x4000, 0xBF, Device::Cpu);
+        apu.write(0x4001, 0x00, Device::Cpu);
+        // timer period 253
+        apu.write(0x4002, 0xFD, Device::Cpu);
+        apu.write(0x4003, 0x08, Device::Cpu);
+
+        for _ in 0..region.cpu_freq() as u32 {
+            apu.clock();
+        }
+
+        // the buffer is stereo, so take one channel
+        let buffer = audio_ring.pop_all();
+        let samples = buffer.iter().step_by(2).collect::<Vec<_>>();
+
+        let max = samples.iter().fold(f32::MIN, |a, b| a.max(**b));
+        let min = samples.iter().fold(f32::MAX, |a, b| a.min(**b));
+        let middle = (max + min) / 2.;
+
+        samples
+            .windows(2)
+            .filter(|w| *w[0] < middle && *w[1] >= middle)
+            .count()
+    }
+
+    /// returns the number of cycles from writing to the frame counter in
+    /// 4-step mode until the frame IRQ is asserted
+    fn frame_irq_cycles(region: Region) -> u32 {
+        frame_irq_cycles_from(&mut new_apu(region))
+    }
+
+    fn frame_irq_cycles_from(apu: &mut APU2A03) -> u32 {
+        apu.write(0x4017, 0x00, Device::Cpu);
+
+        let mut cycles = 0;
+        loop {
+            apu.clock();
+            cycles += 1;
+
+            if apu.irq_level() {
+                break cycles;
+            }
+        }
+    }
+
+    #[test]
+    fn pal_square_pitch() {
+        let ntsc = square_wave_edges(Region::Ntsc);
+        let pal = square_wave_edges(Region::Pal);
+
+        // f = CPU / (16 * (t + 1))
+        assert!((ntsc as i32 - 440).abs() <= 2, "ntsc: {}", ntsc);
+        assert!((pal as i32 - 409).abs() <= 2, "pal: {}", pal);
+
+        let ratio = pal as f64 / ntsc as f64;
+        let expected = Region::Pal.cpu_freq() / Region::Ntsc.cpu_freq();
+        assert!((ratio - expected).abs() < 0.01, "ratio: {}", ratio);
+    }
+
+    #[test]
+    fn pal_frame_irq_timing() {
+        // 4 cycles of delay after the write before the sequencer resets
+        assert_eq!(frame_irq_cycles(Region::Ntsc), 5 + 29828 - 1);
+        assert_eq!(frame_irq_cycles(Region::Pal), 5 + 33252 - 1);
+    }
+
+    #[test]
+    fn frame_irq_is_a_level() {
+        let mut apu = new_apu(Region::Ntsc);
+        frame_irq_cycles_from(&mut apu);
+
+        // stays asserted until acknowledged
+        for _ in 0..1000 {
+            apu.clock();
+        }
+        assert!(apu.irq_level());
+
+        assert_eq!(apu.read(0x4015, Device::Cpu) & 0x40, 0x40);
+        assert!(!apu.irq_level());
+        assert_eq!(apu.read(0x4015, Device::Cpu) & 0x40, 0);
+    }
+
+    #[test]
+    fn status_read_on_the_setting_cycle() {
+        let mut apu = new_apu(Region::Ntsc);
+        frame_irq_cycles_from(&mut apu);
+
+        // reads on the cycles the flag is set don't clear it
+        assert_eq!(apu.read(0x4015, Device::Cpu) & 0x40, 0x40);
+        assert!(apu.irq_level());
+        apu.clock();
+        assert_eq!(apu.read(0x4015, Device::Cpu) & 0x40, 0x40);
+        assert!(apu.irq_level());
+
+        apu.clock();
+        assert_eq!(apu.read(0x4015, Device::Cpu) & 0x40, 0x40);
+        assert!(!apu.irq_level());
+        apu.clock();
+        assert!(!apu.irq_level());
+    }
+
+    /// enable both pulse channels with the same `period` and sweep `$4001/$4005` value
+    fn new_pulse_apu(period: u16, sweep: u8) -> APU2A03 {
+        let mut apu = new_apu(Region::Ntsc);
+        apu.write(0x4015, 0x03, Device::Cpu);
+        for base in [0x4000, 0x4004] {
+            // halt length counter, constant volume 15
+            apu.write(base, 0xBF, Device::Cpu);
+            apu.write(base + 1, sweep, Device::Cpu);
+            apu.write(base + 2, period as u8, Device::Cpu);
+            apu.write(base + 3, (period >> 8) as u8 & 0b111, Device::Cpu);
+        }
+        apu
+    }
+
+    #[test]
+    fn sweep_mutes_small_periods() {
+        // sweep disabled
+        let states = new_pulse_apu(7, 0x00).channel_states();
+        assert!(!states.pulse_1.audible);
+        assert!(!states.pulse_2.audible);
+
+        let states = new_pulse_apu(8, 0x00).channel_states();
+        assert!(states.pulse_1.audible);
+        assert!(states.pulse_2.audible);
+    }
+
+    #[test]
+    fn sweep_mutes_target_overflow() {
+        // sweep disabled with shift 0, the target is double the period
+        let states = new_pulse_apu(0x400, 0x00).channel_states();
+        assert_eq!(states.pulse_1.sweep_target_period, 0x800);
+        assert!(!states.pulse_1.audible);
+        assert!(!states.pulse_2.audible);
+
+        let states = new_pulse_apu(0x3FF, 0x00).channel_states();
+        assert_eq!(states.pulse_1.sweep_target_period, 0x7FE);
+        assert!(states.pulse_1.audible);
+
+        // negated targets never overflow
+        let states = new_pulse_apu(0x400, 0x08).channel_states();
+        assert!(states.pulse_1.audible);
+        assert!(states.pulse_2.audible);
+
+        // a muted channel isn't updated by the sweep
+        let mut apu = new_pulse_apu(0x600, 0x81);
+        for _ in 0..29830 * 4 {
+            apu.clock();
+        }
+        assert_eq!(apu.channel_states().pulse_1.period, 0x600);
+    }
+
+    #[test]
+    fn sweep_negate_differs_between_pulses() {
+        // enabled, period 0 (update every half frame), negate, shift 1
+        let mut apu = new_pulse_apu(0x100, 0x89);
+        let states = apu.channel_states();
+        assert_eq!(states.pulse_1.sweep_target_period, 0x100 - 0x80 - 1);
+        assert_eq!(states.pulse_2.sweep_target_period, 0x100 - 0x80);
+
+        // one frame has two half frames
+        for _ in 0..29830 {
+            apu.clock();
+        }
+        let states = apu.channel_states();
+        assert_eq!(states.pulse_1.period, 0x7F - 0x3F - 1);
+        assert_eq!(states.pulse_2.period, 0x80 - 0x40);
+    }
+
+    #[test]
+    fn sweep_shift_zero_keeps_period() {
+        let mut apu = new_pulse_apu(0x100, 0x88);
+        for _ in 0..29830 * 2 {
+            apu.clock();
+        }
+        let states = apu.channel_states();
+        assert_eq!(states.pulse_1.period, 0x100);
+        assert_eq!(states.pulse_2.period, 0x100);
+    }
+
+    #[test]
+    fn region_mismatch_state() {
+        use crate::common::save_state::{Savable, SaveError};
+
+        let ntsc = new_apu(Region::Ntsc);
+        let mut pal = new_apu(Region::Pal);
+
+        let mut buffer = Vec::new();
+        ntsc.save(&mut buffer).unwrap();
+
+        assert!(matches!(
+            pal.load(&mut buffer.as_slice()),
+            Err(SaveError::RegionMismatch {
+                found: Region::Ntsc,
+                expected: Region::Pal
+            })
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Consistency checks of the bank mapping, done after every mapper register write
+//! when enabled with [`NES::set_mapper_audit`](crate::NES::set_mapper_audit)
+
+use super::mapper::{MapperInvariant, MappingResult};
+use super::Cartridge;
+use crate::common::Device;
+use crate::diagnostics::Diagnostic;
+use std::fmt;
+
+/// The mapping is checked at the first and last byte of every page of this size,
+/// which is the smallest bank size of the supported mappers
+const AUDIT_PAGE_SIZE: u16 = 0x400;
+
+/// The start of the NMI, reset and IRQ vectors
+const VECTORS: [u16; 3] = [0xFFFA, 0xFFFC, 0xFFFE];
+
+/// A problem in the bank mapping found by the mapper audit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingViolation {
+    /// The CPU `address` is mapped outside of the PRG ROM
+    PrgRomOutOfRange { address: u16, offset: usize },
+    /// The CPU `address` in `$8000-$FFFF` (including the interrupt vectors) is not
+    /// mapped to the PRG ROM
+    PrgRomUnmapped { address: u16 },
+    /// The CPU `address` is mapped outside of the PRG RAM
+    PrgRamOutOfRange { address: u16, offset: usize },
+    /// The PPU `address` is mapped outside of the CHR ROM/RAM
+    ChrOutOfRange { address: u16, offset: usize },
+    /// The PPU `address` is writable, but the cartridge has CHR ROM
+    ChrRomWritable { address: u16 },
+    /// The CPU `address` should be mapped to a fixed bank of the board at `expected`,
+    /// but is mapped to `found`
+    PrgRomNotFixed {
+        address: u16,
+        expected: usize,
+        found: Option<usize>,
+    },
+}
+
+impl fmt::Display for MappingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingViolation::PrgRomOutOfRange { address, offset } => {
+                write!(
+                    f,
+                    "CPU ${:04X} is mapped outside of the PRG ROM at ${:X}",
+                    address, offset
+                )
+            }
+            MappingViolation::PrgRomUnmapped { address } => {
+                write!(f, "CPU ${:04X} is not mapped to the PRG ROM", address)
+            }
+            MappingViolation::PrgRamOutOfRange { address, offset } => {
+                write!(
+                    f,
+                    "CPU ${:04X} is mapped outside of the PRG RAM at ${:X}",
+                    address, offset
+                )
+            }
+            MappingViolation::ChrOutOfRange { address, offset } => {
+                write!(
+                    f,
+                    "PPU ${:04X} is mapped outside of the CHR data at ${:X}",
+                    address, offset
+                )
+            }
+            MappingViolation::ChrRomWritable { address } => {
+                write!(f, "PPU ${:04X} is a writable CHR ROM", address)
+            }
+            MappingViolation::PrgRomNotFixed {
+                address,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "CPU ${:04X} should be the fixed bank at ${:X}, but ",
+                    address, expected
+                )?;
+                match found {
+                    Some(offset) => write!(f, "is mapped to ${:X}", offset),
+                    None => write!(f, "is not mapped"),
+                }
+            }
+        }
+    }
+}
+
+/// The first and last address of every audit page in `start..=end`
+fn page_bounds(start: u16, end: u16) -> impl Iterator<Item = u16> {
+    (start..=end)
+        .step_by(AUDIT_PAGE_SIZE as usize)
+        .flat_map(|page| [page, page + (AUDIT_PAGE_SIZE - 1)])
+}
+
+impl Cartridge {
+    pub fn set_mapper_audit(&mut self, enabled: bool) {
+        self.mapper_audit = enabled;
+    }
+
+    /// Check the current bank mapping, after the CPU wrote `data` to the mapper
+    /// register at `register`, and emit [`Diagnostic::MapperAudit`] for every problem
+    pub(super) fn audit_mapping(&mut self, register: u16, data: u8) {
+        let mut violations = Vec::new();
+
+        for address in page_bounds(0x8000, 0xFFFF).chain(VECTORS) {
+            match self.mapper.map_read(address, Device::Cpu) {
+                MappingResult::Allowed(offset) if offset >= self.prg_data.len() => {
+                    violations.push(MappingViolation::PrgRomOutOfRange { address, offset })
+                }
+                MappingResult::Allowed(_) => {}
+                MappingResult::Denied => {
+                    violations.push(MappingViolation::PrgRomUnmapped { address })
+                }
+            }
+        }
+
+        for address in page_bounds(0x6000, 0x7FFF) {
+            if let MappingResult::Allowed(offset) = self.mapper.map_read(address, Device::Cpu) {
+                if offset >= self.prg_ram_data.len() {
+                    violations.push(MappingViolation::PrgRamOutOfRange { address, offset });
+                }
+            }
+        }
+
+        for address in page_bounds(0x0000, 0x1FFF) {
+            if let MappingResult::Allowed(offset) = self.mapper.map_read(address, Device::Ppu) {
+                if offset >= self.chr_data.len() {
+                    violations.push(MappingViolation::ChrOutOfRange { address, offset });
+                }
+            }
+        }
+
+        if !self.header.is_chr_ram {
+            // probing the writes can change the mapper state (latches for example)
+            let state = self.mapper.save_state();
+            for address in page_bounds(0x0000, 0x1FFF) {
+                if let MappingResult::Allowed(_) = self.mapper.map_write(address, 0, Device::Ppu) {
+                    violations.push(MappingViolation::ChrRomWritable { address });
+                }
+            }
+            self.mapper.load_state(state);
+        }
+
+        for invariant in self.mapper.expected_invariants() {
+            match invariant {
+                MapperInvariant::PrgRomFixed { address, offset } => {
+                    let found = match self.mapper.map_read(address, Device::Cpu) {
+                        MappingResult::Allowed(found) => Some(found),
+                        MappingResult::Denied => None,
+                    };
+                    if found != Some(offset) {
+                        violations.push(MappingViolation::PrgRomNotFixed {
+                            address,
+                            expected: offset,
+                            found,
+                        });
+                    }
+                }
+            }
+        }
+
+        for violation in violations {
+            self.diagnostics.push(Diagnostic::MapperAudit {
+                mapper: self.header.mapper_id,
+                register,
+                data,
+                violation,
+            });
+        }
+    }
+}
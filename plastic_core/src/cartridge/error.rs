@@ -1,3 +1,4 @@
+use super::mappers::mapper_name;
 use std::{
     convert::From,
     default::Default,
@@ -6,43 +7,149 @@ use std::{
     io::{Error as ioError, ErrorKind},
 };
 
+/// The part of the ROM file being read, see [`CartridgeError::TruncatedData`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomSection {
+    Header,
+    Trainer,
+    PrgRom,
+    ChrRom,
+}
+
+impl Display for RomSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
+        f.write_str(match self {
+            Self::Header => "header",
+            Self::Trainer => "trainer",
+            Self::PrgRom => "PRG ROM",
+            Self::ChrRom => "CHR ROM",
+        })
+    }
+}
+
+/// Why the header of the file is invalid, see [`CartridgeError::HeaderError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderErrorReason {
+    /// The file doesn't start with `NES<EOF>`, contains the first 4 bytes
+    BadMagic([u8; 4]),
+    /// Bits that must be `0` are set in the header byte at `byte`
+    ReservedBitsSet { byte: usize, value: u8 },
+    /// A size declared in the header is not supported, in bytes. The sizes must be
+    /// whole banks (16KB for PRG ROM, 8KB for CHR ROM), up to 255 of them, and
+    /// there must be PRG ROM
+    InconsistentSize { section: RomSection, size: u64 },
+}
+
+impl Display for HeaderErrorReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
+        match self {
+            Self::BadMagic(magic) => write!(
+                f,
+                "the file starts with {:02X?} instead of the iNES magic {:02X?}",
+                magic,
+                [0x4E, 0x45, 0x53, 0x1A]
+            ),
+            Self::ReservedBitsSet { byte, value } => write!(
+                f,
+                "reserved bits are set in byte {} of the header (value {:02X})",
+                byte, value
+            ),
+            Self::InconsistentSize { section, size } => {
+                write!(f, "the {} size ({} bytes) is not supported", section, size)
+            }
+        }
+    }
+}
+
 /// Error happening when loading a NES cartridge.
 pub enum CartridgeError {
     /// Error with file input/output.
-    /// Contains an [`io::Error`][ioError] which provides more details about the error.
+    /// Contains an [`io::Error`][ioError] which provides more details about the error,
+    /// it is also the [`source`](Error::source) of this error.
     FileError(ioError),
 
     /// The cartridge header is invalid or corrupted.
-    HeaderError,
+    HeaderError { reason: HeaderErrorReason },
+
+    /// The file ended before all the data declared in the header, `expected` and
+    /// `got` are the sizes in bytes of the `section` that was being read.
+    TruncatedData {
+        expected: usize,
+        got: usize,
+        section: RomSection,
+    },
 
-    /// The file size is too large.
-    /// Contains the size of the file in bytes.
-    TooLargeFile(u64),
+    /// The file is larger than the size declared in the header.
+    /// `file_size` is the size of the file and `extra` the bytes after the data, in bytes.
+    TooLargeFile { file_size: u64, extra: u64 },
+
+    /// The file is larger than the size computed from a NES 2.0 header using the
+    /// exponent-multiplier notation. `file_size` is the size of the file and `expected`
+    /// the size of the header, trainer, PRG and CHR ROM declared in the header, in bytes.
+    SizeMismatch { file_size: u64, expected: u64 },
 
     /// The file extension is not recognized or supported.
     ExtensionError,
 
     /// The mapper type is not implemented.
     MapperNotImplemented(u16),
+
+    /// The IPS/BPS patch is invalid or in an unsupported format.
+    InvalidPatch,
+
+    /// The checksum of the source ROM, patched ROM or the patch itself
+    /// does not match the one stored in the BPS patch.
+    PatchChecksumMismatch { expected: u32, found: u32 },
 }
 
 impl CartridgeError {
     fn get_message(&self) -> String {
         match self {
             Self::FileError(err) => format!("FileError: {}", err),
-            Self::HeaderError => "This is not a valid iNES file".to_owned(),
-            Self::TooLargeFile(size) => format!(
+            Self::HeaderError { reason } => format!("This is not a valid iNES file, {}", reason),
+            Self::TruncatedData {
+                expected,
+                got,
+                section,
+            } => format!(
+                "The file ended while reading the {}, expected {}-bytes but got {}-bytes",
+                section, expected, got
+            ),
+            Self::TooLargeFile { file_size, extra } => format!(
                 "The cartridge reader read all the data needed, but the file \
-                still has some data at the end with size {}-bytes",
-                size
+                still has some data at the end with size {}-bytes (file size is {}-bytes)",
+                extra, file_size
             ),
-            Self::MapperNotImplemented(id) => format!("Mapper {} is not yet implemented", id),
+            Self::SizeMismatch {
+                file_size,
+                expected,
+            } => format!(
+                "The file is {} bytes, but the header declares {} bytes of data \
+                (header, trainer, PRG and CHR ROM)",
+                file_size, expected
+            ),
+            Self::MapperNotImplemented(id) => match mapper_name(*id) {
+                Some(name) => format!("Mapper {} ({}) is not yet implemented", id, name),
+                None => format!("Mapper {} is not yet implemented", id),
+            },
             Self::ExtensionError => "The cartridge file must end with `.nes` extension".to_owned(),
+            Self::InvalidPatch => "The patch file is invalid or not supported".to_owned(),
+            Self::PatchChecksumMismatch { expected, found } => format!(
+                "Patch checksum mismatch, expected {:08X} but found {:08X}",
+                expected, found
+            ),
         }
     }
 }
 
-impl Error for CartridgeError {}
+impl Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::FileError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 impl Display for CartridgeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
@@ -62,9 +169,14 @@ impl From<ioError> for CartridgeError {
     }
 }
 
+impl From<HeaderErrorReason> for CartridgeError {
+    fn from(reason: HeaderErrorReason) -> Self {
+        Self::HeaderError { reason }
+    }
+}
+
 pub enum SramError {
     NoSramFileFound,
-    SramFileSizeDoesNotMatch,
     FailedToSaveSramFile,
     Others,
 }
@@ -73,11 +185,11 @@ impl SramError {
     fn get_message(&self) -> &str {
         match self {
             Self::NoSramFileFound => "Could not load cartridge save file",
-            Self::SramFileSizeDoesNotMatch => "There is a conflict in the size \
-                                            of SRAM save file in the INES header and the file in disk",
             Self::FailedToSaveSramFile => "Could not save cartridge save file",
-            Self::Others => "Unknown error occured while trying to save/load \
-                          cartridge save file",
+            Self::Others => {
+                "Unknown error occured while trying to save/load \
+                          cartridge save file"
+            }
         }
     }
 }
@@ -0,0 +1,64 @@
+/// The default expansion device (input device) declared in byte 15 of the NES 2.0 header.
+///
+/// iNES 1.0 files don't have this information, and will always report [`ExpansionDevice::Unspecified`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionDevice {
+    #[default]
+    Unspecified,
+    /// Standard NES/Famicom controllers
+    StandardControllers,
+    /// NES Four Score/Satellite with two additional standard controllers
+    FourScore,
+    /// Famicom Four Players Adapter with two additional standard controllers
+    FamicomFourPlayersAdapter,
+    /// Vs. System (1P via `$4016`)
+    VsSystem,
+    /// Vs. System (1P via `$4017`)
+    VsSystemSwapped,
+    /// Vs. Zapper
+    VsZapper,
+    /// Zapper on port 2 (`$4017`)
+    Zapper,
+    /// Two Zappers
+    TwoZappers,
+    /// Bandai Hyper Shot lightgun
+    BandaiHyperShot,
+    /// Power Pad side A
+    PowerPadSideA,
+    /// Power Pad side B
+    PowerPadSideB,
+    /// Family Trainer side A
+    FamilyTrainerSideA,
+    /// Family Trainer side B
+    FamilyTrainerSideB,
+    /// Arkanoid Vaus controller (NES)
+    VausNes,
+    /// Arkanoid Vaus controller (Famicom)
+    VausFamicom,
+    /// Any other device, contains the device code
+    Other(u8),
+}
+
+impl ExpansionDevice {
+    pub(crate) fn from_code(code: u8) -> Self {
+        match code & 0x3F {
+            0x00 => Self::Unspecified,
+            0x01 => Self::StandardControllers,
+            0x02 => Self::FourScore,
+            0x03 => Self::FamicomFourPlayersAdapter,
+            0x04 => Self::VsSystem,
+            0x05 => Self::VsSystemSwapped,
+            0x07 => Self::VsZapper,
+            0x08 => Self::Zapper,
+            0x09 => Self::TwoZappers,
+            0x0A => Self::BandaiHyperShot,
+            0x0B => Self::PowerPadSideA,
+            0x0C => Self::PowerPadSideB,
+            0x0D => Self::FamilyTrainerSideA,
+            0x0E => Self::FamilyTrainerSideB,
+            0x0F => Self::VausNes,
+            0x10 => Self::VausFamicom,
+            code => Self::Other(code),
+        }
+    }
+}
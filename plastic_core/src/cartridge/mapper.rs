@@ -5,6 +5,24 @@ pub enum MappingResult {
     Denied,
 }
 
+/// `bank % count`, using a mask when `count` is a power of two (almost all
+/// cartridges), as this is done on every cartridge access
+#[inline]
+pub fn wrap_bank(bank: usize, count: usize) -> usize {
+    if count.is_power_of_two() {
+        bank & (count - 1)
+    } else {
+        bank % count
+    }
+}
+
+/// A fact about the bank mapping that always holds for a board, checked by the
+/// mapper audit, see [`Mapper::expected_invariants`]
+pub enum MapperInvariant {
+    /// The CPU `address` is mapped to `offset` in the PRG ROM, a fixed bank
+    PrgRomFixed { address: u16, offset: usize },
+}
+
 pub trait Mapper {
     fn init(&mut self, pgr_count: u8, is_chr_ram: bool, chr_count: u8, sram_count: u8);
 
@@ -24,6 +42,46 @@ pub trait Mapper {
         true
     }
 
+    /// `true` if the four-screen bit of the header overrides the mirroring of the mapper.
+    ///
+    /// Boards with the extra nametable RAM wire all 4 nametables to it, and the mirroring
+    /// control of the mapper is not connected (MMC3 in Rad Racer II for example).
+    /// Boards that can't have it return `false`, so a wrong four-screen bit in the header
+    /// doesn't break their mirroring control.
+    fn four_screen_overrides_mirroring(&self) -> bool {
+        true
+    }
+
+    /// `true` if the mapper has a register at the CPU `address`, used to detect
+    /// writes to hardware that the mapper doesn't have (probably a wrong mapper)
+    fn has_register_at(&self, address: u16) -> bool {
+        address >= 0x8000
+    }
+
+    /// `true` if the board has bus conflicts, a CPU write to ROM is ANDed with
+    /// the ROM byte at the address before reaching the mapper registers
+    fn has_bus_conflicts(&self) -> bool {
+        false
+    }
+
+    /// the size of the smallest PRG ROM bank the mapper switches, used to
+    /// report the banks mapped in the CPU memory map
+    fn prg_bank_size(&self) -> u16 {
+        0x4000
+    }
+
+    /// board specific invariants of the current bank mapping, checked after
+    /// every register write when the mapper audit is enabled
+    fn expected_invariants(&self) -> Vec<MapperInvariant> {
+        Vec::new()
+    }
+
+    /// called on every CPU cycle, for mappers with timers counting CPU cycles
+    fn cpu_cycle(&mut self) {}
+
+    /// set the DIP switches of the board, if it has any
+    fn set_dip_switches(&mut self, _dips: u8) {}
+
     fn nametable_mirroring(&self) -> MirroringMode {
         unreachable!()
     }
@@ -75,6 +75,10 @@ impl Mapper for Mapper0 {
         }
     }
 
+    fn has_register_at(&self, _address: u16) -> bool {
+        false
+    }
+
     fn save_state_size(&self) -> usize {
         1
     }
@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MapperInvariant, MappingResult};
 use crate::common::{Device, MirroringMode};
 
 pub struct Mapper1 {
@@ -174,7 +174,7 @@ impl Mapper1 {
             unreachable!()
         } as usize;
 
-        bank %= self.chr_count as usize;
+        bank = wrap_bank(bank, self.chr_count as usize);
 
         let start_of_bank = 0x1000 * bank;
 
@@ -256,7 +256,7 @@ impl Mapper for Mapper1 {
                             bank |= prg_high_bit_512_mode;
                         }
 
-                        bank %= self.prg_count as usize;
+                        bank = wrap_bank(bank, self.prg_count as usize);
 
                         let start_of_bank = 0x4000 * bank;
 
@@ -356,6 +356,33 @@ impl Mapper for Mapper1 {
         10
     }
 
+    fn expected_invariants(&self) -> Vec<MapperInvariant> {
+        if self.is_prg_32kb_mode() {
+            return Vec::new();
+        }
+
+        // the fixed bank is the first or last of the 256KB outer bank
+        let outer_bank = if self.prg_count > 16 && self.chr_count == 2 {
+            if self.is_chr_8kb_mode() {
+                self.chr_0_bank & 0x10
+            } else {
+                self.chr_1_bank & 0x10
+            }
+        } else {
+            0
+        } as usize;
+        let (address, bank) = if self.is_first_prg_chunk_fixed() {
+            (0x8000, outer_bank)
+        } else {
+            (0xC000, outer_bank + self.prg_count.min(16) as usize - 1)
+        };
+
+        vec![MapperInvariant::PrgRomFixed {
+            address,
+            offset: bank * 0x4000,
+        }]
+    }
+
     fn save_state(&self) -> Vec<u8> {
         vec![
             self.writing_shift_register,
@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::{Device, MirroringMode};
 use serde::{Deserialize, Serialize};
 use std::cell::Cell;
@@ -122,7 +122,7 @@ impl Mapper10 {
             }
         } as usize;
 
-        bank %= self.chr_count as usize;
+        bank = wrap_bank(bank, self.chr_count as usize);
 
         let start_of_bank = bank * 0x1000;
 
@@ -153,7 +153,7 @@ impl Mapper for Mapper10 {
                         _ => unreachable!(),
                     } as usize;
 
-                    bank %= self.prg_count as usize;
+                    bank = wrap_bank(bank, self.prg_count as usize);
 
                     let start_of_bank = bank * 0x4000;
 
@@ -215,6 +215,10 @@ impl Mapper for Mapper10 {
         }
     }
 
+    fn has_register_at(&self, address: u16) -> bool {
+        address >= 0xA000
+    }
+
     fn save_state_size(&self) -> usize {
         bincode::serialized_size(self).unwrap() as usize
     }
@@ -0,0 +1,274 @@
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
+use crate::common::{Device, MirroringMode};
+use serde::{Deserialize, Serialize};
+
+/// The default value of the timer DIP switches, the setting used in the competition
+const DEFAULT_TIMER_DIPS: u8 = 4;
+
+/// NES-EVENT, the Nintendo World Championships 1990 cartridge, an MMC1 with
+/// 2 PRG ROM chips of 128kb and a timer that raises an IRQ at the end of the game
+#[derive(Serialize, Deserialize)]
+pub struct Mapper105 {
+    writing_shift_register: u8,
+
+    /// 4bit0
+    /// -----
+    /// CPPMM
+    /// |||||
+    /// |||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+    /// |||               2: vertical; 3: horizontal)
+    /// |++--- PRG ROM bank mode of the second chip, same as MMC1
+    /// +----- Unused, CHR RAM is not banked
+    control_register: u8,
+
+    /// 4bit0
+    /// -----
+    /// IABBx
+    /// ||||
+    /// ||++-- Select 32 KB PRG ROM bank from the first chip
+    /// |+---- PRG ROM chip select (0: first chip, 32 KB banks; 1: second chip, MMC1 banking)
+    /// +----- Timer (0: counting; 1: reset and acknowledge the IRQ)
+    chr_0_bank: u8,
+
+    /// 4bit0
+    /// -----
+    /// RxPPP
+    /// | |||
+    /// | +++- Select 16 KB PRG ROM bank from the second chip (low bit ignored in 32 KB mode)
+    /// +----- PRG RAM chip enable (0: enabled; 1: disabled)
+    prg_bank: u8,
+
+    /// The PRG ROM is locked to the first 32kb until the timer bit (`I`) of `chr_0_bank`
+    /// is cleared and then set, counts the steps, `2` is unlocked
+    init_state: u8,
+
+    /// number of CPU cycles since the timer started
+    timer_counter: u32,
+
+    irq_pin: bool,
+    is_irq_pin_changed: bool,
+
+    /// in 16kb units
+    prg_count: u8,
+
+    /// in 8kb units
+    prg_ram_count: u8,
+
+    /// the timer duration is `(16 + dips) * 2^25` CPU cycles, a setting
+    /// of the cartridge and not saved in the state
+    #[serde(skip)]
+    timer_dips: u8,
+}
+
+impl Mapper105 {
+    pub fn new() -> Self {
+        Self {
+            writing_shift_register: 0b10000,
+            control_register: 0x0C,
+            // the timer is held in reset until the game starts it
+            chr_0_bank: 0x10,
+            prg_bank: 0,
+            init_state: 0,
+            timer_counter: 0,
+            irq_pin: false,
+            is_irq_pin_changed: false,
+            prg_count: 0,
+            prg_ram_count: 0,
+            timer_dips: DEFAULT_TIMER_DIPS,
+        }
+    }
+
+    /// The number of CPU cycles from starting the timer to the IRQ
+    pub fn timer_cycles(dips: u8) -> u32 {
+        (16 + (dips & 0xF) as u32) << 25
+    }
+
+    fn reset_shift_register(&mut self) {
+        self.writing_shift_register = 0b10000;
+    }
+
+    fn is_timer_held(&self) -> bool {
+        self.chr_0_bank & 0x10 != 0
+    }
+
+    fn write_chr_0_bank(&mut self, data: u8) {
+        self.chr_0_bank = data;
+
+        let timer_held = self.is_timer_held();
+        match (self.init_state, timer_held) {
+            (0, false) | (1, true) => self.init_state += 1,
+            _ => {}
+        }
+
+        if timer_held {
+            self.timer_counter = 0;
+            // acknowledge
+            self.irq_pin = false;
+            self.is_irq_pin_changed = true;
+        }
+    }
+
+    /// the 16kb PRG bank mapped at `address`
+    fn prg_bank(&self, address: u16) -> usize {
+        let half = ((address >> 14) & 1) as u8;
+
+        let bank = if self.init_state < 2 {
+            half
+        } else if self.chr_0_bank & 0x08 == 0 {
+            // 32kb banks of the first chip
+            self.chr_0_bank & 0b110 | half
+        } else {
+            let prg_bank = self.prg_bank & 0b111;
+            let second_chip_bank = match (self.control_register >> 2) & 0b11 {
+                0 | 1 => prg_bank & 0b110 | half,
+                // fix the first bank at $8000
+                2 if half == 0 => 0,
+                // fix the last bank at $C000
+                3 if half == 1 => 0b111,
+                _ => prg_bank,
+            };
+
+            0b1000 | second_chip_bank
+        };
+
+        wrap_bank(bank as usize, self.prg_count as usize)
+    }
+
+    fn map_prg_ram(&self, address: u16) -> MappingResult {
+        if self.prg_bank & 0x10 == 0 && self.prg_ram_count > 0 {
+            MappingResult::Allowed((address & 0x1FFF) as usize)
+        } else {
+            MappingResult::Denied
+        }
+    }
+}
+
+impl Mapper for Mapper105 {
+    fn init(&mut self, prg_count: u8, _is_chr_ram: bool, _chr_count: u8, sram_count: u8) {
+        self.prg_count = prg_count;
+        self.prg_ram_count = sram_count;
+
+        self.reset_shift_register();
+    }
+
+    fn map_read(&self, address: u16, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => self.map_prg_ram(address),
+                0x8000..=0xFFFF => MappingResult::Allowed(
+                    self.prg_bank(address) * 0x4000 + (address & 0x3FFF) as usize,
+                ),
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if address < 0x2000 {
+                    MappingResult::Allowed(address as usize)
+                } else {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    fn map_write(&mut self, address: u16, data: u8, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => self.map_prg_ram(address),
+                0x8000..=0xFFFF => {
+                    if data & 0x80 != 0 {
+                        self.reset_shift_register();
+                    } else {
+                        let should_save = self.writing_shift_register & 1 != 0;
+                        // shift
+                        self.writing_shift_register >>= 1;
+                        self.writing_shift_register |= (data & 1) << 4;
+
+                        // reached the end, then save
+                        if should_save {
+                            let result = self.writing_shift_register & 0b11111;
+                            match address {
+                                0x8000..=0x9FFF => self.control_register = result,
+                                0xA000..=0xBFFF => self.write_chr_0_bank(result),
+                                // CHR bank 1 is not used
+                                0xC000..=0xDFFF => {}
+                                0xE000..=0xFFFF => self.prg_bank = result,
+                                _ => unreachable!(),
+                            }
+
+                            self.reset_shift_register();
+                        }
+                    }
+                    MappingResult::Denied
+                }
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                // 8kb CHR RAM
+                if address <= 0x1FFF {
+                    MappingResult::Allowed(address as usize)
+                } else {
+                    MappingResult::Denied
+                }
+            }
+        }
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.is_timer_held() {
+            return;
+        }
+
+        self.timer_counter = self.timer_counter.wrapping_add(1);
+        if self.timer_counter == Self::timer_cycles(self.timer_dips) {
+            self.irq_pin = true;
+            self.is_irq_pin_changed = true;
+        }
+    }
+
+    fn set_dip_switches(&mut self, dips: u8) {
+        self.timer_dips = dips;
+    }
+
+    fn is_hardwired_mirrored(&self) -> bool {
+        false
+    }
+
+    fn nametable_mirroring(&self) -> MirroringMode {
+        [
+            MirroringMode::SingleScreenLowBank,
+            MirroringMode::SingleScreenHighBank,
+            MirroringMode::Vertical,
+            MirroringMode::Horizontal,
+        ][(self.control_register & 0b11) as usize]
+    }
+
+    fn is_irq_pin_state_changed_requested(&self) -> bool {
+        self.is_irq_pin_changed
+    }
+
+    fn irq_pin_state(&self) -> bool {
+        self.irq_pin
+    }
+
+    fn clear_irq_request_pin(&mut self) {
+        self.irq_pin = false;
+        self.is_irq_pin_changed = false;
+    }
+
+    fn save_state_size(&self) -> usize {
+        bincode::serialized_size(self).unwrap() as usize
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: Vec<u8>) {
+        let mut state: Self = bincode::deserialize(&data).unwrap();
+        state.timer_dips = self.timer_dips;
+
+        let _ = std::mem::replace(self, state);
+    }
+}
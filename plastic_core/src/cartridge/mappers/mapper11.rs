@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::Device;
 
 pub struct Mapper11 {
@@ -30,9 +30,9 @@ impl Mapper11 {
     }
 
     fn map_ppu(&self, address: u16) -> MappingResult {
-        let bank = self.chr_bank % self.chr_count;
+        let bank = wrap_bank(self.chr_bank as usize, self.chr_count as usize);
 
-        let start_of_bank = 0x2000 * bank as usize;
+        let start_of_bank = 0x2000 * bank;
 
         MappingResult::Allowed(start_of_bank + (address & 0x1FFF) as usize)
     }
@@ -53,9 +53,9 @@ impl Mapper for Mapper11 {
             Device::Cpu => match address {
                 0x6000..=0x7FFF => MappingResult::Denied,
                 0x8000..=0xFFFF => {
-                    let bank = self.prg_bank % self.prg_count;
+                    let bank = wrap_bank(self.prg_bank as usize, self.prg_count as usize);
 
-                    let start_of_bank = 0x8000 * bank as usize;
+                    let start_of_bank = 0x8000 * bank;
 
                     MappingResult::Allowed(start_of_bank + (address & 0x7FFF) as usize)
                 }
@@ -96,6 +96,14 @@ impl Mapper for Mapper11 {
         }
     }
 
+    fn has_bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn prg_bank_size(&self) -> u16 {
+        0x8000
+    }
+
     fn save_state_size(&self) -> usize {
         5
     }
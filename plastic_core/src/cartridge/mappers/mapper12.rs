@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::{Device, MirroringMode};
 use serde::{Deserialize, Serialize};
 use std::cell::Cell;
@@ -228,7 +228,7 @@ impl Mapper12 {
 
         bank |= extra_256_bit << 8;
 
-        bank %= self.chr_count as usize;
+        bank = wrap_bank(bank, self.chr_count as usize);
 
         let mask = if is_2k { 0x7FF } else { 0x3FF };
 
@@ -282,7 +282,7 @@ impl Mapper for Mapper12 {
                             _ => unreachable!(),
                         } as usize;
 
-                        bank %= self.prg_count as usize;
+                        bank = wrap_bank(bank, self.prg_count as usize);
 
                         let start_of_bank = bank * 0x2000;
 
@@ -395,6 +395,10 @@ impl Mapper for Mapper12 {
         }
     }
 
+    fn prg_bank_size(&self) -> u16 {
+        0x2000
+    }
+
     fn is_hardwired_mirrored(&self) -> bool {
         false
     }
@@ -420,6 +424,10 @@ impl Mapper for Mapper12 {
         self.is_irq_pin_changed.set(false);
     }
 
+    fn has_register_at(&self, address: u16) -> bool {
+        matches!(address, 0x4020..=0x5FFF | 0x8000..=0xFFFF)
+    }
+
     fn save_state_size(&self) -> usize {
         bincode::serialized_size(self).unwrap() as usize
     }
@@ -1,7 +1,11 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MapperInvariant, MappingResult};
 use crate::common::Device;
 
+/// UxROM, the NES 2.0 submapper tells if the board has bus conflicts (2) or not (1),
+/// otherwise they are not emulated
 pub struct Mapper2 {
+    submapper_id: u8,
+
     prg_top_bank: u8,
 
     /// in 16kb units
@@ -11,8 +15,9 @@ pub struct Mapper2 {
 }
 
 impl Mapper2 {
-    pub fn new() -> Self {
+    pub fn new(submapper_id: u8) -> Self {
         Self {
+            submapper_id,
             prg_top_bank: 0,
             prg_count: 0,
             is_chr_ram: false,
@@ -40,7 +45,7 @@ impl Mapper for Mapper2 {
                             unreachable!();
                         } as usize;
 
-                        bank %= self.prg_count as usize;
+                        bank = wrap_bank(bank, self.prg_count as usize);
 
                         let start_of_bank = 0x4000 * bank;
 
@@ -85,10 +90,21 @@ impl Mapper for Mapper2 {
         }
     }
 
+    fn has_bus_conflicts(&self) -> bool {
+        self.submapper_id == 2
+    }
+
     fn save_state_size(&self) -> usize {
         3
     }
 
+    fn expected_invariants(&self) -> Vec<MapperInvariant> {
+        vec![MapperInvariant::PrgRomFixed {
+            address: 0xC000,
+            offset: (self.prg_count as usize - 1) * 0x4000,
+        }]
+    }
+
     fn save_state(&self) -> Vec<u8> {
         vec![self.prg_top_bank, self.prg_count, self.is_chr_ram as u8]
     }
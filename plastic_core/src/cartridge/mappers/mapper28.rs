@@ -0,0 +1,248 @@
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
+use crate::common::{Device, MirroringMode};
+
+/// Action 53, used by homebrew multicarts
+pub struct Mapper28 {
+    /// ($5000-$5FFF)
+    /// 7  bit  0
+    /// ---- ----
+    /// Sxxx xxxR
+    /// |       |
+    /// +-------+- Select the register written by $8000-$FFFF
+    ///            ($00: CHR bank, $01: inner PRG bank, $80: mode, $81: outer PRG bank)
+    selected_register: u8,
+
+    /// register $00
+    /// 7  bit  0
+    /// ---- ----
+    /// xxxM xxCC
+    ///    |   ||
+    ///    |   ++- Select 8 KB CHR RAM bank for PPU $0000-$1FFF
+    ///    +------ Select the one-screen mirroring page if the mirroring is one-screen
+    chr_bank: u8,
+
+    /// register $01
+    /// 7  bit  0
+    /// ---- ----
+    /// xxxM PPPP
+    ///    | ||||
+    ///    | ++++- Select 16 KB (or 32 KB in 32 KB mode) PRG ROM bank inside the game
+    ///    +------ Select the one-screen mirroring page if the mirroring is one-screen
+    inner_prg_bank: u8,
+
+    /// register $80
+    /// 7  bit  0
+    /// ---- ----
+    /// xxGG PSMM
+    ///   || ||||
+    ///   || ||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+    ///   || ||               2: vertical; 3: horizontal)
+    ///   || |+--- In 16 KB mode (0: switch $C000 and fix $8000;
+    ///   || |                    1: switch $8000 and fix $C000)
+    ///   || +---- PRG ROM bank mode (0: 32 KB; 1: 16 KB)
+    ///   ++------ Game size, the number of inner bank bits (0: 32 KB; 1: 64 KB;
+    ///                                                      2: 128 KB; 3: 256 KB)
+    mode: u8,
+
+    /// register $81
+    /// 7  bit  0
+    /// ---- ----
+    /// OOOO OOOO
+    /// |||| ||||
+    /// ++++-++++- Select 32 KB PRG ROM bank of the game, the inner bank replaces
+    ///            the low bits of it depending on the game size
+    outer_prg_bank: u8,
+
+    /// the one-screen mirroring page, set by the `M` bit of the last register written
+    one_screen_upper: bool,
+
+    /// in 16kb units
+    prg_count: u8,
+
+    /// in 8kb units
+    chr_count: u8,
+
+    /// using CHR RAM
+    is_chr_ram: bool,
+
+    /// in 8kb units
+    prg_ram_count: u8,
+}
+
+impl Mapper28 {
+    pub fn new() -> Self {
+        Self {
+            selected_register: 0,
+            chr_bank: 0,
+            inner_prg_bank: 0,
+            mode: 0,
+            // power-up, the menu is in the last bank
+            outer_prg_bank: 0xFF,
+            one_screen_upper: false,
+            prg_count: 0,
+            chr_count: 0,
+            is_chr_ram: false,
+            prg_ram_count: 0,
+        }
+    }
+
+    /// the 16kb PRG bank mapped at `address`
+    fn prg_bank(&self, address: u16) -> usize {
+        let half = ((address >> 14) & 1) as usize;
+        let outer = (self.outer_prg_bank as usize) << 1;
+        let inner = (self.inner_prg_bank & 0xF) as usize;
+        // the bits of the 16kb bank number that come from the inner bank
+        let inner_mask = (2 << ((self.mode >> 4) & 0b11)) - 1;
+
+        let bank = if self.mode & 0b1000 == 0 {
+            // 32kb mode
+            (outer & !inner_mask) | ((inner << 1 | half) & inner_mask)
+        } else {
+            let switch_8000 = self.mode & 0b100 != 0;
+
+            if (half == 0) == switch_8000 {
+                (outer & !inner_mask) | (inner & inner_mask)
+            } else {
+                // fixed to the first or last 16kb of the outer bank
+                outer | half
+            }
+        };
+
+        wrap_bank(bank, self.prg_count as usize)
+    }
+
+    fn map_ppu(&self, address: u16) -> MappingResult {
+        let bank = wrap_bank((self.chr_bank & 0b11) as usize, self.chr_count as usize);
+
+        MappingResult::Allowed(bank * 0x2000 + (address & 0x1FFF) as usize)
+    }
+
+    fn map_prg_ram(&self, address: u16) -> MappingResult {
+        if self.prg_ram_count > 0 {
+            MappingResult::Allowed((address & 0x1FFF) as usize)
+        } else {
+            MappingResult::Denied
+        }
+    }
+}
+
+impl Mapper for Mapper28 {
+    fn init(&mut self, prg_count: u8, is_chr_ram: bool, chr_count: u8, sram_count: u8) {
+        self.prg_count = prg_count;
+        self.chr_count = chr_count;
+        self.is_chr_ram = is_chr_ram;
+        self.prg_ram_count = sram_count;
+    }
+
+    fn map_read(&self, address: u16, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => self.map_prg_ram(address),
+                0x8000..=0xFFFF => MappingResult::Allowed(
+                    self.prg_bank(address) * 0x4000 + (address & 0x3FFF) as usize,
+                ),
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if address < 0x2000 {
+                    self.map_ppu(address)
+                } else {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    fn map_write(&mut self, address: u16, data: u8, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => self.map_prg_ram(address),
+                0x5000..=0x5FFF => {
+                    self.selected_register = (data >> 6) & 0b10 | data & 1;
+
+                    MappingResult::Denied
+                }
+                0x8000..=0xFFFF => {
+                    match self.selected_register {
+                        0 => {
+                            self.chr_bank = data;
+                            self.one_screen_upper = data & 0x10 != 0;
+                        }
+                        1 => {
+                            self.inner_prg_bank = data;
+                            self.one_screen_upper = data & 0x10 != 0;
+                        }
+                        2 => {
+                            self.mode = data;
+                            self.one_screen_upper = data & 1 != 0;
+                        }
+                        3 => self.outer_prg_bank = data,
+                        _ => unreachable!(),
+                    }
+
+                    MappingResult::Denied
+                }
+                0x4020..=0x4FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if self.is_chr_ram && address <= 0x1FFF {
+                    self.map_ppu(address)
+                } else {
+                    MappingResult::Denied
+                }
+            }
+        }
+    }
+
+    fn has_register_at(&self, address: u16) -> bool {
+        (0x5000..=0x5FFF).contains(&address) || address >= 0x8000
+    }
+
+    fn is_hardwired_mirrored(&self) -> bool {
+        false
+    }
+
+    fn nametable_mirroring(&self) -> MirroringMode {
+        match self.mode & 0b11 {
+            0 | 1 if self.one_screen_upper => MirroringMode::SingleScreenHighBank,
+            0 | 1 => MirroringMode::SingleScreenLowBank,
+            2 => MirroringMode::Vertical,
+            3 => MirroringMode::Horizontal,
+            _ => unreachable!(),
+        }
+    }
+
+    fn save_state_size(&self) -> usize {
+        10
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![
+            self.selected_register,
+            self.chr_bank,
+            self.inner_prg_bank,
+            self.mode,
+            self.outer_prg_bank,
+            self.one_screen_upper as u8,
+            self.prg_count,
+            self.chr_count,
+            self.is_chr_ram as u8,
+            self.prg_ram_count,
+        ]
+    }
+
+    fn load_state(&mut self, data: Vec<u8>) {
+        self.selected_register = data[0];
+        self.chr_bank = data[1];
+        self.inner_prg_bank = data[2];
+        self.mode = data[3];
+        self.outer_prg_bank = data[4];
+        self.one_screen_upper = data[5] != 0;
+        self.prg_count = data[6];
+        self.chr_count = data[7];
+        self.is_chr_ram = data[8] != 0;
+        self.prg_ram_count = data[9];
+    }
+}
@@ -1,7 +1,10 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::Device;
 
+/// CNROM, the boards have bus conflicts, unless the NES 2.0 submapper is 1
 pub struct Mapper3 {
+    submapper_id: u8,
+
     has_32kb_prg_rom: bool,
 
     chr_bank: u8,
@@ -12,8 +15,9 @@ pub struct Mapper3 {
 }
 
 impl Mapper3 {
-    pub fn new() -> Self {
+    pub fn new(submapper_id: u8) -> Self {
         Self {
+            submapper_id,
             has_32kb_prg_rom: false,
             chr_bank: 0,
             chr_count: 0,
@@ -22,9 +26,9 @@ impl Mapper3 {
     }
 
     fn map_ppu(&self, address: u16) -> MappingResult {
-        let bank = self.chr_bank % self.chr_count;
+        let bank = wrap_bank(self.chr_bank as usize, self.chr_count as usize);
 
-        let start_of_bank = 0x2000 * bank as usize;
+        let start_of_bank = 0x2000 * bank;
 
         MappingResult::Allowed(start_of_bank + (address & 0x1FFF) as usize)
     }
@@ -103,6 +107,10 @@ impl Mapper for Mapper3 {
         }
     }
 
+    fn has_bus_conflicts(&self) -> bool {
+        self.submapper_id != 1
+    }
+
     fn save_state_size(&self) -> usize {
         4
     }
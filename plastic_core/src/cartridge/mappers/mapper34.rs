@@ -0,0 +1,196 @@
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
+use crate::common::Device;
+
+/// The two boards sharing mapper 34, they have nothing in common except the
+/// 32KB PRG bank switching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Board {
+    /// registers at $8000-$FFFF, fixed 8KB CHR and no PRG RAM
+    Bnrom,
+    /// registers at $7FFD-$7FFF on top of the 8KB PRG RAM, two 4KB CHR banks
+    Nina001,
+}
+
+/// BNROM and NINA-001, the board is selected by the NES 2.0 submapper (1: NINA-001,
+/// 2: BNROM), otherwise NINA-001 is the only one with more than 8KB of CHR ROM
+pub struct Mapper34 {
+    submapper_id: u8,
+    board: Board,
+
+    /// BNROM ($8000-$FFFF), NINA-001 ($7FFD)
+    /// 7  bit  0
+    /// ---- ----
+    /// PPPP PPPP
+    /// |||| ||||
+    /// ++++-++++- Select 32 KB PRG ROM bank for CPU $8000-$FFFF
+    ///            (NINA-001 only has the lowest bit)
+    prg_bank: u8,
+
+    /// NINA-001 ($7FFE, $7FFF)
+    /// 7  bit  0
+    /// ---- ----
+    /// xxxx CCCC
+    ///      ||||
+    ///      ++++- Select 4 KB CHR ROM bank for PPU $0000-$0FFF ($7FFE)
+    ///            or $1000-$1FFF ($7FFF)
+    chr_banks: [u8; 2],
+
+    /// in 32kb units
+    prg_count: u8,
+    /// in 4kb units
+    chr_count: u8,
+
+    is_chr_ram: bool,
+}
+
+impl Mapper34 {
+    pub fn new(submapper_id: u8) -> Self {
+        Self {
+            submapper_id,
+            board: Board::Bnrom,
+            prg_bank: 0,
+            chr_banks: [0, 1],
+            prg_count: 0,
+            chr_count: 0,
+            is_chr_ram: false,
+        }
+    }
+
+    fn map_ppu(&self, address: u16) -> MappingResult {
+        match self.board {
+            Board::Bnrom => MappingResult::Allowed(address as usize),
+            Board::Nina001 => {
+                let bank = wrap_bank(
+                    self.chr_banks[(address >> 12) as usize & 1] as usize,
+                    self.chr_count as usize,
+                );
+
+                let start_of_bank = 0x1000 * bank;
+
+                MappingResult::Allowed(start_of_bank + (address & 0xFFF) as usize)
+            }
+        }
+    }
+}
+
+impl Mapper for Mapper34 {
+    fn init(&mut self, prg_count: u8, is_chr_ram: bool, chr_count: u8, _sram_count: u8) {
+        // even and positive
+        assert!(prg_count.is_multiple_of(2) && prg_count > 0);
+
+        self.board = match self.submapper_id {
+            1 => Board::Nina001,
+            2 => Board::Bnrom,
+            _ if !is_chr_ram && chr_count > 1 => Board::Nina001,
+            _ => Board::Bnrom,
+        };
+        self.prg_count = prg_count / 2;
+        self.chr_count = chr_count.saturating_mul(2);
+        self.is_chr_ram = is_chr_ram;
+    }
+
+    fn map_read(&self, address: u16, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => match self.board {
+                    Board::Bnrom => MappingResult::Denied,
+                    Board::Nina001 => MappingResult::Allowed((address & 0x1FFF) as usize),
+                },
+                0x8000..=0xFFFF => {
+                    let bank = wrap_bank(self.prg_bank as usize, self.prg_count as usize);
+
+                    let start_of_bank = 0x8000 * bank;
+
+                    MappingResult::Allowed(start_of_bank + (address & 0x7FFF) as usize)
+                }
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if address < 0x2000 {
+                    self.map_ppu(address)
+                } else {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    fn map_write(&mut self, address: u16, data: u8, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match (self.board, address) {
+                (Board::Bnrom, 0x8000..=0xFFFF) => {
+                    self.prg_bank = data;
+
+                    MappingResult::Denied
+                }
+                (Board::Nina001, 0x6000..=0x7FFF) => {
+                    // the registers don't disable the RAM, the byte is written to both
+                    match address {
+                        0x7FFD => self.prg_bank = data & 1,
+                        0x7FFE => self.chr_banks[0] = data & 0xF,
+                        0x7FFF => self.chr_banks[1] = data & 0xF,
+                        _ => {}
+                    }
+
+                    MappingResult::Allowed((address & 0x1FFF) as usize)
+                }
+                (_, 0x4020..=0xFFFF) => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if self.is_chr_ram && address <= 0x1FFF {
+                    self.map_ppu(address)
+                } else {
+                    MappingResult::Denied
+                }
+            }
+        }
+    }
+
+    fn has_register_at(&self, address: u16) -> bool {
+        match self.board {
+            Board::Bnrom => address >= 0x8000,
+            Board::Nina001 => (0x7FFD..=0x7FFF).contains(&address),
+        }
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.board == Board::Bnrom
+    }
+
+    fn prg_bank_size(&self) -> u16 {
+        0x8000
+    }
+
+    fn save_state_size(&self) -> usize {
+        8
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![
+            self.submapper_id,
+            (self.board == Board::Nina001) as u8,
+            self.prg_bank,
+            self.chr_banks[0],
+            self.chr_banks[1],
+            self.prg_count,
+            self.chr_count,
+            self.is_chr_ram as u8,
+        ]
+    }
+
+    fn load_state(&mut self, data: Vec<u8>) {
+        self.submapper_id = data[0];
+        self.board = if data[1] != 0 {
+            Board::Nina001
+        } else {
+            Board::Bnrom
+        };
+        self.prg_bank = data[2];
+        self.chr_banks = [data[3], data[4]];
+        self.prg_count = data[5];
+        self.chr_count = data[6];
+        self.is_chr_ram = data[7] != 0;
+    }
+}
@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MapperInvariant, MappingResult};
 use crate::common::{Device, MirroringMode};
 use serde::{Deserialize, Serialize};
 use std::cell::Cell;
@@ -132,6 +132,15 @@ pub struct Mapper4 {
 
     /// is PRG ram present?
     has_prg_ram: bool,
+
+    /// the start of the bank in each 1kb slot of the CHR, computed when the banks
+    /// change, as the PPU reads the CHR all the time
+    #[serde(skip)]
+    chr_slot_offsets: [usize; 8],
+
+    /// the start of the bank in each 8kb slot of the PRG ROM
+    #[serde(skip)]
+    prg_slot_offsets: [usize; 4],
 }
 
 impl Mapper4 {
@@ -162,6 +171,54 @@ impl Mapper4 {
             prg_count: 0,
             last_pattern_table: Cell::new(false),
             has_prg_ram: false,
+            chr_slot_offsets: [0; 8],
+            prg_slot_offsets: [0; 4],
+        }
+    }
+
+    fn update_bank_offsets(&mut self) {
+        for (slot, offset) in self.chr_slot_offsets.iter_mut().enumerate() {
+            let address = slot * 0x400;
+            let is_2k = (address & 0x1000 == 0) ^ self.chr_bank_2k_1000;
+
+            let (bank, mask) = if is_2k {
+                let bank = if address & 0x0800 == 0 {
+                    self.chr_bank_r0
+                } else {
+                    self.chr_bank_r1
+                };
+                (bank, 0x7FF)
+            } else {
+                let bank = match (address >> 10) & 0b11 {
+                    0 => self.chr_bank_r2,
+                    1 => self.chr_bank_r3,
+                    2 => self.chr_bank_r4,
+                    3 => self.chr_bank_r5,
+                    _ => unreachable!(),
+                };
+                (bank, 0x3FF)
+            };
+
+            // the second half of 2kb banks, the bank is wrapped before adding it
+            // the same way the whole address was
+            *offset = wrap_bank(bank as usize, self.chr_count as usize) * 0x400
+                + (address & mask & !0x3FF);
+        }
+
+        let second_last = self.prg_count.wrapping_sub(2);
+        let (bank_8000, bank_c000) = if self.prg_rom_bank_fix_8000 {
+            (second_last, self.prg_bank_8000_c000)
+        } else {
+            (self.prg_bank_8000_c000, second_last)
+        };
+        let banks = [
+            bank_8000,
+            self.prg_bank_a000,
+            bank_c000,
+            self.prg_count.wrapping_sub(1),
+        ];
+        for (offset, bank) in self.prg_slot_offsets.iter_mut().zip(banks) {
+            *offset = wrap_bank(bank as usize, self.prg_count as usize) * 0x2000;
         }
     }
 
@@ -191,31 +248,9 @@ impl Mapper4 {
     fn map_ppu(&self, address: u16) -> MappingResult {
         self.handle_irq_counter(address);
 
-        let is_2k = (address & 0x1000 == 0) ^ self.chr_bank_2k_1000;
+        let slot = (address >> 10) as usize & 0b111;
 
-        let mut bank = if is_2k {
-            if address & 0x0800 == 0 {
-                self.chr_bank_r0
-            } else {
-                self.chr_bank_r1
-            }
-        } else {
-            match (address >> 10) & 0b11 {
-                0 => self.chr_bank_r2,
-                1 => self.chr_bank_r3,
-                2 => self.chr_bank_r4,
-                3 => self.chr_bank_r5,
-                _ => unreachable!(),
-            }
-        } as usize;
-
-        bank %= self.chr_count as usize;
-
-        let mask = if is_2k { 0x7FF } else { 0x3FF };
-
-        let start_of_bank = bank * 0x400;
-
-        MappingResult::Allowed(start_of_bank + (address & mask) as usize)
+        MappingResult::Allowed(self.chr_slot_offsets[slot] + (address & 0x3FF) as usize)
     }
 }
 
@@ -227,52 +262,30 @@ impl Mapper for Mapper4 {
         self.is_chr_ram = is_chr_ram;
 
         self.has_prg_ram = sram_count != 0;
+
+        self.update_bank_offsets();
     }
 
     fn map_read(&self, address: u16, device: Device) -> MappingResult {
         match device {
-            Device::Cpu => {
-                match address {
-                    0x6000..=0x7FFF => {
-                        if self.prg_ram_enabled && self.has_prg_ram {
-                            MappingResult::Allowed(address as usize & 0x1FFF)
-                        } else {
-                            MappingResult::Denied
-                        }
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => {
+                    if self.prg_ram_enabled && self.has_prg_ram {
+                        MappingResult::Allowed(address as usize & 0x1FFF)
+                    } else {
+                        MappingResult::Denied
                     }
-                    0x8000..=0xFFFF => {
-                        let mut bank = match address {
-                            0x8000..=0x9FFF => {
-                                if self.prg_rom_bank_fix_8000 {
-                                    // second to last
-                                    self.prg_count - 2
-                                } else {
-                                    self.prg_bank_8000_c000
-                                }
-                            }
-                            0xA000..=0xBFFF => self.prg_bank_a000,
-                            0xC000..=0xDFFF => {
-                                if !self.prg_rom_bank_fix_8000 {
-                                    // second to last
-                                    self.prg_count - 2
-                                } else {
-                                    self.prg_bank_8000_c000
-                                }
-                            }
-                            0xE000..=0xFFFF => self.prg_count - 1,
-                            _ => unreachable!(),
-                        } as usize;
-
-                        bank %= self.prg_count as usize;
-
-                        let start_of_bank = bank * 0x2000;
+                }
+                0x8000..=0xFFFF => {
+                    let slot = (address >> 13) as usize & 0b11;
 
-                        MappingResult::Allowed(start_of_bank + (address & 0x1FFF) as usize)
-                    }
-                    0x4020..=0x5FFF => MappingResult::Denied,
-                    _ => unreachable!(),
+                    MappingResult::Allowed(
+                        self.prg_slot_offsets[slot] + (address & 0x1FFF) as usize,
+                    )
                 }
-            }
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
             Device::Ppu => {
                 if address < 0x2000 {
                     self.map_ppu(address)
@@ -316,6 +329,7 @@ impl Mapper for Mapper4 {
                                         _ => unreachable!(),
                                     }
                                 }
+                                self.update_bank_offsets();
                             }
                             0xA000..=0xBFFF => {
                                 if address & 1 == 0 {
@@ -368,6 +382,10 @@ impl Mapper for Mapper4 {
         }
     }
 
+    fn prg_bank_size(&self) -> u16 {
+        0x2000
+    }
+
     fn is_hardwired_mirrored(&self) -> bool {
         false
     }
@@ -397,6 +415,25 @@ impl Mapper for Mapper4 {
         bincode::serialized_size(self).unwrap() as usize
     }
 
+    fn expected_invariants(&self) -> Vec<MapperInvariant> {
+        let second_last_address = if self.prg_rom_bank_fix_8000 {
+            0x8000
+        } else {
+            0xC000
+        };
+
+        vec![
+            MapperInvariant::PrgRomFixed {
+                address: second_last_address,
+                offset: (self.prg_count as usize - 2) * 0x2000,
+            },
+            MapperInvariant::PrgRomFixed {
+                address: 0xE000,
+                offset: (self.prg_count as usize - 1) * 0x2000,
+            },
+        ]
+    }
+
     fn save_state(&self) -> Vec<u8> {
         bincode::serialize(self).unwrap()
     }
@@ -405,5 +442,6 @@ impl Mapper for Mapper4 {
         let state = bincode::deserialize(&data).unwrap();
 
         let _ = std::mem::replace(self, state);
+        self.update_bank_offsets();
     }
 }
@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::Device;
 
 pub struct Mapper66 {
@@ -41,9 +41,9 @@ impl Mapper66 {
     }
 
     fn map_ppu(&self, address: u16) -> MappingResult {
-        let bank = self.chr_bank % self.chr_count;
+        let bank = wrap_bank(self.chr_bank as usize, self.chr_count as usize);
 
-        let start_of_bank = 0x2000 * bank as usize;
+        let start_of_bank = 0x2000 * bank;
 
         MappingResult::Allowed(start_of_bank + (address & 0x1FFF) as usize)
     }
@@ -64,9 +64,9 @@ impl Mapper for Mapper66 {
             Device::Cpu => match address {
                 0x6000..=0x7FFF => MappingResult::Denied,
                 0x8000..=0xFFFF => {
-                    let bank = self.prg_bank % self.prg_count;
+                    let bank = wrap_bank(self.prg_bank as usize, self.prg_count as usize);
 
-                    let start_of_bank = 0x8000 * bank as usize;
+                    let start_of_bank = 0x8000 * bank;
 
                     MappingResult::Allowed(start_of_bank + (address & 0x7FFF) as usize)
                 }
@@ -106,6 +106,10 @@ impl Mapper for Mapper66 {
         }
     }
 
+    fn prg_bank_size(&self) -> u16 {
+        0x8000
+    }
+
     fn save_state_size(&self) -> usize {
         5
     }
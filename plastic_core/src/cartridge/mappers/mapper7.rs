@@ -1,7 +1,11 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::{Device, MirroringMode};
 
+/// AxROM, the NES 2.0 submapper tells if the board has bus conflicts (2, AMROM)
+/// or not (1, ANROM and AOROM), otherwise they are not emulated
 pub struct Mapper7 {
+    submapper_id: u8,
+
     /// select the 32KB bank
     prg_bank: u8,
 
@@ -18,8 +22,9 @@ pub struct Mapper7 {
 }
 
 impl Mapper7 {
-    pub fn new() -> Self {
+    pub fn new(submapper_id: u8) -> Self {
         Self {
+            submapper_id,
             prg_bank: 0,
             prg_count: 0,
             is_mirroring_screen_high_bank: false,
@@ -43,9 +48,9 @@ impl Mapper for Mapper7 {
                 match address {
                     0x6000..=0x7FFF => MappingResult::Denied,
                     0x8000..=0xFFFF => {
-                        let bank = self.prg_bank % self.prg_count;
+                        let bank = wrap_bank(self.prg_bank as usize, self.prg_count as usize);
 
-                        let start_of_bank = 0x8000 * bank as usize;
+                        let start_of_bank = 0x8000 * bank;
 
                         // add the offset
                         MappingResult::Allowed(start_of_bank + (address & 0x7FFF) as usize)
@@ -90,10 +95,23 @@ impl Mapper for Mapper7 {
         }
     }
 
+    fn has_bus_conflicts(&self) -> bool {
+        self.submapper_id == 2
+    }
+
+    fn prg_bank_size(&self) -> u16 {
+        0x8000
+    }
+
     fn is_hardwired_mirrored(&self) -> bool {
         false
     }
 
+    /// AxROM boards only have one-screen mirroring, which the games rely on to scroll
+    fn four_screen_overrides_mirroring(&self) -> bool {
+        false
+    }
+
     fn nametable_mirroring(&self) -> MirroringMode {
         if self.is_mirroring_screen_high_bank {
             MirroringMode::SingleScreenHighBank
@@ -0,0 +1,107 @@
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
+use crate::common::Device;
+
+/// Jaleco/Konami discrete boards, the PRG ROM is fixed and the CHR bank is
+/// selected through $6000-$7FFF
+pub struct Mapper87 {
+    /// in 8kb units
+    chr_count: u8,
+
+    /// ($6000-$7FFF)
+    /// 7  bit  0
+    /// ---- ----
+    /// xxxx xxLH
+    ///        ||
+    ///        ++- Select 8 KB CHR ROM bank for PPU $0000-$1FFF,
+    ///            the bits are swapped: the high bit first, then the low bit
+    chr_bank: u8,
+
+    /// in 16kb units, 16KB of PRG is mirrored at $C000-$FFFF
+    prg_count: u8,
+}
+
+impl Mapper87 {
+    pub fn new() -> Self {
+        Self {
+            chr_count: 0,
+            chr_bank: 0,
+            prg_count: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper87 {
+    fn init(&mut self, prg_count: u8, _is_chr_ram: bool, chr_count: u8, _sram_count: u8) {
+        // 16KB or 32KB
+        assert!(prg_count == 1 || prg_count == 2);
+
+        self.prg_count = prg_count;
+        self.chr_count = chr_count;
+    }
+
+    fn map_read(&self, address: u16, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => MappingResult::Denied,
+                0x8000..=0xFFFF => {
+                    let bank = wrap_bank(
+                        ((address - 0x8000) / 0x4000) as usize,
+                        self.prg_count as usize,
+                    );
+
+                    let start_of_bank = 0x4000 * bank;
+
+                    MappingResult::Allowed(start_of_bank + (address & 0x3FFF) as usize)
+                }
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            Device::Ppu => {
+                if address < 0x2000 {
+                    let bank = wrap_bank(self.chr_bank as usize, self.chr_count as usize);
+
+                    let start_of_bank = 0x2000 * bank;
+
+                    MappingResult::Allowed(start_of_bank + (address & 0x1FFF) as usize)
+                } else {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    fn map_write(&mut self, address: u16, data: u8, device: Device) -> MappingResult {
+        match device {
+            Device::Cpu => match address {
+                0x6000..=0x7FFF => {
+                    self.chr_bank = (data & 1) << 1 | (data >> 1) & 1;
+
+                    MappingResult::Denied
+                }
+                0x8000..=0xFFFF => MappingResult::Denied,
+                0x4020..=0x5FFF => MappingResult::Denied,
+                _ => unreachable!(),
+            },
+            // CHR ROM only
+            Device::Ppu => MappingResult::Denied,
+        }
+    }
+
+    fn has_register_at(&self, address: u16) -> bool {
+        (0x6000..=0x7FFF).contains(&address)
+    }
+
+    fn save_state_size(&self) -> usize {
+        3
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.chr_count, self.chr_bank, self.prg_count]
+    }
+
+    fn load_state(&mut self, data: Vec<u8>) {
+        self.chr_count = data[0];
+        self.chr_bank = data[1];
+        self.prg_count = data[2];
+    }
+}
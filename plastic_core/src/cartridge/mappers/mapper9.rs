@@ -1,4 +1,4 @@
-use super::super::mapper::{Mapper, MappingResult};
+use super::super::mapper::{wrap_bank, Mapper, MappingResult};
 use crate::common::{Device, MirroringMode};
 use serde::{Deserialize, Serialize};
 use std::cell::Cell;
@@ -98,27 +98,12 @@ impl Mapper9 {
 
     fn map_ppu(&self, address: u16) -> MappingResult {
         let mut bank = if address & 0x1000 == 0 {
-            // set latch 0
-            if address == 0x0FD8 {
-                self.latch_0.set(0xFD);
-            } else if address == 0x0FE8 {
-                self.latch_0.set(0xFE);
-            }
-
             match self.latch_0.get() {
                 0xFD => self.chr_fd_0000_bank,
                 0xFE => self.chr_fe_0000_bank,
                 _ => unreachable!(),
             }
         } else {
-            // set latch 1
-            if address & 0x8 != 0 {
-                let middle_byte = (address >> 4) & 0xFF;
-                if middle_byte == 0xFD || middle_byte == 0xFE {
-                    self.latch_1.set(middle_byte as u8);
-                }
-            }
-
             match self.latch_1.get() {
                 0xFD => self.chr_fd_1000_bank,
                 0xFE => self.chr_fe_1000_bank,
@@ -126,7 +111,26 @@ impl Mapper9 {
             }
         } as usize;
 
-        bank %= self.chr_count as usize;
+        // the latches are set after the read, so the byte that triggers it
+        // still comes from the previous bank
+        if address & 0x1000 == 0 {
+            // set latch 0, only at the exact addresses
+            if address == 0x0FD8 {
+                self.latch_0.set(0xFD);
+            } else if address == 0x0FE8 {
+                self.latch_0.set(0xFE);
+            }
+        } else {
+            // set latch 1, in the ranges `$1FD8-$1FDF` and `$1FE8-$1FEF`
+            if address & 0x8 != 0 {
+                let middle_byte = (address >> 4) & 0xFF;
+                if middle_byte == 0xFD || middle_byte == 0xFE {
+                    self.latch_1.set(middle_byte as u8);
+                }
+            }
+        }
+
+        bank = wrap_bank(bank, self.chr_count as usize);
 
         let start_of_bank = bank * 0x1000;
 
@@ -170,7 +174,7 @@ impl Mapper for Mapper9 {
                         _ => unreachable!(),
                     } as usize;
 
-                    bank %= self.prg_count as usize;
+                    bank = wrap_bank(bank, self.prg_count as usize);
 
                     let start_of_bank = bank * 0x2000;
 
@@ -226,6 +230,10 @@ impl Mapper for Mapper9 {
         }
     }
 
+    fn prg_bank_size(&self) -> u16 {
+        0x2000
+    }
+
     fn is_hardwired_mirrored(&self) -> bool {
         false
     }
@@ -238,6 +246,10 @@ impl Mapper for Mapper9 {
         }
     }
 
+    fn has_register_at(&self, address: u16) -> bool {
+        address >= 0xA000
+    }
+
     fn save_state_size(&self) -> usize {
         bincode::serialized_size(self).unwrap() as usize
     }
@@ -10,7 +10,13 @@ mod mapper10;
 mod mapper11;
 mod mapper12;
 
+mod mapper28;
+mod mapper34;
+
 mod mapper66;
+mod mapper87;
+
+mod mapper105;
 
 mod tests;
 
@@ -26,4 +32,54 @@ pub use mapper10::Mapper10;
 pub use mapper11::Mapper11;
 pub use mapper12::Mapper12;
 
+pub use mapper28::Mapper28;
+pub use mapper34::Mapper34;
+
 pub use mapper66::Mapper66;
+pub use mapper87::Mapper87;
+
+pub use mapper105::Mapper105;
+
+/// The common name of the board or chip of the mapper `id`, for the well known mappers,
+/// used in error messages for mappers that are not implemented
+pub fn mapper_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0 => "NROM",
+        1 => "MMC1",
+        2 => "UxROM",
+        3 => "CNROM",
+        4 => "MMC3",
+        5 => "MMC5",
+        7 => "AxROM",
+        9 => "MMC2",
+        10 => "MMC4",
+        11 => "Color Dreams",
+        12 => "MMC3 variant",
+        13 => "CPROM",
+        16 => "Bandai FCG",
+        19 => "Namco 163",
+        21 | 23 | 25 => "VRC4",
+        22 => "VRC2",
+        24 | 26 => "VRC6",
+        28 => "Action 53",
+        30 => "UNROM 512",
+        34 => "BNROM/NINA-001",
+        64 => "RAMBO-1",
+        66 => "GxROM",
+        69 => "Sunsoft FME-7",
+        71 => "Camerica",
+        73 => "VRC3",
+        75 => "VRC1",
+        79 => "NINA-03/NINA-06",
+        85 => "VRC7",
+        87 => "Jaleco/Konami discrete",
+        105 => "NES-EVENT",
+        118 => "TxSROM",
+        119 => "TQROM",
+        180 => "UNROM (AND logic)",
+        206 => "DxROM",
+        210 => "Namco 175/340",
+        232 => "Camerica Quattro",
+        _ => return None,
+    })
+}
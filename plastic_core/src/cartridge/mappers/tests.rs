@@ -2,16 +2,21 @@
 
 #[cfg(test)]
 mod mappers_tests {
-    use crate::tests::{NesTester, TestError};
+    use crate::display::{COLORS, TV_WIDTH};
+    use crate::tests::{rom_from_prg_chr, NesTester, TestError};
 
     /// the return code is the position within the 4 details result code
     /// WRAM, PRG ROM, IRQ, and CHR ROM/RAM.
     fn run_holy_mapperel_test(filename: &str, mapper_id: u8) -> Result<(), TestError> {
         let mut nes = NesTester::new(filename)?;
+        nes.nes_mut().set_mapper_audit(true);
 
         // cannot use until infinite loop :(
         nes.clock_until_pixel_appears(194, 65, 0x38);
 
+        // the bank mapping stays consistent while switching all the banks
+        assert_eq!(nes.nes_mut().take_diagnostics(), vec![]);
+
         let mut result_mapper_id = 0;
 
         for i in 0x2082..=0x2084 {
@@ -181,6 +186,162 @@ mod mappers_tests {
         )
     }
 
+    #[test]
+    fn mapper3_chr_bank_selected_by_program() {
+        let prg = [
+            0xA9, 0x02, // LDA #$02
+            0x8D, 0x08, 0x80, // STA $8008
+            0x4C, 0x05, 0x80, // JMP $8005
+            0xFF, // the ROM byte at $8008, so the write has no conflict
+        ];
+        let mut rom = rom_from_prg_chr(&prg, &[], 0x30);
+        // 4 CHR banks, the first tile of each one is filled with its index
+        rom[5] = 4;
+        rom.resize(rom.len() + 0x6000, 0);
+        for bank in 0..4 {
+            let start = 16 + 0x4000 + bank * 0x2000;
+            rom[start..start + 16].fill(bank as u8);
+        }
+
+        let mut nes = NesTester::from_rom(&rom);
+        assert_eq!(nes.ppu_read_address(0x0000), 0);
+
+        nes.clock_until_infinite_loop();
+        for address in 0x0000..0x0010 {
+            assert_eq!(nes.ppu_read_address(address), 2);
+        }
+    }
+
+    #[test]
+    fn mapper7_program_switches_prg_bank() {
+        // both 32KB banks set the backdrop color and enable rendering after
+        // switching to bank 1, each with a different color
+        let program = |color: u8| {
+            vec![
+                0xA9, 0x3F, // LDA #$3F
+                0x8D, 0x06, 0x20, // STA $2006
+                0xA9, 0x00, // LDA #$00
+                0x8D, 0x06, 0x20, // STA $2006
+                0xA9, 0x01, // LDA #$01
+                0x8D, 0x1C, 0x80, // STA $801C
+                0xA9, color, // LDA #color
+                0x8D, 0x07, 0x20, // STA $2007
+                0xA9, 0x0A, // LDA #$0A
+                0x8D, 0x01, 0x20, // STA $2001
+                0x4C, 0x19, 0x80, // JMP $8019
+                0xFF, // the ROM byte at $801C
+            ]
+        };
+
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 4, 0, 0x70, 0];
+        rom.resize(16, 0);
+        for color in [0x2A, 0x16] {
+            let mut bank = vec![0; 0x8000];
+            let code = program(color);
+            bank[..code.len()].copy_from_slice(&code);
+            // reset vector
+            bank[0x7FFD] = 0x80;
+            rom.extend_from_slice(&bank);
+        }
+
+        let mut nes = NesTester::from_rom(&rom);
+        for _ in 0..3 {
+            nes.clock_for_frame();
+        }
+
+        let color = COLORS[0x16];
+        let index = (100 * TV_WIDTH + 100) * 3;
+        assert_eq!(
+            nes.pixel_buffer()[index..index + 3],
+            [color.r, color.g, color.b]
+        );
+    }
+
+    /// a ROM for `mapper` with 2 32KB PRG banks running `program` followed by the
+    /// bank index, and 4 CHR banks, the first tile of each one is filled with its index
+    fn program_rom(mapper: u8, program: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 4, 4, mapper << 4, mapper & 0xF0];
+        rom.resize(16, 0);
+        for index in 0..2 {
+            let mut bank = vec![0; 0x8000];
+            bank[..program.len()].copy_from_slice(program);
+            bank[program.len()] = index;
+            // reset vector
+            bank[0x7FFD] = 0x80;
+            rom.extend_from_slice(&bank);
+        }
+        for index in 0..4 {
+            let mut bank = vec![0; 0x2000];
+            bank[..16].fill(index);
+            rom.extend_from_slice(&bank);
+        }
+        rom
+    }
+
+    #[test]
+    fn mapper66_program_switches_prg_bank() {
+        let mut nes = NesTester::from_rom(&program_rom(
+            66,
+            &[
+                0xA9, 0x10, // LDA #$10
+                0x8D, 0x00, 0x80, // STA $8000
+                0xAD, 0x0E, 0x80, // LDA $800E
+                0x85, 0x00, // STA $00
+                0x4C, 0x0A, 0x80, // JMP $800A
+                0xEA, // NOP, padding so the bank index is at $800E
+            ],
+        ));
+        assert_eq!(nes.cpu_read_address(0x800E), 0);
+
+        nes.clock_until_infinite_loop();
+        assert_eq!(nes.cpu_read_address(0x800E), 1);
+        assert_eq!(nes.cpu_read_address(0x0000), 1);
+    }
+
+    #[test]
+    fn mapper66_chr_bank_selected_by_program() {
+        let mut nes = NesTester::from_rom(&program_rom(
+            66,
+            &[
+                0xA9, 0x03, // LDA #$03
+                0x8D, 0x00, 0x80, // STA $8000
+                0x4C, 0x05, 0x80, // JMP $8005
+            ],
+        ));
+        assert_eq!(nes.ppu_read_address(0x0000), 0);
+
+        nes.clock_until_infinite_loop();
+        for address in 0x0000..0x0010 {
+            assert_eq!(nes.ppu_read_address(address), 3);
+        }
+        // the PRG bits were 0, so the bank index after the program is still 0
+        assert_eq!(nes.cpu_read_address(0x8008), 0);
+    }
+
+    #[test]
+    fn mapper11_program_switches_prg_and_chr_banks() {
+        let mut nes = NesTester::from_rom(&program_rom(
+            11,
+            &[
+                0xA9, 0x31, // LDA #$31
+                0x8D, 0x0D, 0x80, // STA $800D
+                0xAD, 0x0E, 0x80, // LDA $800E
+                0x85, 0x00, // STA $00
+                0x4C, 0x0A, 0x80, // JMP $800A
+                0x31, // the ROM byte at $800D, so the write has no conflict
+            ],
+        ));
+        assert_eq!(nes.cpu_read_address(0x800E), 0);
+        assert_eq!(nes.ppu_read_address(0x0000), 0);
+
+        nes.clock_until_infinite_loop();
+        assert_eq!(nes.cpu_read_address(0x800E), 1);
+        assert_eq!(nes.cpu_read_address(0x0000), 1);
+        for address in 0x0000..0x0010 {
+            assert_eq!(nes.ppu_read_address(address), 3);
+        }
+    }
+
     #[test]
     fn holy_mapperel_m4_p128k_test() -> Result<(), TestError> {
         run_holy_mapperel_test(
@@ -269,8 +430,7 @@ mod mappers_tests {
         )
     }
 
-    // FIXME: this test is still failing
-    // #[test]
+    #[test]
     fn holy_mapperel_m28_p512k_test() -> Result<(), TestError> {
         run_holy_mapperel_test(
             "../test_roms/holy-mapperel-bin-0.02/testroms/M28_P512K.nes",
@@ -278,8 +438,7 @@ mod mappers_tests {
         )
     }
 
-    // FIXME: this test is still failing
-    // #[test]
+    #[test]
     fn holy_mapperel_m28_p512k_cr32k_test() -> Result<(), TestError> {
         run_holy_mapperel_test(
             "../test_roms/holy-mapperel-bin-0.02/testroms/M28_P512K_CR32K.nes",
@@ -287,8 +446,7 @@ mod mappers_tests {
         )
     }
 
-    // FIXME: this test is still failing
-    // #[test]
+    #[test]
     fn holy_mapperel_m34_p128k_cr8k_h_test() -> Result<(), TestError> {
         run_holy_mapperel_test(
             "../test_roms/holy-mapperel-bin-0.02/testroms/M34_P128K_CR8K_H.nes",
@@ -296,8 +454,7 @@ mod mappers_tests {
         )
     }
 
-    // FIXME: this test is still failing
-    // #[test]
+    #[test]
     fn holy_mapperel_m34_p128k_h_test() -> Result<(), TestError> {
         run_holy_mapperel_test(
             "../test_roms/holy-mapperel-bin-0.02/testroms/M34_P128K_H.nes",
@@ -367,3 +524,748 @@ mod mappers_tests {
         )
     }
 }
+
+#[cfg(test)]
+mod bank_switching_tests {
+    use super::super::super::Cartridge;
+    use crate::common::{Bus, Device, MirroringMode, MirroringProvider};
+
+    /// a ROM for `mapper` with `prg_32k_count` PRG banks of 32KB and `chr_count` CHR banks
+    /// of 8KB, the first byte of each bank is its index and the rest is `0xFF`
+    fn discrete_rom(mapper: u8, prg_32k_count: usize, chr_count: usize) -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A];
+        data.extend_from_slice(&[prg_32k_count as u8 * 2, chr_count as u8]);
+        data.extend_from_slice(&[mapper << 4, mapper & 0xF0]);
+        data.resize(16, 0);
+
+        for bank in 0..prg_32k_count {
+            let start = data.len();
+            data.resize(start + 0x8000, 0xFF);
+            data[start] = bank as u8;
+        }
+        for bank in 0..chr_count {
+            let start = data.len();
+            data.resize(start + 0x2000, 0xFF);
+            data[start] = bank as u8;
+        }
+
+        Cartridge::from_bytes(&data).unwrap()
+    }
+
+    /// returns the `(prg, chr)` banks selected
+    fn selected_banks(cartridge: &Cartridge) -> (u8, u8) {
+        (
+            cartridge.read(0x8000, Device::Cpu),
+            cartridge.read(0x0000, Device::Ppu),
+        )
+    }
+
+    #[test]
+    fn mapper11_registers() {
+        let mut cartridge = discrete_rom(11, 4, 16);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        // PRG in the low bits, CHR in the high nibble
+        cartridge.write(0x8001, 0x92, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (2, 9));
+    }
+
+    #[test]
+    fn mapper11_bank_wrap() {
+        let mut cartridge = discrete_rom(11, 2, 8);
+
+        cartridge.write(0x8001, 0xF3, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (3 % 2, 15 % 8));
+
+        cartridge.write(0x8001, 0xA2, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 10 % 8));
+    }
+
+    #[test]
+    fn mapper11_bus_conflicts() {
+        let mut cartridge = discrete_rom(11, 4, 16);
+        cartridge.write(0x8001, 0x31, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (1, 3));
+
+        // the ROM byte at `$8000` is `0x01` in this bank
+        cartridge.write(0x8000, 0x73, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (1, 0));
+    }
+
+    #[test]
+    fn mapper66_registers() {
+        let mut cartridge = discrete_rom(66, 4, 4);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        // PRG in bits 4-5, CHR in the low bits
+        cartridge.write(0x8001, 0x21, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (2, 1));
+
+        // no bus conflicts, the ROM byte at `$8000` is `0x02` in this bank
+        cartridge.write(0x8000, 0x13, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (1, 3));
+    }
+
+    #[test]
+    fn mapper66_bank_wrap() {
+        let mut cartridge = discrete_rom(66, 2, 2);
+
+        cartridge.write(0x8001, 0x33, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (3 % 2, 3 % 2));
+
+        cartridge.write(0x8001, 0x22, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+    }
+
+    #[test]
+    fn mapper3_registers() {
+        let mut cartridge = discrete_rom(3, 1, 4);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        cartridge.write(0x8001, 0x02, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 2));
+        // only the low 2 bits with 32KB of CHR
+        cartridge.write(0x8001, 0xF3, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 3));
+    }
+
+    #[test]
+    fn mapper3_bus_conflicts_by_submapper() {
+        let mut cartridge = discrete_rom(3, 1, 4);
+        // the ROM byte at `$8000` is `0x00`
+        cartridge.write(0x8000, 0x03, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        cartridge.set_submapper_id(2);
+        cartridge.write(0x8000, 0x03, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        cartridge.set_submapper_id(1);
+        cartridge.write(0x8000, 0x03, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 3));
+    }
+
+    #[test]
+    fn mapper7_registers() {
+        let mut cartridge = discrete_rom(7, 8, 0);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 0);
+
+        // the nametable page in bit 4 doesn't affect the PRG bank
+        cartridge.write(0x8001, 0x15, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 5);
+        assert_eq!(cartridge.read(0xFFFF, Device::Cpu), 0xFF);
+        cartridge.write(0xFFFF, 0x07, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 7);
+    }
+
+    #[test]
+    fn mapper7_bus_conflicts_by_submapper() {
+        let mut cartridge = discrete_rom(7, 8, 0);
+        // the ROM byte at `$8000` is `0x00` in this bank
+        cartridge.write(0x8000, 0x03, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 3);
+
+        cartridge.set_submapper_id(1);
+        cartridge.write(0x8000, 0x05, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 5);
+
+        cartridge.set_submapper_id(2);
+        cartridge.write(0x8000, 0x06, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 0);
+    }
+
+    /// MMC2 with 16 8KB PRG banks starting with their index, and 8 4KB CHR banks
+    /// filled with their index
+    fn mmc2_rom() -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 8, 4, 0x90, 0];
+        data.resize(16, 0);
+        for bank in 0..16 {
+            let start = data.len();
+            data.resize(start + 0x2000, 0xFF);
+            data[start] = bank;
+        }
+        for bank in 0..8 {
+            data.extend_from_slice(&[bank; 0x1000]);
+        }
+
+        Cartridge::from_bytes(&data).unwrap()
+    }
+
+    #[test]
+    fn mapper9_latches() {
+        let mut cartridge = mmc2_rom();
+        for (address, bank) in [(0xB000, 1), (0xC000, 2), (0xD000, 3), (0xE000, 4)] {
+            cartridge.write(address, bank, Device::Cpu);
+        }
+        let chr_banks = |cartridge: &Cartridge| {
+            (
+                cartridge.read(0x0000, Device::Ppu),
+                cartridge.read(0x1000, Device::Ppu),
+            )
+        };
+
+        // both latches start at `$FE`
+        assert_eq!(chr_banks(&cartridge), (2, 4));
+
+        // the read that sets the latch is still from the previous bank
+        assert_eq!(cartridge.read(0x0FD8, Device::Ppu), 2);
+        assert_eq!(chr_banks(&cartridge), (1, 4));
+        // latch 0 is set only by the exact addresses
+        cartridge.read(0x0FE9, Device::Ppu);
+        cartridge.read(0x0FEF, Device::Ppu);
+        assert_eq!(chr_banks(&cartridge), (1, 4));
+        assert_eq!(cartridge.read(0x0FE8, Device::Ppu), 1);
+        assert_eq!(chr_banks(&cartridge), (2, 4));
+
+        // latch 1 is set by the ranges `$1FD8-$1FDF` and `$1FE8-$1FEF`
+        assert_eq!(cartridge.read(0x1FDC, Device::Ppu), 4);
+        assert_eq!(chr_banks(&cartridge), (2, 3));
+        cartridge.read(0x1FE0, Device::Ppu);
+        assert_eq!(chr_banks(&cartridge), (2, 3));
+        assert_eq!(cartridge.read(0x1FEF, Device::Ppu), 3);
+        assert_eq!(chr_banks(&cartridge), (2, 4));
+    }
+
+    #[test]
+    fn mapper9_registers() {
+        let mut cartridge = mmc2_rom();
+
+        // only 4 bits of the bank, and the last 3 banks are fixed at `$A000-$FFFF`
+        cartridge.write(0xA000, 0x12, Device::Cpu);
+        let banks = [0x8000, 0xA000, 0xC000, 0xE000].map(|a| cartridge.read(a, Device::Cpu));
+        assert_eq!(banks, [2, 13, 14, 15]);
+
+        cartridge.write(0xF000, 0, Device::Cpu);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Vertical);
+        cartridge.write(0xF000, 1, Device::Cpu);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Horizontal);
+    }
+
+    #[test]
+    fn mapper34_bnrom_registers() {
+        // 8KB of CHR is BNROM
+        let mut cartridge = discrete_rom(34, 4, 1);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        cartridge.write(0x8001, 0x03, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (3, 0));
+
+        // bus conflicts, the ROM byte at `$8000` is `0x03` in this bank
+        cartridge.write(0x8000, 0x06, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (2, 0));
+
+        // no registers in the PRG RAM range
+        cartridge.write(0x7FFD, 0x01, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (2, 0));
+    }
+
+    #[test]
+    fn mapper34_nina001_registers() {
+        // more than 8KB of CHR is NINA-001
+        let mut cartridge = discrete_rom(34, 2, 8);
+
+        cartridge.write(0x7FFD, 0x01, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 1);
+
+        // 4KB CHR banks, even banks are the start of the 8KB banks in the ROM
+        cartridge.write(0x7FFE, 0x02, Device::Cpu);
+        cartridge.write(0x7FFF, 0x0C, Device::Cpu);
+        assert_eq!(cartridge.read(0x0000, Device::Ppu), 1);
+        assert_eq!(cartridge.read(0x1000, Device::Ppu), 6);
+
+        // the registers are written to the PRG RAM as well
+        assert_eq!(cartridge.read(0x7FFD, Device::Cpu), 0x01);
+        assert_eq!(cartridge.read(0x7FFE, Device::Cpu), 0x02);
+        cartridge.write(0x6000, 0x55, Device::Cpu);
+        assert_eq!(cartridge.read(0x6000, Device::Cpu), 0x55);
+
+        // no registers at $8000-$FFFF
+        cartridge.write(0x8000, 0x00, Device::Cpu);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 1);
+    }
+
+    #[test]
+    fn mapper34_submapper_selects_board() {
+        let mut cartridge = discrete_rom(34, 2, 8);
+
+        // BNROM
+        cartridge.set_submapper_id(2);
+        cartridge.write(0x8001, 0x01, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (1, 0));
+        cartridge.write(0x7FFE, 0x02, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (1, 0));
+    }
+
+    #[test]
+    fn mapper87_chr_bits_swapped() {
+        let mut cartridge = discrete_rom(87, 1, 4);
+        assert_eq!(selected_banks(&cartridge), (0, 0));
+
+        cartridge.write(0x6000, 0x01, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 2));
+        cartridge.write(0x7FFF, 0x02, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 1));
+        cartridge.write(0x6000, 0x03, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 3));
+
+        // no registers at $8000-$FFFF
+        cartridge.write(0x8000, 0x00, Device::Cpu);
+        assert_eq!(selected_banks(&cartridge), (0, 3));
+    }
+}
+
+#[cfg(test)]
+mod multicart_tests {
+    use super::super::super::Cartridge;
+    use crate::common::{
+        interconnection::CPUIrqProvider, Bus, Device, MirroringMode, MirroringProvider,
+    };
+
+    /// a ROM for `mapper` with `prg_16k_count` PRG banks of 16KB and 8KB of CHR RAM,
+    /// the first byte of each bank is its index and the rest is `0xFF`
+    fn prg_16k_rom(mapper: u8, prg_16k_count: usize) -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A];
+        data.extend_from_slice(&[prg_16k_count as u8, 0]);
+        data.extend_from_slice(&[mapper << 4, mapper & 0xF0]);
+        data.resize(16, 0);
+
+        for bank in 0..prg_16k_count {
+            let start = data.len();
+            data.resize(start + 0x4000, 0xFF);
+            data[start] = bank as u8;
+        }
+
+        Cartridge::from_bytes(&data).unwrap()
+    }
+
+    /// returns the 16KB banks at `$8000` and `$C000`
+    fn prg_banks(cartridge: &Cartridge) -> (u8, u8) {
+        (
+            cartridge.read(0x8000, Device::Cpu),
+            cartridge.read(0xC000, Device::Cpu),
+        )
+    }
+
+    fn a53_write(cartridge: &mut Cartridge, register: u8, data: u8) {
+        cartridge.write(0x5000, register, Device::Cpu);
+        cartridge.write(0x8000, data, Device::Cpu);
+    }
+
+    #[test]
+    fn mapper2_switchable_and_fixed_banks() {
+        let mut cartridge = prg_16k_rom(2, 8);
+        assert_eq!(prg_banks(&cartridge), (0, 7));
+
+        // any address in `$8000-$FFFF` selects the bank at `$8000`, `$C000` stays the last one
+        cartridge.write(0x8000, 3, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (3, 7));
+        cartridge.write(0xFFFF, 5, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (5, 7));
+
+        // the bank wraps around the size of the ROM
+        cartridge.write(0xC000, 9, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (1, 7));
+    }
+
+    #[test]
+    fn mapper2_chr_ram() {
+        let mut cartridge = prg_16k_rom(2, 8);
+
+        cartridge.write(0x0123, 0x5A, Device::Ppu);
+        cartridge.write(0x1FFF, 0xA5, Device::Ppu);
+        // switching the PRG bank doesn't affect the CHR RAM
+        cartridge.write(0x8000, 2, Device::Cpu);
+        assert_eq!(cartridge.read(0x0123, Device::Ppu), 0x5A);
+        assert_eq!(cartridge.read(0x1FFF, Device::Ppu), 0xA5);
+    }
+
+    #[test]
+    fn mapper2_bus_conflicts_by_submapper() {
+        let mut cartridge = prg_16k_rom(2, 8);
+        cartridge.write(0x8000, 3, Device::Cpu);
+        // the ROM byte at `$8000` is `3` in this bank, but there are no conflicts
+        cartridge.write(0x8000, 5, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (5, 7));
+
+        cartridge.set_submapper_id(1);
+        cartridge.write(0x8000, 3, Device::Cpu);
+        cartridge.write(0x8000, 5, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (5, 7));
+
+        cartridge.set_submapper_id(2);
+        // the rest of the bank is `0xFF`, so writing there selects the bank as is
+        cartridge.write(0x8001, 3, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (3, 7));
+        cartridge.write(0x8000, 5, Device::Cpu);
+        assert_eq!(prg_banks(&cartridge), (5 & 3, 7));
+    }
+
+    #[test]
+    fn mapper2_no_prg_ram() {
+        let mut cartridge = prg_16k_rom(2, 8);
+
+        cartridge.write(0x6000, 0x42, Device::Cpu);
+        assert_eq!(cartridge.read(0x6000, Device::Cpu), 0);
+        cartridge.write(0x7FFF, 0x42, Device::Cpu);
+        assert_eq!(cartridge.read(0x7FFF, Device::Cpu), 0);
+    }
+
+    #[test]
+    fn mapper28_power_up_last_bank() {
+        let cartridge = prg_16k_rom(28, 32);
+        assert_eq!(prg_banks(&cartridge), (30, 31));
+    }
+
+    #[test]
+    fn mapper28_32k_game_sizes() {
+        let mut cartridge = prg_16k_rom(28, 32);
+        a53_write(&mut cartridge, 0x81, 3);
+
+        // 32KB games use only the outer bank
+        a53_write(&mut cartridge, 0x80, 0x00);
+        a53_write(&mut cartridge, 0x01, 0x0F);
+        assert_eq!(prg_banks(&cartridge), (6, 7));
+
+        // 64KB games replace the low bit of the outer bank with the inner bank
+        a53_write(&mut cartridge, 0x80, 0x10);
+        a53_write(&mut cartridge, 0x01, 0x00);
+        assert_eq!(prg_banks(&cartridge), (4, 5));
+        a53_write(&mut cartridge, 0x01, 0x01);
+        assert_eq!(prg_banks(&cartridge), (6, 7));
+
+        // 256KB games, 8 banks of 32KB
+        a53_write(&mut cartridge, 0x80, 0x30);
+        a53_write(&mut cartridge, 0x81, 0x04);
+        a53_write(&mut cartridge, 0x01, 0x05);
+        assert_eq!(prg_banks(&cartridge), (10, 11));
+    }
+
+    #[test]
+    fn mapper28_16k_modes() {
+        let mut cartridge = prg_16k_rom(28, 32);
+        a53_write(&mut cartridge, 0x81, 1);
+
+        // 256KB game, switch $8000 and fix $C000 to the last bank of the outer bank
+        a53_write(&mut cartridge, 0x80, 0x3C);
+        a53_write(&mut cartridge, 0x01, 0x05);
+        assert_eq!(prg_banks(&cartridge), (5, 3));
+
+        // switch $C000 and fix $8000 to the first bank of the outer bank
+        a53_write(&mut cartridge, 0x80, 0x38);
+        a53_write(&mut cartridge, 0x01, 0x09);
+        assert_eq!(prg_banks(&cartridge), (2, 9));
+
+        // 64KB game, the inner bank only replaces the low 2 bits
+        a53_write(&mut cartridge, 0x81, 0x05);
+        a53_write(&mut cartridge, 0x80, 0x1C);
+        a53_write(&mut cartridge, 0x01, 0x0E);
+        assert_eq!(prg_banks(&cartridge), (10, 11));
+    }
+
+    #[test]
+    fn mapper28_mirroring() {
+        let mut cartridge = prg_16k_rom(28, 32);
+
+        a53_write(&mut cartridge, 0x80, 0x02);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Vertical);
+        a53_write(&mut cartridge, 0x80, 0x03);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Horizontal);
+
+        // one-screen, the page is selected by the last write
+        a53_write(&mut cartridge, 0x80, 0x00);
+        assert_eq!(
+            cartridge.mirroring_mode(),
+            MirroringMode::SingleScreenLowBank
+        );
+        a53_write(&mut cartridge, 0x00, 0x10);
+        assert_eq!(
+            cartridge.mirroring_mode(),
+            MirroringMode::SingleScreenHighBank
+        );
+        a53_write(&mut cartridge, 0x01, 0x00);
+        assert_eq!(
+            cartridge.mirroring_mode(),
+            MirroringMode::SingleScreenLowBank
+        );
+    }
+
+    /// write `data` to the MMC1 register at `address` through the shift register
+    fn mmc1_write(cartridge: &mut Cartridge, address: u16, data: u8) {
+        for bit in 0..5 {
+            cartridge.write(address, (data >> bit) & 1, Device::Cpu);
+        }
+    }
+
+    /// start the game, clearing and then setting the timer bit, the PRG ROM
+    /// is locked to the first 32KB until then
+    fn mapper105_unlock(cartridge: &mut Cartridge) {
+        mmc1_write(cartridge, 0xA000, 0x00);
+        mmc1_write(cartridge, 0xA000, 0x10);
+    }
+
+    #[test]
+    fn mapper105_locked_until_started() {
+        let mut cartridge = prg_16k_rom(105, 16);
+        assert_eq!(prg_banks(&cartridge), (0, 1));
+
+        mmc1_write(&mut cartridge, 0xA000, 0x04);
+        assert_eq!(prg_banks(&cartridge), (0, 1));
+
+        mmc1_write(&mut cartridge, 0xA000, 0x14);
+        assert_eq!(prg_banks(&cartridge), (4, 5));
+    }
+
+    #[test]
+    fn mapper105_prg_chips() {
+        let mut cartridge = prg_16k_rom(105, 16);
+        mapper105_unlock(&mut cartridge);
+
+        // the first chip, in 32KB banks
+        mmc1_write(&mut cartridge, 0xA000, 0x16);
+        assert_eq!(prg_banks(&cartridge), (6, 7));
+
+        // the second chip, with MMC1 banking
+        mmc1_write(&mut cartridge, 0xA000, 0x18);
+        mmc1_write(&mut cartridge, 0x8000, 0x0C);
+        mmc1_write(&mut cartridge, 0xE000, 0x02);
+        assert_eq!(prg_banks(&cartridge), (10, 15));
+
+        mmc1_write(&mut cartridge, 0x8000, 0x08);
+        mmc1_write(&mut cartridge, 0xE000, 0x03);
+        assert_eq!(prg_banks(&cartridge), (8, 11));
+
+        mmc1_write(&mut cartridge, 0x8000, 0x00);
+        mmc1_write(&mut cartridge, 0xE000, 0x05);
+        assert_eq!(prg_banks(&cartridge), (12, 13));
+    }
+
+    fn irq_asserted(cartridge: &Cartridge) -> bool {
+        cartridge.is_irq_change_requested() && cartridge.irq_pin_state()
+    }
+
+    #[test]
+    fn mapper105_timer_irq() {
+        let mut cartridge = prg_16k_rom(105, 16);
+        cartridge.set_dip_switches(2);
+        mapper105_unlock(&mut cartridge);
+
+        // held while the timer bit is set
+        for _ in 0..1000 {
+            cartridge.cpu_cycle();
+        }
+        assert!(!irq_asserted(&cartridge));
+        cartridge.clear_irq_request_pin();
+
+        // start the timer, `(16 + 2) * 2^25` cycles
+        mmc1_write(&mut cartridge, 0xA000, 0x00);
+        for _ in 0..(18 << 25) - 1 {
+            cartridge.cpu_cycle();
+        }
+        assert!(!irq_asserted(&cartridge));
+        cartridge.cpu_cycle();
+        assert!(irq_asserted(&cartridge));
+        cartridge.clear_irq_request_pin();
+
+        // acknowledge
+        mmc1_write(&mut cartridge, 0xA000, 0x10);
+        assert!(cartridge.is_irq_change_requested());
+        assert!(!cartridge.irq_pin_state());
+    }
+}
+
+#[cfg(test)]
+mod mirroring_tests {
+    use super::super::super::Cartridge;
+    use crate::common::{Bus, Device, MirroringMode, MirroringProvider};
+
+    /// `flags_6` bit 3, four-screen nametables
+    const FOUR_SCREEN: u8 = 0b1000;
+    /// `flags_6` bit 0
+    const VERTICAL: u8 = 0b1;
+
+    /// a ROM for `mapper` with 32KB of PRG and 8KB of CHR RAM, `flags` are the
+    /// mirroring bits of the header
+    fn rom(mapper: u8, flags: u8) -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 2, 0];
+        data.extend_from_slice(&[mapper << 4 | flags, mapper & 0xF0]);
+        data.resize(16 + 0x8000, 0);
+
+        Cartridge::from_bytes(&data).unwrap()
+    }
+
+    fn mmc1_write(cartridge: &mut Cartridge, address: u16, data: u8) {
+        for bit in 0..5 {
+            cartridge.write(address, (data >> bit) & 1, Device::Cpu);
+        }
+    }
+
+    #[test]
+    fn hardwired_mirroring() {
+        assert_eq!(rom(0, VERTICAL).mirroring_mode(), MirroringMode::Vertical);
+        assert_eq!(rom(0, 0).mirroring_mode(), MirroringMode::Horizontal);
+        assert_eq!(
+            rom(0, FOUR_SCREEN).mirroring_mode(),
+            MirroringMode::FourScreen
+        );
+        // the four-screen bit overrides the mirroring bit
+        assert_eq!(
+            rom(0, FOUR_SCREEN | VERTICAL).mirroring_mode(),
+            MirroringMode::FourScreen
+        );
+    }
+
+    #[test]
+    fn color_dreams_mirroring_from_header() {
+        for (flags, mirroring) in [
+            (VERTICAL, MirroringMode::Vertical),
+            (0, MirroringMode::Horizontal),
+        ] {
+            let mut cartridge = rom(11, flags);
+            assert_eq!(cartridge.mirroring_mode(), mirroring);
+
+            // the register only selects the banks
+            cartridge.write(0x8000, 0xFF, Device::Cpu);
+            assert_eq!(cartridge.mirroring_mode(), mirroring);
+        }
+    }
+
+    #[test]
+    fn mmc1_one_screen_modes() {
+        // the header bit is ignored
+        let mut cartridge = rom(1, VERTICAL);
+
+        for (control, mirroring) in [
+            (0, MirroringMode::SingleScreenLowBank),
+            (1, MirroringMode::SingleScreenHighBank),
+            (2, MirroringMode::Vertical),
+            (3, MirroringMode::Horizontal),
+        ] {
+            mmc1_write(&mut cartridge, 0x8000, 0x0C | control);
+            assert_eq!(cartridge.mirroring_mode(), mirroring);
+        }
+    }
+
+    #[test]
+    fn mmc3_four_screen_overrides_mapper() {
+        let mut cartridge = rom(4, 0);
+        cartridge.write(0xA000, 0x01, Device::Cpu);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Horizontal);
+        cartridge.write(0xA000, 0x00, Device::Cpu);
+        assert_eq!(cartridge.mirroring_mode(), MirroringMode::Vertical);
+
+        let mut cartridge = rom(4, FOUR_SCREEN);
+        for data in [0x00, 0x01] {
+            cartridge.write(0xA000, data, Device::Cpu);
+            assert_eq!(cartridge.mirroring_mode(), MirroringMode::FourScreen);
+        }
+    }
+
+    #[test]
+    fn axrom_ignores_four_screen() {
+        let mut cartridge = rom(7, FOUR_SCREEN);
+        assert_eq!(
+            cartridge.mirroring_mode(),
+            MirroringMode::SingleScreenLowBank
+        );
+        cartridge.write(0x8000, 0x10, Device::Cpu);
+        assert_eq!(
+            cartridge.mirroring_mode(),
+            MirroringMode::SingleScreenHighBank
+        );
+    }
+}
+
+#[cfg(test)]
+mod bank_wrap_tests {
+    use super::super::super::mapper::wrap_bank;
+    use super::super::super::Cartridge;
+    use crate::common::{Bus, Device};
+
+    #[test]
+    fn wrap_bank_is_modulo() {
+        for count in 1..=64 {
+            for bank in 0..=0x1FF {
+                assert_eq!(wrap_bank(bank, count), bank % count, "{bank} % {count}");
+            }
+        }
+    }
+
+    /// MMC3 with 48KB of PRG and 24KB of CHR, neither is a power of two, every
+    /// byte is the index of its 1KB page in the file, after the header
+    fn mmc3_non_power_of_two() -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 3, 3, 0x40, 0];
+        data.resize(16, 0);
+        data.extend((0..0x12000).map(|offset| (offset >> 10) as u8));
+
+        Cartridge::from_bytes(&data).unwrap()
+    }
+
+    #[test]
+    fn mmc3_wrapping_matches_modulo() {
+        let mut cartridge = mmc3_non_power_of_two();
+        // in 8KB and 1KB units
+        let (prg_count, chr_count) = (6, 24);
+
+        for mode in [0x00, 0x40, 0x80, 0xC0] {
+            for value in 0..=0xFFu8 {
+                let registers: Vec<u8> = (0..8u8)
+                    .map(|r| value.wrapping_add(r.wrapping_mul(37)))
+                    .collect();
+                for (r, &data) in registers.iter().enumerate() {
+                    cartridge.write(0x8000, mode | r as u8, Device::Cpu);
+                    cartridge.write(0x8001, data, Device::Cpu);
+                }
+                // R0 and R1 are stored as even numbers
+                let chr = |r: usize| registers[r] as usize & !((r < 2) as usize);
+
+                for address in (0x8000..=0xFFFFu16).step_by(0x400) {
+                    let bank = match (address >> 13) & 3 {
+                        0 if mode & 0x40 == 0 => registers[6] as usize,
+                        0 => prg_count - 2,
+                        1 => registers[7] as usize,
+                        2 if mode & 0x40 == 0 => prg_count - 2,
+                        2 => registers[6] as usize,
+                        _ => prg_count - 1,
+                    };
+                    let offset = bank % prg_count * 0x2000 + (address & 0x1FFF) as usize;
+                    assert_eq!(
+                        cartridge.read(address, Device::Cpu),
+                        (offset >> 10) as u8,
+                        "mode {mode:02X} value {value:02X} ${address:04X}"
+                    );
+                }
+
+                for address in (0..0x2000u16).step_by(0x400) {
+                    let is_2k = (address & 0x1000 == 0) ^ (mode & 0x80 != 0);
+                    let (bank, mask) = if is_2k {
+                        (chr((address as usize >> 11) & 1), 0x7FF)
+                    } else {
+                        (chr(2 + ((address as usize >> 10) & 3)), 0x3FF)
+                    };
+                    let offset = bank % chr_count * 0x400 + (address & mask) as usize;
+                    assert_eq!(
+                        cartridge.read(address | 0x3FF, Device::Ppu),
+                        ((0xC000 + offset) >> 10) as u8,
+                        "mode {mode:02X} value {value:02X} PPU ${address:04X}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mmc3_offsets_restored_with_state() {
+        let mut cartridge = mmc3_non_power_of_two();
+        cartridge.write(0x8000, 0x02, Device::Cpu);
+        cartridge.write(0x8001, 0x05, Device::Cpu);
+        let state = cartridge.mapper_state();
+        let before = cartridge.read(0x0000, Device::Ppu);
+
+        cartridge.write(0x8000, 0x82, Device::Cpu);
+        cartridge.write(0x8001, 0x07, Device::Cpu);
+        assert_ne!(cartridge.read(0x0000, Device::Ppu), before);
+
+        cartridge.restore_mapper_state(state);
+        assert_eq!(cartridge.read(0x0000, Device::Ppu), before);
+    }
+}
@@ -1,34 +1,88 @@
+mod audit;
 mod error;
+mod expansion_device;
 mod mapper;
 mod mappers;
+mod patch;
+mod rom_data;
+mod timing_mode;
 
 mod tests;
 
-pub use error::CartridgeError;
+pub use audit::MappingViolation;
 use error::SramError;
+pub use error::{CartridgeError, HeaderErrorReason, RomSection};
+pub use expansion_device::ExpansionDevice;
 use mapper::{Mapper, MappingResult};
 use mappers::{
-    Mapper0, Mapper1, Mapper10, Mapper11, Mapper12, Mapper2, Mapper3, Mapper4, Mapper66, Mapper7,
-    Mapper9,
+    Mapper0, Mapper1, Mapper10, Mapper105, Mapper11, Mapper12, Mapper2, Mapper28, Mapper3,
+    Mapper34, Mapper4, Mapper66, Mapper7, Mapper87, Mapper9,
 };
+pub use patch::apply_patch;
+pub use rom_data::RomData;
+pub use timing_mode::TimingMode;
 
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{take_json_field, JsonSavable};
 use crate::common::{
     interconnection::CPUIrqProvider,
     save_state::{Savable, SaveError},
     Bus, Device, MirroringMode, MirroringProvider,
 };
+use crate::diagnostics::{Diagnostic, Diagnostics};
 use std::{
     fs::File,
-    io::{Read, Seek, SeekFrom, Write},
-    path::Path,
+    io::{Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
+/// Size of the header some tools add before the raw save RAM data
+const SRAM_FILE_HEADER_SIZE: usize = 0x200;
+
+/// Size of the extra nametable RAM of four-screen cartridges
+const NAMETABLE_RAM_SIZE: usize = 0x800;
+
+/// The units of the ROM sizes in the header, and the bank sizes the mappers take
+const PRG_ROM_UNIT: u64 = 0x4000;
+const CHR_ROM_UNIT: u64 = 0x2000;
+
+/// The trainer is loaded at `$7000`, this offset in the PRG RAM mapped at `$6000`
+const TRAINER_PRG_RAM_OFFSET: usize = 0x1000;
+
+/// Fit the content of a save RAM file into `sram_size` bytes, returns the data
+/// and the number of bytes dropped from its end.
+///
+/// The file is the raw content of the save RAM, smaller files are padded with
+/// zeros. If a file is larger than `sram_size` and its size is a multiple of
+/// `8kb` plus [`SRAM_FILE_HEADER_SIZE`], the header is skipped.
+pub(crate) fn fit_sram_data(mut data: &[u8], sram_size: usize) -> (Vec<u8>, usize) {
+    if data.len() > sram_size && data.len() % 0x2000 == SRAM_FILE_HEADER_SIZE {
+        data = &data[SRAM_FILE_HEADER_SIZE..];
+    }
+
+    let mut result = vec![0; sram_size];
+    let size = data.len().min(sram_size);
+    result[..size].copy_from_slice(&data[..size]);
+
+    (result, data.len() - size)
+}
+
+/// The save RAM files of the cartridge at `path`, in the order they are looked up,
+/// `<name>.nes.sav` then `<name>.srm` which other emulators use
+pub(crate) fn sram_file_paths(path: &Path) -> [PathBuf; 2] {
+    [path.with_extension("nes.sav"), path.with_extension("srm")]
+}
+
+#[derive(Clone)]
 #[allow(dead_code)]
 struct INesHeader {
-    // in 16kb units
-    prg_rom_size: u16,
-    // in 8kb units
-    chr_rom_size: u16,
+    /// in bytes
+    prg_rom_size: u64,
+    /// in bytes
+    chr_rom_size: u64,
+    /// a NES 2.0 header with one of the sizes in the exponent-multiplier notation
+    exponent_rom_size: bool,
     is_chr_ram: bool,
     hardwired_mirroring_vertical: bool,
     has_prg_ram_battery: bool,
@@ -40,6 +94,11 @@ struct INesHeader {
     prg_sram_size: u32,
     chr_wram_size: u32,
     chr_sram_size: u32,
+    expansion_device: ExpansionDevice,
+    /// `None` for iNES 1.0 files
+    timing_mode: Option<TimingMode>,
+    /// the TV system bit of iNES 1.0 files (byte 9), which most dumps leave unset
+    ines1_pal: bool,
 }
 
 impl INesHeader {
@@ -47,9 +106,8 @@ impl INesHeader {
         // decode header
         Self::check_magic(&header[0..4])?;
 
-        let prg_size_low = header[4] as u16;
-        let chr_size_low = header[5] as u16;
-        let is_chr_ram = chr_size_low == 0;
+        let prg_size_low = header[4];
+        let chr_size_low = header[5];
 
         let hardwired_mirroring_vertical = header[6] & 1 != 0;
         header[6] >>= 1;
@@ -76,13 +134,18 @@ impl INesHeader {
             }
 
             let prg_ram_size;
+            let mut ines1_pal = false;
 
             if !is_archaic_ines {
                 prg_ram_size = if header[8] == 0 { 1 } else { header[8] };
-                // let ntcs_tv_system = header[9] & 1 == 0;
+                ines1_pal = header[9] & 1 != 0;
 
                 if header[9] >> 1 != 0 {
-                    return Err(CartridgeError::HeaderError);
+                    return Err(HeaderErrorReason::ReservedBitsSet {
+                        byte: 9,
+                        value: header[9],
+                    }
+                    .into());
                 }
 
                 // let is_prg_ram_present = (header[10] >> 4) & 1 == 0;
@@ -96,9 +159,10 @@ impl INesHeader {
             }
 
             Ok(Self {
-                prg_rom_size: prg_size_low,
-                chr_rom_size: chr_size_low,
-                is_chr_ram,
+                prg_rom_size: prg_size_low as u64 * PRG_ROM_UNIT,
+                chr_rom_size: chr_size_low as u64 * CHR_ROM_UNIT,
+                exponent_rom_size: false,
+                is_chr_ram: chr_size_low == 0,
                 hardwired_mirroring_vertical,
                 has_prg_ram_battery,
                 contain_trainer_data,
@@ -109,14 +173,17 @@ impl INesHeader {
                 prg_sram_size: prg_ram_size as u32 * 0x2000,
                 chr_wram_size: 0x2000, // can only use 8kb
                 chr_sram_size: 0x2000,
+                expansion_device: ExpansionDevice::Unspecified,
+                timing_mode: None,
+                ines1_pal,
             })
         } else {
             let mapper_id_high = (header[8] & 0xF) as u16;
             header[8] >>= 4;
             let submapper_id = header[8] & 0xF;
 
-            let prg_size_high = (header[9] & 0xF) as u16;
-            let chr_size_high = ((header[9] >> 4) & 0xF) as u16;
+            let prg_rom_size = Self::nes2_rom_size(prg_size_low, header[9] & 0xF, PRG_ROM_UNIT);
+            let chr_rom_size = Self::nes2_rom_size(chr_size_low, header[9] >> 4, CHR_ROM_UNIT);
 
             let shift_size = (header[10] & 0xF) as u32;
             let prg_wram_size_bytes = if shift_size != 0 { 64 << shift_size } else { 0 };
@@ -130,12 +197,16 @@ impl INesHeader {
             let shift_size = (header[11] & 0xF) as u32;
             let chr_sram_size_bytes = if shift_size != 0 { 64 << shift_size } else { 0 };
 
+            let timing_mode = TimingMode::from_code(header[12]);
+            let expansion_device = ExpansionDevice::from_code(header[15]);
+
             // TODO: implement the rest
 
             Ok(Self {
-                prg_rom_size: prg_size_high << 8 | prg_size_low,
-                chr_rom_size: chr_size_high << 8 | chr_size_low,
-                is_chr_ram,
+                prg_rom_size,
+                chr_rom_size,
+                exponent_rom_size: header[9] & 0xF == 0xF || header[9] >> 4 == 0xF,
+                is_chr_ram: chr_rom_size == 0,
                 hardwired_mirroring_vertical,
                 has_prg_ram_battery,
                 contain_trainer_data,
@@ -146,10 +217,27 @@ impl INesHeader {
                 prg_sram_size: prg_sram_size_bytes,
                 chr_wram_size: chr_wram_size_bytes,
                 chr_sram_size: chr_sram_size_bytes,
+                expansion_device,
+                timing_mode: Some(timing_mode),
+                ines1_pal: false,
             })
         }
     }
 
+    /// The size in bytes of a ROM section in NES 2.0 headers, `msb` is the high nibble
+    /// of the number of `unit` banks, or `0xF` for the exponent-multiplier notation in
+    /// `lsb` (`EEEE EEMM`), which is `2^E * (MM * 2 + 1)` bytes
+    fn nes2_rom_size(lsb: u8, msb: u8, unit: u64) -> u64 {
+        if msb == 0xF {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = (lsb & 0x3) as u64 * 2 + 1;
+            // too large to be valid, rejected by `check_sizes`
+            (1u64 << exponent).saturating_mul(multiplier)
+        } else {
+            ((msb as u64) << 8 | lsb as u64) * unit
+        }
+    }
+
     fn empty() -> Self {
         Self::from_bytes([0x4E, 0x45, 0x53, 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap()
     }
@@ -160,23 +248,106 @@ impl INesHeader {
         if header == real {
             Ok(())
         } else {
-            Err(CartridgeError::HeaderError)
+            let mut magic = [0; 4];
+            magic.copy_from_slice(header);
+            Err(HeaderErrorReason::BadMagic(magic).into())
+        }
+    }
+
+    /// The mappers take the sizes as `u8` banks, so the sizes must be whole banks,
+    /// and there must be PRG ROM
+    fn check_sizes(&self) -> Result<(), CartridgeError> {
+        let is_supported =
+            |size: u64, unit: u64| size.is_multiple_of(unit) && size / unit <= u8::MAX as u64;
+
+        if self.prg_rom_size == 0 || !is_supported(self.prg_rom_size, PRG_ROM_UNIT) {
+            Err(HeaderErrorReason::InconsistentSize {
+                section: RomSection::PrgRom,
+                size: self.prg_rom_size,
+            }
+            .into())
+        } else if !is_supported(self.chr_rom_size, CHR_ROM_UNIT) {
+            Err(HeaderErrorReason::InconsistentSize {
+                section: RomSection::ChrRom,
+                size: self.chr_rom_size,
+            }
+            .into())
+        } else {
+            Ok(())
         }
     }
 }
 
+/// Where a CPU address in the cartridge space (`$4020-$FFFF`) is mapped with the
+/// current banks, returned by [`Cartridge::map_cpu_address`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuMapping {
+    /// offset in the PRG ROM
+    PrgRom(usize),
+    /// offset in the PRG RAM
+    PrgRam(usize),
+    Unmapped,
+}
+
 pub struct Cartridge {
-    file_path: Box<Path>,
+    file_path: Option<Box<Path>>,
     header: INesHeader,
 
     _trainer_data: Vec<u8>,
-    pub(crate) prg_data: Vec<u8>,
-    pub(crate) chr_data: Vec<u8>,
+    /// shared with the other cartridges created from the same [`RomData`],
+    /// writes go through [`Arc::make_mut`]
+    pub(crate) prg_data: Arc<Vec<u8>>,
+    pub(crate) chr_data: Arc<Vec<u8>>,
     prg_ram_data: Vec<u8>,
+    /// see [`Cartridge::rom_crc32`], computed once as the PRG can be written by some mappers
+    rom_crc32: u32,
+    /// the 2kb of RAM for the upper two nametables of four-screen games,
+    /// empty for other games
+    nametable_ram: Vec<u8>,
 
     mapper: Box<dyn Mapper>,
 
     is_empty: bool,
+
+    /// number of CPU writes to addresses that the mapper doesn't have registers at,
+    /// and the address of the first one, see [`Cartridge::take_unsupported_writes`]
+    unsupported_writes: u32,
+    first_unsupported_write: Option<u16>,
+
+    /// replaces [`Mapper::has_bus_conflicts`] if set
+    bus_conflicts_override: Option<bool>,
+
+    /// set with [`Cartridge::set_dip_switches`], kept to apply them again
+    /// when the mapper is created again
+    dip_switches: Option<u8>,
+
+    /// number of CPU writes to battery backed PRG RAM, see [`Cartridge::take_sram_writes`]
+    sram_writes: u32,
+
+    /// check the bank mapping after every mapper register write, see [`Cartridge::audit_mapping`]
+    mapper_audit: bool,
+    diagnostics: Diagnostics,
+}
+
+/// Read `size` bytes of `section`, fails with [`CartridgeError::TruncatedData`]
+/// if the reader ends before that
+fn read_section<R: Read>(
+    reader: &mut R,
+    size: usize,
+    section: RomSection,
+) -> Result<Vec<u8>, CartridgeError> {
+    let mut data = Vec::with_capacity(size);
+    reader.take(size as u64).read_to_end(&mut data)?;
+
+    if data.len() != size {
+        return Err(CartridgeError::TruncatedData {
+            expected: size,
+            got: data.len(),
+            section,
+        });
+    }
+
+    Ok(data)
 }
 
 impl Cartridge {
@@ -186,94 +357,156 @@ impl Cartridge {
             if extension == "nes" {
                 let mut file = File::open(file_path.as_ref())?;
 
-                let mut header = [0; 16];
-                file.read_exact(&mut header)?;
+                Self::from_reader(&mut file, Some(file_path.as_ref()))
+            } else {
+                Err(CartridgeError::ExtensionError)
+            }
+        } else {
+            Err(CartridgeError::ExtensionError)
+        }
+    }
 
-                // decode header
-                let header = INesHeader::from_bytes(header)?;
+    /// Load a cartridge from a `.nes` file, and apply an IPS or BPS patch to it before loading.
+    ///
+    /// The patching is done in memory, the ROM file is not modified.
+    /// The battery backed SRAM is saved next to the ROM file with the name
+    /// `<rom name>.<CRC32>.nes.sav`, where the CRC32 is [`Cartridge::rom_crc32`] of
+    /// the patched ROM, so it doesn't collide with the original ROM saves.
+    pub fn from_file_with_patch<P: AsRef<Path>, Q: AsRef<Path>>(
+        file_path: P,
+        patch_path: Q,
+    ) -> Result<Self, CartridgeError> {
+        let patch = std::fs::read(patch_path)?;
+        Self::from_file_with_patch_bytes(file_path, &patch)
+    }
 
-                let sram_data = if header.has_prg_ram_battery {
-                    // try to load old save data
-                    if let Ok(data) =
-                        Self::load_sram_file(file_path.as_ref(), header.prg_sram_size as usize)
-                    {
-                        data
-                    } else {
-                        vec![0; header.prg_sram_size as usize]
-                    }
-                } else {
-                    vec![0; header.prg_wram_size as usize]
-                };
+    /// Same as [`Cartridge::from_file_with_patch`], with the patch content in memory.
+    pub fn from_file_with_patch_bytes<P: AsRef<Path>>(
+        file_path: P,
+        patch: &[u8],
+    ) -> Result<Self, CartridgeError> {
+        let file_path = file_path.as_ref();
+        if file_path.extension().is_none_or(|ext| ext != "nes") {
+            return Err(CartridgeError::ExtensionError);
+        }
 
-                println!("mapper {}", header.mapper_id);
+        let rom = std::fs::read(file_path)?;
+        let rom = RomData::parse(&apply_patch(&rom, patch)?)?;
 
-                // initialize the mapper first, so that if it is not supported yet,
-                // panic
-                let mapper = Self::get_mapper(&header)?;
+        let patched_path = file_path.with_extension(format!("{:08X}.nes", rom.crc32()));
+        Ok(Self::from_rom_data(&rom, Some(&patched_path)))
+    }
 
-                let mut trainer_data = Vec::new();
+    /// Load a cartridge from the content of an iNES file in memory.
+    ///
+    /// Since there is no file path, battery backed SRAM will not be loaded or saved.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CartridgeError> {
+        Self::from_reader(&mut Cursor::new(data), None)
+    }
 
-                // read training data if present
-                if header.contain_trainer_data {
-                    trainer_data.resize(512, 0);
-                    file.read_exact(&mut trainer_data)?;
-                }
+    fn from_reader<R: Read + Seek>(
+        reader: &mut R,
+        file_path: Option<&Path>,
+    ) -> Result<Self, CartridgeError> {
+        let rom = RomData::from_reader(reader)?;
+        Ok(Self::from_rom_data(&rom, file_path))
+    }
 
-                // read PRG data
-                let mut prg_data = vec![0; (header.prg_rom_size as usize) * 16 * 1024];
-                file.read_exact(&mut prg_data)?;
-
-                // read CHR data
-                let chr_data = if !header.is_chr_ram {
-                    let mut data = vec![0; (header.chr_rom_size as usize) * 8 * 1024];
-                    file.read_exact(&mut data)?;
-
-                    data
-                } else {
-                    // TODO: there is no way of knowing if we are using CHR WRAM or SRAM
-                    let ram_size = header.chr_wram_size;
-
-                    vec![0; ram_size as usize]
-                };
-
-                // there are missing parts
-                let current = file.stream_position()?;
-                let end = file.seek(SeekFrom::End(0))?;
-                if current != end {
-                    Err(CartridgeError::TooLargeFile(end - current))
-                } else {
-                    Ok(Self {
-                        file_path: file_path.as_ref().to_path_buf().into_boxed_path(),
-                        header,
-                        _trainer_data: trainer_data,
-                        prg_data,
-                        chr_data,
-                        prg_ram_data: sram_data,
-                        mapper,
-
-                        is_empty: false,
-                    })
+    /// Create a cartridge sharing the PRG and CHR ROM of `rom`
+    pub fn from_rom_data(rom: &RomData, file_path: Option<&Path>) -> Self {
+        let header = rom.header.clone();
+
+        let mut diagnostics = Diagnostics::default();
+        let mut sram_data = if header.has_prg_ram_battery {
+            // try to load old save data
+            if let Some(Ok((data, dropped_bytes))) = file_path
+                .map(|file_path| Self::load_sram_file(file_path, header.prg_sram_size as usize))
+            {
+                if dropped_bytes != 0 {
+                    diagnostics.push(Diagnostic::SramImportTruncated { dropped_bytes });
                 }
+                data
             } else {
-                Err(CartridgeError::ExtensionError)
+                vec![0; header.prg_sram_size as usize]
             }
         } else {
-            Err(CartridgeError::ExtensionError)
+            vec![0; header.prg_wram_size as usize]
+        };
+
+        // the trainer was loaded by the copier devices on boot, so it replaces what is saved
+        if let Some(trainer_ram) =
+            sram_data.get_mut(TRAINER_PRG_RAM_OFFSET..TRAINER_PRG_RAM_OFFSET + rom.trainer.len())
+        {
+            trainer_ram.copy_from_slice(&rom.trainer);
+        }
+
+        let mapper =
+            Self::get_mapper(&header).expect("the mapper was created when parsing the ROM");
+
+        // TODO: there is no way of knowing if we are using CHR WRAM or SRAM
+        let chr_data = rom
+            .chr
+            .clone()
+            .unwrap_or_else(|| Arc::new(vec![0; header.chr_wram_size as usize]));
+
+        let nametable_ram = if header.use_hardwaired_4_screen_mirroring {
+            vec![0; NAMETABLE_RAM_SIZE]
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            file_path: file_path.map(|file_path| file_path.to_path_buf().into_boxed_path()),
+            header,
+            _trainer_data: rom.trainer.clone(),
+            prg_data: rom.prg.clone(),
+            chr_data,
+            prg_ram_data: sram_data,
+            rom_crc32: rom.crc32(),
+            nametable_ram,
+            mapper,
+
+            is_empty: false,
+
+            unsupported_writes: 0,
+            first_unsupported_write: None,
+
+            bus_conflicts_override: None,
+
+            dip_switches: None,
+
+            sram_writes: 0,
+
+            mapper_audit: false,
+            diagnostics,
         }
     }
 
     pub fn new_without_file() -> Self {
         Self {
-            // should not be used
-            file_path: Path::new("").to_path_buf().into_boxed_path(),
+            file_path: None,
             header: INesHeader::empty(),
             _trainer_data: Vec::new(),
-            prg_data: Vec::new(),
-            chr_data: Vec::new(),
+            prg_data: Arc::default(),
+            chr_data: Arc::default(),
             prg_ram_data: Vec::new(),
+            rom_crc32: 0,
+            nametable_ram: Vec::new(),
             mapper: Box::new(Mapper0::new()),
 
             is_empty: true,
+
+            unsupported_writes: 0,
+            first_unsupported_write: None,
+
+            bus_conflicts_override: None,
+
+            dip_switches: None,
+
+            sram_writes: 0,
+
+            mapper_audit: false,
+            diagnostics: Diagnostics::default(),
         }
     }
 
@@ -281,15 +514,19 @@ impl Cartridge {
         let mut mapper: Box<dyn Mapper> = match header.mapper_id {
             0 => Box::new(Mapper0::new()),
             1 => Box::new(Mapper1::new()),
-            2 => Box::new(Mapper2::new()),
-            3 => Box::new(Mapper3::new()),
+            2 => Box::new(Mapper2::new(header.submapper_id)),
+            3 => Box::new(Mapper3::new(header.submapper_id)),
             4 => Box::new(Mapper4::new()),
-            7 => Box::new(Mapper7::new()),
+            7 => Box::new(Mapper7::new(header.submapper_id)),
             9 => Box::new(Mapper9::new()),
             10 => Box::new(Mapper10::new()),
             11 => Box::new(Mapper11::new()),
             12 => Box::new(Mapper12::new()),
+            28 => Box::new(Mapper28::new()),
+            34 => Box::new(Mapper34::new(header.submapper_id)),
             66 => Box::new(Mapper66::new()),
+            87 => Box::new(Mapper87::new()),
+            105 => Box::new(Mapper105::new()),
             _ => {
                 return Err(CartridgeError::MapperNotImplemented(header.mapper_id));
             }
@@ -299,10 +536,10 @@ impl Cartridge {
         // should always call init in a new mapper, as it is the only way
         // they share a constructor
         mapper.init(
-            header.prg_rom_size as u8,
+            (header.prg_rom_size / PRG_ROM_UNIT) as u8,
             header.is_chr_ram,
             if !header.is_chr_ram {
-                header.chr_rom_size as u8
+                (header.chr_rom_size / CHR_ROM_UNIT) as u8
             } else {
                 (header.chr_wram_size / 0x2000) as u8
             },
@@ -316,21 +553,32 @@ impl Cartridge {
         Ok(mapper)
     }
 
-    fn load_sram_file<P: AsRef<Path>>(path: P, sram_size: usize) -> Result<Vec<u8>, SramError> {
-        let path = path.as_ref().with_extension("nes.sav");
-        println!("Loading SRAM file data from {:?}", path);
-
-        let mut file = File::open(path)?;
-        let mut result = vec![0; sram_size];
+    /// Load the first save RAM file found for the cartridge at `path`, see [`fit_sram_data`].
+    /// Returns the data and the number of bytes that didn't fit.
+    fn load_sram_file<P: AsRef<Path>>(
+        path: P,
+        sram_size: usize,
+    ) -> Result<(Vec<u8>, usize), SramError> {
+        for path in sram_file_paths(path.as_ref()) {
+            let data = match std::fs::read(&path) {
+                Ok(data) => data,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            println!("Loading SRAM file data from {:?}", path);
 
-        file.read_exact(&mut result)
-            .map_err(|_| SramError::SramFileSizeDoesNotMatch)?;
+            return Ok(fit_sram_data(&data, sram_size));
+        }
 
-        Ok(result)
+        Err(SramError::NoSramFileFound)
     }
 
     fn save_sram_file(&self) -> Result<(), SramError> {
-        let path = self.file_path.with_extension("nes.sav");
+        let Some(file_path) = self.file_path.as_ref() else {
+            // loaded from memory, nowhere to save
+            return Ok(());
+        };
+        let path = file_path.with_extension("nes.sav");
         println!("Writing SRAM file data to {:?}", path);
 
         let mut file = File::create(&path)?;
@@ -351,8 +599,168 @@ impl Cartridge {
         self.is_empty
     }
 
-    pub fn cartridge_path(&self) -> &Path {
-        &self.file_path
+    pub fn cartridge_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    pub fn expansion_device(&self) -> ExpansionDevice {
+        self.header.expansion_device
+    }
+
+    pub fn set_expansion_device(&mut self, device: ExpansionDevice) {
+        self.header.expansion_device = device;
+    }
+
+    pub fn timing_mode(&self) -> Option<TimingMode> {
+        self.header.timing_mode
+    }
+
+    pub fn ines1_pal(&self) -> bool {
+        self.header.ines1_pal
+    }
+
+    pub fn submapper_id(&self) -> u8 {
+        self.header.submapper_id
+    }
+
+    /// Change the submapper (board revision), this creates the mapper again,
+    /// so it should be followed by a reset
+    pub fn set_submapper_id(&mut self, submapper_id: u8) {
+        if self.is_empty {
+            return;
+        }
+
+        self.header.submapper_id = submapper_id;
+        self.mapper = Self::get_mapper(&self.header)
+            .expect("the mapper was created before from the same header");
+        if let Some(dips) = self.dip_switches {
+            self.mapper.set_dip_switches(dips);
+        }
+    }
+
+    pub fn has_bus_conflicts(&self) -> bool {
+        self.bus_conflicts_override
+            .unwrap_or_else(|| self.mapper.has_bus_conflicts())
+    }
+
+    pub fn set_bus_conflicts_override(&mut self, bus_conflicts: Option<bool>) {
+        self.bus_conflicts_override = bus_conflicts;
+    }
+
+    /// CRC32 of the PRG and CHR ROM data when loaded, without the header, which is the
+    /// checksum used by most game databases
+    pub fn rom_crc32(&self) -> u32 {
+        self.rom_crc32
+    }
+
+    pub fn mapper_id(&self) -> u16 {
+        self.header.mapper_id
+    }
+
+    pub fn prg_rom(&self) -> &[u8] {
+        &self.prg_data
+    }
+
+    pub fn chr_rom(&self) -> Option<&[u8]> {
+        (!self.header.is_chr_ram).then_some(self.chr_data.as_slice())
+    }
+
+    pub fn chr_ram(&self) -> Option<&[u8]> {
+        self.header.is_chr_ram.then_some(self.chr_data.as_slice())
+    }
+
+    /// Returns the number of CPU writes to ROM or expansion addresses where the mapper
+    /// doesn't have registers, and the address of the first one, then resets them.
+    ///
+    /// A game writing there repeatedly is probably using a different mapper.
+    pub fn take_unsupported_writes(&mut self) -> (u32, Option<u16>) {
+        let result = (self.unsupported_writes, self.first_unsupported_write);
+        self.unsupported_writes = 0;
+        self.first_unsupported_write = None;
+        result
+    }
+
+    /// Run the timers of the mapper that count CPU cycles, called on every CPU cycle
+    pub fn cpu_cycle(&mut self) {
+        if !self.is_empty {
+            self.mapper.cpu_cycle();
+        }
+    }
+
+    /// Set the DIP switches of the board, boards without them ignore it.
+    /// This is a setting, and is not saved in the state.
+    pub fn set_dip_switches(&mut self, dips: u8) {
+        self.dip_switches = Some(dips);
+        self.mapper.set_dip_switches(dips);
+    }
+
+    /// Take the diagnostics of the mapper audit and of loading the save RAM file
+    /// emitted since the last call
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.diagnostics.take()
+    }
+
+    /// Returns the number of CPU writes to battery backed PRG RAM since the last call,
+    /// always `0` for cartridges without a battery
+    pub fn take_sram_writes(&mut self) -> u32 {
+        std::mem::take(&mut self.sram_writes)
+    }
+
+    /// The content of the battery backed PRG RAM, `None` if the cartridge doesn't have it
+    pub fn sram_data(&self) -> Option<&[u8]> {
+        self.header
+            .has_prg_ram_battery
+            .then_some(self.prg_ram_data.as_slice())
+    }
+
+    /// Replace the battery backed PRG RAM with the content of a save RAM file,
+    /// see [`fit_sram_data`]. Returns the number of bytes that didn't fit.
+    pub fn import_sram(&mut self, data: &[u8]) -> Result<usize, SaveError> {
+        if !self.header.has_prg_ram_battery {
+            return Err(SaveError::NoBatteryBackedRam);
+        }
+
+        let (data, dropped) = fit_sram_data(data, self.prg_ram_data.len());
+        self.prg_ram_data = data;
+
+        Ok(dropped)
+    }
+
+    /// Where the CPU `address` is mapped with the current banks, without any side effects
+    pub fn map_cpu_address(&self, address: u16) -> CpuMapping {
+        if self.is_empty || address < 0x4020 {
+            return CpuMapping::Unmapped;
+        }
+
+        match (self.mapper.map_read(address, Device::Cpu), address) {
+            (MappingResult::Allowed(offset), 0x6000..=0x7FFF) => CpuMapping::PrgRam(offset),
+            (MappingResult::Allowed(offset), 0x8000..=0xFFFF) => CpuMapping::PrgRom(offset),
+            _ => CpuMapping::Unmapped,
+        }
+    }
+
+    /// `true` if a CPU write to `address` lands on the PRG ROM without a mapper register,
+    /// so it doesn't do anything
+    pub fn is_rom_write(&self, address: u16) -> bool {
+        !self.is_empty && address >= 0x8000 && !self.mapper.has_register_at(address)
+    }
+
+    /// The size of the smallest PRG ROM bank the mapper switches
+    pub fn prg_bank_size(&self) -> u16 {
+        self.mapper.prg_bank_size()
+    }
+
+    pub fn has_prg_ram_battery(&self) -> bool {
+        self.header.has_prg_ram_battery
+    }
+
+    /// The internal state of the mapper, used to undo changes caused by reads
+    pub fn mapper_state(&self) -> Vec<u8> {
+        self.mapper.save_state()
+    }
+
+    pub fn restore_mapper_state(&mut self, state: Vec<u8>) {
+        self.mapper.load_state(state);
     }
 }
 
@@ -365,6 +773,14 @@ impl Bus for Cartridge {
             };
         }
 
+        if device == Device::Ppu && address >= 0x2000 {
+            return self
+                .nametable_ram
+                .get((address & 0x7FF) as usize)
+                .copied()
+                .unwrap_or(0);
+        }
+
         let result = self.mapper.map_read(address, device);
 
         if let MappingResult::Allowed(new_address) = result {
@@ -396,9 +812,36 @@ impl Bus for Cartridge {
             return;
         }
 
+        if device == Device::Ppu && address >= 0x2000 {
+            if let Some(byte) = self.nametable_ram.get_mut((address & 0x7FF) as usize) {
+                *byte = data;
+            }
+            return;
+        }
+
+        // the ROM drives the data bus at the same time as the CPU
+        let data = if device == Device::Cpu && address >= 0x8000 && self.has_bus_conflicts() {
+            data & self.read(address, device)
+        } else {
+            data
+        };
+
         // send the write signal, this might trigger bank change
         let result = self.mapper.map_write(address, data, device);
 
+        if let (MappingResult::Denied, Device::Cpu, 0x4020..=0x5FFF | 0x8000..=0xFFFF) =
+            (&result, device, address)
+        {
+            if !self.mapper.has_register_at(address) {
+                self.unsupported_writes += 1;
+                self.first_unsupported_write.get_or_insert(address);
+            }
+        }
+
+        if self.mapper_audit && device == Device::Cpu && self.mapper.has_register_at(address) {
+            self.audit_mapping(address, data);
+        }
+
         if let MappingResult::Allowed(new_address) = result {
             match device {
                 Device::Cpu => match address {
@@ -407,10 +850,13 @@ impl Bus for Cartridge {
                             .prg_ram_data
                             .get_mut(new_address)
                             .expect("SRAM out of bounds") = data;
+
+                        if self.header.has_prg_ram_battery {
+                            self.sram_writes += 1;
+                        }
                     }
                     0x8000..=0xFFFF => {
-                        *self
-                            .prg_data
+                        *Arc::make_mut(&mut self.prg_data)
                             .get_mut(new_address)
                             .expect("PRG out of bounds") = data;
                     }
@@ -420,8 +866,7 @@ impl Bus for Cartridge {
                 },
                 Device::Ppu => {
                     if address <= 0x1FFF {
-                        *self
-                            .chr_data
+                        *Arc::make_mut(&mut self.chr_data)
                             .get_mut(new_address)
                             .expect("CHR out of bounds") = data;
                     } else {
@@ -434,13 +879,20 @@ impl Bus for Cartridge {
 }
 
 impl MirroringProvider for Cartridge {
+    /// The mirroring is decided in this order:
+    /// 1. The four-screen bit of the header, if the mapper allows it to override its
+    ///    mirroring, see [`Mapper::four_screen_overrides_mirroring`].
+    /// 2. The mirroring bit of the header, for mappers with hardwired mirroring.
+    /// 3. The mirroring control of the mapper.
     fn mirroring_mode(&self) -> MirroringMode {
         if self.is_empty {
             //anything
             return MirroringMode::Vertical;
         }
 
-        if self.header.use_hardwaired_4_screen_mirroring {
+        if self.header.use_hardwaired_4_screen_mirroring
+            && self.mapper.four_screen_overrides_mirroring()
+        {
             MirroringMode::FourScreen
         } else if self.mapper.is_hardwired_mirrored() {
             if self.header.hardwired_mirroring_vertical {
@@ -481,7 +933,7 @@ impl CPUIrqProvider for Cartridge {
 }
 
 impl Savable for Cartridge {
-    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn Write) -> Result<(), SaveError> {
         let mapper_saved_state = self.mapper.save_state();
         writer.write_all(&mapper_saved_state)?;
 
@@ -492,10 +944,12 @@ impl Savable for Cartridge {
             writer.write_all(&self.chr_data)?;
         }
 
+        writer.write_all(&self.nametable_ram)?;
+
         Ok(())
     }
 
-    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
         let mut mapper_load_data = vec![0; self.mapper.save_state_size()];
         reader.read_exact(&mut mapper_load_data)?;
         self.mapper.load_state(mapper_load_data);
@@ -505,8 +959,65 @@ impl Savable for Cartridge {
         let mut is_chr_ram = [0u8; 1];
         reader.read_exact(&mut is_chr_ram)?;
         if is_chr_ram[0] != 0 {
-            reader.read_exact(&mut self.chr_data)?;
+            reader.read_exact(Arc::make_mut(&mut self.chr_data).as_mut_slice())?;
+        }
+
+        reader.read_exact(&mut self.nametable_ram)?;
+
+        Ok(())
+    }
+}
+
+/// Decode the base64 `value` of a JSON state, which must be `len` bytes
+#[cfg(feature = "state-json")]
+fn decode_json_bytes(value: serde_json::Value, len: usize) -> Result<Vec<u8>, SaveError> {
+    use base64::Engine;
+
+    value
+        .as_str()
+        .and_then(|data| base64::engine::general_purpose::STANDARD.decode(data).ok())
+        .filter(|data| data.len() == len)
+        .ok_or(SaveError::SerializationError)
+}
+
+/// The same sections as the binary state, the data is base64 encoded
+#[cfg(feature = "state-json")]
+impl JsonSavable for Cartridge {
+    fn to_json(&self) -> serde_json::Value {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        serde_json::json!({
+            "mapper": STANDARD.encode(self.mapper.save_state()),
+            "prg_ram": STANDARD.encode(&self.prg_ram_data),
+            "chr_ram": self.header.is_chr_ram.then(|| STANDARD.encode(self.chr_data.as_slice())),
+            "nametable_ram": STANDARD.encode(&self.nametable_ram),
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        let mapper = decode_json_bytes(
+            take_json_field(&mut value, "mapper")?,
+            self.mapper.save_state_size(),
+        )?;
+        let prg_ram = decode_json_bytes(
+            take_json_field(&mut value, "prg_ram")?,
+            self.prg_ram_data.len(),
+        )?;
+        let chr_ram = match take_json_field(&mut value, "chr_ram")? {
+            serde_json::Value::Null => None,
+            chr_ram => Some(decode_json_bytes(chr_ram, self.chr_data.len())?),
+        };
+        let nametable_ram = decode_json_bytes(
+            take_json_field(&mut value, "nametable_ram")?,
+            self.nametable_ram.len(),
+        )?;
+
+        self.mapper.load_state(mapper);
+        self.prg_ram_data = prg_ram;
+        if let Some(chr_ram) = chr_ram {
+            self.chr_data = Arc::new(chr_ram);
         }
+        self.nametable_ram = nametable_ram;
 
         Ok(())
     }
@@ -0,0 +1,324 @@
+//! Applying IPS and BPS patches to ROM data in memory
+
+use super::{CartridgeError, CHR_ROM_UNIT, PRG_ROM_UNIT};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// source, target and patch checksums
+const BPS_FOOTER_SIZE: usize = 12;
+/// The largest ROM that can be loaded, with the header, the trainer and the largest PRG
+/// and CHR ROM allowed by `INesHeader::check_sizes`, larger BPS targets are rejected
+/// before allocating them
+const MAX_PATCHED_ROM_SIZE: usize =
+    16 + 512 + u8::MAX as usize * (PRG_ROM_UNIT + CHR_ROM_UNIT) as usize;
+
+/// Apply an IPS or BPS `patch` to `rom`, the format is detected from the patch header.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(CartridgeError::InvalidPatch)
+    }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], CartridgeError> {
+        if self.remaining() < len {
+            return Err(CartridgeError::InvalidPatch);
+        }
+
+        let result = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(result)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, CartridgeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// big endian number of `len` bytes, used in IPS
+    fn read_be(&mut self, len: usize) -> Result<usize, CartridgeError> {
+        Ok(self
+            .read_bytes(len)?
+            .iter()
+            .fold(0, |result, &byte| result << 8 | byte as usize))
+    }
+
+    /// variable length number, used in BPS
+    fn read_number(&mut self) -> Result<usize, CartridgeError> {
+        let mut result = 0usize;
+        let mut shift = 1usize;
+
+        loop {
+            let byte = self.read_u8()?;
+            result = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|value| result.checked_add(value))
+                .ok_or(CartridgeError::InvalidPatch)?;
+
+            if byte & 0x80 != 0 {
+                break;
+            }
+
+            shift = shift.checked_shl(7).ok_or(CartridgeError::InvalidPatch)?;
+            result = result
+                .checked_add(shift)
+                .ok_or(CartridgeError::InvalidPatch)?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// A single IPS record, without the data being applied yet
+enum IpsRecord<'a> {
+    Data {
+        offset: usize,
+        data: &'a [u8],
+    },
+    Rle {
+        offset: usize,
+        count: usize,
+        value: u8,
+    },
+}
+
+impl<'a> PatchReader<'a> {
+    fn read_ips_record(&mut self) -> Result<IpsRecord<'a>, CartridgeError> {
+        let offset = self.read_be(3)?;
+        let size = self.read_be(2)?;
+
+        if size == 0 {
+            let count = self.read_be(2)?;
+            let value = self.read_u8()?;
+            Ok(IpsRecord::Rle {
+                offset,
+                count,
+                value,
+            })
+        } else {
+            Ok(IpsRecord::Data {
+                offset,
+                data: self.read_bytes(size)?,
+            })
+        }
+    }
+
+    /// `EOF` followed by nothing, or by the 3 bytes of the truncation size
+    fn at_ips_footer(&self) -> bool {
+        self.data[self.position..].starts_with(IPS_EOF) && matches!(self.remaining(), 3 | 6)
+    }
+
+    /// Check that the rest of the patch is a sequence of records ending
+    /// with the footer, starting with a record even if it is at offset `EOF`
+    fn ips_records_valid(&self) -> bool {
+        let mut reader = PatchReader::new(self.data, self.position);
+
+        loop {
+            if reader.read_ips_record().is_err() {
+                return false;
+            }
+            if reader.at_ips_footer() {
+                return true;
+            }
+        }
+    }
+}
+
+/// IPS format:
+/// - `PATCH` header
+/// - records of: 3 bytes offset, 2 bytes size, `size` bytes of data.
+///   if `size` is 0, then its an RLE record: 2 bytes count, 1 byte value.
+/// - `EOF` footer, optionally followed by 3 bytes of the size to truncate the file to.
+///
+/// Since `EOF` is also a valid offset (`0x454F46`), it is only treated as the
+/// footer if its at the end of the patch (or followed only by the truncation size),
+/// or if the rest of the patch can't be read as records. In the latter case,
+/// the data after it is junk added by some tools and is ignored.
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let mut result = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        if reader.at_ips_footer() {
+            reader.position += IPS_EOF.len();
+
+            if reader.remaining() == 3 {
+                let size = reader.read_be(3)?;
+                result.truncate(size);
+            }
+            return Ok(result);
+        }
+
+        if reader.data[reader.position..].starts_with(IPS_EOF) && !reader.ips_records_valid() {
+            return Ok(result);
+        }
+
+        match reader.read_ips_record()? {
+            IpsRecord::Data { offset, data } => {
+                ips_target(&mut result, offset, data.len()).copy_from_slice(data)
+            }
+            IpsRecord::Rle {
+                offset,
+                count,
+                value,
+            } => ips_target(&mut result, offset, count).fill(value),
+        }
+    }
+}
+
+/// The `size` bytes at `offset` in `result`, writing after the end grows the file
+fn ips_target(result: &mut Vec<u8>, offset: usize, size: usize) -> &mut [u8] {
+    if result.len() < offset + size {
+        result.resize(offset + size, 0);
+    }
+
+    &mut result[offset..offset + size]
+}
+
+/// BPS format, see <https://github.com/blakesmith/rombp/blob/master/docs/bps_spec.md>
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(CartridgeError::InvalidPatch);
+    }
+
+    let actions_end = patch.len() - BPS_FOOTER_SIZE;
+    let footer = &patch[actions_end..];
+    let read_u32 =
+        |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+    let (source_crc, target_crc, patch_crc) = (read_u32(0), read_u32(1), read_u32(2));
+
+    let found = crc32(&patch[..patch.len() - 4]);
+    if found != patch_crc {
+        return Err(CartridgeError::PatchChecksumMismatch {
+            expected: patch_crc,
+            found,
+        });
+    }
+
+    let found = crc32(rom);
+    if found != source_crc {
+        return Err(CartridgeError::PatchChecksumMismatch {
+            expected: source_crc,
+            found,
+        });
+    }
+
+    let mut reader = PatchReader::new(&patch[..actions_end], BPS_MAGIC.len());
+
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_number()?;
+    let metadata_size = reader.read_number()?;
+    reader.read_bytes(metadata_size)?;
+
+    if source_size != rom.len() || target_size > MAX_PATCHED_ROM_SIZE {
+        return Err(CartridgeError::InvalidPatch);
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+
+    let relative_offset = |reader: &mut PatchReader, offset: usize| {
+        let data = reader.read_number()?;
+        let delta = data >> 1;
+
+        if data & 1 != 0 {
+            offset.checked_sub(delta)
+        } else {
+            offset.checked_add(delta)
+        }
+        .ok_or(CartridgeError::InvalidPatch)
+    };
+
+    while reader.remaining() > 0 {
+        let data = reader.read_number()?;
+        let length = (data >> 2) + 1;
+
+        if length > target_size - target.len() {
+            return Err(CartridgeError::InvalidPatch);
+        }
+
+        match data & 3 {
+            // source read
+            0 => {
+                let start = target.len();
+                let source = start
+                    .checked_add(length)
+                    .and_then(|end| rom.get(start..end))
+                    .ok_or(CartridgeError::InvalidPatch)?;
+                target.extend_from_slice(source);
+            }
+            // target read
+            1 => target.extend_from_slice(reader.read_bytes(length)?),
+            // source copy
+            2 => {
+                source_offset = relative_offset(&mut reader, source_offset)?;
+                let end = source_offset
+                    .checked_add(length)
+                    .ok_or(CartridgeError::InvalidPatch)?;
+                let source = rom
+                    .get(source_offset..end)
+                    .ok_or(CartridgeError::InvalidPatch)?;
+                target.extend_from_slice(source);
+                source_offset = end;
+            }
+            // target copy, can overlap with the data being written, so copy byte by byte
+            3 => {
+                target_offset = relative_offset(&mut reader, target_offset)?;
+                if target_offset >= target.len() {
+                    return Err(CartridgeError::InvalidPatch);
+                }
+                for _ in 0..length {
+                    target.push(target[target_offset]);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(CartridgeError::InvalidPatch);
+    }
+
+    let found = crc32(&target);
+    if found != target_crc {
+        return Err(CartridgeError::PatchChecksumMismatch {
+            expected: target_crc,
+            found,
+        });
+    }
+
+    Ok(target)
+}
@@ -0,0 +1,104 @@
+use super::{read_section, Cartridge, CartridgeError, INesHeader, RomSection};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+/// A parsed iNES file, to create many emulators of the same game without
+/// copying the ROM, see [`NES::from_shared_rom`](crate::NES::from_shared_rom).
+///
+/// The PRG and CHR ROM are shared by all the cartridges created from it, each
+/// cartridge has its own CHR RAM and PRG RAM.
+pub struct RomData {
+    pub(super) header: INesHeader,
+    pub(super) trainer: Vec<u8>,
+    pub(crate) prg: Arc<Vec<u8>>,
+    /// `None` for games with CHR RAM
+    pub(crate) chr: Option<Arc<Vec<u8>>>,
+}
+
+impl RomData {
+    /// Parse the content of an iNES file, fails like [`NES::new_from_bytes`](crate::NES::new_from_bytes)
+    pub fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        Self::from_reader(&mut Cursor::new(data))
+    }
+
+    pub(super) fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, CartridgeError> {
+        let mut header = [0; 16];
+        header.copy_from_slice(&read_section(reader, 16, RomSection::Header)?);
+
+        // decode header
+        let header = INesHeader::from_bytes(header)?;
+        header.check_sizes()?;
+
+        println!("mapper {}", header.mapper_id);
+
+        // create the mapper first, so that unsupported mappers fail before
+        // reading the data
+        Cartridge::get_mapper(&header)?;
+
+        // read training data if present
+        let trainer = if header.contain_trainer_data {
+            read_section(reader, 512, RomSection::Trainer)?
+        } else {
+            Vec::new()
+        };
+
+        // read PRG data
+        let prg = read_section(reader, header.prg_rom_size as usize, RomSection::PrgRom)?;
+
+        // read CHR data
+        let chr = if !header.is_chr_ram {
+            Some(read_section(
+                reader,
+                header.chr_rom_size as usize,
+                RomSection::ChrRom,
+            )?)
+        } else {
+            None
+        };
+
+        // there is data after the sizes declared in the header
+        let expected = reader.stream_position()?;
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        if expected != file_size {
+            return Err(if header.exponent_rom_size {
+                CartridgeError::SizeMismatch {
+                    file_size,
+                    expected,
+                }
+            } else {
+                CartridgeError::TooLargeFile {
+                    file_size,
+                    extra: file_size - expected,
+                }
+            });
+        }
+
+        Ok(Self {
+            header,
+            trainer,
+            prg: Arc::new(prg),
+            chr: chr.map(Arc::new),
+        })
+    }
+
+    pub fn prg_rom(&self) -> &[u8] {
+        &self.prg
+    }
+
+    /// `None` for games with CHR RAM
+    pub fn chr_rom(&self) -> Option<&[u8]> {
+        self.chr.as_deref().map(Vec::as_slice)
+    }
+
+    /// CRC32 of the PRG and CHR ROM, see [`Cartridge::rom_crc32`](super::Cartridge::rom_crc32)
+    pub(crate) fn crc32(&self) -> u32 {
+        let mut data = self.prg.to_vec();
+        if let Some(chr) = &self.chr {
+            data.extend_from_slice(chr);
+        }
+
+        super::patch::crc32(&data)
+    }
+}
@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod cartridge_tests {
-    use super::super::{Cartridge, CartridgeError};
+    use super::super::{
+        Cartridge, CartridgeError, ExpansionDevice, HeaderErrorReason, INesHeader, RomSection,
+    };
+    use crate::common::{Bus, Device};
 
     #[test]
     fn cartridge_file_not_found() {
@@ -34,8 +37,11 @@ mod cartridge_tests {
             .err()
             .expect("Should get an error as the cartridge has wrong header");
 
-        if let CartridgeError::HeaderError = err {
-            // passed
+        if let CartridgeError::HeaderError {
+            reason: HeaderErrorReason::BadMagic(magic),
+        } = err
+        {
+            assert_eq!(magic, [0x5E, 0x45, 0x53, 0x1A]);
         } else {
             panic!("Should get header error");
         }
@@ -47,8 +53,9 @@ mod cartridge_tests {
             .err()
             .expect("Should get an error as the cartridge file is larger than expected");
 
-        if let CartridgeError::TooLargeFile(exceeded_size) = err {
-            assert_eq!(exceeded_size, 1);
+        if let CartridgeError::TooLargeFile { file_size, extra } = err {
+            assert_eq!(extra, 1);
+            assert_eq!(file_size, 16 + 0x8000 + 0x2000 + 1);
         } else {
             panic!("Should get too large file error");
         }
@@ -59,16 +66,845 @@ mod cartridge_tests {
         let cartridge = Cartridge::from_file("../test_roms/cartridge_tests/test_creation.nes")?;
 
         // make sure that all characters match, meaning we read all of them
-        for &c in &cartridge.prg_data {
+        for &c in cartridge.prg_data.iter() {
             assert_eq!(c, 0xFF);
         }
 
         // make sure that all characters match, meaning we read all of them
-        for &c in &cartridge.chr_data {
+        for &c in cartridge.chr_data.iter() {
             assert_eq!(c, 0xEE);
         }
 
         // test passed
         Ok(())
     }
+
+    #[test]
+    fn test_cartridge_from_bytes() -> Result<(), CartridgeError> {
+        let data = std::fs::read("../test_roms/cartridge_tests/test_creation.nes")?;
+        let cartridge = Cartridge::from_bytes(&data)?;
+
+        assert!(cartridge.cartridge_path().is_none());
+        assert!(cartridge.prg_data.iter().all(|&c| c == 0xFF));
+        assert!(cartridge.chr_data.iter().all(|&c| c == 0xEE));
+
+        // the same error checking as files
+        let err = Cartridge::from_bytes(&data[..data.len() - 1])
+            .err()
+            .expect("Should get an error as the data is incomplete");
+        assert!(matches!(
+            err,
+            CartridgeError::TruncatedData {
+                section: RomSection::ChrRom,
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+
+    /// NROM with 16KB PRG and 8KB CHR
+    fn synthetic_rom(nes2: bool, byte_15: u8) -> Vec<u8> {
+        let mut data = vec![
+            0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, byte_15,
+        ];
+        if nes2 {
+            data[7] = 0x08;
+        }
+        data.resize(16 + 0x4000 + 0x2000, 0);
+        data
+    }
+
+    #[test]
+    fn test_nes2_expansion_device() -> Result<(), CartridgeError> {
+        let devices = [
+            (0x00, ExpansionDevice::Unspecified),
+            (0x01, ExpansionDevice::StandardControllers),
+            (0x02, ExpansionDevice::FourScore),
+            (0x03, ExpansionDevice::FamicomFourPlayersAdapter),
+            (0x04, ExpansionDevice::VsSystem),
+            (0x05, ExpansionDevice::VsSystemSwapped),
+            (0x06, ExpansionDevice::Other(0x06)),
+            (0x07, ExpansionDevice::VsZapper),
+            (0x08, ExpansionDevice::Zapper),
+            (0x09, ExpansionDevice::TwoZappers),
+            (0x0A, ExpansionDevice::BandaiHyperShot),
+            (0x0B, ExpansionDevice::PowerPadSideA),
+            (0x0C, ExpansionDevice::PowerPadSideB),
+            (0x0D, ExpansionDevice::FamilyTrainerSideA),
+            (0x0E, ExpansionDevice::FamilyTrainerSideB),
+            (0x0F, ExpansionDevice::VausNes),
+            (0x10, ExpansionDevice::VausFamicom),
+            (0x2A, ExpansionDevice::Other(0x2A)),
+            // upper bits are reserved
+            (0xC8, ExpansionDevice::Zapper),
+        ];
+
+        for (code, device) in devices {
+            let cartridge = Cartridge::from_bytes(&synthetic_rom(true, code))?;
+            assert_eq!(cartridge.expansion_device(), device, "code {:02X}", code);
+        }
+
+        // the device is not available in iNES 1.0
+        let cartridge = Cartridge::from_bytes(&synthetic_rom(false, 0))?;
+        assert_eq!(cartridge.expansion_device(), ExpansionDevice::Unspecified);
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_prg_rom() {
+        let data = synthetic_rom(false, 0);
+
+        let err = Cartridge::from_bytes(&data[..16 + 0x1000])
+            .err()
+            .expect("Should get an error as the PRG ROM is incomplete");
+        if let CartridgeError::TruncatedData {
+            expected,
+            got,
+            section,
+        } = err
+        {
+            assert_eq!(
+                (expected, got, section),
+                (0x4000, 0x1000, RomSection::PrgRom)
+            );
+        } else {
+            panic!("Should get truncated data error, got {}", err);
+        }
+
+        let err = Cartridge::from_bytes(&data[..10]).err().unwrap();
+        assert!(matches!(
+            err,
+            CartridgeError::TruncatedData {
+                expected: 16,
+                got: 10,
+                section: RomSection::Header,
+            }
+        ));
+    }
+
+    #[test]
+    fn bad_header_reasons() {
+        let mut data = synthetic_rom(false, 0);
+        data[3] = 0x1B;
+        let err = Cartridge::from_bytes(&data).err().unwrap();
+        assert!(matches!(
+            err,
+            CartridgeError::HeaderError {
+                reason: HeaderErrorReason::BadMagic([0x4E, 0x45, 0x53, 0x1B]),
+            }
+        ));
+
+        let mut data = synthetic_rom(false, 0);
+        data[9] = 0x04;
+        let err = Cartridge::from_bytes(&data).err().unwrap();
+        assert!(matches!(
+            err,
+            CartridgeError::HeaderError {
+                reason: HeaderErrorReason::ReservedBitsSet { byte: 9, value: 4 },
+            }
+        ));
+
+        // NES 2.0 with 256 * 16KB of PRG ROM, more than the mappers support
+        let mut data = synthetic_rom(true, 0);
+        data[4] = 0;
+        data[9] = 0x01;
+        let err = Cartridge::from_bytes(&data).err().unwrap();
+        assert!(matches!(
+            err,
+            CartridgeError::HeaderError {
+                reason: HeaderErrorReason::InconsistentSize {
+                    section: RomSection::PrgRom,
+                    size: 0x40_0000,
+                },
+            }
+        ));
+    }
+
+    /// a NES 2.0 header for NROM with the size bytes 4, 5 and 9
+    fn nes2_header(prg: u8, chr: u8, high_nibbles: u8) -> [u8; 16] {
+        [
+            0x4E,
+            0x45,
+            0x53,
+            0x1A,
+            prg,
+            chr,
+            0,
+            0x08,
+            0,
+            high_nibbles,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    fn rom_sizes(header: [u8; 16]) -> Result<(u64, u64), CartridgeError> {
+        let header = INesHeader::from_bytes(header)?;
+        header.check_sizes()?;
+        Ok((header.prg_rom_size, header.chr_rom_size))
+    }
+
+    #[test]
+    fn nes2_rom_sizes_in_banks() -> Result<(), CartridgeError> {
+        assert_eq!(rom_sizes(nes2_header(2, 1, 0x00))?, (0x8000, 0x2000));
+        assert_eq!(
+            rom_sizes(nes2_header(0xFF, 0xFF, 0x00))?,
+            (0x3F_C000, 0x1F_E000)
+        );
+        // the high nibbles are in byte 9, CHR ROM can be 256 banks or more in the header
+        assert_eq!(
+            INesHeader::from_bytes(nes2_header(0x10, 0x02, 0x10))?.chr_rom_size,
+            0x102 * 0x2000
+        );
+        // CHR RAM
+        assert_eq!(rom_sizes(nes2_header(1, 0, 0x00))?, (0x4000, 0));
+        assert!(INesHeader::from_bytes(nes2_header(1, 0, 0x00))?.is_chr_ram);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nes2_rom_sizes_exponent_multiplier() -> Result<(), CartridgeError> {
+        // 2^15 * 1 PRG and 2^13 * 1 CHR
+        assert_eq!(
+            rom_sizes(nes2_header(15 << 2, 13 << 2, 0xFF))?,
+            (0x8000, 0x2000)
+        );
+        // 2^14 * 3 PRG and 2^13 * 5 CHR
+        assert_eq!(
+            rom_sizes(nes2_header(14 << 2 | 1, 13 << 2 | 2, 0xFF))?,
+            (0xC000, 0xA000)
+        );
+        // only one of them in the exponent notation
+        assert_eq!(
+            rom_sizes(nes2_header(2, 13 << 2 | 3, 0xF0))?,
+            (0x8000, 0xE000)
+        );
+        assert_eq!(rom_sizes(nes2_header(14 << 2, 3, 0x0F))?, (0x4000, 0x6000));
+
+        // the largest values don't overflow
+        let header = INesHeader::from_bytes(nes2_header(0xFF, 0xFF, 0xFF))?;
+        assert_eq!(header.prg_rom_size, u64::MAX);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nes2_unsupported_rom_sizes() {
+        let inconsistent = |header, section, size| {
+            let err = rom_sizes(header).expect_err("the size is not supported");
+            assert!(
+                matches!(
+                    err,
+                    CartridgeError::HeaderError {
+                        reason: HeaderErrorReason::InconsistentSize { section: s, size: z },
+                    } if s == section && z == size
+                ),
+                "{}",
+                err
+            );
+        };
+
+        // 8KB of PRG ROM, not a whole bank
+        inconsistent(nes2_header(13 << 2, 1, 0x0F), RomSection::PrgRom, 0x2000);
+        // 2^14 * 3 CHR ROM is whole banks, but 2^12 * 3 isn't
+        inconsistent(
+            nes2_header(1, 12 << 2 | 1, 0xF0),
+            RomSection::ChrRom,
+            0x3000,
+        );
+        // more banks than the mappers take
+        inconsistent(nes2_header(23 << 2, 1, 0x0F), RomSection::PrgRom, 0x80_0000);
+        inconsistent(nes2_header(0xFF, 0xFF, 0xFF), RomSection::PrgRom, u64::MAX);
+        // no PRG ROM
+        inconsistent(nes2_header(0, 1, 0x00), RomSection::PrgRom, 0);
+    }
+
+    #[test]
+    fn exponent_sizes_are_read_from_the_file() -> Result<(), CartridgeError> {
+        let mut data = nes2_header(15 << 2, 13 << 2, 0xFF).to_vec();
+        data.resize(16 + 0x8000, 0xAA);
+        data.resize(16 + 0x8000 + 0x2000, 0xBB);
+
+        let cartridge = Cartridge::from_bytes(&data)?;
+        assert_eq!(cartridge.prg_data.len(), 0x8000);
+        assert_eq!(cartridge.chr_data.len(), 0x2000);
+        assert_eq!(cartridge.read(0x8000, Device::Cpu), 0xAA);
+        assert_eq!(cartridge.read(0x0000, Device::Ppu), 0xBB);
+
+        // the size mismatch reports the size declared in the header
+        data.extend_from_slice(&[0; 0x4000]);
+        let err = Cartridge::from_bytes(&data).err().unwrap();
+        assert!(matches!(
+            err,
+            CartridgeError::SizeMismatch {
+                file_size: 0xE010,
+                expected: 0xA010,
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The file is 57360 bytes, but the header declares 40976 bytes of data \
+            (header, trainer, PRG and CHR ROM)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn trainer_loaded_at_7000() -> Result<(), CartridgeError> {
+        let mut data = synthetic_rom(true, 0);
+        // MMC1, as NROM doesn't have PRG RAM, with the trainer flag
+        data[6] = 0x14;
+        // 8KB of PRG RAM
+        data[10] = 0x07;
+        let trainer = (0..0x200).map(|i| i as u8).collect::<Vec<_>>();
+        data.splice(16..16, trainer.iter().copied());
+
+        let mut cartridge = Cartridge::from_bytes(&data)?;
+        // enable the PRG RAM, 5 zero bits to the PRG bank register
+        for _ in 0..5 {
+            cartridge.write(0xE000, 0, Device::Cpu);
+        }
+        assert_eq!(cartridge.read(0x6FFF, Device::Cpu), 0);
+        for (i, &byte) in trainer.iter().enumerate() {
+            assert_eq!(cartridge.read(0x7000 + i as u16, Device::Cpu), byte);
+        }
+        assert_eq!(cartridge.read(0x7200, Device::Cpu), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_messages_context() {
+        use std::error::Error;
+
+        let mut data = synthetic_rom(false, 0);
+        // mapper 5
+        data[6] = 0x50;
+        let err = Cartridge::from_bytes(&data).err().unwrap();
+        assert!(matches!(err, CartridgeError::MapperNotImplemented(5)));
+        assert_eq!(err.to_string(), "Mapper 5 (MMC5) is not yet implemented");
+
+        let err = Cartridge::from_file("./file/does/not/exists.nes")
+            .err()
+            .unwrap();
+        let source = err.source().expect("io errors should be the source");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+    }
+}
+
+#[cfg(test)]
+mod patch_tests {
+    use super::super::{apply_patch, patch::crc32, Cartridge, CartridgeError};
+    use std::path::Path;
+
+    /// build an IPS patch from the records `(offset, data)`, if `data` has one byte
+    /// and `rle_count` is non zero, an RLE record is written instead
+    fn ips(records: &[(u32, &[u8], u16)], truncate: Option<u32>) -> Vec<u8> {
+        let mut patch = b"PATCH".to_vec();
+
+        for &(offset, data, rle_count) in records {
+            patch.extend_from_slice(&offset.to_be_bytes()[1..]);
+            if rle_count != 0 {
+                patch.extend_from_slice(&[0, 0]);
+                patch.extend_from_slice(&rle_count.to_be_bytes());
+                patch.push(data[0]);
+            } else {
+                patch.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                patch.extend_from_slice(data);
+            }
+        }
+
+        patch.extend_from_slice(b"EOF");
+        if let Some(size) = truncate {
+            patch.extend_from_slice(&size.to_be_bytes()[1..]);
+        }
+
+        patch
+    }
+
+    fn bps_number(patch: &mut Vec<u8>, mut number: usize) {
+        loop {
+            let x = (number & 0x7F) as u8;
+            number >>= 7;
+            if number == 0 {
+                patch.push(0x80 | x);
+                break;
+            }
+            patch.push(x);
+            number -= 1;
+        }
+    }
+
+    enum BpsAction<'a> {
+        SourceRead(usize),
+        TargetRead(&'a [u8]),
+        SourceCopy(usize, isize),
+        TargetCopy(usize, isize),
+    }
+
+    fn bps(
+        source: &[u8],
+        target: &[u8],
+        actions: &[BpsAction],
+        target_crc: Option<u32>,
+    ) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        bps_number(&mut patch, source.len());
+        bps_number(&mut patch, target.len());
+        // metadata
+        bps_number(&mut patch, 3);
+        patch.extend_from_slice(b"abc");
+
+        let relative = |patch: &mut Vec<u8>, offset: isize| {
+            bps_number(patch, (offset.unsigned_abs() << 1) | (offset < 0) as usize)
+        };
+
+        for action in actions {
+            match *action {
+                BpsAction::SourceRead(len) => bps_number(&mut patch, (len - 1) << 2),
+                BpsAction::TargetRead(data) => {
+                    bps_number(&mut patch, (data.len() - 1) << 2 | 1);
+                    patch.extend_from_slice(data);
+                }
+                BpsAction::SourceCopy(len, offset) => {
+                    bps_number(&mut patch, (len - 1) << 2 | 2);
+                    relative(&mut patch, offset);
+                }
+                BpsAction::TargetCopy(len, offset) => {
+                    bps_number(&mut patch, (len - 1) << 2 | 3);
+                    relative(&mut patch, offset);
+                }
+            }
+        }
+
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&target_crc.unwrap_or_else(|| crc32(target)).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+
+        patch
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn unknown_patch_format() {
+        let err = apply_patch(&[1, 2, 3], b"NOT A PATCH").unwrap_err();
+        assert!(matches!(err, CartridgeError::InvalidPatch));
+    }
+
+    #[test]
+    fn ips_records() -> Result<(), CartridgeError> {
+        let rom = [0u8; 16];
+        let patch = ips(&[(1, &[1, 2, 3], 0), (8, &[0xAA], 4)], None);
+
+        let result = apply_patch(&rom, &patch)?;
+        assert_eq!(
+            result,
+            [0, 1, 2, 3, 0, 0, 0, 0, 0xAA, 0xAA, 0xAA, 0xAA, 0, 0, 0, 0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ips_grow_file() -> Result<(), CartridgeError> {
+        let rom = [0xFFu8; 4];
+        let patch = ips(&[(6, &[1, 2], 0), (8, &[3], 2)], None);
+
+        let result = apply_patch(&rom, &patch)?;
+        assert_eq!(result, [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 1, 2, 3, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ips_truncate() -> Result<(), CartridgeError> {
+        let rom = [0xFFu8; 8];
+        let patch = ips(&[(0, &[1], 0)], Some(3));
+
+        let result = apply_patch(&rom, &patch)?;
+        assert_eq!(result, [1, 0xFF, 0xFF]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ips_eof_offset() -> Result<(), CartridgeError> {
+        // `EOF` as an offset is a valid record, when it's not at the end of the patch
+        const EOF_OFFSET: u32 = 0x454F46;
+        let rom = vec![0u8; EOF_OFFSET as usize + 4];
+        let patch = ips(&[(EOF_OFFSET, &[1, 2], 0)], None);
+
+        let result = apply_patch(&rom, &patch)?;
+        assert_eq!(result.len(), rom.len());
+        assert_eq!(result[EOF_OFFSET as usize - 1..], [0, 1, 2, 0, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ips_trailing_junk() -> Result<(), CartridgeError> {
+        let rom = [0xFFu8; 8];
+
+        let mut patch = ips(&[(1, &[1, 2], 0)], None);
+        patch.extend_from_slice(b"junk after the footer");
+        assert_eq!(
+            apply_patch(&rom, &patch)?,
+            [0xFF, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        // junk after the truncation size is ignored, and the size too
+        let mut patch = ips(&[(1, &[1, 2], 0)], Some(3));
+        patch.extend_from_slice(&[0; 5]);
+        assert_eq!(
+            apply_patch(&rom, &patch)?,
+            [0xFF, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ips_incomplete() {
+        let mut patch = ips(&[(0, &[1, 2, 3, 4], 0)], None);
+        // remove `EOF` and part of the data
+        patch.truncate(patch.len() - 5);
+
+        let err = apply_patch(&[0; 8], &patch).unwrap_err();
+        assert!(matches!(err, CartridgeError::InvalidPatch));
+    }
+
+    #[test]
+    fn bps_all_actions() -> Result<(), CartridgeError> {
+        let source = b"0123456789";
+        // larger than the source
+        let target = b"0123ab67896789678945";
+        let patch = bps(
+            source,
+            target,
+            &[
+                BpsAction::SourceRead(4),
+                BpsAction::TargetRead(b"ab"),
+                BpsAction::SourceCopy(4, 6),
+                // copy from `6789` (written by the last action), overlapping the output
+                BpsAction::TargetCopy(8, 6),
+                BpsAction::SourceCopy(2, -6),
+            ],
+            None,
+        );
+
+        let result = apply_patch(source, &patch)?;
+        assert_eq!(result, target);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bps_wrong_source() {
+        let source = b"0123456789";
+        let target = b"0123";
+        let patch = bps(source, target, &[BpsAction::SourceRead(4)], None);
+
+        let err = apply_patch(b"0123456780", &patch).unwrap_err();
+        if let CartridgeError::PatchChecksumMismatch { expected, found } = err {
+            assert_eq!(expected, crc32(source));
+            assert_eq!(found, crc32(b"0123456780"));
+        } else {
+            panic!("Should get checksum error, got {}", err);
+        }
+    }
+
+    #[test]
+    fn bps_wrong_target_checksum() {
+        let source = b"0123456789";
+        let target = b"0123";
+        let patch = bps(
+            source,
+            target,
+            &[BpsAction::SourceRead(4)],
+            Some(0x12345678),
+        );
+
+        let err = apply_patch(source, &patch).unwrap_err();
+        if let CartridgeError::PatchChecksumMismatch { expected, found } = err {
+            assert_eq!(expected, 0x12345678);
+            assert_eq!(found, crc32(target));
+        } else {
+            panic!("Should get checksum error, got {}", err);
+        }
+    }
+
+    #[test]
+    fn bps_corrupted_patch() {
+        let source = b"0123456789";
+        let mut patch = bps(
+            source,
+            b"01ab",
+            &[BpsAction::SourceRead(2), BpsAction::TargetRead(b"ab")],
+            None,
+        );
+        // modify the `TargetRead` data
+        let index = patch.len() - 13;
+        patch[index] = b'c';
+
+        let err = apply_patch(source, &patch).unwrap_err();
+        assert!(matches!(err, CartridgeError::PatchChecksumMismatch { .. }));
+    }
+
+    #[test]
+    fn bps_huge_target_size() {
+        let source = b"0123456789";
+        // only the header, with valid source and patch checksums
+        for target_size in [1 << 40, usize::MAX - 0xFF] {
+            let mut patch = b"BPS1".to_vec();
+            bps_number(&mut patch, source.len());
+            bps_number(&mut patch, target_size);
+            bps_number(&mut patch, 0);
+            patch.extend_from_slice(&crc32(source).to_le_bytes());
+            patch.extend_from_slice(&0u32.to_le_bytes());
+            patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+
+            let err = apply_patch(source, &patch).unwrap_err();
+            assert!(
+                matches!(err, CartridgeError::InvalidPatch),
+                "target size {target_size}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn bps_source_copy_out_of_range() {
+        let source = b"0123456789";
+        let patch = bps(
+            source,
+            b"01234567",
+            &[
+                BpsAction::SourceRead(4),
+                BpsAction::SourceCopy(4, isize::MAX),
+            ],
+            None,
+        );
+
+        let err = apply_patch(source, &patch).unwrap_err();
+        assert!(matches!(err, CartridgeError::InvalidPatch));
+    }
+
+    #[test]
+    fn cartridge_from_file_with_patch() -> Result<(), CartridgeError> {
+        let patch_path = std::env::temp_dir().join("plastic_test_creation_patch.ips");
+        // modify the first byte of PRG
+        std::fs::write(&patch_path, ips(&[(16, &[0x12], 0)], None))?;
+
+        let cartridge = Cartridge::from_file_with_patch(
+            "../test_roms/cartridge_tests/test_creation.nes",
+            &patch_path,
+        );
+        std::fs::remove_file(&patch_path)?;
+        let cartridge = cartridge?;
+
+        assert_eq!(cartridge.prg_data[0], 0x12);
+        assert!(cartridge.prg_data[1..].iter().all(|&c| c == 0xFF));
+        // the SRAM is named after the patched ROM, not the patch
+        assert_eq!(
+            cartridge.cartridge_path(),
+            Some(Path::new(&format!(
+                "../test_roms/cartridge_tests/test_creation.{:08X}.nes",
+                cartridge.rom_crc32()
+            )))
+        );
+        assert_ne!(
+            cartridge.rom_crc32(),
+            Cartridge::from_file("../test_roms/cartridge_tests/test_creation.nes")?.rom_crc32()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::super::mapper::{Mapper, MapperInvariant, MappingResult};
+    use super::super::{Cartridge, MappingViolation};
+    use crate::common::{Bus, Device};
+    use crate::diagnostics::Diagnostic;
+
+    /// A mapper with bugs selected by writes to `$8000`:
+    /// bits 0-3 select the PRG bank for the whole `$8000-$FFFF`, bit 6 unmaps
+    /// the vectors, and bit 7 makes the CHR writable
+    #[derive(Default)]
+    struct BrokenMapper {
+        prg_bank: usize,
+        vectors_unmapped: bool,
+        chr_writable: bool,
+    }
+
+    impl Mapper for BrokenMapper {
+        fn init(&mut self, _pgr_count: u8, _is_chr_ram: bool, _chr_count: u8, _sram_count: u8) {}
+
+        fn map_read(&self, address: u16, device: Device) -> MappingResult {
+            match (device, address) {
+                (Device::Cpu, 0xFFFA..=0xFFFF) if self.vectors_unmapped => MappingResult::Denied,
+                (Device::Cpu, 0x8000..=0xFFFF) => {
+                    MappingResult::Allowed(self.prg_bank * 0x4000 + (address & 0x3FFF) as usize)
+                }
+                (Device::Cpu, _) => MappingResult::Denied,
+                (Device::Ppu, _) => MappingResult::Allowed(address as usize),
+            }
+        }
+
+        fn map_write(&mut self, address: u16, data: u8, device: Device) -> MappingResult {
+            match (device, address) {
+                (Device::Cpu, 0x8000..=0xFFFF) => {
+                    self.prg_bank = data as usize & 0xF;
+                    self.vectors_unmapped = data & 0x40 != 0;
+                    self.chr_writable = data & 0x80 != 0;
+                    MappingResult::Denied
+                }
+                (Device::Ppu, _) if self.chr_writable => MappingResult::Allowed(address as usize),
+                _ => MappingResult::Denied,
+            }
+        }
+
+        fn expected_invariants(&self) -> Vec<MapperInvariant> {
+            vec![MapperInvariant::PrgRomFixed {
+                address: 0xC000,
+                offset: 0,
+            }]
+        }
+
+        fn save_state_size(&self) -> usize {
+            3
+        }
+
+        fn save_state(&self) -> Vec<u8> {
+            vec![
+                self.prg_bank as u8,
+                self.vectors_unmapped as u8,
+                self.chr_writable as u8,
+            ]
+        }
+
+        fn load_state(&mut self, data: Vec<u8>) {
+            self.prg_bank = data[0] as usize;
+            self.vectors_unmapped = data[1] != 0;
+            self.chr_writable = data[2] != 0;
+        }
+    }
+
+    /// NROM sized cartridge (16KB PRG, 8KB CHR ROM) with the broken mapper
+    fn broken_cartridge() -> Cartridge {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.resize(16 + 0x4000 + 0x2000, 0);
+        let mut cartridge = Cartridge::from_bytes(&data).unwrap();
+        cartridge.mapper = Box::<BrokenMapper>::default();
+        cartridge.set_mapper_audit(true);
+        cartridge
+    }
+
+    fn violations(cartridge: &mut Cartridge, data: u8) -> Vec<MappingViolation> {
+        cartridge.write(0x8000, data, Device::Cpu);
+        cartridge
+            .take_diagnostics()
+            .into_iter()
+            .map(|diagnostic| match diagnostic {
+                Diagnostic::MapperAudit {
+                    mapper: 0,
+                    register: 0x8000,
+                    data: written,
+                    violation,
+                } if written == data => violation,
+                _ => panic!("unexpected diagnostic {:?}", diagnostic),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consistent_mapping() {
+        let mut cartridge = broken_cartridge();
+        assert_eq!(violations(&mut cartridge, 0x00), vec![]);
+    }
+
+    #[test]
+    fn prg_bank_out_of_range() {
+        let mut cartridge = broken_cartridge();
+        let violations = violations(&mut cartridge, 0x01);
+
+        assert!(violations.contains(&MappingViolation::PrgRomOutOfRange {
+            address: 0x8000,
+            offset: 0x4000
+        }));
+        assert!(violations.contains(&MappingViolation::PrgRomOutOfRange {
+            address: 0xFFFF,
+            offset: 0x7FFF
+        }));
+        assert!(violations.contains(&MappingViolation::PrgRomNotFixed {
+            address: 0xC000,
+            expected: 0,
+            found: Some(0x4000)
+        }));
+    }
+
+    #[test]
+    fn vectors_unmapped() {
+        let mut cartridge = broken_cartridge();
+        assert_eq!(
+            violations(&mut cartridge, 0x40),
+            vec![
+                MappingViolation::PrgRomUnmapped { address: 0xFFFF },
+                MappingViolation::PrgRomUnmapped { address: 0xFFFA },
+                MappingViolation::PrgRomUnmapped { address: 0xFFFC },
+                MappingViolation::PrgRomUnmapped { address: 0xFFFE },
+            ]
+        );
+    }
+
+    #[test]
+    fn chr_rom_writable() {
+        let mut cartridge = broken_cartridge();
+        let violations = violations(&mut cartridge, 0x80);
+
+        assert_eq!(violations.len(), 16);
+        assert_eq!(
+            violations[0],
+            MappingViolation::ChrRomWritable { address: 0x0000 }
+        );
+        // the probing writes don't change the mapper state
+        assert_eq!(cartridge.mapper.save_state(), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut cartridge = broken_cartridge();
+        cartridge.set_mapper_audit(false);
+        cartridge.write(0x8000, 0xC1, Device::Cpu);
+        assert!(cartridge.take_diagnostics().is_empty());
+
+        let diagnostic = Diagnostic::MapperAudit {
+            mapper: 4,
+            register: 0xE001,
+            data: 0x12,
+            violation: MappingViolation::PrgRomNotFixed {
+                address: 0xE000,
+                expected: 0x1E000,
+                found: None,
+            },
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "mapper 4 after the write of $12 to $E001: CPU $E000 should be the fixed bank \
+             at $1E000, but is not mapped"
+        );
+    }
 }
@@ -0,0 +1,38 @@
+use crate::common::Region;
+
+/// The CPU/PPU timing the game was made for, declared in byte 12 of the NES 2.0 header.
+///
+/// iNES 1.0 files don't have reliable timing information, see [`NES::timing_mode`](crate::NES::timing_mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingMode {
+    /// RP2C02 (NTSC consoles)
+    Ntsc,
+    /// RP2C07 (PAL consoles)
+    Pal,
+    /// Works on both NTSC and PAL consoles
+    MultiRegion,
+    /// UA6538 (Dendy famiclones)
+    Dendy,
+}
+
+impl TimingMode {
+    pub(crate) fn from_code(code: u8) -> Self {
+        match code & 0x3 {
+            0 => Self::Ntsc,
+            1 => Self::Pal,
+            2 => Self::MultiRegion,
+            _ => Self::Dendy,
+        }
+    }
+
+    /// The region to run the game with, multi-region games run as NTSC.
+    ///
+    /// Dendy consoles are not emulated, they run with 50 frames per second like
+    /// PAL consoles, so [`Region::Pal`] is the closest.
+    pub fn region(&self) -> Region {
+        match self {
+            Self::Ntsc | Self::MultiRegion => Region::Ntsc,
+            Self::Pal | Self::Dendy => Region::Pal,
+        }
+    }
+}
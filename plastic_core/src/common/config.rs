@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use super::save_state::{Savable, SaveError};
+use super::Region;
+
+/// The runtime settings of the emulator that affect the emulation result,
+/// saved in the header of save states, see [`NES::config`](crate::NES::config).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EmulatorConfig {
+    /// The region of the console, see [`NES::set_region`](crate::NES::set_region)
+    pub region: Region,
+    /// The CPU/PPU alignment applied on the last power-up, see
+    /// [`NES::set_cpu_ppu_alignment`](crate::NES::set_cpu_ppu_alignment)
+    pub cpu_ppu_alignment: u8,
+}
+
+impl Savable for EmulatorConfig {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
+        bincode::serialize_into(writer, self).map_err(|err| match *err {
+            bincode::ErrorKind::Io(err) => SaveError::IoError(err),
+            _ => SaveError::SerializationError,
+        })
+    }
+
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
+        *self = bincode::deserialize_from(reader).map_err(|err| match *err {
+            bincode::ErrorKind::Io(err) => SaveError::IoError(err),
+            _ => SaveError::SerializationError,
+        })?;
+
+        Ok(())
+    }
+}
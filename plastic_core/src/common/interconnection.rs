@@ -12,8 +12,16 @@ pub trait APUCPUConnection {
     fn submit_dmc_buffer_byte(&mut self, byte: u8);
 }
 
+/// The sources of IRQ.
+///
+/// The mappers notify the CPU when their IRQ pin changes with `is_irq_change_requested`,
+/// while the APU IRQ is a level (`irq_level`) that the CPU checks on every poll.
 pub trait CPUIrqProvider {
     fn is_irq_change_requested(&self) -> bool;
     fn irq_pin_state(&self) -> bool;
     fn clear_irq_request_pin(&mut self);
+
+    fn irq_level(&self) -> bool {
+        false
+    }
 }
@@ -1,11 +1,19 @@
 #[macro_use]
 mod bus;
+mod config;
 mod mirroring;
+mod region;
 
 pub mod interconnection;
 pub mod save_state;
+mod state_sync;
 
 pub use bus::{Bus, Device};
+pub use config::EmulatorConfig;
 pub use mirroring::{MirroringMode, MirroringProvider};
-
-pub const CPU_FREQ: f64 = 1.789773 * 1E6;
+pub use region::{
+    Region, CPU_FREQ_NTSC, CPU_FREQ_PAL, CYCLES_PER_FRAME_NTSC, CYCLES_PER_FRAME_NTSC_EVEN,
+    CYCLES_PER_FRAME_NTSC_ODD, CYCLES_PER_FRAME_PAL, FRAME_RATE_NTSC, FRAME_RATE_PAL, NTSC_FPS,
+    PAL_FPS,
+};
+pub use state_sync::SyncBaseline;
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// The master clock of NTSC consoles in Hz, `236.25 / 11` MHz
+const MASTER_CLOCK_NTSC: f64 = 236.25 / 11. * 1E6;
+/// The master clock of PAL consoles in Hz
+const MASTER_CLOCK_PAL: f64 = 26.601712 * 1E6;
+
+/// The CPU clock frequency of NTSC consoles in Hz, the master clock divided by 12
+pub const CPU_FREQ_NTSC: f64 = MASTER_CLOCK_NTSC / 12.;
+/// The CPU clock frequency of PAL consoles in Hz, the master clock divided by 16
+pub const CPU_FREQ_PAL: f64 = MASTER_CLOCK_PAL / 16.;
+
+/// PPU dots in a frame, 262 scanlines of 341 dots
+const PPU_DOTS_PER_FRAME_NTSC: u32 = 262 * 341;
+/// PPU dots in a frame, 312 scanlines of 341 dots
+const PPU_DOTS_PER_FRAME_PAL: u32 = 312 * 341;
+
+/// CPU cycles in an even NTSC frame, the PPU runs 3 dots per CPU cycle
+pub const CYCLES_PER_FRAME_NTSC_EVEN: f64 = PPU_DOTS_PER_FRAME_NTSC as f64 / 3.;
+/// CPU cycles in an odd NTSC frame, one dot shorter than even frames when rendering is enabled
+pub const CYCLES_PER_FRAME_NTSC_ODD: f64 = (PPU_DOTS_PER_FRAME_NTSC - 1) as f64 / 3.;
+/// The average CPU cycles in an NTSC frame, `29780.5`
+pub const CYCLES_PER_FRAME_NTSC: f64 =
+    (CYCLES_PER_FRAME_NTSC_EVEN + CYCLES_PER_FRAME_NTSC_ODD) / 2.;
+/// CPU cycles in a PAL frame, the PPU runs 3.2 dots per CPU cycle and doesn't skip dots
+pub const CYCLES_PER_FRAME_PAL: f64 = PPU_DOTS_PER_FRAME_PAL as f64 / 3.2;
+
+/// Number of video frames per second on NTSC consoles, `~60.0988`
+pub const NTSC_FPS: f64 = CPU_FREQ_NTSC / CYCLES_PER_FRAME_NTSC;
+/// Number of video frames per second on PAL consoles, `~50.007`
+pub const PAL_FPS: f64 = CPU_FREQ_PAL / CYCLES_PER_FRAME_PAL;
+
+/// Same as [`NTSC_FPS`]
+pub const FRAME_RATE_NTSC: f64 = NTSC_FPS;
+/// Same as [`PAL_FPS`]
+pub const FRAME_RATE_PAL: f64 = PAL_FPS;
+
+/// The TV system/region of the console, which affects the timing of the components.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Region {
+    /// NTSC consoles (North America and Japan), using the 2A03 CPU/APU
+    #[default]
+    Ntsc,
+    /// PAL consoles (Europe and Australia), using the 2A07 CPU/APU
+    Pal,
+}
+
+impl Region {
+    /// The CPU clock frequency in Hz
+    pub fn cpu_freq(&self) -> f64 {
+        match self {
+            Region::Ntsc => CPU_FREQ_NTSC,
+            Region::Pal => CPU_FREQ_PAL,
+        }
+    }
+
+    /// The CPU clock frequency in Hz as an exact fraction `(numerator, denominator)`,
+    /// used where floating point rounding would accumulate over time
+    pub fn cpu_freq_ratio(&self) -> (u64, u64) {
+        match self {
+            // 236.25 MHz / 11 / 12
+            Region::Ntsc => (236_250_000, 11 * 12),
+            // 26.601712 MHz / 16
+            Region::Pal => (26_601_712, 16),
+        }
+    }
+
+    /// The number of video frames per second
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Region::Ntsc => NTSC_FPS,
+            Region::Pal => PAL_FPS,
+        }
+    }
+}
@@ -3,9 +3,102 @@ use std::error::Error;
 use std::fmt::Display;
 use std::io::{Error as ioError, Read, Write};
 
+use super::{EmulatorConfig, Region};
+
+/// A component that can save and load its state.
+///
+/// The reader and writer are trait objects, so this can be used as a trait object as well.
 pub trait Savable {
-    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError>;
-    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError>;
+    fn save(&self, writer: &mut dyn Write) -> Result<(), SaveError>;
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError>;
+}
+
+/// The first bytes of every state, to reject files that are not states early
+const STATE_MAGIC: [u8; 4] = *b"PLST";
+/// Increased every time the format of the state changes, states of other versions
+/// are rejected with [`SaveError::VersionMismatch`] instead of loading garbage
+pub(crate) const STATE_FORMAT_VERSION: u16 = 2;
+
+/// The start of a state, with the format version and the ROM it was saved from,
+/// checked before any of the components is loaded
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StateHeader {
+    pub mapper_id: u16,
+    /// see [`NES::rom_crc32`](crate::NES::rom_crc32)
+    pub rom_crc32: u32,
+}
+
+impl Savable for StateHeader {
+    fn save(&self, writer: &mut dyn Write) -> Result<(), SaveError> {
+        writer.write_all(&STATE_MAGIC)?;
+        writer.write_all(&STATE_FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.mapper_id.to_le_bytes())?;
+        writer.write_all(&self.rom_crc32.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != STATE_MAGIC {
+            return Err(SaveError::NotAState);
+        }
+
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != STATE_FORMAT_VERSION {
+            return Err(SaveError::VersionMismatch {
+                found: version,
+                expected: STATE_FORMAT_VERSION,
+            });
+        }
+
+        let mut mapper_id = [0; 2];
+        reader.read_exact(&mut mapper_id)?;
+        let mut rom_crc32 = [0; 4];
+        reader.read_exact(&mut rom_crc32)?;
+
+        self.mapper_id = u16::from_le_bytes(mapper_id);
+        self.rom_crc32 = u32::from_le_bytes(rom_crc32);
+
+        Ok(())
+    }
+}
+
+/// A component that can export its state as JSON, see
+/// [`NES::export_state_json`](crate::NES::export_state_json).
+#[cfg(feature = "state-json")]
+pub trait JsonSavable {
+    fn to_json(&self) -> serde_json::Value;
+    fn load_json(&mut self, value: serde_json::Value) -> Result<(), SaveError>;
+}
+
+/// Serialize `state` to a JSON value, used by most [`JsonSavable`] implementations
+#[cfg(feature = "state-json")]
+pub fn to_json_value<S: serde::Serialize>(state: &S) -> serde_json::Value {
+    serde_json::to_value(state).expect("state can be serialized to JSON")
+}
+
+/// Deserialize a state from a JSON value, used by most [`JsonSavable`] implementations
+#[cfg(feature = "state-json")]
+pub fn from_json_value<S: serde::de::DeserializeOwned>(
+    value: serde_json::Value,
+) -> Result<S, SaveError> {
+    serde_json::from_value(value).map_err(|_| SaveError::SerializationError)
+}
+
+/// Take `field` out of a JSON object, fails if `value` is not an object or doesn't have it
+#[cfg(feature = "state-json")]
+pub fn take_json_field(
+    value: &mut serde_json::Value,
+    field: &str,
+) -> Result<serde_json::Value, SaveError> {
+    value
+        .get_mut(field)
+        .map(serde_json::Value::take)
+        .ok_or(SaveError::SerializationError)
 }
 
 /// Error happening when saving/loading a state
@@ -18,6 +111,24 @@ pub enum SaveError {
     ContainExtraData,
     /// Error happened during serialization/deserialization, faulty data
     SerializationError,
+    /// The data doesn't start like a state
+    NotAState,
+    /// The state was saved in a different format version, by another version of the emulator
+    VersionMismatch { found: u16, expected: u16 },
+    /// The state was saved while running a different ROM
+    RomMismatch,
+    /// The state was saved from a console of a different [`Region`]
+    RegionMismatch { found: Region, expected: Region },
+    /// The state was saved with a different [`EmulatorConfig`], only returned when
+    /// [`NES::set_strict_state_config`](crate::NES::set_strict_state_config) is enabled
+    ConfigMismatch {
+        saved: EmulatorConfig,
+        current: EmulatorConfig,
+    },
+    /// The cartridge doesn't have battery backed save RAM
+    NoBatteryBackedRam,
+    /// The [`Movie`](crate::movie::Movie) has less than `frame` frames
+    MovieTooShort { frame: u64, length: u64 },
 }
 
 impl From<ioError> for SaveError {
@@ -36,6 +147,41 @@ impl Display for SaveError {
                 write!(f, "Contain Extra Data after the end of the file")
             }
             SaveError::SerializationError => write!(f, "Serialization Error"),
+            SaveError::NotAState => write!(f, "The data is not a save state"),
+            SaveError::VersionMismatch { found, expected } => {
+                write!(
+                    f,
+                    "Version mismatch, the state has format version {} but {} is supported",
+                    found, expected
+                )
+            }
+            SaveError::RomMismatch => {
+                write!(f, "The state was saved with a different ROM")
+            }
+            SaveError::RegionMismatch { found, expected } => {
+                write!(
+                    f,
+                    "Region mismatch, the state is for {:?} but the console is {:?}",
+                    found, expected
+                )
+            }
+            SaveError::ConfigMismatch { saved, current } => {
+                write!(
+                    f,
+                    "Config mismatch, the state was saved with {:?} but the emulator uses {:?}",
+                    saved, current
+                )
+            }
+            SaveError::NoBatteryBackedRam => {
+                write!(f, "The cartridge doesn't have battery backed save RAM")
+            }
+            SaveError::MovieTooShort { frame, length } => {
+                write!(
+                    f,
+                    "Can't reach frame {} of a movie of {} frames",
+                    frame, length
+                )
+            }
         }
     }
 }
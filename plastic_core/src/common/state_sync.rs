@@ -0,0 +1,217 @@
+//! Incremental state sync, see [`NES::state_sync_baseline`](crate::NES::state_sync_baseline)
+
+use super::save_state::SaveError;
+
+/// The section is the same as in the baseline
+const SECTION_UNCHANGED: u8 = 0;
+/// The section is XORed with the baseline, and the runs of zeros are skipped
+const SECTION_XOR: u8 = 1;
+/// The section is stored as is, when it has a different length than the baseline
+/// or the XOR doesn't make it smaller
+const SECTION_FULL: u8 = 2;
+
+/// A full state split into its sections (header, config, cartridge, CPU with the system
+/// RAM, PPU and APU), that the deltas of [`NES::state_sync_delta`](crate::NES::state_sync_delta)
+/// are relative to.
+///
+/// Both sides of the sync must have the same baseline, send it once with
+/// [`SyncBaseline::to_bytes`] and read it with [`SyncBaseline::from_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncBaseline {
+    sections: Vec<Vec<u8>>,
+}
+
+impl SyncBaseline {
+    pub(crate) fn new(sections: Vec<Vec<u8>>) -> Self {
+        Self { sections }
+    }
+
+    /// The full state, in the same format as [`NES::save_state`](crate::NES::save_state)
+    pub fn state(&self) -> Vec<u8> {
+        self.sections.concat()
+    }
+
+    /// Serialize the baseline, to send it to the other side of the sync
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_varint(&mut data, self.sections.len());
+        for section in &self.sections {
+            write_varint(&mut data, section.len());
+            data.extend_from_slice(section);
+        }
+        data
+    }
+
+    /// Read a baseline serialized with [`SyncBaseline::to_bytes`]
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, SaveError> {
+        let count = read_varint(&mut data)?;
+        let sections = (0..count)
+            .map(|_| {
+                let length = read_varint(&mut data)?;
+                Ok(take(&mut data, length)?.to_vec())
+            })
+            .collect::<Result<Vec<_>, SaveError>>()?;
+
+        if !data.is_empty() {
+            return Err(SaveError::ContainExtraData);
+        }
+
+        Ok(Self { sections })
+    }
+
+    /// Encode the difference between the `sections` of a state and the baseline
+    pub(crate) fn delta(&self, sections: &[Vec<u8>]) -> Result<Vec<u8>, SaveError> {
+        if sections.len() != self.sections.len() {
+            return Err(SaveError::SerializationError);
+        }
+
+        let mut delta = Vec::new();
+        for (section, base) in sections.iter().zip(&self.sections) {
+            if section == base {
+                delta.push(SECTION_UNCHANGED);
+                continue;
+            }
+
+            let xored = (section.len() == base.len()).then(|| encode_xor(section, base));
+            match xored {
+                Some(xored) if xored.len() < section.len() => {
+                    delta.push(SECTION_XOR);
+                    write_varint(&mut delta, xored.len());
+                    delta.extend_from_slice(&xored);
+                }
+                _ => {
+                    delta.push(SECTION_FULL);
+                    write_varint(&mut delta, section.len());
+                    delta.extend_from_slice(section);
+                }
+            }
+        }
+
+        Ok(delta)
+    }
+
+    /// Rebuild the full state from a delta created with [`SyncBaseline::delta`]
+    pub(crate) fn apply(&self, mut delta: &[u8]) -> Result<Vec<u8>, SaveError> {
+        let mut state = Vec::new();
+        for base in &self.sections {
+            let (&kind, rest) = delta.split_first().ok_or(SaveError::SerializationError)?;
+            delta = rest;
+
+            match kind {
+                SECTION_UNCHANGED => state.extend_from_slice(base),
+                SECTION_XOR => {
+                    let length = read_varint(&mut delta)?;
+                    decode_xor(take(&mut delta, length)?, base, &mut state)?;
+                }
+                SECTION_FULL => {
+                    let length = read_varint(&mut delta)?;
+                    state.extend_from_slice(take(&mut delta, length)?);
+                }
+                _ => return Err(SaveError::SerializationError),
+            }
+        }
+
+        if !delta.is_empty() {
+            return Err(SaveError::ContainExtraData);
+        }
+
+        Ok(state)
+    }
+}
+
+/// XOR `data` with `base` (same length), and encode the result as pairs of
+/// the number of zero bytes to skip and the number of bytes that follow
+fn encode_xor(data: &[u8], base: &[u8]) -> Vec<u8> {
+    let xored = data
+        .iter()
+        .zip(base)
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>();
+    let mut encoded = Vec::new();
+
+    let mut i = 0;
+    while i < xored.len() {
+        let zeros_start = i;
+        while i < xored.len() && xored[i] == 0 {
+            i += 1;
+        }
+
+        // single zeros are kept in the literals, they are smaller than a new pair
+        let literals_start = i;
+        while i < xored.len() && !(xored[i] == 0 && xored.get(i + 1).is_none_or(|&b| b == 0)) {
+            i += 1;
+        }
+
+        write_varint(&mut encoded, literals_start - zeros_start);
+        write_varint(&mut encoded, i - literals_start);
+        encoded.extend_from_slice(&xored[literals_start..i]);
+    }
+
+    encoded
+}
+
+/// Reverse of [`encode_xor`], appending the result to `output`
+fn decode_xor(mut encoded: &[u8], base: &[u8], output: &mut Vec<u8>) -> Result<(), SaveError> {
+    let mut position = 0;
+    while !encoded.is_empty() {
+        let zeros = read_varint(&mut encoded)?;
+        let length = read_varint(&mut encoded)?;
+        let literals = take(&mut encoded, length)?;
+
+        let unchanged = section(base, position, zeros)?;
+        output.extend_from_slice(unchanged);
+        position += zeros;
+
+        let changed = section(base, position, length)?;
+        output.extend(changed.iter().zip(literals).map(|(a, b)| a ^ b));
+        position += length;
+    }
+
+    if position != base.len() {
+        return Err(SaveError::SerializationError);
+    }
+
+    Ok(())
+}
+
+/// `length` bytes of `base` from `start`, fails if they are out of bounds
+fn section(base: &[u8], start: usize, length: usize) -> Result<&[u8], SaveError> {
+    start
+        .checked_add(length)
+        .and_then(|end| base.get(start..end))
+        .ok_or(SaveError::SerializationError)
+}
+
+/// Write `value` in 7 bits per byte, with the top bit set if more bytes follow
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<usize, SaveError> {
+    let mut value = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or(SaveError::SerializationError)?;
+        *input = rest;
+
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(SaveError::SerializationError)
+}
+
+/// Take the first `length` bytes of `input`
+fn take<'a>(input: &mut &'a [u8], length: usize) -> Result<&'a [u8], SaveError> {
+    if input.len() < length {
+        return Err(SaveError::SerializationError);
+    }
+    let (taken, rest) = input.split_at(length);
+    *input = rest;
+    Ok(taken)
+}
@@ -0,0 +1,183 @@
+//! Running two emulators in lockstep and reporting the first frame where they differ.
+//!
+//! This is useful for A/B testing changes to the emulator, for example running the same
+//! ROM with two different [`EmulatorConfig`](crate::EmulatorConfig)s, or from a state
+//! saved before and after a change.
+//!
+//! ```no_run
+//! use plastic_core::compare::LockstepRunner;
+//! use plastic_core::NES;
+//!
+//! let nes_a = NES::new("game.nes").unwrap();
+//! let nes_b = NES::new("game.nes").unwrap();
+//!
+//! let mut runner = LockstepRunner::new(nes_a, nes_b);
+//! if let Some(report) = runner.run_until_divergence(600, std::iter::repeat(0)) {
+//!     println!("diverged at frame {}: {:?}", report.frame, report);
+//! }
+//! ```
+
+use crate::cpu::CpuState;
+use crate::nes_display::{COLOR_BYTES_LEN, TV_WIDTH};
+use crate::{NESKey, NES};
+
+pub use crate::nes_display::frame_hash;
+
+/// Bounding box of the different pixels between two frames, the bounds are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelDiffBounds {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+/// Compute the bounding box of the different pixels of two RGB pixel buffers
+/// (as returned by [`NES::pixel_buffer`]), `None` if they are the same.
+pub fn pixel_diff_bounds(a: &[u8], b: &[u8]) -> Option<PixelDiffBounds> {
+    let mut bounds: Option<PixelDiffBounds> = None;
+
+    for (i, (pixel_a, pixel_b)) in a
+        .chunks_exact(COLOR_BYTES_LEN)
+        .zip(b.chunks_exact(COLOR_BYTES_LEN))
+        .enumerate()
+    {
+        if pixel_a == pixel_b {
+            continue;
+        }
+
+        let (x, y) = (i % TV_WIDTH, i / TV_WIDTH);
+        bounds = Some(match bounds {
+            Some(bounds) => PixelDiffBounds {
+                min_x: bounds.min_x.min(x),
+                min_y: bounds.min_y.min(y),
+                max_x: bounds.max_x.max(x),
+                max_y: bounds.max_y.max(y),
+            },
+            None => PixelDiffBounds {
+                min_x: x,
+                min_y: y,
+                max_x: x,
+                max_y: y,
+            },
+        });
+    }
+
+    bounds
+}
+
+/// Information about the first frame where the two emulators differ,
+/// the tuples contain the values of `(nes_a, nes_b)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// The frame number, counting from `0` since the creation of the runner
+    pub frame: u64,
+    pub frame_hashes: (u64, u64),
+    pub audio_samples: (usize, usize),
+    pub cpu_states: (CpuState, CpuState),
+    /// The different area of the frames, `None` if the frames are the same
+    pub pixel_diff: Option<PixelDiffBounds>,
+}
+
+/// Runs two [`NES`] instances frame by frame with the same inputs, and compares
+/// the frames, the number of audio samples and optionally the CPU registers after each frame.
+pub struct LockstepRunner {
+    nes_a: NES,
+    nes_b: NES,
+    compare_cpu_state: bool,
+    frame: u64,
+}
+
+impl LockstepRunner {
+    pub fn new(nes_a: NES, nes_b: NES) -> Self {
+        Self {
+            nes_a,
+            nes_b,
+            compare_cpu_state: true,
+            frame: 0,
+        }
+    }
+
+    /// Compare the CPU registers after each frame, enabled by default.
+    ///
+    /// Disable it when comparing emulators that are expected to have different
+    /// timing inside a frame but the same output.
+    pub fn set_compare_cpu_state(&mut self, compare: bool) {
+        self.compare_cpu_state = compare;
+    }
+
+    /// The number of frames run so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn nes_a(&self) -> &NES {
+        &self.nes_a
+    }
+
+    pub fn nes_b(&self) -> &NES {
+        &self.nes_b
+    }
+
+    /// Mutable access to both emulators, useful for changing something in the
+    /// middle of a run.
+    pub fn nes_mut(&mut self) -> (&mut NES, &mut NES) {
+        (&mut self.nes_a, &mut self.nes_b)
+    }
+
+    /// Run both emulators for up to `max_frames` frames, and stop at the first frame
+    /// where they differ.
+    ///
+    /// `inputs` contains the controller state of player 1 for each frame, each bit is a
+    /// [`NESKey`] (`NESKey::A as u8 | NESKey::Start as u8`), if it ends before `max_frames`,
+    /// no keys are pressed in the remaining frames.
+    ///
+    /// Only player 1 is driven by `inputs`, the controller of player 2 keeps its state,
+    /// which can be changed with [`LockstepRunner::nes_mut`] between runs.
+    pub fn run_until_divergence(
+        &mut self,
+        max_frames: u32,
+        inputs: impl IntoIterator<Item = u8>,
+    ) -> Option<DivergenceReport> {
+        let mut inputs = inputs.into_iter();
+
+        for _ in 0..max_frames {
+            let input = inputs.next().unwrap_or(0);
+            let frame = self.frame;
+            self.frame += 1;
+
+            let audio_a = Self::run_frame(&mut self.nes_a, input);
+            let audio_b = Self::run_frame(&mut self.nes_b, input);
+
+            let pixels_a = self.nes_a.pixel_buffer();
+            let pixels_b = self.nes_b.pixel_buffer();
+            let frame_hashes = (frame_hash(pixels_a), frame_hash(pixels_b));
+            let cpu_states = (self.nes_a.cpu_state(), self.nes_b.cpu_state());
+
+            let diverged = frame_hashes.0 != frame_hashes.1
+                || audio_a != audio_b
+                || (self.compare_cpu_state && cpu_states.0 != cpu_states.1);
+
+            if diverged {
+                return Some(DivergenceReport {
+                    frame,
+                    frame_hashes,
+                    audio_samples: (audio_a, audio_b),
+                    cpu_states,
+                    pixel_diff: pixel_diff_bounds(pixels_a, pixels_b),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Run one frame and return the number of audio samples generated
+    fn run_frame(nes: &mut NES, input: u8) -> usize {
+        for key in NESKey::ALL {
+            nes.set_controller_state(key, input & key as u8 != 0);
+        }
+        nes.clock_for_frame();
+        nes.audio_buffer().len()
+    }
+}
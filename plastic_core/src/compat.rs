@@ -0,0 +1,190 @@
+//! Detecting common reasons for a ROM not working, see [`NES::compatibility_report`].
+
+use crate::NES;
+use std::fmt;
+
+/// The CPU is considered stuck if it runs at most this many distinct `PC` values in a frame
+const STUCK_MAX_DISTINCT_PCS: u32 = 8;
+/// A game reading `$4015` at least this many times in a frame while running a small
+/// loop is considered busy waiting on it
+const POLLING_MIN_STATUS_READS: u32 = 100;
+const POLLING_MAX_DISTINCT_PCS: u32 = 16;
+/// Number of frames at the end of the run that must all be polling `$4015`
+const POLLING_FRAMES: u32 = 30;
+/// Writes to missing mapper registers less than this are ignored, some games
+/// do a few of these by mistake
+const UNSUPPORTED_WRITES_THRESHOLD: u32 = 16;
+
+/// A possible problem found by [`NES::compatibility_report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompatFinding {
+    /// The CPU never got past a small loop after reset and never enabled rendering,
+    /// usually the result of a wrong mapper, as the reset vector is read from the wrong bank.
+    StuckAfterReset {
+        /// The `PC` at the end of the run
+        pc: u16,
+        /// The most distinct `PC` values executed in a frame
+        distinct_pcs: u32,
+    },
+    /// The game is running, but never enabled rendering, it's probably stuck during initialization.
+    RenderingNeverEnabled,
+    /// The game wrote to addresses where the mapper doesn't have registers many times,
+    /// the ROM is probably using a different or unsupported mapper.
+    UnsupportedMapperWrites {
+        /// The first address written
+        address: u16,
+        count: u32,
+    },
+    /// The game is busy waiting on a bit of the APU status register (`$4015`), which
+    /// may depend on a feature that isn't emulated correctly (for example DMC or frame IRQ).
+    PollingApuStatus {
+        /// The `PC` at the end of the run
+        pc: u16,
+    },
+}
+
+impl fmt::Display for CompatFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompatFinding::StuckAfterReset { pc, distinct_pcs } => write!(
+                f,
+                "the CPU is stuck in a loop at ${:04X} ({} instructions) after reset, \
+                 the mapper is probably wrong",
+                pc, distinct_pcs
+            ),
+            CompatFinding::RenderingNeverEnabled => {
+                write!(
+                    f,
+                    "rendering was never enabled, the game is stuck before starting"
+                )
+            }
+            CompatFinding::UnsupportedMapperWrites { address, count } => write!(
+                f,
+                "{} writes to addresses without mapper registers (first at ${:04X}), \
+                 the mapper is probably wrong or unsupported",
+                count, address
+            ),
+            CompatFinding::PollingApuStatus { pc } => write!(
+                f,
+                "the game is waiting forever on the APU status register ($4015) at ${:04X}",
+                pc
+            ),
+        }
+    }
+}
+
+/// The result of [`NES::compatibility_report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    /// Number of frames run
+    pub frames: u32,
+    /// The address in the reset vector (`$FFFC`) at the start of the run
+    pub reset_vector: u16,
+    pub findings: Vec<CompatFinding>,
+}
+
+impl CompatReport {
+    /// `true` if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ran {} frames, reset vector: ${:04X}",
+            self.frames, self.reset_vector
+        )?;
+
+        if self.is_ok() {
+            return writeln!(f, "no problems found");
+        }
+        for finding in &self.findings {
+            writeln!(f, "- {}", finding)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NES {
+    /// Run the console for `frames` frames and check for common reasons of a game
+    /// not working, like a wrong mapper or waiting on unimplemented hardware features.
+    ///
+    /// The emulator is restored to its current state afterwards, so this can be called
+    /// in the middle of a session. Around 5 seconds (`300` frames) after power-up is enough
+    /// for most games to show their title screen.
+    pub fn compatibility_report(&mut self, frames: u32) -> CompatReport {
+        let reset_vector = self.cpu_read_u16(0xFFFC);
+
+        if self.is_empty() || frames == 0 {
+            return CompatReport {
+                frames: 0,
+                reset_vector,
+                findings: Vec::new(),
+            };
+        }
+
+        self.run_detached(|nes| {
+            nes.set_distinct_pc_tracking(true);
+            nes.take_apu_status_reads();
+            nes.take_unsupported_mapper_writes();
+
+            let mut rendering_was_enabled = false;
+            let mut max_distinct_pcs = 0;
+            let mut polling_frames = 0;
+
+            for _ in 0..frames {
+                nes.clock_for_frame();
+
+                let stats = nes.frame_stats();
+                let distinct_pcs = stats.distinct_pcs.unwrap_or_default();
+                rendering_was_enabled |= stats.rendering_was_enabled;
+                max_distinct_pcs = max_distinct_pcs.max(distinct_pcs);
+
+                let status_reads = nes.take_apu_status_reads();
+                if status_reads >= POLLING_MIN_STATUS_READS
+                    && distinct_pcs <= POLLING_MAX_DISTINCT_PCS
+                {
+                    polling_frames += 1;
+                } else {
+                    polling_frames = 0;
+                }
+            }
+
+            let pc = nes.cpu_state().pc;
+            let mut findings = Vec::new();
+
+            if !rendering_was_enabled {
+                if max_distinct_pcs <= STUCK_MAX_DISTINCT_PCS {
+                    findings.push(CompatFinding::StuckAfterReset {
+                        pc,
+                        distinct_pcs: max_distinct_pcs,
+                    });
+                } else {
+                    findings.push(CompatFinding::RenderingNeverEnabled);
+                }
+            }
+
+            let (count, address) = nes.take_unsupported_mapper_writes();
+            if count >= UNSUPPORTED_WRITES_THRESHOLD {
+                findings.push(CompatFinding::UnsupportedMapperWrites {
+                    address: address.unwrap_or_default(),
+                    count,
+                });
+            }
+
+            if polling_frames >= POLLING_FRAMES.min(frames) {
+                findings.push(CompatFinding::PollingApuStatus { pc });
+            }
+
+            CompatReport {
+                frames,
+                reset_vector,
+                findings,
+            }
+        })
+    }
+}
@@ -0,0 +1,361 @@
+//! A conformance vector to make changes in the emulation visible, see [`NES::conformance_check`].
+//!
+//! The emulation is deterministic: the same ROM with the same inputs on the same frames
+//! gives the same frames and audio samples on every run and every platform, as long as
+//! the config ([`EmulatorConfig`](crate::EmulatorConfig), region) is the same and the
+//! audio uses [`AudioSampling::Exact`], which produces a fixed number of samples per frame.
+//! The power-on state doesn't depend on anything outside the emulator either.
+//!
+//! The vector is a small ROM written for this ([`conformance_rom`]) run with a fixed input
+//! script, and the expected frame hashes and audio checksums at a few checkpoints, stored in
+//! `src/conformance/vector.txt`. Any change to the emulation that affects the ROM changes
+//! the vector, so it has to be updated deliberately, run the `conformance` test with
+//! `PLASTIC_CONFORMANCE_UPDATE=1` to write the new values.
+
+use crate::display::frame_hash;
+use crate::nes_audio::AudioSampling;
+use crate::{NESKey, NES};
+use std::fmt;
+
+/// The expected checkpoints, one per line: the frame, the frame hash and the audio
+/// checksum, the hashes are in hex
+const VECTOR: &str = include_str!("vector.txt");
+
+/// The frames of the checkpoints in the vector, changing them requires updating the vector
+pub const CONFORMANCE_FRAMES: [u64; 3] = [60, 300, 600];
+
+/// The input script changes the buttons every this many frames
+const INPUT_SCRIPT_STEP: u64 = 40;
+/// The buttons pressed in each step of the input script, bits in the order of [`NESKey::ALL`],
+/// `Up` and `Down` are never pressed together
+const INPUT_SCRIPT: [u8; 9] = [0x00, 0x80, 0x01, 0x81, 0x08, 0x42, 0x04, 0x10, 0x20];
+
+/// The PRG of the conformance ROM, it fills the palettes, nametables and sprites, and starts
+/// the pulse, triangle and noise channels. Then on every NMI it reads the controller, and
+/// changes the scroll, a sprite and the channel periods based on the frame and the buttons.
+#[rustfmt::skip]
+const CONFORMANCE_PRG: [u8; 188] = [
+        // reset, $8000
+        0x78, // SEI
+        0xD8, // CLD
+        0xA2, 0xFF, // LDX #$FF
+        0x9A, // TXS
+        // vblank1, $8005
+        0x2C, 0x02, 0x20, // BIT $2002
+        0x10, 0xFB, // BPL vblank1
+        // vblank2, $800A
+        0x2C, 0x02, 0x20, // BIT $2002
+        0x10, 0xFB, // BPL vblank2
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA2, 0x00, // LDX #$00
+        0x8E, 0x06, 0x20, // STX $2006
+        // palette, $8019
+        0x8A, // TXA
+        0x8D, 0x07, 0x20, // STA $2007
+        0xE8, // INX
+        0xE0, 0x20, // CPX #$20
+        0xD0, 0xF7, // BNE palette
+        0xA9, 0x20, // LDA #$20
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA0, 0x04, // LDY #$04
+        // nametable, $802E
+        0x8A, // TXA
+        0x8D, 0x07, 0x20, // STA $2007
+        0xE8, // INX
+        0xD0, 0xF9, // BNE nametable
+        0x88, // DEY
+        0xD0, 0xF6, // BNE nametable
+        // sprites, $8038
+        0x8A, // TXA
+        0x9D, 0x00, 0x02, // STA $0200,X
+        0xE8, // INX
+        0xD0, 0xF9, // BNE sprites
+        0xA9, 0x0F, // LDA #$0F
+        0x8D, 0x15, 0x40, // STA $4015
+        0xA9, 0xBF, // LDA #$BF
+        0x8D, 0x00, 0x40, // STA $4000
+        0xA9, 0xFD, // LDA #$FD
+        0x8D, 0x02, 0x40, // STA $4002
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x03, 0x40, // STA $4003
+        0xA9, 0xFF, // LDA #$FF
+        0x8D, 0x08, 0x40, // STA $4008
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x0A, 0x40, // STA $400A
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x0B, 0x40, // STA $400B
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x0C, 0x40, // STA $400C
+        0xA9, 0x05, // LDA #$05
+        0x8D, 0x0E, 0x40, // STA $400E
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x0F, 0x40, // STA $400F
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000
+        0xA9, 0x1E, // LDA #$1E
+        0x8D, 0x01, 0x20, // STA $2001
+        // idle, $807B
+        0x4C, 0x7B, 0x80, // JMP idle
+        // nmi, $807E
+        0xE6, 0x00, // INC $00
+        0xA9, 0x02, // LDA #$02
+        0x8D, 0x14, 0x40, // STA $4014
+        0xA9, 0x01, // LDA #$01
+        0x8D, 0x16, 0x40, // STA $4016
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x16, 0x40, // STA $4016
+        0xA2, 0x08, // LDX #$08
+        // controller, $8091
+        0xAD, 0x16, 0x40, // LDA $4016
+        0x4A, // LSR A
+        0x26, 0x01, // ROL $01
+        0xCA, // DEX
+        0xD0, 0xF7, // BNE controller
+        0xA5, 0x00, // LDA $00
+        0x8D, 0x05, 0x20, // STA $2005
+        0xA5, 0x01, // LDA $01
+        0x8D, 0x05, 0x20, // STA $2005
+        0xA5, 0x00, // LDA $00
+        0x8D, 0x03, 0x02, // STA $0203
+        0x8D, 0x02, 0x40, // STA $4002
+        0xA5, 0x01, // LDA $01
+        0x8D, 0x0A, 0x40, // STA $400A
+        0x29, 0x0F, // AND #$0F
+        0x8D, 0x0E, 0x40, // STA $400E
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000
+        0x40, // RTI
+];
+const RESET_VECTOR: u16 = 0x8000;
+const NMI_VECTOR: u16 = 0x807E;
+
+/// The conformance ROM, an NROM cartridge with a small test program and CHR ROM with a
+/// pattern derived from the tile number
+pub fn conformance_rom() -> Vec<u8> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    let mut prg = vec![0; 0x4000];
+    prg[..CONFORMANCE_PRG.len()].copy_from_slice(&CONFORMANCE_PRG);
+    prg[0x3FFA..0x3FFC].copy_from_slice(&NMI_VECTOR.to_le_bytes());
+    prg[0x3FFC..0x3FFE].copy_from_slice(&RESET_VECTOR.to_le_bytes());
+    prg[0x3FFE..].copy_from_slice(&NMI_VECTOR.to_le_bytes());
+    rom.extend_from_slice(&prg);
+
+    for tile in 0..0x200usize {
+        let (tile, row) = (tile as u8, 0..8u8);
+        // low plane, then high plane
+        rom.extend(row.clone().map(|row| tile ^ row.wrapping_mul(0x11)));
+        rom.extend(row.map(|row| tile.rotate_left(row as u32) & !row));
+    }
+
+    rom
+}
+
+/// The buttons pressed on `frame` by the input script, in the order of [`NESKey::ALL`]
+fn input_script(frame: u64) -> u8 {
+    INPUT_SCRIPT[(frame / INPUT_SCRIPT_STEP) as usize % INPUT_SCRIPT.len()]
+}
+
+/// A checksum of audio samples, updated as they are produced, so it doesn't need to
+/// keep them. It hashes the bits of the samples, so any difference is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioChecksum {
+    hash: u64,
+    samples: u64,
+}
+
+impl Default for AudioChecksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioChecksum {
+    pub fn new() -> Self {
+        Self {
+            // FNV-1a, the same as `frame_hash`
+            hash: 0xcbf29ce484222325,
+            samples: 0,
+        }
+    }
+
+    pub fn update(&mut self, samples: &[f32]) {
+        for sample in samples {
+            for byte in sample.to_bits().to_le_bytes() {
+                self.hash = (self.hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+        self.samples += samples.len() as u64;
+    }
+
+    /// The checksum of all the samples so far
+    pub fn value(&self) -> u64 {
+        self.hash
+    }
+
+    /// Number of samples so far
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+/// The state of the conformance run at a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The number of frames run
+    pub frame: u64,
+    /// The [`frame_hash`] of the pixel buffer
+    pub frame_hash: u64,
+    /// The [`AudioChecksum`] of all the samples from power-on
+    pub audio_checksum: u64,
+}
+
+/// A checkpoint of the conformance vector and the result of the emulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointResult {
+    pub expected: Checkpoint,
+    pub actual: Checkpoint,
+}
+
+impl CheckpointResult {
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// The result of [`NES::conformance_check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub checkpoints: Vec<CheckpointResult>,
+}
+
+impl ConformanceReport {
+    /// `true` if all the checkpoints match the vector
+    pub fn passed(&self) -> bool {
+        self.checkpoints.iter().all(CheckpointResult::passed)
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.checkpoints {
+            let (expected, actual) = (&result.expected, &result.actual);
+            write!(f, "frame {}: ", expected.frame)?;
+            if result.passed() {
+                writeln!(f, "pass")?;
+                continue;
+            }
+            write!(f, "FAIL")?;
+            if expected.frame_hash != actual.frame_hash {
+                write!(
+                    f,
+                    ", frame hash {:016x} (expected {:016x})",
+                    actual.frame_hash, expected.frame_hash
+                )?;
+            }
+            if expected.audio_checksum != actual.audio_checksum {
+                write!(
+                    f,
+                    ", audio checksum {:016x} (expected {:016x})",
+                    actual.audio_checksum, expected.audio_checksum
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the checkpoints of [`VECTOR`], `#` starts a comment
+fn parse_vector(vector: &str) -> Vec<Checkpoint> {
+    vector
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let [frame, frame_hash, audio_checksum] = fields[..] else {
+                panic!("invalid conformance vector line: {line}");
+            };
+            let hex = |value| u64::from_str_radix(value, 16).expect("invalid hash in the vector");
+            Checkpoint {
+                frame: frame.parse().expect("invalid frame in the vector"),
+                frame_hash: hex(frame_hash),
+                audio_checksum: hex(audio_checksum),
+            }
+        })
+        .collect()
+}
+
+/// Format `checkpoints` in the format of the vector file
+#[cfg(test)]
+pub(crate) fn format_vector(checkpoints: &[Checkpoint]) -> String {
+    let mut vector = String::from(
+        "# The conformance vector, see `src/conformance/mod.rs`, update it by running the\n\
+         # `conformance` test with `PLASTIC_CONFORMANCE_UPDATE=1`\n\
+         # frame  frame_hash        audio_checksum\n",
+    );
+    for checkpoint in checkpoints {
+        vector += &format!(
+            "{:<8} {:016x}  {:016x}\n",
+            checkpoint.frame, checkpoint.frame_hash, checkpoint.audio_checksum
+        );
+    }
+    vector
+}
+
+/// Run the conformance ROM and take a checkpoint at each of `frames` (sorted)
+pub(crate) fn run_checkpoints(frames: &[u64]) -> Vec<Checkpoint> {
+    let mut nes = NES::new_from_bytes(&conformance_rom()).expect("the conformance ROM is valid");
+    nes.set_audio_sampling(AudioSampling::Exact);
+
+    let mut audio = AudioChecksum::new();
+    let mut checkpoints = Vec::new();
+    let mut frame = 0;
+    for &checkpoint_frame in frames {
+        while frame < checkpoint_frame {
+            let buttons = input_script(frame);
+            for (i, key) in NESKey::ALL.into_iter().enumerate() {
+                nes.set_controller_state(key, buttons & (1 << i) != 0);
+            }
+            nes.clock_for_frame();
+            audio.update(&nes.audio_buffer());
+            frame += 1;
+        }
+
+        checkpoints.push(Checkpoint {
+            frame,
+            frame_hash: frame_hash(nes.pixel_buffer()),
+            audio_checksum: audio.value(),
+        });
+    }
+
+    checkpoints
+}
+
+impl NES {
+    /// Run the conformance vector and compare the frame hashes and the audio checksums
+    /// at each checkpoint, see the [module documentation](crate::conformance) for details.
+    ///
+    /// This runs a separate emulator, `600` frames.
+    pub fn conformance_check() -> ConformanceReport {
+        let expected = parse_vector(VECTOR);
+        let frames = expected
+            .iter()
+            .map(|checkpoint| checkpoint.frame)
+            .collect::<Vec<_>>();
+        let actual = run_checkpoints(&frames);
+
+        ConformanceReport {
+            checkpoints: expected
+                .into_iter()
+                .zip(actual)
+                .map(|(expected, actual)| CheckpointResult { expected, actual })
+                .collect(),
+        }
+    }
+}
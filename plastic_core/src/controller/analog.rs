@@ -0,0 +1,115 @@
+/// How to resolve Simultaneous Opposite Cardinal Directions (SOCD), i.e. both
+/// `Left` and `Right` (or `Up` and `Down`) pressed at the same time, which is not
+/// possible on a real D-pad and can cause glitches in games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocdPolicy {
+    /// Release both directions
+    Neutral,
+    /// Keep `Up` (for vertical) or `Left` (for horizontal)
+    PreferUpLeft,
+    /// Keep `Down` (for vertical) or `Right` (for horizontal)
+    PreferDownRight,
+    /// Keep both directions pressed
+    Allow,
+}
+
+impl SocdPolicy {
+    /// resolve `(first, second)` directions, where `first` is `Up` or `Left`
+    fn resolve(&self, first: bool, second: bool) -> (bool, bool) {
+        if !(first && second) {
+            return (first, second);
+        }
+
+        match self {
+            SocdPolicy::Neutral => (false, false),
+            SocdPolicy::PreferUpLeft => (true, false),
+            SocdPolicy::PreferDownRight => (false, true),
+            SocdPolicy::Allow => (true, true),
+        }
+    }
+}
+
+/// The state of the directional buttons of the controller
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DpadState {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// Configuration of [`AnalogToDpad`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogToDpadConfig {
+    /// The stick is neutral if its distance from the center is less than this, `0.0..1.0`
+    pub dead_zone: f32,
+    /// A direction is pressed if its axis is more than this ratio of the distance
+    /// from the center, lower values make diagonals easier to press.
+    ///
+    /// The default is `sin(22.5°)`, which divides the stick into 8 equal sectors.
+    pub diagonal_threshold: f32,
+    /// Policy when both `Left` and `Right` are pressed
+    pub horizontal_socd: SocdPolicy,
+    /// Policy when both `Up` and `Down` are pressed
+    pub vertical_socd: SocdPolicy,
+}
+
+impl Default for AnalogToDpadConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.25,
+            diagonal_threshold: std::f32::consts::FRAC_PI_8.sin(),
+            horizontal_socd: SocdPolicy::Neutral,
+            vertical_socd: SocdPolicy::PreferUpLeft,
+        }
+    }
+}
+
+/// Converts analog stick positions to D-pad directions, and cleans opposite
+/// directions pressed together, see [`NES::set_controller_analog`](crate::NES::set_controller_analog).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AnalogToDpad {
+    config: AnalogToDpadConfig,
+}
+
+impl AnalogToDpad {
+    pub fn new(config: AnalogToDpadConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &AnalogToDpadConfig {
+        &self.config
+    }
+
+    /// Convert a stick position to D-pad directions, `x` is positive to the right and
+    /// `y` is positive downwards, both in `-1.0..=1.0`.
+    pub fn convert(&self, x: f32, y: f32) -> DpadState {
+        let distance = x.hypot(y);
+
+        if distance.is_nan() || distance < self.config.dead_zone || distance == 0. {
+            return DpadState::default();
+        }
+
+        let threshold = distance * self.config.diagonal_threshold;
+
+        DpadState {
+            up: -y > threshold,
+            down: y > threshold,
+            left: -x > threshold,
+            right: x > threshold,
+        }
+    }
+
+    /// Resolve opposite directions pressed together using the SOCD policies
+    pub fn clean(&self, state: DpadState) -> DpadState {
+        let (up, down) = self.config.vertical_socd.resolve(state.up, state.down);
+        let (left, right) = self.config.horizontal_socd.resolve(state.left, state.right);
+
+        DpadState {
+            up,
+            down,
+            left,
+            right,
+        }
+    }
+}
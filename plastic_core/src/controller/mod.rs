@@ -1,4 +1,14 @@
-use crate::common::{Bus, Device};
+mod analog;
+mod tests;
+
+pub use analog::{AnalogToDpad, AnalogToDpadConfig, DpadState, SocdPolicy};
+
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, take_json_field, JsonSavable};
+use crate::common::{
+    save_state::{Savable, SaveError},
+    Bus, Device,
+};
 use bitflags::bitflags;
 use std::cell::Cell;
 
@@ -23,6 +33,20 @@ pub enum NESKey {
     Right = 1 << 7,
 }
 
+impl NESKey {
+    /// All the keys, in the order of their bits
+    pub const ALL: [NESKey; 8] = [
+        NESKey::A,
+        NESKey::B,
+        NESKey::Select,
+        NESKey::Start,
+        NESKey::Up,
+        NESKey::Down,
+        NESKey::Left,
+        NESKey::Right,
+    ];
+}
+
 bitflags! {
    pub struct StandardNESControllerState : u8{
         const A = 1 << 0;
@@ -54,11 +78,34 @@ impl StandardNESControllerState {
     }
 }
 
+/// A device connected to the expansion port, connected with
+/// [`NES::set_expansion_port_device`](crate::NES::set_expansion_port_device).
+pub trait ExpansionPortDevice {
+    /// Called on every write to `$4016` with the 3 output lines of the port
+    /// (`OUT0-OUT2`, bits 0-2 of the written value).
+    ///
+    /// `OUT0` is also the strobe of the standard controllers.
+    fn write_strobe(&mut self, bits: u8);
+}
+
 pub struct Controller {
     primary_state: StandardNESControllerState,
     polled_state: Cell<u8>,
 
     polling: bool,
+
+    /// the buttons latched by the last strobe, what the game read and not what is pressed now
+    last_latched_state: u8,
+    /// the frame of `last_latched_state`, `0` if the controller was never latched
+    last_latch_frame: u64,
+    /// the frame currently running, set by the NES with `set_frame`
+    frame: u64,
+    /// number of latches since the last `take_polls`
+    polls: u32,
+
+    /// directions from the analog stick, combined with `primary_state`
+    analog_state: DpadState,
+    analog_to_dpad: AnalogToDpad,
 }
 
 impl Controller {
@@ -68,19 +115,71 @@ impl Controller {
             polled_state: Cell::new(0),
 
             polling: false,
+
+            last_latched_state: 0,
+            last_latch_frame: 0,
+            frame: 0,
+            polls: 0,
+
+            analog_state: DpadState::default(),
+            analog_to_dpad: AnalogToDpad::default(),
         }
     }
 
     pub fn set_controller_state(&mut self, key: NESKey, pressed: bool) {
         self.primary_state.set_controller_state(key, pressed);
     }
+
+    /// Set the frame number used to tag the latches, called by the NES at the
+    /// start of every frame
+    pub(crate) fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    /// The buttons latched by the last strobe and the frame it happened in
+    pub fn last_polled(&self) -> (u8, u64) {
+        (self.last_latched_state, self.last_latch_frame)
+    }
+
+    /// Number of latches since the last call, and reset the count
+    pub(crate) fn take_polls(&mut self) -> u32 {
+        std::mem::take(&mut self.polls)
+    }
+
+    pub fn set_analog_state(&mut self, x: f32, y: f32) {
+        self.analog_state = self.analog_to_dpad.convert(x, y);
+    }
+
+    pub fn set_analog_to_dpad(&mut self, analog_to_dpad: AnalogToDpad) {
+        self.analog_to_dpad = analog_to_dpad;
+    }
+
+    /// the buttons state as seen by the console, the directions of the buttons and the
+    /// analog stick are combined and cleaned of opposite directions
+    fn state(&self) -> u8 {
+        let mut state = self.primary_state;
+
+        let dpad = self.analog_to_dpad.clean(DpadState {
+            up: state.contains(StandardNESControllerState::UP) || self.analog_state.up,
+            down: state.contains(StandardNESControllerState::DOWN) || self.analog_state.down,
+            left: state.contains(StandardNESControllerState::LEFT) || self.analog_state.left,
+            right: state.contains(StandardNESControllerState::RIGHT) || self.analog_state.right,
+        });
+
+        state.set(StandardNESControllerState::UP, dpad.up);
+        state.set(StandardNESControllerState::DOWN, dpad.down);
+        state.set(StandardNESControllerState::LEFT, dpad.left);
+        state.set(StandardNESControllerState::RIGHT, dpad.right);
+
+        state.bits
+    }
 }
 
 impl Bus for Controller {
     fn read(&self, _address: u16, _device: Device) -> u8 {
         // refresh polled here
         if self.polling {
-            self.polled_state.set(self.primary_state.bits);
+            self.polled_state.set(self.state());
         }
         let result = self.polled_state.get() & 1;
 
@@ -94,9 +193,82 @@ impl Bus for Controller {
 
         // if the state changed, then refresh
         if self.polling ^ new_polling {
-            self.polled_state.set(self.primary_state.bits);
+            self.polled_state.set(self.state());
+        }
+
+        // the buttons are latched when the strobe goes low
+        if self.polling && !new_polling {
+            self.last_latched_state = self.polled_state.get();
+            self.last_latch_frame = self.frame;
+            self.polls += 1;
         }
 
         self.polling = new_polling;
     }
 }
+
+/// The `primary_state` is saved as well, so that loading a state will resume
+/// the serial read sequence exactly where it was, the frontend's actual pressed
+/// keys will override it on the next call to `set_controller_state`.
+///
+/// The last latched buttons are saved with their frame number, so input viewers
+/// show the same input after loading.
+impl Savable for Controller {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
+        writer.write_all(&[
+            self.primary_state.bits,
+            self.polled_state.get(),
+            self.polling as u8,
+            self.last_latched_state,
+        ])?;
+        writer.write_all(&self.last_latch_frame.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
+        let mut data = [0; 4];
+        reader.read_exact(&mut data)?;
+        let mut last_latch_frame = [0; 8];
+        reader.read_exact(&mut last_latch_frame)?;
+
+        self.primary_state = StandardNESControllerState::from_bits_truncate(data[0]);
+        self.polled_state.set(data[1]);
+        self.polling = data[2] != 0;
+        self.last_latched_state = data[3];
+        self.last_latch_frame = u64::from_le_bytes(last_latch_frame);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "state-json")]
+impl JsonSavable for Controller {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "primary_state": self.primary_state.bits,
+            "polled_state": self.polled_state.get(),
+            "polling": self.polling,
+            "last_latched_state": self.last_latched_state,
+            "last_latch_frame": self.last_latch_frame,
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        let primary_state: u8 = from_json_value(take_json_field(&mut value, "primary_state")?)?;
+        let polled_state: u8 = from_json_value(take_json_field(&mut value, "polled_state")?)?;
+        let polling: bool = from_json_value(take_json_field(&mut value, "polling")?)?;
+        let last_latched_state: u8 =
+            from_json_value(take_json_field(&mut value, "last_latched_state")?)?;
+        let last_latch_frame: u64 =
+            from_json_value(take_json_field(&mut value, "last_latch_frame")?)?;
+
+        self.primary_state = StandardNESControllerState::from_bits_truncate(primary_state);
+        self.polled_state.set(polled_state);
+        self.polling = polling;
+        self.last_latched_state = last_latched_state;
+        self.last_latch_frame = last_latch_frame;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod controller_tests {
+    use super::super::{
+        AnalogToDpad, AnalogToDpadConfig, Controller, DpadState, NESKey, SocdPolicy,
+    };
+    use crate::common::{save_state::Savable, Bus, Device};
+
+    fn read_bit(controller: &Controller) -> u8 {
+        controller.read(0x4016, Device::Cpu)
+    }
+
+    #[test]
+    fn save_load_mid_read() {
+        let mut controller = Controller::new();
+        // 0b1010_1001
+        controller.set_controller_state(NESKey::A, true);
+        controller.set_controller_state(NESKey::Start, true);
+        controller.set_controller_state(NESKey::Down, true);
+        controller.set_controller_state(NESKey::Right, true);
+
+        // strobe
+        controller.write(0x4016, 1, Device::Cpu);
+        controller.write(0x4016, 0, Device::Cpu);
+
+        let mut expected = Vec::new();
+        for _ in 0..3 {
+            expected.push(read_bit(&controller));
+        }
+        assert_eq!(expected, [1, 0, 0]);
+
+        let mut buffer = Vec::new();
+        controller.save(&mut buffer).unwrap();
+
+        // the remaining reads from the original controller
+        let remaining = (0..5).map(|_| read_bit(&controller)).collect::<Vec<_>>();
+        assert_eq!(remaining, [1, 0, 1, 0, 1]);
+
+        let mut loaded = Controller::new();
+        loaded.load(&mut buffer.as_slice()).unwrap();
+
+        // changing the keys after loading should not affect the latched bits
+        loaded.set_controller_state(NESKey::Right, false);
+
+        let loaded_remaining = (0..5).map(|_| read_bit(&loaded)).collect::<Vec<_>>();
+        assert_eq!(loaded_remaining, remaining);
+    }
+
+    fn dpad(up: bool, down: bool, left: bool, right: bool) -> DpadState {
+        DpadState {
+            up,
+            down,
+            left,
+            right,
+        }
+    }
+
+    #[test]
+    fn analog_dead_zone() {
+        let analog = AnalogToDpad::new(AnalogToDpadConfig {
+            dead_zone: 0.5,
+            ..Default::default()
+        });
+
+        assert_eq!(analog.convert(0., 0.), DpadState::default());
+        assert_eq!(analog.convert(0.49, 0.), DpadState::default());
+        assert_eq!(analog.convert(0., -0.49), DpadState::default());
+        // the distance is used, not each axis
+        assert_eq!(analog.convert(0.3, 0.3), DpadState::default());
+        assert_eq!(analog.convert(f32::NAN, 0.), DpadState::default());
+
+        assert_eq!(analog.convert(0.5, 0.), dpad(false, false, false, true));
+        assert_eq!(analog.convert(0., -0.5), dpad(true, false, false, false));
+        assert_eq!(analog.convert(0.4, 0.4), dpad(false, true, false, true));
+    }
+
+    #[test]
+    fn analog_diagonal_threshold() {
+        let analog = AnalogToDpad::default();
+
+        // 8 equal sectors, the diagonal starts at 22.5 degrees
+        let (sin, cos) = 20f32.to_radians().sin_cos();
+        assert_eq!(analog.convert(cos, -sin), dpad(false, false, false, true));
+        let (sin, cos) = 25f32.to_radians().sin_cos();
+        assert_eq!(analog.convert(cos, -sin), dpad(true, false, false, true));
+        assert_eq!(analog.convert(-sin, cos), dpad(false, true, true, false));
+
+        let analog = AnalogToDpad::new(AnalogToDpadConfig {
+            diagonal_threshold: 0.5,
+            ..Default::default()
+        });
+        // sin(25) is less than 0.5
+        assert_eq!(analog.convert(cos, -sin), dpad(false, false, false, true));
+        let (sin, cos) = 35f32.to_radians().sin_cos();
+        assert_eq!(analog.convert(cos, -sin), dpad(true, false, false, true));
+    }
+
+    #[test]
+    fn socd_policies() {
+        let all = dpad(true, true, true, true);
+
+        let clean = |horizontal_socd, vertical_socd| {
+            AnalogToDpad::new(AnalogToDpadConfig {
+                horizontal_socd,
+                vertical_socd,
+                ..Default::default()
+            })
+            .clean(all)
+        };
+
+        // default: Left+Right -> neutral, Up+Down -> Up
+        assert_eq!(
+            AnalogToDpad::default().clean(all),
+            dpad(true, false, false, false)
+        );
+        assert_eq!(
+            clean(SocdPolicy::Neutral, SocdPolicy::Neutral),
+            dpad(false, false, false, false)
+        );
+        assert_eq!(
+            clean(SocdPolicy::PreferUpLeft, SocdPolicy::PreferDownRight),
+            dpad(false, true, true, false)
+        );
+        assert_eq!(
+            clean(SocdPolicy::PreferDownRight, SocdPolicy::PreferUpLeft),
+            dpad(true, false, false, true)
+        );
+        assert_eq!(clean(SocdPolicy::Allow, SocdPolicy::Allow), all);
+
+        // a single direction is not affected
+        let single = dpad(false, true, true, false);
+        assert_eq!(AnalogToDpad::default().clean(single), single);
+    }
+
+    #[test]
+    fn analog_combined_with_buttons() {
+        let mut controller = Controller::new();
+        controller.set_controller_state(NESKey::A, true);
+        controller.set_controller_state(NESKey::Left, true);
+        controller.set_analog_state(1., 1.);
+
+        // strobe
+        controller.write(0x4016, 1, Device::Cpu);
+        controller.write(0x4016, 0, Device::Cpu);
+
+        // A, B, Select, Start, Up, Down, Left, Right
+        // Left+Right are cleaned to neutral, Down from the stick
+        let bits = (0..8).map(|_| read_bit(&controller)).collect::<Vec<_>>();
+        assert_eq!(bits, [1, 0, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn last_polled_tracking() {
+        let mut controller = Controller::new();
+        controller.set_controller_state(NESKey::B, true);
+        assert_eq!(controller.last_polled(), (0, 0));
+
+        controller.set_frame(5);
+        controller.write(0x4016, 1, Device::Cpu);
+        // still high, nothing is latched yet
+        assert_eq!(controller.last_polled(), (0, 0));
+        controller.write(0x4016, 0, Device::Cpu);
+        assert_eq!(controller.last_polled(), (0b10, 5));
+
+        // writing low again or pressing keys does not latch
+        controller.set_controller_state(NESKey::Up, true);
+        controller.write(0x4016, 0, Device::Cpu);
+        assert_eq!(controller.last_polled(), (0b10, 5));
+        assert_eq!(controller.take_polls(), 1);
+
+        // debouncing, two strobes in the same frame
+        controller.set_frame(6);
+        for _ in 0..2 {
+            controller.write(0x4016, 1, Device::Cpu);
+            controller.write(0x4016, 0, Device::Cpu);
+        }
+        assert_eq!(controller.last_polled(), (0b1_0010, 6));
+        assert_eq!(controller.take_polls(), 2);
+        assert_eq!(controller.take_polls(), 0);
+    }
+}
@@ -183,6 +183,44 @@ const ADDRESSING_MODES: [AddressingMode; 256] = [
 ];
 
 // public
+impl Opcode {
+    /// `true` for the stores and read-modify-write instructions, with the indexed
+    /// addressing modes they always take the page cross cycle and its dummy read
+    pub fn writes_memory(&self) -> bool {
+        matches!(
+            self,
+            Asl | Lsr
+                | Rol
+                | Ror
+                | Dec
+                | Inc
+                | Sta
+                | Stx
+                | Sty
+                | Slo
+                | Sre
+                | Rla
+                | Rra
+                | Isc
+                | Dcp
+                | Sax
+                | Ahx
+                | Shy
+                | Shx
+                | Tas
+        )
+    }
+
+    /// `true` for the instructions that have a memory operand but don't read it,
+    /// the stores and jumps
+    pub fn skips_operand_read(&self) -> bool {
+        matches!(
+            self,
+            Sta | Stx | Sty | Sax | Ahx | Shy | Shx | Tas | Jmp | Jsr
+        )
+    }
+}
+
 impl AddressingMode {
     pub fn can_cross_page(&self) -> bool {
         self == &AddressingMode::IndirectY
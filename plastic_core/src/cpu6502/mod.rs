@@ -1,4 +1,5 @@
 pub mod instruction;
+mod reference_tests;
 mod tests;
 
 use crate::common::interconnection::{APUCPUConnection, CPUIrqProvider, PPUCPUConnection};
@@ -9,9 +10,21 @@ pub trait CPUBusTrait: Savable + PPUCPUConnection + APUCPUConnection + CPUIrqPro
     fn write(&mut self, address: u16, data: u8);
 
     fn reset(&mut self);
+
+    /// A read that the CPU does while calculating an address, the result is not used
+    /// but it still has the side effects of a normal read
+    fn dummy_read(&self, address: u16) -> u8 {
+        self.read(address)
+    }
+
+    /// Called with the address of each instruction before it is run
+    fn set_instruction_pc(&mut self, _pc: u16) {}
 }
 
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, take_json_field, to_json_value, JsonSavable};
 use crate::common::save_state::{Savable, SaveError};
+use crate::diagnostics::{Diagnostic, Diagnostics, StackWrap};
 use instruction::{AddressingMode, Instruction, Opcode};
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
@@ -20,6 +33,10 @@ const NMI_VECTOR_ADDRESS: u16 = 0xFFFA;
 const RESET_VECTOR_ADDRESS: u16 = 0xFFFC;
 const IRQ_VECTOR_ADDRESS: u16 = 0xFFFE;
 
+/// The most CPU cycles a DMC fetch can be delayed in the collision stress mode,
+/// short enough for the sample buffer to be filled before the output unit needs it
+const DMC_STRESS_MAX_DELAY: u8 = 32;
+
 /// The state of the CPU after one clock cycle
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CPURunState {
@@ -33,6 +50,21 @@ pub enum CPURunState {
     StartingInterrupt,
     /// The CPU is executing a normal instruction (most common)
     NormalInstructionExecution,
+    /// The CPU executed a `KIL` opcode and is jammed until reset, the program counter
+    /// stays on the `KIL` opcode
+    Halted,
+}
+
+/// A snapshot of the CPU registers, see [`NES::cpu_state`](crate::NES::cpu_state)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CpuState {
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    /// flags: `[N, V, _, B, D, I, Z, C]`
+    pub status: u8,
 }
 
 // helper function
@@ -51,7 +83,6 @@ enum StatusFlag {
     Negative = 1 << 7,
 }
 
-// TODO: this CPU does not support BCD mode yet
 pub struct CPU6502<T: CPUBusTrait> {
     reg_pc: u16,
     reg_sp: u8,
@@ -61,10 +92,27 @@ pub struct CPU6502<T: CPUBusTrait> {
     reg_status: u8,
 
     nmi_pin_status: bool,
+    /// the IRQ pin of the mapper, updated when the cartridge requests it
     irq_pin_status: bool,
+    /// the IRQ level of the APU, sampled on every cycle
+    irq_level: bool,
+    /// the IRQ level seen by the IRQ polling, at the end of the second to last cycle
+    /// of the instruction
+    irq_level_polled: bool,
+    /// the value of the `InterruptDisable` flag as seen by the IRQ polling.
+    /// IRQ is polled before the last cycle of the instruction, but `CLI`, `SEI` and `PLP`
+    /// change the flag in the last cycle, so their effect on IRQ is delayed by one instruction
+    irq_poll_interrupt_disable: bool,
 
     cycles_to_wait: u8,
 
+    /// set by the `KIL` opcodes, only a reset clears it
+    halted: bool,
+
+    /// the last value written by a read-modify-write instruction, the unofficial
+    /// instructions that combine two operations use it as the operand of the second one
+    modified_value: u8,
+
     dma_remaining: u16,
     dma_address: u8,
 
@@ -72,6 +120,25 @@ pub struct CPU6502<T: CPUBusTrait> {
     /// check `run_next` for more info
     next_instruction: Option<(Instruction, u8)>,
 
+    /// the address of the last fetched instruction, used for diagnostics
+    instruction_pc: u16,
+    in_interrupt_sequence: bool,
+    stack_wrap_warnings: bool,
+    diagnostics: Diagnostics,
+
+    /// the number of DMC fetches that landed on the read cycle of `$4016/$4017`
+    controller_read_glitches: u64,
+    dmc_collision_stress: bool,
+    /// the cycles the current DMC fetch has been delayed in the stress mode
+    dmc_fetch_delay: u8,
+    /// a DMC fetch halted the CPU on the cycle before the write of a `SHY`, `SHX`,
+    /// `AHX` or `TAS`, which drops the high byte from the stored value, not saved
+    /// like `dmc_fetch_delay` since it only lasts until the end of the instruction
+    unstable_store_halted: bool,
+    /// run `ADC` and `SBC` in BCD when the decimal flag is set, the 2A03 doesn't
+    /// have it, so this is disabled by default
+    decimal_mode: bool,
+
     bus: T,
 }
 
@@ -91,14 +158,32 @@ where
 
             nmi_pin_status: false,
             irq_pin_status: false,
+            irq_level: false,
+            irq_level_polled: false,
+            irq_poll_interrupt_disable: true,
 
             cycles_to_wait: 0,
 
+            halted: false,
+
+            modified_value: 0,
+
             dma_remaining: 0,
             dma_address: 0,
 
             next_instruction: None,
 
+            instruction_pc: 0,
+            in_interrupt_sequence: false,
+            stack_wrap_warnings: false,
+            diagnostics: Diagnostics::default(),
+
+            controller_read_glitches: 0,
+            dmc_collision_stress: false,
+            dmc_fetch_delay: 0,
+            unstable_store_halted: false,
+            decimal_mode: false,
+
             bus,
         }
     }
@@ -114,12 +199,21 @@ where
 
         self.nmi_pin_status = false;
         self.irq_pin_status = false;
+        self.irq_level = false;
+        self.irq_level_polled = false;
+        self.irq_poll_interrupt_disable = true;
 
         self.cycles_to_wait = 0;
 
+        self.halted = false;
+
         self.dma_remaining = 0;
         self.dma_address = 0;
 
+        self.controller_read_glitches = 0;
+        self.dmc_fetch_delay = 0;
+        self.unstable_store_halted = false;
+
         self.set_flag(StatusFlag::InterruptDisable);
         self.reg_sp = 0xFD; //reset
 
@@ -137,6 +231,14 @@ where
     }
 
     pub fn run_next(&mut self) -> CPURunState {
+        // a jammed CPU doesn't respond to interrupts or DMA
+        if self.halted {
+            return CPURunState::Halted;
+        }
+
+        let previous_irq_level = self.irq_level;
+        self.irq_level = self.bus.irq_level();
+
         self.check_and_run_dmc_transfer();
 
         if self.cycles_to_wait == 0 && self.next_instruction.is_none() {
@@ -157,8 +259,8 @@ where
                 self.cycles_to_wait = 1;
                 CPURunState::DmaTransfer
             } else if self.nmi_pin_status
-                || (self.irq_pin_status
-                    && self.reg_status & StatusFlag::InterruptDisable as u8 == 0)
+                || ((self.irq_pin_status || self.irq_level_polled)
+                    && !self.irq_poll_interrupt_disable)
             {
                 // execute interrupt
                 // hardware side interrupt
@@ -204,8 +306,20 @@ where
 
             let (instruction, cycle_time) = self.next_instruction.take().unwrap();
 
+            let interrupt_disable_before =
+                self.reg_status & (StatusFlag::InterruptDisable as u8) != 0;
+            self.irq_level_polled = previous_irq_level;
+
+            self.run_indexed_dummy_read(&instruction);
             let return_state = self.run_instruction(&instruction);
 
+            // the IRQ poll happened before the last cycle, which is where these
+            // instructions modify the flag
+            self.irq_poll_interrupt_disable = match instruction.opcode {
+                Opcode::Cli | Opcode::Sei | Opcode::Plp => interrupt_disable_before,
+                _ => self.reg_status & (StatusFlag::InterruptDisable as u8) != 0,
+            };
+
             // `run_instruction` will set `self.cycles_to_wait` to the amount
             // of cycles to wait minus 1, but before we have already waited
             // `cycle_time` cycles (excluding this one), so subtract that
@@ -219,6 +333,54 @@ where
         }
     }
 
+    /// The current value of the program counter register
+    pub fn reg_pc(&self) -> u16 {
+        self.reg_pc
+    }
+
+    /// `true` after a `KIL` opcode, until reset
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            pc: self.reg_pc,
+            sp: self.reg_sp,
+            a: self.reg_a,
+            x: self.reg_x,
+            y: self.reg_y,
+            status: self.reg_status,
+        }
+    }
+
+    /// Emit [`Diagnostic::StackWrap`] when the stack pointer wraps around, disabled by default
+    pub fn set_stack_wrap_warnings(&mut self, enabled: bool) {
+        self.stack_wrap_warnings = enabled;
+    }
+
+    /// The number of DMC fetches that collided with a controller read since reset
+    pub fn controller_read_glitches(&self) -> u64 {
+        self.controller_read_glitches
+    }
+
+    /// Delay the DMC fetches (up to [`DMC_STRESS_MAX_DELAY`] cycles) until they
+    /// collide with a controller read, disabled by default
+    pub fn set_dmc_collision_stress(&mut self, enabled: bool) {
+        self.dmc_collision_stress = enabled;
+        self.dmc_fetch_delay = 0;
+    }
+
+    /// Run `ADC` and `SBC` in BCD when the decimal flag is set, like the 65C02,
+    /// disabled by default like the 2A03
+    pub fn set_decimal_mode(&mut self, enabled: bool) {
+        self.decimal_mode = enabled;
+    }
+
+    pub fn diagnostics_mut(&mut self) -> &mut Diagnostics {
+        &mut self.diagnostics
+    }
+
     pub fn bus(&self) -> &T {
         &self.bus
     }
@@ -257,6 +419,53 @@ where
         self.bus.write(address, data);
     }
 
+    /// Write the result of a read-modify-write instruction, and keep it for the
+    /// second operation of the unofficial instructions, as reading it back may
+    /// not return it (ROM or I/O registers)
+    fn write_modified_value(&mut self, address: u16, data: u8) {
+        self.modified_value = data;
+        self.write_bus(address, data);
+    }
+
+    /// `SHY`, `SHX`, `AHX` and `TAS` store `value` AND the high byte of the address
+    /// before indexing plus one, and on page cross the stored value also replaces the high
+    /// byte of the address, `address` is the indexed address and `index` the register added.
+    ///
+    /// If a DMA halted the CPU on the cycle before the write, the AND is dropped.
+    fn run_unstable_store(&mut self, value: u8, address: u16, index: u8, did_page_cross: bool) {
+        let base_high = (address.wrapping_sub(index as u16) >> 8) as u8;
+
+        let value = if std::mem::take(&mut self.unstable_store_halted) {
+            value
+        } else {
+            value & base_high.wrapping_add(1)
+        };
+
+        let address = if did_page_cross {
+            (value as u16) << 8 | address & 0xFF
+        } else {
+            address
+        };
+        self.write_bus(address, value);
+    }
+
+    /// The indexed addressing modes read the address before fixing its high byte
+    /// on page cross, and the instructions that write to memory always do this read.
+    /// This read is visible when the address is an I/O register, like `$2007`.
+    fn run_indexed_dummy_read(&self, instruction: &Instruction) {
+        if !instruction.addressing_mode.can_cross_page() {
+            return;
+        }
+
+        let (address, _, did_page_cross) = self.decode_operand(instruction);
+        if did_page_cross {
+            // the index is at most `0xFF`, so the high byte is off by one
+            self.bus.dummy_read(address.wrapping_sub(0x100));
+        } else if instruction.opcode.writes_memory() {
+            self.bus.dummy_read(address);
+        }
+    }
+
     /// decods the operand of an instruction and returnrs
     /// (the decoded_operand, base cycle time for the instruction, has crossed page)
     fn decode_operand(&self, instruction: &Instruction) -> (u16, u8, bool) {
@@ -302,7 +511,7 @@ where
                 let high = self.read_bus((location_indirect + 1) & 0xFF) as u16;
 
                 let unindxed_address = high << 8 | low;
-                let result = unindxed_address + self.reg_y as u16;
+                let result = unindxed_address.wrapping_add(self.reg_y as u16);
 
                 let page_cross = if is_on_same_page(unindxed_address, result) {
                     0
@@ -322,7 +531,7 @@ where
                 false,
             ),
             AddressingMode::AbsoluteX => {
-                let result = instruction.operand + self.reg_x as u16;
+                let result = instruction.operand.wrapping_add(self.reg_x as u16);
                 let page_cross = if is_on_same_page(instruction.operand, result) {
                     0
                 } else {
@@ -336,7 +545,7 @@ where
                 )
             }
             AddressingMode::AbsoluteY => {
-                let result = instruction.operand + self.reg_y as u16;
+                let result = instruction.operand.wrapping_add(self.reg_y as u16);
                 let page_cross = if is_on_same_page(instruction.operand, result) {
                     0
                 } else {
@@ -375,6 +584,52 @@ where
         self.set_flag_status(StatusFlag::Negative, result & 0x80 != 0);
     }
 
+    /// `ADC` in BCD, the result and the carry are the decimal ones, the overflow is
+    /// from the signed sum after adjusting the low digit, like the 65C02
+    fn run_decimal_adc(&mut self, operand: u8, carry: u8) {
+        let mut low = (self.reg_a & 0xF) + (operand & 0xF) + carry;
+        if low >= 0xA {
+            low = ((low + 6) & 0xF) + 0x10;
+        }
+
+        let mut result = (self.reg_a & 0xF0) as u16 + (operand & 0xF0) as u16 + low as u16;
+        let signed_result = (self.reg_a & 0xF0) as i8 as i16 + (operand & 0xF0) as i8 as i16;
+        let signed_result = signed_result + low as i16;
+        if result >= 0xA0 {
+            result += 0x60;
+        }
+
+        self.set_flag_status(StatusFlag::Overflow, !(-128..=127).contains(&signed_result));
+        self.set_flag_status(StatusFlag::Carry, result >= 0x100);
+        self.update_zero_negative_flags(result as u8);
+        self.reg_a = result as u8;
+    }
+
+    /// `SBC` in BCD, the carry and overflow are the same as in binary, the result is
+    /// adjusted for each digit that borrowed, like the 65C02
+    fn run_decimal_sbc(&mut self, operand: u8, borrow: u8) {
+        let low = (self.reg_a & 0xF) as i16 - (operand & 0xF) as i16 - borrow as i16;
+        let binary = (self.reg_a as u16)
+            .wrapping_sub(operand as u16)
+            .wrapping_sub(borrow as u16);
+
+        let mut result = self.reg_a as i16 - operand as i16 - borrow as i16;
+        if result < 0 {
+            result -= 0x60;
+        }
+        if low < 0 {
+            result -= 0x06;
+        }
+
+        self.set_flag_status(
+            StatusFlag::Overflow,
+            ((binary as u8 ^ self.reg_a) & 0x80 != 0) && ((operand ^ self.reg_a) & 0x80 != 0),
+        );
+        self.set_flag_status(StatusFlag::Carry, binary & 0xff00 == 0);
+        self.update_zero_negative_flags(result as u8);
+        self.reg_a = result as u8;
+    }
+
     fn run_bitwise_operation<F>(&mut self, decoded_operand: u16, is_operand_address: bool, f: F)
     where
         F: Fn(u8, u8) -> u8,
@@ -446,14 +701,36 @@ where
 
     fn push_stack(&mut self, data: u8) {
         self.write_bus(0x0100 | self.reg_sp as u16, data);
+        if self.reg_sp == 0x00 {
+            self.report_stack_wrap(StackWrap::Overflow);
+        }
         self.reg_sp = self.reg_sp.wrapping_sub(1);
     }
 
     fn pull_stack(&mut self) -> u8 {
+        if self.reg_sp == 0xFF {
+            self.report_stack_wrap(StackWrap::Underflow);
+        }
         self.reg_sp = self.reg_sp.wrapping_add(1);
         self.read_bus(0x0100 | self.reg_sp as u16)
     }
 
+    fn report_stack_wrap(&mut self, direction: StackWrap) {
+        if self.stack_wrap_warnings {
+            let pc = if self.in_interrupt_sequence {
+                self.reg_pc
+            } else {
+                self.instruction_pc
+            };
+
+            self.diagnostics.push(Diagnostic::StackWrap {
+                pc,
+                direction,
+                in_interrupt: self.in_interrupt_sequence,
+            });
+        }
+    }
+
     // is_soft should be only from BRK
     fn execute_interrupt(&mut self, is_soft: bool, is_nmi: bool) {
         let pc = self.reg_pc;
@@ -461,12 +738,15 @@ where
         let low = pc as u8;
         let high = (pc >> 8) as u8;
 
+        self.in_interrupt_sequence = true;
         self.push_stack(high);
         self.push_stack(low);
 
         self.set_flag_status(StatusFlag::BreakCommand, is_soft);
 
-        self.push_stack(self.reg_status);
+        // bit 5 is not stored, it is always pushed as 1
+        self.push_stack(self.reg_status | 0x20);
+        self.in_interrupt_sequence = false;
 
         let jump_vector_address = if is_nmi {
             NMI_VECTOR_ADDRESS
@@ -482,6 +762,7 @@ where
         }
 
         self.set_flag(StatusFlag::InterruptDisable);
+        self.irq_poll_interrupt_disable = true;
 
         let low = self.read_bus(jump_vector_address) as u16;
         let high = self.read_bus(jump_vector_address + 1) as u16;
@@ -511,6 +792,40 @@ where
         let request = self.bus.request_dmc_reader_read();
 
         if let Some(addr) = request {
+            let controller_address = self.pending_controller_read();
+
+            if self.dmc_collision_stress
+                && controller_address.is_none()
+                && self.dmc_fetch_delay < DMC_STRESS_MAX_DELAY
+            {
+                self.dmc_fetch_delay += 1;
+                return;
+            }
+            self.dmc_fetch_delay = 0;
+
+            // the cycle before the write is the dummy read, where the DMA halts the CPU
+            if self.cycles_to_wait == 2
+                && self
+                    .next_instruction
+                    .as_ref()
+                    .is_some_and(|(instruction, _)| {
+                        matches!(
+                            instruction.opcode,
+                            Opcode::Shy | Opcode::Shx | Opcode::Ahx | Opcode::Tas
+                        )
+                    })
+            {
+                self.unstable_store_halted = true;
+            }
+
+            if let Some(controller_address) = controller_address {
+                // the CPU is halted on the read cycle, which is repeated after
+                // the DMA, the extra read clocks the controller shift register
+                // and a bit is lost
+                self.read_bus(controller_address);
+                self.controller_read_glitches += 1;
+            }
+
             let data = self.read_bus(addr);
 
             self.bus.submit_dmc_buffer_byte(data);
@@ -521,7 +836,37 @@ where
         }
     }
 
+    /// The address of the controller port if the next cycle is the last cycle of an
+    /// instruction reading from `$4016/$4017`, which is where it reads its operand.
+    ///
+    /// The instructions run on their last cycle, so this is an approximation for
+    /// read-modify-write instructions, which read the operand earlier.
+    fn pending_controller_read(&self) -> Option<u16> {
+        if self.cycles_to_wait != 1 {
+            return None;
+        }
+        let (instruction, _) = self.next_instruction.as_ref()?;
+
+        match instruction.addressing_mode {
+            AddressingMode::Immediate
+            | AddressingMode::Indirect
+            | AddressingMode::Accumulator
+            | AddressingMode::Relative
+            | AddressingMode::Implied => return None,
+            _ => {}
+        }
+        if instruction.opcode.skips_operand_read() {
+            return None;
+        }
+
+        // the indirect modes only read the zero page to get the address
+        let (address, _, _) = self.decode_operand(instruction);
+        matches!(address, 0x4016 | 0x4017).then_some(address)
+    }
+
     fn fetch_next_instruction(&mut self) -> Instruction {
+        self.instruction_pc = self.reg_pc;
+        self.bus.set_instruction_pc(self.reg_pc);
         let opcode = self.read_bus(self.reg_pc);
         self.reg_pc += 1;
 
@@ -558,7 +903,6 @@ where
         let mut state = CPURunState::NormalInstructionExecution;
 
         match instruction.opcode {
-            // TODO: Add support for BCD mode
             Opcode::Adc => {
                 let operand = if is_operand_address {
                     self.read_bus(decoded_operand)
@@ -570,20 +914,24 @@ where
                 } else {
                     1
                 };
-                let result = (self.reg_a as u16)
-                    .wrapping_add(operand as u16)
-                    .wrapping_add(carry);
-                // overflow = result is negative ^ (reg_A is negative | operand is negative)
-                // meaning, that if the operands are positive but the result is negative, then something
-                // is not right, and the same way vise versa
-                self.set_flag_status(
-                    StatusFlag::Overflow,
-                    (((result as u8 ^ self.reg_a) & 0x80) != 0)
-                        && (((operand ^ self.reg_a) & 0x80) == 0),
-                );
-                self.update_zero_negative_flags(result as u8);
-                self.set_flag_status(StatusFlag::Carry, result & 0xff00 != 0);
-                self.reg_a = result as u8;
+                if self.decimal_mode && self.reg_status & (StatusFlag::DecimalMode as u8) != 0 {
+                    self.run_decimal_adc(operand, carry as u8);
+                } else {
+                    let result = (self.reg_a as u16)
+                        .wrapping_add(operand as u16)
+                        .wrapping_add(carry);
+                    // overflow = result is negative ^ (reg_A is negative | operand is negative)
+                    // meaning, that if the operands are positive but the result is negative, then something
+                    // is not right, and the same way vise versa
+                    self.set_flag_status(
+                        StatusFlag::Overflow,
+                        (((result as u8 ^ self.reg_a) & 0x80) != 0)
+                            && (((operand ^ self.reg_a) & 0x80) == 0),
+                    );
+                    self.update_zero_negative_flags(result as u8);
+                    self.set_flag_status(StatusFlag::Carry, result & 0xff00 != 0);
+                    self.reg_a = result as u8;
+                }
             }
             Opcode::Asl => {
                 let mut operand = if is_operand_address {
@@ -603,7 +951,7 @@ where
 
                 if is_operand_address {
                     // save back
-                    self.write_bus(decoded_operand, operand);
+                    self.write_modified_value(decoded_operand, operand);
 
                     if instruction.addressing_mode == AddressingMode::AbsoluteX {
                         cycle_time = 7; // special case
@@ -632,7 +980,7 @@ where
 
                 if is_operand_address {
                     // save back
-                    self.write_bus(decoded_operand, operand);
+                    self.write_modified_value(decoded_operand, operand);
 
                     if instruction.addressing_mode == AddressingMode::AbsoluteX {
                         cycle_time = 7; // special case
@@ -665,7 +1013,7 @@ where
 
                 if is_operand_address {
                     // save back
-                    self.write_bus(decoded_operand, operand);
+                    self.write_modified_value(decoded_operand, operand);
 
                     if instruction.addressing_mode == AddressingMode::AbsoluteX {
                         cycle_time = 7; // special case
@@ -698,7 +1046,7 @@ where
 
                 if is_operand_address {
                     // save back
-                    self.write_bus(decoded_operand, operand);
+                    self.write_modified_value(decoded_operand, operand);
 
                     if instruction.addressing_mode == AddressingMode::AbsoluteX {
                         cycle_time = 7; // special case
@@ -718,7 +1066,6 @@ where
             Opcode::Ora => {
                 self.run_bitwise_operation(decoded_operand, is_operand_address, |a, b| a | b);
             }
-            // TODO: Add support for BCD mode
             Opcode::Sbc => {
                 let operand = if is_operand_address {
                     self.read_bus(decoded_operand)
@@ -731,20 +1078,24 @@ where
                 } else {
                     1
                 };
-                let result = (self.reg_a as u16)
-                    .wrapping_sub(operand as u16)
-                    .wrapping_sub(carry);
-                // overflow = (result's sign) & (2nd operand's sign) & !(1st operand's sign)
-                // this was obtained from binary table
-                self.set_flag_status(
-                    StatusFlag::Overflow,
-                    ((result as u8 ^ self.reg_a) & 0x80 != 0)
-                        && ((operand ^ self.reg_a) & 0x80 != 0),
-                );
-                self.set_flag_status(StatusFlag::Carry, result & 0xff00 == 0);
-                self.update_zero_negative_flags(result as u8);
-
-                self.reg_a = result as u8;
+                if self.decimal_mode && self.reg_status & (StatusFlag::DecimalMode as u8) != 0 {
+                    self.run_decimal_sbc(operand, carry as u8);
+                } else {
+                    let result = (self.reg_a as u16)
+                        .wrapping_sub(operand as u16)
+                        .wrapping_sub(carry);
+                    // overflow = (result's sign) & (2nd operand's sign) & !(1st operand's sign)
+                    // this was obtained from binary table
+                    self.set_flag_status(
+                        StatusFlag::Overflow,
+                        ((result as u8 ^ self.reg_a) & 0x80 != 0)
+                            && ((operand ^ self.reg_a) & 0x80 != 0),
+                    );
+                    self.set_flag_status(StatusFlag::Carry, result & 0xff00 == 0);
+                    self.update_zero_negative_flags(result as u8);
+
+                    self.reg_a = result as u8;
+                }
             }
             Opcode::Bit => {
                 // only Absolute and Zero page
@@ -851,7 +1202,7 @@ where
                 self.update_zero_negative_flags(result);
 
                 // put back
-                self.write_bus(decoded_operand, result);
+                self.write_modified_value(decoded_operand, result);
 
                 if instruction.addressing_mode == AddressingMode::AbsoluteX {
                     cycle_time = 7; // special case
@@ -867,7 +1218,7 @@ where
                 self.update_zero_negative_flags(result);
 
                 // put back
-                self.write_bus(decoded_operand, result);
+                self.write_modified_value(decoded_operand, result);
 
                 if instruction.addressing_mode == AddressingMode::AbsoluteX {
                     cycle_time = 7; // special case
@@ -950,7 +1301,7 @@ where
                 let address = high << 8 | low;
 
                 // go to address + 1
-                self.reg_pc = address + 1;
+                self.reg_pc = address.wrapping_add(1);
 
                 cycle_time = 6;
             }
@@ -1092,9 +1443,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::Ora,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1113,9 +1464,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::Eor,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1134,9 +1485,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::And,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1155,9 +1506,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::Adc,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1176,9 +1527,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::Sbc,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1197,9 +1548,9 @@ where
                 });
                 self.run_instruction(&Instruction {
                     opcode_byte: 0,
-                    operand: instruction.operand,
+                    operand: self.modified_value as u16,
                     opcode: Opcode::Cmp,
-                    addressing_mode: instruction.addressing_mode,
+                    addressing_mode: AddressingMode::Immediate,
                 });
                 self.cycles_to_wait = old_cycles_to_wait;
 
@@ -1318,44 +1669,30 @@ where
             Opcode::Ahx => {
                 assert!(is_operand_address);
 
-                let high_byte = (decoded_operand >> 8) as u8;
-
-                self.write_bus(decoded_operand, self.reg_a & self.reg_x & high_byte);
+                let value = self.reg_a & self.reg_x;
+                self.run_unstable_store(value, decoded_operand, self.reg_y, did_page_cross);
 
                 cycle_time += !did_page_cross as u8;
             }
             Opcode::Shy => {
                 assert!(is_operand_address);
 
-                let low_byte = decoded_operand & 0xFF;
-                let high_byte = (decoded_operand >> 8) as u8;
-
-                let value = self.reg_y & (high_byte + 1);
-
-                self.write_bus((value as u16) << 8 | low_byte, value);
+                self.run_unstable_store(self.reg_y, decoded_operand, self.reg_x, did_page_cross);
 
                 cycle_time += !did_page_cross as u8;
             }
             Opcode::Shx => {
                 assert!(is_operand_address);
 
-                let low_byte = decoded_operand & 0xFF;
-                let high_byte = (decoded_operand >> 8) as u8;
-
-                let value = self.reg_x & (high_byte + 1);
-
-                self.write_bus((value as u16) << 8 | low_byte, value);
+                self.run_unstable_store(self.reg_x, decoded_operand, self.reg_y, did_page_cross);
 
                 cycle_time += !did_page_cross as u8;
             }
             Opcode::Tas => {
                 assert!(is_operand_address);
 
-                let high_byte = (decoded_operand >> 8) as u8;
-
                 self.reg_sp = self.reg_x & self.reg_a;
-
-                self.write_bus(decoded_operand, self.reg_sp & high_byte);
+                self.run_unstable_store(self.reg_sp, decoded_operand, self.reg_y, did_page_cross);
 
                 cycle_time += !did_page_cross as u8;
             }
@@ -1372,8 +1709,14 @@ where
                 self.reg_sp = result;
             }
             Opcode::Kil => {
-                // TODO: implement halt
-                println!("KIL instruction executed, should halt....");
+                // the CPU jams until reset, stay on the opcode
+                self.reg_pc = self.instruction_pc;
+                self.halted = true;
+                self.diagnostics.push(Diagnostic::CpuHalted {
+                    pc: self.instruction_pc,
+                    opcode: instruction.opcode_byte,
+                });
+                state = CPURunState::Halted;
             }
         };
 
@@ -1392,7 +1735,11 @@ where
         self.reg_status = state.reg_status;
         self.nmi_pin_status = state.nmi_pin_status;
         self.irq_pin_status = state.irq_pin_status;
+        self.irq_level = state.irq_level;
+        self.irq_level_polled = state.irq_level_polled;
+        self.irq_poll_interrupt_disable = state.irq_poll_interrupt_disable;
         self.cycles_to_wait = state.cycles_to_wait;
+        self.halted = state.halted;
         self.dma_remaining = state.dma_remaining;
         self.dma_address = state.dma_address;
         self.next_instruction = state.next_instruction;
@@ -1410,9 +1757,14 @@ struct SavableCPUState {
 
     nmi_pin_status: bool,
     irq_pin_status: bool,
+    irq_level: bool,
+    irq_level_polled: bool,
+    irq_poll_interrupt_disable: bool,
 
     cycles_to_wait: u8,
 
+    halted: bool,
+
     dma_remaining: u16,
     dma_address: u8,
 
@@ -1430,7 +1782,11 @@ impl SavableCPUState {
             reg_status: cpu.reg_status,
             nmi_pin_status: cpu.nmi_pin_status,
             irq_pin_status: cpu.irq_pin_status,
+            irq_level: cpu.irq_level,
+            irq_level_polled: cpu.irq_level_polled,
+            irq_poll_interrupt_disable: cpu.irq_poll_interrupt_disable,
             cycles_to_wait: cpu.cycles_to_wait,
+            halted: cpu.halted,
             dma_remaining: cpu.dma_remaining,
             dma_address: cpu.dma_address,
             next_instruction: cpu.next_instruction,
@@ -1438,22 +1794,11 @@ impl SavableCPUState {
     }
 }
 
-/// This is a solution to wrap a reference to reader
-struct WrapperReader<'a, R: Read> {
-    pub inner: &'a mut R,
-}
-
-impl<R: Read> Read for WrapperReader<'_, R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.inner.read(buf)
-    }
-}
-
 impl<T> Savable for CPU6502<T>
 where
     T: CPUBusTrait,
 {
-    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn Write) -> Result<(), SaveError> {
         let state = SavableCPUState::from_cpu(self);
 
         let data = bincode::serialize(&state).map_err(|_| SaveError::SerializationError)?;
@@ -1464,12 +1809,10 @@ where
         Ok(())
     }
 
-    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
-        let outer_reader = WrapperReader { inner: reader };
-
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
         {
             let state: SavableCPUState =
-                bincode::deserialize_from(outer_reader).map_err(|err| match *err {
+                bincode::deserialize_from(&mut *reader).map_err(|err| match *err {
                     bincode::ErrorKind::Io(err) => SaveError::IoError(err),
                     _ => SaveError::SerializationError,
                 })?;
@@ -1482,3 +1825,23 @@ where
         Ok(())
     }
 }
+
+#[cfg(feature = "state-json")]
+impl<T> JsonSavable for CPU6502<T>
+where
+    T: CPUBusTrait + JsonSavable,
+{
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "registers": to_json_value(&SavableCPUState::from_cpu(self)),
+            "bus": self.bus.to_json(),
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        let state: SavableCPUState = from_json_value(take_json_field(&mut value, "registers")?)?;
+        self.load_serialized_state(state);
+
+        self.bus.load_json(take_json_field(&mut value, "bus")?)
+    }
+}
@@ -0,0 +1,1004 @@
+//! Random instruction streams run by [`CPU6502`] and by a small reference interpreter
+//! written only for these tests, the registers, flags, written memory and cycles are
+//! compared after every instruction.
+//!
+//! The cases are seeded, set `PLASTIC_CPU_FUZZ_SEED` to run a single case (the seed is
+//! printed on failure), or `PLASTIC_CPU_FUZZ_CASES` to run more cases than the default.
+
+#[cfg(test)]
+mod cpu_reference_tests {
+    use super::super::{instruction::Instruction, CPUBusTrait, CPU6502};
+    use crate::common::{interconnection::*, save_state::Savable};
+    use std::cell::RefCell;
+
+    const DEFAULT_CASES: u64 = 3000;
+    const INSTRUCTIONS_PER_CASE: usize = 24;
+
+    /// writes to `$8000-$FFFF` are ignored, so the programs can't modify themselves
+    const ROM_START: u16 = 0x8000;
+    /// the programs are placed randomly in `$8000-$EFFF`
+    const PROGRAM_START_MAX: u16 = 0xEF00;
+    /// the pointers used by `JMP ($xxxx)`
+    const JMP_POINTERS_PAGE: u16 = 0xF000;
+    /// `BRK` jumps here, to an `RTI`, which returns after the `BRK` padding byte
+    const RTI_STUB: u16 = 0xF800;
+    /// `JSR` jumps here, to an `RTS`, which returns after the `JSR`
+    const RTS_STUB: u16 = 0xF801;
+
+    /// The halting opcodes, and the unstable ones (which depend on analog effects
+    /// in the chip), `RTI` and `RTS` are only used through the stubs.
+    ///
+    /// `SHY`, `SHX`, `AHX` and `TAS` are only unstable with DMA, which doesn't happen here.
+    const EXCLUDED_OPCODES: [u8; 16] = [
+        0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2, // KIL
+        0x8B, 0xAB, // XAA, LAX #imm
+        0x40, 0x60, // RTI, RTS
+    ];
+
+    /// Cycles of each opcode, without the page cross and branch taken cycles
+    #[rustfmt::skip]
+    const CYCLES: [u8; 256] = [
+    //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+        7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 00
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 10
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 20
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 30
+        6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 40
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 50
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 60
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 70
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 80
+        2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 90
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // A0
+        2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // B0
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // C0
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // D0
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // E0
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // F0
+    ];
+
+    const CARRY: u8 = 1 << 0;
+    const ZERO: u8 = 1 << 1;
+    const INTERRUPT_DISABLE: u8 = 1 << 2;
+    const DECIMAL: u8 = 1 << 3;
+    const BREAK: u8 = 1 << 4;
+    const UNUSED: u8 = 1 << 5;
+    const OVERFLOW: u8 = 1 << 6;
+    const NEGATIVE: u8 = 1 << 7;
+
+    /// `xorshift64*`, good enough to generate the programs
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            Self((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn byte(&mut self) -> u8 {
+            (self.next() >> 32) as u8
+        }
+
+        fn below(&mut self, max: u64) -> u64 {
+            (self.next() >> 16) % max
+        }
+    }
+
+    struct FlatBus {
+        data: Vec<u8>,
+        /// the addresses written since the last instruction
+        writes: RefCell<Vec<u16>>,
+    }
+
+    impl Savable for FlatBus {
+        fn save(
+            &self,
+            _: &mut dyn std::io::Write,
+        ) -> Result<(), crate::common::save_state::SaveError> {
+            unreachable!()
+        }
+
+        fn load(
+            &mut self,
+            _: &mut dyn std::io::Read,
+        ) -> Result<(), crate::common::save_state::SaveError> {
+            unreachable!()
+        }
+    }
+
+    impl CPUBusTrait for FlatBus {
+        fn read(&self, address: u16) -> u8 {
+            self.data[address as usize]
+        }
+
+        fn write(&mut self, address: u16, data: u8) {
+            self.writes.borrow_mut().push(address);
+            if address < ROM_START {
+                self.data[address as usize] = data;
+            }
+        }
+
+        fn reset(&mut self) {
+            unreachable!()
+        }
+    }
+
+    impl PPUCPUConnection for FlatBus {
+        fn is_nmi_pin_set(&self) -> bool {
+            false
+        }
+        fn clear_nmi_pin(&mut self) {}
+        fn is_dma_request(&self) -> bool {
+            false
+        }
+        fn clear_dma_request(&mut self) {}
+        fn dma_address(&mut self) -> u8 {
+            unreachable!()
+        }
+        fn send_oam_data(&mut self, _address: u8, _data: u8) {
+            unreachable!();
+        }
+    }
+
+    impl APUCPUConnection for FlatBus {
+        fn request_dmc_reader_read(&self) -> Option<u16> {
+            None
+        }
+        fn submit_dmc_buffer_byte(&mut self, _: u8) {
+            unreachable!();
+        }
+    }
+
+    impl CPUIrqProvider for FlatBus {
+        fn is_irq_change_requested(&self) -> bool {
+            false
+        }
+
+        fn irq_pin_state(&self) -> bool {
+            unreachable!();
+        }
+
+        fn clear_irq_request_pin(&mut self) {
+            unreachable!();
+        }
+    }
+
+    /// The operand of an instruction after resolving its addressing mode
+    enum Operand {
+        None,
+        Immediate(u8),
+        Address {
+            address: u16,
+            /// the high byte of the address before indexing
+            base_high: u8,
+            page_crossed: bool,
+        },
+    }
+
+    #[derive(Clone, Copy)]
+    enum Mode {
+        Implied,
+        Immediate,
+        ZeroPage,
+        ZeroPageX,
+        ZeroPageY,
+        Absolute,
+        AbsoluteX,
+        AbsoluteY,
+        IndirectX,
+        IndirectY,
+        Indirect,
+        Relative,
+    }
+
+    /// The addressing mode from the `aaabbbcc` opcode layout
+    fn mode(opcode: u8) -> Mode {
+        let aaa = opcode >> 5;
+        let bbb = (opcode >> 2) & 7;
+        let cc = opcode & 3;
+        // the `X` register is the operand of `STX`, `LDX`, `SAX` and `LAX`, so they index with `Y`
+        let uses_y = cc >= 2 && (aaa == 4 || aaa == 5);
+
+        match (bbb, cc) {
+            (0, 0) if aaa == 1 => Mode::Absolute,
+            (0, 0) if aaa < 4 => Mode::Implied,
+            (0, 1 | 3) => Mode::IndirectX,
+            (0, _) => Mode::Immediate,
+            (1, _) => Mode::ZeroPage,
+            (2, 1 | 3) => Mode::Immediate,
+            (2, _) => Mode::Implied,
+            (3, 0) if aaa == 3 => Mode::Indirect,
+            (3, _) => Mode::Absolute,
+            (4, 0) => Mode::Relative,
+            (4, 2) => Mode::Implied,
+            (4, _) => Mode::IndirectY,
+            (5, _) if uses_y => Mode::ZeroPageY,
+            (5, _) => Mode::ZeroPageX,
+            (6, 1 | 3) => Mode::AbsoluteY,
+            (6, _) => Mode::Implied,
+            (7, _) if uses_y => Mode::AbsoluteY,
+            (7, _) => Mode::AbsoluteX,
+            _ => unreachable!(),
+        }
+    }
+
+    fn instruction_len(opcode: u8) -> u16 {
+        match mode(opcode) {
+            // the padding byte after `BRK`
+            Mode::Implied if opcode == 0x00 => 2,
+            Mode::Implied => 1,
+            Mode::Immediate
+            | Mode::ZeroPage
+            | Mode::ZeroPageX
+            | Mode::ZeroPageY
+            | Mode::IndirectX
+            | Mode::IndirectY
+            | Mode::Relative => 2,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 3,
+        }
+    }
+
+    /// The reads that take an extra cycle when the indexing crosses a page
+    fn has_page_cross_cycle(opcode: u8) -> bool {
+        match opcode & 3 {
+            // all except `STA`
+            1 => opcode >> 5 != 4,
+            // `NOP abs,X`, `LDY abs,X`, `LDX abs,Y`, `LAX`, `LAS`
+            _ => matches!(
+                opcode,
+                0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC | 0xBC | 0xBE | 0xB3 | 0xBF | 0xBB
+            ),
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Registers {
+        pc: u16,
+        sp: u8,
+        a: u8,
+        x: u8,
+        y: u8,
+        /// without the `B` and unused bits, which are not stored in the CPU
+        p: u8,
+    }
+
+    /// A straightforward interpreter of the NMOS 6502, without decimal mode like the NES,
+    /// unless `decimal_mode` is set, then `ADC` and `SBC` are decimal like in the 65C02
+    struct Reference {
+        regs: Registers,
+        memory: Vec<u8>,
+        writes: Vec<u16>,
+        decimal_mode: bool,
+    }
+
+    impl Reference {
+        fn read(&self, address: u16) -> u8 {
+            self.memory[address as usize]
+        }
+
+        fn write(&mut self, address: u16, data: u8) {
+            self.writes.push(address);
+            if address < ROM_START {
+                self.memory[address as usize] = data;
+            }
+        }
+
+        fn fetch(&mut self) -> u8 {
+            let data = self.read(self.regs.pc);
+            self.regs.pc = self.regs.pc.wrapping_add(1);
+            data
+        }
+
+        fn fetch_word(&mut self) -> u16 {
+            let low = self.fetch() as u16;
+            let high = self.fetch() as u16;
+            high << 8 | low
+        }
+
+        fn read_zero_page_word(&self, address: u8) -> u16 {
+            let low = self.read(address as u16) as u16;
+            let high = self.read(address.wrapping_add(1) as u16) as u16;
+            high << 8 | low
+        }
+
+        fn push(&mut self, data: u8) {
+            self.write(0x100 | self.regs.sp as u16, data);
+            self.regs.sp = self.regs.sp.wrapping_sub(1);
+        }
+
+        fn pull(&mut self) -> u8 {
+            self.regs.sp = self.regs.sp.wrapping_add(1);
+            self.read(0x100 | self.regs.sp as u16)
+        }
+
+        fn flag(&self, flag: u8) -> bool {
+            self.regs.p & flag != 0
+        }
+
+        fn set_flag(&mut self, flag: u8, value: bool) {
+            if value {
+                self.regs.p |= flag;
+            } else {
+                self.regs.p &= !flag;
+            }
+        }
+
+        fn set_zero_negative(&mut self, value: u8) -> u8 {
+            self.set_flag(ZERO, value == 0);
+            self.set_flag(NEGATIVE, value & 0x80 != 0);
+            value
+        }
+
+        fn indexed(base: u16, index: u8) -> Operand {
+            let address = base.wrapping_add(index as u16);
+            Operand::Address {
+                address,
+                base_high: (base >> 8) as u8,
+                page_crossed: address & 0xFF00 != base & 0xFF00,
+            }
+        }
+
+        fn operand(&mut self, mode: Mode) -> Operand {
+            let direct = |address: u16| Operand::Address {
+                address,
+                base_high: (address >> 8) as u8,
+                page_crossed: false,
+            };
+
+            match mode {
+                Mode::Implied => Operand::None,
+                Mode::Immediate | Mode::Relative => Operand::Immediate(self.fetch()),
+                Mode::ZeroPage => direct(self.fetch() as u16),
+                Mode::ZeroPageX => direct(self.fetch().wrapping_add(self.regs.x) as u16),
+                Mode::ZeroPageY => direct(self.fetch().wrapping_add(self.regs.y) as u16),
+                Mode::Absolute => direct(self.fetch_word()),
+                Mode::AbsoluteX => {
+                    let base = self.fetch_word();
+                    Self::indexed(base, self.regs.x)
+                }
+                Mode::AbsoluteY => {
+                    let base = self.fetch_word();
+                    Self::indexed(base, self.regs.y)
+                }
+                Mode::IndirectX => {
+                    let pointer = self.fetch().wrapping_add(self.regs.x);
+                    direct(self.read_zero_page_word(pointer))
+                }
+                Mode::IndirectY => {
+                    let pointer = self.fetch();
+                    let base = self.read_zero_page_word(pointer);
+                    Self::indexed(base, self.regs.y)
+                }
+                Mode::Indirect => {
+                    let pointer = self.fetch_word();
+                    // the high byte is read from the same page
+                    let high_pointer = pointer & 0xFF00 | pointer.wrapping_add(1) & 0xFF;
+                    let low = self.read(pointer) as u16;
+                    let high = self.read(high_pointer) as u16;
+                    direct(high << 8 | low)
+                }
+            }
+        }
+
+        fn load(&self, operand: &Operand) -> u8 {
+            match *operand {
+                Operand::None => self.regs.a,
+                Operand::Immediate(value) => value,
+                Operand::Address { address, .. } => self.read(address),
+            }
+        }
+
+        fn store(&mut self, operand: &Operand, value: u8) {
+            match *operand {
+                Operand::None => self.regs.a = value,
+                Operand::Address { address, .. } => self.write(address, value),
+                Operand::Immediate(_) => unreachable!(),
+            }
+        }
+
+        fn add(&mut self, value: u8) {
+            let a = self.regs.a;
+            let sum = a as u16 + value as u16 + self.flag(CARRY) as u16;
+            let result = sum as u8;
+            self.set_flag(CARRY, sum > 0xFF);
+            // both inputs have the same sign, and the result has a different one
+            self.set_flag(OVERFLOW, (a ^ result) & (value ^ result) & 0x80 != 0);
+            self.regs.a = self.set_zero_negative(result);
+        }
+
+        /// Decimal `ADC`, each digit is added in binary, and a digit over 9 is adjusted
+        /// by 6 and carries to the next one. The overflow is from the signed sum of the
+        /// high digits before adjusting it
+        fn decimal_add(&mut self, value: u8) {
+            let a = self.regs.a;
+            let mut low = (a & 0xF) + (value & 0xF) + self.flag(CARRY) as u8;
+            let low_carry = low > 9;
+            if low_carry {
+                low += 6;
+            }
+
+            let signed_high = (a as i8 >> 4) + (value as i8 >> 4) + low_carry as i8;
+            self.set_flag(OVERFLOW, !(-8..=7).contains(&signed_high));
+
+            let mut high = (a >> 4) + (value >> 4) + low_carry as u8;
+            let carry = high > 9;
+            if carry {
+                high += 6;
+            }
+            self.set_flag(CARRY, carry);
+            self.regs.a = self.set_zero_negative(high << 4 | low & 0xF);
+        }
+
+        /// Decimal `SBC`, the binary subtraction with the same carry and overflow, then
+        /// the result is adjusted by 6 for each digit that borrowed
+        fn decimal_subtract(&mut self, value: u8) {
+            let a = self.regs.a;
+            let low_borrow = a & 0xF < (value & 0xF) + !self.flag(CARRY) as u8;
+            self.add(!value);
+
+            let mut result = self.regs.a;
+            if low_borrow {
+                result = result.wrapping_sub(0x06);
+            }
+            if !self.flag(CARRY) {
+                result = result.wrapping_sub(0x60);
+            }
+            self.regs.a = self.set_zero_negative(result);
+        }
+
+        fn compare(&mut self, register: u8, value: u8) {
+            self.set_flag(CARRY, register >= value);
+            self.set_zero_negative(register.wrapping_sub(value));
+        }
+
+        /// `ASL`, `ROL`, `LSR` and `ROR`, by the `aaa` bits of the opcode
+        fn shift(&mut self, kind: u8, value: u8) -> u8 {
+            let carry = self.flag(CARRY) as u8;
+            let (result, carry_out) = match kind {
+                0 => (value << 1, value & 0x80 != 0),
+                1 => (value << 1 | carry, value & 0x80 != 0),
+                2 => (value >> 1, value & 1 != 0),
+                _ => (value >> 1 | carry << 7, value & 1 != 0),
+            };
+            self.set_flag(CARRY, carry_out);
+            self.set_zero_negative(result)
+        }
+
+        /// `ORA`, `AND`, `EOR`, `ADC`, `CMP` and `SBC`, by the `aaa` bits of the opcode
+        fn alu(&mut self, kind: u8, value: u8) {
+            match kind {
+                0 => self.regs.a = self.set_zero_negative(self.regs.a | value),
+                1 => self.regs.a = self.set_zero_negative(self.regs.a & value),
+                2 => self.regs.a = self.set_zero_negative(self.regs.a ^ value),
+                3 if self.decimal_mode && self.flag(DECIMAL) => self.decimal_add(value),
+                3 => self.add(value),
+                6 => self.compare(self.regs.a, value),
+                7 if self.decimal_mode && self.flag(DECIMAL) => self.decimal_subtract(value),
+                7 => self.add(!value),
+                _ => unreachable!(),
+            }
+        }
+
+        /// `BPL`, `BMI`, `BVC`, `BVS`, `BCC`, `BCS`, `BNE` and `BEQ`, returns the extra
+        /// cycles of the taken branch
+        fn branch(&mut self, opcode: u8, offset: u8) -> u8 {
+            let flag = [NEGATIVE, OVERFLOW, CARRY, ZERO][(opcode >> 6) as usize];
+            if self.flag(flag) != (opcode & 0x20 != 0) {
+                return 0;
+            }
+
+            let target = self.regs.pc.wrapping_add(offset as i8 as u16);
+            let cycles = if target & 0xFF00 == self.regs.pc & 0xFF00 {
+                1
+            } else {
+                2
+            };
+            self.regs.pc = target;
+            cycles
+        }
+
+        /// Run one instruction, returns the number of cycles it took
+        fn step(&mut self) -> u8 {
+            let opcode = self.fetch();
+            let mode = mode(opcode);
+            let operand = self.operand(mode);
+            let mut cycles = CYCLES[opcode as usize];
+
+            if let Operand::Address {
+                page_crossed: true, ..
+            } = operand
+            {
+                cycles += has_page_cross_cycle(opcode) as u8;
+            }
+
+            let aaa = opcode >> 5;
+            match opcode {
+                // BRK
+                0x00 => {
+                    // skip the padding byte
+                    self.regs.pc = self.regs.pc.wrapping_add(1);
+                    self.push((self.regs.pc >> 8) as u8);
+                    self.push(self.regs.pc as u8);
+                    self.push(self.regs.p | BREAK | UNUSED);
+                    self.set_flag(INTERRUPT_DISABLE, true);
+                    self.regs.pc = self.read(0xFFFE) as u16 | (self.read(0xFFFF) as u16) << 8;
+                }
+                // JSR
+                0x20 => {
+                    let Operand::Address { address, .. } = operand else {
+                        unreachable!()
+                    };
+                    let return_address = self.regs.pc.wrapping_sub(1);
+                    self.push((return_address >> 8) as u8);
+                    self.push(return_address as u8);
+                    self.regs.pc = address;
+                }
+                // RTI
+                0x40 => {
+                    self.regs.p = self.pull() & !(BREAK | UNUSED);
+                    let low = self.pull() as u16;
+                    let high = self.pull() as u16;
+                    self.regs.pc = high << 8 | low;
+                }
+                // RTS
+                0x60 => {
+                    let low = self.pull() as u16;
+                    let high = self.pull() as u16;
+                    self.regs.pc = (high << 8 | low).wrapping_add(1);
+                }
+                // JMP
+                0x4C | 0x6C => {
+                    let Operand::Address { address, .. } = operand else {
+                        unreachable!()
+                    };
+                    self.regs.pc = address;
+                }
+                0x08 => self.push(self.regs.p | BREAK | UNUSED),
+                0x28 => self.regs.p = self.pull() & !(BREAK | UNUSED),
+                0x48 => self.push(self.regs.a),
+                0x68 => {
+                    let value = self.pull();
+                    self.regs.a = self.set_zero_negative(value);
+                }
+                0x18 => self.set_flag(CARRY, false),
+                0x38 => self.set_flag(CARRY, true),
+                0x58 => self.set_flag(INTERRUPT_DISABLE, false),
+                0x78 => self.set_flag(INTERRUPT_DISABLE, true),
+                0xB8 => self.set_flag(OVERFLOW, false),
+                0xD8 => self.set_flag(DECIMAL, false),
+                0xF8 => self.set_flag(DECIMAL, true),
+                0x88 => self.regs.y = self.set_zero_negative(self.regs.y.wrapping_sub(1)),
+                0xC8 => self.regs.y = self.set_zero_negative(self.regs.y.wrapping_add(1)),
+                0xCA => self.regs.x = self.set_zero_negative(self.regs.x.wrapping_sub(1)),
+                0xE8 => self.regs.x = self.set_zero_negative(self.regs.x.wrapping_add(1)),
+                0x8A => self.regs.a = self.set_zero_negative(self.regs.x),
+                0x98 => self.regs.a = self.set_zero_negative(self.regs.y),
+                0xA8 => self.regs.y = self.set_zero_negative(self.regs.a),
+                0xAA => self.regs.x = self.set_zero_negative(self.regs.a),
+                0xBA => self.regs.x = self.set_zero_negative(self.regs.sp),
+                0x9A => self.regs.sp = self.regs.x,
+                // BIT
+                0x24 | 0x2C => {
+                    let value = self.load(&operand);
+                    self.set_flag(ZERO, self.regs.a & value == 0);
+                    self.set_flag(NEGATIVE, value & 0x80 != 0);
+                    self.set_flag(OVERFLOW, value & 0x40 != 0);
+                }
+                // SHY, SHX, AHX, TAS
+                0x9C | 0x9E | 0x93 | 0x9F | 0x9B => {
+                    let Operand::Address {
+                        address,
+                        base_high,
+                        page_crossed,
+                    } = operand
+                    else {
+                        unreachable!()
+                    };
+                    let register = match opcode {
+                        0x9C => self.regs.y,
+                        0x9E => self.regs.x,
+                        0x93 | 0x9F => self.regs.a & self.regs.x,
+                        _ => {
+                            self.regs.sp = self.regs.a & self.regs.x;
+                            self.regs.sp
+                        }
+                    };
+                    let value = register & base_high.wrapping_add(1);
+                    // on page cross, the value replaces the high byte of the address
+                    let address = if page_crossed {
+                        (value as u16) << 8 | address & 0xFF
+                    } else {
+                        address
+                    };
+                    self.write(address, value);
+                }
+                // LAS
+                0xBB => {
+                    let value = self.load(&operand) & self.regs.sp;
+                    self.regs.a = value;
+                    self.regs.x = value;
+                    self.regs.sp = self.set_zero_negative(value);
+                }
+                // ANC
+                0x0B | 0x2B => {
+                    self.regs.a = self.set_zero_negative(self.regs.a & self.load(&operand));
+                    self.set_flag(CARRY, self.flag(NEGATIVE));
+                }
+                // ALR
+                0x4B => {
+                    let value = self.regs.a & self.load(&operand);
+                    self.regs.a = self.shift(2, value);
+                }
+                // ARR
+                0x6B => {
+                    let value = self.regs.a & self.load(&operand);
+                    let result = value >> 1 | (self.flag(CARRY) as u8) << 7;
+                    self.regs.a = self.set_zero_negative(result);
+                    self.set_flag(CARRY, result & 0x40 != 0);
+                    self.set_flag(OVERFLOW, (result >> 6 ^ result >> 5) & 1 != 0);
+                }
+                // AXS
+                0xCB => {
+                    let value = self.load(&operand);
+                    let and = self.regs.a & self.regs.x;
+                    self.set_flag(CARRY, and >= value);
+                    self.regs.x = self.set_zero_negative(and.wrapping_sub(value));
+                }
+                // SBC #imm (unofficial)
+                0xEB => {
+                    let value = self.load(&operand);
+                    self.alu(aaa, value);
+                }
+                _ if matches!(mode, Mode::Relative) => {
+                    let Operand::Immediate(offset) = operand else {
+                        unreachable!()
+                    };
+                    cycles += self.branch(opcode, offset);
+                }
+                _ => match opcode & 3 {
+                    0 => match aaa {
+                        // NOP #imm
+                        4 if matches!(mode, Mode::Immediate) => {}
+                        4 => self.store(&operand, self.regs.y),
+                        5 => self.regs.y = self.set_zero_negative(self.load(&operand)),
+                        6 if !matches!(mode, Mode::ZeroPageX | Mode::AbsoluteX) => {
+                            self.compare(self.regs.y, self.load(&operand))
+                        }
+                        7 if !matches!(mode, Mode::ZeroPageX | Mode::AbsoluteX) => {
+                            self.compare(self.regs.x, self.load(&operand))
+                        }
+                        // NOP
+                        _ => {}
+                    },
+                    1 => match aaa {
+                        // NOP #imm
+                        4 if matches!(mode, Mode::Immediate) => {}
+                        4 => self.store(&operand, self.regs.a),
+                        5 => self.regs.a = self.set_zero_negative(self.load(&operand)),
+                        _ => {
+                            let value = self.load(&operand);
+                            self.alu(aaa, value);
+                        }
+                    },
+                    2 => match aaa {
+                        // NOP
+                        _ if matches!(mode, Mode::Implied) && (aaa >= 4 || opcode & 0x10 != 0) => {}
+                        _ if matches!(mode, Mode::Immediate) && aaa != 5 => {}
+                        0..=3 => {
+                            let value = self.load(&operand);
+                            let result = self.shift(aaa, value);
+                            self.store(&operand, result);
+                        }
+                        4 => self.store(&operand, self.regs.x),
+                        5 => self.regs.x = self.set_zero_negative(self.load(&operand)),
+                        6 => {
+                            let result = self.load(&operand).wrapping_sub(1);
+                            self.set_zero_negative(result);
+                            self.store(&operand, result);
+                        }
+                        _ => {
+                            let result = self.load(&operand).wrapping_add(1);
+                            self.set_zero_negative(result);
+                            self.store(&operand, result);
+                        }
+                    },
+                    _ => match aaa {
+                        // SAX
+                        4 => self.store(&operand, self.regs.a & self.regs.x),
+                        // LAX
+                        5 => {
+                            let value = self.set_zero_negative(self.load(&operand));
+                            self.regs.a = value;
+                            self.regs.x = value;
+                        }
+                        // DCP
+                        6 => {
+                            let result = self.load(&operand).wrapping_sub(1);
+                            self.store(&operand, result);
+                            self.compare(self.regs.a, result);
+                        }
+                        // ISC
+                        7 => {
+                            let result = self.load(&operand).wrapping_add(1);
+                            self.store(&operand, result);
+                            self.alu(aaa, result);
+                        }
+                        // SLO, RLA, SRE, RRA
+                        _ => {
+                            let value = self.load(&operand);
+                            let result = self.shift(aaa, value);
+                            self.store(&operand, result);
+                            self.alu(aaa, result);
+                        }
+                    },
+                },
+            }
+
+            cycles
+        }
+    }
+
+    /// The instructions of one case, placed in memory, with the operands of the
+    /// control flow instructions fixed to stay inside the program
+    struct Program {
+        start: u16,
+        end: u16,
+        memory: Vec<u8>,
+        registers: Registers,
+    }
+
+    fn generate_program(rng: &mut Rng) -> Program {
+        let mut memory = (0..0x10000).map(|_| rng.byte()).collect::<Vec<_>>();
+
+        let opcodes = (0..INSTRUCTIONS_PER_CASE)
+            .map(|_| loop {
+                let opcode = rng.byte();
+                if !EXCLUDED_OPCODES.contains(&opcode) {
+                    break opcode;
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let start = ROM_START + rng.below((PROGRAM_START_MAX - ROM_START) as u64) as u16;
+        // the address of each instruction, and the end of the program
+        let mut boundaries = vec![start];
+        for &opcode in &opcodes {
+            boundaries.push(boundaries.last().unwrap() + instruction_len(opcode));
+        }
+        let end = *boundaries.last().unwrap();
+
+        let mut used_pointers = Vec::new();
+        for (i, &opcode) in opcodes.iter().enumerate() {
+            let address = boundaries[i] as usize;
+            // jump forward only, to avoid infinite loops
+            let forward_target = boundaries[(i + 1 + rng.below(3) as usize).min(opcodes.len())];
+
+            memory[address] = opcode;
+            match opcode {
+                0x20 => memory[address + 1..address + 3].copy_from_slice(&RTS_STUB.to_le_bytes()),
+                0x4C => {
+                    memory[address + 1..address + 3].copy_from_slice(&forward_target.to_le_bytes())
+                }
+                0x6C => {
+                    // the pointer can be at the end of the page, to test the wrap around
+                    let pointer = loop {
+                        let pointer = JMP_POINTERS_PAGE | rng.byte() as u16;
+                        let high_pointer = pointer & 0xFF00 | pointer.wrapping_add(1) & 0xFF;
+                        if !used_pointers.contains(&pointer)
+                            && !used_pointers.contains(&high_pointer)
+                        {
+                            used_pointers.extend([pointer, high_pointer]);
+                            break pointer;
+                        }
+                    };
+                    let high_pointer = pointer & 0xFF00 | pointer.wrapping_add(1) & 0xFF;
+                    memory[pointer as usize] = forward_target as u8;
+                    memory[high_pointer as usize] = (forward_target >> 8) as u8;
+                    memory[address + 1..address + 3].copy_from_slice(&pointer.to_le_bytes());
+                }
+                _ if matches!(mode(opcode), Mode::Relative) => {
+                    memory[address + 1] = (forward_target - boundaries[i] - 2) as u8;
+                }
+                _ => {}
+            }
+        }
+
+        memory[RTI_STUB as usize] = 0x40;
+        memory[RTS_STUB as usize] = 0x60;
+        memory[0xFFFE..].copy_from_slice(&RTI_STUB.to_le_bytes());
+
+        Program {
+            start,
+            end,
+            memory,
+            registers: Registers {
+                pc: start,
+                sp: rng.byte(),
+                a: rng.byte(),
+                x: rng.byte(),
+                y: rng.byte(),
+                p: rng.byte() & !(BREAK | UNUSED),
+            },
+        }
+    }
+
+    fn plastic_registers(cpu: &CPU6502<FlatBus>) -> Registers {
+        Registers {
+            pc: cpu.reg_pc,
+            sp: cpu.reg_sp,
+            a: cpu.reg_a,
+            x: cpu.reg_x,
+            y: cpu.reg_y,
+            p: cpu.reg_status & !(BREAK | UNUSED),
+        }
+    }
+
+    /// Run one instruction in plastic's CPU, returns the number of cycles it took
+    fn plastic_step(cpu: &mut CPU6502<FlatBus>) -> u8 {
+        let mut cycles = 0;
+        loop {
+            cpu.run_next();
+            cycles += 1;
+            if cpu.cycles_to_wait == 0 && cpu.next_instruction.is_none() {
+                break cycles;
+            }
+        }
+    }
+
+    fn run_case(seed: u64, decimal_mode: bool) {
+        let mut rng = Rng::new(seed);
+        let program = generate_program(&mut rng);
+
+        let mut reference = Reference {
+            regs: program.registers,
+            memory: program.memory.clone(),
+            writes: Vec::new(),
+            decimal_mode,
+        };
+
+        let mut cpu = CPU6502::new(FlatBus {
+            data: program.memory,
+            writes: RefCell::new(Vec::new()),
+        });
+        cpu.set_decimal_mode(decimal_mode);
+        cpu.reg_pc = program.registers.pc;
+        cpu.reg_sp = program.registers.sp;
+        cpu.reg_a = program.registers.a;
+        cpu.reg_x = program.registers.x;
+        cpu.reg_y = program.registers.y;
+        cpu.reg_status = program.registers.p;
+
+        // each instruction runs at most once, plus the `RTI` and `RTS` stubs
+        for _ in 0..INSTRUCTIONS_PER_CASE * 2 {
+            let pc = reference.regs.pc;
+            if pc == program.end {
+                return;
+            }
+            let opcode = reference.read(pc);
+            let before = reference.regs;
+
+            let expected_cycles = reference.step();
+            let cycles = plastic_step(&mut cpu);
+
+            let mut writes = std::mem::take(&mut reference.writes);
+            writes.append(&mut cpu.bus().writes.borrow_mut());
+            let memory_mismatch = writes
+                .iter()
+                .find(|&&address| reference.read(address) != cpu.bus().read(address));
+
+            let registers = plastic_registers(&cpu);
+            assert!(
+                registers == reference.regs && cycles == expected_cycles && memory_mismatch.is_none(),
+                "CPU mismatch with seed {seed:#018X} (program at {:04X}, decimal mode: {decimal_mode}), running {:02X} ({}) at {pc:04X}\n\
+                 before:   {before:02X?}\n\
+                 expected: {:02X?}, {expected_cycles} cycles\n\
+                 got:      {registers:02X?}, {cycles} cycles\n\
+                 memory mismatch at: {memory_mismatch:04X?}",
+                program.start,
+                opcode,
+                Instruction::from_byte(opcode).opcode,
+                reference.regs,
+            );
+        }
+
+        panic!("the program with seed {seed:#018X} didn't reach its end");
+    }
+
+    fn run_cases(decimal_mode: bool) {
+        if let Ok(seed) = std::env::var("PLASTIC_CPU_FUZZ_SEED") {
+            let seed = seed.trim_start_matches("0x").trim_start_matches("0X");
+            run_case(
+                u64::from_str_radix(seed, 16).expect("the seed should be hex"),
+                decimal_mode,
+            );
+            return;
+        }
+
+        let cases = std::env::var("PLASTIC_CPU_FUZZ_CASES")
+            .ok()
+            .and_then(|cases| cases.parse().ok())
+            .unwrap_or(DEFAULT_CASES);
+
+        for case in 0..cases {
+            run_case(case.wrapping_mul(0x9E37_79B9_7F4A_7C15), decimal_mode);
+        }
+    }
+
+    #[test]
+    fn random_instruction_streams() {
+        run_cases(false);
+    }
+
+    /// The same programs with the optional decimal mode of `ADC` and `SBC`, the random
+    /// flags set the decimal flag in half of the cases, and `SED` and `CLD` change it
+    #[test]
+    fn random_instruction_streams_decimal_mode() {
+        run_cases(true);
+    }
+
+    /// Decimal `ADC #imm` and `SBC #imm` for all the values of `A`, the operand and
+    /// the carry, including the invalid BCD values
+    #[test]
+    fn decimal_all_operands() {
+        let mut memory = vec![0; 0x10000];
+        memory[ROM_START as usize] = 0x69;
+        memory[ROM_START as usize + 2] = 0xE9;
+
+        let mut reference = Reference {
+            regs: Registers {
+                pc: ROM_START,
+                sp: 0xFD,
+                a: 0,
+                x: 0,
+                y: 0,
+                p: 0,
+            },
+            memory: memory.clone(),
+            writes: Vec::new(),
+            decimal_mode: true,
+        };
+        let mut cpu = CPU6502::new(FlatBus {
+            data: memory,
+            writes: RefCell::new(Vec::new()),
+        });
+        cpu.set_decimal_mode(true);
+        cpu.reg_sp = reference.regs.sp;
+
+        for pc in [ROM_START, ROM_START + 2] {
+            for a in 0..=0xFF {
+                for operand in 0..=0xFF {
+                    for carry in [0, CARRY] {
+                        reference.memory[pc as usize + 1] = operand;
+                        cpu.bus_mut().data[pc as usize + 1] = operand;
+
+                        let before = Registers {
+                            pc,
+                            a,
+                            p: DECIMAL | carry,
+                            ..reference.regs
+                        };
+                        reference.regs = before;
+                        cpu.reg_pc = pc;
+                        cpu.reg_a = a;
+                        cpu.reg_status = before.p;
+
+                        reference.step();
+                        plastic_step(&mut cpu);
+                        assert_eq!(
+                            plastic_registers(&cpu),
+                            reference.regs,
+                            "running {:02X} {operand:02X} with {before:02X?}",
+                            reference.read(pc)
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
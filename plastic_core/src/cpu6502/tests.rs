@@ -2,28 +2,42 @@
 mod cpu_tests {
     use super::super::{CPUBusTrait, CPURunState, CPU6502};
     use crate::common::{interconnection::*, save_state::Savable};
+    use std::cell::{Cell, RefCell};
 
     struct DummyBus {
         data: [u8; 0x10000],
+        /// all the addresses read, in order
+        reads: RefCell<Vec<u16>>,
+        /// all the writes, in order
+        writes: Vec<(u16, u8)>,
+        /// request a DMC fetch on this call of `run_next`, counting from 1
+        dmc_fetch_at: Option<u32>,
+        run_count: Cell<u32>,
     }
 
     impl DummyBus {
         pub fn new(data: [u8; 0x10000]) -> Self {
-            Self { data }
+            Self {
+                data,
+                reads: RefCell::new(Vec::new()),
+                writes: Vec::new(),
+                dmc_fetch_at: None,
+                run_count: Cell::new(0),
+            }
         }
     }
 
     impl Savable for DummyBus {
-        fn save<W: std::io::Write>(
+        fn save(
             &self,
-            _: &mut W,
+            _: &mut dyn std::io::Write,
         ) -> Result<(), crate::common::save_state::SaveError> {
             unreachable!()
         }
 
-        fn load<R: std::io::Read>(
+        fn load(
             &mut self,
-            _: &mut R,
+            _: &mut dyn std::io::Read,
         ) -> Result<(), crate::common::save_state::SaveError> {
             unreachable!()
         }
@@ -31,10 +45,12 @@ mod cpu_tests {
 
     impl CPUBusTrait for DummyBus {
         fn read(&self, address: u16) -> u8 {
+            self.reads.borrow_mut().push(address);
             self.data[address as usize]
         }
         fn write(&mut self, address: u16, data: u8) {
             self.data[address as usize] = data;
+            self.writes.push((address, data));
         }
 
         fn reset(&mut self) {
@@ -61,11 +77,11 @@ mod cpu_tests {
 
     impl APUCPUConnection for DummyBus {
         fn request_dmc_reader_read(&self) -> Option<u16> {
-            None
-        }
-        fn submit_dmc_buffer_byte(&mut self, _: u8) {
-            unreachable!();
+            // called once per `run_next`
+            self.run_count.set(self.run_count.get() + 1);
+            (Some(self.run_count.get()) == self.dmc_fetch_at).then_some(0xC000)
         }
+        fn submit_dmc_buffer_byte(&mut self, _: u8) {}
     }
 
     impl CPUIrqProvider for DummyBus {
@@ -114,4 +130,346 @@ mod cpu_tests {
             }
         }
     }
+
+    /// Run `program` from `$0400` until it reaches an infinite loop, and return
+    /// the reads of the PPU registers (`$2000-$3FFF`)
+    fn ppu_register_reads(program: &[u8]) -> Vec<u16> {
+        let mut data = [0; 0x10000];
+        data[0x400..0x400 + program.len()].copy_from_slice(program);
+        // `JMP` to itself
+        let end = 0x400 + program.len() as u16;
+        data[end as usize..end as usize + 3].copy_from_slice(&[0x4C, end as u8, (end >> 8) as u8]);
+        // the pointer used by the `($10),Y` instructions
+        data[0x10] = 0xF2;
+        data[0x11] = 0x20;
+        data[0xFFFC] = 0x00;
+        data[0xFFFD] = 0x04;
+
+        let mut cpu = CPU6502::new(DummyBus::new(data));
+        cpu.reset();
+        while !matches!(cpu.run_next(), CPURunState::InfiniteLoop(_)) {}
+
+        let reads = cpu.bus().reads.borrow();
+        reads
+            .iter()
+            .copied()
+            .filter(|address| (0x2000..=0x3FFF).contains(address))
+            .collect()
+    }
+
+    #[test]
+    fn dummy_read_load_page_cross() {
+        // LDX #$15, LDA $20F2,X
+        assert_eq!(
+            ppu_register_reads(&[0xA2, 0x15, 0xBD, 0xF2, 0x20]),
+            [0x2007, 0x2107]
+        );
+        // LDY #$15, LDA ($10),Y
+        assert_eq!(
+            ppu_register_reads(&[0xA0, 0x15, 0xB1, 0x10]),
+            [0x2007, 0x2107]
+        );
+    }
+
+    #[test]
+    fn no_dummy_read_load_same_page() {
+        // LDX #$05, LDA $2002,X
+        assert_eq!(
+            ppu_register_reads(&[0xA2, 0x05, 0xBD, 0x02, 0x20]),
+            [0x2007]
+        );
+    }
+
+    #[test]
+    fn dummy_read_indexed_stores() {
+        // LDX #$00, STA $2007,X
+        assert_eq!(
+            ppu_register_reads(&[0xA2, 0x00, 0x9D, 0x07, 0x20]),
+            [0x2007]
+        );
+        // LDY #$15, STA $20F2,Y
+        assert_eq!(
+            ppu_register_reads(&[0xA0, 0x15, 0x99, 0xF2, 0x20]),
+            [0x2007]
+        );
+        // LDY #$15, STA ($10),Y
+        assert_eq!(ppu_register_reads(&[0xA0, 0x15, 0x91, 0x10]), [0x2007]);
+        // non-indexed stores don't read, STA $2007
+        assert!(ppu_register_reads(&[0x8D, 0x07, 0x20]).is_empty());
+    }
+
+    /// Run `program` from `$0400` until it reaches an infinite loop, with a DMC fetch
+    /// on the `dmc_fetch_at` call of `run_next` if set, and return the last write.
+    ///
+    /// The pointer used by the `($10),Y` instructions is `$FFF0`
+    fn last_write(program: &[u8], dmc_fetch_at: Option<u32>) -> (u16, u8) {
+        let mut data = [0; 0x10000];
+        data[0x400..0x400 + program.len()].copy_from_slice(program);
+        let end = 0x400 + program.len() as u16;
+        data[end as usize..end as usize + 3].copy_from_slice(&[0x4C, end as u8, (end >> 8) as u8]);
+        data[0x10] = 0xF0;
+        data[0x11] = 0xFF;
+        data[0xFFFC] = 0x00;
+        data[0xFFFD] = 0x04;
+
+        let mut bus = DummyBus::new(data);
+        bus.dmc_fetch_at = dmc_fetch_at;
+        let mut cpu = CPU6502::new(bus);
+        cpu.reset();
+        while !matches!(cpu.run_next(), CPURunState::InfiniteLoop(_)) {}
+
+        *cpu.bus().writes.last().unwrap()
+    }
+
+    /// `LDX #x`, `LDY #y`, `LDA #a` then `instruction`
+    fn with_registers(x: u8, y: u8, a: u8, instruction: &[u8]) -> Vec<u8> {
+        let mut program = vec![0xA2, x, 0xA0, y, 0xA9, a];
+        program.extend_from_slice(instruction);
+        program
+    }
+
+    #[test]
+    fn shy_boundaries() {
+        // SHY $FFF0,X, the high byte plus one wraps to 0
+        let shy = [0x9C, 0xF0, 0xFF];
+        assert_eq!(
+            last_write(&with_registers(0x05, 0xFF, 0, &shy), None),
+            (0xFFF5, 0)
+        );
+        // page cross wraps the address around to $0010
+        assert_eq!(
+            last_write(&with_registers(0x20, 0xFF, 0, &shy), None),
+            (0x0010, 0)
+        );
+
+        // SHY $12F0,X, stores Y & $13
+        let shy = [0x9C, 0xF0, 0x12];
+        assert_eq!(
+            last_write(&with_registers(0x05, 0x3F, 0, &shy), None),
+            (0x12F5, 0x13)
+        );
+        // the value replaces the high byte of the address on page cross
+        assert_eq!(
+            last_write(&with_registers(0x20, 0x0F, 0, &shy), None),
+            (0x0310, 0x03)
+        );
+    }
+
+    #[test]
+    fn shx_boundaries() {
+        // SHX $FFF0,Y
+        let shx = [0x9E, 0xF0, 0xFF];
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x05, 0, &shx), None),
+            (0xFFF5, 0)
+        );
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x20, 0, &shx), None),
+            (0x0010, 0)
+        );
+
+        // SHX $12F0,Y
+        let shx = [0x9E, 0xF0, 0x12];
+        assert_eq!(
+            last_write(&with_registers(0x3F, 0x05, 0, &shx), None),
+            (0x12F5, 0x13)
+        );
+        assert_eq!(
+            last_write(&with_registers(0x0F, 0x20, 0, &shx), None),
+            (0x0310, 0x03)
+        );
+    }
+
+    #[test]
+    fn ahx_boundaries() {
+        // AHX $FFF0,Y, stores A & X & (H + 1)
+        let ahx = [0x9F, 0xF0, 0xFF];
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x05, 0xFF, &ahx), None),
+            (0xFFF5, 0)
+        );
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x20, 0xFF, &ahx), None),
+            (0x0010, 0)
+        );
+
+        let ahx = [0x9F, 0xF0, 0x12];
+        assert_eq!(
+            last_write(&with_registers(0x3F, 0x05, 0xF7, &ahx), None),
+            (0x12F5, 0x13)
+        );
+        assert_eq!(
+            last_write(&with_registers(0x0F, 0x20, 0xF7, &ahx), None),
+            (0x0310, 0x03)
+        );
+
+        // AHX ($10),Y, the pointer is $FFF0
+        let ahx = [0x93, 0x10];
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x05, 0xFF, &ahx), None),
+            (0xFFF5, 0)
+        );
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x20, 0xFF, &ahx), None),
+            (0x0010, 0)
+        );
+    }
+
+    #[test]
+    fn tas_boundaries() {
+        // TAS $FFF0,Y, SP = A & X, stores SP & (H + 1)
+        let tas = [0x9B, 0xF0, 0xFF];
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x05, 0xFF, &tas), None),
+            (0xFFF5, 0)
+        );
+        assert_eq!(
+            last_write(&with_registers(0xFF, 0x20, 0xFF, &tas), None),
+            (0x0010, 0)
+        );
+
+        let tas = [0x9B, 0xF0, 0x12];
+        assert_eq!(
+            last_write(&with_registers(0x3F, 0x05, 0xF7, &tas), None),
+            (0x12F5, 0x13)
+        );
+        assert_eq!(
+            last_write(&with_registers(0x0F, 0x20, 0xF7, &tas), None),
+            (0x0310, 0x03)
+        );
+    }
+
+    #[test]
+    fn unstable_store_dma_drops_the_and() {
+        // SHY $12F0,X, after the reset and the 3 loads, the 16th call of `run_next` is the
+        // 4th cycle of the store (the dummy read before the write)
+        let program = with_registers(0x05, 0x3F, 0, &[0x9C, 0xF0, 0x12]);
+        assert_eq!(last_write(&program, Some(16)), (0x12F5, 0x3F));
+        // a DMA on another cycle doesn't affect it
+        assert_eq!(last_write(&program, Some(15)), (0x12F5, 0x13));
+
+        // on page cross, the whole register becomes the high byte of the address, the
+        // instruction runs a cycle later, as the fixed address is known a cycle later
+        let program = with_registers(0x20, 0x0F, 0, &[0x9C, 0xF0, 0x12]);
+        assert_eq!(last_write(&program, Some(16)), (0x0310, 0x03));
+        assert_eq!(last_write(&program, Some(17)), (0x0F10, 0x0F));
+    }
+
+    const NEGATIVE: u8 = 0x80;
+    const OVERFLOW: u8 = 0x40;
+    const ZERO: u8 = 0x02;
+    const CARRY: u8 = 0x01;
+
+    /// Run `opcode` (`ADC $10` or `SBC $10`) in decimal mode for all the values of `A`,
+    /// the operand and the carry, in this order, and return the results and the flags
+    fn decimal_results(opcode: u8) -> Vec<(u8, u8)> {
+        #[rustfmt::skip]
+        let program = [
+            0xF8,             // SED
+            0xA2, 0x00,       // LDX #0
+            0xA0, 0x00,       // outer: LDY #0
+            0x84, 0x10,       // inner: STY $10
+            0x18,             // CLC
+            0x8A,             // TXA
+            opcode, 0x10,     // ADC/SBC $10
+            0x85, 0x20,       // STA $20
+            0x08,             // PHP
+            0x68,             // PLA
+            0x38,             // SEC
+            0x8A,             // TXA
+            opcode, 0x10,     // ADC/SBC $10
+            0x85, 0x20,       // STA $20
+            0x08,             // PHP
+            0x68,             // PLA
+            0xC8,             // INY
+            0xD0, 0xEB,       // BNE inner
+            0xE8,             // INX
+            0xD0, 0xE6,       // BNE outer
+            0x4C, 0x1D, 0x04, // JMP self
+        ];
+        let mut data = [0; 0x10000];
+        data[0x400..0x400 + program.len()].copy_from_slice(&program);
+        data[0xFFFC] = 0x00;
+        data[0xFFFD] = 0x04;
+
+        let mut cpu = CPU6502::new(DummyBus::new(data));
+        cpu.set_decimal_mode(true);
+        cpu.reset();
+        while !matches!(cpu.run_next(), CPURunState::InfiniteLoop(_)) {}
+
+        let writes = &cpu.bus().writes;
+        let results = writes.iter().filter(|(address, _)| *address == 0x20);
+        // `PHP` pushes the flags to the stack
+        let flags = writes.iter().filter(|(address, _)| *address >> 8 == 0x01);
+        results
+            .zip(flags)
+            .map(|((_, result), (_, flags))| {
+                (*result, flags & (NEGATIVE | OVERFLOW | ZERO | CARRY))
+            })
+            .collect()
+    }
+
+    fn to_bcd(value: u32) -> u8 {
+        (((value / 10) << 4) | (value % 10)) as u8
+    }
+
+    fn from_bcd(value: u8) -> u32 {
+        (value >> 4) as u32 * 10 + (value & 0xF) as u32
+    }
+
+    /// The result and the flags of `decimal_results` for the BCD values `a` and `b`
+    fn decimal_result(results: &[(u8, u8)], a: u32, b: u32, carry: u32) -> (u8, u8) {
+        results[(to_bcd(a) as usize) << 9 | (to_bcd(b) as usize) << 1 | carry as usize]
+    }
+
+    // the invalid BCD values are compared with the reference interpreter in `reference_tests`
+
+    #[test]
+    fn decimal_adc_table() {
+        let results = decimal_results(0x65);
+        assert_eq!(results.len(), 0x20000);
+
+        // the valid BCD values give the decimal sum
+        for a in 0..100 {
+            for b in 0..100 {
+                for carry in 0..=1 {
+                    let (result, flags) = decimal_result(&results, a, b, carry);
+                    let sum = a + b + carry;
+                    assert_eq!(from_bcd(result), sum % 100, "{a} + {b} + {carry}");
+                    assert_eq!(flags & CARRY != 0, sum >= 100, "{a} + {b} + {carry}");
+                    assert_eq!(flags & ZERO != 0, sum % 100 == 0, "{a} + {b} + {carry}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decimal_sbc_table() {
+        let results = decimal_results(0xE5);
+        assert_eq!(results.len(), 0x20000);
+
+        // the valid BCD values give the decimal difference
+        for a in 0..100 {
+            for b in 0..100 {
+                for carry in 0..=1 {
+                    let (result, flags) = decimal_result(&results, a, b, carry);
+                    let difference = a as i32 - b as i32 - (1 - carry as i32);
+                    let expected = difference.rem_euclid(100) as u32;
+                    assert_eq!(from_bcd(result), expected, "{a} - {b} - {}", 1 - carry);
+                    assert_eq!(
+                        flags & CARRY != 0,
+                        difference >= 0,
+                        "{a} - {b} - {}",
+                        1 - carry
+                    );
+                    assert_eq!(
+                        flags & ZERO != 0,
+                        expected == 0,
+                        "{a} - {b} - {}",
+                        1 - carry
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! Optional warnings about suspicious behavior of the running game, mostly useful
+//! for homebrew development. Collected with [`NES::take_diagnostics`](crate::NES::take_diagnostics).
+
+use crate::cartridge::MappingViolation;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+/// Maximum number of diagnostics kept until they are taken, a buggy game can
+/// trigger some of them every frame
+const MAX_PENDING_DIAGNOSTICS: usize = 256;
+
+/// The direction of a stack pointer wrap around
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackWrap {
+    /// A push wrapped `SP` from `$00` to `$FF`, too many pushes
+    Overflow,
+    /// A pull wrapped `SP` from `$FF` to `$00`, more pulls than pushes
+    Underflow,
+}
+
+/// A warning emitted by the emulator about the running game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The stack pointer wrapped around the stack page (`$0100-$01FF`),
+    /// enabled by [`NES::set_stack_wrap_warnings`](crate::NES::set_stack_wrap_warnings).
+    StackWrap {
+        /// The address of the instruction, or the interrupted `PC` if it
+        /// happened while pushing an interrupt return address
+        pc: u16,
+        direction: StackWrap,
+        /// `true` if it happened while entering an interrupt (`NMI`, `IRQ` or `BRK`)
+        in_interrupt: bool,
+    },
+    /// A save RAM file imported with [`NES::import_sram_file`](crate::NES::import_sram_file),
+    /// or loaded next to the ROM file, was larger than the cartridge save RAM, and its end
+    /// was ignored.
+    SramImportTruncated {
+        /// The number of bytes ignored
+        dropped_bytes: usize,
+    },
+    /// The CPU wrote to ROM where the mapper doesn't have a register, the write
+    /// was probably intended for RAM. Enabled by
+    /// [`NES::set_homebrew_diagnostics`](crate::NES::set_homebrew_diagnostics).
+    RomWrite {
+        /// The address of the instruction
+        pc: u16,
+        address: u16,
+        data: u8,
+    },
+    /// The CPU read a byte of the 2KB RAM that was not written since power-on,
+    /// reported once per byte. Enabled by
+    /// [`NES::set_homebrew_diagnostics`](crate::NES::set_homebrew_diagnostics).
+    UninitializedRamRead {
+        /// The address of the instruction
+        pc: u16,
+        /// The address in RAM (`$0000-$07FF`), without the mirroring
+        address: u16,
+    },
+    /// The CPU wrote to the test mode registers (`$4018-$401F`), which are disabled
+    /// on retail consoles, so the write does nothing. Enabled by
+    /// [`NES::set_homebrew_diagnostics`](crate::NES::set_homebrew_diagnostics).
+    TestRegisterWrite {
+        /// The address of the instruction
+        pc: u16,
+        address: u16,
+        data: u8,
+    },
+    /// The ROM header declares PAL (or Dendy) timing but the console was switched
+    /// to [`Region::Ntsc`](crate::Region::Ntsc), the game will probably run too fast or glitch.
+    PalRomOnNtsc,
+    /// The CPU executed a `KIL` opcode and is halted until reset, the game crashed,
+    /// see [`NES::is_cpu_halted`](crate::NES::is_cpu_halted).
+    CpuHalted {
+        /// The address of the opcode
+        pc: u16,
+        opcode: u8,
+    },
+    /// The bank mapping is inconsistent after a write to a mapper register, probably
+    /// a bug in the emulated mapper. Enabled by [`NES::set_mapper_audit`](crate::NES::set_mapper_audit).
+    MapperAudit {
+        /// The iNES mapper number
+        mapper: u16,
+        /// The CPU address of the register write that caused it
+        register: u16,
+        data: u8,
+        violation: MappingViolation,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::StackWrap {
+                pc,
+                direction,
+                in_interrupt,
+            } => {
+                let direction = match direction {
+                    StackWrap::Overflow => "overflow",
+                    StackWrap::Underflow => "underflow",
+                };
+                write!(f, "stack {} at ${:04X}", direction, pc)?;
+                if *in_interrupt {
+                    write!(f, " while entering an interrupt")?;
+                }
+                Ok(())
+            }
+            Diagnostic::SramImportTruncated { dropped_bytes } => {
+                write!(
+                    f,
+                    "save RAM file is too large, ignored the last {} bytes",
+                    dropped_bytes
+                )
+            }
+            Diagnostic::RomWrite { pc, address, data } => {
+                write!(
+                    f,
+                    "write of ${:02X} to ROM at ${:04X} from ${:04X}",
+                    data, address, pc
+                )
+            }
+            Diagnostic::UninitializedRamRead { pc, address } => {
+                write!(
+                    f,
+                    "read of uninitialized RAM at ${:04X} from ${:04X}",
+                    address, pc
+                )
+            }
+            Diagnostic::TestRegisterWrite { pc, address, data } => {
+                write!(
+                    f,
+                    "write of ${:02X} to the disabled test register ${:04X} from ${:04X}",
+                    data, address, pc
+                )
+            }
+            Diagnostic::PalRomOnNtsc => {
+                write!(
+                    f,
+                    "the game is made for PAL consoles but is running as NTSC"
+                )
+            }
+            Diagnostic::CpuHalted { pc, opcode } => {
+                write!(
+                    f,
+                    "the CPU halted on the KIL opcode ${:02X} at ${:04X}",
+                    opcode, pc
+                )
+            }
+            Diagnostic::MapperAudit {
+                mapper,
+                register,
+                data,
+                violation,
+            } => {
+                write!(
+                    f,
+                    "mapper {} after the write of ${:02X} to ${:04X}: {}",
+                    mapper, data, register, violation
+                )
+            }
+        }
+    }
+}
+
+/// Pending diagnostics of a component, diagnostics after [`MAX_PENDING_DIAGNOSTICS`]
+/// are dropped until they are taken.
+#[derive(Default)]
+pub(crate) struct Diagnostics {
+    pending: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        if self.pending.len() < MAX_PENDING_DIAGNOSTICS {
+            self.pending.push(diagnostic);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Replace the pending diagnostics, used to restore them after a detached run
+    pub fn replace(&mut self, pending: Vec<Diagnostic>) {
+        self.pending = pending;
+    }
+}
+
+/// The RAM pages ignored by default when reporting uninitialized reads, the stack page,
+/// as games pull from it after interrupts without pushing first
+pub(crate) const DEFAULT_IGNORED_RAM_PAGES: u8 = 1 << 1;
+
+/// The state of the [`Diagnostic::RomWrite`], [`Diagnostic::UninitializedRamRead`]
+/// and [`Diagnostic::TestRegisterWrite`] checks, done by the CPU bus
+pub(crate) struct HomebrewChecks {
+    enabled: bool,
+    /// a bit for each byte of the 2KB RAM, set when it is written or when its
+    /// uninitialized read was reported
+    ram_initialized: [Cell<u64>; 32],
+    /// a bit for each 256 bytes page of the RAM to not report
+    ignored_pages: u8,
+    /// the address of the instruction being run
+    pc: u16,
+    diagnostics: RefCell<Diagnostics>,
+}
+
+impl Default for HomebrewChecks {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ram_initialized: Default::default(),
+            ignored_pages: DEFAULT_IGNORED_RAM_PAGES,
+            pc: 0,
+            diagnostics: RefCell::default(),
+        }
+    }
+}
+
+impl HomebrewChecks {
+    /// Enabling starts tracking the RAM writes from now, so it should be done at power-on
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.clear_ram();
+    }
+
+    pub fn set_ignored_pages(&mut self, pages: u8) {
+        self.ignored_pages = pages;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// Forget the RAM writes, on power-on
+    pub fn clear_ram(&mut self) {
+        for bits in &self.ram_initialized {
+            bits.set(0);
+        }
+    }
+
+    /// Consider all the RAM as written, when the content is loaded from a state
+    pub fn mark_all_ram(&mut self) {
+        for bits in &self.ram_initialized {
+            bits.set(u64::MAX);
+        }
+    }
+
+    /// The bits of the written RAM bytes, to restore them after a detached run
+    pub fn initialized_ram(&self) -> [u64; 32] {
+        std::array::from_fn(|i| self.ram_initialized[i].get())
+    }
+
+    pub fn set_initialized_ram(&mut self, bits: [u64; 32]) {
+        for (cell, bits) in self.ram_initialized.iter().zip(bits) {
+            cell.set(bits);
+        }
+    }
+
+    /// `address` is in `$0000-$07FF`
+    #[inline]
+    pub fn ram_write(&self, address: u16) {
+        if self.enabled {
+            let bits = &self.ram_initialized[address as usize / 64];
+            bits.set(bits.get() | 1 << (address % 64));
+        }
+    }
+
+    /// `address` is in `$0000-$07FF`
+    #[inline]
+    pub fn ram_read(&self, address: u16) {
+        if !self.enabled || self.ignored_pages & (1 << (address >> 8)) != 0 {
+            return;
+        }
+
+        let bits = &self.ram_initialized[address as usize / 64];
+        let mask = 1 << (address % 64);
+        if bits.get() & mask == 0 {
+            // only report the first read
+            bits.set(bits.get() | mask);
+            self.diagnostics
+                .borrow_mut()
+                .push(Diagnostic::UninitializedRamRead {
+                    pc: self.pc,
+                    address,
+                });
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn test_register_write(&self, address: u16, data: u8) {
+        if !self.enabled {
+            return;
+        }
+        self.diagnostics
+            .borrow_mut()
+            .push(Diagnostic::TestRegisterWrite {
+                pc: self.pc,
+                address,
+                data,
+            });
+    }
+
+    pub fn rom_write(&self, address: u16, data: u8) {
+        self.diagnostics.borrow_mut().push(Diagnostic::RomWrite {
+            pc: self.pc,
+            address,
+            data,
+        });
+    }
+
+    pub fn diagnostics_mut(&mut self) -> &mut Diagnostics {
+        self.diagnostics.get_mut()
+    }
+}
@@ -1,4 +1,5 @@
-#[derive(Clone, Copy)]
+/// An RGB color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -156,3 +157,58 @@ const DEFAULT_COLORS: [Color; 0x40] = [
 
 /// Selects which of the two colors to use
 pub const COLORS: [Color; 0x40] = NEW_COLORS;
+
+/// Number of possible pixel values, 6 bits of color index and 3 bits of emphasis
+pub const PIXEL_VALUES_COUNT: usize = 0x200;
+
+/// Apply the emphasis bits (`0bBGR`, same order as in the PPU mask register) to a color
+fn emphasis_color(color: Color, emphasis: u8) -> Color {
+    let mut red = 1.;
+    let mut green = 1.;
+    let mut blue = 1.;
+
+    if emphasis & 0b001 != 0 {
+        red *= 1.1;
+        green *= 0.9;
+        blue *= 0.9;
+    }
+    if emphasis & 0b010 != 0 {
+        red *= 0.9;
+        green *= 1.1;
+        blue *= 0.9;
+    }
+    if emphasis & 0b100 != 0 {
+        red *= 0.9;
+        green *= 0.9;
+        blue *= 1.1;
+    }
+
+    color!(
+        (color.r as f32 * red) as u8,
+        (color.g as f32 * green) as u8,
+        (color.b as f32 * blue) as u8
+    )
+}
+
+/// Build a table converting pixel values (`emphasis << 6 | color_index`) into RGB colors
+/// from the 64 colors of each emphasis, see [`PaletteGenerator::generate`](super::PaletteGenerator::generate)
+pub fn build_color_table_from(palettes: &[[Color; 0x40]; 8]) -> Box<[Color; PIXEL_VALUES_COUNT]> {
+    let mut table = Box::new([color!(0, 0, 0); PIXEL_VALUES_COUNT]);
+
+    for (value, color) in table.iter_mut().enumerate() {
+        *color = palettes[value >> 6][value & 0x3F];
+    }
+
+    table
+}
+
+/// Build a table converting pixel values (`emphasis << 6 | color_index`) into RGB colors
+pub fn build_color_table() -> Box<[Color; PIXEL_VALUES_COUNT]> {
+    let mut table = Box::new([color!(0, 0, 0); PIXEL_VALUES_COUNT]);
+
+    for (value, color) in table.iter_mut().enumerate() {
+        *color = emphasis_color(COLORS[value & 0x3F], (value >> 6) as u8);
+    }
+
+    table
+}
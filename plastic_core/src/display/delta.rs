@@ -0,0 +1,121 @@
+use super::tv::{COLOR_BYTES_LEN, TV_BUFFER_SIZE, TV_HEIGHT, TV_WIDTH};
+
+/// The width and height in pixels of the tiles in a [`FrameDelta`]
+pub const DELTA_TILE_SIZE: usize = 8;
+/// The number of tile columns in the screen
+pub const DELTA_TILES_X: usize = TV_WIDTH / DELTA_TILE_SIZE;
+/// The number of tile rows in the screen
+pub const DELTA_TILES_Y: usize = TV_HEIGHT / DELTA_TILE_SIZE;
+/// The size of the pixels of a single tile in bytes
+pub const DELTA_TILE_BYTES: usize = DELTA_TILE_SIZE * DELTA_TILE_SIZE * COLOR_BYTES_LEN;
+
+/// An 8x8 tile of the screen that changed, see [`FrameDelta`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeltaTile {
+    /// The tile column, `0..DELTA_TILES_X`
+    pub column: u8,
+    /// The tile row, `0..DELTA_TILES_Y`
+    pub row: u8,
+    /// The RGB pixels of the tile, row by row, in the same format as
+    /// [`NES::pixel_buffer`](crate::NES::pixel_buffer)
+    pub pixels: [u8; DELTA_TILE_BYTES],
+}
+
+impl DeltaTile {
+    /// The byte offset of the start of `row` of the tile in the pixel buffer
+    fn buffer_offset(column: usize, row: usize, tile_row: usize) -> usize {
+        ((row * DELTA_TILE_SIZE + tile_row) * TV_WIDTH + column * DELTA_TILE_SIZE) * COLOR_BYTES_LEN
+    }
+}
+
+/// The tiles of the screen that changed since the last call to
+/// [`NES::frame_delta`](crate::NES::frame_delta)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameDelta {
+    /// The changed tiles, ordered row by row
+    pub tiles: Vec<DeltaTile>,
+}
+
+impl FrameDelta {
+    /// `true` if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// `true` if all the tiles of the screen are in the delta
+    pub fn is_full(&self) -> bool {
+        self.tiles.len() == DELTA_TILES_X * DELTA_TILES_Y
+    }
+
+    /// Write the changed tiles into `buffer`, a pixel buffer of
+    /// [`TV_BUFFER_SIZE`] bytes, which results in the same buffer as the emulator if
+    /// all the previous deltas were applied to it.
+    ///
+    /// # Panics
+    /// If `buffer` is smaller than [`TV_BUFFER_SIZE`]
+    pub fn apply(&self, buffer: &mut [u8]) {
+        assert!(buffer.len() >= TV_BUFFER_SIZE);
+
+        const ROW_BYTES: usize = DELTA_TILE_SIZE * COLOR_BYTES_LEN;
+        for tile in &self.tiles {
+            for (tile_row, pixels) in tile.pixels.chunks_exact(ROW_BYTES).enumerate() {
+                let offset =
+                    DeltaTile::buffer_offset(tile.column as usize, tile.row as usize, tile_row);
+                buffer[offset..offset + ROW_BYTES].copy_from_slice(pixels);
+            }
+        }
+    }
+}
+
+/// Keeps a copy of the pixels returned in the last delta to compare against
+#[derive(Default)]
+pub struct DeltaTracker {
+    /// `None` until the first delta, or after `force_full`
+    reference: Option<Box<[u8; TV_BUFFER_SIZE]>>,
+}
+
+impl DeltaTracker {
+    /// The tiles of `pixels` that changed since the last call
+    pub fn delta(&mut self, pixels: &[u8; TV_BUFFER_SIZE]) -> FrameDelta {
+        const ROW_BYTES: usize = DELTA_TILE_SIZE * COLOR_BYTES_LEN;
+
+        let full = self.reference.is_none();
+        let reference = self
+            .reference
+            .get_or_insert_with(|| Box::new([0; TV_BUFFER_SIZE]));
+
+        let mut delta = FrameDelta::default();
+        for row in 0..DELTA_TILES_Y {
+            for column in 0..DELTA_TILES_X {
+                let changed = full
+                    || (0..DELTA_TILE_SIZE).any(|tile_row| {
+                        let offset = DeltaTile::buffer_offset(column, row, tile_row);
+                        pixels[offset..offset + ROW_BYTES] != reference[offset..offset + ROW_BYTES]
+                    });
+                if !changed {
+                    continue;
+                }
+
+                let mut tile = DeltaTile {
+                    column: column as u8,
+                    row: row as u8,
+                    pixels: [0; DELTA_TILE_BYTES],
+                };
+                for (tile_row, tile_pixels) in tile.pixels.chunks_exact_mut(ROW_BYTES).enumerate() {
+                    let offset = DeltaTile::buffer_offset(column, row, tile_row);
+                    let row_pixels = &pixels[offset..offset + ROW_BYTES];
+                    tile_pixels.copy_from_slice(row_pixels);
+                    reference[offset..offset + ROW_BYTES].copy_from_slice(row_pixels);
+                }
+                delta.tiles.push(tile);
+            }
+        }
+
+        delta
+    }
+
+    /// Make the next delta include the whole screen
+    pub fn force_full(&mut self) {
+        self.reference = None;
+    }
+}
@@ -0,0 +1,88 @@
+//! The screen shown by an empty console, see [`NES::set_idle_screen`](crate::NES::set_idle_screen)
+
+use super::tv::{TV, TV_HEIGHT, TV_WIDTH};
+
+/// 5x7 glyphs of the letters used in the idle screen, a byte per row with the
+/// leftmost pixel in bit 4
+const FONT: [(char, [u8; 7]); 11] = [
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+/// width of a character including the space after it
+const CHAR_WIDTH: usize = 6;
+/// width of the border in pixels
+const BORDER: usize = 8;
+
+const BLACK: u8 = 0x0F;
+const WHITE: u8 = 0x30;
+/// the first of the 12 colors of the border, `$11-$1C`
+const BORDER_FIRST_COLOR: u8 = 0x11;
+
+/// Draw the text centered horizontally at `y`, each font pixel is `scale` pixels
+fn draw_text(tv: &mut TV, text: &str, y: usize, scale: usize) {
+    let width = text.len() * CHAR_WIDTH * scale - scale;
+    let start_x = (TV_WIDTH - width) / 2;
+
+    for (i, c) in text.chars().enumerate() {
+        let Some((_, glyph)) = FONT.iter().find(|(glyph_char, _)| *glyph_char == c) else {
+            // space
+            continue;
+        };
+        let char_x = start_x + i * CHAR_WIDTH * scale;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        tv.set_pixel(
+                            (char_x + column * scale + dx) as u32,
+                            (y + row * scale + dy) as u32,
+                            WHITE,
+                            0,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draw the idle screen for `frame` to the TV and show it: the name of the emulator
+/// and a hint to load a ROM, inside a border with moving colors
+pub fn draw_idle_screen(tv: &mut TV, frame: u32) {
+    // the border moves one 8x8 block every 4 frames
+    let offset = frame / 4;
+
+    for y in 0..TV_HEIGHT {
+        for x in 0..TV_WIDTH {
+            let in_border =
+                x < BORDER || y < BORDER || x >= TV_WIDTH - BORDER || y >= TV_HEIGHT - BORDER;
+
+            let color = if in_border {
+                let block = (x / BORDER + y / BORDER) as u32;
+                BORDER_FIRST_COLOR + ((block + offset) % 12) as u8
+            } else {
+                BLACK
+            };
+            tv.set_pixel(x as u32, y as u32, color, 0);
+        }
+    }
+
+    draw_text(tv, "PLASTIC", 88, 3);
+    draw_text(tv, "DROP A ROM", 128, 1);
+
+    tv.signal_end_of_frame();
+}
@@ -1,7 +1,20 @@
 #[macro_use]
 mod color;
+mod delta;
+mod idle_screen;
+mod palette_generator;
 mod tv;
 
 pub use color::Color;
+#[cfg(test)]
 pub use color::COLORS;
-pub use tv::{COLOR_BYTES_LEN, TV, TV_BUFFER_SIZE, TV_HEIGHT, TV_WIDTH};
+pub use delta::{
+    DeltaTile, FrameDelta, DELTA_TILES_X, DELTA_TILES_Y, DELTA_TILE_BYTES, DELTA_TILE_SIZE,
+};
+pub use idle_screen::draw_idle_screen;
+pub use palette_generator::PaletteGenerator;
+pub use tv::{
+    frame_hash, COLOR_BYTES_LEN, LAYER_MAP_SIZE, LAYER_PALETTE_SHIFT, LAYER_SOURCE_BACKDROP,
+    LAYER_SOURCE_BACKGROUND, LAYER_SOURCE_MASK, LAYER_SOURCE_SPRITE_BEHIND,
+    LAYER_SOURCE_SPRITE_FRONT, LAYER_SPRITE_0, TV, TV_BUFFER_SIZE, TV_HEIGHT, TV_WIDTH,
+};
@@ -0,0 +1,112 @@
+use super::color::Color;
+use std::f32::consts::PI;
+
+/// Voltage levels of the NTSC signal, the low levels of the 4 luma rows then the high levels
+const LEVELS: [f32; 8] = [0.350, 0.518, 0.962, 1.550, 1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = LEVELS[1];
+const WHITE: f32 = LEVELS[7];
+/// The signal is multiplied by this when emphasized
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+/// Phase of the color burst in the demodulator, in 1/12th of a color cycle
+const BURST_PHASE: f32 = 3.9;
+
+/// Builds the palette from a model of the composite signal of the PPU, the way a TV
+/// decodes it, so the colors can be tuned like the controls of a TV.
+///
+/// The emphasis bits attenuate the signal during parts of the color cycle, so the
+/// emphasized colors come from the same model instead of being approximated.
+///
+/// The default parameters are close to the built-in palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteGenerator {
+    /// Rotates the hue of all colors, in degrees
+    pub hue: f32,
+    /// Multiplier of the color (chroma) part of the signal, `0` gives a grayscale palette
+    pub saturation: f32,
+    /// Multiplier of the whole signal
+    pub contrast: f32,
+    /// Added to the brightness (luma) of all colors, `0` keeps them as is
+    pub brightness: f32,
+    /// The gamma of the display, `2.2` is the standard one and leaves the colors as decoded
+    pub gamma: f32,
+}
+
+impl Default for PaletteGenerator {
+    fn default() -> Self {
+        Self {
+            hue: 0.,
+            saturation: 1.,
+            contrast: 1.,
+            brightness: 0.,
+            gamma: 2.2,
+        }
+    }
+}
+
+impl PaletteGenerator {
+    /// Generate the 64 colors for each of the 8 values of the emphasis bits (`0bBGR`,
+    /// same order as in the PPU mask register)
+    pub fn generate(&self) -> [[Color; 0x40]; 8] {
+        let mut palettes = [[color!(0, 0, 0); 0x40]; 8];
+
+        for (emphasis, palette) in palettes.iter_mut().enumerate() {
+            for (index, color) in palette.iter_mut().enumerate() {
+                *color = self.decode(index as u8, emphasis as u8);
+            }
+        }
+
+        palettes
+    }
+
+    fn decode(&self, index: u8, emphasis: u8) -> Color {
+        let hue = index & 0xF;
+        // `$xE` and `$xF` are black with the luma of the second row
+        let luma = if hue > 0xD { 1 } else { (index >> 4) & 3 } as usize;
+
+        // the signal is a square wave between these two levels, gray for hues 0 and `$D`
+        let low = LEVELS[luma + if hue == 0 { 4 } else { 0 }];
+        let high = LEVELS[luma + if hue < 0xD { 4 } else { 0 }];
+
+        let in_color_phase = |hue: u8, phase: u8| (hue + phase) % 12 < 6;
+
+        let mut y = 0.;
+        let mut i = 0.;
+        let mut q = 0.;
+        for phase in 0..12 {
+            let mut signal = if in_color_phase(hue, phase) {
+                high
+            } else {
+                low
+            };
+
+            let emphasized = (emphasis & 0b001 != 0 && in_color_phase(0, phase))
+                || (emphasis & 0b010 != 0 && in_color_phase(4, phase))
+                || (emphasis & 0b100 != 0 && in_color_phase(8, phase));
+            if hue < 0xE && emphasized {
+                signal *= EMPHASIS_ATTENUATION;
+            }
+
+            let signal = (signal - BLACK) / (WHITE - BLACK);
+            let angle = PI * (phase as f32 + BURST_PHASE) / 6. + self.hue.to_radians();
+            y += signal;
+            i += signal * angle.cos();
+            q += signal * angle.sin();
+        }
+
+        let y = y / 12. * self.contrast + self.brightness;
+        let i = i * 2. / 12. * self.saturation * self.contrast;
+        let q = q * 2. / 12. * self.saturation * self.contrast;
+
+        let channel = |value: f32| {
+            let value = value.clamp(0., 1.).powf(2.2 / self.gamma);
+            (value * 255.).round() as u8
+        };
+
+        // the FCC YIQ to RGB conversion
+        color!(
+            channel(y + 0.946882 * i + 0.623557 * q),
+            channel(y - 0.274788 * i - 0.635691 * q),
+            channel(y - 1.108545 * i + 1.709007 * q)
+        )
+    }
+}
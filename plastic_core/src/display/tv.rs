@@ -1,4 +1,6 @@
-use super::color::Color;
+use super::color::{build_color_table, build_color_table_from, Color, PIXEL_VALUES_COUNT};
+use super::delta::{DeltaTracker, FrameDelta};
+use std::cell::OnceCell;
 
 /// The width of the rendering buffer in pixels
 pub const TV_WIDTH: usize = 256;
@@ -9,54 +11,273 @@ pub const COLOR_BYTES_LEN: usize = 3;
 /// The size of the rendering buffer in bytes ([`TV_WIDTH`]* [`TV_HEIGHT`] * [`COLOR_BYTES_LEN`])
 pub const TV_BUFFER_SIZE: usize = TV_WIDTH * TV_HEIGHT * COLOR_BYTES_LEN;
 
+/// Mask of the source bits in a layer map byte, see [`NES::layer_map`][crate::NES::layer_map]
+pub const LAYER_SOURCE_MASK: u8 = 0b11;
+/// Layer map source: the pixel is the backdrop color (both background and sprites are transparent)
+pub const LAYER_SOURCE_BACKDROP: u8 = 0;
+/// Layer map source: the pixel is from the background
+pub const LAYER_SOURCE_BACKGROUND: u8 = 1;
+/// Layer map source: the pixel is from a sprite in front of the background
+pub const LAYER_SOURCE_SPRITE_FRONT: u8 = 2;
+/// Layer map source: the pixel is from a sprite behind the background
+/// (only visible because the background is transparent)
+pub const LAYER_SOURCE_SPRITE_BEHIND: u8 = 3;
+/// Shift of the palette index bits (2 bits) in a layer map byte
+pub const LAYER_PALETTE_SHIFT: u8 = 2;
+/// Set in a layer map byte if sprite 0 has a non-transparent pixel in this location
+pub const LAYER_SPRITE_0: u8 = 1 << 4;
+
+/// The size of the layer map buffer in bytes ([`TV_WIDTH`]* [`TV_HEIGHT`])
+pub const LAYER_MAP_SIZE: usize = TV_WIDTH * TV_HEIGHT;
+
+/// FNV-1a hash of a pixel buffer, used to compare frames
+pub fn frame_hash(pixels: &[u8]) -> u64 {
+    pixels.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Black in the built-in palette, used for the frames before the first one is drawn
+const BLACK_COLOR_INDEX: u8 = 0x0F;
+
+struct LayerMap {
+    to_display: Box<[u8; LAYER_MAP_SIZE]>,
+    building: Box<[u8; LAYER_MAP_SIZE]>,
+}
+
+/// The pixels of a frame as palette indices, with the emphasis stored separately
+/// since the 3 emphasis bits don't fit in the byte with the 6-bit index.
+///
+/// Games rarely change the emphasis in the middle of the frame, so it is stored
+/// as runs of `(first pixel index, emphasis)`, this requires the pixels to be
+/// written in order when the emphasis changes, which the PPU does.
+struct IndexedFrame {
+    indices: Box<[u8; TV_WIDTH * TV_HEIGHT]>,
+    emphasis_runs: Vec<(u32, u8)>,
+}
+
+impl IndexedFrame {
+    fn new() -> Self {
+        Self {
+            indices: Box::new([BLACK_COLOR_INDEX; TV_WIDTH * TV_HEIGHT]),
+            emphasis_runs: Vec::new(),
+        }
+    }
+
+    /// the emphasis of the last written pixel, `0` at the start of the frame
+    fn current_emphasis(&self) -> u8 {
+        self.emphasis_runs
+            .last()
+            .map_or(0, |&(_, emphasis)| emphasis)
+    }
+
+    fn write_rgb(&self, color_table: &[Color; PIXEL_VALUES_COUNT], result: &mut [u8]) {
+        let mut emphasis = 0;
+        let mut runs = self.emphasis_runs.iter().peekable();
+
+        for (i, (result, &color_index)) in result
+            .chunks_exact_mut(COLOR_BYTES_LEN)
+            .zip(self.indices.iter())
+            .enumerate()
+        {
+            while let Some(&&(start, run_emphasis)) = runs.peek() {
+                if start as usize > i {
+                    break;
+                }
+                emphasis = run_emphasis;
+                runs.next();
+            }
+
+            let color = &color_table[(emphasis as usize) << 6 | color_index as usize];
+            result.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+}
+
+/// The RGB pixels of `frame`, converted and cached in `rgb` on the first access
+fn display_rgb<'a>(
+    rgb: &'a OnceCell<Box<[u8; TV_BUFFER_SIZE]>>,
+    frame: &IndexedFrame,
+    color_table: &[Color; PIXEL_VALUES_COUNT],
+) -> &'a [u8; TV_BUFFER_SIZE] {
+    rgb.get_or_init(|| {
+        let mut pixels = Box::new([0; TV_BUFFER_SIZE]);
+        frame.write_rgb(color_table, pixels.as_mut_slice());
+        pixels
+    })
+}
+
+/// The zeroed display buffer before the first frame
+fn black_pixels() -> OnceCell<Box<[u8; TV_BUFFER_SIZE]>> {
+    OnceCell::from(Box::new([0; TV_BUFFER_SIZE]))
+}
+
 pub struct TV {
-    /// Current pixel buffer ready for display.
-    pixels_to_display: Box<[u8; TV_BUFFER_SIZE]>,
+    /// The frame ready for display
+    to_display: IndexedFrame,
+
+    /// The RGB pixels of `to_display`, converted on the first access after
+    /// the frame or the palette changes
+    pixels_to_display: OnceCell<Box<[u8; TV_BUFFER_SIZE]>>,
 
-    /// A temporary buffer to holds the screen state while the PPU is drawing
+    /// A temporary frame to holds the screen state while the PPU is drawing
     /// in the current frame
-    building_pixels: Box<[Color; TV_WIDTH * TV_HEIGHT]>,
+    building: IndexedFrame,
+
+    /// Conversion table from pixel values (`emphasis << 6 | color_index`) to RGB
+    color_table: Box<[Color; PIXEL_VALUES_COUNT]>,
+
+    /// Optional buffers containing the source of every pixel, see [`LAYER_SOURCE_MASK`]
+    layer_map: Option<LayerMap>,
+
+    /// If `false`, pixels are not written and the display buffer is not updated
+    output_enabled: bool,
+
+    /// The display buffer as of the last `frame_delta`
+    delta_tracker: DeltaTracker,
 }
 
 impl TV {
     pub fn new() -> Self {
         Self {
-            pixels_to_display: Box::new([0; TV_BUFFER_SIZE]),
-            building_pixels: Box::new([color!(0, 0, 0); TV_WIDTH * TV_HEIGHT]),
+            to_display: IndexedFrame::new(),
+            pixels_to_display: black_pixels(),
+            building: IndexedFrame::new(),
+            color_table: build_color_table(),
+            layer_map: None,
+            output_enabled: true,
+            delta_tracker: DeltaTracker::default(),
+        }
+    }
+
+    /// Use the 64 colors of each emphasis for the displayed frame and the next frames,
+    /// or the built-in palette if `None`
+    pub fn set_palette(&mut self, palettes: Option<&[[Color; 0x40]; 8]>) {
+        self.color_table = match palettes {
+            Some(palettes) => build_color_table_from(palettes),
+            None => build_color_table(),
+        };
+        self.pixels_to_display.take();
+    }
+
+    pub fn set_output_enabled(&mut self, enabled: bool) {
+        self.output_enabled = enabled;
+    }
+
+    pub fn is_output_enabled(&self) -> bool {
+        self.output_enabled
+    }
+
+    pub fn set_layer_map_enabled(&mut self, enabled: bool) {
+        if enabled {
+            if self.layer_map.is_none() {
+                self.layer_map = Some(LayerMap {
+                    to_display: Box::new([0; LAYER_MAP_SIZE]),
+                    building: Box::new([0; LAYER_MAP_SIZE]),
+                });
+            }
+        } else {
+            self.layer_map = None;
         }
     }
 
-    /// update the pixel of the temporary buffer [`building_pixels`]
-    pub fn set_pixel(&mut self, x: u32, y: u32, color: &Color) {
-        let index = y as usize * TV_WIDTH + x as usize;
-        self.building_pixels[index] = *color;
+    pub fn is_layer_map_enabled(&self) -> bool {
+        self.layer_map.is_some()
+    }
+
+    /// update the layer information of a pixel, does nothing if the layer map is disabled
+    pub fn set_pixel_layer(&mut self, x: u32, y: u32, layer: u8) {
+        if let Some(layer_map) = self.layer_map.as_mut() {
+            layer_map.building[y as usize * TV_WIDTH + x as usize] = layer;
+        }
+    }
+
+    /// update the pixel of the temporary frame [`building`],
+    /// `color_index` is the 6-bit palette color and `emphasis` is the 3 emphasis bits (`0bBGR`)
+    pub fn set_pixel(&mut self, x: u32, y: u32, color_index: u8, emphasis: u8) {
+        let index = y * TV_WIDTH as u32 + x;
+        self.building.indices[index as usize] = color_index & 0x3F;
+
+        let emphasis = emphasis & 0b111;
+        if emphasis != self.building.current_emphasis() {
+            self.building.emphasis_runs.push((index, emphasis));
+        }
+    }
+
+    /// the same as [`set_pixel`](Self::set_pixel) for all the pixels of the row `y`
+    /// with the same `emphasis`
+    pub fn set_row(&mut self, y: u32, color_indices: &[u8; TV_WIDTH], emphasis: u8) {
+        let index = y * TV_WIDTH as u32;
+        let row = &mut self.building.indices[index as usize..][..TV_WIDTH];
+        for (pixel, &color_index) in row.iter_mut().zip(color_indices) {
+            *pixel = color_index & 0x3F;
+        }
+
+        let emphasis = emphasis & 0b111;
+        if emphasis != self.building.current_emphasis() {
+            self.building.emphasis_runs.push((index, emphasis));
+        }
     }
 
     /// the PPU must call this at the end of the frame, maybe around `VBLANK`
-    /// to tell the screen to copy and translate the [`Color`] data into the
-    /// [`Arc`] shared screen buffer
+    /// to tell the screen to show the current frame, it is converted to RGB
+    /// when the display buffer is accessed
     pub fn signal_end_of_frame(&mut self) {
-        for (result, color) in self
-            .pixels_to_display
-            .chunks_exact_mut(COLOR_BYTES_LEN)
-            .zip(self.building_pixels.iter())
-        {
-            result[0..COLOR_BYTES_LEN].copy_from_slice(&[color.r, color.g, color.b]);
+        let emphasis_runs = std::mem::take(&mut self.building.emphasis_runs);
+
+        if !self.output_enabled {
+            return;
+        }
+
+        self.to_display
+            .indices
+            .copy_from_slice(self.building.indices.as_ref());
+        self.to_display.emphasis_runs = emphasis_runs;
+        self.pixels_to_display.take();
+
+        if let Some(layer_map) = self.layer_map.as_mut() {
+            layer_map
+                .to_display
+                .copy_from_slice(layer_map.building.as_ref());
         }
     }
 
     /// resets and zero all buffers
     pub fn reset(&mut self) {
-        for i in self.pixels_to_display.iter_mut() {
-            *i = 0;
-        }
+        self.to_display = IndexedFrame::new();
+        self.building = IndexedFrame::new();
+        self.pixels_to_display = black_pixels();
 
-        for i in self.building_pixels.as_mut() {
-            *i = color!(0, 0, 0);
+        if let Some(layer_map) = self.layer_map.as_mut() {
+            layer_map.to_display.fill(0);
+            layer_map.building.fill(0);
         }
     }
 
     pub fn display_pixel_buffer(&self) -> &[u8] {
-        self.pixels_to_display.as_ref()
+        display_rgb(&self.pixels_to_display, &self.to_display, &self.color_table)
+    }
+
+    /// The palette indices (`0x00..0x40`) of the display buffer, one byte per pixel,
+    /// without the emphasis
+    pub fn display_index_buffer(&self) -> &[u8] {
+        self.to_display.indices.as_ref()
+    }
+
+    /// The tiles of the display buffer that changed since the last call
+    pub fn frame_delta(&mut self) -> FrameDelta {
+        let pixels = display_rgb(&self.pixels_to_display, &self.to_display, &self.color_table);
+        self.delta_tracker.delta(pixels)
+    }
+
+    /// Make the next [`frame_delta`](Self::frame_delta) include the whole screen
+    pub fn force_full_frame_delta(&mut self) {
+        self.delta_tracker.force_full();
+    }
+
+    pub fn display_layer_map(&self) -> Option<&[u8]> {
+        self.layer_map
+            .as_ref()
+            .map(|layer_map| layer_map.to_display.as_ref().as_slice())
     }
 }
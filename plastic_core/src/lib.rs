@@ -29,32 +29,76 @@
 mod common;
 mod apu2a03;
 mod cartridge;
+#[cfg(feature = "compare")]
+pub mod compare;
+mod compat;
+pub mod conformance;
 mod controller;
 mod cpu6502;
+mod diagnostics;
 mod display;
+mod memory_map;
 #[cfg(feature = "frontend_misc")]
 pub mod misc;
+pub mod movie;
 mod nes;
+#[cfg(feature = "overrides")]
+pub mod overrides;
 mod ppu2c02;
+#[cfg(feature = "profiling")]
+pub mod profiler;
+#[cfg(feature = "rl")]
+pub mod rl;
 
 #[cfg(test)]
 mod tests;
 
-pub use cartridge::CartridgeError;
+pub use cartridge::{
+    apply_patch, CartridgeError, ExpansionDevice, HeaderErrorReason, MappingViolation, RomData,
+    RomSection, TimingMode,
+};
 pub use common::save_state::SaveError;
-pub use controller::NESKey;
-pub use nes::NES;
+pub use common::{EmulatorConfig, Region, SyncBaseline, FRAME_RATE_NTSC, FRAME_RATE_PAL};
+pub use compat::{CompatFinding, CompatReport};
+pub use controller::{
+    AnalogToDpad, AnalogToDpadConfig, DpadState, ExpansionPortDevice, NESKey, SocdPolicy,
+};
+pub use diagnostics::{Diagnostic, StackWrap};
+pub use memory_map::MemoryRegion;
+pub use nes::{
+    BudgetResult, FrameStats, NESBuilder, RegionSource, SramActivity, StateSnapshot, NES,
+};
+pub use ppu2c02::{NametableSource, NametableView, PpuBackend};
+#[cfg(feature = "debug")]
+pub use ppu2c02::{OamEntry, PpuSnapshot};
 
 /// Structures used when interacting with the CPU, see also [`NES::clock`][NES::clock]
 pub mod cpu {
-    pub use super::cpu6502::CPURunState;
+    pub use super::cpu6502::{CPURunState, CpuState};
 }
 
 /// Helper variables related to handling pixel buffers from the emulator
 pub mod nes_display {
-    pub use super::display::{COLOR_BYTES_LEN, TV_BUFFER_SIZE, TV_HEIGHT, TV_WIDTH};
+    pub use super::display::{
+        frame_hash, Color, DeltaTile, FrameDelta, PaletteGenerator, COLOR_BYTES_LEN, DELTA_TILES_X,
+        DELTA_TILES_Y, DELTA_TILE_BYTES, DELTA_TILE_SIZE, LAYER_MAP_SIZE, LAYER_PALETTE_SHIFT,
+        LAYER_SOURCE_BACKDROP, LAYER_SOURCE_BACKGROUND, LAYER_SOURCE_MASK,
+        LAYER_SOURCE_SPRITE_BEHIND, LAYER_SOURCE_SPRITE_FRONT, LAYER_SPRITE_0, TV_BUFFER_SIZE,
+        TV_HEIGHT, TV_WIDTH,
+    };
+}
+/// The clock frequencies and frame timing of the consoles, see also [`NES::current_fps`]
+pub mod nes_timing {
+    pub use super::common::{
+        CPU_FREQ_NTSC, CPU_FREQ_PAL, CYCLES_PER_FRAME_NTSC, CYCLES_PER_FRAME_NTSC_EVEN,
+        CYCLES_PER_FRAME_NTSC_ODD, CYCLES_PER_FRAME_PAL, NTSC_FPS, PAL_FPS,
+    };
 }
 /// Helper variables related to handling audio buffers from the emulator
 pub mod nes_audio {
-    pub use super::apu2a03::SAMPLE_RATE;
+    pub use super::apu2a03::{
+        ApuSnapshot, ApuWrite, AudioRing, AudioSampling, ChannelSamples, ChannelTap,
+        DmcSampleEvent, DmcState, NoiseState, PulseState, TriangleState,
+        DEFAULT_AUDIO_RING_CAPACITY, SAMPLE_RATE,
+    };
 }
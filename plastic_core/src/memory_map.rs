@@ -0,0 +1,88 @@
+//! Labels of the CPU address space, for memory viewers and debuggers, see
+//! [`NES::memory_region`](crate::NES::memory_region) and
+//! [`NES::memory_map`](crate::NES::memory_map).
+
+use crate::cartridge::{Cartridge, CpuMapping};
+use std::ops::RangeInclusive;
+
+/// The size of a PRG RAM bank
+const PRG_RAM_BANK_SIZE: usize = 0x2000;
+
+/// What a CPU address is mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// The 2kb internal RAM, `$0000-$07FF`
+    Ram,
+    /// Mirrors of the internal RAM, `$0800-$1FFF`
+    RamMirror,
+    /// The PPU registers, `$2000-$2007`
+    PpuRegisters,
+    /// Mirrors of the PPU registers every 8 bytes, `$2008-$3FFF`
+    PpuRegistersMirror,
+    /// The APU, OAM DMA and controller registers, `$4000-$4017`
+    ApuIo,
+    /// The APU and I/O test registers, normally disabled, `$4018-$401F`
+    ApuIoTest,
+    /// PRG RAM in the cartridge, `bank` is in units of 8kb
+    PrgRam { bank: u16, battery: bool },
+    /// PRG ROM in the cartridge, `bank` is in units of `mapper_bank_size` bytes,
+    /// the smallest bank size the mapper switches
+    PrgRom { bank: u16, mapper_bank_size: u16 },
+    /// Nothing is mapped for reading, such as disabled PRG RAM or mapper registers
+    Unmapped,
+}
+
+pub(crate) fn memory_region(cartridge: &Cartridge, address: u16) -> MemoryRegion {
+    match address {
+        0x0000..=0x07FF => MemoryRegion::Ram,
+        0x0800..=0x1FFF => MemoryRegion::RamMirror,
+        0x2000..=0x2007 => MemoryRegion::PpuRegisters,
+        0x2008..=0x3FFF => MemoryRegion::PpuRegistersMirror,
+        0x4000..=0x4017 => MemoryRegion::ApuIo,
+        0x4018..=0x401F => MemoryRegion::ApuIoTest,
+        0x4020..=0xFFFF => match cartridge.map_cpu_address(address) {
+            CpuMapping::PrgRom(offset) => {
+                let mapper_bank_size = cartridge.prg_bank_size();
+                MemoryRegion::PrgRom {
+                    bank: (offset / mapper_bank_size as usize) as u16,
+                    mapper_bank_size,
+                }
+            }
+            CpuMapping::PrgRam(offset) => MemoryRegion::PrgRam {
+                bank: (offset / PRG_RAM_BANK_SIZE) as u16,
+                battery: cartridge.has_prg_ram_battery(),
+            },
+            CpuMapping::Unmapped => MemoryRegion::Unmapped,
+        },
+    }
+}
+
+/// The whole address space as ranges of the same region, ranges of PRG ROM and
+/// PRG RAM are split where the mapped data is not contiguous, so a mirrored bank
+/// appears in multiple ranges
+pub(crate) fn memory_map(cartridge: &Cartridge) -> Vec<(RangeInclusive<u16>, MemoryRegion)> {
+    let mut map: Vec<(RangeInclusive<u16>, MemoryRegion)> = Vec::new();
+    // the mapping of the last address, to know if the next one continues it
+    let mut last_mapping = CpuMapping::Unmapped;
+
+    for address in 0..=0xFFFF {
+        let region = memory_region(cartridge, address);
+        let mapping = cartridge.map_cpu_address(address);
+
+        let is_contiguous = match (last_mapping, mapping) {
+            (CpuMapping::PrgRom(last), CpuMapping::PrgRom(offset))
+            | (CpuMapping::PrgRam(last), CpuMapping::PrgRam(offset)) => offset == last + 1,
+            _ => true,
+        };
+        last_mapping = mapping;
+
+        match map.last_mut() {
+            Some((range, last_region)) if *last_region == region && is_contiguous => {
+                *range = *range.start()..=address;
+            }
+            _ => map.push((address..=address, region)),
+        }
+    }
+
+    map
+}
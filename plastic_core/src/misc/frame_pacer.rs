@@ -0,0 +1,122 @@
+//! Frame pacing independent of the display refresh rate
+
+use std::time::Duration;
+
+/// Default maximum number of frames returned by [`FramePacer::frames_to_run`]
+const DEFAULT_MAX_FRAMES: u32 = 4;
+/// Maximum rate adjustment applied by the audio feedback, in parts-per-thousand
+const MAX_AUDIO_ADJUSTMENT_PPT: f64 = 5.0;
+
+/// Decide how many emulation frames to run on each render tick, so that the
+/// emulation runs at the correct speed regardless of the display refresh rate.
+///
+/// Feed it with the time elapsed since the last tick with [`FramePacer::elapsed`],
+/// then run [`FramePacer::frames_to_run`] frames. The fractional part of frames
+/// is kept for the next ticks.
+///
+/// ```
+/// # use plastic_core::{misc::FramePacer, nes_timing::NTSC_FPS};
+/// # use std::time::Duration;
+/// let mut pacer = FramePacer::new(NTSC_FPS);
+///
+/// // 144Hz display
+/// pacer.elapsed(Duration::from_secs_f64(1. / 144.));
+/// let frames = pacer.frames_to_run();
+/// assert!(frames <= 1);
+/// ```
+pub struct FramePacer {
+    frame_rate: f64,
+    max_frames: u32,
+    /// frames that should be run, but were not yet returned
+    pending_frames: f64,
+
+    /// target number of queued audio samples, `None` if the audio feedback is disabled
+    audio_target: Option<usize>,
+    /// multiplier of the frame rate, changed by the audio feedback
+    rate_adjustment: f64,
+}
+
+impl FramePacer {
+    /// Create a new pacer running at `frame_rate` frames per second,
+    /// see [`NES::current_fps`](crate::NES::current_fps)
+    pub fn new(frame_rate: f64) -> Self {
+        assert!(frame_rate > 0., "frame rate must be positive");
+
+        Self {
+            frame_rate,
+            max_frames: DEFAULT_MAX_FRAMES,
+            pending_frames: 0.,
+            audio_target: None,
+            rate_adjustment: 1.,
+        }
+    }
+
+    /// Set the maximum number of frames to return from a single [`FramePacer::frames_to_run`],
+    /// any frames more than that are dropped, so that the emulator doesn't keep falling
+    /// behind if it can't run at full speed. `4` by default.
+    pub fn set_max_frames(&mut self, max_frames: u32) {
+        self.max_frames = max_frames.max(1);
+    }
+
+    /// Change the target frame rate, keeping the pending fractional frame
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        assert!(frame_rate > 0., "frame rate must be positive");
+        self.frame_rate = frame_rate;
+    }
+
+    /// The frame rate currently used, including the audio feedback adjustment
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate * self.rate_adjustment
+    }
+
+    /// Enable the audio feedback mode, which keeps the audio and video in sync without
+    /// resampling by nudging the frame rate (by at most 5 parts-per-thousand) to keep the
+    /// number of queued audio samples around `target_queued_samples`.
+    ///
+    /// The queue size must be reported with [`FramePacer::audio_queued`].
+    /// `None` disables it.
+    pub fn set_audio_feedback(&mut self, target_queued_samples: Option<usize>) {
+        self.audio_target = target_queued_samples.filter(|&target| target > 0);
+        self.rate_adjustment = 1.;
+    }
+
+    /// Report the number of audio samples currently queued in the audio device,
+    /// only used in the audio feedback mode
+    pub fn audio_queued(&mut self, queued_samples: usize) {
+        let Some(target) = self.audio_target else {
+            return;
+        };
+
+        // less samples than the target means we should run faster and produce more
+        let error = (target as f64 - queued_samples as f64) / target as f64;
+        let error = error.clamp(-1., 1.);
+
+        self.rate_adjustment = 1. + error * MAX_AUDIO_ADJUSTMENT_PPT / 1000.;
+    }
+
+    /// Forget the pending frames and the audio feedback adjustment, call this when
+    /// resuming from a pause, so the pause doesn't skew the pacing
+    pub fn reset(&mut self) {
+        self.pending_frames = 0.;
+        self.rate_adjustment = 1.;
+    }
+
+    /// Add the time elapsed since the last call
+    pub fn elapsed(&mut self, dt: Duration) {
+        self.pending_frames += dt.as_secs_f64() * self.frame_rate();
+    }
+
+    /// The number of frames to run now, based on the time elapsed
+    pub fn frames_to_run(&mut self) -> u32 {
+        let frames = self.pending_frames.floor();
+
+        if frames > self.max_frames as f64 {
+            // drop the frames we couldn't keep up with, but keep the fraction
+            self.pending_frames = self.pending_frames.fract();
+            self.max_frames
+        } else {
+            self.pending_frames -= frames;
+            frames as u32
+        }
+    }
+}
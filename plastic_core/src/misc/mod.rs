@@ -1,5 +1,14 @@
 //! Some common tools used for the emulator UIs to limit FPs
 
+mod frame_pacer;
+mod resampler;
+mod scaler;
+mod tests;
+
+pub use frame_pacer::FramePacer;
+pub use resampler::Resampler;
+pub use scaler::{scale2x, scale3x, scale_nearest, Scaler};
+
 use std::time::{Duration, Instant};
 
 pub struct MovingAverage {
@@ -90,25 +99,35 @@ impl Fps {
 /// Also add or remove samples to match the current speed
 /// more speed_modifier means faster speed and less samples
 /// `speed_modifier == 1.0` means normal speed
+///
+/// The buffer is expected to be interleaved stereo with both channels equal (as returned
+/// from [`NES::audio_buffer`](crate::NES::audio_buffer)), it is resampled using [`Resampler`].
+/// Since there is no state kept between calls, use [`Resampler`] directly for the best quality.
 pub fn process_audio(audio_buffer: &[f32], speed_modifier: f32) -> Vec<f32> {
-    let target_len = (audio_buffer.len() as f32 * speed_modifier).ceil() as usize;
-    let mut adjusted_buffer = Vec::with_capacity(target_len);
-
-    for i in 0..target_len {
-        let src_index_f = i as f32 / speed_modifier;
-        let src_index = src_index_f.floor() as usize;
-        let next_index = std::cmp::min(src_index + 1, audio_buffer.len() - 1);
-        let fraction = src_index_f.fract();
-
-        let sample = if src_index < audio_buffer.len() {
-            let current_sample = audio_buffer[src_index];
-            let next_sample = audio_buffer[next_index];
-            current_sample * (1.0 - fraction) + next_sample * fraction
-        } else {
-            *audio_buffer.last().unwrap_or(&0.0)
-        };
-        adjusted_buffer.push(sample);
+    // resolution of the speed modifier ratio
+    const RATE_BASE: u32 = 10000;
+
+    if speed_modifier == 1.0 || audio_buffer.is_empty() {
+        return audio_buffer.to_vec();
+    }
+
+    let mono = audio_buffer.iter().step_by(2).copied().collect::<Vec<_>>();
+    let target_len = (mono.len() as f32 * speed_modifier).ceil() as usize;
+    let dst_rate = ((RATE_BASE as f32 * speed_modifier).round() as u32).max(1);
+
+    // extend the edges of the buffer, to account for the filter delay
+    let first = mono[0];
+    let last = mono[mono.len() - 1];
+    let mut resampler = Resampler::with_initial_sample(RATE_BASE, dst_rate, first);
+    let mut resampled = Vec::with_capacity(target_len + 1);
+    resampler.process(&mono, &mut resampled);
+    while resampled.len() < target_len {
+        resampler.process(&[last; 16], &mut resampled);
     }
+    resampled.truncate(target_len);
 
-    adjusted_buffer
+    resampled
+        .iter()
+        .flat_map(|&sample| [sample, sample])
+        .collect()
 }
@@ -0,0 +1,154 @@
+//! Windowed-sinc audio resampler
+
+/// Number of input samples on each side of the output sample used by the filter
+const HALF_TAPS: usize = 8;
+/// Kaiser window shape parameter
+const KAISER_BETA: f64 = 6.0;
+/// Fraction of the Nyquist frequency kept by the low-pass filter
+const CUTOFF_RATIO: f64 = 0.9;
+/// Number of fractional positions between two input samples in the filter table,
+/// positions in between are linearly interpolated
+const TABLE_PHASES: usize = 512;
+
+/// Zeroth order modified Bessel function of the first kind
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+
+    for k in 1..32 {
+        term *= half_x / k as f64;
+        sum += term * term;
+    }
+
+    sum
+}
+
+fn kaiser_window(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+
+    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// A streaming mono audio resampler using a windowed-sinc (Kaiser, 16 taps) filter.
+///
+/// The filter state is kept between calls to [`Resampler::process`], so the input
+/// can be split into blocks of any size, and the output will be the same as
+/// processing it all at once.
+///
+/// The output is delayed by [`HALF_TAPS`] input samples.
+pub struct Resampler {
+    src_rate: u32,
+    dst_rate: u32,
+    /// filter weights for each of the `TABLE_PHASES + 1` fractional positions,
+    /// `2 * HALF_TAPS` weights each, normalized to unity gain at DC
+    table: Vec<f64>,
+
+    /// input samples not fully consumed yet, including the history needed by the filter
+    buffer: Vec<f32>,
+    /// the position of the next output sample in `buffer` is
+    /// `position + position_fraction / dst_rate`
+    position: usize,
+    position_fraction: u32,
+}
+
+impl Resampler {
+    pub fn new(src_rate: u32, dst_rate: u32) -> Self {
+        Self::with_initial_sample(src_rate, dst_rate, 0.0)
+    }
+
+    /// Same as [`Resampler::new`], but the filter history is filled with
+    /// `sample` instead of silence.
+    pub fn with_initial_sample(src_rate: u32, dst_rate: u32, sample: f32) -> Self {
+        assert!(src_rate > 0 && dst_rate > 0, "sample rates must not be 0");
+
+        let ratio = (dst_rate as f64 / src_rate as f64).min(1.0);
+
+        Self {
+            src_rate,
+            dst_rate,
+            table: build_table(0.5 * ratio * CUTOFF_RATIO),
+            buffer: vec![sample; HALF_TAPS],
+            position: HALF_TAPS - 1,
+            position_fraction: 0,
+        }
+    }
+
+    /// Resample `input` and append the result into `output`.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+
+        while self.position + HALF_TAPS < self.buffer.len() {
+            output.push(self.filter_at_position());
+
+            self.position_fraction += self.src_rate;
+            self.position += (self.position_fraction / self.dst_rate) as usize;
+            self.position_fraction %= self.dst_rate;
+        }
+
+        // keep only the history needed for the next output sample, when downsampling
+        // by a large ratio the position can be past the end of the buffer, the rest
+        // stays in `position` and skips the start of the next input
+        let consumed = (self.position + 1)
+            .saturating_sub(HALF_TAPS)
+            .min(self.buffer.len());
+        self.buffer.drain(..consumed);
+        self.position -= consumed;
+    }
+
+    fn filter_at_position(&self) -> f32 {
+        let phase = self.position_fraction as f64 * TABLE_PHASES as f64 / self.dst_rate as f64;
+        let phase_index = phase as usize;
+        let phase_fraction = phase - phase_index as f64;
+
+        let taps = HALF_TAPS * 2;
+        let current = &self.table[phase_index * taps..][..taps];
+        let next = &self.table[(phase_index + 1) * taps..][..taps];
+
+        self.buffer[self.position + 1 - HALF_TAPS..=self.position + HALF_TAPS]
+            .iter()
+            .zip(current.iter().zip(next))
+            .map(|(&sample, (&a, &b))| sample as f64 * (a + (b - a) * phase_fraction))
+            .sum::<f64>() as f32
+    }
+}
+
+/// Compute the filter weights for every phase in the table, `cutoff` is
+/// relative to the input sample rate
+fn build_table(cutoff: f64) -> Vec<f64> {
+    let mut table = Vec::with_capacity((TABLE_PHASES + 1) * HALF_TAPS * 2);
+
+    for phase in 0..=TABLE_PHASES {
+        let fraction = phase as f64 / TABLE_PHASES as f64;
+        let start = table.len();
+
+        for i in 0..HALF_TAPS * 2 {
+            // distance from the output sample, in input samples
+            let distance = i as f64 - (HALF_TAPS - 1) as f64 - fraction;
+
+            table.push(
+                2.0 * cutoff
+                    * sinc(2.0 * cutoff * distance)
+                    * kaiser_window(distance / HALF_TAPS as f64),
+            );
+        }
+
+        // normalize to keep unity gain at DC
+        let weights = &mut table[start..];
+        let sum = weights.iter().sum::<f64>();
+        weights.iter_mut().for_each(|weight| *weight /= sum);
+    }
+
+    table
+}
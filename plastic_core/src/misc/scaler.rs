@@ -0,0 +1,245 @@
+//! Integer upscaling of RGB pixel buffers, for screenshots and video dumps
+//!
+//! All the functions take a buffer in the format of [`NES::pixel_buffer`](crate::NES::pixel_buffer),
+//! 3 bytes per pixel row by row, with its size in pixels, and return a new buffer of
+//! `width * factor` by `height * factor` pixels in the same format.
+
+use crate::display::COLOR_BYTES_LEN;
+
+type Pixel = [u8; COLOR_BYTES_LEN];
+
+/// The upscaling algorithms, see [`Scaler::scale`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaler {
+    /// Each pixel is repeated `factor` times horizontally and vertically, see [`scale_nearest`]
+    Nearest(usize),
+    /// See [`scale2x`]
+    Scale2x,
+    /// See [`scale3x`]
+    Scale3x,
+}
+
+impl Scaler {
+    /// The factor the width and height are multiplied by
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest(factor) => *factor,
+            Scaler::Scale2x => 2,
+            Scaler::Scale3x => 3,
+        }
+    }
+
+    /// Scale `src` of `width` by `height` pixels, the result is
+    /// `width * self.factor()` by `height * self.factor()` pixels
+    pub fn scale(&self, src: &[u8], width: usize, height: usize) -> Vec<u8> {
+        match self {
+            Scaler::Nearest(factor) => scale_nearest(src, width, height, *factor),
+            Scaler::Scale2x => scale2x(src, width, height),
+            Scaler::Scale3x => scale3x(src, width, height),
+        }
+    }
+}
+
+/// Access to the pixels of the source image, the pixels outside it are the same as the
+/// nearest edge pixel
+struct Image<'a> {
+    pixels: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Image<'a> {
+    fn new(pixels: &'a [u8], width: usize, height: usize) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height * COLOR_BYTES_LEN,
+            "the buffer size doesn't match the image size"
+        );
+
+        Self {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> Pixel {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        let offset = (y * self.width + x) * COLOR_BYTES_LEN;
+
+        self.pixels[offset..offset + COLOR_BYTES_LEN]
+            .try_into()
+            .unwrap()
+    }
+}
+
+/// Writes the `factor * factor` block of pixels of every source pixel
+struct Output {
+    pixels: Vec<u8>,
+    width: usize,
+    factor: usize,
+}
+
+impl Output {
+    fn new(width: usize, height: usize, factor: usize) -> Self {
+        Self {
+            pixels: vec![0; width * factor * height * factor * COLOR_BYTES_LEN],
+            width: width * factor,
+            factor,
+        }
+    }
+
+    /// `block` has `factor * factor` pixels, row by row
+    fn write_block(&mut self, x: usize, y: usize, block: &[Pixel]) {
+        for (row, block_row) in block.chunks_exact(self.factor).enumerate() {
+            let start = ((y * self.factor + row) * self.width + x * self.factor) * COLOR_BYTES_LEN;
+            for (pixel, out) in block_row.iter().zip(
+                self.pixels[start..start + self.factor * COLOR_BYTES_LEN]
+                    .chunks_exact_mut(COLOR_BYTES_LEN),
+            ) {
+                out.copy_from_slice(pixel);
+            }
+        }
+    }
+}
+
+/// Repeat each pixel `factor` times horizontally and vertically, the result is
+/// `width * factor` by `height * factor` pixels.
+///
+/// # Panics
+/// If `src` is not `width * height` pixels, or `factor` is 0
+pub fn scale_nearest(src: &[u8], width: usize, height: usize, factor: usize) -> Vec<u8> {
+    assert!(factor > 0, "the scale factor must not be 0");
+    let image = Image::new(src, width, height);
+
+    let row_len = width * factor * COLOR_BYTES_LEN;
+    let mut pixels = Vec::with_capacity(row_len * height * factor);
+    for row in image.pixels.chunks_exact(width * COLOR_BYTES_LEN) {
+        let start = pixels.len();
+        for pixel in row.chunks_exact(COLOR_BYTES_LEN) {
+            for _ in 0..factor {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+        for _ in 1..factor {
+            pixels.extend_from_within(start..start + row_len);
+        }
+    }
+
+    pixels
+}
+
+/// Scale by 2 with the Scale2x algorithm (also known as EPX or AdvMAME2x), the result
+/// is `width * 2` by `height * 2` pixels.
+///
+/// Each pixel becomes a 2x2 block, and a corner of the block takes the color of the
+/// two neighbours next to it if they are equal, which smooths diagonal edges without
+/// blurring or adding new colors. The pixels outside the image are the same as the
+/// edge pixels.
+///
+/// # Panics
+/// If `src` is not `width * height` pixels
+pub fn scale2x(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let image = Image::new(src, width, height);
+    let mut output = Output::new(width, height, 2);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as isize, y as isize);
+            //   a
+            // c p b
+            //   d
+            let p = image.pixel(xi, yi);
+            let a = image.pixel(xi, yi - 1);
+            let b = image.pixel(xi + 1, yi);
+            let c = image.pixel(xi - 1, yi);
+            let d = image.pixel(xi, yi + 1);
+
+            let block = if a != d && c != b {
+                [
+                    if c == a { a } else { p },
+                    if a == b { b } else { p },
+                    if d == c { c } else { p },
+                    if b == d { d } else { p },
+                ]
+            } else {
+                [p; 4]
+            };
+            output.write_block(x, y, &block);
+        }
+    }
+
+    output.pixels
+}
+
+/// Scale by 3 with the Scale3x algorithm (also known as AdvMAME3x), the result is
+/// `width * 3` by `height * 3` pixels.
+///
+/// The same idea as [`scale2x`] with 3x3 blocks, the edge pixels of the block also
+/// take the color of the neighbours when they continue a diagonal edge.
+///
+/// # Panics
+/// If `src` is not `width * height` pixels
+pub fn scale3x(src: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let image = Image::new(src, width, height);
+    let mut output = Output::new(width, height, 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as isize, y as isize);
+            // a b c
+            // d e f
+            // g h i
+            let a = image.pixel(xi - 1, yi - 1);
+            let b = image.pixel(xi, yi - 1);
+            let c = image.pixel(xi + 1, yi - 1);
+            let d = image.pixel(xi - 1, yi);
+            let e = image.pixel(xi, yi);
+            let f = image.pixel(xi + 1, yi);
+            let g = image.pixel(xi - 1, yi + 1);
+            let h = image.pixel(xi, yi + 1);
+            let i = image.pixel(xi + 1, yi + 1);
+
+            let block = if b != h && d != f {
+                let top_left = d == b;
+                let top_right = b == f;
+                let bottom_left = d == h;
+                let bottom_right = h == f;
+
+                [
+                    if top_left { d } else { e },
+                    if (top_left && e != c) || (top_right && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if top_right { f } else { e },
+                    if (top_left && e != g) || (bottom_left && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (top_right && e != i) || (bottom_right && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if bottom_left { d } else { e },
+                    if (bottom_left && e != i) || (bottom_right && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if bottom_right { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+            output.write_block(x, y, &block);
+        }
+    }
+
+    output.pixels
+}
@@ -0,0 +1,325 @@
+#[cfg(test)]
+mod misc_tests {
+    use super::super::{process_audio, scale2x, scale3x, scale_nearest, FramePacer, Resampler};
+    use crate::nes_timing::{NTSC_FPS, PAL_FPS};
+    use std::f64::consts::PI;
+    use std::time::Duration;
+
+    fn sine(frequency: f64, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2. * PI * frequency * i as f64 / rate as f64).sin() as f32 * 0.5)
+            .collect()
+    }
+
+    /// magnitude of the `frequency` component of `samples`
+    fn magnitude(samples: &[f32], frequency: f64, rate: u32) -> f64 {
+        let (re, im) = samples
+            .iter()
+            .enumerate()
+            .fold((0., 0.), |(re, im), (i, &s)| {
+                let angle = 2. * PI * frequency * i as f64 / rate as f64;
+                (re + s as f64 * angle.cos(), im - s as f64 * angle.sin())
+            });
+
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn sine_harmonic_distortion() {
+        for (src, dst) in [(44100, 48000), (48000, 44100), (44100, 22050)] {
+            let input = sine(1000., src, src as usize * 2);
+
+            let mut resampler = Resampler::new(src, dst);
+            let mut output = Vec::new();
+            resampler.process(&input, &mut output);
+
+            // skip the start, and take a whole number of cycles
+            let window = &output[dst as usize / 2..dst as usize / 2 + dst as usize / 10];
+
+            let fundamental = magnitude(window, 1000., dst);
+            let harmonics = (2..=8)
+                .map(|h| magnitude(window, 1000. * h as f64, dst).powi(2))
+                .sum::<f64>()
+                .sqrt();
+
+            let thd = harmonics / fundamental;
+            assert!(thd < 0.001, "{} -> {}: THD {}", src, dst, thd);
+        }
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let input = sine(1234., 44100, 10000)
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s + ((i * 7919) % 13) as f32 / 100.)
+            .collect::<Vec<_>>();
+
+        let mut one_shot = Vec::new();
+        Resampler::new(44100, 48000).process(&input, &mut one_shot);
+
+        for block_sizes in [&[1][..], &[7, 100], &[333, 1, 4096, 2]] {
+            let mut resampler = Resampler::new(44100, 48000);
+            let mut streamed = Vec::new();
+
+            let mut rest = input.as_slice();
+            for &size in block_sizes.iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let (block, next) = rest.split_at(size.min(rest.len()));
+                resampler.process(block, &mut streamed);
+                rest = next;
+            }
+
+            assert_eq!(streamed, one_shot, "block sizes {:?}", block_sizes);
+        }
+    }
+
+    #[test]
+    fn streaming_large_ratio_small_blocks() {
+        let input = sine(100., 48000, 48000);
+
+        let mut one_shot = Vec::new();
+        Resampler::new(48000, 1000).process(&input, &mut one_shot);
+
+        let mut resampler = Resampler::new(48000, 1000);
+        let mut streamed = Vec::new();
+        for block in input.chunks(30) {
+            resampler.process(block, &mut streamed);
+        }
+
+        assert_eq!(streamed, one_shot);
+    }
+
+    #[test]
+    fn process_audio_length() {
+        let mono = sine(440., 44100, 735);
+        let stereo = mono.iter().flat_map(|&s| [s, s]).collect::<Vec<_>>();
+
+        assert_eq!(process_audio(&stereo, 1.0), stereo);
+        assert_eq!(process_audio(&stereo, 0.5).len(), 368 * 2);
+        assert_eq!(process_audio(&stereo, 2.0).len(), 735 * 2 * 2);
+        assert!(process_audio(&[], 2.0).is_empty());
+    }
+
+    /// run the pacer with a scripted clock of `ticks` ticks with the given durations
+    /// repeated, returns the average frame rate
+    fn simulate_pacer(pacer: &mut FramePacer, durations: &[f64], ticks: usize) -> f64 {
+        let mut total_frames = 0u64;
+        let mut total_time = 0.;
+
+        for &dt in durations.iter().cycle().take(ticks) {
+            pacer.elapsed(Duration::from_secs_f64(dt));
+            total_frames += pacer.frames_to_run() as u64;
+            total_time += dt;
+        }
+
+        total_frames as f64 / total_time
+    }
+
+    #[test]
+    fn frame_pacer_average_rate() {
+        // 60Hz, 144Hz, and variable refresh rate with jitter
+        let displays: &[&[f64]] = &[
+            &[1. / 60.],
+            &[1. / 144.],
+            &[0.007, 0.013, 0.0165, 0.009, 0.021, 0.011],
+        ];
+
+        for frame_rate in [NTSC_FPS, PAL_FPS] {
+            for durations in displays {
+                let mut pacer = FramePacer::new(frame_rate);
+                let rate = simulate_pacer(&mut pacer, durations, 500_000);
+
+                let error = (rate - frame_rate).abs() / frame_rate;
+                assert!(error < 0.0001, "{} fps: got {} fps", frame_rate, rate);
+            }
+        }
+    }
+
+    #[test]
+    fn frame_pacer_clamp() {
+        let mut pacer = FramePacer::new(NTSC_FPS);
+        pacer.set_max_frames(3);
+
+        // a long stall shouldn't make us try to catch up
+        pacer.elapsed(Duration::from_secs(2));
+        assert_eq!(pacer.frames_to_run(), 3);
+        assert_eq!(pacer.frames_to_run(), 0);
+
+        pacer.elapsed(Duration::from_secs_f64(1. / NTSC_FPS));
+        assert_eq!(pacer.frames_to_run(), 1);
+    }
+
+    #[test]
+    fn frame_pacer_audio_feedback() {
+        let mut pacer = FramePacer::new(NTSC_FPS);
+        pacer.set_audio_feedback(Some(4096));
+
+        // starving audio, run faster
+        pacer.audio_queued(1024);
+        assert!(pacer.frame_rate() > NTSC_FPS);
+        assert!(pacer.frame_rate() <= NTSC_FPS * 1.005);
+
+        // too much audio, run slower
+        pacer.audio_queued(100_000);
+        assert!(pacer.frame_rate() < NTSC_FPS);
+        assert!(pacer.frame_rate() >= NTSC_FPS * 0.995);
+
+        pacer.audio_queued(4096);
+        assert_eq!(pacer.frame_rate(), NTSC_FPS);
+
+        pacer.set_audio_feedback(None);
+        pacer.audio_queued(0);
+        assert_eq!(pacer.frame_rate(), NTSC_FPS);
+    }
+
+    #[test]
+    fn frame_pacer_reset_after_pause() {
+        let mut pacer = FramePacer::new(NTSC_FPS);
+        pacer.set_audio_feedback(Some(4096));
+
+        for _ in 0..10 {
+            // while paused, the audio queue drains and the time keeps going
+            pacer.audio_queued(0);
+            pacer.elapsed(Duration::from_secs(2));
+
+            pacer.reset();
+            assert_eq!(pacer.frame_rate(), NTSC_FPS);
+            assert_eq!(pacer.frames_to_run(), 0);
+        }
+
+        // the audio feedback is still enabled
+        pacer.audio_queued(1024);
+        assert!(pacer.frame_rate() > NTSC_FPS);
+    }
+
+    const WHITE: [u8; 3] = [0xFF, 0xFF, 0xFF];
+    const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+
+    /// RGB image from rows of `#` (white) and `.` (black)
+    fn image(rows: &[&str]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|c| if c == '#' { WHITE } else { BLACK })
+            .collect()
+    }
+
+    #[test]
+    fn nearest_scaling() {
+        let src = image(&["#.", ".."]);
+
+        assert_eq!(scale_nearest(&src, 2, 2, 1), src);
+        assert_eq!(
+            scale_nearest(&src, 2, 2, 3),
+            image(&["###...", "###...", "###...", "......", "......", "......"])
+        );
+    }
+
+    /// The pixels of `scaled`, a `factor` scale of a `size` by `size` image, without
+    /// the blocks of the edge pixels of the source
+    fn inner_blocks(scaled: &[u8], size: usize, factor: usize) -> Vec<u8> {
+        let width = size * factor;
+        scaled
+            .chunks_exact(width * 3)
+            .skip(factor)
+            .take(width - 2 * factor)
+            .flat_map(|row| &row[factor * 3..(width - factor) * 3])
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn checkerboard_is_not_smoothed() {
+        // every neighbour of a pixel has the other color, so the inside of the image is
+        // the same as nearest, only the edges differ as the outside repeats the edge
+        let src = image(&["#.#.#.", ".#.#.#", "#.#.#.", ".#.#.#", "#.#.#.", ".#.#.#"]);
+
+        for (scaled, factor) in [(scale2x(&src, 6, 6), 2), (scale3x(&src, 6, 6), 3)] {
+            assert_eq!(
+                inner_blocks(&scaled, 6, factor),
+                inner_blocks(&scale_nearest(&src, 6, 6, factor), 6, factor)
+            );
+        }
+    }
+
+    #[test]
+    fn scale2x_diagonal() {
+        let src = image(&["#...", ".#..", "..#.", "...#"]);
+
+        // the black corners next to the line are filled, and the ends are cut,
+        // the pixels outside the image are the same as the edges
+        assert_eq!(
+            scale2x(&src, 4, 4),
+            image(&[
+                "##......", //
+                "#.#.....", //
+                ".###....", //
+                "..###...", //
+                "...###..", //
+                "....###.", //
+                ".....#.#", //
+                "......##", //
+            ])
+        );
+    }
+
+    #[test]
+    fn scale3x_diagonal() {
+        let src = image(&["#...", ".#..", "..#.", "...#"]);
+
+        assert_eq!(
+            scale3x(&src, 4, 4),
+            image(&[
+                "###.........", //
+                "##.#........", //
+                "#..#........", //
+                ".#####......", //
+                "...###......", //
+                "...####.....", //
+                ".....####...", //
+                "......###...", //
+                "......#####.", //
+                "........#..#", //
+                "........#.##", //
+                ".........###", //
+            ])
+        );
+    }
+
+    #[test]
+    fn scaled_size() {
+        let src = vec![0x80; 5 * 3 * 3];
+
+        assert_eq!(scale_nearest(&src, 5, 3, 4).len(), 20 * 12 * 3);
+        assert_eq!(scale2x(&src, 5, 3).len(), 10 * 6 * 3);
+        assert_eq!(scale3x(&src, 5, 3).len(), 15 * 9 * 3);
+    }
+
+    /// run with `cargo test --release --features frontend_misc -- --ignored scaler_benchmark`
+    #[test]
+    #[ignore]
+    fn scaler_benchmark() {
+        use crate::nes_display::{TV_BUFFER_SIZE, TV_HEIGHT, TV_WIDTH};
+        use std::time::Instant;
+
+        let frame = (0..TV_BUFFER_SIZE)
+            .map(|i| ((i / 3 / 16) % 4 * 60) as u8)
+            .collect::<Vec<_>>();
+
+        for (name, scale) in [
+            ("scale2x", scale2x as fn(&[u8], usize, usize) -> Vec<u8>),
+            ("scale3x", scale3x),
+        ] {
+            const RUNS: u32 = 100;
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                std::hint::black_box(scale(&frame, TV_WIDTH, TV_HEIGHT));
+            }
+            let average = start.elapsed() / RUNS;
+            println!("{}: {:?} per frame", name, average);
+            assert!(average < Duration::from_millis(1));
+        }
+    }
+}
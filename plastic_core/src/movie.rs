@@ -0,0 +1,253 @@
+//! Recording the controller inputs of a play session, and replaying them to reach
+//! a specific frame quickly.
+//!
+//! A [`Movie`] is recorded with [`NES::record_movie_frame`], it stores the input of
+//! every frame and embeds snapshots of the emulator state every
+//! [`Movie::snapshot_interval`] frames. [`NES::play_movie_from_frame`] then loads the
+//! nearest snapshot before the target frame and replays only the remaining inputs.
+//!
+//! Emulation is deterministic, so the result is the same as replaying the whole
+//! movie from the start, which is useful to debug issues that happen late in a game.
+//!
+//! Only the controller of player 1 is recorded, the state of player 2 is saved in the
+//! snapshots but not changed by the movie, so games that read player 2 while it is
+//! being pressed will not replay the same.
+
+use crate::common::save_state::{Savable, SaveError};
+use crate::nes_audio::AudioSampling;
+use crate::{NESKey, NES};
+use std::io::{Read, Write};
+
+/// A state embedded in a [`Movie`]
+struct MovieSnapshot {
+    /// the state is before running this frame
+    frame: u64,
+    /// the fraction of the CPU cycles carried over between frames, it is not
+    /// in the state but affects where the next frame ends
+    frame_counter: f32,
+    state: Vec<u8>,
+}
+
+/// Controller inputs recorded frame by frame with snapshots of the emulator state,
+/// see the [module documentation](self).
+pub struct Movie {
+    /// the buttons of player 1 for each frame, in the order of [`NESKey`]
+    inputs: Vec<u8>,
+    /// embed a snapshot every this many frames, `0` only embeds the first one
+    snapshot_interval: u32,
+    /// sorted by frame
+    snapshots: Vec<MovieSnapshot>,
+}
+
+impl Movie {
+    /// Create an empty movie, recording will embed a snapshot every `snapshot_interval`
+    /// frames, or only at the start of the movie if it is `0`.
+    pub fn new(snapshot_interval: u32) -> Self {
+        Self {
+            inputs: Vec::new(),
+            snapshot_interval,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// The number of frames recorded
+    pub fn len(&self) -> u64 {
+        self.inputs.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// The buttons of player 1 for each frame, as bits in the order of [`NESKey`]
+    pub fn inputs(&self) -> &[u8] {
+        &self.inputs
+    }
+
+    /// The number of frames between the embedded snapshots, `0` if only the
+    /// start of the movie has a snapshot
+    pub fn snapshot_interval(&self) -> u32 {
+        self.snapshot_interval
+    }
+
+    /// The frames that have an embedded snapshot, the state before running the frame
+    pub fn snapshot_frames(&self) -> impl Iterator<Item = u64> + '_ {
+        self.snapshots.iter().map(|snapshot| snapshot.frame)
+    }
+
+    /// The frame of the snapshot that [`NES::play_movie_from_frame`] starts from to
+    /// reach `frame`, `None` if the movie is shorter than `frame`.
+    ///
+    /// The snapshot is before `frame` and not at it, so at least one frame is run and
+    /// the pixel buffer is of the target frame, unless `frame` is `0`.
+    pub fn seek_index(&self, frame: u64) -> Option<u64> {
+        if frame > self.len() {
+            return None;
+        }
+
+        self.snapshot_frames()
+            .take_while(|&snapshot_frame| snapshot_frame < frame || snapshot_frame == 0)
+            .last()
+    }
+
+    /// Write the movie with its embedded snapshots, to be read back with [`Movie::load_from`]
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), SaveError> {
+        let mut data = Vec::new();
+        self.save(&mut data)?;
+        writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    /// Read a movie written by [`Movie::save_to`]
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self, SaveError> {
+        let mut movie = Self::new(0);
+        movie.load(&mut reader)?;
+
+        Ok(movie)
+    }
+
+    fn should_embed_snapshot(&self) -> bool {
+        let frame = self.len();
+
+        frame == 0
+            || (self.snapshot_interval != 0 && frame.is_multiple_of(self.snapshot_interval as u64))
+    }
+}
+
+/// The embedded snapshots are saved as well, a movie always has a snapshot at
+/// its start, as it may not be recorded from power-up.
+impl Savable for Movie {
+    fn save(&self, writer: &mut dyn Write) -> Result<(), SaveError> {
+        writer.write_all(&self.snapshot_interval.to_le_bytes())?;
+        writer.write_all(&self.len().to_le_bytes())?;
+        writer.write_all(&self.inputs)?;
+
+        writer.write_all(&(self.snapshots.len() as u64).to_le_bytes())?;
+        for snapshot in &self.snapshots {
+            writer.write_all(&snapshot.frame.to_le_bytes())?;
+            writer.write_all(&snapshot.frame_counter.to_le_bytes())?;
+            writer.write_all(&(snapshot.state.len() as u64).to_le_bytes())?;
+            writer.write_all(&snapshot.state)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
+        fn read_u64(reader: &mut dyn Read) -> Result<u64, SaveError> {
+            let mut data = [0; 8];
+            reader.read_exact(&mut data)?;
+            Ok(u64::from_le_bytes(data))
+        }
+
+        fn read_u32(reader: &mut dyn Read) -> Result<u32, SaveError> {
+            let mut data = [0; 4];
+            reader.read_exact(&mut data)?;
+            Ok(u32::from_le_bytes(data))
+        }
+
+        let snapshot_interval = read_u32(reader)?;
+
+        let len = read_u64(reader)?;
+        let mut inputs = Vec::new();
+        reader.take(len).read_to_end(&mut inputs)?;
+        if inputs.len() as u64 != len {
+            return Err(SaveError::SerializationError);
+        }
+
+        let snapshots_count = read_u64(reader)?;
+        let mut snapshots = Vec::new();
+        for _ in 0..snapshots_count {
+            let frame = read_u64(reader)?;
+            let frame_counter = f32::from_bits(read_u32(reader)?);
+            let state_len = read_u64(reader)?;
+            let mut state = Vec::new();
+            reader.take(state_len).read_to_end(&mut state)?;
+
+            let is_sorted = snapshots
+                .last()
+                .map_or(frame == 0, |last: &MovieSnapshot| frame > last.frame);
+            if state.len() as u64 != state_len || !is_sorted || frame > len {
+                return Err(SaveError::SerializationError);
+            }
+            snapshots.push(MovieSnapshot {
+                frame,
+                frame_counter,
+                state,
+            });
+        }
+        if snapshots.is_empty() && len != 0 {
+            return Err(SaveError::SerializationError);
+        }
+
+        self.snapshot_interval = snapshot_interval;
+        self.inputs = inputs;
+        self.snapshots = snapshots;
+
+        Ok(())
+    }
+}
+
+impl NES {
+    /// Run one frame with the buttons of player 1 set to `input`, as bits in the order of
+    /// [`NESKey`], and record it to `movie`. Player 2 is not recorded, see the
+    /// [module documentation](crate::movie).
+    ///
+    /// The state before the frame is embedded in the movie every [`Movie::snapshot_interval`]
+    /// frames, and for the first frame.
+    ///
+    /// This switches the audio to [`AudioSampling::Exact`], so the samples of the
+    /// movie are reproducible.
+    pub fn record_movie_frame(&mut self, movie: &mut Movie, input: u8) -> Result<(), SaveError> {
+        self.set_audio_sampling(AudioSampling::Exact);
+        if movie.should_embed_snapshot() {
+            let mut state = Vec::new();
+            self.save_state(&mut state)?;
+            movie.snapshots.push(MovieSnapshot {
+                frame: movie.len(),
+                frame_counter: self.frame_counter,
+                state,
+            });
+        }
+
+        movie.inputs.push(input);
+        self.run_movie_frame(input);
+
+        Ok(())
+    }
+
+    /// Load the nearest snapshot of `movie` before `frame`, see [`Movie::seek_index`],
+    /// and replay the remaining inputs, so the emulator ends at the same state as
+    /// after running the first `frame` frames of the movie.
+    ///
+    /// Fails without changing the emulator if the movie is shorter than `frame`.
+    /// Like recording, the audio is switched to [`AudioSampling::Exact`].
+    pub fn play_movie_from_frame(&mut self, movie: &Movie, frame: u64) -> Result<(), SaveError> {
+        let snapshot_frame = movie.seek_index(frame).ok_or(SaveError::MovieTooShort {
+            frame,
+            length: movie.len(),
+        })?;
+        let snapshot = movie
+            .snapshots
+            .iter()
+            .find(|snapshot| snapshot.frame == snapshot_frame)
+            .expect("the seek index is a snapshot frame");
+
+        self.load_state(snapshot.state.as_slice())?;
+        self.frame_counter = snapshot.frame_counter;
+        self.set_audio_sampling(AudioSampling::Exact);
+        for &input in &movie.inputs[snapshot_frame as usize..frame as usize] {
+            self.run_movie_frame(input);
+        }
+
+        Ok(())
+    }
+
+    fn run_movie_frame(&mut self, input: u8) {
+        for key in NESKey::ALL {
+            self.set_controller_state(key, input & key as u8 != 0);
+        }
+        self.clock_for_frame();
+    }
+}
@@ -1,20 +1,36 @@
-use crate::apu2a03::APU2A03;
-use crate::cartridge::{Cartridge, CartridgeError};
+use crate::apu2a03::{
+    ApuSnapshot, ApuWrite, AudioRing, AudioSampling, ChannelTap, DmcSampleEvent, APU2A03,
+    DEFAULT_AUDIO_RING_CAPACITY, SAMPLE_RATE,
+};
+use crate::cartridge::{
+    apply_patch, Cartridge, CartridgeError, ExpansionDevice, RomData, TimingMode,
+};
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, take_json_field, to_json_value, JsonSavable};
 use crate::common::{
     interconnection::*,
-    save_state::{Savable, SaveError},
-    Bus, Device, MirroringProvider,
+    save_state::{Savable, SaveError, StateHeader},
+    Bus, Device, EmulatorConfig, MirroringProvider, Region, SyncBaseline, CYCLES_PER_FRAME_NTSC,
 };
-use crate::controller::Controller;
-use crate::cpu6502::{CPUBusTrait, CPURunState, CPU6502};
-use crate::display::TV;
-use crate::ppu2c02::{Palette, VRam, PPU2C02};
+use crate::controller::{AnalogToDpad, Controller, ExpansionPortDevice};
+use crate::cpu6502::{CPUBusTrait, CPURunState, CpuState, CPU6502};
+use crate::diagnostics::{Diagnostic, HomebrewChecks};
+use crate::display::{draw_idle_screen, FrameDelta, PaletteGenerator, TV};
+use crate::memory_map::{self, MemoryRegion};
+#[cfg(feature = "debug")]
+use crate::ppu2c02::PpuSnapshot;
+use crate::ppu2c02::{NametableSource, NametableView, Palette, PpuBackend, VRam, PPU2C02};
+#[cfg(feature = "profiling")]
+use crate::profiler::{Component, Profiler};
 use crate::NESKey;
 use std::cell::Cell;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::collections::VecDeque;
 use std::io::Read;
-use std::path::Path;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
 
 struct PPUBus {
     cartridge: Rc<RefCell<dyn Bus>>,
@@ -39,7 +55,10 @@ impl Bus for PPUBus {
     fn read(&self, address: u16, device: Device) -> u8 {
         match address {
             0x0000..=0x1FFF => self.cartridge.borrow().read(address, device),
-            0x2000..=0x3EFF => self.vram.read(address & 0x2FFF, device),
+            0x2000..=0x3EFF => match self.vram.try_read(address & 0x2FFF) {
+                Some(data) => data,
+                None => self.cartridge.borrow().read(address & 0x2FFF, device),
+            },
             0x3F00..=0x3FFF => self.palettes.read(address, device),
             // mirror
             0x4000..=0xFFFF => self.read(address & 0x3FFF, device),
@@ -48,6 +67,10 @@ impl Bus for PPUBus {
     fn write(&mut self, address: u16, data: u8, device: Device) {
         match address {
             0x0000..=0x1FFF => self.cartridge.borrow_mut().write(address, data, device),
+            0x2000..=0x3EFF if self.vram.is_on_cartridge(address) => self
+                .cartridge
+                .borrow_mut()
+                .write(address & 0x2FFF, data, device),
             0x2000..=0x3EFF => self.vram.write(address & 0x2FFF, data, device),
             0x3F00..=0x3FFF => self.palettes.write(address, data, device),
             // mirror
@@ -57,14 +80,14 @@ impl Bus for PPUBus {
 }
 
 impl Savable for PPUBus {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         self.vram.save(writer)?;
         self.palettes.save(writer)?;
 
         Ok(())
     }
 
-    fn load<R: std::io::Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
         self.vram.load(reader)?;
         self.palettes.load(reader)?;
 
@@ -72,13 +95,39 @@ impl Savable for PPUBus {
     }
 }
 
+#[cfg(feature = "state-json")]
+impl JsonSavable for PPUBus {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "vram": self.vram.to_json(),
+            "palettes": self.palettes.to_json(),
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        self.vram.load_json(take_json_field(&mut value, "vram")?)?;
+        self.palettes
+            .load_json(take_json_field(&mut value, "palettes")?)
+    }
+}
+
 struct CPUBus {
     ram: [u8; 0x800],
     cartridge: Rc<RefCell<Cartridge>>,
     ppu: PPU2C02<PPUBus>,
     apu: APU2A03,
     contoller: Controller,
-    irq_pin_change_requested: Cell<bool>,
+    /// the controller of player 2, read from `$4017`, strobed with player 1 by `$4016`
+    contoller2: Controller,
+    expansion_port: Option<Box<dyn ExpansionPortDevice>>,
+    /// the last value of the expansion port output lines written to `$4016`
+    expansion_strobe: u8,
+    /// number of reads from `$4015`, used to detect games waiting on APU status bits
+    apu_status_reads: Cell<u32>,
+    homebrew_checks: HomebrewChecks,
+    /// the last value on the CPU data bus, returned when reading addresses that nothing
+    /// drives (open bus). Not saved, the next instruction fetch sets it again
+    open_bus: Cell<u8>,
 }
 
 impl CPUBus {
@@ -94,7 +143,12 @@ impl CPUBus {
             ppu,
             apu,
             contoller,
-            irq_pin_change_requested: Cell::new(false),
+            contoller2: Controller::new(),
+            expansion_port: None,
+            expansion_strobe: 0,
+            apu_status_reads: Cell::new(0),
+            homebrew_checks: HomebrewChecks::default(),
+            open_bus: Cell::new(0),
         }
     }
 
@@ -104,56 +158,153 @@ impl CPUBus {
 }
 
 impl CPUBusTrait for CPUBus {
+    // The registers in `$4000-$401F` are not mirrored, unlike the PPU registers
     fn read(&self, address: u16) -> u8 {
-        match address {
-            0x0000..=0x1FFF => self.ram[(address & 0x7FF) as usize],
+        let data = match address {
+            0x0000..=0x1FFF => {
+                self.homebrew_checks.ram_read(address & 0x7FF);
+                self.ram[(address & 0x7FF) as usize]
+            }
             0x2000..=0x3FFF => self.ppu.read(0x2000 | (address & 0x7), Device::Cpu),
-            0x4000..=0x4013 => self.apu.read(address, Device::Cpu),
-            0x4014 => self.ppu.read(address, Device::Cpu),
-            0x4015 => self.apu.read(address, Device::Cpu),
-            0x4016 => self.contoller.read(address, Device::Cpu),
-            0x4017 => self.apu.read(address, Device::Cpu),
-            0x4018..=0x401F => {
-                // unused CPU test mode registers
-                0
+            // write-only APU registers and OAM DMA
+            0x4000..=0x4014 => self.open_bus.get(),
+            0x4015 => {
+                self.apu_status_reads.set(self.apu_status_reads.get() + 1);
+                // the APU is inside the CPU, so this read doesn't drive the
+                // external data bus
+                return self.apu.read(address, Device::Cpu);
             }
+            // the controller ports only drive the low 5 bits
+            0x4016 => self.contoller.read(address, Device::Cpu) | self.open_bus.get() & 0xE0,
+            0x4017 => self.contoller2.read(address, Device::Cpu) | self.open_bus.get() & 0xE0,
+            // unused CPU test mode registers
+            0x4018..=0x401F => self.open_bus.get(),
             0x4020..=0xFFFF => self.cartridge.borrow().read(address, Device::Cpu),
-        }
+        };
+        self.open_bus.set(data);
+
+        data
     }
 
     fn write(&mut self, address: u16, data: u8) {
+        self.open_bus.set(data);
+
         match address {
-            0x0000..=0x1FFF => self.ram[(address & 0x7FF) as usize] = data,
+            0x0000..=0x1FFF => {
+                self.homebrew_checks.ram_write(address & 0x7FF);
+                self.ram[(address & 0x7FF) as usize] = data
+            }
             0x2000..=0x3FFF => self.ppu.write(0x2000 | (address & 0x7), data, Device::Cpu),
             0x4000..=0x4013 => self.apu.write(address, data, Device::Cpu),
             0x4014 => self.ppu.write(address, data, Device::Cpu),
             0x4015 => self.apu.write(address, data, Device::Cpu),
-            0x4016 => self.contoller.write(address, data, Device::Cpu),
+            0x4016 => {
+                // bits 0-2 are the output lines of the expansion port,
+                // the controllers only use bit 0
+                self.expansion_strobe = data & 0x07;
+                if let Some(device) = self.expansion_port.as_mut() {
+                    device.write_strobe(self.expansion_strobe);
+                }
+                self.contoller.write(address, data, Device::Cpu);
+                self.contoller2.write(address, data, Device::Cpu)
+            }
             0x4017 => self.apu.write(address, data, Device::Cpu),
             0x4018..=0x401F => {
-                // unused CPU test mode registers
+                // unused CPU test mode registers, disabled on retail consoles
+                self.homebrew_checks.test_register_write(address, data);
+            }
+            0x4020..=0xFFFF => {
+                if self.homebrew_checks.is_enabled()
+                    && self.cartridge.borrow().is_rom_write(address)
+                {
+                    self.homebrew_checks.rom_write(address, data);
+                }
+                self.cartridge
+                    .borrow_mut()
+                    .write(address, data, Device::Cpu)
             }
-            0x4020..=0xFFFF => self
-                .cartridge
-                .borrow_mut()
-                .write(address, data, Device::Cpu),
         }
     }
 
     fn reset(&mut self) {
         self.ram = [0; 0x800];
+        self.homebrew_checks.clear_ram();
+    }
+
+    fn dummy_read(&self, address: u16) -> u8 {
+        match address {
+            // not a read of the program, so not checked
+            0x0000..=0x1FFF => {
+                let data = self.ram[(address & 0x7FF) as usize];
+                self.open_bus.set(data);
+                data
+            }
+            _ => self.read(address),
+        }
+    }
+
+    fn set_instruction_pc(&mut self, pc: u16) {
+        self.homebrew_checks.set_pc(pc);
     }
 }
 
 impl Savable for CPUBus {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         writer.write_all(&self.ram)?;
+        self.contoller.save(writer)?;
+        self.contoller2.save(writer)?;
+        writer.write_all(&[self.expansion_strobe])?;
 
         Ok(())
     }
 
-    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn Read) -> Result<(), SaveError> {
         reader.read_exact(&mut self.ram)?;
+        self.homebrew_checks.mark_all_ram();
+        self.contoller.load(reader)?;
+        self.contoller2.load(reader)?;
+        let mut expansion_strobe = [0];
+        reader.read_exact(&mut expansion_strobe)?;
+        self.expansion_strobe = expansion_strobe[0] & 0x07;
+        // the device may depend on the level of the lines and not only the changes
+        if let Some(device) = self.expansion_port.as_mut() {
+            device.write_strobe(self.expansion_strobe);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "state-json")]
+impl JsonSavable for CPUBus {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ram": to_json_value(&self.ram.as_slice()),
+            "controller": self.contoller.to_json(),
+            "controller2": self.contoller2.to_json(),
+            "expansion_strobe": self.expansion_strobe,
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        let ram: Vec<u8> = from_json_value(take_json_field(&mut value, "ram")?)?;
+        if ram.len() != self.ram.len() {
+            return Err(SaveError::SerializationError);
+        }
+        self.ram.copy_from_slice(&ram);
+        self.homebrew_checks.mark_all_ram();
+
+        self.contoller
+            .load_json(take_json_field(&mut value, "controller")?)?;
+        self.contoller2
+            .load_json(take_json_field(&mut value, "controller2")?)?;
+
+        let expansion_strobe: u8 =
+            from_json_value(take_json_field(&mut value, "expansion_strobe")?)?;
+        self.expansion_strobe = expansion_strobe & 0x07;
+        if let Some(device) = self.expansion_port.as_mut() {
+            device.write_strobe(self.expansion_strobe);
+        }
 
         Ok(())
     }
@@ -197,29 +348,267 @@ impl APUCPUConnection for CPUBus {
 
 impl CPUIrqProvider for CPUBus {
     fn is_irq_change_requested(&self) -> bool {
-        let result =
-            self.apu.is_irq_change_requested() || self.cartridge.borrow().is_irq_change_requested();
-        self.irq_pin_change_requested.set(result);
-        result
+        self.cartridge.borrow().is_irq_change_requested()
     }
 
     fn irq_pin_state(&self) -> bool {
-        if self.irq_pin_change_requested.get() {
-            let mut result = self.apu.irq_pin_state();
-            if self.cartridge.borrow().is_irq_change_requested() {
-                result = result || self.cartridge.borrow().irq_pin_state();
-            }
-            result
-        } else {
-            false
-        }
+        self.cartridge.borrow().irq_pin_state()
     }
 
     fn clear_irq_request_pin(&mut self) {
-        *self.irq_pin_change_requested.get_mut() = false;
         self.cartridge.borrow_mut().clear_irq_request_pin();
-        self.apu.clear_irq_request_pin();
     }
+
+    fn irq_level(&self) -> bool {
+        self.apu.irq_level()
+    }
+}
+
+/// Statistics collected while running the last frame using [`NES::clock_for_frame`].
+///
+/// Can be used by frontends to display the emulation load, or to detect that
+/// the game is idle (for example waiting in a loading screen).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of CPU cycles executed in the frame (including DMA cycles)
+    pub cpu_cycles: u32,
+    /// Number of instructions executed in the frame
+    pub instructions: u32,
+    /// Number of CPU cycles spent in OAM DMA transfers
+    pub dma_cycles: u32,
+    /// `true` if the last instruction executed in the frame was an infinite loop,
+    /// i.e. a jump or branch to itself
+    pub ended_in_infinite_loop: bool,
+    /// Number of distinct `PC` values seen after executing instructions in the frame.
+    ///
+    /// This is only tracked if enabled with [`NES::set_distinct_pc_tracking`], otherwise it is `None`.
+    pub distinct_pcs: Option<u32>,
+    /// The CPU/PPU alignment applied on the last reset (or loaded from a state), which is
+    /// not the one set with [`NES::set_cpu_ppu_alignment`] until the next reset
+    pub cpu_ppu_alignment: u8,
+    /// `true` if background or sprites rendering was enabled at any point during the
+    /// visible scanlines of the last rendered frame, games disable it while loading or in transitions
+    pub rendering_was_enabled: bool,
+    /// `true` if all pixels of the last rendered frame were the backdrop color
+    /// (usually black), for example when rendering is disabled
+    pub frame_is_black: bool,
+    /// Number of times the game latched the controller in the frame, games that
+    /// debounce the input read it more than once
+    pub controller_polls: u32,
+}
+
+/// The progress of [`NES::clock_with_budget`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetResult {
+    /// `true` if the frame ended in this call, the pixel buffer has the new frame
+    /// and [`NES::frame_stats`] is updated
+    pub frame_complete: bool,
+    /// Number of CPU cycles run in this call
+    pub cycles_run: u32,
+    /// Number of CPU cycles run in the current frame so far, including the
+    /// previous calls, or in the whole frame if it is complete
+    pub frame_cycles: u32,
+}
+
+/// Writes to the battery backed save RAM (SRAM), returned by [`NES::sram_write_activity`].
+///
+/// Can be used by frontends to show a saving indicator, or to copy the save
+/// somewhere else a while after the game stops writing to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SramActivity {
+    /// Number of writes to SRAM in the last frame run with [`NES::clock_for_frame`]
+    pub writes_last_frame: u32,
+    /// The frame number of the last frame with SRAM writes, counting from `1` for the
+    /// first frame run, `0` if there were no writes
+    pub last_write_frame: u64,
+    /// `true` if SRAM was written since the last [`NES::clear_sram_dirty`]
+    pub dirty: bool,
+}
+
+/// A snapshot of the emulator state kept in memory, created with [`NES::snapshot`].
+///
+/// Useful for quickly going back to a specific point, for example to reset episodes.
+#[derive(Clone)]
+pub struct StateSnapshot {
+    data: Vec<u8>,
+}
+
+impl StateSnapshot {
+    /// The serialized state, in the same format as [`NES::save_state`]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+enum RomSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+enum PatchSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+/// Builder for creating a [`NES`] from a ROM file or bytes, with an optional
+/// IPS or BPS patch applied to the ROM before loading it.
+///
+/// ```no_run
+/// use plastic_core::NESBuilder;
+///
+/// let nes = NESBuilder::from_file("path/to/rom-file.nes")
+///     .patch_file("path/to/hack.bps")
+///     .build()
+///     .unwrap();
+/// ```
+pub struct NESBuilder {
+    rom: RomSource,
+    patch: Option<PatchSource>,
+    ppu_backend: PpuBackend,
+}
+
+impl NESBuilder {
+    /// Load the ROM from a `.nes` file, like [`NES::new`]
+    pub fn from_file<P: AsRef<Path>>(filename: P) -> Self {
+        Self {
+            rom: RomSource::File(filename.as_ref().to_path_buf()),
+            patch: None,
+            ppu_backend: PpuBackend::DotAccurate,
+        }
+    }
+
+    /// Load the ROM from the content of a file in memory, like [`NES::new_from_bytes`]
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            rom: RomSource::Bytes(data.to_vec()),
+            patch: None,
+            ppu_backend: PpuBackend::DotAccurate,
+        }
+    }
+
+    /// Apply the IPS or BPS patch file to the ROM, like [`NES::new_with_patch`]
+    pub fn patch_file<P: AsRef<Path>>(mut self, patch: P) -> Self {
+        self.patch = Some(PatchSource::File(patch.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Apply the IPS or BPS patch content to the ROM
+    pub fn patch_bytes(mut self, patch: &[u8]) -> Self {
+        self.patch = Some(PatchSource::Bytes(patch.to_vec()));
+        self
+    }
+
+    /// Render with the PPU `backend` from power-up, [`NES::set_ppu_backend`] only
+    /// switches at the end of the current frame
+    pub fn ppu_backend(mut self, backend: PpuBackend) -> Self {
+        self.ppu_backend = backend;
+        self
+    }
+
+    /// Create the [`NES`], fails if the ROM or the patch can't be read, or if
+    /// the patch can't be applied to the ROM.
+    pub fn build(self) -> Result<NES, CartridgeError> {
+        let patch = match self.patch {
+            Some(PatchSource::File(path)) => Some(std::fs::read(path)?),
+            Some(PatchSource::Bytes(data)) => Some(data),
+            None => None,
+        };
+
+        let cartridge = match (self.rom, patch) {
+            (RomSource::File(path), Some(patch)) => {
+                Cartridge::from_file_with_patch_bytes(path, &patch)?
+            }
+            (RomSource::File(path), None) => Cartridge::from_file(path)?,
+            (RomSource::Bytes(data), Some(patch)) => {
+                Cartridge::from_bytes(&apply_patch(&data, &patch)?)?
+            }
+            (RomSource::Bytes(data), None) => Cartridge::from_bytes(&data)?,
+        };
+
+        let mut nes = NES::create_nes(cartridge);
+        nes.cpu.bus_mut().ppu.set_initial_backend(self.ppu_backend);
+
+        Ok(nes)
+    }
+}
+
+/// A bitset with a bit for every possible `PC` value, used to count distinct
+/// `PC` values in a frame.
+struct PcTracker {
+    bits: Box<[u64; 0x10000 / 64]>,
+    count: u32,
+}
+
+impl PcTracker {
+    fn new() -> Self {
+        Self {
+            bits: Box::new([0; 0x10000 / 64]),
+            count: 0,
+        }
+    }
+
+    fn insert(&mut self, pc: u16) {
+        let index = (pc / 64) as usize;
+        let bit = 1 << (pc % 64);
+
+        if self.bits[index] & bit == 0 {
+            self.bits[index] |= bit;
+            self.count += 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits.fill(0);
+        self.count = 0;
+    }
+}
+
+/// The frames and audio presented with a delay, see [`NES::set_output_delay_frames`]
+#[derive(Default)]
+struct OutputDelay {
+    frames: u8,
+    /// the emulated frames not presented yet with their audio, the oldest first
+    pending: VecDeque<(Vec<u8>, Vec<f32>)>,
+    /// the presented frame
+    pixels: Vec<u8>,
+    /// the audio of the presented frames not taken yet
+    audio: Vec<f32>,
+}
+
+impl OutputDelay {
+    fn push_frame(&mut self, pixels: &[u8], audio: Vec<f32>) {
+        self.pending.push_back((pixels.to_vec(), audio));
+
+        while self.pending.len() > self.frames as usize {
+            let (pixels, audio) = self.pending.pop_front().unwrap();
+            self.pixels = pixels;
+            self.audio.extend(audio);
+        }
+    }
+}
+
+/// Settings changed with the API, these have priority over game overrides
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(not(feature = "overrides"), allow(dead_code))]
+pub(crate) struct ExplicitSettings {
+    pub region: bool,
+    pub expansion_device: bool,
+    pub bus_conflicts: bool,
+    pub submapper: bool,
+}
+
+/// Where the current region of the console comes from, see [`NES::region_source`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RegionSource {
+    /// Nothing selected the region, [`Region::Ntsc`]
+    #[default]
+    Default,
+    /// The timing of the ROM header, see [`NES::timing_mode`]
+    Header,
+    /// A game entry of [`NES::load_overrides`]
+    Overrides,
+    /// [`NES::set_region`]
+    Api,
 }
 
 /// The main `NES` emulator struct, containing all components and what is actually doing the emulation.
@@ -251,7 +640,46 @@ pub struct NES {
     /// CPU and containing all components through the `CPUBus`.
     cpu: CPU6502<CPUBus>,
 
-    frame_counter: f32,
+    /// the CPU cycles left to run in the current frame, the fraction carries over
+    /// to the next frame, not saved in the states
+    pub(crate) frame_counter: f32,
+
+    frame_stats: FrameStats,
+    /// the statistics of the frame started with `clock_with_budget` that didn't end yet
+    partial_frame: Option<FrameStats>,
+    pc_tracker: Option<PcTracker>,
+
+    /// the ring the APU writes the samples to, kept when the APU is recreated
+    audio_ring: Arc<AudioRing>,
+    /// applied to the APU when it is recreated
+    audio_sampling: AudioSampling,
+    /// applied to the APU when it is recreated
+    audio_sample_rate: u32,
+    /// draw the idle screen when the cartridge is empty
+    idle_screen: bool,
+    /// number of idle screen frames drawn, for the animation
+    idle_frame: u32,
+    output_delay: OutputDelay,
+
+    /// number of frames run with `clock_for_frame`
+    frame_number: u64,
+    sram_activity: SramActivity,
+
+    /// number of PPU dots to run before the CPU on power-up, `0..=2`
+    cpu_ppu_alignment: u8,
+    /// the `cpu_ppu_alignment` applied on the last reset, or loaded from a state
+    applied_cpu_ppu_alignment: u8,
+    /// fail loading states saved with a different config instead of applying it
+    strict_state_config: bool,
+    pub(crate) region_source: RegionSource,
+    /// guess the region of iNES 1.0 ROMs from byte 9 and the file name
+    region_guessing: bool,
+    pub(crate) explicit_settings: ExplicitSettings,
+
+    #[cfg(feature = "rl")]
+    pub(crate) rl_config: crate::rl::RlConfig,
+    #[cfg(feature = "profiling")]
+    pub(crate) profiler: Profiler,
 }
 
 impl NES {
@@ -261,11 +689,44 @@ impl NES {
         Ok(Self::create_nes(cartridge))
     }
 
+    /// Creates a new NES instance from a given file path, with an IPS or BPS patch applied to it.
+    ///
+    /// The battery backed SRAM is named after the patched ROM, see
+    /// [`NESBuilder`] for applying patches to in memory ROMs.
+    pub fn new_with_patch<P: AsRef<Path>, Q: AsRef<Path>>(
+        filename: P,
+        patch: Q,
+    ) -> Result<Self, CartridgeError> {
+        let cartridge = Cartridge::from_file_with_patch(filename, patch)?;
+        Ok(Self::create_nes(cartridge))
+    }
+
+    /// Creates a new NES instance from the content of a ROM file in memory.
+    ///
+    /// Since there is no file path, battery backed SRAM will not be loaded or saved to disk.
+    pub fn new_from_bytes(data: &[u8]) -> Result<Self, CartridgeError> {
+        let cartridge = Cartridge::from_bytes(data)?;
+        Ok(Self::create_nes(cartridge))
+    }
+
+    /// Creates a new NES instance sharing the PRG and CHR ROM of `rom` with the other
+    /// instances created from it, for running many instances of the same game.
+    ///
+    /// Like [`NES::new_from_bytes`], battery backed SRAM is not loaded or saved to disk.
+    /// `NES` is not `Send`, so to run instances on other threads, send the `Arc` and
+    /// create the instances there.
+    pub fn from_shared_rom(rom: Arc<RomData>) -> Self {
+        let cartridge = Cartridge::from_rom_data(&rom, None);
+        Self::create_nes(cartridge)
+    }
+
     /// Creates a new NES instance without loading a cartridge from a file.
     ///
     /// Returns a new NES instance with an empty cartridge.
     ///
-    /// Do note that running [`NES::clock_for_frame`] or [`NES::clock`] will not do anything if the cartridge is empty.
+    /// Do note that running [`NES::clock_for_frame`] or [`NES::clock`] will not run anything if the
+    /// cartridge is empty, [`NES::clock_for_frame`] only counts the frame and draws the idle screen,
+    /// see [`NES::set_idle_screen`], so it is cheap to call it unconditionally.
     pub fn new_without_file() -> Self {
         let cartridge = Cartridge::new_without_file();
         Self::create_nes(cartridge)
@@ -279,7 +740,8 @@ impl NES {
 
         let ppu = PPU2C02::new(ppubus, tv);
 
-        let apu = APU2A03::new();
+        let audio_ring = Arc::new(AudioRing::new(DEFAULT_AUDIO_RING_CAPACITY));
+        let apu = APU2A03::new(Region::default(), audio_ring.clone());
 
         let ctrl = Controller::new();
 
@@ -289,15 +751,42 @@ impl NES {
 
         cpu.reset();
 
-        Self {
+        let mut nes = Self {
             cartridge,
             cpu,
             frame_counter: 0.,
-        }
+            frame_stats: FrameStats::default(),
+            partial_frame: None,
+            pc_tracker: None,
+            audio_ring,
+            audio_sampling: AudioSampling::default(),
+            audio_sample_rate: SAMPLE_RATE,
+            idle_screen: true,
+            idle_frame: 0,
+            output_delay: OutputDelay::default(),
+            frame_number: 0,
+            sram_activity: SramActivity::default(),
+            cpu_ppu_alignment: 0,
+            applied_cpu_ppu_alignment: 0,
+            strict_state_config: false,
+            region_source: RegionSource::Default,
+            region_guessing: false,
+            explicit_settings: ExplicitSettings::default(),
+
+            #[cfg(feature = "rl")]
+            rl_config: crate::rl::RlConfig::default(),
+            #[cfg(feature = "profiling")]
+            profiler: Profiler::default(),
+        };
+        nes.apply_header_region();
+
+        nes
     }
 
     /// Reset the NES emulator using the same cartridge loaded already.
     pub fn reset(&mut self) {
+        self.drop_partial_frame();
+        self.output_delay.pending.clear();
         self.cpu.reset();
         self.cpu.reset_bus();
 
@@ -305,131 +794,1566 @@ impl NES {
 
         self.cpu.bus_mut().ppu.reset(ppubus);
 
-        self.cpu.bus_mut().apu = APU2A03::new();
+        self.cpu.bus_mut().apu = self.new_apu(self.region());
+
+        // the CPU reset sequence takes 7 cycles before fetching the first instruction
+        // (in `cycles_to_wait`), and the PPU is clocked normally during them, so only the
+        // sub-cycle alignment is applied here
+        let ppu = &mut self.cpu.bus_mut().ppu;
+        for _ in 0..self.cpu_ppu_alignment {
+            ppu.clock();
+        }
+        self.applied_cpu_ppu_alignment = self.cpu_ppu_alignment;
     }
 
-    /// Run the NES emulator for one video frame, which is equal to `29780` CPU cycles.
+    /// Set the CPU/PPU clock alignment on power-up, i.e. which of the 3 PPU dots in a
+    /// CPU cycle the CPU starts at. This differs between power-ups on real hardware,
+    /// and some timing sensitive tests and games behave differently based on it.
     ///
-    /// This is the main function to run the emulator, call this once, and then render and play audio.
-    pub fn clock_for_frame(&mut self) {
-        if self.cartridge.borrow().is_empty() {
-            return;
-        }
+    /// `phase` is the number of PPU dots to run before the CPU starts, `0` by default.
+    /// The alignment is applied on the next [`NES::reset`].
+    ///
+    /// # Panics
+    /// If `phase` is not in `0..=2`.
+    pub fn set_cpu_ppu_alignment(&mut self, phase: u8) {
+        assert!(phase <= 2, "CPU/PPU alignment must be in 0..=2");
+        self.cpu_ppu_alignment = phase;
+    }
 
-        const CPU_CYCLES_PER_FRAME: f32 = 29780.5; // number of CPU cycles per loop, one full frame
+    /// The CPU/PPU alignment set by [`NES::set_cpu_ppu_alignment`]
+    pub fn cpu_ppu_alignment(&self) -> u8 {
+        self.cpu_ppu_alignment
+    }
 
-        self.frame_counter += CPU_CYCLES_PER_FRAME;
+    /// The region of the console.
+    ///
+    /// When a ROM is loaded, the region is selected from the timing declared in its
+    /// header (see [`NES::timing_mode`]), and is [`Region::Ntsc`] if there is none.
+    /// The header has the lowest priority: [`NES::set_region`] > overrides > header.
+    pub fn region(&self) -> Region {
+        self.cpu.bus().apu.region()
+    }
 
-        while self.frame_counter >= 0. {
-            self.frame_counter -= 1.;
-            self.cpu.run_next();
-            self.cpu.bus_mut().apu.clock();
-            {
-                let ppu = &mut self.cpu.bus_mut().ppu;
-                ppu.clock();
-                ppu.clock();
-                ppu.clock();
-            }
-        }
+    /// What selected the current [`NES::region`]
+    pub fn region_source(&self) -> RegionSource {
+        self.region_source
     }
 
-    /// Run the NES emulator for one CPU cycle.
+    /// Set the region of the console, this resets the APU.
     ///
-    /// This is useful for debugging and testing purposes.
-    pub fn clock(&mut self) -> Option<CPURunState> {
-        if self.cartridge.borrow().is_empty() {
+    /// Currently, only the APU (frame counter, noise/DMC rates and audio sampling)
+    /// is affected by the region, the CPU and PPU still run with NTSC timing.
+    pub fn set_region(&mut self, region: Region) {
+        self.explicit_settings.region = true;
+        self.region_source = RegionSource::Api;
+        self.change_region(region);
+    }
+
+    /// The CPU/PPU timing declared in the NES 2.0 header, `None` for iNES 1.0 files
+    /// and empty consoles.
+    pub fn timing_mode(&self) -> Option<TimingMode> {
+        self.cartridge.borrow().timing_mode()
+    }
+
+    /// iNES 1.0 files don't declare the timing reliably, enabling this guesses [`Region::Pal`]
+    /// for them from the TV system bit (byte 9) and from `(E)` or `(Europe)` in the file name.
+    /// Disabled by default, as many dumps have a wrong byte 9.
+    ///
+    /// Like with the header, this doesn't replace a region set with [`NES::set_region`]
+    /// or the overrides. If it changes the region, the APU is reset, so this should be
+    /// called right after loading the ROM.
+    pub fn set_region_guessing(&mut self, enabled: bool) {
+        self.region_guessing = enabled;
+        self.apply_header_region();
+    }
+
+    /// The region the ROM header asks for, if any
+    fn header_region(&self) -> Option<Region> {
+        if let Some(timing_mode) = self.timing_mode() {
+            return Some(timing_mode.region());
+        }
+        if !self.region_guessing || self.is_empty() {
             return None;
         }
 
-        self.cpu.bus_mut().apu.clock();
+        let europe_name = self.rom_file_name().is_some_and(|name| {
+            let name = name.to_lowercase();
+            name.contains("(e)") || name.contains("(europe)")
+        });
+        (self.cartridge.borrow().ines1_pal() || europe_name).then_some(Region::Pal)
+    }
 
-        let r = self.cpu.run_next();
-        {
-            let ppu = &mut self.cpu.bus_mut().ppu;
-            ppu.clock();
-            ppu.clock();
-            ppu.clock();
+    /// Select the region from the header, if no other source has selected it
+    fn apply_header_region(&mut self) {
+        if !matches!(
+            self.region_source,
+            RegionSource::Default | RegionSource::Header
+        ) {
+            return;
         }
 
-        Some(r)
+        let header_region = self.header_region();
+        self.region_source = if header_region.is_some() {
+            RegionSource::Header
+        } else {
+            RegionSource::Default
+        };
+
+        let region = header_region.unwrap_or_default();
+        if region != self.region() {
+            self.change_region(region);
+        }
     }
 
-    /// Return the pixel buffer as RGB format
+    /// The number of frames per second to run [`NES::clock_for_frame`] at, so the
+    /// emulation runs at the speed of the CPU clock of the current region.
     ///
-    /// The size of the buffer will be [`TV_BUFFER_SIZE`][crate::nes_display::TV_BUFFER_SIZE]
-    pub fn pixel_buffer(&self) -> &[u8] {
-        self.cpu.bus().ppu.tv().display_pixel_buffer()
+    /// This is [`NTSC_FPS`](crate::nes_timing::NTSC_FPS) for NTSC. With PAL, the frames
+    /// still have NTSC timing, so this is higher than
+    /// [`PAL_FPS`](crate::nes_timing::PAL_FPS) to keep the audio at the correct speed.
+    pub fn current_fps(&self) -> f64 {
+        self.region().cpu_freq() / CYCLES_PER_FRAME_NTSC
+    }
+
+    pub(crate) fn change_region(&mut self, region: Region) {
+        self.cpu.bus_mut().apu = self.new_apu(region);
+
+        if region == Region::Ntsc && self.header_region() == Some(Region::Pal) {
+            self.cpu.diagnostics_mut().push(Diagnostic::PalRomOnNtsc);
+        }
+    }
+
+    /// A new APU writing to the same ring with the same sampling mode and rate, the
+    /// channel tap is moved from the current APU
+    fn new_apu(&mut self, region: Region) -> APU2A03 {
+        let mut apu = APU2A03::new(region, self.audio_ring.clone());
+        apu.set_audio_sampling(self.audio_sampling);
+        apu.set_audio_sample_rate(self.audio_sample_rate);
+        apu.replace_channel_tap(self.cpu.bus_mut().apu.replace_channel_tap(None));
+        apu
+    }
+
+    /// The current settings that affect the emulation, these are saved with the state
+    /// in [`NES::save_state`].
+    pub fn config(&self) -> EmulatorConfig {
+        EmulatorConfig {
+            region: self.region(),
+            cpu_ppu_alignment: self.applied_cpu_ppu_alignment,
+        }
+    }
+
+    /// When loading a state saved with a different [`EmulatorConfig`], by default the saved
+    /// config is applied to the emulator. If `strict` is `true`, [`NES::load_state`] fails
+    /// with [`SaveError::ConfigMismatch`] instead.
+    pub fn set_strict_state_config(&mut self, strict: bool) {
+        self.strict_state_config = strict;
+    }
+
+    fn apply_config(&mut self, config: EmulatorConfig) {
+        if config.region != self.region() {
+            self.change_region(config.region);
+        }
+        self.cpu_ppu_alignment = config.cpu_ppu_alignment;
+        self.applied_cpu_ppu_alignment = config.cpu_ppu_alignment;
     }
 
-    /// Take and return the audio buffer as f32 format stereo (2 channels)
+    /// Run the NES emulator for one video frame, which is equal to
+    /// [`CYCLES_PER_FRAME_NTSC`](crate::nes_timing::CYCLES_PER_FRAME_NTSC) CPU cycles on
+    /// average, alternating between `29781` and `29780` cycles.
     ///
-    /// **Take** here means that if you call the function again, it will return an empty buffer
-    /// until the emulator runs again.
+    /// This is the main function to run the emulator, call this once, and then render and play audio.
     ///
-    /// The emulator keeps accumulating audio samples until this function is called,
-    /// so its better to call this function even if audio isn't needed in order to free up space.
-    pub fn audio_buffer(&mut self) -> Vec<f32> {
-        self.cpu.bus_mut().apu.take_audio_buffer()
+    /// If a frame was started with [`NES::clock_with_budget`], only the rest of it is run.
+    pub fn clock_for_frame(&mut self) {
+        self.clock_with_budget(u32::MAX);
     }
 
-    /// Check if there is no cartridge loaded in the emulator.
-    pub fn is_empty(&self) -> bool {
-        self.cartridge.borrow().is_empty()
-    }
+    /// Run the emulator for at most `max_cpu_cycles` CPU cycles, stopping early at the
+    /// end of the frame, so that a frame can be spread over multiple calls when the
+    /// frontend can't run a whole frame in time.
+    ///
+    /// Call this repeatedly until [`BudgetResult::frame_complete`], then render the pixel
+    /// buffer. Running a frame in slices gives the same result as [`NES::clock_for_frame`].
+    ///
+    /// The audio samples are written while running, and input changes between the calls
+    /// are seen by the game the next time it reads the controller, as with a real console.
+    /// [`NES::save_state`] can be used between the calls, but the progress in the frame is
+    /// not part of the state, so [`NES::load_state`] and [`NES::reset`] drop the rest of the
+    /// frame, and the next call starts a new one.
+    pub fn clock_with_budget(&mut self, max_cpu_cycles: u32) -> BudgetResult {
+        if self.cartridge.borrow().is_empty() {
+            // nothing to run, but keep counting the frames so frontends can call this
+            // unconditionally and still wait for `frame_complete`
+            self.frame_number += 1;
+            if self.idle_screen {
+                draw_idle_screen(self.cpu.bus_mut().ppu.tv_mut(), self.idle_frame);
+                self.idle_frame = self.idle_frame.wrapping_add(1);
+            }
+            return BudgetResult {
+                frame_complete: true,
+                ..BudgetResult::default()
+            };
+        }
 
-    /// Set the state of a controller key. `pressed` or `released`.
-    pub fn set_controller_state(&mut self, key: NESKey, pressed: bool) {
-        self.cpu
-            .bus_mut()
-            .contoller_mut()
-            .set_controller_state(key, pressed);
+        let mut stats = match self.partial_frame.take() {
+            Some(stats) => stats,
+            None => self.start_frame(),
+        };
+
+        #[cfg(feature = "profiling")]
+        let call_start = self.profiler.start_call();
+
+        let mut cycles_run = 0;
+        while cycles_run < max_cpu_cycles && self.frame_counter >= 0. {
+            self.frame_counter -= 1.;
+            self.run_frame_cycle(&mut stats);
+            cycles_run += 1;
+        }
+
+        #[cfg(feature = "profiling")]
+        self.profiler.end_call(call_start);
+
+        let frame_cycles = stats.cpu_cycles;
+        let frame_complete = self.frame_counter < 0.;
+        if frame_complete {
+            self.finish_frame(stats);
+        } else {
+            self.partial_frame = Some(stats);
+        }
+
+        BudgetResult {
+            frame_complete,
+            cycles_run,
+            frame_cycles,
+        }
     }
 
-    /// Get the name of the save state file that can be associated with the current cartridge.
+    /// Run the emulator until the PPU enters vertical blank, so that the pixel buffer has
+    /// exactly one complete frame, without the drift of [`NES::clock_for_frame`], which
+    /// runs a fixed number of CPU cycles while the PPU frame length changes with the
+    /// skipped dot of odd frames.
     ///
-    /// This is just a helper function, and the emulator implementation at [`save_state`] doesn't use it.
+    /// This is meant for tools that step one video frame at a time, read the pixel buffer
+    /// and change the input for the next frame. It works the same when rendering is
+    /// disabled, but then the pixel buffer keeps the last rendered frame, unless
+    /// [`NES::set_rendering_disabled_backdrop`] is enabled.
     ///
-    /// Just a convenience.
-    pub fn save_state_file_name(&self, slot: u8) -> Option<String> {
+    /// The frame ends at the start of vertical blank, the first call after power-on or
+    /// [`NES::reset`] runs a shorter frame to get there, and the rest of a frame started
+    /// with [`NES::clock_with_budget`] is run until vertical blank. [`NES::frame_number`]
+    /// is incremented like with [`NES::clock_for_frame`], and the two can be mixed.
+    pub fn clock_until_vblank(&mut self) -> BudgetResult {
         if self.cartridge.borrow().is_empty() {
-            return None;
+            return self.clock_with_budget(u32::MAX);
         }
 
-        let cart = self.cartridge.borrow();
-        let cartridge_path = cart.cartridge_path();
+        let mut stats = match self.partial_frame.take() {
+            Some(stats) => stats,
+            None => self.start_frame(),
+        };
 
-        Some(format!(
-            "{}_{}.pst",
-            cartridge_path.file_stem().unwrap().to_string_lossy(),
-            slot
-        ))
+        #[cfg(feature = "profiling")]
+        let call_start = self.profiler.start_call();
+
+        let mut cycles_run = 0;
+        loop {
+            self.run_frame_cycle(&mut stats);
+            cycles_run += 1;
+            if self.cpu.bus().ppu.vblank_started_in_last_cpu_cycle() {
+                break;
+            }
+        }
+
+        #[cfg(feature = "profiling")]
+        self.profiler.end_call(call_start);
+
+        // the next `clock_for_frame` starts a full frame from here
+        self.frame_counter = 0.;
+
+        let frame_cycles = stats.cpu_cycles;
+        self.finish_frame(stats);
+
+        BudgetResult {
+            frame_complete: true,
+            cycles_run,
+            frame_cycles,
+        }
     }
 
-    /// Save the current state of the emulator to a writer.
-    pub fn save_state<W: std::io::Write>(&self, mut writer: W) -> Result<(), SaveError> {
-        self.cartridge.borrow().save(&mut writer)?;
-        self.cpu.save(&mut writer)?;
-        self.cpu.bus().ppu.save(&mut writer)?;
-        self.cpu.bus().apu.save(&mut writer)?;
+    fn start_frame(&mut self) -> FrameStats {
+        self.frame_counter += CYCLES_PER_FRAME_NTSC as f32;
 
-        Ok(())
+        if let Some(tracker) = self.pc_tracker.as_mut() {
+            tracker.clear();
+        }
+        let bus = self.cpu.bus_mut();
+        bus.contoller.set_frame(self.frame_number + 1);
+        bus.contoller2.set_frame(self.frame_number + 1);
+
+        FrameStats {
+            cpu_ppu_alignment: self.applied_cpu_ppu_alignment,
+            ..FrameStats::default()
+        }
     }
 
-    /// Load the state of the emulator from a reader.
-    pub fn load_state<R: std::io::Read>(&mut self, mut reader: R) -> Result<(), SaveError> {
-        self.cartridge.borrow_mut().load(&mut reader)?;
-        self.cpu.load(&mut reader)?;
-        self.cpu.bus_mut().ppu.load(&mut reader)?;
-        self.cpu.bus_mut().apu.load(&mut reader)?;
+    fn run_frame_cycle(&mut self, stats: &mut FrameStats) {
+        #[cfg(feature = "profiling")]
+        let mut timer = self.profiler.timer();
+
+        let state = self.cpu.run_next();
+        #[cfg(feature = "profiling")]
+        timer.lap(&mut self.profiler, Component::Cpu);
+
+        stats.cpu_cycles += 1;
+        match state {
+            // a DMA transfer of one byte takes two cycles, read and write
+            CPURunState::DmaTransfer => stats.dma_cycles += 2,
+            CPURunState::NormalInstructionExecution | CPURunState::InfiniteLoop(_) => {
+                stats.instructions += 1;
+                stats.ended_in_infinite_loop = matches!(state, CPURunState::InfiniteLoop(_));
+
+                if let Some(tracker) = self.pc_tracker.as_mut() {
+                    tracker.insert(self.cpu.reg_pc());
+                }
+            }
+            _ => {}
+        }
+
+        self.cpu.bus_mut().apu.clock();
+        #[cfg(feature = "profiling")]
+        timer.lap(&mut self.profiler, Component::Apu);
+        self.cartridge.borrow_mut().cpu_cycle();
+        #[cfg(feature = "profiling")]
+        let mut timer = self.profiler.timer();
+        {
+            let ppu = &mut self.cpu.bus_mut().ppu;
+            ppu.clock();
+            ppu.clock();
+            ppu.clock();
+        }
+        #[cfg(feature = "profiling")]
+        timer.lap(&mut self.profiler, Component::Ppu);
+    }
 
-        let mut rest = Vec::new();
-        reader.read_to_end(&mut rest)?;
+    fn finish_frame(&mut self, mut stats: FrameStats) {
+        self.frame_number += 1;
+        let sram_writes = self.cartridge.borrow_mut().take_sram_writes();
+        self.sram_activity.writes_last_frame = sram_writes;
+        if sram_writes != 0 {
+            self.sram_activity.last_write_frame = self.frame_number;
+            self.sram_activity.dirty = true;
+        }
 
-        if !rest.is_empty() {
-            return Err(SaveError::ContainExtraData);
+        // both controllers are latched by the same strobe
+        stats.controller_polls = self.cpu.bus_mut().contoller_mut().take_polls();
+        self.cpu.bus_mut().contoller2.take_polls();
+        stats.distinct_pcs = self.pc_tracker.as_ref().map(|tracker| tracker.count);
+        let ppu = &self.cpu.bus().ppu;
+        stats.rendering_was_enabled = ppu.last_frame_rendering_enabled();
+        stats.frame_is_black = ppu.last_frame_is_backdrop_only();
+        self.frame_stats = stats;
+
+        if self.output_delay.frames != 0 {
+            let audio = self.audio_ring.pop_all();
+            let pixels = self.cpu.bus().ppu.tv().display_pixel_buffer();
+            self.output_delay.push_frame(pixels, audio);
         }
 
-        Ok(())
+        #[cfg(feature = "profiling")]
+        self.profiler.finish_frame();
+    }
+
+    /// Drop the rest of a frame started with [`NES::clock_with_budget`], keeping
+    /// the fraction of the cycles that carries over to the next frame
+    fn drop_partial_frame(&mut self) {
+        if self.partial_frame.take().is_some() {
+            self.frame_counter -= self.frame_counter.ceil();
+        }
+    }
+
+    /// Statistics of the last frame executed with [`NES::clock_for_frame`].
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// The number of frames run with [`NES::clock_for_frame`] or [`NES::clock_until_vblank`],
+    /// or completed with [`NES::clock_with_budget`], frames of an empty console included.
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// The writes to the battery backed save RAM, updated after each frame run with
+    /// [`NES::clock_for_frame`], always empty for cartridges without a battery.
+    ///
+    /// This is not saved in the states.
+    pub fn sram_write_activity(&self) -> SramActivity {
+        self.sram_activity
+    }
+
+    /// Clear [`SramActivity::dirty`], after the frontend copied the save RAM somewhere.
+    pub fn clear_sram_dirty(&mut self) {
+        self.sram_activity.dirty = false;
+    }
+
+    /// What the CPU `address` is mapped to, with the current cartridge banks
+    pub fn memory_region(&self, address: u16) -> MemoryRegion {
+        memory_map::memory_region(&self.cartridge.borrow(), address)
+    }
+
+    /// The whole CPU address space as ranges of [`MemoryRegion`], with the
+    /// current cartridge banks
+    pub fn memory_map(&self) -> Vec<(RangeInclusive<u16>, MemoryRegion)> {
+        memory_map::memory_map(&self.cartridge.borrow())
+    }
+
+    /// Read the CPU memory at `address` without side effects, for inspecting the
+    /// RAM and the cartridge, such as the result of test ROMs.
+    ///
+    /// Returns `None` for the PPU, APU and IO registers in `$2000-$401F`, as
+    /// reading them changes the state of the emulator.
+    pub fn peek_memory(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x1FFF => Some(self.cpu.bus().ram[(address & 0x7FF) as usize]),
+            0x2000..=0x401F => None,
+            0x4020..=0xFFFF => Some(self.cartridge.borrow().read(address, Device::Cpu)),
+        }
+    }
+
+    /// Replace the battery backed save RAM with the content of the file at `path`,
+    /// such as a `.sav` or `.srm` file of another emulator.
+    ///
+    /// Files smaller than the save RAM are padded with zeros, and larger files are
+    /// truncated with a [`Diagnostic::SramImportTruncated`]. A header before the data
+    /// is skipped if the file size is a multiple of `8kb` plus `512` bytes.
+    pub fn import_sram_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SaveError> {
+        let data = std::fs::read(path)?;
+        let dropped_bytes = self.cartridge.borrow_mut().import_sram(&data)?;
+
+        if dropped_bytes != 0 {
+            self.cpu
+                .diagnostics_mut()
+                .push(Diagnostic::SramImportTruncated { dropped_bytes });
+        }
+        self.sram_activity.dirty = true;
+
+        Ok(())
+    }
+
+    /// Write the battery backed save RAM to the file at `path`, as raw data
+    /// without a header, the same format as the `.nes.sav` files.
+    pub fn export_sram_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SaveError> {
+        let cartridge = self.cartridge.borrow();
+        let data = cartridge.sram_data().ok_or(SaveError::NoBatteryBackedRam)?;
+        std::fs::write(path, data)?;
+
+        Ok(())
+    }
+
+    /// Enable or disable tracking the number of distinct `PC` values in
+    /// [`FrameStats::distinct_pcs`], disabled by default.
+    pub fn set_distinct_pc_tracking(&mut self, enabled: bool) {
+        if enabled {
+            if self.pc_tracker.is_none() {
+                self.pc_tracker = Some(PcTracker::new());
+            }
+        } else {
+            self.pc_tracker = None;
+        }
+    }
+
+    /// Run the NES emulator for one CPU cycle.
+    ///
+    /// This is useful for debugging and testing purposes.
+    pub fn clock(&mut self) -> Option<CPURunState> {
+        if self.cartridge.borrow().is_empty() {
+            return None;
+        }
+
+        self.cpu.bus_mut().apu.clock();
+
+        let r = self.cpu.run_next();
+        self.cartridge.borrow_mut().cpu_cycle();
+        {
+            let ppu = &mut self.cpu.bus_mut().ppu;
+            ppu.clock();
+            ppu.clock();
+            ppu.clock();
+        }
+
+        Some(r)
+    }
+
+    /// Render the 4 nametables as arranged by the current mirroring, with the scroll
+    /// position of the screen at the start of the last frame, for map viewers.
+    ///
+    /// The nametables are rendered with the current background pattern table, CHR banks
+    /// and palettes, so they may differ from what was displayed in games that change them
+    /// in the middle of the frame.
+    pub fn debug_nametable_arrangement(&self) -> NametableView {
+        let ppu = &self.cpu.bus().ppu;
+        let vram = &ppu.ppu_bus().vram;
+
+        // reading CHR can change the latches of some mappers (MMC2/MMC4)
+        let mapper_state = self.cartridge.borrow().mapper_state();
+
+        let mut nametables = Vec::new();
+        let mut physical_nametables = Vec::new();
+        let mut arrangement = [0; 4];
+        for (nametable, index) in arrangement.iter_mut().enumerate() {
+            let physical = vram.physical_nametable(nametable as u8);
+
+            *index = match physical_nametables.iter().position(|&p| p == physical) {
+                Some(index) => index,
+                None => {
+                    physical_nametables.push(physical);
+                    nametables.push(ppu.render_nametable(nametable as u8));
+                    nametables.len() - 1
+                }
+            };
+        }
+
+        self.cartridge
+            .borrow_mut()
+            .restore_mapper_state(mapper_state);
+
+        let (scroll_x, scroll_y) = ppu.frame_start_scroll_origin();
+        NametableView {
+            nametables,
+            arrangement,
+            scroll_x,
+            scroll_y,
+        }
+    }
+
+    /// The memory each logical nametable (`$2000`, `$2400`, `$2800` and `$2C00`) is
+    /// mapped to with the current mirroring.
+    pub fn debug_nametable_mapping(&self) -> [NametableSource; 4] {
+        let vram = &self.cpu.bus().ppu.ppu_bus().vram;
+        [0, 1, 2, 3].map(|nametable| vram.nametable_source(nametable))
+    }
+
+    /// A copy of the nametables, pattern tables, palette RAM and OAM of the PPU,
+    /// for debugger frontends.
+    ///
+    /// This doesn't affect the emulation, the PPU registers and the mapper state
+    /// are the same after it.
+    #[cfg(feature = "debug")]
+    pub fn debug_ppu_snapshot(&self) -> PpuSnapshot {
+        // reading CHR can change the latches of some mappers (MMC2/MMC4)
+        let mapper_state = self.cartridge.borrow().mapper_state();
+        let snapshot = self.cpu.bus().ppu.debug_snapshot();
+        self.cartridge
+            .borrow_mut()
+            .restore_mapper_state(mapper_state);
+
+        snapshot
+    }
+
+    /// Return the pixel buffer as RGB format
+    ///
+    /// The size of the buffer will be [`TV_BUFFER_SIZE`][crate::nes_display::TV_BUFFER_SIZE]
+    ///
+    /// This is the frame from [`NES::set_output_delay_frames`] frames ago if a delay is set.
+    pub fn pixel_buffer(&self) -> &[u8] {
+        if self.is_output_delayed() {
+            &self.output_delay.pixels
+        } else {
+            self.cpu.bus().ppu.tv().display_pixel_buffer()
+        }
+    }
+
+    /// Return the palette indices (`0x00..0x40`) of the pixels, one byte per pixel, without
+    /// the emphasis bits, which is a smaller buffer than [`NES::pixel_buffer`].
+    ///
+    /// The size of the buffer will be [`TV_WIDTH`][crate::nes_display::TV_WIDTH] *
+    /// [`TV_HEIGHT`][crate::nes_display::TV_HEIGHT].
+    ///
+    /// This is always the last frame, [`NES::set_output_delay_frames`] doesn't apply to it.
+    pub fn pixel_index_buffer(&self) -> &[u8] {
+        self.cpu.bus().ppu.tv().display_index_buffer()
+    }
+
+    /// Delay the frames returned by [`NES::pixel_buffer`] and the audio returned by
+    /// [`NES::audio_buffer`] by `frames` frames run with [`NES::clock_for_frame`], `0` by default.
+    ///
+    /// This simulates the latency of a real console and CRT compared to an emulator
+    /// (around 1-2 frames), so timing practiced on the emulator transfers to the hardware.
+    /// Only the presented output is delayed, the emulation and the inputs are not affected.
+    /// The delayed samples are taken from [`NES::audio_ring`] at the end of every frame,
+    /// so it can't be read directly while a delay is set.
+    ///
+    /// The delayed frames are not saved in the states, so after loading a state, or a
+    /// reset, the output stays on the last presented frame (and the audio is silent)
+    /// for `frames` frames until the delay is filled again. Changing the delay does the same.
+    ///
+    /// # Panics
+    /// If `frames` is more than `5`.
+    pub fn set_output_delay_frames(&mut self, frames: u8) {
+        assert!(frames <= 5, "the output delay must be in 0..=5 frames");
+
+        if frames != 0 && !self.is_output_delayed() {
+            self.output_delay.pixels = self.pixel_buffer().to_vec();
+            self.output_delay.audio = self.audio_ring.pop_all();
+        } else if frames == 0 {
+            // the samples of the pending frames are dropped
+            self.output_delay.pixels = Vec::new();
+            self.output_delay.audio = Vec::new();
+        }
+        self.output_delay.pending.clear();
+        self.output_delay.frames = frames;
+    }
+
+    /// The delay set by [`NES::set_output_delay_frames`]
+    pub fn output_delay_frames(&self) -> u8 {
+        self.output_delay.frames
+    }
+
+    fn is_output_delayed(&self) -> bool {
+        self.output_delay.frames != 0 && !self.is_empty()
+    }
+
+    /// The 8x8 tiles of [`NES::pixel_buffer`] that changed since the last call, with
+    /// their pixels, for frontends that send the frames over the network.
+    ///
+    /// The first call returns the whole screen, applying every delta with
+    /// [`FrameDelta::apply`] to a buffer results in the same pixels as [`NES::pixel_buffer`].
+    /// A copy of the pixel buffer is kept after the first call, and the tiles are compared
+    /// only in this call, so the emulation is not slowed down for frontends not using it.
+    pub fn frame_delta(&mut self) -> FrameDelta {
+        self.cpu.bus_mut().ppu.tv_mut().frame_delta()
+    }
+
+    /// Make the next [`NES::frame_delta`] include the whole screen, for example when a
+    /// new client connects and doesn't have the previous frames.
+    pub fn force_full_frame_delta(&mut self) {
+        self.cpu.bus_mut().ppu.tv_mut().force_full_frame_delta();
+    }
+
+    /// Select the PPU rendering backend, [`PpuBackend::DotAccurate`] by default.
+    ///
+    /// The backend is switched at the end of the current frame. [`PpuBackend::Scanline`] is
+    /// faster, but the output differs in games that change the PPU state in the middle of
+    /// scanlines (raster effects), so frame hashes are not comparable between backends.
+    ///
+    /// The selected backend is saved in the states, and applied when loading them. To render
+    /// with a backend from power-up, use [`NESBuilder::ppu_backend`].
+    pub fn set_ppu_backend(&mut self, backend: PpuBackend) {
+        self.cpu.bus_mut().ppu.set_backend(backend);
+    }
+
+    /// Output the backdrop color in frames where rendering is disabled the whole frame,
+    /// disabled by default, which keeps the last rendered frame instead, to avoid flashing
+    /// when games turn off the screen while loading.
+    ///
+    /// The parts of a frame where rendering is disabled in the middle of the frame
+    /// always show the backdrop, like the hardware.
+    ///
+    /// Like the hardware, if the VRAM address (set with `$2006`) points to the palettes
+    /// (`$3F00-$3FFF`), the color at that address is shown instead of the backdrop, so
+    /// games updating the palettes during the visible scanlines show color streaks.
+    pub fn set_rendering_disabled_backdrop(&mut self, enabled: bool) {
+        self.cpu
+            .bus_mut()
+            .ppu
+            .set_rendering_disabled_backdrop(enabled);
+    }
+
+    /// Render all the sprites in a scanline instead of only the first 8, which removes
+    /// the flicker games use to show more sprites, disabled by default.
+    ///
+    /// This is an enhancement, the sprite overflow flag and sprite 0 hit behave as if
+    /// the limit applied so games run the same, only the output differs. It is not
+    /// saved in the states, and should be disabled when comparing the output
+    /// against the hardware.
+    pub fn set_sprite_limit_removed(&mut self, removed: bool) {
+        self.cpu.bus_mut().ppu.set_sprite_limit_removed(removed);
+    }
+
+    /// `true` if the sprite limit is removed with [`NES::set_sprite_limit_removed`]
+    pub fn is_sprite_limit_removed(&self) -> bool {
+        self.cpu.bus().ppu.is_sprite_limit_removed()
+    }
+
+    /// The PPU backend selected with [`NES::set_ppu_backend`]
+    pub fn ppu_backend(&self) -> PpuBackend {
+        self.cpu.bus().ppu.selected_backend()
+    }
+
+    /// Enable or disable generating the layer map, see [`NES::layer_map`], disabled by default.
+    pub fn set_layer_map_enabled(&mut self, enabled: bool) {
+        self.cpu
+            .bus_mut()
+            .ppu
+            .tv_mut()
+            .set_layer_map_enabled(enabled);
+    }
+
+    /// Return the layer map of the last frame, if enabled with [`NES::set_layer_map_enabled`].
+    ///
+    /// The layer map contains one byte for each pixel in [`NES::pixel_buffer`], so its size will
+    /// be [`LAYER_MAP_SIZE`][crate::nes_display::LAYER_MAP_SIZE]. Each byte is encoded as:
+    /// ```text
+    /// 43210
+    /// |||||
+    /// |||++- Source, see `LAYER_SOURCE_*` in `nes_display`
+    /// |++--- Palette index (from the attribute table or OAM)
+    /// +----- Sprite 0 has a non-transparent pixel here
+    /// ```
+    pub fn layer_map(&self) -> Option<&[u8]> {
+        self.cpu.bus().ppu.tv().display_layer_map()
+    }
+
+    /// Take and return the audio buffer as f32 format stereo (2 channels), at
+    /// [`NES::audio_sample_rate`] samples per second
+    ///
+    /// **Take** here means that if you call the function again, it will return an empty buffer
+    /// until the emulator runs again.
+    ///
+    /// The emulator keeps accumulating audio samples in [`NES::audio_ring`] until this function
+    /// is called, and drops new samples when it is full, so its better to call this function
+    /// even if audio isn't needed.
+    ///
+    /// This allocates a new buffer on every call, use [`NES::audio_ring`] to read the samples
+    /// from a real-time audio thread.
+    pub fn audio_buffer(&mut self) -> Vec<f32> {
+        if self.is_output_delayed() {
+            std::mem::take(&mut self.output_delay.audio)
+        } else {
+            self.audio_ring.pop_all()
+        }
+    }
+
+    /// Same as [`NES::audio_buffer`], but appends the samples to `out`, so its
+    /// allocation can be reused
+    #[cfg(feature = "rl")]
+    pub(crate) fn append_audio_buffer(&mut self, out: &mut Vec<f32>) {
+        if self.is_output_delayed() {
+            out.append(&mut self.output_delay.audio);
+        } else {
+            let start = out.len();
+            out.resize(start + self.audio_ring.read_available(), 0.);
+            let read = self.audio_ring.pop_slice(&mut out[start..]);
+            out.truncate(start + read);
+        }
+    }
+
+    /// Call `tap` with the outputs of the channels before they are mixed, for every
+    /// audio sample written to [`NES::audio_ring`] (at [`NES::audio_sample_rate`]),
+    /// to record each channel separately for example.
+    ///
+    /// `tap` is called from inside the emulation, so it should be fast.
+    pub fn set_channel_tap(&mut self, tap: ChannelTap) {
+        self.cpu.bus_mut().apu.replace_channel_tap(Some(tap));
+    }
+
+    /// Remove the tap set with [`NES::set_channel_tap`]
+    pub fn remove_channel_tap(&mut self) {
+        self.cpu.bus_mut().apu.replace_channel_tap(None);
+    }
+
+    /// Start recording the writes to the APU registers, with the CPU cycle of each write,
+    /// replacing the log if it was already started.
+    ///
+    /// The sample bytes read by the DMC are recorded too, so the log has everything
+    /// needed to play the audio again without the game, see [`NES::render_apu_log`].
+    /// The log is not saved in the states, and is dropped if the region changes.
+    pub fn start_apu_register_log(&mut self) {
+        self.cpu.bus_mut().apu.start_register_log();
+    }
+
+    /// Stop recording the APU register writes and return them,
+    /// see [`NES::start_apu_register_log`]
+    pub fn stop_apu_register_log(&mut self) -> Vec<ApuWrite> {
+        self.cpu.bus_mut().apu.stop_register_log()
+    }
+
+    /// Play a log of [`NES::stop_apu_register_log`] for `seconds` through a new APU,
+    /// without running the game, and return the audio, in the same format as
+    /// [`NES::audio_buffer`].
+    ///
+    /// The APU starts from the power-on state, so the audio is the same as the live
+    /// audio only if the log was started at power-on. Otherwise, the notes playing when
+    /// the log started are missing until the game writes their registers again.
+    pub fn render_apu_log(&self, log: &[ApuWrite], seconds: f32) -> Vec<f32> {
+        let region = self.region();
+        let cycles = (seconds.max(0.) as f64 * region.cpu_freq()) as u64;
+        APU2A03::render_register_log(
+            region,
+            self.audio_sampling,
+            self.audio_sample_rate,
+            log,
+            cycles,
+        )
+    }
+
+    /// The DMC samples started by the game since the last [`NES::clear_dmc_sample_log`],
+    /// in order, useful for extracting the samples of a game.
+    ///
+    /// Only the starts from `$4015` writes are logged, the restarts of looping samples are not.
+    /// The log is limited to 4096 events, later ones are dropped until it is cleared.
+    pub fn dmc_sample_log(&self) -> Vec<DmcSampleEvent> {
+        self.cpu.bus().apu.dmc_sample_log().to_vec()
+    }
+
+    pub fn clear_dmc_sample_log(&mut self) {
+        self.cpu.bus_mut().apu.clear_dmc_sample_log();
+    }
+
+    /// Stop producing audio samples, for when the frontend pauses the emulation.
+    ///
+    /// The samples already produced are kept, and a short fade out to silence is added
+    /// after them to avoid a click. [`NES::resume_audio`] fades the audio back in.
+    /// Resetting or changing the region resumes the audio.
+    pub fn pause_audio(&mut self) {
+        self.cpu.bus_mut().apu.pause_audio();
+    }
+
+    /// Produce audio samples again after [`NES::pause_audio`], starting with a short fade in
+    pub fn resume_audio(&mut self) {
+        self.cpu.bus_mut().apu.resume_audio();
+    }
+
+    pub fn is_audio_paused(&self) -> bool {
+        self.cpu.bus().apu.is_audio_paused()
+    }
+
+    /// The ring buffer the audio samples are written to, clone it to read the samples
+    /// from the audio thread without locks or allocations.
+    ///
+    /// See [`AudioRing`] for the threading contract, [`NES::audio_buffer`] reads from
+    /// the same ring.
+    pub fn audio_ring(&self) -> &Arc<AudioRing> {
+        &self.audio_ring
+    }
+
+    /// Replace the audio ring with a new empty one of `capacity` samples, the
+    /// rings returned by [`NES::audio_ring`] before this will not receive new samples.
+    ///
+    /// The default capacity is [`DEFAULT_AUDIO_RING_CAPACITY`](crate::nes_audio::DEFAULT_AUDIO_RING_CAPACITY),
+    /// one second of audio at the default [`SAMPLE_RATE`](crate::nes_audio::SAMPLE_RATE).
+    pub fn set_audio_ring_capacity(&mut self, capacity: usize) {
+        self.audio_ring = Arc::new(AudioRing::new(capacity));
+        self.cpu
+            .bus_mut()
+            .apu
+            .set_audio_ring(self.audio_ring.clone());
+    }
+
+    /// Change how the CPU cycles that produce audio samples are picked,
+    /// [`AudioSampling::Float`] by default.
+    ///
+    /// With [`AudioSampling::Exact`], the number of samples produced in each frame follows
+    /// a fixed pattern that only depends on the CPU cycles run, which is what
+    /// [`NES::record_movie_frame`] and [`NES::play_movie_from_frame`] use.
+    pub fn set_audio_sampling(&mut self, sampling: AudioSampling) {
+        self.audio_sampling = sampling;
+        self.cpu.bus_mut().apu.set_audio_sampling(sampling);
+    }
+
+    /// The sampling mode set by [`NES::set_audio_sampling`]
+    pub fn audio_sampling(&self) -> AudioSampling {
+        self.audio_sampling
+    }
+
+    /// Produce `rate` samples per second of emulated time, instead of the default
+    /// [`SAMPLE_RATE`](crate::nes_audio::SAMPLE_RATE), so the audio can be played at
+    /// the native rate of the audio device without resampling.
+    ///
+    /// The samples produced at the old rate and not taken yet are dropped, including
+    /// the ones delayed by [`NES::set_output_delay_frames`], so they are not played at
+    /// the wrong speed. Like [`NES::audio_buffer`], this reads from [`NES::audio_ring`],
+    /// so no other thread should be reading from it at the same time.
+    ///
+    /// The rate is kept on reset and when the region changes.
+    ///
+    /// # Panics
+    /// If `rate` is `0`.
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        assert!(rate > 0, "the audio sample rate must not be 0");
+
+        self.audio_sample_rate = rate;
+        self.cpu.bus_mut().apu.set_audio_sample_rate(rate);
+
+        self.audio_ring.pop_all();
+        self.output_delay.audio.clear();
+        for (_, audio) in self.output_delay.pending.iter_mut() {
+            audio.clear();
+        }
+    }
+
+    /// The rate set by [`NES::set_audio_sample_rate`]
+    pub fn audio_sample_rate(&self) -> u32 {
+        self.audio_sample_rate
+    }
+
+    /// The current state of the APU channels (frequency, volume, ...),
+    /// useful for audio visualizers.
+    pub fn apu_channel_states(&self) -> ApuSnapshot {
+        self.cpu.bus().apu.channel_states()
+    }
+
+    /// The default input device declared in the cartridge header (NES 2.0 only).
+    ///
+    /// Only the standard controllers are emulated currently, so this is for
+    /// information only, frontends can use it to warn that the game needs a different device,
+    /// or connect their own with [`NES::set_expansion_port_device`].
+    pub fn expansion_device(&self) -> ExpansionDevice {
+        self.cartridge.borrow().expansion_device()
+    }
+
+    /// Replace the expansion device reported by [`NES::expansion_device`], for ROMs
+    /// with a missing or wrong header.
+    pub fn set_expansion_device(&mut self, device: ExpansionDevice) {
+        self.explicit_settings.expansion_device = true;
+        self.cartridge.borrow_mut().set_expansion_device(device);
+    }
+
+    /// Connect a device to the expansion port, it will see the output lines written
+    /// to `$4016`, `None` disconnects the current device.
+    ///
+    /// The standard controllers stay connected and only use the strobe (bit 0).
+    pub fn set_expansion_port_device(&mut self, device: Option<Box<dyn ExpansionPortDevice>>) {
+        self.cpu.bus_mut().expansion_port = device;
+    }
+
+    /// Force bus conflicts on or off for writes to ROM, `None` uses the default of the mapper.
+    pub fn set_bus_conflicts(&mut self, bus_conflicts: Option<bool>) {
+        self.explicit_settings.bus_conflicts = true;
+        self.cartridge
+            .borrow_mut()
+            .set_bus_conflicts_override(bus_conflicts);
+    }
+
+    /// Set the timer DIP switches of the Nintendo World Championships 1990
+    /// cartridge (mapper 105), other cartridges ignore it.
+    ///
+    /// The timer of the game runs for `(16 + dips) * 2^25` CPU cycles, from `5:00`
+    /// (`0`) to `9:41` (`15`) minutes, only the low 4 bits are used. The default
+    /// is `4` (`6:15` minutes), the setting used in the competition.
+    pub fn set_nwc_timer_dips(&mut self, dips: u8) {
+        self.cartridge.borrow_mut().set_dip_switches(dips & 0xF);
+    }
+
+    /// The submapper (board revision) from the NES 2.0 header, `0` for iNES 1.0
+    pub fn submapper_id(&self) -> u8 {
+        self.cartridge.borrow().submapper_id()
+    }
+
+    /// Change the submapper (board revision), this resets the console.
+    pub fn set_submapper_id(&mut self, submapper_id: u8) {
+        self.explicit_settings.submapper = true;
+        self.cartridge.borrow_mut().set_submapper_id(submapper_id);
+        self.reset();
+    }
+
+    /// CRC32 of the PRG and CHR ROM data (without the header), as used by most game databases
+    pub fn rom_crc32(&self) -> u32 {
+        self.cartridge.borrow().rom_crc32()
+    }
+
+    /// The PRG-ROM of the loaded cartridge, after applying the patch if any.
+    pub fn prg_rom(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.cartridge.borrow(), |cartridge| cartridge.prg_rom())
+    }
+
+    /// The CHR-ROM of the loaded cartridge, after applying the patch if any.
+    ///
+    /// Returns `None` if the cartridge uses CHR-RAM, see [`NES::chr_ram`].
+    pub fn chr_rom(&self) -> Option<Ref<'_, [u8]>> {
+        Ref::filter_map(self.cartridge.borrow(), |cartridge| cartridge.chr_rom()).ok()
+    }
+
+    /// The current content of the CHR-RAM of the loaded cartridge, which is written by the game.
+    ///
+    /// Returns `None` if the cartridge uses CHR-ROM, see [`NES::chr_rom`].
+    pub fn chr_ram(&self) -> Option<Ref<'_, [u8]>> {
+        Ref::filter_map(self.cartridge.borrow(), |cartridge| cartridge.chr_ram()).ok()
+    }
+
+    /// Check if there is no cartridge loaded in the emulator.
+    pub fn is_empty(&self) -> bool {
+        self.cartridge.borrow().is_empty()
+    }
+
+    /// Use a palette generated from a model of the NTSC signal with the parameters of
+    /// `generator`, the colors with emphasis included, or go back to the built-in
+    /// palette with `None`. Applied from the next frame.
+    pub fn set_generated_palette(&mut self, generator: Option<PaletteGenerator>) {
+        let palettes = generator.map(|generator| generator.generate());
+        self.cpu
+            .bus_mut()
+            .ppu
+            .tv_mut()
+            .set_palette(palettes.as_ref());
+    }
+
+    /// Show an animated screen asking to load a ROM when the console is empty
+    /// ([`NES::is_empty`]), drawn on every call to [`NES::clock_for_frame`].
+    /// Enabled by default, disable it to show a placeholder in the frontend instead,
+    /// the pixel buffer is then kept black.
+    pub fn set_idle_screen(&mut self, enabled: bool) {
+        self.idle_screen = enabled;
+        if !enabled && self.is_empty() {
+            self.cpu.bus_mut().ppu.tv_mut().reset();
+        }
+    }
+
+    /// The current values of the CPU registers
+    pub fn cpu_state(&self) -> CpuState {
+        self.cpu.state()
+    }
+
+    /// `true` if the CPU executed a `KIL` opcode, which jams it until [`reset`](Self::reset),
+    /// the game crashed. The PPU and APU keep running, so the last picture stays on
+    /// screen and the channels keep playing as they were set.
+    pub fn is_cpu_halted(&self) -> bool {
+        self.cpu.is_halted()
+    }
+
+    /// Emit [`Diagnostic::StackWrap`] when the stack pointer wraps around the stack
+    /// page, which is almost always a bug in the game. Disabled by default.
+    pub fn set_stack_wrap_warnings(&mut self, enabled: bool) {
+        self.cpu.set_stack_wrap_warnings(enabled);
+    }
+
+    /// The number of times a DMC sample fetch collided with a read of the controller
+    /// ports (`$4016/$4017`) since reset.
+    ///
+    /// On the console, the collision reads the port twice, which clocks the controller
+    /// shift register an extra time and deletes a bit. Games avoid it by re-reading
+    /// until two reads match, or by syncing the reads to OAM DMA. This counter is
+    /// useful to check that the reading routine handles it, the glitch itself is
+    /// emulated whether the counter is used or not.
+    pub fn controller_read_glitches(&self) -> u64 {
+        self.cpu.controller_read_glitches()
+    }
+
+    /// Delay the DMC sample fetches, by up to 32 CPU cycles each, until they land
+    /// on a controller read. This makes the collisions counted by
+    /// [`controller_read_glitches`](Self::controller_read_glitches) as likely as possible,
+    /// to test that the controller reading routine is robust.
+    ///
+    /// The delayed fetches change the timing of the DMC, so this is a testing aid and
+    /// should stay disabled (the default) for normal play.
+    pub fn set_dmc_collision_stress(&mut self, enabled: bool) {
+        self.cpu.set_dmc_collision_stress(enabled);
+    }
+
+    /// Run `ADC` and `SBC` in BCD (Binary Coded Decimal) when the decimal flag is set
+    /// with `SED`, with the flags of the 65C02.
+    ///
+    /// The 2A03 of the NES doesn't have the decimal mode, the flag can be set but the
+    /// arithmetic stays binary, and some test ROMs check that, so this should stay
+    /// disabled (the default) for NES games. It is useful for running 6502 code that
+    /// wasn't written for the NES. This is not saved in the states.
+    pub fn set_cpu_decimal_mode(&mut self, enabled: bool) {
+        self.cpu.set_decimal_mode(enabled);
+    }
+
+    /// Take the diagnostics emitted since the last call.
+    ///
+    /// Only a limited number of diagnostics are kept, so this should be called
+    /// regularly (every frame for example) when any of them is enabled.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.cpu.diagnostics_mut().take();
+        diagnostics.extend(self.cpu.bus_mut().homebrew_checks.diagnostics_mut().take());
+        diagnostics.extend(self.cartridge.borrow_mut().take_diagnostics());
+        diagnostics
+    }
+
+    /// Check the bank mapping of the cartridge after every write to a mapper register,
+    /// and emit [`Diagnostic::MapperAudit`] for every inconsistency, disabled by default.
+    ///
+    /// The checks are that the PRG ROM space (with the interrupt vectors) is readable
+    /// and the banks are inside the PRG ROM, PRG RAM and CHR data, that CHR ROM is not
+    /// writable, and the board specific invariants of the mapper (the fixed banks).
+    /// This is for debugging the emulated mappers, it makes register writes slower.
+    pub fn set_mapper_audit(&mut self, enabled: bool) {
+        self.cartridge.borrow_mut().set_mapper_audit(enabled);
+    }
+
+    /// Emit diagnostics for common bugs of homebrew games, disabled by default:
+    /// - [`Diagnostic::RomWrite`] when the game writes to ROM where the mapper doesn't
+    ///   have a register, probably intended for RAM.
+    /// - [`Diagnostic::UninitializedRamRead`] when the game reads a byte of the 2KB RAM
+    ///   before writing to it, once per byte. The pages ignored are set with
+    ///   [`NES::set_uninitialized_ram_ignored_pages`].
+    /// - [`Diagnostic::TestRegisterWrite`] when the game writes to the disabled CPU test
+    ///   registers (`$4018-$401F`).
+    ///
+    /// The RAM writes are tracked from when this is enabled, so it should be enabled
+    /// before [`NES::reset`] or right after loading the ROM. The RAM loaded from
+    /// a state is considered initialized.
+    pub fn set_homebrew_diagnostics(&mut self, enabled: bool) {
+        self.cpu.bus_mut().homebrew_checks.set_enabled(enabled);
+    }
+
+    /// The 256 bytes pages of RAM to not report in [`Diagnostic::UninitializedRamRead`],
+    /// bit `n` is the page `$0n00-$0nFF`. By default only the stack page (bit 1) is
+    /// ignored, as games pull from it without pushing when returning from interrupts.
+    pub fn set_uninitialized_ram_ignored_pages(&mut self, pages: u8) {
+        self.cpu.bus_mut().homebrew_checks.set_ignored_pages(pages);
+    }
+
+    /// Guess the return addresses of the current subroutine calls, innermost first.
+    ///
+    /// This is a heuristic, the stack doesn't record which bytes are return addresses.
+    /// The stack page above `SP` is scanned for 2 bytes pointing to the last byte of a
+    /// `JSR` instruction in RAM or cartridge space (using the current bank mapping), so it
+    /// can include stale or pushed data that happens to look like a return address, and it
+    /// doesn't include interrupt return addresses.
+    pub fn call_stack_guess(&self) -> Vec<u16> {
+        const JSR_OPCODE: u8 = 0x20;
+
+        let stack = &self.cpu.bus().ram[0x100..0x200];
+        let mut call_stack = Vec::new();
+
+        let mut sp = self.cpu.state().sp as usize + 1;
+        while sp < 0xFF {
+            // `JSR` pushes the address of its last byte
+            let pushed = u16::from_le_bytes([stack[sp], stack[sp + 1]]);
+            let jsr_address = pushed.wrapping_sub(2);
+
+            if self.peek_code(jsr_address) == Some(JSR_OPCODE) {
+                call_stack.push(pushed.wrapping_add(1));
+                sp += 2;
+            } else {
+                sp += 1;
+            }
+        }
+
+        call_stack
+    }
+
+    /// Read code from the CPU address space without side effects, `None` for
+    /// addresses that can't contain code
+    fn peek_code(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x1FFF => Some(self.cpu.bus().ram[(address & 0x7FF) as usize]),
+            0x6000..=0xFFFF => Some(self.cartridge.borrow().read(address, Device::Cpu)),
+            _ => None,
+        }
+    }
+
+    /// Set the state of a controller key. `pressed` or `released`.
+    pub fn set_controller_state(&mut self, key: NESKey, pressed: bool) {
+        self.cpu
+            .bus_mut()
+            .contoller_mut()
+            .set_controller_state(key, pressed);
+    }
+
+    /// Same as [`NES::set_controller_state`], for the controller of player 2, which the
+    /// game reads from `$4017`.
+    pub fn set_controller2_state(&mut self, key: NESKey, pressed: bool) {
+        self.cpu
+            .bus_mut()
+            .contoller2
+            .set_controller_state(key, pressed);
+    }
+
+    /// The buttons the game latched the last time it polled the controller of `player`
+    /// (`1` or `2`), as bits in the order of [`NESKey`], and the frame number it
+    /// happened in, counting from `1` for the first frame run with
+    /// [`NES::clock_for_frame`], `0` if it was never polled.
+    ///
+    /// Useful for input viewers, as this is what the game saw and not what is
+    /// pressed now. The number of polls in a frame is in [`FrameStats::controller_polls`].
+    pub fn last_polled_input(&self, player: u8) -> (u8, u64) {
+        match player {
+            1 => self.cpu.bus().contoller.last_polled(),
+            2 => self.cpu.bus().contoller2.last_polled(),
+            _ => (0, 0),
+        }
+    }
+
+    /// Set the position of an analog stick mapped to the D-pad, `x` is positive to the right
+    /// and `y` is positive downwards, both in `-1.0..=1.0`.
+    ///
+    /// The directions are combined with the ones set by [`NES::set_controller_state`], and
+    /// opposite directions pressed together are cleaned, see [`NES::set_analog_to_dpad`].
+    pub fn set_controller_analog(&mut self, x: f32, y: f32) {
+        self.cpu.bus_mut().contoller_mut().set_analog_state(x, y);
+    }
+
+    /// Set the dead-zone, diagonal threshold and SOCD policies used for the D-pad, the
+    /// SOCD policies also apply to the directions set by [`NES::set_controller_state`].
+    pub fn set_analog_to_dpad(&mut self, analog_to_dpad: AnalogToDpad) {
+        self.cpu
+            .bus_mut()
+            .contoller_mut()
+            .set_analog_to_dpad(analog_to_dpad);
+    }
+
+    /// Get the name of the save state file that can be associated with the current cartridge.
+    ///
+    /// This is just a helper function, and the emulator implementation at [`save_state`] doesn't use it.
+    ///
+    /// Just a convenience.
+    pub fn save_state_file_name(&self, slot: u8) -> Option<String> {
+        if self.cartridge.borrow().is_empty() {
+            return None;
+        }
+
+        let cart = self.cartridge.borrow();
+        let cartridge_path = cart.cartridge_path()?;
+
+        Some(format!(
+            "{}_{}.pst",
+            cartridge_path.file_stem().unwrap().to_string_lossy(),
+            slot
+        ))
+    }
+
+    /// Save the current state of the emulator to a writer.
+    ///
+    /// The state starts with a header of the format version and the mapper and CRC32 of
+    /// the ROM, followed by the [`EmulatorConfig`] used, see [`NES::set_strict_state_config`].
+    pub fn save_state<W: std::io::Write>(&self, mut writer: W) -> Result<(), SaveError> {
+        // write the whole state at once, so a failing component doesn't leave
+        // a partially written state in the writer
+        let mut data = Vec::new();
+        self.state_header().save(&mut data)?;
+        self.config().save(&mut data)?;
+        self.cartridge.borrow().save(&mut data)?;
+        self.cpu.save(&mut data)?;
+        self.cpu.bus().ppu.save(&mut data)?;
+        self.cpu.bus().apu.save(&mut data)?;
+
+        writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    /// Load the state of the emulator from a reader.
+    ///
+    /// States of other format versions fail with [`SaveError::VersionMismatch`], and
+    /// states of other ROMs with [`SaveError::RomMismatch`], before loading anything.
+    ///
+    /// If the state was saved with a different [`EmulatorConfig`], it is applied
+    /// to the emulator, unless [`NES::set_strict_state_config`] is enabled.
+    ///
+    /// If loading fails, the emulator is left in the same state as before the call.
+    pub fn load_state<R: std::io::Read>(&mut self, mut reader: R) -> Result<(), SaveError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut sections = data.as_slice();
+
+        let mut header = StateHeader::default();
+        header.load(&mut sections)?;
+        if header != self.state_header() {
+            return Err(SaveError::RomMismatch);
+        }
+
+        let mut saved_config = EmulatorConfig::default();
+        saved_config.load(&mut sections)?;
+
+        let current_config = self.config();
+        if saved_config != current_config && self.strict_state_config {
+            return Err(SaveError::ConfigMismatch {
+                saved: saved_config,
+                current: current_config,
+            });
+        }
+
+        // the components are loaded in place, so keep the current state to go
+        // back to if any of them fails
+        let backup = self.snapshot()?;
+
+        let result = self.load_state_sections(&data);
+        if result.is_err() {
+            self.load_state_sections(&backup.data)
+                .expect("restoring the state saved before loading should not fail");
+        } else {
+            self.drop_partial_frame();
+            self.output_delay.pending.clear();
+        }
+
+        result
+    }
+
+    /// Export the state of the emulator as readable JSON, with a field for each component,
+    /// useful to find which component differs between two states.
+    ///
+    /// The memories of the cartridge are base64 encoded. The pixel buffer and audio
+    /// are not included, the same as [`NES::save_state`].
+    #[cfg(feature = "state-json")]
+    pub fn export_state_json(&self) -> String {
+        let state = serde_json::json!({
+            "config": to_json_value(&self.config()),
+            "cartridge": self.cartridge.borrow().to_json(),
+            "cpu": self.cpu.to_json(),
+            "ppu": self.cpu.bus().ppu.to_json(),
+            "apu": self.cpu.bus().apu.to_json(),
+        });
+
+        serde_json::to_string_pretty(&state).expect("state can be serialized to JSON")
+    }
+
+    /// Load a state exported with [`NES::export_state_json`], the config is handled the
+    /// same way as [`NES::load_state`], and on failure the emulator is not modified.
+    ///
+    /// This is much slower than [`NES::load_state`], and the JSON follows the internal
+    /// structure of the components, so it may not load in other versions of the emulator.
+    #[cfg(feature = "state-json")]
+    pub fn import_state_json(&mut self, json: &str) -> Result<(), SaveError> {
+        let mut state: serde_json::Value =
+            serde_json::from_str(json).map_err(|_| SaveError::SerializationError)?;
+
+        let saved_config: EmulatorConfig = from_json_value(take_json_field(&mut state, "config")?)?;
+        let current_config = self.config();
+        if saved_config != current_config && self.strict_state_config {
+            return Err(SaveError::ConfigMismatch {
+                saved: saved_config,
+                current: current_config,
+            });
+        }
+
+        let backup = self.snapshot()?;
+
+        let result = self.load_json_sections(saved_config, state);
+        if result.is_err() {
+            self.load_state_sections(&backup.data)
+                .expect("restoring the state saved before loading should not fail");
+        }
+
+        result
+    }
+
+    /// The same as [`NES::load_state_sections`] for JSON states
+    #[cfg(feature = "state-json")]
+    fn load_json_sections(
+        &mut self,
+        config: EmulatorConfig,
+        mut state: serde_json::Value,
+    ) -> Result<(), SaveError> {
+        if config != self.config() {
+            self.apply_config(config);
+        }
+
+        self.cartridge
+            .borrow_mut()
+            .load_json(take_json_field(&mut state, "cartridge")?)?;
+        self.cpu.load_json(take_json_field(&mut state, "cpu")?)?;
+        self.cpu
+            .bus_mut()
+            .ppu
+            .load_json(take_json_field(&mut state, "ppu")?)?;
+        self.cpu
+            .bus_mut()
+            .apu
+            .load_json(take_json_field(&mut state, "apu")?)?;
+
+        Ok(())
+    }
+
+    /// Load all the sections of a state in order without restoring the previous
+    /// state on failure, use [`NES::load_state`] instead.
+    fn load_state_sections(&mut self, mut data: &[u8]) -> Result<(), SaveError> {
+        // already checked by the callers
+        StateHeader::default().load(&mut data)?;
+
+        let mut config = EmulatorConfig::default();
+        config.load(&mut data)?;
+        if config != self.config() {
+            self.apply_config(config);
+        }
+
+        self.cartridge.borrow_mut().load(&mut data)?;
+        self.cpu.load(&mut data)?;
+        self.cpu.bus_mut().ppu.load(&mut data)?;
+        self.cpu.bus_mut().apu.load(&mut data)?;
+
+        if !data.is_empty() {
+            return Err(SaveError::ContainExtraData);
+        }
+
+        Ok(())
+    }
+
+    /// The length of each section of the state, in the order they are saved
+    #[cfg(test)]
+    pub(crate) fn state_section_lengths(&self) -> Vec<usize> {
+        self.state_sections()
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .collect()
+    }
+
+    /// Create a [`StateSnapshot`] of the current state in memory.
+    pub fn snapshot(&self) -> Result<StateSnapshot, SaveError> {
+        let mut data = Vec::new();
+        self.save_state(&mut data)?;
+
+        Ok(StateSnapshot { data })
+    }
+
+    /// Restore the state from a [`StateSnapshot`] created with [`NES::snapshot`].
+    pub fn restore_snapshot(&mut self, snapshot: &StateSnapshot) -> Result<(), SaveError> {
+        self.load_state(snapshot.data.as_slice())
+    }
+
+    /// Capture the current state as the baseline of an incremental state sync, for
+    /// example to keep spectators in sync without sending the full state every time.
+    ///
+    /// Send the baseline to the other side once ([`SyncBaseline::to_bytes`]), then send the
+    /// deltas of [`NES::state_sync_delta`] which are applied with [`NES::apply_state_delta`].
+    pub fn state_sync_baseline(&self) -> Result<SyncBaseline, SaveError> {
+        Ok(SyncBaseline::new(self.state_sections()?))
+    }
+
+    /// Encode the current state as a delta from `baseline`, the sections of the state
+    /// that didn't change are skipped, and the others only keep the changed bytes,
+    /// so the delta is small when little changed since the baseline, like the RAM.
+    ///
+    /// The deltas are all relative to the baseline, so they get larger the further the
+    /// state moves from it, take a new baseline from time to time.
+    pub fn state_sync_delta(&self, baseline: &SyncBaseline) -> Result<Vec<u8>, SaveError> {
+        baseline.delta(&self.state_sections()?)
+    }
+
+    /// Load the state encoded by [`NES::state_sync_delta`] from `baseline`, which must be
+    /// the same baseline the delta was created with. The same as [`NES::load_state`]
+    /// otherwise, if loading fails the emulator is not modified.
+    pub fn apply_state_delta(
+        &mut self,
+        baseline: &SyncBaseline,
+        delta: &[u8],
+    ) -> Result<(), SaveError> {
+        let state = baseline.apply(delta)?;
+        self.load_state(state.as_slice())
+    }
+
+    /// Identifies the ROM the states are saved from
+    fn state_header(&self) -> StateHeader {
+        let cartridge = self.cartridge.borrow();
+        StateHeader {
+            mapper_id: cartridge.mapper_id(),
+            rom_crc32: cartridge.rom_crc32(),
+        }
+    }
+
+    /// The sections of the state in the order of [`NES::save_state`], each saved separately
+    fn state_sections(&self) -> Result<Vec<Vec<u8>>, SaveError> {
+        fn save(savable: &dyn Savable) -> Result<Vec<u8>, SaveError> {
+            let mut data = Vec::new();
+            savable.save(&mut data)?;
+            Ok(data)
+        }
+
+        Ok(vec![
+            save(&self.state_header())?,
+            save(&self.config())?,
+            save(&*self.cartridge.borrow())?,
+            save(&self.cpu)?,
+            save(&self.cpu.bus().ppu)?,
+            save(&self.cpu.bus().apu)?,
+        ])
+    }
+
+    /// Skip writing the pixels to the pixel buffer, the PPU still runs normally,
+    /// but [`NES::pixel_buffer`] will keep the last frame rendered before skipping.
+    ///
+    /// Useful when running frames that will not be displayed, like fast-forwarding.
+    pub fn set_skip_rendering(&mut self, skip: bool) {
+        self.cpu.bus_mut().ppu.tv_mut().set_output_enabled(!skip);
+    }
+
+    /// Run `f` and then restore the emulator to the state before it, including
+    /// the pixel buffer, pending audio, diagnostics and frame statistics.
+    ///
+    /// Used to run the emulator forward without affecting the user session.
+    pub(crate) fn run_detached<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let snapshot = self
+            .snapshot()
+            .expect("saving the state to memory should not fail");
+        let frame_counter = self.frame_counter;
+        let frame_stats = self.frame_stats;
+        let partial_frame = self.partial_frame.take();
+        let frame_number = self.frame_number;
+        let sram_activity = self.sram_activity;
+        let pc_tracker = self.pc_tracker.take();
+        #[cfg(feature = "profiling")]
+        let profiler = std::mem::take(&mut self.profiler);
+        let diagnostics = self.take_diagnostics();
+        let initialized_ram = self.cpu.bus().homebrew_checks.initialized_ram();
+        let output_enabled = self.cpu.bus().ppu.tv().is_output_enabled();
+        let output_delay = std::mem::take(&mut self.output_delay);
+        let channel_tap = self.cpu.bus_mut().apu.replace_channel_tap(None);
+
+        self.set_skip_rendering(true);
+        // drop the samples instead of writing them to the ring of the user,
+        // this is also used if the APU is recreated
+        let audio_ring = std::mem::take(&mut self.audio_ring);
+        self.cpu
+            .bus_mut()
+            .apu
+            .set_audio_ring(self.audio_ring.clone());
+        let result = f(self);
+
+        self.restore_snapshot(&snapshot)
+            .expect("loading a snapshot of the same emulator should not fail");
+        self.frame_counter = frame_counter;
+        self.frame_stats = frame_stats;
+        self.partial_frame = partial_frame;
+        self.frame_number = frame_number;
+        self.sram_activity = sram_activity;
+        self.pc_tracker = pc_tracker;
+        #[cfg(feature = "profiling")]
+        {
+            self.profiler = profiler;
+        }
+        self.cpu.diagnostics_mut().replace(diagnostics);
+        let homebrew_checks = &mut self.cpu.bus_mut().homebrew_checks;
+        homebrew_checks.diagnostics_mut().take();
+        homebrew_checks.set_initialized_ram(initialized_ram);
+        self.set_skip_rendering(!output_enabled);
+        self.output_delay = output_delay;
+        self.cpu.bus_mut().apu.replace_channel_tap(channel_tap);
+        self.audio_ring = audio_ring;
+        self.cpu
+            .bus_mut()
+            .apu
+            .set_audio_ring(self.audio_ring.clone());
+
+        result
+    }
+
+    /// The file name of the loaded ROM, `None` if it was loaded from memory
+    pub(crate) fn rom_file_name(&self) -> Option<String> {
+        self.cartridge
+            .borrow()
+            .cartridge_path()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// Read a little endian `u16` from the CPU address space
+    pub(crate) fn cpu_read_u16(&self, address: u16) -> u16 {
+        let bus = self.cpu.bus();
+        u16::from_le_bytes([bus.read(address), bus.read(address.wrapping_add(1))])
+    }
+
+    /// Returns the number of `$4015` reads since the last call
+    pub(crate) fn take_apu_status_reads(&mut self) -> u32 {
+        self.cpu.bus_mut().apu_status_reads.replace(0)
+    }
+
+    /// See [`Cartridge::take_unsupported_writes`]
+    pub(crate) fn take_unsupported_mapper_writes(&mut self) -> (u32, Option<u16>) {
+        self.cartridge.borrow_mut().take_unsupported_writes()
+    }
+
+    /// The internal 2KB RAM of the console
+    #[cfg_attr(not(feature = "rl"), allow(dead_code))]
+    pub(crate) fn cpu_ram(&self) -> &[u8] {
+        &self.cpu.bus().ram
+    }
+
+    #[cfg(all(test, feature = "compare"))]
+    pub(crate) fn cpu_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.cpu.bus_mut().ram
     }
 
     #[cfg(test)]
@@ -437,6 +2361,11 @@ impl NES {
         self.cpu.bus()
     }
 
+    #[cfg(test)]
+    pub(crate) fn cpu_bus_mut(&mut self) -> &mut impl CPUBusTrait {
+        self.cpu.bus_mut()
+    }
+
     #[cfg(test)]
     pub(crate) fn ppu_bus(&self) -> &impl Bus {
         self.cpu.bus().ppu.ppu_bus()
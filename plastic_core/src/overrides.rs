@@ -0,0 +1,268 @@
+//! Per game settings loaded from a TOML document, see [`NES::load_overrides`].
+//!
+//! Each `[[game]]` entry is matched by the CRC32 of the ROM data (PRG and CHR without
+//! the header, see [`NES::rom_crc32`]) or by a pattern of the ROM file name, where `*`
+//! matches any characters and `?` a single character (case insensitive).
+//!
+//! ```toml
+//! [[game]]
+//! crc32 = "0x1A2B3C4D"
+//! region = "pal"
+//!
+//! [[game]]
+//! name = "Vs. *"
+//! expansion_device = 4 # Vs. System, NES 2.0 expansion device code
+//! bus_conflicts = false
+//! submapper = 1
+//! ```
+//!
+//! The settings are applied with this priority: settings changed with the API
+//! (for example [`NES::set_region`]) > overrides > the ROM header.
+//! Between matching entries, `crc32` entries have priority over `name` entries,
+//! and later entries have priority over earlier ones.
+
+use crate::{ExpansionDevice, Region, RegionSource, NES};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Deserialize)]
+struct OverridesFile {
+    #[serde(default)]
+    game: Vec<GameEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GameEntry {
+    crc32: Option<String>,
+    name: Option<String>,
+    region: Option<String>,
+    expansion_device: Option<u8>,
+    bus_conflicts: Option<bool>,
+    submapper: Option<u8>,
+}
+
+/// The settings of a game entry after validation
+#[derive(Default)]
+struct GameSettings {
+    region: Option<Region>,
+    expansion_device: Option<ExpansionDevice>,
+    bus_conflicts: Option<bool>,
+    submapper: Option<u8>,
+}
+
+impl GameSettings {
+    /// Replace the settings that are set in `other`
+    fn merge(&mut self, other: &GameSettings) {
+        self.region = other.region.or(self.region);
+        self.expansion_device = other.expansion_device.or(self.expansion_device);
+        self.bus_conflicts = other.bus_conflicts.or(self.bus_conflicts);
+        self.submapper = other.submapper.or(self.submapper);
+    }
+}
+
+/// A setting changed by [`NES::load_overrides`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppliedOverride {
+    Region(Region),
+    ExpansionDevice(ExpansionDevice),
+    BusConflicts(bool),
+    Submapper(u8),
+}
+
+impl fmt::Display for AppliedOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppliedOverride::Region(region) => write!(f, "region: {:?}", region),
+            AppliedOverride::ExpansionDevice(device) => {
+                write!(f, "expansion device: {:?}", device)
+            }
+            AppliedOverride::BusConflicts(enabled) => write!(f, "bus conflicts: {}", enabled),
+            AppliedOverride::Submapper(submapper) => write!(f, "submapper: {}", submapper),
+        }
+    }
+}
+
+/// Error happening when loading an overrides document
+#[derive(Debug)]
+pub enum OverridesError {
+    /// The document is not valid TOML, or has unknown fields
+    Parse(toml::de::Error),
+    /// A `[[game]]` entry has neither `crc32` nor `name`
+    MissingKey { game: usize },
+    /// A field of the `[[game]]` entry number `game` (starting from 0) has an invalid value
+    InvalidValue {
+        game: usize,
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl Error for OverridesError {}
+
+impl fmt::Display for OverridesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverridesError::Parse(err) => write!(f, "Parse error: {}", err),
+            OverridesError::MissingKey { game } => {
+                write!(f, "game entry {} needs a `crc32` or `name` key", game)
+            }
+            OverridesError::InvalidValue { game, field, value } => write!(
+                f,
+                "game entry {} has an invalid `{}`: {:?}",
+                game, field, value
+            ),
+        }
+    }
+}
+
+/// Match `text` with a pattern where `*` matches any characters and `?` a single character
+fn matches_pattern(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| matches_pattern(rest, &text[skip..])),
+        Some((&c, rest)) => match text.split_first() {
+            Some((&t, text_rest)) => (c == '?' || c == t) && matches_pattern(rest, text_rest),
+            None => false,
+        },
+    }
+}
+
+fn parse_crc32(game: usize, value: &str) -> Result<u32, OverridesError> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+
+    u32::from_str_radix(digits, 16).map_err(|_| OverridesError::InvalidValue {
+        game,
+        field: "crc32",
+        value: value.to_string(),
+    })
+}
+
+impl GameEntry {
+    fn settings(&self, game: usize) -> Result<GameSettings, OverridesError> {
+        let region = match self.region.as_deref().map(str::to_lowercase).as_deref() {
+            None => None,
+            Some("ntsc") => Some(Region::Ntsc),
+            Some("pal") => Some(Region::Pal),
+            Some(_) => {
+                return Err(OverridesError::InvalidValue {
+                    game,
+                    field: "region",
+                    value: self.region.clone().unwrap_or_default(),
+                })
+            }
+        };
+
+        if let Some(submapper) = self.submapper.filter(|&submapper| submapper > 0xF) {
+            return Err(OverridesError::InvalidValue {
+                game,
+                field: "submapper",
+                value: submapper.to_string(),
+            });
+        }
+
+        Ok(GameSettings {
+            region,
+            expansion_device: self.expansion_device.map(ExpansionDevice::from_code),
+            bus_conflicts: self.bus_conflicts,
+            submapper: self.submapper,
+        })
+    }
+}
+
+impl NES {
+    /// Load per game settings from a TOML document (see the [`overrides`](crate::overrides)
+    /// module for the format), and apply the ones matching the loaded ROM.
+    ///
+    /// Returns the settings that were applied, settings changed before with the API are
+    /// not replaced. If any setting is applied, the console is reset, so this should be
+    /// called right after loading the ROM.
+    pub fn load_overrides(&mut self, toml: &str) -> Result<Vec<AppliedOverride>, OverridesError> {
+        let file: OverridesFile = toml::from_str(toml).map_err(OverridesError::Parse)?;
+
+        // validate everything first, so errors don't depend on the loaded ROM
+        let mut entries = Vec::with_capacity(file.game.len());
+        for (game, entry) in file.game.iter().enumerate() {
+            let crc32 = entry
+                .crc32
+                .as_deref()
+                .map(|crc32| parse_crc32(game, crc32))
+                .transpose()?;
+            if crc32.is_none() && entry.name.is_none() {
+                return Err(OverridesError::MissingKey { game });
+            }
+
+            entries.push((crc32, entry.name.as_deref(), entry.settings(game)?));
+        }
+
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rom_crc32 = self.rom_crc32();
+        let file_name = self
+            .rom_file_name()
+            .map(|name| name.to_lowercase().chars().collect::<Vec<_>>());
+
+        let mut settings = GameSettings::default();
+        // name matches first, so that CRC matches replace them
+        for (crc32, name, entry_settings) in &entries {
+            if crc32.is_none() {
+                let pattern = name.unwrap_or_default().to_lowercase();
+                let pattern = pattern.chars().collect::<Vec<_>>();
+
+                if file_name
+                    .as_ref()
+                    .is_some_and(|file_name| matches_pattern(&pattern, file_name))
+                {
+                    settings.merge(entry_settings);
+                }
+            }
+        }
+        for (crc32, _, entry_settings) in &entries {
+            if *crc32 == Some(rom_crc32) {
+                settings.merge(entry_settings);
+            }
+        }
+
+        Ok(self.apply_overrides(settings))
+    }
+
+    fn apply_overrides(&mut self, settings: GameSettings) -> Vec<AppliedOverride> {
+        let explicit = self.explicit_settings;
+        let mut applied = Vec::new();
+
+        if let Some(region) = settings.region.filter(|_| !explicit.region) {
+            self.region_source = RegionSource::Overrides;
+            self.change_region(region);
+            applied.push(AppliedOverride::Region(region));
+        }
+        if let Some(device) = settings
+            .expansion_device
+            .filter(|_| !explicit.expansion_device)
+        {
+            self.set_expansion_device(device);
+            applied.push(AppliedOverride::ExpansionDevice(device));
+        }
+        if let Some(bus_conflicts) = settings.bus_conflicts.filter(|_| !explicit.bus_conflicts) {
+            self.set_bus_conflicts(Some(bus_conflicts));
+            applied.push(AppliedOverride::BusConflicts(bus_conflicts));
+        }
+        // this resets the console
+        if let Some(submapper) = settings.submapper.filter(|_| !explicit.submapper) {
+            self.set_submapper_id(submapper);
+            applied.push(AppliedOverride::Submapper(submapper));
+        } else if !applied.is_empty() {
+            self.reset();
+        }
+
+        // the setters mark the settings as explicit, but these came from the overrides
+        self.explicit_settings = explicit;
+
+        applied
+    }
+}
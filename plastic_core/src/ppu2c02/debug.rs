@@ -0,0 +1,250 @@
+//! Debug views of the PPU memory, see
+//! [`NES::debug_nametable_arrangement`](crate::NES::debug_nametable_arrangement).
+
+use super::PPU2C02;
+use crate::common::{save_state::Savable, Bus};
+use crate::display::{TV_HEIGHT, TV_WIDTH};
+
+/// The memory a logical nametable is mapped to, see
+/// [`NES::debug_nametable_mapping`](crate::NES::debug_nametable_mapping).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NametableSource {
+    /// The first 1KB of the console nametable RAM (CIRAM)
+    CiramA,
+    /// The second 1KB of the console nametable RAM (CIRAM)
+    CiramB,
+    /// A 1KB page of the nametable RAM of four-screen cartridges
+    CartridgeVram(u8),
+}
+
+/// The 4 logical nametables (`$2000`, `$2400`, `$2800` and `$2C00`) arranged in a
+/// 512x480 space, with the scroll position of the screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NametableView {
+    /// The physical nametables rendered with the current background pattern table
+    /// and palettes, each is `TV_WIDTH * TV_HEIGHT` palette color indices (`0x00-0x3F`).
+    ///
+    /// Nametables that are mirrors of each other are rendered once.
+    pub nametables: Vec<Vec<u8>>,
+    /// The index in `nametables` of each logical nametable, in order
+    /// top left, top right, bottom left and bottom right
+    pub arrangement: [usize; 4],
+    /// The top left corner of the screen in the 512x480 space, taken from the scroll
+    /// registers at the start of the last frame
+    pub scroll_x: u16,
+    pub scroll_y: u16,
+}
+
+impl NametableView {
+    pub const WIDTH: usize = TV_WIDTH * 2;
+    pub const HEIGHT: usize = TV_HEIGHT * 2;
+
+    /// The palette color index at `(x, y)` in the 512x480 space
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let nametable = self.arrangement[(y / TV_HEIGHT % 2) * 2 + x / TV_WIDTH % 2];
+        self.nametables[nametable][(y % TV_HEIGHT) * TV_WIDTH + x % TV_WIDTH]
+    }
+
+    /// The area of the screen in the 512x480 space as `(x, y, width, height)` rectangles.
+    ///
+    /// The screen wraps around the edges of the space, so it's split into up to 4 rectangles.
+    pub fn viewport_rects(&self) -> Vec<(usize, usize, usize, usize)> {
+        /// split `length` starting from `start` at the end of `total`
+        fn split(start: usize, length: usize, total: usize) -> [(usize, usize); 2] {
+            let first = length.min(total - start);
+            [(start, first), (0, length - first)]
+        }
+
+        let mut rects = Vec::with_capacity(4);
+        for (y, height) in split(self.scroll_y as usize, TV_HEIGHT, Self::HEIGHT) {
+            for (x, width) in split(self.scroll_x as usize, TV_WIDTH, Self::WIDTH) {
+                if width != 0 && height != 0 {
+                    rects.push((x, y, width, height));
+                }
+            }
+        }
+
+        rects
+    }
+}
+
+/// An entry of the primary OAM, see [`PpuSnapshot::oam`]
+#[cfg(feature = "debug")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OamEntry {
+    /// The Y byte as stored in OAM, the sprite is displayed one scanline below it
+    pub y: u8,
+    pub tile: u8,
+    /// The sprite palette (0-3), the colors are at `$3F10 + palette * 4` in palette RAM
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub x: u8,
+}
+
+/// The memory of the PPU, see [`NES::debug_ppu_snapshot`](crate::NES::debug_ppu_snapshot).
+#[cfg(feature = "debug")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PpuSnapshot {
+    /// The 4 logical nametables (`$2000`, `$2400`, `$2800` and `$2C00`) with the current
+    /// mirroring, including the attribute tables at the end of each one
+    pub nametables: [[u8; 0x400]; 4],
+    /// The pattern tables at `$0000` and `$1000` with the current CHR banks, each is
+    /// [`PpuSnapshot::PATTERN_TABLE_SIZE`] squared color indices (0-3) with the 256 tiles
+    /// arranged in 16 rows of 16
+    pub pattern_tables: [Vec<u8>; 2],
+    /// The palette RAM at `$3F00-$3F1F`, the background palettes then the sprite palettes
+    pub palette_ram: [u8; 0x20],
+    pub oam: [OamEntry; 64],
+}
+
+#[cfg(feature = "debug")]
+impl PpuSnapshot {
+    pub const PATTERN_TABLE_SIZE: usize = 128;
+}
+
+impl<T> PPU2C02<T>
+where
+    T: Bus + Savable,
+{
+    pub(super) fn capture_frame_start_scroll(&mut self) {
+        // `v` is only reloaded from `t` before the frame if rendering is enabled, and
+        // its horizontal part has already moved by the prefetch of the first 2 tiles,
+        // so take that part from `t`, which it was copied from
+        const HORIZONTAL_BITS: u16 = 0x041F;
+        let v = if self.reg_mask.rendering_enabled() {
+            self.vram_address_cur.get() & !HORIZONTAL_BITS
+                | self.vram_address_top_left & HORIZONTAL_BITS
+        } else {
+            self.vram_address_top_left
+        };
+
+        self.frame_start_scroll = (v, self.fine_x_scroll);
+    }
+
+    /// The top left corner of the screen at the start of the current frame,
+    /// in the 512x480 space of the 4 nametables.
+    ///
+    /// Coarse Y values of 30 and 31 (the attribute rows) wrap inside the same nametable.
+    pub fn frame_start_scroll_origin(&self) -> (u16, u16) {
+        let (v, fine_x) = self.frame_start_scroll;
+
+        let coarse_x = v & 0x1F;
+        let coarse_y = (v >> 5) & 0x1F;
+        let nametable = (v >> 10) & 0b11;
+        let fine_y = (v >> 12) & 0b111;
+
+        let x = (nametable & 1) * TV_WIDTH as u16 + coarse_x * 8 + fine_x as u16;
+        let y = (nametable >> 1) * TV_HEIGHT as u16 + (coarse_y * 8 + fine_y) % TV_HEIGHT as u16;
+
+        (x, y)
+    }
+
+    /// Render the logical `nametable` (0-3) with the current background pattern table
+    /// and palettes as palette color indices.
+    ///
+    /// This reads CHR through the bus, so mappers that latch on pattern reads (MMC2/MMC4)
+    /// may change their state.
+    pub fn render_nametable(&self, nametable: u8) -> Vec<u8> {
+        let base = 0x2000 | (nametable as u16 & 0b11) << 10;
+        let pattern_table = self.reg_control.background_pattern_address();
+
+        let mut palettes = [0; 16];
+        for (i, palette) in palettes.iter_mut().enumerate() {
+            *palette = self.read_bus(0x3F00 | i as u16);
+        }
+
+        let mut pixels = vec![0; TV_WIDTH * TV_HEIGHT];
+        for tile_y in 0..TV_HEIGHT / 8 {
+            for tile_x in 0..TV_WIDTH / 8 {
+                let tile = self.read_bus(base | (tile_y << 5 | tile_x) as u16);
+                let attribute_byte =
+                    self.read_bus(base | 0x3C0 | ((tile_y >> 2) << 3 | tile_x >> 2) as u16);
+                let palette = (attribute_byte >> ((tile_y & 0b10) << 1 | tile_x & 0b10)) & 0b11;
+
+                for fine_y in 0..8 {
+                    let [low, high] = self.fetch_pattern(pattern_table, tile, fine_y as u8);
+                    let row = (tile_y * 8 + fine_y) * TV_WIDTH + tile_x * 8;
+
+                    for (i, pixel) in pixels[row..row + 8].iter_mut().enumerate() {
+                        let bit = 7 - i;
+                        let color_bits = ((high >> bit) & 1) << 1 | (low >> bit) & 1;
+
+                        // color 0 of all palettes is the backdrop
+                        let color = if color_bits == 0 {
+                            palettes[0]
+                        } else {
+                            palettes[(palette << 2 | color_bits) as usize]
+                        };
+                        *pixel = color & 0x3F;
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Copy the memory of the PPU, without changing the state of the PPU (the `$2007`
+    /// read buffer and the address are not used).
+    ///
+    /// This reads CHR through the bus, so mappers that latch on pattern reads (MMC2/MMC4)
+    /// may change their state.
+    #[cfg(feature = "debug")]
+    pub fn debug_snapshot(&self) -> PpuSnapshot {
+        const SIZE: usize = PpuSnapshot::PATTERN_TABLE_SIZE;
+
+        let mut nametables = [[0; 0x400]; 4];
+        for (nametable, data) in nametables.iter_mut().enumerate() {
+            let base = 0x2000 | (nametable as u16) << 10;
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte = self.read_bus(base | i as u16);
+            }
+        }
+
+        let pattern_tables = [0x0000, 0x1000].map(|pattern_table| {
+            let mut pixels = vec![0; SIZE * SIZE];
+            for tile in 0..=255u8 {
+                let tile_x = (tile & 0xF) as usize * 8;
+                let tile_y = (tile >> 4) as usize * 8;
+
+                for fine_y in 0..8 {
+                    let [low, high] = self.fetch_pattern(pattern_table, tile, fine_y as u8);
+                    let row = (tile_y + fine_y) * SIZE + tile_x;
+
+                    for (i, pixel) in pixels[row..row + 8].iter_mut().enumerate() {
+                        let bit = 7 - i;
+                        *pixel = ((high >> bit) & 1) << 1 | (low >> bit) & 1;
+                    }
+                }
+            }
+            pixels
+        });
+
+        let mut palette_ram = [0; 0x20];
+        for (i, color) in palette_ram.iter_mut().enumerate() {
+            *color = self.read_bus(0x3F00 | i as u16);
+        }
+
+        let oam = self.primary_oam.map(|sprite| {
+            let attribute = sprite.get_attribute();
+            OamEntry {
+                y: sprite.read_offset(0),
+                tile: sprite.get_tile(),
+                palette: attribute.palette(),
+                behind_background: attribute.is_behind_background(),
+                flip_horizontal: attribute.is_flip_horizontal(),
+                flip_vertical: attribute.is_flip_vertical(),
+                x: sprite.read_offset(3),
+            }
+        });
+
+        PpuSnapshot {
+            nametables,
+            pattern_tables,
+            palette_ram,
+            oam,
+        }
+    }
+}
@@ -1,23 +1,70 @@
+mod debug;
 mod palette;
 mod ppu2c02_registers;
+mod scanline;
 mod sprite;
+mod tests;
 mod vram;
 
+pub use debug::{NametableSource, NametableView};
+#[cfg(feature = "debug")]
+pub use debug::{OamEntry, PpuSnapshot};
 pub use palette::Palette;
 pub use vram::VRam;
 
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, take_json_field, to_json_value, JsonSavable};
 use crate::common::{
     interconnection::PPUCPUConnection,
     save_state::{Savable, SaveError},
     Bus, Device,
 };
-use crate::display::{Color, COLORS, TV};
+use crate::display::{
+    LAYER_PALETTE_SHIFT, LAYER_SOURCE_BACKDROP, LAYER_SOURCE_BACKGROUND,
+    LAYER_SOURCE_SPRITE_BEHIND, LAYER_SOURCE_SPRITE_FRONT, LAYER_SPRITE_0, TV,
+};
 use bitflags::bitflags;
 use ppu2c02_registers::Register;
 use serde::{Deserialize, Serialize};
 use sprite::{Sprite, SpriteAttribute};
 use std::cell::Cell;
-use std::cmp::min;
+
+/// number of frames (about 600ms) before a bit of the I/O latch decays to 0 if
+/// it's not refreshed
+const IO_LATCH_DECAY_FRAMES: u8 = 36;
+
+/// number of sprites the hardware can render in a scanline
+const SPRITES_PER_SCANLINE: usize = 8;
+
+/// The method used by the PPU to render the picture, see
+/// [`NES::set_ppu_backend`](crate::NES::set_ppu_backend)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PpuBackend {
+    /// Fetch and render pixel by pixel, like the hardware does
+    #[default]
+    DotAccurate,
+    /// Render every scanline at once at its start, using the registers of that time.
+    ///
+    /// This is faster, but changes to the PPU registers or CHR banks in the middle of
+    /// a scanline only take effect in the next scanline, so raster effects may be off
+    /// by a line, and the output will differ from [`PpuBackend::DotAccurate`] in such games.
+    /// Sprite 0 hit is still reported at the correct dot.
+    Scanline,
+}
+/// The vertical blank flag is set, and NMI is raised, when running this dot
+const VBL_SET_SCANLINE: u16 = 241;
+const VBL_SET_DOT: u16 = 1;
+
+/// `$2002` reads up to this many dots after the vertical blank flag is set return it
+/// clear, `0` being the read right after the dot that set it
+const STATUS_READ_HIDES_VBL_DOTS: u16 = 0;
+
+/// `$2002` reads and NMI disables (`$2000` writes) up to this many dots after the
+/// vertical blank flag is set cancel the NMI of this frame
+const NMI_CANCEL_DOTS: u16 = 2;
+
+/// `v` and `t` are 15 bit registers: `yyy NN YYYYY XXXXX`
+const VRAM_ADDRESS_MASK: u16 = 0x7FFF;
 
 bitflags! {
     pub struct ControlReg: u8 {
@@ -134,6 +181,12 @@ pub struct PPU2C02<T: Bus + Savable> {
 
     ppu_data_read_buffer: Cell<u8>,
 
+    /// the value of the data bus between the CPU and the PPU, returned when reading
+    /// write-only registers (open bus)
+    io_latch: Cell<u8>,
+    /// frames remaining before each bit of `io_latch` decays to 0
+    io_latch_decay: Cell<[u8; 8]>,
+
     fine_x_scroll: u8,
 
     w_toggle: Cell<bool>, // this is used for registers that require 2 writes
@@ -148,8 +201,9 @@ pub struct PPU2C02<T: Bus + Savable> {
     tv: TV,
 
     primary_oam: [Sprite; 64],
-    secondary_oam: [Sprite; 8],
-    rendering_oam: [Sprite; 8],
+    /// only the first [`SPRITES_PER_SCANLINE`] are used, unless `sprite_limit_removed`
+    secondary_oam: [Sprite; 64],
+    rendering_oam: [Sprite; 64],
 
     rendering_oam_counter: u8,
 
@@ -160,6 +214,29 @@ pub struct PPU2C02<T: Bus + Savable> {
     dma_request_address: u8,
 
     is_odd_frame: bool,
+
+    /// tracked during the visible scanlines of the current frame, and moved
+    /// to the `last_frame_*` fields at the end of the frame
+    rendering_enabled_in_frame: bool,
+    non_backdrop_pixel_in_frame: bool,
+    last_frame_rendering_enabled: bool,
+    last_frame_non_backdrop_pixel: bool,
+
+    backend: PpuBackend,
+    /// the backend to switch to at the end of the frame
+    next_backend: PpuBackend,
+    /// the dot of the current scanline where sprite 0 hit happens,
+    /// computed in advance by the scanline backend
+    sprite_0_hit_dot: Option<u16>,
+
+    /// output the frames where rendering is disabled the whole frame,
+    /// see `render_disabled_pixel`
+    rendering_disabled_backdrop: bool,
+    /// render all the sprites in a scanline instead of the first 8, not saved
+    sprite_limit_removed: bool,
+
+    /// `(v, fine_x)` at the start of the current frame, for debug views
+    frame_start_scroll: (u16, u8),
 }
 
 impl<T> PPU2C02<T>
@@ -182,6 +259,9 @@ where
 
             ppu_data_read_buffer: Cell::new(0),
 
+            io_latch: Cell::new(0),
+            io_latch_decay: Cell::new([0; 8]),
+
             fine_x_scroll: 0,
 
             w_toggle: Cell::new(false),
@@ -196,8 +276,8 @@ where
             tv,
 
             primary_oam: [Sprite::empty(); 64],
-            secondary_oam: [Sprite::empty(); 8],
-            rendering_oam: [Sprite::empty(); 8],
+            secondary_oam: [Sprite::empty(); 64],
+            rendering_oam: [Sprite::empty(); 64],
 
             rendering_oam_counter: 0,
 
@@ -208,38 +288,58 @@ where
             dma_request_address: 0,
 
             is_odd_frame: false,
+
+            rendering_enabled_in_frame: false,
+            non_backdrop_pixel_in_frame: false,
+            last_frame_rendering_enabled: false,
+            last_frame_non_backdrop_pixel: false,
+
+            backend: PpuBackend::DotAccurate,
+            next_backend: PpuBackend::DotAccurate,
+            sprite_0_hit_dot: None,
+
+            rendering_disabled_backdrop: false,
+            sprite_limit_removed: false,
+
+            frame_start_scroll: (0, 0),
         }
     }
 
-    pub(crate) fn read_register(&self, register: Register) -> u8 {
-        match register {
-            Register::Status => {
-                // reset w_mode
-                self.w_toggle.set(false);
-
-                if self.scanline == 241 {
-                    // Race Condition Warning: Reading PPUSTATUS within two
-                    // cycles of the start of vertical blank will return 0 in bit 7
-                    // but clear the latch anyway, causing NMI to not occur that frame
-                    if self.cycle <= 2 {
-                        self.reg_status
-                            .set(StatusReg::from_bits(self.reg_status.get().bits & 0x7F).unwrap());
-                    }
-                    // for NMI it has quite a different range
-                    // source: tests
-                    if self.cycle >= 2 && self.cycle <= 4 {
-                        self.nmi_pin_status.set(false);
-                        self.nmi_occured_in_this_frame.set(true);
-                    }
-                }
-                let result = self.reg_status.get().bits;
-                //  reading the status register will clear bit 7
-                self.reg_status
-                    .set(StatusReg::from_bits(result & 0x7F).unwrap());
+    /// Select the rendering backend, applied at the end of the current frame
+    pub fn set_backend(&mut self, backend: PpuBackend) {
+        self.next_backend = backend;
+    }
 
-                result
+    /// Select the rendering backend and apply it immediately, used before the first
+    /// frame, as switching in the middle of a frame is not supported
+    pub fn set_initial_backend(&mut self, backend: PpuBackend) {
+        self.backend = backend;
+        self.next_backend = backend;
+    }
+
+    /// The backend selected by [`set_backend`](Self::set_backend), may not be applied yet
+    pub fn selected_backend(&self) -> PpuBackend {
+        self.next_backend
+    }
+
+    /// Output the backdrop color in frames where rendering is disabled the whole
+    /// frame, instead of keeping the last frame, see `render_disabled_pixel`
+    pub fn set_rendering_disabled_backdrop(&mut self, enabled: bool) {
+        self.rendering_disabled_backdrop = enabled;
+    }
+
+    pub(crate) fn read_register(&self, register: Register) -> u8 {
+        // the data and the bits of it that are driven to the bus, the rest
+        // of the bits are taken from the I/O latch
+        let (data, driven_bits) = match register {
+            // only the top 3 bits are driven
+            Register::Status => (self.read_status(), 0xE0),
+            Register::OmaData => {
+                let data = self
+                    .read_sprite_byte_while_rendering()
+                    .unwrap_or_else(|| self.read_sprite_byte(self.reg_oam_addr.get()));
+                (data, 0xFF)
             }
-            Register::OmaData => self.read_sprite_byte(self.reg_oam_addr.get()),
             Register::PPUData => {
                 let address = self.vram_address_cur.get();
                 let data_in_addr = self.read_bus(address);
@@ -251,27 +351,105 @@ where
                     // fill buffer
                     self.ppu_data_read_buffer.set(data_in_addr);
 
-                    tmp_result
+                    (tmp_result, 0xFF)
                 } else {
                     // reload buffer with VRAM address hidden by palette
                     // wrap to 0x2FFF rather than 0x3EFF, to avoid the mirror
                     self.ppu_data_read_buffer
                         .set(self.read_bus(address & 0x2FFF));
-                    data_in_addr
+                    // palette entries are 6 bits
+                    (data_in_addr, 0x3F)
                 };
 
                 self.increment_vram_readwrite();
 
                 result
             }
-            _ => {
-                // unreadable
-                0
+            // write-only registers, only the I/O latch is returned
+            _ => (0, 0),
+        };
+
+        self.drive_io_latch(data, driven_bits)
+    }
+
+    /// The number of dots run since the one that set the vertical blank flag, negative
+    /// before it, only in [`VBL_SET_SCANLINE`], as `cycle` is the next dot to run, `0`
+    /// means the flag was set by the last dot.
+    fn dots_since_vbl_set(&self) -> Option<i32> {
+        (self.scanline == VBL_SET_SCANLINE).then(|| self.cycle as i32 - (VBL_SET_DOT as i32 + 1))
+    }
+
+    /// All the side effects of reading `$2002`, this runs on every CPU read of the
+    /// register, including dummy reads, so each of them clears the vertical blank flag.
+    ///
+    /// `cycle` is the next dot to run, the vertical blank flag is set when running dot 1
+    /// of scanline 241, reading it before that returns it clear, and doesn't affect it.
+    fn read_status(&self) -> u8 {
+        // reset w_mode
+        self.w_toggle.set(false);
+
+        let mut status = self.reg_status.get();
+
+        if let Some(dots) = self.dots_since_vbl_set() {
+            // Race Condition Warning: Reading PPUSTATUS within two
+            // cycles of the start of vertical blank will return 0 in bit 7
+            // but clear the latch anyway, causing NMI to not occur that frame
+            if dots <= STATUS_READ_HIDES_VBL_DOTS as i32 {
+                status.remove(StatusReg::VERTICAL_BLANK);
+            }
+            // for NMI it has quite a different range
+            // source: tests
+            if (0..=NMI_CANCEL_DOTS as i32).contains(&dots) {
+                self.nmi_pin_status.set(false);
+                self.nmi_occured_in_this_frame.set(true);
+            }
+        }
+
+        let result = status.bits;
+        //  reading the status register will clear bit 7
+        status.remove(StatusReg::VERTICAL_BLANK);
+        self.reg_status.set(status);
+
+        result
+    }
+
+    /// update the bits `driven_bits` of the I/O latch with `data`, refreshing their
+    /// decay timers, and return the new latch value
+    fn drive_io_latch(&self, data: u8, driven_bits: u8) -> u8 {
+        let latch = (self.io_latch.get() & !driven_bits) | (data & driven_bits);
+        self.io_latch.set(latch);
+
+        let mut decay = self.io_latch_decay.get();
+        for (bit, frames) in decay.iter_mut().enumerate() {
+            if driven_bits & (1 << bit) != 0 {
+                *frames = IO_LATCH_DECAY_FRAMES;
+            }
+        }
+        self.io_latch_decay.set(decay);
+
+        latch
+    }
+
+    /// run once per frame, clears the bits of the I/O latch that were not refreshed for a while
+    fn decay_io_latch(&mut self) {
+        let latch = self.io_latch.get_mut();
+
+        for (bit, frames) in self.io_latch_decay.get_mut().iter_mut().enumerate() {
+            if *frames > 0 {
+                *frames -= 1;
+                if *frames == 0 {
+                    *latch &= !(1 << bit);
+                }
             }
         }
     }
 
     pub(crate) fn write_register(&mut self, register: Register, data: u8) {
+        // `$4014` is not connected to the PPU data bus
+        if !matches!(register, Register::DmaOma) {
+            self.drive_io_latch(data, 0xFF);
+        }
+
         match register {
             // After power/reset, writes to this register are ignored for about 30,000 cycles
             // TODO: not sure, if I should account for that
@@ -279,8 +457,7 @@ where
                 self.reg_control.bits = data;
 
                 // write nametable also in top_left vram address
-                self.vram_address_top_left &= 0xF3FF;
-                self.vram_address_top_left |= (self.reg_control.nametable_selector() as u16) << 10;
+                self.set_top_left_nametable(self.reg_control.nametable_selector());
 
                 // if the NMI flag is set, run immediate NMI to the CPU
                 // but only run if we are in the VBLANK period and no
@@ -294,7 +471,10 @@ where
                     }
                 } else {
                     // if the NMI is disabled, stop the NMI (if the flag was set)
-                    if self.scanline == 241 && self.cycle <= 4 {
+                    if self
+                        .dots_since_vbl_set()
+                        .is_some_and(|dots| dots <= NMI_CANCEL_DOTS as i32)
+                    {
                         self.nmi_pin_status.set(false);
                         self.nmi_occured_in_this_frame.set(true);
                     } else {
@@ -313,47 +493,8 @@ where
                     *self.reg_oam_addr.get_mut() = self.reg_oam_addr.get().wrapping_add(1);
                 }
             }
-            Register::Scroll => {
-                if self.w_toggle.get() {
-                    // w == 1
-                    self.set_top_left_y_scroll(data);
-                } else {
-                    // w == 0
-                    self.set_top_left_x_scroll(data);
-                }
-
-                self.w_toggle.set(!self.w_toggle.get());
-            }
-            Register::PPUAddress => {
-                if self.w_toggle.get() {
-                    // w == 1
-
-                    // zero out the bottom 8 bits
-                    self.vram_address_top_left &= 0xff00;
-                    // set the data from the parameters
-                    self.vram_address_top_left |= data as u16;
-
-                    // a dummy read to the cartridge as some mappers rely
-                    // on PPU address pins for operations
-                    let _ = self.read_bus(self.vram_address_top_left);
-
-                    // copy to the current vram address
-                    *self.vram_address_cur.get_mut() = self.vram_address_top_left;
-                } else {
-                    // w == 0
-
-                    // zero out the top 8 bits
-                    self.vram_address_top_left &= 0x00ff;
-                    // set the data from the parameters
-                    self.vram_address_top_left |= (data as u16) << 8;
-
-                    // update nametable
-                    self.reg_control.bits &= !(ControlReg::BASE_NAMETABLE.bits);
-                    self.reg_control.bits |= (data >> 2) & 0b11;
-                }
-
-                self.w_toggle.set(!self.w_toggle.get());
-            }
+            Register::Scroll => self.write_scroll_register(data),
+            Register::PPUAddress => self.write_address_register(data),
             Register::PPUData => {
                 self.write_bus(self.vram_address_cur.get(), data);
                 self.increment_vram_readwrite();
@@ -369,7 +510,6 @@ where
     }
 
     /// expose the bus for reading only
-    #[cfg(test)]
     pub fn ppu_bus(&self) -> &T {
         &self.bus
     }
@@ -387,6 +527,79 @@ where
         self.primary_oam[sprite_location as usize].read_offset(address & 0b11)
     }
 
+    /// While rendering, `$2004` reads return the data the sprite evaluation is working on
+    /// at the current dot instead of the OAM at `OAMADDR`:
+    /// - dots 1-64 of visible scanlines: `$FF`, while the secondary OAM is cleared.
+    /// - dots 65-256 of visible scanlines: the byte of the primary OAM being evaluated.
+    /// - dots 257-320: the bytes of the secondary OAM being fetched, Y, tile, attribute
+    ///   and X repeated 5 times for each sprite.
+    /// - dots 321-340 and 0: the first byte of the secondary OAM.
+    ///
+    /// Only with the dot accurate backend, returns `None` when the normal read applies.
+    fn read_sprite_byte_while_rendering(&self) -> Option<u8> {
+        if self.backend != PpuBackend::DotAccurate || !self.reg_mask.rendering_enabled() {
+            return None;
+        }
+        let visible = self.scanline <= 239;
+        if !visible && self.scanline != 261 {
+            return None;
+        }
+
+        let data = match self.cycle {
+            1..=64 if visible => 0xFF,
+            65..=256 if visible => self.sprite_evaluation_read(self.cycle),
+            // no evaluation in the pre-render scanline
+            1..=256 => return None,
+            257..=320 => {
+                let fetch_cycle = self.cycle - 257;
+                let byte = (fetch_cycle % 8).min(3) as u8;
+                self.secondary_oam[fetch_cycle as usize / 8].read_offset(byte)
+            }
+            _ => self.secondary_oam[0].read_offset(0),
+        };
+
+        Some(data)
+    }
+
+    /// The byte of the primary OAM read by the sprite evaluation at `cycle` (65-256),
+    /// the evaluation reads a byte every 2 dots, the Y of every sprite and the other
+    /// 3 bytes of the sprites in range, until 8 are found.
+    ///
+    /// After 8 sprites are found, the hardware checks for overflow reading the wrong
+    /// bytes of the next sprites, this reads their Y instead.
+    fn sprite_evaluation_read(&self, cycle: u16) -> u8 {
+        let next_y = self.get_next_scroll_y_render();
+
+        let mut step = (cycle - 65) / 2;
+        let mut found = 0;
+        for sprite in &self.primary_oam {
+            if step == 0 {
+                return sprite.read_offset(0);
+            }
+            step -= 1;
+
+            if found < SPRITES_PER_SCANLINE && self.is_sprite_in_range(sprite, next_y) {
+                found += 1;
+                if step < 3 {
+                    return sprite.read_offset(step as u8 + 1);
+                }
+                step -= 3;
+            }
+        }
+
+        // all the sprites were evaluated, the index wraps to sprite 0 and only
+        // its Y is read
+        self.primary_oam[0].read_offset(0)
+    }
+
+    /// `true` if `sprite` is drawn in the scanline `y`
+    fn is_sprite_in_range(&self, sprite: &Sprite, y: u8) -> bool {
+        let diff = y as i16 - sprite.get_y() as i16;
+        let height = self.reg_control.sprite_height() as i16;
+
+        diff >= 0 && diff < height
+    }
+
     fn write_sprite_byte(&mut self, address: u8, data: u8) {
         let sprite_location = address >> 2;
         self.primary_oam[sprite_location as usize].write_offset(address & 0b11, data);
@@ -489,6 +702,61 @@ where
         self.set_top_left_fine_y_scroll(y_scroll & 0b111);
     }
 
+    fn set_top_left_nametable(&mut self, nametable: u8) {
+        // clear nametable bits
+        self.vram_address_top_left &= 0xF3FF;
+        // copy new value
+        self.vram_address_top_left |= ((nametable & 0b11) as u16) << 10;
+    }
+
+    /// $2005 write, shares the `w` toggle with $2006
+    ///
+    /// ```text
+    /// w == 0: t: ....... ...ABCDE <- d: ABCDE...
+    ///         x:              FGH <- d: .....FGH
+    /// w == 1: t: FGH..AB CDE..... <- d: ABCDEFGH
+    /// ```
+    fn write_scroll_register(&mut self, data: u8) {
+        if self.w_toggle.get() {
+            self.set_top_left_y_scroll(data);
+        } else {
+            self.set_top_left_x_scroll(data);
+        }
+
+        self.w_toggle.set(!self.w_toggle.get());
+    }
+
+    /// $2006 write, shares the `w` toggle with $2005
+    ///
+    /// ```text
+    /// w == 0: t: .CDEFGH ........ <- d: ..CDEFGH
+    ///         t: Z...... ........ <- 0 (bit 14 is cleared)
+    /// w == 1: t: ....... ABCDEFGH <- d: ABCDEFGH
+    ///         v: <...all bits...> <- t: <...all bits...>
+    /// ```
+    fn write_address_register(&mut self, data: u8) {
+        if self.w_toggle.get() {
+            // zero out the bottom 8 bits
+            self.vram_address_top_left &= 0xFF00;
+            // set the data from the parameters
+            self.vram_address_top_left |= data as u16;
+
+            // a dummy read to the cartridge as some mappers rely
+            // on PPU address pins for operations
+            let _ = self.read_bus(self.vram_address_top_left);
+
+            // copy to the current vram address
+            *self.vram_address_cur.get_mut() = self.vram_address_top_left & VRAM_ADDRESS_MASK;
+        } else {
+            // zero out the top 8 bits, including bit 14 (top bit of fine Y)
+            self.vram_address_top_left &= 0x00FF;
+            // set the data from the parameters, only 6 bits are used
+            self.vram_address_top_left |= ((data & 0x3F) as u16) << 8;
+        }
+
+        self.w_toggle.set(!self.w_toggle.get());
+    }
+
     fn increment_y_scroll(&mut self) {
         // increment fine scrolling Y on the last dot without carry
         let fine_y = self.current_fine_y_scroll() + 1;
@@ -530,8 +798,13 @@ where
     fn increment_vram_readwrite(&self) {
         // only increment if its valid, and increment by the correct ammount
         if self.scanline > 240 || !self.reg_mask.rendering_enabled() {
-            self.vram_address_cur
-                .set(self.vram_address_cur.get() + self.reg_control.vram_increment());
+            // the register is 15 bits, so incrementing past `0x7FFF` wraps around to `0x0000`
+            self.vram_address_cur.set(
+                self.vram_address_cur
+                    .get()
+                    .wrapping_add(self.reg_control.vram_increment())
+                    & VRAM_ADDRESS_MASK,
+            );
 
             // dummy read to update the cartridge, which mappers rely on some
             // address pins from the PPU
@@ -539,13 +812,31 @@ where
         }
     }
 
+    /// copy the horizontal bits from `t` to `v`
+    ///
+    /// ```text
+    /// v: ....A.. ...BCDEF <- t: ....A.. ...BCDEF
+    /// ```
     fn restore_rendering_scroll_x(&mut self) {
         self.set_current_coarse_x_scroll(self.top_left_coarse_x_scroll());
+
+        let vram_cur = self.vram_address_cur.get_mut();
+        *vram_cur &= 0xFBFF;
+        *vram_cur |= self.vram_address_top_left & 0x0400;
     }
 
+    /// copy the vertical bits from `t` to `v`
+    ///
+    /// ```text
+    /// v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
+    /// ```
     fn restore_rendering_scroll_y(&mut self) {
         self.set_current_fine_y_scroll(self.top_left_fine_y_scroll());
         self.set_current_coarse_y_scroll(self.top_left_coarse_y_scroll());
+
+        let vram_cur = self.vram_address_cur.get_mut();
+        *vram_cur &= 0xF7FF;
+        *vram_cur |= self.vram_address_top_left & 0x0800;
     }
 
     fn increment_vram_nametable_horizontal(&mut self) {
@@ -556,25 +847,6 @@ where
         *self.vram_address_cur.get_mut() ^= 0b10 << 10;
     }
 
-    // restore from top_left or original nametable selector from `reg_control`
-    fn restore_nametable(&mut self) {
-        let vram_cur = self.vram_address_cur.get_mut();
-
-        // clear old nametable data
-        *vram_cur &= 0xF3FF;
-
-        *vram_cur |= (self.reg_control.nametable_selector() as u16) << 10;
-    }
-
-    fn restore_nametable_horizontal(&mut self) {
-        let vram_cur = self.vram_address_cur.get_mut();
-
-        // clear horizontal nametable data
-        *vram_cur &= 0xFBFF;
-
-        *vram_cur |= (self.reg_control.nametable_selector() as u16 & 1) << 10;
-    }
-
     fn current_nametable(&self) -> u16 {
         (self.vram_address_cur.get() >> 10) & 0b11
     }
@@ -641,6 +913,18 @@ where
         }
     }
 
+    /// fetch one of the first 2 tiles of the next scanline into the shift registers
+    fn prefetch_next_scanline_tile(&mut self) {
+        for i in 0..=1 {
+            // the registers are not shifted by rendering in this stage, so shift
+            // manually to move the previous tile to the high byte
+            self.bg_pattern_shift_registers[i] = self.bg_pattern_shift_registers[i].wrapping_shl(8);
+            self.bg_palette_shift_registers[i] = self.bg_palette_shift_registers[i].wrapping_shl(8);
+        }
+        self.reload_background_shift_registers();
+        self.increment_coarse_x_scroll();
+    }
+
     /// ## PPU pattern table addressing ##
     /// DCBA98 76543210
     /// ---------------
@@ -691,6 +975,16 @@ where
         self.read_bus(0x2000 | self.current_nametable() << 10 | 0xF << 6 | y << 3 | x)
     }
 
+    /// Render all the sprites in a scanline instead of only the first 8, the sprite
+    /// overflow flag and sprite 0 still behave as if the limit applied
+    pub fn set_sprite_limit_removed(&mut self, removed: bool) {
+        self.sprite_limit_removed = removed;
+    }
+
+    pub fn is_sprite_limit_removed(&self) -> bool {
+        self.sprite_limit_removed
+    }
+
     fn reload_sprite_shift_registers(&mut self) {
         // move sprite_0_present
         self.sprite_0_present = self.next_scanline_sprite_0_present;
@@ -703,8 +997,16 @@ where
 
         // loop through all secondary_oam, even the empty ones (0xFF)
         // a write to the cartridge MUST be done here even if no sprites
-        // are drawn
-        for i in 0..8_usize {
+        // are drawn.
+        // The extra sprites are fetched after the ones the hardware fetches, for 8x8
+        // sprites they use the same pattern table, so the mappers counting the
+        // pattern table switches (MMC3) are not affected
+        let sprites_count = if self.sprite_limit_removed {
+            (self.rendering_oam_counter as usize).max(SPRITES_PER_SCANLINE)
+        } else {
+            SPRITES_PER_SCANLINE
+        };
+        for i in 0..sprites_count {
             let mut sprite = self.secondary_oam[i];
             let mut fine_y = next_y.wrapping_sub(sprite.get_y());
 
@@ -804,7 +1106,7 @@ where
 
     /// this method fetches background and sprite pixels, check overflow for
     /// sprite_0 and priority, and handles the left 8-pixel clipping
-    /// and then outputs a color index
+    /// and then outputs a color location
     ///
     /// ## color location offset 0x3F00 ##
     /// 43210
@@ -818,21 +1120,18 @@ where
         let (sprite_color_location, background_priority, is_sprite_0) =
             self.get_sprites_first_non_transparent_pixel();
 
-        // sprite and background multiplexer procedure
-        let color_location = if sprite_color_location != 0 && background_color_location != 0 {
-            if is_sprite_0 {
-                // if sprite and background are not transparent, then there is a collision
-                self.reg_status.get_mut().insert(StatusReg::SPRITE_0_HIT);
-            }
-            // use background priority flag
-            if background_priority {
-                background_color_location
-            } else {
-                sprite_color_location
-            }
-        } else {
-            sprite_color_location | background_color_location
-        };
+        if is_sprite_0 && sprite_color_location != 0 && background_color_location != 0 {
+            // if sprite and background are not transparent, then there is a collision
+            self.reg_status.get_mut().insert(StatusReg::SPRITE_0_HIT);
+        }
+
+        let color_location = self.mux_pixel(
+            self.cycle,
+            background_color_location,
+            sprite_color_location,
+            background_priority,
+            is_sprite_0,
+        );
 
         // advance the shift registers
         for i in 0..=1 {
@@ -840,60 +1139,136 @@ where
             self.bg_palette_shift_registers[i] = self.bg_palette_shift_registers[i].wrapping_shl(1);
         }
 
-        self.read_bus(0x3F00 | color_location as u16)
-    }
-
-    fn emphasis_color(&self, color: Color) -> Color {
-        let is_red_emph = self.reg_mask.intersects(MaskReg::EMPHASIZE_RED);
-        let is_green_emph = self.reg_mask.intersects(MaskReg::EMPHASIZE_GREEN);
-        let is_blue_emph = self.reg_mask.intersects(MaskReg::EMPHASIZE_BLUE);
-
-        let mut red = 1.;
-        let mut green = 1.;
-        let mut blue = 1.;
+        color_location
+    }
+
+    /// the sprite and background multiplexer procedure, returns the color location
+    /// of the pixel at `x` in the current scanline, and updates the frame tracking and layer map
+    fn mux_pixel(
+        &mut self,
+        x: u16,
+        background_color_location: u8,
+        sprite_color_location: u8,
+        background_priority: bool,
+        is_sprite_0: bool,
+    ) -> u8 {
+        let color_location = mux_color_location(
+            background_color_location,
+            sprite_color_location,
+            background_priority,
+        );
 
-        if is_red_emph {
-            red *= 1.1;
-            green *= 0.9;
-            blue *= 0.9;
+        if color_location & 0b11 != 0 {
+            self.non_backdrop_pixel_in_frame = true;
         }
-        if is_green_emph {
-            red *= 0.9;
-            green *= 1.1;
-            blue *= 0.9;
+
+        if self.tv.is_layer_map_enabled() {
+            self.set_pixel_layer(
+                x,
+                background_color_location,
+                sprite_color_location,
+                background_priority,
+                is_sprite_0,
+                color_location,
+            );
         }
-        if is_blue_emph {
-            red *= 0.9;
-            green *= 0.9;
-            blue *= 1.1;
+
+        color_location
+    }
+
+    /// update the layer map with the source of the pixel at `x` in the current scanline
+    fn set_pixel_layer(
+        &mut self,
+        x: u16,
+        background_color_location: u8,
+        sprite_color_location: u8,
+        background_priority: bool,
+        is_sprite_0: bool,
+        color_location: u8,
+    ) {
+        let source = if sprite_color_location != 0
+            && (background_color_location == 0 || !background_priority)
+        {
+            if background_priority {
+                LAYER_SOURCE_SPRITE_BEHIND
+            } else {
+                LAYER_SOURCE_SPRITE_FRONT
+            }
+        } else if background_color_location != 0 {
+            LAYER_SOURCE_BACKGROUND
+        } else {
+            LAYER_SOURCE_BACKDROP
+        };
+        let palette = (color_location >> 2) & 0b11;
+        let sprite_0 = if is_sprite_0 { LAYER_SPRITE_0 } else { 0 };
+
+        self.tv.set_pixel_layer(
+            x as u32,
+            self.scanline as u32,
+            source | palette << LAYER_PALETTE_SHIFT | sprite_0,
+        );
+    }
+
+    fn render_pixel(&mut self) {
+        // still need to generate the pixel for sprite 0 hit
+        let color_location = self.generate_pixel();
+
+        if !self.tv.is_output_enabled() {
+            return;
         }
 
-        Color {
-            r: min((color.r as f32 * red) as u8, 255),
-            g: min((color.g as f32 * green) as u8, 255),
-            b: min((color.b as f32 * blue) as u8, 255),
+        let color = self.read_bus(0x3F00 | color_location as u16);
+        self.output_pixel(self.cycle, color);
+    }
+
+    /// with rendering disabled, the PPU outputs the backdrop color (`$3F00`), but if
+    /// `v` points to the palettes, the entry at `v` is output instead, which is
+    /// the source of the color streaks when games write to `$2006`/`$2007` during
+    /// the visible scanlines
+    fn render_disabled_pixel(&mut self) {
+        if !self.tv.is_output_enabled() {
+            return;
         }
+
+        let address = self.vram_address_cur.get() & 0x3FFF;
+        let color = if address >= 0x3F00 {
+            self.read_bus(address)
+        } else {
+            self.read_bus(0x3F00)
+        };
+        self.output_pixel(self.cycle, color);
     }
 
-    fn render_pixel(&mut self) {
+    /// write the pixel at `x` in the current scanline to the TV, `color` is
+    /// the palette entry value
+    fn output_pixel(&mut self, x: u16, color: u8) {
         // fix overflowing colors
-        let mut color = self.generate_pixel() & 0x3F;
+        let mut color = color & 0x3F;
 
         if self.reg_mask.is_grayscale() {
             // select from the gray column (0x00, 0x10, 0x20, 0x30)
             color &= 0x30;
         }
 
+        // the emphasis bits are the top 3 bits of the mask register
+        let emphasis = self.reg_mask.bits() >> 5;
+
         // render the color
-        self.tv.set_pixel(
-            self.cycle as u32,
-            self.scanline as u32,
-            &self.emphasis_color(COLORS[color as usize]),
-        );
+        self.tv
+            .set_pixel(x as u32, self.scanline as u32, color, emphasis);
     }
 
     // run one cycle, this should be fed from Master clock
+    #[inline]
     pub fn clock(&mut self) {
+        // most of the dots of the scanline backend are skipped here, so keep
+        // this part inlined
+        if self.backend != PpuBackend::Scanline || !self.skip_scanline_dot() {
+            self.clock_dot();
+        }
+    }
+
+    fn clock_dot(&mut self) {
         // current scanline
         match (self.scanline, self.cycle) {
             (261, 0) => {
@@ -911,44 +1286,60 @@ where
                 self.reg_status.get_mut().remove(StatusReg::SPRITE_OVERFLOW);
                 // clear v-blank
                 self.reg_status.get_mut().remove(StatusReg::VERTICAL_BLANK);
-
-                if self.reg_mask.rendering_enabled() {
-                    self.restore_rendering_scroll_x();
-                    self.restore_rendering_scroll_y();
-
-                    self.restore_nametable();
-
-                    // load next 2 bytes
-                    for _ in 0..2 {
-                        for i in 0..=1 {
-                            // as this is the first time, shift the registers
-                            // as we are reloading 2 times
-                            self.bg_pattern_shift_registers[i] =
-                                self.bg_pattern_shift_registers[i].wrapping_shl(8);
-                            self.bg_palette_shift_registers[i] =
-                                self.bg_palette_shift_registers[i].wrapping_shl(8);
-                        }
-                        self.reload_background_shift_registers();
-                        self.increment_coarse_x_scroll();
-                    }
-                }
             }
-            (261, 257) => {
-                // reload all of them in one go
+            (261, 257) if self.reg_mask.rendering_enabled() => {
+                self.restore_rendering_scroll_x();
+            }
+            (261, 261) => {
+                // reload all of them in one go, at the first sprite pattern fetch
                 self.reload_sprite_shift_registers();
             }
+            // the vertical bits are copied on every dot of this range, so `$2006`
+            // writes before it on the pre-render scanline are overwritten
+            (261, 280..=304) if self.reg_mask.rendering_enabled() => {
+                self.restore_rendering_scroll_y();
+            }
+            (261, 328) | (261, 336) if self.reg_mask.rendering_enabled() => match self.backend {
+                PpuBackend::DotAccurate => self.prefetch_next_scanline_tile(),
+                // the scanline backend fetches all tiles when rendering the line
+                PpuBackend::Scanline => self.fetch_next_scanline_tile_pattern(self.cycle == 336),
+            },
             (0..=239, _) => {
-                // render only if allowed
+                if self.scanline == 0 && self.cycle == 0 {
+                    self.capture_frame_start_scroll();
+                }
+
+                // the mask is checked on every dot, so disabling rendering in the middle
+                // of a scanline freezes `v` and stops the sprite evaluation at that dot,
+                // and enabling it again continues from there
                 if self.reg_mask.rendering_enabled() {
-                    self.run_render_cycle();
+                    self.rendering_enabled_in_frame = true;
+                    match self.backend {
+                        PpuBackend::DotAccurate => self.run_render_cycle(),
+                        PpuBackend::Scanline => self.run_scanline_render_cycle(),
+                    }
+                } else if self.cycle <= 255 {
+                    self.render_disabled_pixel();
                 }
             }
             (240, 1) => {
                 // post-render
                 // idle
-                self.tv.signal_end_of_frame();
+
+                // a frame without rendering keeps the last frame, unless the
+                // backdrop is requested, see `set_rendering_disabled_backdrop`
+                if self.rendering_enabled_in_frame || self.rendering_disabled_backdrop {
+                    self.tv.signal_end_of_frame();
+                }
+                self.decay_io_latch();
+                self.backend = self.next_backend;
+
+                self.last_frame_rendering_enabled = self.rendering_enabled_in_frame;
+                self.last_frame_non_backdrop_pixel = self.non_backdrop_pixel_in_frame;
+                self.rendering_enabled_in_frame = false;
+                self.non_backdrop_pixel_in_frame = false;
             }
-            (241, 1) => {
+            (VBL_SET_SCANLINE, VBL_SET_DOT) => {
                 // set v-blank
                 self.reg_status.get_mut().insert(StatusReg::VERTICAL_BLANK);
 
@@ -984,9 +1375,7 @@ where
         match self.cycle {
             // secondary OAM clear, cycles 1-64, but we do it in one go
             // TODO: should it be in multiple times, instead of one go?
-            1 => {
-                self.secondary_oam = [Sprite::filled_ff(); 8];
-            }
+            1 => self.clear_secondary_oam(),
             // fetch and reload shift registers
             8..=256 if self.cycle % 8 == 0 => {
                 self.reload_background_shift_registers();
@@ -1000,62 +1389,21 @@ where
                 }
             }
             // check all oam memory in one go
-            255 => {
-                let next_y = self.get_next_scroll_y_render() as i16;
-
-                let mut counter = 0;
-                for (i, sprite) in self.primary_oam.iter().enumerate() {
-                    let sprite_y = sprite.get_y() as i16;
-
-                    let diff = next_y - sprite_y;
-                    let height = self.reg_control.sprite_height() as i16;
-
-                    if diff >= 0 && diff < height {
-                        // in range
-
-                        // sprite 0
-                        if i == 0 {
-                            self.next_scanline_sprite_0_present = true;
-                        }
-
-                        if counter > 7 {
-                            // overflow
-                            self.reg_status.get_mut().insert(StatusReg::SPRITE_OVERFLOW);
-                            break;
-                        }
-
-                        self.secondary_oam[counter] = *sprite;
-
-                        counter += 1;
-                    }
-                }
-
-                self.rendering_oam_counter = counter as u8;
-            }
+            255 => self.evaluate_next_scanline_sprites(),
             257 => {
                 self.restore_rendering_scroll_x();
-
-                // to fix nametable wrapping around
-                self.restore_nametable_horizontal();
             }
-            258 => {
-                // reload them all in one go
+            // reload them all in one go, at the dot of the first sprite pattern fetch,
+            // after the 2 garbage nametable fetches, as MMC3 clocks its IRQ counter
+            // when A12 rises there
+            261 => {
                 self.reload_sprite_shift_registers();
             }
-            321 => {
-                // load next 2 bytes
-                for _ in 0..2 {
-                    for i in 0..=1 {
-                        // as this is the first time, shift the registers
-                        // as we are reloading 2 times
-                        self.bg_pattern_shift_registers[i] =
-                            self.bg_pattern_shift_registers[i].wrapping_shl(8);
-                        self.bg_palette_shift_registers[i] =
-                            self.bg_palette_shift_registers[i].wrapping_shl(8);
-                    }
-                    self.reload_background_shift_registers();
-                    self.increment_coarse_x_scroll();
-                }
+            // fetch the first 2 tiles of the next scanline, each in its own 8 dots
+            // slot (321-328 and 329-336), so that mapper CHR bank switches timed
+            // between them affect only the second tile
+            328 | 336 => {
+                self.prefetch_next_scanline_tile();
             }
             _ => {}
         }
@@ -1067,6 +1415,41 @@ where
         }
     }
 
+    fn clear_secondary_oam(&mut self) {
+        self.secondary_oam = [Sprite::filled_ff(); 64];
+    }
+
+    /// find the sprites in the next scanline and put them in the secondary OAM
+    fn evaluate_next_scanline_sprites(&mut self) {
+        let next_y = self.get_next_scroll_y_render();
+
+        let mut counter = 0;
+        for (i, sprite) in self.primary_oam.iter().enumerate() {
+            if self.is_sprite_in_range(sprite, next_y) {
+                // in range
+
+                // sprite 0
+                if i == 0 {
+                    self.next_scanline_sprite_0_present = true;
+                }
+
+                if counter == SPRITES_PER_SCANLINE {
+                    // overflow
+                    self.reg_status.get_mut().insert(StatusReg::SPRITE_OVERFLOW);
+                    if !self.sprite_limit_removed {
+                        break;
+                    }
+                }
+
+                self.secondary_oam[counter] = *sprite;
+
+                counter += 1;
+            }
+        }
+
+        self.rendering_oam_counter = counter as u8;
+    }
+
     pub fn reset(&mut self, bus: T) {
         // just as if calling the constructor but without TV, just reset it
         self.reg_control = ControlReg::empty();
@@ -1082,6 +1465,9 @@ where
 
         self.ppu_data_read_buffer = Cell::new(0);
 
+        self.io_latch = Cell::new(0);
+        self.io_latch_decay = Cell::new([0; 8]);
+
         self.fine_x_scroll = 0;
 
         self.w_toggle = Cell::new(false);
@@ -1095,8 +1481,8 @@ where
         self.bus = bus;
 
         self.primary_oam = [Sprite::empty(); 64];
-        self.secondary_oam = [Sprite::empty(); 8];
-        self.rendering_oam = [Sprite::empty(); 8];
+        self.secondary_oam = [Sprite::empty(); 64];
+        self.rendering_oam = [Sprite::empty(); 64];
 
         self.rendering_oam_counter = 0;
 
@@ -1108,6 +1494,15 @@ where
 
         self.is_odd_frame = false;
 
+        self.rendering_enabled_in_frame = false;
+        self.non_backdrop_pixel_in_frame = false;
+        self.last_frame_rendering_enabled = false;
+        self.last_frame_non_backdrop_pixel = false;
+
+        self.backend = self.next_backend;
+        self.sprite_0_hit_dot = None;
+        self.frame_start_scroll = (0, 0);
+
         self.tv.reset();
     }
 
@@ -1124,6 +1519,8 @@ where
         *self.vram_address_cur.get_mut() = state.vram_address_cur;
         self.vram_address_top_left = state.vram_address_top_left;
         *self.ppu_data_read_buffer.get_mut() = state.ppu_data_read_buffer;
+        *self.io_latch.get_mut() = state.io_latch;
+        *self.io_latch_decay.get_mut() = state.io_latch_decay;
         self.fine_x_scroll = state.fine_x_scroll;
         *self.w_toggle.get_mut() = state.w_toggle;
         self.bg_pattern_shift_registers = state.bg_pattern_shift_registers;
@@ -1131,18 +1528,45 @@ where
         *self.nmi_pin_status.get_mut() = state.nmi_pin_status;
         *self.nmi_occured_in_this_frame.get_mut() = state.nmi_occured_in_this_frame;
         self.primary_oam = primary_oam;
-        self.secondary_oam = state.secondary_oam;
+        self.secondary_oam[..SPRITES_PER_SCANLINE].copy_from_slice(&state.secondary_oam);
         self.rendering_oam_counter = state.rendering_oam_counter;
         self.sprite_0_present = state.sprite_0_present;
         self.next_scanline_sprite_0_present = state.next_scanline_sprite_0_present;
         self.is_dma_request = state.is_dma_request;
         self.dma_request_address = state.dma_request_address;
         self.is_odd_frame = state.is_odd_frame;
+        self.backend = state.backend;
+        self.next_backend = state.next_backend;
+        self.sprite_0_hit_dot = state.sprite_0_hit_dot;
+        // the best guess until the next frame starts
+        self.frame_start_scroll = (self.vram_address_top_left, self.fine_x_scroll);
+    }
+
+    /// `true` if the vertical blank flag was set by one of the last 3 dots, which is
+    /// the last CPU cycle, so the frame is complete in the display buffer
+    pub fn vblank_started_in_last_cpu_cycle(&self) -> bool {
+        self.dots_since_vbl_set()
+            .is_some_and(|dots| (0..3).contains(&dots))
+    }
+
+    /// `true` if background or sprites rendering was enabled at any point during
+    /// the visible scanlines of the last frame
+    pub fn last_frame_rendering_enabled(&self) -> bool {
+        self.last_frame_rendering_enabled
+    }
+
+    /// `true` if all pixels of the last frame were the backdrop color
+    pub fn last_frame_is_backdrop_only(&self) -> bool {
+        !self.last_frame_non_backdrop_pixel
     }
 
     pub fn tv(&self) -> &TV {
         &self.tv
     }
+
+    pub fn tv_mut(&mut self) -> &mut TV {
+        &mut self.tv
+    }
 }
 
 impl<T> PPUCPUConnection for PPU2C02<T>
@@ -1189,6 +1613,9 @@ struct SavablePPUState {
 
     ppu_data_read_buffer: u8,
 
+    io_latch: u8,
+    io_latch_decay: [u8; 8],
+
     fine_x_scroll: u8,
 
     w_toggle: bool,
@@ -1216,6 +1643,10 @@ struct SavablePPUState {
     dma_request_address: u8,
 
     is_odd_frame: bool,
+
+    backend: PpuBackend,
+    next_backend: PpuBackend,
+    sprite_0_hit_dot: Option<u16>,
 }
 
 impl SavablePPUState {
@@ -1233,6 +1664,8 @@ impl SavablePPUState {
             vram_address_cur: ppu.vram_address_cur.get(),
             vram_address_top_left: ppu.vram_address_top_left,
             ppu_data_read_buffer: ppu.ppu_data_read_buffer.get(),
+            io_latch: ppu.io_latch.get(),
+            io_latch_decay: ppu.io_latch_decay.get(),
             fine_x_scroll: ppu.fine_x_scroll,
             w_toggle: ppu.w_toggle.get(),
             bg_pattern_shift_registers: ppu.bg_pattern_shift_registers,
@@ -1240,8 +1673,11 @@ impl SavablePPUState {
             nmi_pin_status: ppu.nmi_pin_status.get(),
             nmi_occured_in_this_frame: ppu.nmi_occured_in_this_frame.get(),
             primary_oam,
-            secondary_oam: ppu.secondary_oam,
-            rendering_oam_counter: ppu.rendering_oam_counter,
+            secondary_oam: ppu.secondary_oam[..SPRITES_PER_SCANLINE]
+                .try_into()
+                .unwrap(),
+            // the extra sprites of `sprite_limit_removed` are not saved
+            rendering_oam_counter: ppu.rendering_oam_counter.min(SPRITES_PER_SCANLINE as u8),
 
             // Since these are removed, we keep them in the save just to not corrupt
             // the files
@@ -1254,12 +1690,15 @@ impl SavablePPUState {
             is_dma_request: ppu.is_dma_request,
             dma_request_address: ppu.dma_request_address,
             is_odd_frame: ppu.is_odd_frame,
+            backend: ppu.backend,
+            next_backend: ppu.next_backend,
+            sprite_0_hit_dot: ppu.sprite_0_hit_dot,
         }
     }
 }
 
 impl<T: Bus + Savable> Savable for PPU2C02<T> {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         self.bus.save(writer)?;
 
         let state = SavablePPUState::from_ppu(self);
@@ -1272,7 +1711,7 @@ impl<T: Bus + Savable> Savable for PPU2C02<T> {
         Ok(())
     }
 
-    fn load<R: std::io::Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
         self.bus.load(reader)?;
 
         let state: SavablePPUState =
@@ -1286,3 +1725,41 @@ impl<T: Bus + Savable> Savable for PPU2C02<T> {
         Ok(())
     }
 }
+
+#[cfg(feature = "state-json")]
+impl<T: Bus + Savable + JsonSavable> JsonSavable for PPU2C02<T> {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "bus": self.bus.to_json(),
+            "state": to_json_value(&SavablePPUState::from_ppu(self)),
+        })
+    }
+
+    fn load_json(&mut self, mut value: serde_json::Value) -> Result<(), SaveError> {
+        self.bus.load_json(take_json_field(&mut value, "bus")?)?;
+
+        let state: SavablePPUState = from_json_value(take_json_field(&mut value, "state")?)?;
+        self.load_serialized_state(state);
+
+        Ok(())
+    }
+}
+
+/// the priority between the background and sprite pixels, returns the color location
+/// of the visible one
+fn mux_color_location(
+    background_color_location: u8,
+    sprite_color_location: u8,
+    background_priority: bool,
+) -> u8 {
+    if sprite_color_location != 0 && background_color_location != 0 {
+        // use background priority flag
+        if background_priority {
+            background_color_location
+        } else {
+            sprite_color_location
+        }
+    } else {
+        sprite_color_location | background_color_location
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, to_json_value, JsonSavable};
 use crate::common::{
     save_state::{Savable, SaveError},
     Bus, Device,
@@ -48,15 +50,32 @@ impl Bus for Palette {
 }
 
 impl Savable for Palette {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         writer.write_all(&self.palette_data)?;
 
         Ok(())
     }
 
-    fn load<R: std::io::Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
         reader.read_exact(&mut self.palette_data)?;
 
         Ok(())
     }
 }
+
+#[cfg(feature = "state-json")]
+impl JsonSavable for Palette {
+    fn to_json(&self) -> serde_json::Value {
+        to_json_value(&self.palette_data.as_slice())
+    }
+
+    fn load_json(&mut self, value: serde_json::Value) -> Result<(), SaveError> {
+        let data: Vec<u8> = from_json_value(value)?;
+        if data.len() != self.palette_data.len() {
+            return Err(SaveError::SerializationError);
+        }
+        self.palette_data.copy_from_slice(&data);
+
+        Ok(())
+    }
+}
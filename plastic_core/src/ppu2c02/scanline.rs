@@ -0,0 +1,262 @@
+//! The scanline renderer of [`PpuBackend::Scanline`], renders a whole scanline at
+//! its start instead of fetching and rendering pixel by pixel.
+
+use super::{mux_color_location, PpuBackend, StatusReg, PPU2C02};
+use crate::common::{save_state::Savable, Bus};
+use crate::display::TV_WIDTH;
+
+/// number of tiles fetched for a scanline, one more than the visible tiles
+/// to account for the fine X scroll
+const SCANLINE_TILES: usize = TV_WIDTH / 8 + 1;
+
+/// the dots of the visible scanlines handled by `run_scanline_render_cycle`, and the
+/// last dot that moves to the next scanline
+const EVENT_DOTS: [bool; 341] = {
+    let mut dots = [false; 341];
+    let events = [0, 1, 255, 256, 257, 261, 328, 336, 340];
+    let mut i = 0;
+    while i < events.len() {
+        dots[events[i]] = true;
+        i += 1;
+    }
+    dots
+};
+
+impl<T> PPU2C02<T>
+where
+    T: Bus + Savable,
+{
+    /// Advance a dot of a visible scanline without running the whole `clock`, if the
+    /// scanline backend has nothing to do in it, returns `false` otherwise
+    #[inline]
+    pub(super) fn skip_scanline_dot(&mut self) -> bool {
+        if self.scanline >= 240
+            || EVENT_DOTS[self.cycle as usize]
+            || self.sprite_0_hit_dot == Some(self.cycle)
+            || !self.reg_mask.rendering_enabled()
+        {
+            return false;
+        }
+
+        self.rendering_enabled_in_frame = true;
+        self.cycle += 1;
+        true
+    }
+
+    /// the scanline version of `run_render_cycle`, only the events that affect the
+    /// registers, the mapper or the CPU run at their dots, and the pixels are
+    /// generated all at once in dot 0
+    pub(super) fn run_scanline_render_cycle(&mut self) {
+        debug_assert_eq!(self.backend, PpuBackend::Scanline);
+
+        match self.cycle {
+            0 => self.render_scanline(),
+            1 => self.clear_secondary_oam(),
+            255 => self.evaluate_next_scanline_sprites(),
+            256 => self.increment_y_scroll(),
+            257 => self.restore_rendering_scroll_x(),
+            261 => self.reload_sprite_shift_registers(),
+            328 => self.fetch_next_scanline_tile_pattern(false),
+            336 => self.fetch_next_scanline_tile_pattern(true),
+            _ => {}
+        }
+
+        if self.sprite_0_hit_dot == Some(self.cycle) {
+            self.sprite_0_hit_dot = None;
+            self.reg_status.get_mut().insert(StatusReg::SPRITE_0_HIT);
+        }
+    }
+
+    /// Fetch the background tiles of the current scanline starting from `v`, and
+    /// return the color location of each pixel (`palette << 2 | color_bits`),
+    /// the first `fine_x` pixels are not visible.
+    fn fetch_scanline_background(&self) -> [u8; SCANLINE_TILES * 8] {
+        let mut pixels = [0; SCANLINE_TILES * 8];
+
+        let pattern_table = self.reg_control.background_pattern_address();
+        let mut v = self.vram_address_cur.get();
+        // 4 tiles share an attribute byte, and nametable reads don't reach the
+        // mapper, so read it only when the address changes
+        let mut attribute = None;
+
+        for tile_pixels in pixels.chunks_exact_mut(8) {
+            let tile = self.read_bus(0x2000 | v & 0xFFF);
+            let attribute_address = 0x23C0 | v & 0x0C00 | (v >> 4) & 0x38 | (v >> 2) & 0x07;
+            let attribute_byte = match attribute {
+                Some((address, byte)) if address == attribute_address => byte,
+                _ => {
+                    let byte = self.read_bus(attribute_address);
+                    attribute = Some((attribute_address, byte));
+                    byte
+                }
+            };
+            let [low, high] = self.fetch_pattern(pattern_table, tile, (v >> 12) as u8 & 0b111);
+
+            // the same as `reload_background_shift_registers`, bit 1 of coarse Y
+            // and coarse X select the 2 bits of the attribute byte
+            let palette = (attribute_byte >> ((v >> 4) & 0b100 | v & 0b10)) & 0b11;
+
+            for (i, pixel) in tile_pixels.iter_mut().enumerate() {
+                let bit = 7 - i;
+                let color_bits = ((high >> bit) & 1) << 1 | (low >> bit) & 1;
+                if color_bits != 0 {
+                    *pixel = palette << 2 | color_bits;
+                }
+            }
+
+            v = next_tile_address(v);
+        }
+
+        pixels
+    }
+
+    /// Read the pattern of the first (or `second`) tile of the next scanline, at the dot
+    /// the dot accurate backend fetches it. The tiles are fetched again when rendering
+    /// the line, but the mappers watching the PPU address bus (A12 for the MMC3 IRQ)
+    /// must see these reads at the same dots with both backends.
+    pub(super) fn fetch_next_scanline_tile_pattern(&self, second: bool) {
+        let mut v = self.vram_address_cur.get();
+        if second {
+            v = next_tile_address(v);
+        }
+
+        let tile = self.read_bus(0x2000 | v & 0xFFF);
+        self.fetch_pattern(
+            self.reg_control.background_pattern_address(),
+            tile,
+            (v >> 12) as u8 & 0b111,
+        );
+    }
+
+    /// Returns the sprite pixels of the current scanline as
+    /// `(color_location, behind_background, is_sprite_0)`
+    fn scanline_sprites(&self) -> [(u8, bool, bool); TV_WIDTH] {
+        let mut pixels = [(0, false, false); TV_WIDTH];
+
+        if !self.reg_mask.sprites_enabled() {
+            return pixels;
+        }
+
+        let start_x = if self.reg_mask.sprites_left_clipping_enabled() {
+            8
+        } else {
+            0
+        };
+
+        // the first sprite has priority, so draw in reverse to overwrite the others
+        for (i, sprite) in self
+            .rendering_oam
+            .iter()
+            .take(self.rendering_oam_counter as usize)
+            .enumerate()
+            .rev()
+        {
+            let attribute = sprite.get_attribute();
+            let sprite_x = sprite.read_offset(3) as u16;
+
+            // sprites are not rendered at x=255, same as the dot renderer
+            for x in sprite_x.max(start_x)..(sprite_x + 8).min(TV_WIDTH as u16 - 1) {
+                let color_bits = sprite.get_color_bits(x);
+
+                if color_bits != 0 {
+                    pixels[x as usize] = (
+                        // (1 << 4) to select the sprite table
+                        1 << 4 | attribute.palette() << 2 | color_bits,
+                        attribute.is_behind_background(),
+                        i == 0 && self.sprite_0_present,
+                    );
+                }
+            }
+        }
+
+        pixels
+    }
+
+    fn render_scanline(&mut self) {
+        self.sprite_0_hit_dot = None;
+
+        let background = self.fetch_scanline_background();
+        let sprites = self.scanline_sprites();
+
+        let background_enabled = self.reg_mask.background_enabled();
+        let background_start_x = if self.reg_mask.background_left_clipping_enabled() {
+            8
+        } else {
+            0
+        };
+        let fine_x = self.current_fine_x_scroll() as usize;
+
+        // palettes can't change in the middle of the scanline, so read them once
+        let mut palettes = [0; 32];
+        for (i, palette) in palettes.iter_mut().enumerate() {
+            *palette = self.read_bus(0x3F00 | i as u16);
+        }
+
+        // the same as `output_pixel`, but the mask register can't change in the
+        // middle of the scanline either
+        let output_enabled = self.tv.is_output_enabled();
+        let color_mask = if self.reg_mask.is_grayscale() {
+            0x30
+        } else {
+            0x3F
+        };
+        let emphasis = self.reg_mask.bits() >> 5;
+        let layer_map_enabled = self.tv.is_layer_map_enabled();
+
+        // the same as `mux_pixel`, but the frame tracking is updated once for the line
+        let mut non_backdrop_pixel = false;
+        let mut colors = [0; TV_WIDTH];
+        for (x, color) in colors.iter_mut().enumerate() {
+            let background_color_location = if background_enabled && x >= background_start_x {
+                background[x + fine_x]
+            } else {
+                0
+            };
+            let (sprite_color_location, background_priority, is_sprite_0) = sprites[x];
+
+            if is_sprite_0
+                && background_color_location != 0
+                && sprite_color_location != 0
+                && self.sprite_0_hit_dot.is_none()
+            {
+                self.sprite_0_hit_dot = Some(x as u16);
+            }
+
+            let color_location = mux_color_location(
+                background_color_location,
+                sprite_color_location,
+                background_priority,
+            );
+            non_backdrop_pixel |= color_location & 0b11 != 0;
+
+            if layer_map_enabled {
+                self.set_pixel_layer(
+                    x as u16,
+                    background_color_location,
+                    sprite_color_location,
+                    background_priority,
+                    is_sprite_0,
+                    color_location,
+                );
+            }
+
+            *color = palettes[color_location as usize] & color_mask;
+        }
+
+        if non_backdrop_pixel {
+            self.non_backdrop_pixel_in_frame = true;
+        }
+        if output_enabled {
+            self.tv.set_row(self.scanline as u32, &colors, emphasis);
+        }
+    }
+}
+
+/// Increment coarse X of the VRAM address `v`, and wrap to the next nametable horizontally
+fn next_tile_address(v: u16) -> u16 {
+    if v & 0b11111 == 31 {
+        (v & !0b11111) ^ 0x0400
+    } else {
+        v + 1
+    }
+}
@@ -59,8 +59,10 @@ impl Sprite {
         }
     }
 
+    /// the sprite is rendered one scanline after the y value stored in OAM,
+    /// but it does not wrap, so `0xFF` (and `0xFE`) are always hidden
     pub fn get_y(&self) -> u8 {
-        self.y
+        self.y.saturating_add(1)
     }
 
     /// for 8x8:
@@ -99,13 +101,7 @@ impl Sprite {
 
     pub fn read_offset(&self, offset: u8) -> u8 {
         match offset {
-            0 => {
-                if self.y != 0 {
-                    self.y - 1
-                } else {
-                    self.y
-                }
-            }
+            0 => self.y,
             1 => self.tile_index,
             2 => self.attributes.bits,
             3 => self.x,
@@ -121,12 +117,7 @@ impl Sprite {
             3 => &mut self.x,
             _ => unreachable!(),
         };
-        // y location is set to the position before the sprite (so weird)
-        // but do not wrap 255
-        *to_change = if offset == 0 && data != 255 {
-            data + 1
-        } else {
-            data
-        }
+
+        *to_change = data;
     }
 }
@@ -0,0 +1,581 @@
+#[cfg(test)]
+mod ppu_tests {
+    use super::super::{ppu2c02_registers::Register, PpuBackend, StatusReg, PPU2C02};
+    use crate::common::{
+        save_state::{Savable, SaveError},
+        Bus, Device,
+    };
+    use crate::display::{
+        LAYER_SOURCE_BACKDROP, LAYER_SOURCE_BACKGROUND, LAYER_SOURCE_MASK,
+        LAYER_SOURCE_SPRITE_FRONT, TV, TV_WIDTH,
+    };
+
+    struct DummyBus {
+        data: [u8; 0x4000],
+    }
+
+    impl Bus for DummyBus {
+        fn read(&self, address: u16, _device: Device) -> u8 {
+            self.data[address as usize & 0x3FFF]
+        }
+
+        fn write(&mut self, address: u16, data: u8, _device: Device) {
+            self.data[address as usize & 0x3FFF] = data;
+        }
+    }
+
+    impl Savable for DummyBus {
+        fn save(&self, _: &mut dyn std::io::Write) -> Result<(), SaveError> {
+            unreachable!()
+        }
+
+        fn load(&mut self, _: &mut dyn std::io::Read) -> Result<(), SaveError> {
+            unreachable!()
+        }
+    }
+
+    fn new_ppu() -> PPU2C02<DummyBus> {
+        PPU2C02::new(DummyBus { data: [0; 0x4000] }, TV::new())
+    }
+
+    /// returns `(t, v, x, w)`
+    fn registers(ppu: &PPU2C02<DummyBus>) -> (u16, u16, u8, bool) {
+        (
+            ppu.vram_address_top_left,
+            ppu.vram_address_cur.get(),
+            ppu.fine_x_scroll,
+            ppu.w_toggle.get(),
+        )
+    }
+
+    #[test]
+    fn documented_write_sequence() {
+        let mut ppu = new_ppu();
+
+        ppu.write_register(Register::Control, 0b0000_0000);
+        assert_eq!(registers(&ppu), (0x0000, 0x0000, 0, false));
+
+        let _ = ppu.read_register(Register::Status);
+        assert!(!ppu.w_toggle.get());
+
+        ppu.write_register(Register::Scroll, 0b0111_1101);
+        assert_eq!(registers(&ppu), (0b000_0000_0000_1111, 0x0000, 0b101, true));
+
+        ppu.write_register(Register::Scroll, 0b0101_1110);
+        assert_eq!(
+            registers(&ppu),
+            (0b110_0001_0110_1111, 0x0000, 0b101, false)
+        );
+
+        ppu.write_register(Register::PPUAddress, 0b0011_1101);
+        assert_eq!(registers(&ppu), (0b011_1101_0110_1111, 0x0000, 0b101, true));
+
+        ppu.write_register(Register::PPUAddress, 0b1111_0000);
+        assert_eq!(
+            registers(&ppu),
+            (0b011_1101_1111_0000, 0b011_1101_1111_0000, 0b101, false)
+        );
+    }
+
+    #[test]
+    fn control_nametable_merges_into_t() {
+        let mut ppu = new_ppu();
+
+        ppu.write_register(Register::Scroll, 0xFF);
+        ppu.write_register(Register::Scroll, 0xFF);
+        let (t, ..) = registers(&ppu);
+        assert_eq!(t & 0x0C00, 0);
+
+        ppu.write_register(Register::Control, 0b0000_0011);
+        let (new_t, ..) = registers(&ppu);
+        assert_eq!(new_t, t | 0x0C00);
+
+        ppu.write_register(Register::Control, 0b0000_0010);
+        let (new_t, ..) = registers(&ppu);
+        assert_eq!(new_t, t | 0x0800);
+    }
+
+    #[test]
+    fn address_first_write_clears_bit_14() {
+        let mut ppu = new_ppu();
+
+        // set fine Y to 7 (bits 12-14)
+        ppu.write_register(Register::Scroll, 0x00);
+        ppu.write_register(Register::Scroll, 0x07);
+        assert_eq!(registers(&ppu).0, 0x7000);
+
+        ppu.write_register(Register::PPUAddress, 0xFF);
+        assert_eq!(registers(&ppu), (0x3F00, 0x0000, 0, true));
+
+        ppu.write_register(Register::PPUAddress, 0x12);
+        assert_eq!(registers(&ppu), (0x3F12, 0x3F12, 0, false));
+    }
+
+    #[test]
+    fn status_read_resets_w() {
+        let mut ppu = new_ppu();
+
+        ppu.write_register(Register::PPUAddress, 0x21);
+        assert!(registers(&ppu).3);
+
+        let _ = ppu.read_register(Register::Status);
+        assert!(!registers(&ppu).3);
+
+        // this is a first write again
+        ppu.write_register(Register::PPUAddress, 0x23);
+        ppu.write_register(Register::PPUAddress, 0x45);
+        assert_eq!(registers(&ppu).1, 0x2345);
+    }
+
+    #[test]
+    fn split_scroll_2006_2005_2005_2006() {
+        let mut ppu = new_ppu();
+
+        let nametable = 1u8;
+        let x = 0x7Du8;
+        let y = 0x5Eu8;
+
+        ppu.write_register(Register::PPUAddress, nametable << 2);
+        assert_eq!(registers(&ppu), (0x0400, 0x0000, 0, true));
+
+        ppu.write_register(Register::Scroll, y);
+        assert_eq!(registers(&ppu), (0x6560, 0x0000, 0, false));
+
+        ppu.write_register(Register::Scroll, x);
+        assert_eq!(registers(&ppu), (0x656F, 0x0000, 0b101, true));
+
+        // the low byte of the address of the coarse X and Y (the top bits are shifted out)
+        ppu.write_register(Register::PPUAddress, ((y & 0xF8) << 2) | (x >> 3));
+        assert_eq!(registers(&ppu), (0x656F, 0x656F, 0b101, false));
+
+        // v now has the full scroll position
+        assert_eq!(ppu.current_fine_y_scroll(), y & 0b111);
+        assert_eq!(ppu.current_coarse_y_scroll(), y >> 3);
+        assert_eq!(ppu.current_coarse_x_scroll(), x >> 3);
+        assert_eq!(ppu.current_nametable(), nametable as u16);
+        assert_eq!(ppu.current_fine_x_scroll(), x & 0b111);
+    }
+
+    #[test]
+    fn horizontal_and_vertical_copy() {
+        let mut ppu = new_ppu();
+
+        ppu.write_register(Register::Control, 0b0000_0011);
+        ppu.write_register(Register::Scroll, 0x7D);
+        ppu.write_register(Register::Scroll, 0x5E);
+        // t = 0x6D6F
+
+        ppu.restore_rendering_scroll_x();
+        assert_eq!(registers(&ppu).1, 0x040F);
+
+        ppu.restore_rendering_scroll_y();
+        assert_eq!(registers(&ppu).1, 0x6D6F);
+    }
+
+    #[test]
+    fn vram_increment_32_wraps_at_15_bits() {
+        let mut ppu = new_ppu();
+
+        // increment by 32
+        ppu.write_register(Register::Control, 0b0000_0100);
+        ppu.write_register(Register::PPUAddress, 0x20);
+        ppu.write_register(Register::PPUAddress, 0x1F);
+
+        let mut expected = 0x201Fu16;
+        for _ in 0..2048 {
+            assert_eq!(registers(&ppu).1, expected);
+            assert_eq!(ppu.current_coarse_x_scroll(), 0x1F);
+            assert_eq!(
+                ppu.current_coarse_y_scroll(),
+                ((expected >> 5) & 0x1F) as u8
+            );
+            assert_eq!(ppu.current_fine_y_scroll(), (expected >> 12) as u8);
+
+            ppu.write_register(Register::PPUData, 0);
+            expected = (expected + 32) & 0x7FFF;
+        }
+
+        // 2048 * 32 is 0x10000, so it wraps around twice and ends where it started
+        assert_eq!(registers(&ppu).1, 0x201F);
+    }
+
+    #[test]
+    fn vram_increment_1_wraps_at_15_bits() {
+        let ppu = new_ppu();
+        // can't be set with `$2006`, but can be reached with scrolling
+        ppu.vram_address_cur.set(0x7FFF);
+
+        let _ = ppu.read_register(Register::PPUData);
+        assert_eq!(registers(&ppu).1, 0x0000);
+    }
+
+    fn clock_until(ppu: &mut PPU2C02<DummyBus>, scanline: u16, cycle: u16) {
+        while ppu.scanline != scanline || ppu.cycle != cycle {
+            ppu.clock();
+        }
+    }
+
+    #[test]
+    fn chr_change_between_prefetched_tiles() {
+        let mut ppu = new_ppu();
+        ppu.tv_mut().set_layer_map_enabled(true);
+        // background enabled, including the leftmost 8 pixels.
+        // all nametable entries are tile 0, which is empty for now
+        ppu.write_register(Register::Mask, 0b0000_1010);
+
+        // between the fetch of the first tile (dots 321-328) and the second
+        // tile (dots 329-336) of scanline 11, change the pattern of tile 0, as
+        // a mapper CHR bank switch would do
+        clock_until(&mut ppu, 10, 330);
+        ppu.bus.data[0..8].fill(0xFF);
+
+        // finish the frame
+        clock_until(&mut ppu, 240, 2);
+
+        let layer_map = ppu.tv().display_layer_map().unwrap();
+        let row = &layer_map[11 * TV_WIDTH..12 * TV_WIDTH];
+        assert!(row[..8]
+            .iter()
+            .all(|&layer| layer & LAYER_SOURCE_MASK == LAYER_SOURCE_BACKDROP));
+        assert!(row[8..]
+            .iter()
+            .all(|&layer| layer & LAYER_SOURCE_MASK == LAYER_SOURCE_BACKGROUND));
+    }
+
+    #[test]
+    fn rendering_disabled_mid_scanline_freezes_v() {
+        let mut ppu = new_ppu();
+        // sprite 0 on scanlines 11-18
+        ppu.write_sprite_byte(0, 10);
+        ppu.write_register(Register::Mask, 0b0001_1110);
+
+        clock_until(&mut ppu, 10, 100);
+        ppu.write_register(Register::Mask, 0);
+        let frozen = ppu.vram_address_cur.get();
+
+        // the coarse X increments, the fine Y increment at dot 256 and the
+        // horizontal copy at dot 257 are skipped
+        clock_until(&mut ppu, 11, 100);
+        assert_eq!(ppu.vram_address_cur.get(), frozen);
+        // and the sprites of scanline 11 were not evaluated
+        assert_eq!(ppu.rendering_oam_counter, 0);
+
+        // continues from the frozen address, coarse X is incremented at dots 104 and 112
+        ppu.write_register(Register::Mask, 0b0001_1110);
+        clock_until(&mut ppu, 11, 113);
+        assert_eq!(ppu.vram_address_cur.get(), frozen + 2);
+
+        // the evaluation resumes for scanline 12
+        clock_until(&mut ppu, 11, 259);
+        assert_eq!(ppu.rendering_oam_counter, 1);
+    }
+
+    #[test]
+    fn pre_render_vertical_copy_dots() {
+        let mut ppu = new_ppu();
+        ppu.write_register(Register::Mask, 0b0000_1010);
+
+        // change the vertical bits of `t` after dot 1 of the pre-render scanline,
+        // the copy during dots 280-304 still takes them
+        clock_until(&mut ppu, 261, 100);
+        ppu.write_register(Register::Scroll, 0);
+        ppu.write_register(Register::Scroll, 0b0101_1110);
+        let vertical_bits = 0b111_1011_1110_0000;
+        assert_ne!(
+            ppu.vram_address_cur.get() & vertical_bits,
+            ppu.vram_address_top_left & vertical_bits
+        );
+
+        clock_until(&mut ppu, 261, 305);
+        assert_eq!(
+            ppu.vram_address_cur.get() & vertical_bits,
+            ppu.vram_address_top_left & vertical_bits
+        );
+    }
+
+    #[test]
+    fn backend_switches_at_frame_end() {
+        let mut ppu = new_ppu();
+        ppu.tv_mut().set_layer_map_enabled(true);
+        ppu.write_register(Register::Mask, 0b0000_1010);
+
+        clock_until(&mut ppu, 10, 0);
+        ppu.set_backend(PpuBackend::Scanline);
+        assert_eq!(ppu.selected_backend(), PpuBackend::Scanline);
+        assert_eq!(ppu.backend, PpuBackend::DotAccurate);
+
+        clock_until(&mut ppu, 240, 2);
+        assert_eq!(ppu.backend, PpuBackend::Scanline);
+
+        // the scanline backend renders the whole line at its start, so the
+        // same change as `chr_change_between_prefetched_tiles` affects all
+        // the pixels of the line
+        clock_until(&mut ppu, 10, 330);
+        ppu.bus.data[0..8].fill(0xFF);
+        clock_until(&mut ppu, 240, 2);
+
+        let layer_map = ppu.tv().display_layer_map().unwrap();
+        assert!(layer_map[11 * TV_WIDTH..12 * TV_WIDTH]
+            .iter()
+            .all(|&layer| layer & LAYER_SOURCE_MASK == LAYER_SOURCE_BACKGROUND));
+    }
+
+    #[test]
+    fn io_latch_status_partial_drive() {
+        let mut ppu = new_ppu();
+
+        ppu.write_register(Register::OmaAddress, 0x1F);
+        // write-only registers return the latch
+        assert_eq!(ppu.read_register(Register::Control), 0x1F);
+        assert_eq!(ppu.read_register(Register::Mask), 0x1F);
+
+        // only the top 3 bits are driven by the status register
+        ppu.reg_status.get_mut().insert(StatusReg::VERTICAL_BLANK);
+        assert_eq!(ppu.read_register(Register::Status), 0x9F);
+        assert_eq!(ppu.read_register(Register::Scroll), 0x9F);
+
+        // vblank is cleared and bit 7 is driven to 0
+        assert_eq!(ppu.read_register(Register::Status), 0x1F);
+        assert_eq!(ppu.read_register(Register::PPUAddress), 0x1F);
+    }
+
+    #[test]
+    fn io_latch_palette_read() {
+        let mut ppu = new_ppu();
+        ppu.bus.data[0x3F00] = 0x2A;
+
+        ppu.write_register(Register::PPUAddress, 0x3F);
+        ppu.write_register(Register::PPUAddress, 0x00);
+        ppu.write_register(Register::OmaAddress, 0xC0);
+
+        // palette entries only drive the low 6 bits
+        assert_eq!(ppu.read_register(Register::PPUData), 0xEA);
+        assert_eq!(ppu.read_register(Register::Control), 0xEA);
+    }
+
+    #[test]
+    fn io_latch_decay() {
+        let mut ppu = new_ppu();
+        ppu.write_register(Register::OmaAddress, 0xFF);
+
+        let clock_frame = |ppu: &mut PPU2C02<DummyBus>| {
+            clock_until(ppu, 240, 1);
+            ppu.clock();
+        };
+
+        for _ in 0..35 {
+            clock_frame(&mut ppu);
+        }
+        assert_eq!(ppu.read_register(Register::Control), 0xFF);
+
+        // refreshing some bits delays their decay
+        ppu.reg_status.get_mut().insert(StatusReg::VERTICAL_BLANK);
+        assert_eq!(ppu.read_register(Register::Status), 0x9F);
+
+        clock_frame(&mut ppu);
+        assert_eq!(ppu.read_register(Register::Control), 0x80);
+
+        for _ in 0..35 {
+            clock_frame(&mut ppu);
+        }
+        assert_eq!(ppu.read_register(Register::Control), 0x00);
+    }
+
+    /// Read `$2002` in scanline 241 when `cycle` is the next dot to run, returns the
+    /// read bit 7, and `(vertical blank flag, NMI pin)` at the end of the dot 10
+    fn read_status_at_vblank_start(cycle: u16) -> (bool, (bool, bool)) {
+        let mut ppu = new_ppu();
+        ppu.write_register(Register::Control, 0x80);
+
+        clock_until(&mut ppu, 241, cycle);
+        let status = ppu.read_register(Register::Status);
+        clock_until(&mut ppu, 241, 11);
+
+        (
+            status & 0x80 != 0,
+            (
+                ppu.reg_status.get().contains(StatusReg::VERTICAL_BLANK),
+                ppu.nmi_pin_status.get(),
+            ),
+        )
+    }
+
+    #[test]
+    fn status_read_before_vblank_start() {
+        // the flag is set after the read, and NMI is not affected
+        assert_eq!(read_status_at_vblank_start(0), (false, (true, true)));
+        assert_eq!(read_status_at_vblank_start(1), (false, (true, true)));
+    }
+
+    #[test]
+    fn status_read_at_vblank_start() {
+        // the flag was just set, it is read as clear and NMI is suppressed
+        assert_eq!(read_status_at_vblank_start(2), (false, (false, false)));
+    }
+
+    #[test]
+    fn status_read_after_vblank_start() {
+        // the flag is read, and NMI is still suppressed for 2 more dots
+        assert_eq!(read_status_at_vblank_start(3), (true, (false, false)));
+        assert_eq!(read_status_at_vblank_start(4), (true, (false, false)));
+        // too late, NMI already happened
+        assert_eq!(read_status_at_vblank_start(5), (true, (false, true)));
+    }
+
+    /// Disable NMI in scanline 241 when `cycle` is the next dot to run, and enable it
+    /// again at dot 20, returns the NMI pin right after each of the writes
+    fn disable_nmi_at_vblank_start(cycle: u16) -> (bool, bool) {
+        let mut ppu = new_ppu();
+        ppu.write_register(Register::Control, 0x80);
+
+        clock_until(&mut ppu, 241, cycle);
+        ppu.write_register(Register::Control, 0x00);
+        let after_disable = ppu.nmi_pin_status.get();
+        clock_until(&mut ppu, 241, 20);
+        ppu.write_register(Register::Control, 0x80);
+
+        (after_disable, ppu.nmi_pin_status.get())
+    }
+
+    #[test]
+    fn nmi_disable_around_vblank_start() {
+        // before and up to 2 dots after the flag is set, the NMI of this frame is
+        // cancelled, and enabling it again doesn't bring it back
+        for cycle in 0..=4 {
+            assert_eq!(
+                disable_nmi_at_vblank_start(cycle),
+                (false, false),
+                "{cycle}"
+            );
+        }
+        // too late, the NMI was already raised, and enabling it again is allowed to
+        // raise another one
+        assert_eq!(disable_nmi_at_vblank_start(5), (true, true));
+        assert_eq!(disable_nmi_at_vblank_start(6), (true, true));
+    }
+
+    #[test]
+    fn status_multiple_reads_clear_vblank() {
+        let mut ppu = new_ppu();
+        clock_until(&mut ppu, 241, 20);
+
+        // every read has the side effects, like a dummy read followed by the real
+        // read of the same instruction, the second one sees the flag cleared
+        assert_eq!(ppu.read_register(Register::Status) & 0x80, 0x80);
+        assert_eq!(ppu.read_register(Register::Status) & 0x80, 0x00);
+    }
+
+    /// Render a frame with 12 sprites on scanlines 21-28, returns the number of sprites
+    /// visible in scanline 24 and the sprite overflow flag
+    fn render_12_sprites_line(backend: PpuBackend, limit_removed: bool) -> (usize, bool) {
+        let mut ppu = new_ppu();
+        ppu.tv_mut().set_layer_map_enabled(true);
+        ppu.set_sprite_limit_removed(limit_removed);
+        ppu.set_backend(backend);
+        // tile 1 is solid
+        ppu.bus.data[0x10..0x18].fill(0xFF);
+
+        for i in 0..12 {
+            for (offset, data) in [20, 1, 0, i * 16].into_iter().enumerate() {
+                ppu.write_sprite_byte(i * 4 + offset as u8, data);
+            }
+        }
+        // sprites enabled, including the leftmost 8 pixels
+        ppu.write_register(Register::Mask, 0b0001_0100);
+
+        // finish the frame to apply the backend, then render a full frame
+        clock_until(&mut ppu, 240, 2);
+        clock_until(&mut ppu, 240, 1);
+
+        let layer_map = ppu.tv().display_layer_map().unwrap();
+        let row = &layer_map[24 * TV_WIDTH..25 * TV_WIDTH];
+        let visible = (0..12)
+            .filter(|i| row[i * 16] & LAYER_SOURCE_MASK == LAYER_SOURCE_SPRITE_FRONT)
+            .count();
+
+        (
+            visible,
+            ppu.reg_status.get().contains(StatusReg::SPRITE_OVERFLOW),
+        )
+    }
+
+    #[test]
+    fn sprite_limit() {
+        for backend in [PpuBackend::DotAccurate, PpuBackend::Scanline] {
+            assert_eq!(render_12_sprites_line(backend, false), (8, true));
+            assert_eq!(render_12_sprites_line(backend, true), (12, true));
+        }
+    }
+
+    /// Sprite 0 is out of range, sprite 1 is in range of scanline 11
+    fn oam_read_ppu(backend: PpuBackend) -> PPU2C02<DummyBus> {
+        let mut ppu = new_ppu();
+        ppu.set_backend(backend);
+        for (address, data) in [50, 0, 0, 0, 10, 0x21, 0x02, 0x33].into_iter().enumerate() {
+            ppu.write_sprite_byte(address as u8, data);
+        }
+        ppu.write_register(Register::Mask, 0b0001_1110);
+        // apply the backend
+        clock_until(&mut ppu, 240, 2);
+        ppu
+    }
+
+    fn read_oam_at(ppu: &mut PPU2C02<DummyBus>, scanline: u16, cycle: u16) -> u8 {
+        clock_until(ppu, scanline, cycle);
+        ppu.read_register(Register::OmaData)
+    }
+
+    #[test]
+    fn oam_data_read_while_rendering() {
+        let mut ppu = oam_read_ppu(PpuBackend::DotAccurate);
+
+        // secondary OAM clear
+        assert_eq!(read_oam_at(&mut ppu, 10, 1), 0xFF);
+        assert_eq!(read_oam_at(&mut ppu, 10, 64), 0xFF);
+
+        // evaluation, a byte every 2 dots: Y of sprite 0, then all of sprite 1,
+        // then the Y of the rest
+        for (cycle, expected) in [
+            (65, 50),
+            (66, 50),
+            (67, 10),
+            (69, 0x21),
+            (71, 0x02),
+            (73, 0x33),
+            (75, 0),
+            (256, 50),
+        ] {
+            assert_eq!(read_oam_at(&mut ppu, 10, cycle), expected, "dot {}", cycle);
+        }
+
+        // sprite fetches, Y, tile, attribute and X 5 times, then the empty slots
+        for (cycle, expected) in [
+            (257, 10),
+            (258, 0x21),
+            (259, 0x02),
+            (260, 0x33),
+            (264, 0x33),
+            (265, 0xFF),
+            (320, 0xFF),
+        ] {
+            assert_eq!(read_oam_at(&mut ppu, 10, cycle), expected, "dot {}", cycle);
+        }
+
+        assert_eq!(read_oam_at(&mut ppu, 10, 321), 10);
+        assert_eq!(read_oam_at(&mut ppu, 11, 0), 10);
+
+        // outside rendering, the OAM at OAMADDR
+        assert_eq!(read_oam_at(&mut ppu, 245, 0), 50);
+    }
+
+    #[test]
+    fn oam_data_read_scanline_backend() {
+        let mut ppu = oam_read_ppu(PpuBackend::Scanline);
+
+        // the OAM at OAMADDR is read
+        ppu.write_register(Register::OmaAddress, 5);
+        for cycle in [1, 65, 69, 257, 321] {
+            assert_eq!(read_oam_at(&mut ppu, 10, cycle), 0x21, "dot {}", cycle);
+        }
+    }
+}
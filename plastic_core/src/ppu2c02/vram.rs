@@ -1,41 +1,74 @@
+use super::NametableSource;
+#[cfg(feature = "state-json")]
+use crate::common::save_state::{from_json_value, to_json_value, JsonSavable};
 use crate::common::{
     save_state::{Savable, SaveError},
     Bus, Device, MirroringMode, MirroringProvider,
 };
 use std::{cell::RefCell, rc::Rc};
 
+/// The 2KB nametable RAM of the console, in `FourScreen` mode it only holds the
+/// first 2 nametables and the cartridge provides the RAM for the other 2
 pub struct VRam {
-    /// this have 4 blocks, only the first 2 are used for `Vertical`, `Horizontal`,
-    /// and `SingleScreen` mirroring modes. The remaining 2 blocks are used for
-    /// `FourScreen` mode
-    vram_data: [u8; 0x1000],
+    vram_data: [u8; 0x800],
     mirroring_provider: Rc<RefCell<dyn MirroringProvider>>,
 }
 
 impl VRam {
     pub fn new(mirroring_provider: Rc<RefCell<dyn MirroringProvider>>) -> Self {
         Self {
-            vram_data: [0; 0x1000],
+            vram_data: [0; 0x800],
             mirroring_provider,
         }
     }
 
-    fn map_address(&self, address: u16) -> usize {
+    /// The index of the 1KB block of VRAM used by the logical `nametable` (0-3)
+    /// with the current mirroring, blocks 2 and 3 are the nametable RAM of
+    /// four-screen cartridges
+    pub fn physical_nametable(&self, nametable: u8) -> usize {
+        let address = (nametable as u16 & 0b11) << 10;
+
+        match self.map_address(address) {
+            Some(address) => address >> 10,
+            None => nametable as usize & 0b11,
+        }
+    }
+
+    /// The memory used by the logical `nametable` (0-3) with the current mirroring
+    pub fn nametable_source(&self, nametable: u8) -> NametableSource {
+        match self.physical_nametable(nametable) {
+            0 => NametableSource::CiramA,
+            1 => NametableSource::CiramB,
+            page => NametableSource::CartridgeVram(page as u8 - 2),
+        }
+    }
+
+    /// The upper 2 nametables are in the cartridge RAM in `FourScreen` mode
+    pub fn is_on_cartridge(&self, address: u16) -> bool {
+        self.map_address(address).is_none()
+    }
+
+    /// Read `address` from the VRAM, or `None` if it is in the cartridge RAM. The same
+    /// as checking [`is_on_cartridge`](Self::is_on_cartridge) before reading, but gets
+    /// the mirroring from the cartridge only once, as the PPU reads the nametables a lot
+    pub fn try_read(&self, address: u16) -> Option<u8> {
+        self.map_address(address)
+            .map(|address| self.vram_data[address])
+    }
+
+    /// `None` for the addresses in the cartridge RAM
+    fn map_address(&self, address: u16) -> Option<usize> {
         let block_num = match self.mirroring_provider.borrow().mirroring_mode() {
-            MirroringMode::Vertical => (address >> 10) & 1,
+            MirroringMode::FourScreen if address & 0x800 != 0 => return None,
+            MirroringMode::Vertical | MirroringMode::FourScreen => (address >> 10) & 1,
             MirroringMode::Horizontal => (address >> 11) & 1,
             MirroringMode::SingleScreenLowBank => 0,
             MirroringMode::SingleScreenHighBank => 1,
-            MirroringMode::FourScreen => {
-                // directly return the address, as there is no mirroring, and
-                // all the vram address is being used
-                return address as usize & 0xFFF;
-            }
         } as usize;
 
         let start_address = block_num << 10;
 
-        start_address + (address as usize & 0x3FF)
+        Some(start_address + (address as usize & 0x3FF))
     }
 }
 
@@ -43,29 +76,45 @@ impl Bus for VRam {
     fn read(&self, address: u16, device: Device) -> u8 {
         assert!(device == Device::Ppu);
 
-        let address = self.map_address(address);
-
-        self.vram_data[address]
+        // the cartridge RAM is read by the `PPUBus`
+        self.try_read(address).unwrap_or(0)
     }
     fn write(&mut self, address: u16, data: u8, device: Device) {
         assert!(device == Device::Ppu);
 
-        let address = self.map_address(address);
-
-        self.vram_data[address] = data;
+        if let Some(address) = self.map_address(address) {
+            self.vram_data[address] = data;
+        }
     }
 }
 
 impl Savable for VRam {
-    fn save<W: std::io::Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+    fn save(&self, writer: &mut dyn std::io::Write) -> Result<(), SaveError> {
         writer.write_all(&self.vram_data)?;
 
         Ok(())
     }
 
-    fn load<R: std::io::Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+    fn load(&mut self, reader: &mut dyn std::io::Read) -> Result<(), SaveError> {
         reader.read_exact(&mut self.vram_data)?;
 
         Ok(())
     }
 }
+
+#[cfg(feature = "state-json")]
+impl JsonSavable for VRam {
+    fn to_json(&self) -> serde_json::Value {
+        to_json_value(&self.vram_data.as_slice())
+    }
+
+    fn load_json(&mut self, value: serde_json::Value) -> Result<(), SaveError> {
+        let data: Vec<u8> = from_json_value(value)?;
+        if data.len() != self.vram_data.len() {
+            return Err(SaveError::SerializationError);
+        }
+        self.vram_data.copy_from_slice(&data);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! A lightweight profiler measuring the wall time spent in each component of the
+//! emulator, to find which one dominates on slow hardware.
+//!
+//! Enabled with [`NES::set_profiling`], after that every frame run with
+//! [`NES::clock_for_frame`] is measured and reported with [`NES::profile`]. The time is
+//! measured around the component clock calls of every CPU cycle, so it is coarse and
+//! makes the emulation slower while enabled. When disabled, the cost is one branch per
+//! CPU cycle.
+
+use crate::NES;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// The number of frames averaged in [`NES::profile_average`]
+const AVERAGE_FRAMES: usize = 60;
+
+/// The wall time spent in each component while running a frame, in nanoseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameProfile {
+    /// running instructions and DMA, including the register accesses to other components
+    pub cpu_ns: u64,
+    pub ppu_ns: u64,
+    pub apu_ns: u64,
+    /// the rest of the frame: the cartridge, statistics and the frame loop
+    pub other_ns: u64,
+}
+
+impl FrameProfile {
+    /// The wall time of the whole frame
+    pub fn total_ns(&self) -> u64 {
+        self.cpu_ns + self.ppu_ns + self.apu_ns + self.other_ns
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Component {
+    Cpu,
+    Ppu,
+    Apu,
+}
+
+/// Measures the time since the last lap, `None` if profiling is disabled
+pub(crate) struct ProfileTimer(Option<Instant>);
+
+impl ProfileTimer {
+    /// Add the time since the last lap to `component` of the current frame
+    #[inline]
+    pub(crate) fn lap(&mut self, profiler: &mut Profiler, component: Component) {
+        if let Some(last) = self.0.as_mut() {
+            let now = Instant::now();
+            let elapsed = now.duration_since(*last).as_nanos() as u64;
+            *last = now;
+
+            let frame = &mut profiler.frame;
+            match component {
+                Component::Cpu => frame.cpu_ns += elapsed,
+                Component::Ppu => frame.ppu_ns += elapsed,
+                Component::Apu => frame.apu_ns += elapsed,
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    enabled: bool,
+    /// the components of the frame being run, `other_ns` is computed at the end
+    frame: FrameProfile,
+    /// the wall time of the frame being run, it may be run over multiple calls
+    frame_total_ns: u64,
+    last: FrameProfile,
+    /// the last `AVERAGE_FRAMES` frames
+    history: VecDeque<FrameProfile>,
+}
+
+impl Profiler {
+    /// A timer for the components of one CPU cycle
+    #[inline]
+    pub(crate) fn timer(&self) -> ProfileTimer {
+        ProfileTimer(self.enabled.then(Instant::now))
+    }
+
+    /// The start of a call running a part of a frame, `None` if disabled
+    pub(crate) fn start_call(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    /// Add the wall time of a call started with [`Profiler::start_call`] to the frame
+    pub(crate) fn end_call(&mut self, call_start: Option<Instant>) {
+        if let Some(call_start) = call_start {
+            self.frame_total_ns += call_start.elapsed().as_nanos() as u64;
+        }
+    }
+
+    pub(crate) fn finish_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        let components = self.frame.cpu_ns + self.frame.ppu_ns + self.frame.apu_ns;
+        self.frame.other_ns = self.frame_total_ns.saturating_sub(components);
+        self.last = std::mem::take(&mut self.frame);
+        self.frame_total_ns = 0;
+
+        if self.history.len() == AVERAGE_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.last);
+    }
+}
+
+impl NES {
+    /// Measure the wall time of the CPU, PPU and APU in every frame, disabled by default.
+    ///
+    /// Disabling clears the measurements.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler = Profiler {
+            enabled,
+            ..Profiler::default()
+        };
+    }
+
+    /// The time breakdown of the last frame, all zeros if profiling is disabled,
+    /// see [`NES::set_profiling`]
+    pub fn profile(&self) -> FrameProfile {
+        self.profiler.last
+    }
+
+    /// The average time breakdown of the last 60 frames (or less if fewer were run)
+    pub fn profile_average(&self) -> FrameProfile {
+        let history = &self.profiler.history;
+        if history.is_empty() {
+            return FrameProfile::default();
+        }
+
+        let count = history.len() as u64;
+        let sum = history
+            .iter()
+            .fold(FrameProfile::default(), |sum, frame| FrameProfile {
+                cpu_ns: sum.cpu_ns + frame.cpu_ns,
+                ppu_ns: sum.ppu_ns + frame.ppu_ns,
+                apu_ns: sum.apu_ns + frame.apu_ns,
+                other_ns: sum.other_ns + frame.other_ns,
+            });
+
+        FrameProfile {
+            cpu_ns: sum.cpu_ns / count,
+            ppu_ns: sum.ppu_ns / count,
+            apu_ns: sum.apu_ns / count,
+            other_ns: sum.other_ns / count,
+        }
+    }
+}
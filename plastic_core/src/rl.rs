@@ -0,0 +1,205 @@
+//! Helpers for training reinforcement learning agents on NES games.
+//!
+//! The main entry point is [`NES::rl_step`], which applies an action, advances the emulator
+//! some frames and returns an [`RlObservation`]. What to extract from the emulator is configured
+//! with [`NES::set_rl_config`].
+//!
+//! To record datasets, [`NES::run_frames_batch`] runs many frames with one input per frame and
+//! collects the outputs into a reusable [`BatchOutput`].
+//!
+//! Emulation is deterministic, so starting from the same [`StateSnapshot`] (see [`NES::rl_reset_to`])
+//! and applying the same actions will always produce the same observations.
+
+use crate::nes_display::{frame_hash, TV_BUFFER_SIZE};
+use crate::{NESKey, SaveError, StateSnapshot, NES};
+
+/// Predicate on the console's 2KB RAM deciding if the episode is done
+pub type DonePredicate = Box<dyn Fn(&[u8]) -> bool>;
+
+/// Configuration of what [`NES::rl_step`] extracts from the emulator.
+#[derive(Default)]
+pub struct RlConfig {
+    /// Addresses to read from the CPU RAM (`0x0000..0x0800`, mirrors are allowed)
+    /// into [`RlObservation::ram`], for example score or lives addresses.
+    pub ram_addresses: Vec<u16>,
+    /// If set, [`RlObservation::done`] will be the result of this predicate on the RAM
+    pub done_predicate: Option<DonePredicate>,
+    /// If `true`, all frames except the last one of a step will not be rendered into the
+    /// pixel buffer, which is faster
+    pub skip_intermediate_frames: bool,
+    /// If `true`, [`RlObservation::pixels`] will be the palette indices of the pixels
+    /// (see [`NES::pixel_index_buffer`]) instead of RGB, which is 3 times smaller
+    pub indexed_pixels: bool,
+}
+
+/// The result of [`NES::rl_step`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RlObservation {
+    /// The pixel buffer of the last frame, same as [`NES::pixel_buffer`], or
+    /// [`NES::pixel_index_buffer`] if [`RlConfig::indexed_pixels`] is set
+    pub pixels: Vec<u8>,
+    /// The values of [`RlConfig::ram_addresses`] in order
+    pub ram: Vec<u8>,
+    /// The result of [`RlConfig::done_predicate`], `false` if not set
+    pub done: bool,
+}
+
+/// Information about a single frame run with [`NES::run_frames_batch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchFrame {
+    /// [`frame_hash`] of the pixels of the frame
+    pub hash: u64,
+    /// Same as [`FrameStats::rendering_was_enabled`](crate::FrameStats::rendering_was_enabled)
+    pub rendering_enabled: bool,
+}
+
+/// The outputs of [`NES::run_frames_batch`].
+///
+/// The buffers are cleared at the start of every batch but keep their allocation, so
+/// reusing the same [`BatchOutput`] for all batches doesn't allocate once the buffers
+/// are large enough.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchOutput {
+    /// The pixels of all the frames one after the other, each [`TV_BUFFER_SIZE`] bytes,
+    /// the same as [`NES::pixel_buffer`]
+    pub pixels: Vec<u8>,
+    /// The audio of all the frames one after the other, the same as [`NES::audio_buffer`]
+    pub audio: Vec<f32>,
+    /// The start of the audio of every frame in [`BatchOutput::audio`], with an extra
+    /// entry at the end, so frame `i` is `audio[audio_offsets[i]..audio_offsets[i + 1]]`
+    pub audio_offsets: Vec<usize>,
+    /// Information about every frame
+    pub frames: Vec<BatchFrame>,
+}
+
+impl BatchOutput {
+    /// The number of frames in the output
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The pixels of frame `index`
+    pub fn frame_pixels(&self, index: usize) -> &[u8] {
+        &self.pixels[index * TV_BUFFER_SIZE..(index + 1) * TV_BUFFER_SIZE]
+    }
+
+    /// The audio samples of frame `index`
+    pub fn frame_audio(&self, index: usize) -> &[f32] {
+        &self.audio[self.audio_offsets[index]..self.audio_offsets[index + 1]]
+    }
+
+    fn clear(&mut self) {
+        self.pixels.clear();
+        self.audio.clear();
+        self.audio_offsets.clear();
+        self.audio_offsets.push(0);
+        self.frames.clear();
+    }
+}
+
+impl NES {
+    /// Set the configuration used by [`NES::rl_step`].
+    pub fn set_rl_config(&mut self, config: RlConfig) {
+        self.rl_config = config;
+    }
+
+    /// Apply the action, and run the emulator for `frames` frames.
+    ///
+    /// `action_bits` is the state of the controller, where each bit is the
+    /// same as the value of [`NESKey`] (A is bit 0, Right is bit 7). The action
+    /// is held for all the frames.
+    pub fn rl_step(&mut self, action_bits: u8, frames: u32) -> RlObservation {
+        for key in NESKey::ALL {
+            self.set_controller_state(key, action_bits & key as u8 != 0);
+        }
+
+        let skip = self.rl_config.skip_intermediate_frames;
+        for i in 0..frames {
+            // `clock_for_frame` is not aligned with the start of the PPU frame, so render
+            // the last 2 frames, to make sure the last full frame is rendered completely
+            self.set_skip_rendering(skip && i + 2 < frames);
+            self.clock_for_frame();
+        }
+        self.set_skip_rendering(false);
+
+        let pixels = if self.rl_config.indexed_pixels {
+            self.pixel_index_buffer()
+        } else {
+            self.pixel_buffer()
+        };
+
+        let ram = self.cpu_ram();
+        RlObservation {
+            pixels: pixels.to_vec(),
+            ram: self
+                .rl_config
+                .ram_addresses
+                .iter()
+                .map(|&address| ram[address as usize & 0x7FF])
+                .collect(),
+            done: self
+                .rl_config
+                .done_predicate
+                .as_ref()
+                .map(|predicate| predicate(ram))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Reset the emulator to the state of `snapshot`, used to start a new episode.
+    pub fn rl_reset_to(&mut self, snapshot: &StateSnapshot) -> Result<(), SaveError> {
+        self.restore_snapshot(snapshot)?;
+        // the controller state is not part of the action history
+        for key in NESKey::ALL {
+            self.set_controller_state(key, false);
+            self.set_controller2_state(key, false);
+        }
+
+        Ok(())
+    }
+
+    /// Run one frame for every entry of `inputs`, and write the outputs of all the
+    /// frames into `out`, replacing its content.
+    ///
+    /// Each entry is the controller state of player 1 and player 2 for the frame, in the
+    /// same format as [`NES::rl_step`]. The result is the same as calling
+    /// [`NES::set_controller_state`] and [`NES::set_controller2_state`] for every key,
+    /// then [`NES::clock_for_frame`], [`NES::pixel_buffer`] and [`NES::audio_buffer`]
+    /// for every frame.
+    ///
+    /// Stops early if the CPU halts (see [`NES::is_cpu_halted`]), returns the number of
+    /// frames run, which is also the number of frames in `out`.
+    pub fn run_frames_batch(&mut self, inputs: &[[u8; 2]], out: &mut BatchOutput) -> usize {
+        out.clear();
+        out.pixels.reserve(inputs.len() * TV_BUFFER_SIZE);
+        out.audio_offsets.reserve(inputs.len());
+        out.frames.reserve(inputs.len());
+
+        for &[player_1, player_2] in inputs {
+            if self.is_cpu_halted() {
+                break;
+            }
+            for key in NESKey::ALL {
+                self.set_controller_state(key, player_1 & key as u8 != 0);
+                self.set_controller2_state(key, player_2 & key as u8 != 0);
+            }
+            self.clock_for_frame();
+
+            let pixels = self.pixel_buffer();
+            let hash = frame_hash(pixels);
+            out.pixels.extend_from_slice(pixels);
+            self.append_audio_buffer(&mut out.audio);
+            out.audio_offsets.push(out.audio.len());
+            out.frames.push(BatchFrame {
+                hash,
+                rendering_enabled: self.frame_stats().rendering_was_enabled,
+            });
+        }
+
+        out.len()
+    }
+}
@@ -0,0 +1,72 @@
+use crate::cpu6502::CPURunState;
+use crate::tests::NesTester;
+
+const VBL_TIMING_ROMS: &[&str] = &[
+    "../test_roms/ppu_vbl_nmi/rom_singles/02-vbl_set_time.nes",
+    "../test_roms/ppu_vbl_nmi/rom_singles/03-vbl_clear_time.nes",
+    "../test_roms/ppu_vbl_nmi/rom_singles/05-nmi_timing.nes",
+];
+
+/// same as `run_blargg_test_6000_80`, but with a specific alignment
+fn vbl_test_passes(filename: &str, alignment: u8) -> bool {
+    let mut nes = NesTester::new(filename).unwrap();
+    nes.nes.set_cpu_ppu_alignment(alignment);
+    nes.nes.reset();
+
+    nes.clock_until_infinite_loop();
+    nes.clock_until_memory_neq(0x6000, 0x80);
+
+    nes.clock_for_frame();
+    assert_eq!(nes.nes.frame_stats().cpu_ppu_alignment, alignment);
+
+    nes.cpu_read_address(0x6000) == 0
+}
+
+#[test]
+fn reset_waits_7_cycles() {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+
+    // 7 cycles for the reset sequence, and then fetching the first instruction
+    for _ in 0..8 {
+        assert_eq!(nes.clock(), CPURunState::Waiting);
+    }
+    // the first instruction (at least 2 cycles including the fetch) is executed after that
+    let mut cycles = 1;
+    loop {
+        cycles += 1;
+        if nes.clock() == CPURunState::NormalInstructionExecution {
+            break;
+        }
+    }
+    assert!((2..=7).contains(&cycles));
+}
+
+#[test]
+fn vbl_timing_alignments() {
+    let passing = (0..=2)
+        .map(|alignment| {
+            VBL_TIMING_ROMS
+                .iter()
+                .filter(|rom| vbl_test_passes(rom, alignment))
+                .count()
+        })
+        .collect::<Vec<_>>();
+
+    // the tests pass with all the alignments
+    assert_eq!(passing, [VBL_TIMING_ROMS.len(); 3]);
+}
+
+#[test]
+fn alignment_reported_after_reset() {
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    nes.nes.set_cpu_ppu_alignment(2);
+    nes.clock_for_frame();
+    // not applied until the next reset
+    assert_eq!(nes.nes.frame_stats().cpu_ppu_alignment, 0);
+    assert_eq!(nes.nes.config().cpu_ppu_alignment, 0);
+
+    nes.nes.reset();
+    nes.clock_for_frame();
+    assert_eq!(nes.nes.frame_stats().cpu_ppu_alignment, 2);
+    assert_eq!(nes.nes.config().cpu_ppu_alignment, 2);
+}
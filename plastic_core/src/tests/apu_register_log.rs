@@ -0,0 +1,98 @@
+use super::NesTester;
+use crate::nes_audio::SAMPLE_RATE;
+
+/// Plays notes changing every ~0.2 seconds on the pulse, triangle and noise
+/// channels, with the DMC looping over the program as its sample
+const MUSIC: &[u8] = &[
+    0xA9, 0x1F, //       LDA #$1F
+    0x8D, 0x15, 0x40, // STA $4015
+    0xA9, 0xBF, //       LDA #$BF
+    0x8D, 0x00, 0x40, // STA $4000
+    0xA9, 0x7F, //       LDA #$7F
+    0x8D, 0x08, 0x40, // STA $4008
+    0xA9, 0x3C, //       LDA #$3C
+    0x8D, 0x0C, 0x40, // STA $400C
+    0xA9, 0x4F, //       LDA #$4F
+    0x8D, 0x10, 0x40, // STA $4010  (loop, fastest rate)
+    0xA9, 0x00, //       LDA #$00
+    0x8D, 0x12, 0x40, // STA $4012  (sample at $C000)
+    0xA9, 0x10, //       LDA #$10
+    0x8D, 0x13, 0x40, // STA $4013  (257 bytes)
+    0xA9, 0x1F, //       LDA #$1F
+    0x8D, 0x15, 0x40, // STA $4015  (start the DMC)
+    // $8028
+    0xE8, //             INX
+    0x8E, 0x02, 0x40, // STX $4002
+    0xA9, 0x01, //       LDA #$01
+    0x8D, 0x03, 0x40, // STA $4003
+    0x8E, 0x0A, 0x40, // STX $400A
+    0x8D, 0x0B, 0x40, // STA $400B
+    0x8E, 0x0E, 0x40, // STX $400E
+    0x8D, 0x0F, 0x40, // STA $400F
+    // $803D
+    0xA0, 0x00, //       LDY #$00
+    0x88, //             DEY
+    0xD0, 0xFD, //       BNE $803F
+    0xC6, 0x00, //       DEC $00
+    0xD0, 0xF7, //       BNE $803D
+    0x4C, 0x28, 0x80, // JMP $8028
+];
+
+fn correlation(a: &[f32], b: &[f32]) -> f64 {
+    let mean = |x: &[f32]| x.iter().map(|&v| v as f64).sum::<f64>() / x.len() as f64;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+
+    let (mut covariance, mut variance_a, mut variance_b) = (0., 0., 0.);
+    for (&x, &y) in a.iter().zip(b) {
+        let (x, y) = (x as f64 - mean_a, y as f64 - mean_b);
+        covariance += x * y;
+        variance_a += x * x;
+        variance_b += y * y;
+    }
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+#[test]
+fn rendered_log_matches_live_audio() {
+    let mut nes = NesTester::from_prg(MUSIC).nes;
+    nes.start_apu_register_log();
+
+    let mut live = Vec::new();
+    for _ in 0..600 {
+        nes.clock_for_frame();
+        live.extend(nes.audio_buffer());
+    }
+    let log = nes.stop_apu_register_log();
+
+    assert!(log.iter().any(|write| write.address == 0x4002));
+    assert!(log.iter().any(|write| write.is_dmc_sample_byte()));
+    assert!(log.windows(2).all(|w| w[0].cycle <= w[1].cycle));
+
+    let seconds = live.len() as f32 / 2. / SAMPLE_RATE as f32;
+    let rendered = nes.render_apu_log(&log, seconds);
+
+    // the stereo samples
+    assert!(
+        (rendered.len() as i64 - live.len() as i64).abs() <= 4,
+        "rendered: {}, live: {}",
+        rendered.len(),
+        live.len()
+    );
+    let len = rendered.len().min(live.len());
+    let correlation = correlation(&live[..len], &rendered[..len]);
+    assert!(correlation > 0.99, "correlation: {}", correlation);
+}
+
+#[test]
+fn log_not_started() {
+    let mut nes = NesTester::from_prg(MUSIC).nes;
+    nes.clock_for_frame();
+    assert!(nes.stop_apu_register_log().is_empty());
+
+    nes.start_apu_register_log();
+    nes.clock_for_frame();
+    assert!(!nes.stop_apu_register_log().is_empty());
+    // stopped
+    nes.clock_for_frame();
+    assert!(nes.stop_apu_register_log().is_empty());
+}
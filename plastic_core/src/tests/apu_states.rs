@@ -0,0 +1,32 @@
+use crate::tests::NesTester;
+
+#[test]
+fn pulse_1_beep() {
+    // the test framework plays a short beep on pulse 1 when it starts
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/rom_singles/01-basics.nes").unwrap();
+
+    let mut states = None;
+    for _ in 0..60 {
+        nes.clock_for_frame();
+
+        let current = nes.nes.apu_channel_states();
+        if current.pulse_1.audible {
+            states = Some(current);
+            break;
+        }
+    }
+
+    let states = states.expect("pulse 1 should play a tone");
+    assert!(
+        (210.0..230.0).contains(&states.pulse_1.frequency),
+        "frequency: {}",
+        states.pulse_1.frequency
+    );
+    assert!(states.pulse_1.volume > 0);
+    assert!(states.pulse_1.length_counter > 0);
+
+    assert!(!states.pulse_2.audible);
+    assert!(!states.triangle.audible);
+    assert!(!states.noise.audible);
+    assert!(!states.dmc.audible);
+}
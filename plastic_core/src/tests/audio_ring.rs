@@ -0,0 +1,197 @@
+use crate::nes_audio::{AudioRing, SAMPLE_RATE};
+use crate::tests::NesTester;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+
+thread_local! {
+    /// allocations made by the current thread
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations of each thread, to check that reading from the ring doesn't allocate
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+#[test]
+fn producer_consumer_threads() {
+    // 10 seconds of audio
+    const FRAMES: usize = SAMPLE_RATE as usize * 10;
+
+    let ring = Arc::new(AudioRing::new(1024));
+
+    let producer = {
+        let ring = ring.clone();
+        std::thread::spawn(move || {
+            for i in 0..FRAMES {
+                // wait for the consumer instead of dropping samples
+                while ring.capacity() - ring.read_available() < 2 {
+                    std::thread::yield_now();
+                }
+                assert!(ring.push_stereo(i as f32));
+            }
+        })
+    };
+
+    let mut buffer = [0.; 300];
+    let mut read = 0;
+    while read < FRAMES * 2 {
+        let allocations_before = allocations();
+        let count = ring.pop_slice(&mut buffer);
+        assert_eq!(allocations(), allocations_before);
+
+        for (i, sample) in buffer[..count].iter().enumerate() {
+            assert_eq!(*sample, ((read + i) / 2) as f32);
+        }
+        read += count;
+
+        if count == 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    producer.join().unwrap();
+    assert_eq!(ring.read_available(), 0);
+    assert_eq!(ring.overrun_samples(), 0);
+}
+
+#[test]
+fn audio_buffer_reads_from_the_ring() {
+    // JMP self
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    nes.clock_for_frame();
+
+    let ring = nes.nes.audio_ring().clone();
+    let available = ring.read_available();
+    // 29781 CPU cycles of stereo samples
+    assert!((1460..=1480).contains(&available), "{available}");
+
+    let mut buffer = [0.; 100];
+    assert_eq!(ring.pop_slice(&mut buffer), 100);
+    assert_eq!(nes.nes.audio_buffer().len(), available - 100);
+    assert_eq!(ring.read_available(), 0);
+}
+
+#[test]
+fn overrun_drops_new_samples() {
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    let old_ring = nes.nes.audio_ring().clone();
+
+    nes.nes.set_audio_ring_capacity(101);
+    let ring = nes.nes.audio_ring().clone();
+    assert_eq!(ring.capacity(), 100);
+
+    nes.clock_for_frame();
+    assert_eq!(ring.read_available(), 100);
+    assert!(ring.overrun_samples() > 1000);
+    assert_eq!(old_ring.read_available(), 0);
+
+    // reset keeps the ring
+    nes.nes.audio_buffer();
+    nes.nes.reset();
+    nes.clock_for_frame();
+    assert_eq!(ring.read_available(), 100);
+}
+
+/// Plays a square wave, so the samples are not silent
+const SQUARE_WAVE: &[u8] = &[
+    0xA9, 0x01, 0x8D, 0x15, 0x40, // LDA #$01; STA $4015
+    0xA9, 0xBF, 0x8D, 0x00, 0x40, // LDA #$BF; STA $4000
+    0xA9, 0xFD, 0x8D, 0x02, 0x40, // LDA #$FD; STA $4002
+    0xA9, 0x08, 0x8D, 0x03, 0x40, // LDA #$08; STA $4003
+    0x4C, 0x14, 0x80, // JMP self
+];
+
+/// The length of the fades, 5ms
+const FADE_SAMPLES: usize = SAMPLE_RATE as usize / 200 * 2;
+
+#[test]
+fn pause_fades_out() {
+    let mut nes = NesTester::from_prg(SQUARE_WAVE);
+    nes.clock_for_frame();
+    let before = nes.nes.audio_buffer();
+    let last = *before.last().unwrap();
+    assert!(last.abs() > 0.01);
+
+    nes.nes.pause_audio();
+    assert!(nes.nes.is_audio_paused());
+    // pausing again doesn't add another fade
+    nes.nes.pause_audio();
+    let fade = nes.nes.audio_buffer();
+    assert_eq!(fade.len(), FADE_SAMPLES);
+
+    // stereo, decreasing from the last sample to silence
+    for pair in fade.chunks(2) {
+        assert_eq!(pair[0], pair[1]);
+    }
+    assert!(fade.windows(2).all(|w| w[1].abs() <= w[0].abs()));
+    assert!(fade[0].abs() <= last.abs());
+    assert_eq!(*fade.last().unwrap(), 0.);
+
+    // no samples while paused
+    nes.clock_for_frame();
+    assert!(nes.nes.audio_buffer().is_empty());
+}
+
+#[test]
+fn resume_fades_in() {
+    let mut nes = NesTester::from_prg(SQUARE_WAVE);
+    let mut reference = NesTester::from_prg(SQUARE_WAVE);
+    nes.clock_for_frame();
+    reference.clock_for_frame();
+    reference.nes.audio_buffer();
+
+    nes.nes.pause_audio();
+    nes.nes.audio_buffer();
+    nes.nes.resume_audio();
+    assert!(!nes.nes.is_audio_paused());
+
+    // the emulation is the same, only the start of the audio is faded
+    nes.clock_for_frame();
+    reference.clock_for_frame();
+    let audio = nes.nes.audio_buffer();
+    let reference_audio = reference.nes.audio_buffer();
+    assert_eq!(audio.len(), reference_audio.len());
+
+    for (i, (sample, reference)) in audio.iter().zip(&reference_audio).enumerate() {
+        if i < FADE_SAMPLES {
+            assert!(sample.abs() < reference.abs() || *reference == 0.);
+        } else {
+            assert_eq!(sample, reference);
+        }
+    }
+    assert!(audio[0].abs() < 0.01);
+}
+
+#[test]
+fn pause_is_kept_when_loading_states() {
+    let mut nes = NesTester::from_prg(SQUARE_WAVE);
+    nes.clock_for_frame();
+    let state = nes.nes.snapshot().unwrap();
+
+    nes.nes.pause_audio();
+    nes.nes.restore_snapshot(&state).unwrap();
+    assert!(nes.nes.is_audio_paused());
+}
@@ -0,0 +1,157 @@
+use crate::common::Region;
+use crate::movie::Movie;
+use crate::nes_audio::{AudioSampling, SAMPLE_RATE};
+use crate::tests::NesTester;
+
+/// `loop: JMP loop`
+const IDLE_LOOP: [u8; 3] = [0x4C, 0x00, 0x80];
+
+/// Number of samples the exact sampler should have produced after `cycles` CPU cycles
+fn expected_samples(region: Region, cycles: u64) -> u64 {
+    let (numerator, denominator) = region.cpu_freq_ratio();
+    cycles * SAMPLE_RATE as u64 * denominator / numerator
+}
+
+fn exact_sampling_pattern(region: Region, min_per_frame: u64) {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    tester.nes.set_region(region);
+    tester.nes.set_audio_sampling(AudioSampling::Exact);
+
+    let mut total_cycles = 0;
+    let mut total_samples = 0;
+    for frame in 0..1000 {
+        tester.clock_for_frame();
+        total_cycles += tester.nes.frame_stats().cpu_cycles as u64;
+
+        // stereo, 2 values per sample
+        let samples = tester.nes.audio_buffer().len() as u64 / 2;
+        let expected = expected_samples(region, total_cycles) - total_samples;
+        assert_eq!(samples, expected, "frame {frame}");
+        assert!(
+            (min_per_frame..=min_per_frame + 1).contains(&samples),
+            "frame {frame}: {samples} samples"
+        );
+        total_samples += samples;
+    }
+
+    // `SAMPLE_RATE * seconds`, where `seconds = cycles / cpu_freq`
+    let (numerator, denominator) = region.cpu_freq_ratio();
+    assert_eq!(
+        total_samples as u128,
+        SAMPLE_RATE as u128 * total_cycles as u128 * denominator as u128 / numerator as u128
+    );
+}
+
+#[test]
+fn exact_sampling_pattern_ntsc() {
+    // 29780.5 cycles per frame at 1789772.72 Hz
+    exact_sampling_pattern(Region::Ntsc, 733);
+}
+
+#[test]
+fn exact_sampling_pattern_pal() {
+    // the frames still have NTSC timing, at the PAL CPU clock of 1662607 Hz
+    exact_sampling_pattern(Region::Pal, 789);
+}
+
+#[test]
+fn sampling_mode_kept_on_reset_and_used_by_movies() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    assert_eq!(tester.nes.audio_sampling(), AudioSampling::Float);
+
+    let mut movie = Movie::new(10);
+    tester.nes.record_movie_frame(&mut movie, 0).unwrap();
+    assert_eq!(tester.nes.audio_sampling(), AudioSampling::Exact);
+
+    tester.nes.reset();
+    tester.nes.set_region(Region::Pal);
+    assert_eq!(tester.nes.audio_sampling(), AudioSampling::Exact);
+
+    // the new APU uses the exact sampler
+    let _ = tester.nes.audio_buffer();
+    tester.clock_for_frame();
+    let cycles = tester.nes.frame_stats().cpu_cycles as u64;
+    assert_eq!(
+        tester.nes.audio_buffer().len() as u64 / 2,
+        expected_samples(Region::Pal, cycles)
+    );
+}
+
+/// Run frames until at least one second of emulated time has passed, returns
+/// the CPU cycles run and the samples produced
+fn run_one_second(tester: &mut NesTester) -> (u64, u64) {
+    let cycles_per_second = tester.nes.region().cpu_freq() as u64;
+    let mut cycles = 0;
+    let mut samples = 0;
+    while cycles < cycles_per_second {
+        tester.clock_for_frame();
+        cycles += tester.nes.frame_stats().cpu_cycles as u64;
+        // stereo, 2 values per sample
+        samples += tester.nes.audio_buffer().len() as u64 / 2;
+    }
+    (cycles, samples)
+}
+
+#[test]
+fn sample_rate_sets_samples_per_second() {
+    for sampling in [AudioSampling::Float, AudioSampling::Exact] {
+        for rate in [22050, 48000, 96000] {
+            let mut tester = NesTester::from_prg(&IDLE_LOOP);
+            tester.nes.set_audio_sampling(sampling);
+            tester.nes.set_audio_sample_rate(rate);
+            assert_eq!(tester.nes.audio_sample_rate(), rate);
+
+            let (cycles, samples) = run_one_second(&mut tester);
+            let expected = rate as f64 * cycles as f64 / Region::Ntsc.cpu_freq();
+            assert!(
+                (samples as f64 - expected).abs() <= 1.,
+                "{sampling:?} at {rate}: {samples} samples, expected {expected}"
+            );
+        }
+    }
+}
+
+#[test]
+fn sample_rate_change_drops_old_samples() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    tester.nes.set_output_delay_frames(2);
+    for _ in 0..4 {
+        tester.clock_for_frame();
+    }
+    assert!(!tester.nes.audio_buffer().is_empty());
+    tester.clock_for_frame();
+
+    tester.nes.set_audio_sample_rate(48000);
+    assert!(tester.nes.audio_buffer().is_empty());
+
+    // the delayed frames come out without the audio of the old rate, then the
+    // frames run after the change have the audio of the new rate
+    tester.clock_for_frame();
+    assert!(tester.nes.audio_buffer().is_empty());
+    tester.clock_for_frame();
+    assert!(tester.nes.audio_buffer().is_empty());
+    tester.clock_for_frame();
+    let samples = tester.nes.audio_buffer().len() / 2;
+    assert!((798..=800).contains(&samples), "{samples} samples");
+}
+
+#[test]
+fn sample_rate_kept_on_reset_and_region_change() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    assert_eq!(tester.nes.audio_sample_rate(), SAMPLE_RATE);
+    tester.nes.set_audio_sampling(AudioSampling::Exact);
+    tester.nes.set_audio_sample_rate(32000);
+
+    tester.nes.reset();
+    tester.nes.set_region(Region::Pal);
+    assert_eq!(tester.nes.audio_sample_rate(), 32000);
+
+    let _ = tester.nes.audio_buffer();
+    tester.clock_for_frame();
+    let cycles = tester.nes.frame_stats().cpu_cycles as u64;
+    let (numerator, denominator) = Region::Pal.cpu_freq_ratio();
+    assert_eq!(
+        tester.nes.audio_buffer().len() as u64 / 2,
+        cycles * 32000 * denominator / numerator
+    );
+}
@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 
 use super::{NesTester, TestError};
+use crate::ppu2c02::PpuBackend;
 
 fn run_sprite_hit_test(filename: &str) -> Result<(), TestError> {
+    run_sprite_hit_test_with_backend(filename, PpuBackend::DotAccurate)
+}
+
+fn run_sprite_hit_test_with_backend(filename: &str, backend: PpuBackend) -> Result<(), TestError> {
     let result_memory_address = 0x00F8;
 
-    let mut nes = NesTester::new(filename)?;
+    let mut nes = NesTester::new_with_ppu_backend(filename, backend)?;
 
     // this is the top-left pixel of the word "PASSED" or "FAILED"
     nes.clock_until_pixel_appears(17, 48, 0x30);
@@ -34,9 +39,16 @@ fn run_blargg_test_00f0(filename: &str) -> Result<(), TestError> {
 }
 
 fn run_blargg_test_6000_80(filename: &str) -> Result<(), TestError> {
+    run_blargg_test_6000_80_with_backend(filename, PpuBackend::DotAccurate)
+}
+
+fn run_blargg_test_6000_80_with_backend(
+    filename: &str,
+    backend: PpuBackend,
+) -> Result<(), TestError> {
     let result_memory_address = 0x6000;
 
-    let mut nes = NesTester::new(filename)?;
+    let mut nes = NesTester::new_with_ppu_backend(filename, backend)?;
 
     // first loop until an infnite loop (this infinite loop might be the
     // end or not), then loop until the value of `0x6000` is not `0x80`
@@ -327,7 +339,8 @@ mod mappers {
         run_blargg_test_6000_80("../test_roms/mmc3_test_2/rom_singles/3-A12_clocking.nes")
     }
 
-    // FIXME: this test is still failing
+    // FIXME: this test is still failing, with #9 "Scanline 0 IRQ should occur sooner
+    //        when $2000=$10"
     // #[test]
     fn mmc3_test_4_scanline_timing() -> Result<(), TestError> {
         run_blargg_test_6000_80("../test_roms/mmc3_test_2/rom_singles/4-scanline_timing.nes")
@@ -338,3 +351,49 @@ mod mappers {
         run_blargg_test_6000_80("../test_roms/mmc3_test_2/rom_singles/5-MMC3.nes")
     }
 }
+
+/// The scanline backend must not affect the CPU, and should handle sprite 0 hit
+/// of the common cases
+mod scanline_backend {
+    use super::*;
+
+    #[test]
+    fn instructions_test() -> Result<(), TestError> {
+        run_blargg_test_6000_80_with_backend(
+            "../test_roms/instr_test-v5/all_instrs.nes",
+            PpuBackend::Scanline,
+        )
+    }
+
+    #[test]
+    fn instructions_timing_test() -> Result<(), TestError> {
+        run_blargg_test_6000_80_with_backend(
+            "../test_roms/instr_timing/instr_timing.nes",
+            PpuBackend::Scanline,
+        )
+    }
+
+    #[test]
+    fn sprite_hit_tests() -> Result<(), TestError> {
+        for name in [
+            "01.basics",
+            "02.alignment",
+            "03.corners",
+            "04.flip",
+            "05.left_clip",
+            "06.right_edge",
+            "07.screen_bottom",
+            "08.double_height",
+            "09.timing_basics",
+            "10.timing_order",
+            "11.edge_timing",
+        ] {
+            run_sprite_hit_test_with_backend(
+                &format!("../test_roms/sprite_hit_tests/{}.nes", name),
+                PpuBackend::Scanline,
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::display::frame_hash;
+use crate::tests::NesTester;
+
+const ROM: &str = "../test_roms/sprite_hit_tests/01.basics.nes";
+
+#[test]
+fn frame_in_slices_same_as_whole_frame() {
+    let mut whole = NesTester::new(ROM).unwrap();
+    let mut sliced = NesTester::new(ROM).unwrap();
+
+    for _ in 0..30 {
+        whole.clock_for_frame();
+
+        let mut slices = 0;
+        loop {
+            slices += 1;
+            let result = sliced.nes.clock_with_budget(6000);
+            if result.frame_complete {
+                assert!(result.cycles_run <= 6000);
+                assert_eq!(result.frame_cycles, whole.nes.frame_stats().cpu_cycles);
+                break;
+            }
+            assert_eq!(result.cycles_run, 6000);
+            assert_eq!(result.frame_cycles, slices * 6000);
+        }
+        assert_eq!(slices, 5);
+
+        assert_eq!(
+            frame_hash(sliced.pixel_buffer()),
+            frame_hash(whole.pixel_buffer())
+        );
+        assert_eq!(sliced.nes.frame_stats(), whole.nes.frame_stats());
+        assert_eq!(sliced.nes.cpu_state(), whole.nes.cpu_state());
+    }
+    assert_eq!(sliced.nes.audio_buffer(), whole.nes.audio_buffer());
+}
+
+#[test]
+fn clock_for_frame_finishes_partial_frame() {
+    let mut whole = NesTester::new(ROM).unwrap();
+    let mut partial = NesTester::new(ROM).unwrap();
+
+    whole.clock_for_frame();
+    assert!(!partial.nes.clock_with_budget(100).frame_complete);
+    partial.clock_for_frame();
+
+    assert_eq!(partial.nes.frame_stats(), whole.nes.frame_stats());
+    assert_eq!(partial.nes.cpu_state(), whole.nes.cpu_state());
+}
+
+#[test]
+fn load_state_drops_partial_frame() {
+    let mut nes = NesTester::new(ROM).unwrap();
+    nes.clock_for_frame();
+
+    let mut state = Vec::new();
+    nes.nes.save_state(&mut state).unwrap();
+    let mut reference = NesTester::new(ROM).unwrap();
+    reference.nes.load_state(state.as_slice()).unwrap();
+    reference.clock_for_frame();
+
+    nes.nes.clock_with_budget(10000);
+    nes.nes.load_state(state.as_slice()).unwrap();
+
+    // a new frame is started, not the rest of the old one
+    let result = nes.nes.clock_with_budget(u32::MAX);
+    assert!(result.frame_complete);
+    assert_eq!(result.frame_cycles, reference.nes.frame_stats().cpu_cycles);
+    assert_eq!(nes.nes.cpu_state(), reference.nes.cpu_state());
+}
@@ -0,0 +1,69 @@
+use super::NesTester;
+use crate::nes_audio::ChannelSamples;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Plays a constant tone on the first pulse channel
+const PULSE_TONE: &[u8] = &[
+    0xA9, 0x01, //       LDA #$01
+    0x8D, 0x15, 0x40, // STA $4015
+    0xA9, 0xBF, //       LDA #$BF
+    0x8D, 0x00, 0x40, // STA $4000
+    0xA9, 0xFD, //       LDA #$FD
+    0x8D, 0x02, 0x40, // STA $4002
+    0xA9, 0x08, //       LDA #$08
+    0x8D, 0x03, 0x40, // STA $4003
+    0x4C, 0x14, 0x80, // JMP $8014
+];
+
+#[test]
+fn pulse_stem() {
+    let mut nes = NesTester::from_prg(PULSE_TONE).nes;
+    let samples = Rc::new(RefCell::new(Vec::<ChannelSamples>::new()));
+
+    let tap_samples = samples.clone();
+    nes.set_channel_tap(Box::new(move |channels| {
+        tap_samples.borrow_mut().push(channels)
+    }));
+
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+    // one channel sample for every stereo sample
+    let audio = nes.audio_buffer();
+    let samples = samples.take();
+    assert_eq!(samples.len() * 2, audio.len());
+
+    // the mix of the channels is the audio output
+    for (channels, output) in samples.iter().zip(audio.iter().step_by(2)) {
+        assert_eq!(channels.mix(), *output);
+    }
+
+    // the tone is a square wave, about 440Hz, which is around 7 rising edges in a frame
+    let rising_edges = samples
+        .windows(2)
+        .filter(|w| w[0].pulse1 < 0. && w[1].pulse1 >= 0.)
+        .count();
+    assert!((60..=80).contains(&rising_edges), "{}", rising_edges);
+
+    assert!(samples.iter().all(|channels| channels.noise == 0.));
+    assert!(samples.iter().all(|channels| channels.pulse2 == 0.));
+}
+
+#[test]
+fn tap_survives_reset_and_can_be_removed() {
+    let mut nes = NesTester::from_prg(PULSE_TONE).nes;
+    let count = Rc::new(RefCell::new(0));
+
+    let tap_count = count.clone();
+    nes.set_channel_tap(Box::new(move |_| *tap_count.borrow_mut() += 1));
+
+    nes.reset();
+    nes.clock_for_frame();
+    let after_reset = *count.borrow();
+    assert!(after_reset > 0);
+
+    nes.remove_channel_tap();
+    nes.clock_for_frame();
+    assert_eq!(*count.borrow(), after_reset);
+}
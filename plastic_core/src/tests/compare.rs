@@ -0,0 +1,59 @@
+use super::NesTester;
+use crate::compare::{pixel_diff_bounds, LockstepRunner, PixelDiffBounds};
+use crate::display::{COLOR_BYTES_LEN, TV_BUFFER_SIZE, TV_WIDTH};
+use crate::{NESKey, NES};
+
+#[test]
+fn identical_instances_dont_diverge() {
+    let path = "../test_roms/instr_test-v5/all_instrs.nes";
+    let mut runner = LockstepRunner::new(NES::new(path).unwrap(), NES::new(path).unwrap());
+
+    let inputs = (0..600u32).map(|i| if i % 50 < 5 { NESKey::Start as u8 } else { 0 });
+    assert_eq!(runner.run_until_divergence(600, inputs), None);
+    assert_eq!(runner.frame(), 600);
+}
+
+#[test]
+fn perturbed_ram_diverges() {
+    let prg = [
+        0xA5, 0x10, // LDA $10
+        0x8D, 0x00, 0x03, // STA $0300
+        0x4C, 0x00, 0x80, // JMP $8000
+    ];
+    let mut runner =
+        LockstepRunner::new(NesTester::from_prg(&prg).nes, NesTester::from_prg(&prg).nes);
+
+    assert_eq!(runner.run_until_divergence(30, []), None);
+
+    runner.nes_mut().1.cpu_ram_mut()[0x10] = 0x42;
+
+    let report = runner.run_until_divergence(30, []).unwrap();
+    assert_eq!(report.frame, 30);
+    assert_eq!(report.cpu_states.0.a, 0);
+    assert_eq!(report.cpu_states.1.a, 0x42);
+    assert_eq!(report.frame_hashes.0, report.frame_hashes.1);
+    assert_eq!(report.pixel_diff, None);
+}
+
+#[test]
+fn pixel_diff_bounding_box() {
+    let a = vec![0; TV_BUFFER_SIZE];
+    let mut b = a.clone();
+    let pixel = |x: usize, y: usize| (y * TV_WIDTH + x) * COLOR_BYTES_LEN;
+
+    assert_eq!(pixel_diff_bounds(&a, &b), None);
+
+    b[pixel(10, 20)] = 1;
+    b[pixel(3, 50) + 2] = 1;
+    b[pixel(40, 30) + 1] = 1;
+
+    assert_eq!(
+        pixel_diff_bounds(&a, &b),
+        Some(PixelDiffBounds {
+            min_x: 3,
+            min_y: 20,
+            max_x: 40,
+            max_y: 50,
+        })
+    );
+}
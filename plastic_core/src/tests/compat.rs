@@ -0,0 +1,91 @@
+use super::NesTester;
+use crate::{CompatFinding, NES};
+
+/// A 128KB PRG ROM for `mapper`, where only the last bank (fixed at `$C000` in UxROM)
+/// contains code, which enables rendering and loops forever.
+fn uxrom_game(mapper: u8) -> Vec<u8> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 8, 0, mapper << 4, mapper & 0xF0];
+    rom.resize(16, 0);
+
+    let mut last_bank = vec![0; 0x4000];
+    last_bank[..8].copy_from_slice(&[
+        0xA9, 0x1E, // LDA #$1E
+        0x8D, 0x01, 0x20, // STA $2001
+        0x4C, 0x05, 0xC0, // JMP $C005
+    ]);
+    // reset vector
+    last_bank[0x3FFC] = 0x00;
+    last_bank[0x3FFD] = 0xC0;
+
+    rom.resize(16 + 7 * 0x4000, 0);
+    rom.extend_from_slice(&last_bank);
+    rom
+}
+
+#[test]
+fn correct_mapper_has_no_findings() {
+    let mut nes = NES::new_from_bytes(&uxrom_game(2)).unwrap();
+
+    let report = nes.compatibility_report(60);
+    assert_eq!(report.reset_vector, 0xC000);
+    assert!(report.is_ok(), "{}", report);
+}
+
+#[test]
+fn wrong_mapper_stuck_after_reset() {
+    // AxROM maps the first 32KB at power-up, so the reset vector is read from an empty bank
+    let mut nes = NES::new_from_bytes(&uxrom_game(7)).unwrap();
+
+    let report = nes.compatibility_report(60);
+    assert_eq!(report.reset_vector, 0x0000);
+    assert!(
+        matches!(
+            report.findings.as_slice(),
+            [CompatFinding::StuckAfterReset { .. }]
+        ),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn polling_apu_status() {
+    let mut nes = NesTester::from_prg(&[
+        0xA9, 0x1E, // LDA #$1E
+        0x8D, 0x01, 0x20, // STA $2001
+        0xAD, 0x15, 0x40, // LDA $4015
+        0x29, 0x10, // AND #$10 (DMC active)
+        0xF0, 0xF9, // BEQ $8005
+    ])
+    .nes;
+
+    let report = nes.compatibility_report(60);
+    assert!(
+        matches!(
+            report.findings.as_slice(),
+            [CompatFinding::PollingApuStatus { .. }]
+        ),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn report_doesnt_change_the_session() {
+    let mut nes = NES::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+
+    let pixels = nes.pixel_buffer().to_vec();
+    let frame_stats = nes.frame_stats();
+    // includes the CPU state and the pending audio samples
+    let state = nes.snapshot().unwrap();
+
+    let report = nes.compatibility_report(30);
+    assert_eq!(report.frames, 30);
+
+    assert_eq!(nes.pixel_buffer(), pixels);
+    assert_eq!(nes.frame_stats(), frame_stats);
+    assert_eq!(nes.snapshot().unwrap().as_bytes(), state.as_bytes());
+}
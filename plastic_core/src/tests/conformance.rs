@@ -0,0 +1,73 @@
+//! Checks the conformance vector, when a change to the emulation is intended, update
+//! the vector with:
+//! ```text
+//! PLASTIC_CONFORMANCE_UPDATE=1 cargo test -p plastic_core conformance
+//! ```
+
+use crate::conformance::{
+    conformance_rom, format_vector, run_checkpoints, AudioChecksum, CONFORMANCE_FRAMES,
+};
+use crate::NES;
+use std::fs;
+
+const VECTOR_FILE: &str = "src/conformance/vector.txt";
+
+#[test]
+fn conformance_vector() {
+    if std::env::var("PLASTIC_CONFORMANCE_UPDATE").is_ok_and(|value| value == "1") {
+        let checkpoints = run_checkpoints(&CONFORMANCE_FRAMES);
+        fs::write(VECTOR_FILE, format_vector(&checkpoints)).unwrap();
+        // the vector is embedded when compiling, it is checked in the next run
+        return;
+    }
+
+    let report = NES::conformance_check();
+    assert_eq!(report.checkpoints.len(), CONFORMANCE_FRAMES.len());
+    assert!(
+        report.passed(),
+        "the emulation changed, update the vector if this is intended:\n{report}"
+    );
+}
+
+#[test]
+fn conformance_rom_exercises_the_console() {
+    let checkpoints = run_checkpoints(&CONFORMANCE_FRAMES);
+
+    // the picture and the audio change between the checkpoints
+    for pair in checkpoints.windows(2) {
+        assert_ne!(pair[0].frame_hash, pair[1].frame_hash);
+        assert_ne!(pair[0].audio_checksum, pair[1].audio_checksum);
+    }
+
+    // the ROM renders something and is not silent
+    let mut nes = NES::new_from_bytes(&conformance_rom()).unwrap();
+    let mut audio = Vec::new();
+    for _ in 0..10 {
+        nes.clock_for_frame();
+        audio.extend(nes.audio_buffer());
+    }
+    let first_pixel = &nes.pixel_buffer()[..3];
+    assert!(nes
+        .pixel_buffer()
+        .chunks(3)
+        .any(|pixel| pixel != first_pixel));
+    assert!(audio.iter().any(|&sample| sample != audio[0]));
+}
+
+#[test]
+fn audio_checksum_is_streaming() {
+    let samples = (0..1000).map(|i| (i as f32).sin()).collect::<Vec<_>>();
+
+    let mut whole = AudioChecksum::new();
+    whole.update(&samples);
+    let mut chunked = AudioChecksum::new();
+    for chunk in samples.chunks(37) {
+        chunked.update(chunk);
+    }
+    assert_eq!(whole, chunked);
+    assert_eq!(whole.samples(), 1000);
+
+    let mut different = AudioChecksum::new();
+    different.update(&samples[1..]);
+    assert_ne!(different.value(), whole.value());
+}
@@ -0,0 +1,76 @@
+use super::NesTester;
+use crate::{Diagnostic, NES};
+
+/// Sets the backdrop color, then jams the CPU with `KIL` at `$800F`
+const HALTING: &[u8] = &[
+    0xA9, 0x3F, //       LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, //       LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x16, //       LDA #$16
+    0x8D, 0x07, 0x20, // STA $2007
+    0x02, //             KIL
+];
+
+const KIL_ADDRESS: u16 = 0x800F;
+
+fn new_nes() -> NES {
+    let mut nes = NesTester::from_prg(HALTING).nes;
+    nes.set_rendering_disabled_backdrop(true);
+    nes
+}
+
+#[test]
+fn kil_halts_until_reset() {
+    let mut nes = new_nes();
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+
+    assert!(nes.is_cpu_halted());
+    assert_eq!(nes.cpu_state().pc, KIL_ADDRESS);
+    assert_eq!(
+        nes.take_diagnostics(),
+        vec![Diagnostic::CpuHalted {
+            pc: KIL_ADDRESS,
+            opcode: 0x02
+        }]
+    );
+
+    // the PPU keeps producing frames with the last picture
+    let pixels = nes.pixel_buffer().to_vec();
+    assert!(pixels.iter().any(|&byte| byte != 0));
+    for _ in 0..60 {
+        assert!(nes.clock_with_budget(u32::MAX).frame_complete);
+        assert_eq!(nes.frame_stats().instructions, 0);
+        assert!(nes.is_cpu_halted());
+        assert_eq!(nes.cpu_state().pc, KIL_ADDRESS);
+        assert!(nes.pixel_buffer() == pixels.as_slice());
+    }
+    // reported once
+    assert!(nes.take_diagnostics().is_empty());
+
+    nes.reset();
+    assert!(!nes.is_cpu_halted());
+    assert_eq!(nes.cpu_state().pc, 0x8000);
+
+    // runs the program again, until the `KIL`
+    nes.clock_for_frame();
+    assert!(nes.frame_stats().instructions > 0);
+    assert!(nes.is_cpu_halted());
+}
+
+#[test]
+fn halt_is_saved() {
+    let mut nes = new_nes();
+    nes.clock_for_frame();
+    assert!(nes.is_cpu_halted());
+
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    nes.reset();
+    assert!(!nes.is_cpu_halted());
+
+    nes.load_state(&mut state.as_slice()).unwrap();
+    assert!(nes.is_cpu_halted());
+    assert_eq!(nes.cpu_state().pc, KIL_ADDRESS);
+}
@@ -0,0 +1,34 @@
+use crate::tests::NesTester;
+
+/// `$19 + $28` and `$47 - $19` with the decimal flag set, stored in `$00` and `$01`
+const DECIMAL_PROGRAM: &[u8] = &[
+    0xF8, // SED
+    0x18, // CLC
+    0xA9, 0x19, // LDA #$19
+    0x69, 0x28, // ADC #$28
+    0x85, 0x00, // STA $00
+    0x38, // SEC
+    0xA9, 0x47, // LDA #$47
+    0xE9, 0x19, // SBC #$19
+    0x85, 0x01, // STA $01
+    0x4C, 0x0F, 0x80, // JMP self
+];
+
+#[test]
+fn binary_by_default() {
+    let mut nes = NesTester::from_prg(DECIMAL_PROGRAM);
+    nes.clock_until_infinite_loop();
+
+    assert_eq!(nes.cpu_read_address(0x00), 0x41);
+    assert_eq!(nes.cpu_read_address(0x01), 0x2E);
+}
+
+#[test]
+fn decimal_when_enabled() {
+    let mut nes = NesTester::from_prg(DECIMAL_PROGRAM);
+    nes.nes.set_cpu_decimal_mode(true);
+    nes.clock_until_infinite_loop();
+
+    assert_eq!(nes.cpu_read_address(0x00), 0x47);
+    assert_eq!(nes.cpu_read_address(0x01), 0x28);
+}
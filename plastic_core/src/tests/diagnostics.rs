@@ -0,0 +1,174 @@
+use super::NesTester;
+use crate::{Diagnostic, StackWrap};
+
+const STACK_WRAP_PROGRAM: &[u8] = &[
+    0xA2, 0x02, // LDX #$02
+    0x9A, // TXS
+    0x48, // PHA
+    0x48, // PHA
+    0x48, // PHA (SP: $00 -> $FF)
+    0xA2, 0xFE, // LDX #$FE
+    0x9A, // TXS
+    0x68, // PLA
+    0x68, // PLA (SP: $FF -> $00)
+    0x4C, 0x0B, 0x80, // JMP $800B
+];
+
+#[test]
+fn stack_wrap_warnings() {
+    let mut nes = NesTester::from_prg(STACK_WRAP_PROGRAM).nes;
+    nes.set_stack_wrap_warnings(true);
+    nes.clock_for_frame();
+
+    assert_eq!(
+        nes.take_diagnostics(),
+        vec![
+            Diagnostic::StackWrap {
+                pc: 0x8005,
+                direction: StackWrap::Overflow,
+                in_interrupt: false,
+            },
+            Diagnostic::StackWrap {
+                pc: 0x800A,
+                direction: StackWrap::Underflow,
+                in_interrupt: false,
+            },
+        ]
+    );
+    // taken, and the loop doesn't use the stack
+    nes.clock_for_frame();
+    assert!(nes.take_diagnostics().is_empty());
+}
+
+#[test]
+fn stack_wrap_warnings_disabled_by_default() {
+    let mut nes = NesTester::from_prg(STACK_WRAP_PROGRAM).nes;
+    nes.clock_for_frame();
+
+    assert!(nes.take_diagnostics().is_empty());
+}
+
+#[test]
+fn stack_wrap_in_interrupt() {
+    let mut prg = vec![0; 0x3FFC];
+    prg[..10].copy_from_slice(&[
+        0xA2, 0x01, // LDX #$01
+        0x9A, // TXS
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000 (enable NMI)
+        0xD0, 0xFE, // BNE $8008
+    ]);
+    // NMI handler
+    prg[0x20..0x22].copy_from_slice(&[
+        0xD0, 0xFE, // BNE $8020
+    ]);
+    // NMI vector
+    prg[0x3FFA..].copy_from_slice(&[0x20, 0x80]);
+
+    let mut nes = NesTester::from_prg(&prg).nes;
+    nes.set_stack_wrap_warnings(true);
+    for _ in 0..2 {
+        nes.clock_for_frame();
+    }
+
+    assert_eq!(
+        nes.take_diagnostics(),
+        vec![Diagnostic::StackWrap {
+            pc: 0x8008,
+            direction: StackWrap::Overflow,
+            in_interrupt: true,
+        }]
+    );
+}
+
+#[test]
+fn call_stack_guess() {
+    let mut prg = vec![0; 0x30];
+    prg[..3].copy_from_slice(&[
+        0x20, 0x10, 0x80, // JSR $8010
+    ]);
+    prg[0x10..0x16].copy_from_slice(&[
+        0xA9, 0x42, // LDA #$42
+        0x48, // PHA
+        0x20, 0x20, 0x80, // JSR $8020
+    ]);
+    prg[0x20..0x23].copy_from_slice(&[
+        0x4C, 0x20, 0x80, // JMP $8020
+    ]);
+
+    let mut nes = NesTester::from_prg(&prg).nes;
+    assert!(nes.call_stack_guess().is_empty());
+
+    nes.clock_for_frame();
+    // the pushed `$42` is skipped
+    assert_eq!(nes.call_stack_guess(), vec![0x8016, 0x8003]);
+}
+
+const HOMEBREW_BUGS_PROGRAM: &[u8] = &[
+    0xA9, 0x05, // LDA #$05
+    0x85, 0x10, // STA $10
+    0xA5, 0x10, // LDA $10 (initialized)
+    0xA5, 0x11, // LDA $11 (uninitialized)
+    0xAD, 0x11, 0x08, // LDA $0811 (mirror of $11, already reported)
+    0xAD, 0x20, 0x03, // LDA $0320 (uninitialized)
+    0xAD, 0x80, 0x01, // LDA $0180 (stack page, ignored)
+    0x8D, 0x34, 0x92, // STA $9234 (ROM, A is 0 from $0180)
+    0x4C, 0x14, 0x80, // JMP $8014
+];
+
+#[test]
+fn homebrew_diagnostics() {
+    let mut nes = NesTester::from_prg(HOMEBREW_BUGS_PROGRAM).nes;
+    nes.set_homebrew_diagnostics(true);
+    nes.reset();
+    nes.clock_for_frame();
+
+    assert_eq!(
+        nes.take_diagnostics(),
+        vec![
+            Diagnostic::UninitializedRamRead {
+                pc: 0x8006,
+                address: 0x0011,
+            },
+            Diagnostic::UninitializedRamRead {
+                pc: 0x800B,
+                address: 0x0320,
+            },
+            Diagnostic::RomWrite {
+                pc: 0x8011,
+                address: 0x9234,
+                data: 0x00,
+            },
+        ]
+    );
+    nes.clock_for_frame();
+    assert!(nes.take_diagnostics().is_empty());
+}
+
+#[test]
+fn homebrew_diagnostics_ignored_pages() {
+    let mut nes = NesTester::from_prg(HOMEBREW_BUGS_PROGRAM).nes;
+    nes.set_homebrew_diagnostics(true);
+    // only the page 3
+    nes.set_uninitialized_ram_ignored_pages(1 << 3);
+    nes.reset();
+    nes.clock_for_frame();
+
+    let reads = nes
+        .take_diagnostics()
+        .into_iter()
+        .filter_map(|diagnostic| match diagnostic {
+            Diagnostic::UninitializedRamRead { address, .. } => Some(address),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(reads, vec![0x0011, 0x0180]);
+}
+
+#[test]
+fn homebrew_diagnostics_disabled_by_default() {
+    let mut nes = NesTester::from_prg(HOMEBREW_BUGS_PROGRAM).nes;
+    nes.clock_for_frame();
+
+    assert!(nes.take_diagnostics().is_empty());
+}
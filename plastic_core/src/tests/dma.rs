@@ -0,0 +1,89 @@
+use crate::tests::NesTester;
+
+/// `LDX #0; loop: STX $2003; LDA $2004; STA $0300,X; INX; BNE loop`
+///
+/// copies the whole OAM into `$0300-$03FF`
+const DUMP_OAM: &[u8] = &[
+    0xA2, 0x00, 0x8E, 0x03, 0x20, 0xAD, 0x04, 0x20, 0x9D, 0x00, 0x03, 0xE8, 0xD0, 0xF4,
+];
+
+/// build a program from `parts` followed by an infinite loop, and `data` at `$8100`
+fn program(parts: &[&[u8]], data: &[u8]) -> Vec<u8> {
+    let mut prg = parts.concat();
+    let loop_address = 0x8000 + prg.len() as u16;
+    // JMP loop_address
+    prg.extend_from_slice(&[0x4C, loop_address as u8, (loop_address >> 8) as u8]);
+    assert!(prg.len() <= 0x100);
+
+    prg.resize(0x100, 0xEA);
+    prg.extend_from_slice(data);
+    prg
+}
+
+fn rom_data() -> Vec<u8> {
+    (0..=255u8).map(|i| i ^ 0x5A).collect()
+}
+
+fn run_dma_from_rom(oam_address: u8) {
+    let prg = program(
+        &[
+            // SEI; LDA #oam_address; STA $2003
+            &[0x78, 0xA9, oam_address, 0x8D, 0x03, 0x20],
+            // LDA #$81; STA $4014
+            &[0xA9, 0x81, 0x8D, 0x14, 0x40],
+            DUMP_OAM,
+        ],
+        &rom_data(),
+    );
+
+    let mut nes = NesTester::from_prg(&prg);
+    nes.clock_until_infinite_loop();
+
+    for (i, &data) in rom_data().iter().enumerate() {
+        let oam_index = (i as u8).wrapping_add(oam_address);
+        assert_eq!(
+            nes.cpu_read_address(0x300 + oam_index as u16),
+            data,
+            "OAM[{:02X}]",
+            oam_index
+        );
+    }
+}
+
+#[test]
+fn dma_from_rom() {
+    run_dma_from_rom(0);
+}
+
+#[test]
+fn dma_wraps_around_oam_address() {
+    // starting from the middle of OAM, should wrap around to the beginning
+    run_dma_from_rom(0x10);
+    run_dma_from_rom(0xFF);
+}
+
+#[test]
+fn dma_from_ppu_registers() {
+    let prg = program(
+        &[
+            // SEI; LDA #$21; STA $2006; LDA #$00; STA $2006
+            &[
+                0x78, 0xA9, 0x21, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20,
+            ],
+            // LDA #$20; STA $4014
+            &[0xA9, 0x20, 0x8D, 0x14, 0x40],
+            // LDA #$AB; STA $2007
+            &[0xA9, 0xAB, 0x8D, 0x07, 0x20],
+        ],
+        &[],
+    );
+
+    let mut nes = NesTester::from_prg(&prg);
+    nes.clock_until_infinite_loop();
+
+    // DMA reads `$2000-$20FF`, which mirror the 8 PPU registers, so `PPUDATA`
+    // is read 32 times (once every 8 bytes), each read increments the VRAM address by 1
+    assert_eq!(nes.ppu_read_address(0x2100 + 32), 0xAB);
+    assert_eq!(nes.ppu_read_address(0x2100 + 31), 0x00);
+    assert_eq!(nes.ppu_read_address(0x2100), 0x00);
+}
@@ -0,0 +1,72 @@
+use crate::tests::NesTester;
+
+/// Plays a looping DMC sample at the highest rate and reads the controller
+/// in a loop, when `safe` the DMC is stopped while reading
+fn reading_program(safe: bool) -> Vec<u8> {
+    let mut prg = vec![
+        // LDA #$4F; STA $4010 (loop, highest rate)
+        0xA9, 0x4F, 0x8D, 0x10, 0x40, // LDA #0; STA $4012; LDA #$FF; STA $4013
+        0xA9, 0x00, 0x8D, 0x12, 0x40, 0xA9, 0xFF, 0x8D, 0x13, 0x40,
+        // loop: LDA #$10; STA $4015
+        0xA9, 0x10, 0x8D, 0x15, 0x40, // LDA #1; STA $4016; LDA #0; STA $4016
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+    ];
+    if safe {
+        // STA $4015 (A is 0)
+        prg.extend_from_slice(&[0x8D, 0x15, 0x40]);
+    }
+    let loop_address = 0x800F_u16;
+    prg.extend_from_slice(&[
+        // LDX #8; read: LDA $4016; DEX; BNE read
+        0xA2,
+        0x08,
+        0xAD,
+        0x16,
+        0x40,
+        0xCA,
+        0xD0,
+        0xFA,
+        // JMP loop
+        0x4C,
+        loop_address as u8,
+        (loop_address >> 8) as u8,
+    ]);
+
+    prg
+}
+
+fn glitches_after_frames(safe: bool, stress: bool) -> u64 {
+    let mut nes = NesTester::from_prg(&reading_program(safe));
+    nes.nes.set_dmc_collision_stress(stress);
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+
+    nes.nes.controller_read_glitches()
+}
+
+#[test]
+fn vulnerable_routine_glitches() {
+    let normal = glitches_after_frames(false, false);
+    let stressed = glitches_after_frames(false, true);
+
+    assert_ne!(normal, 0);
+    assert!(stressed > normal);
+}
+
+#[test]
+fn safe_routine_never_glitches() {
+    assert_eq!(glitches_after_frames(true, false), 0);
+    assert_eq!(glitches_after_frames(true, true), 0);
+}
+
+#[test]
+fn glitches_cleared_on_reset() {
+    let mut nes = NesTester::from_prg(&reading_program(false));
+    nes.nes.set_dmc_collision_stress(true);
+    nes.clock_for_frame();
+    assert_ne!(nes.nes.controller_read_glitches(), 0);
+
+    nes.nes.reset();
+    assert_eq!(nes.nes.controller_read_glitches(), 0);
+}
@@ -0,0 +1,57 @@
+use super::NesTester;
+use crate::nes_audio::DmcSampleEvent;
+
+/// Starts a short sample, stops it and starts a looping one, then
+/// writes `$4015` again while it is playing
+const SAMPLES: &[u8] = &[
+    0xA9, 0x0A, //       LDA #$0A
+    0x8D, 0x10, 0x40, // STA $4010  (rate 10)
+    0xA9, 0x01, //       LDA #$01
+    0x8D, 0x12, 0x40, // STA $4012  (sample at $C040)
+    0xA9, 0x02, //       LDA #$02
+    0x8D, 0x13, 0x40, // STA $4013  (33 bytes)
+    0xA9, 0x10, //       LDA #$10
+    0x8D, 0x15, 0x40, // STA $4015  (start)
+    0xA9, 0x00, //       LDA #$00
+    0x8D, 0x15, 0x40, // STA $4015  (stop)
+    0xA9, 0x4F, //       LDA #$4F
+    0x8D, 0x10, 0x40, // STA $4010  (loop, rate 15)
+    0xA9, 0x03, //       LDA #$03
+    0x8D, 0x12, 0x40, // STA $4012  (sample at $C0C0)
+    0xA9, 0x04, //       LDA #$04
+    0x8D, 0x13, 0x40, // STA $4013  (65 bytes)
+    0xA9, 0x10, //       LDA #$10
+    0x8D, 0x15, 0x40, // STA $4015  (start)
+    0x8D, 0x15, 0x40, // STA $4015  (already playing)
+    0x4C, 0x2D, 0x80, // JMP $802D
+];
+
+#[test]
+fn logs_sample_starts() {
+    let mut nes = NesTester::from_prg(SAMPLES).nes;
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+
+    assert_eq!(
+        nes.dmc_sample_log(),
+        [
+            DmcSampleEvent {
+                address: 0xC040,
+                length: 33,
+                rate_index: 10,
+                looping: false,
+            },
+            DmcSampleEvent {
+                address: 0xC0C0,
+                length: 65,
+                rate_index: 15,
+                looping: true,
+            },
+        ]
+    );
+
+    nes.clear_dmc_sample_log();
+    nes.clock_for_frame();
+    assert!(nes.dmc_sample_log().is_empty());
+}
@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::NesTester;
+use crate::ExpansionPortDevice;
+
+/// Records all the values written to the expansion port
+struct MockDevice {
+    writes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl ExpansionPortDevice for MockDevice {
+    fn write_strobe(&mut self, bits: u8) {
+        self.writes.borrow_mut().push(bits);
+    }
+}
+
+const STROBE_PROGRAM: &[u8] = &[
+    0xA9, 0x05, // LDA #$05
+    0x8D, 0x16, 0x40, // STA $4016
+    0xA9, 0xFA, // LDA #$FA
+    0x8D, 0x16, 0x40, // STA $4016
+    0xA9, 0x03, // LDA #$03
+    0x8D, 0x16, 0x40, // STA $4016
+    0xA9, 0x06, // LDA #$06
+    0x8D, 0x16, 0x40, // STA $4016
+    0x4C, 0x14, 0x80, // JMP $8014
+];
+
+fn connect_mock(nes: &mut NesTester) -> Rc<RefCell<Vec<u8>>> {
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    nes.nes.set_expansion_port_device(Some(Box::new(MockDevice {
+        writes: writes.clone(),
+    })));
+    writes
+}
+
+#[test]
+fn expansion_port_strobe_bits() {
+    let mut nes = NesTester::from_prg(STROBE_PROGRAM);
+    let writes = connect_mock(&mut nes);
+    nes.clock_for_frame();
+
+    // only the 3 output lines are sent
+    assert_eq!(*writes.borrow(), [0x05, 0x02, 0x03, 0x06]);
+}
+
+#[test]
+fn expansion_port_strobe_saved() {
+    let mut nes = NesTester::from_prg(STROBE_PROGRAM);
+    nes.clock_for_frame();
+    let mut state = Vec::new();
+    nes.nes.save_state(&mut state).unwrap();
+
+    let mut nes = NesTester::from_prg(STROBE_PROGRAM);
+    let writes = connect_mock(&mut nes);
+    nes.nes.load_state(state.as_slice()).unwrap();
+
+    // the device gets the level of the lines at the time of the save
+    assert_eq!(*writes.borrow(), [0x06]);
+}
@@ -0,0 +1,132 @@
+use super::{rom_from_prg_chr, NesTester};
+use crate::{NametableSource, NES};
+
+/// `flags_6` bit 3, four-screen nametables
+const FOUR_SCREEN: u8 = 0b1000;
+/// `flags_6` bit 0
+const VERTICAL: u8 = 0b1;
+
+/// Writes `$11 * (n + 1)` to the first byte of each logical nametable `n` with
+/// `$2006`/`$2007`, and then reads them back into `$10-$13`
+fn nametables_program() -> Vec<u8> {
+    let mut prg = Vec::new();
+    for nametable in 0..4u8 {
+        prg.extend_from_slice(&[
+            // LDA #high; STA $2006; LDA #0; STA $2006
+            0xA9,
+            0x20 + nametable * 4,
+            0x8D,
+            0x06,
+            0x20,
+            0xA9,
+            0x00,
+            0x8D,
+            0x06,
+            0x20,
+            // LDA #value; STA $2007
+            0xA9,
+            0x11 * (nametable + 1),
+            0x8D,
+            0x07,
+            0x20,
+        ]);
+    }
+    for nametable in 0..4u8 {
+        prg.extend_from_slice(&[
+            // LDA #high; STA $2006; LDA #0; STA $2006
+            0xA9,
+            0x20 + nametable * 4,
+            0x8D,
+            0x06,
+            0x20,
+            0xA9,
+            0x00,
+            0x8D,
+            0x06,
+            0x20,
+            // LDA $2007 (buffered); LDA $2007; STA $10+n
+            0xAD,
+            0x07,
+            0x20,
+            0xAD,
+            0x07,
+            0x20,
+            0x85,
+            0x10 + nametable,
+        ]);
+    }
+    let loop_address = 0x8000 + prg.len() as u16;
+    // JMP self
+    prg.extend_from_slice(&[0x4C, loop_address as u8, (loop_address >> 8) as u8]);
+
+    prg
+}
+
+fn run(flags_6: u8) -> NesTester {
+    let nes = NES::new_from_bytes(&rom_from_prg_chr(&nametables_program(), &[], flags_6)).unwrap();
+    let mut nes = NesTester { nes };
+    nes.clock_until_infinite_loop();
+    nes
+}
+
+fn read_back(nes: &NesTester) -> Vec<u8> {
+    (0x10..0x14)
+        .map(|address| nes.cpu_read_address(address))
+        .collect()
+}
+
+#[test]
+fn four_separate_nametables() {
+    let nes = run(FOUR_SCREEN);
+    assert_eq!(read_back(&nes), [0x11, 0x22, 0x33, 0x44]);
+
+    // the mirrors at `$3000` as well
+    assert_eq!(nes.ppu_read_address(0x3800), 0x33);
+    assert_eq!(nes.ppu_read_address(0x3C00), 0x44);
+}
+
+#[test]
+fn vertical_mirroring_collapses_nametables() {
+    let nes = run(VERTICAL);
+    assert_eq!(read_back(&nes), [0x33, 0x44, 0x33, 0x44]);
+}
+
+#[test]
+fn cartridge_nametables_in_save_state() {
+    let nes = run(FOUR_SCREEN);
+    let mut state = Vec::new();
+    nes.nes.save_state(&mut state).unwrap();
+
+    // the same ROM, before it wrote anything
+    let mut loaded =
+        NesTester::from_rom(&rom_from_prg_chr(&nametables_program(), &[], FOUR_SCREEN));
+    assert_eq!(loaded.ppu_read_address(0x2C00), 0);
+    loaded.nes.load_state(state.as_slice()).unwrap();
+
+    let nametables =
+        [0x2000, 0x2400, 0x2800, 0x2C00].map(|address| loaded.ppu_read_address(address));
+    assert_eq!(nametables, [0x11, 0x22, 0x33, 0x44]);
+}
+
+#[test]
+fn nametable_mapping() {
+    use NametableSource::*;
+
+    let nes = run(FOUR_SCREEN);
+    assert_eq!(
+        nes.nes.debug_nametable_mapping(),
+        [CiramA, CiramB, CartridgeVram(0), CartridgeVram(1)]
+    );
+
+    let nes = run(VERTICAL);
+    assert_eq!(
+        nes.nes.debug_nametable_mapping(),
+        [CiramA, CiramB, CiramA, CiramB]
+    );
+
+    let nes = run(0);
+    assert_eq!(
+        nes.nes.debug_nametable_mapping(),
+        [CiramA, CiramA, CiramB, CiramB]
+    );
+}
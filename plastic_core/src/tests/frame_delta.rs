@@ -0,0 +1,161 @@
+use super::NesTester;
+use crate::nes_display::{DELTA_TILES_X, DELTA_TILES_Y, TV_BUFFER_SIZE};
+
+/// Fills the background with vertical stripes one tile wide, and in every NMI draws a
+/// sprite at `(X, 100)` and scrolls horizontally, then adds `sprite_step` to `X` and
+/// `scroll_step` to the scroll.
+fn delta_program(sprite_step: u8, scroll_step: u8) -> Vec<u8> {
+    #[rustfmt::skip]
+    let program = [
+        0xD8, // CLD
+        // wait for the PPU to warm up
+        0x2C, 0x02, 0x20, // BIT $2002
+        0x10, 0xFB, // BPL $8001
+        0x2C, 0x02, 0x20, // BIT $2002
+        0x10, 0xFB, // BPL $8006
+        // background palette 0: black, white, white, white
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x0F, // LDA #$0F
+        0x8D, 0x07, 0x20, // STA $2007
+        0xA9, 0x30, // LDA #$30
+        0x8D, 0x07, 0x20, // STA $2007
+        0x8D, 0x07, 0x20, // STA $2007
+        0x8D, 0x07, 0x20, // STA $2007
+        // sprite palette 0 color 3: red
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x13, // LDA #$13
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x16, // LDA #$16
+        0x8D, 0x07, 0x20, // STA $2007
+        // nametable 0: tiles 1, 0, 1, 0, ...
+        0xA9, 0x20, // LDA #$20
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA2, 0x04, // LDX #$04
+        0xA0, 0x78, // LDY #$78
+        0xA9, 0x01, // LDA #$01
+        0x8D, 0x07, 0x20, // STA $2007
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x07, 0x20, // STA $2007
+        0x88, // DEY
+        0xD0, 0xF3, // BNE $8042
+        0xCA, // DEX
+        0xD0, 0xEE, // BNE $8040
+        // enable NMI, background and sprites
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000
+        0xA9, 0x1E, // LDA #$1E
+        0x8D, 0x01, 0x20, // STA $2001
+        0x4C, 0x5C, 0x80, // JMP $805C
+        // NMI: sprite 0 at (X, 100) with tile 1
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x03, 0x20, // STA $2003
+        0xA9, 0x64, // LDA #$64
+        0x8D, 0x04, 0x20, // STA $2004
+        0xA9, 0x01, // LDA #$01
+        0x8D, 0x04, 0x20, // STA $2004
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x04, 0x20, // STA $2004
+        0xA5, 0x10, // LDA $10
+        0x8D, 0x04, 0x20, // STA $2004
+        0xA5, 0x11, // LDA $11
+        0x8D, 0x05, 0x20, // STA $2005
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x05, 0x20, // STA $2005
+        0xA5, 0x10, // LDA $10
+        0x18, // CLC
+        0x69, sprite_step, // ADC #sprite_step
+        0x85, 0x10, // STA $10
+        0xA5, 0x11, // LDA $11
+        0x18, // CLC
+        0x69, scroll_step, // ADC #scroll_step
+        0x85, 0x11, // STA $11
+        0x40, // RTI
+    ];
+
+    let mut prg = vec![0; 0x4000];
+    prg[..program.len()].copy_from_slice(&program);
+    // NMI vector
+    prg[0x3FFA] = 0x5F;
+    prg[0x3FFB] = 0x80;
+    prg
+}
+
+fn delta_tester(sprite_step: u8, scroll_step: u8) -> NesTester {
+    // tile 1 is filled with color 3
+    let mut chr = [0; 32];
+    chr[16..].fill(0xFF);
+
+    // horizontal mirroring, so the scrolled in nametable has the same stripes
+    let prg = delta_program(sprite_step, scroll_step);
+    let mut tester = NesTester::from_prg_chr(&prg, &chr, false);
+    for _ in 0..10 {
+        tester.clock_for_frame();
+    }
+    tester
+}
+
+#[test]
+fn first_delta_is_full() {
+    let mut tester = delta_tester(0, 0);
+
+    let delta = tester.nes.frame_delta();
+    assert!(delta.is_full());
+    let mut buffer = vec![0; TV_BUFFER_SIZE];
+    delta.apply(&mut buffer);
+    assert_eq!(buffer, tester.pixel_buffer());
+
+    // nothing moves
+    tester.clock_for_frame();
+    assert!(tester.nes.frame_delta().is_empty());
+
+    tester.nes.force_full_frame_delta();
+    assert!(tester.nes.frame_delta().is_full());
+}
+
+#[test]
+fn moving_sprite_delta() {
+    let mut tester = delta_tester(1, 0);
+
+    let mut buffer = vec![0; TV_BUFFER_SIZE];
+    tester.nes.frame_delta().apply(&mut buffer);
+
+    for _ in 0..20 {
+        tester.clock_for_frame();
+
+        let delta = tester.nes.frame_delta();
+        // the sprite is at Y=100, so it is drawn in scanlines 101-108, in rows 12 and 13.
+        // It moves 1 pixel, so the old and new positions cover at most 3 columns
+        assert!(!delta.is_empty());
+        assert!(delta.tiles.len() <= 6, "{} tiles", delta.tiles.len());
+        assert!(delta.tiles.iter().all(|tile| (12..=13).contains(&tile.row)));
+
+        delta.apply(&mut buffer);
+        assert_eq!(buffer, tester.pixel_buffer());
+    }
+}
+
+#[test]
+fn scrolling_delta() {
+    let mut tester = delta_tester(0, 1);
+
+    let mut buffer = vec![0; TV_BUFFER_SIZE];
+    tester.nes.frame_delta().apply(&mut buffer);
+
+    for _ in 0..5 {
+        tester.clock_for_frame();
+
+        // every tile has an edge of the stripes that moves
+        let delta = tester.nes.frame_delta();
+        assert!(delta.is_full());
+        assert_eq!(delta.tiles.len(), DELTA_TILES_X * DELTA_TILES_Y);
+
+        delta.apply(&mut buffer);
+        assert_eq!(buffer, tester.pixel_buffer());
+    }
+}
@@ -0,0 +1,56 @@
+use crate::tests::NesTester;
+
+#[test]
+fn idle_loop_frame_stats() {
+    let mut nes = NesTester::new("../test_roms/blargg_ppu_tests/palette_ram.nes").unwrap();
+    nes.nes.set_distinct_pc_tracking(true);
+
+    // the test ends in an infinite loop, so after reaching it, the
+    // whole frame should be spent in that loop
+    nes.clock_until_infinite_loop();
+    nes.clock_for_frame();
+
+    let stats = nes.nes.frame_stats();
+    assert!(stats.ended_in_infinite_loop);
+    assert!(stats.instructions > 0);
+    assert_eq!(stats.dma_cycles, 0);
+    assert!(
+        stats.distinct_pcs.unwrap() <= 4,
+        "distinct pcs: {:?}",
+        stats.distinct_pcs
+    );
+}
+
+#[test]
+fn busy_frame_stats() {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+
+    let stats = nes.nes.frame_stats();
+    assert!(!stats.ended_in_infinite_loop);
+    assert!((29780..=29781).contains(&stats.cpu_cycles));
+    assert!(stats.instructions > 29780 / 7);
+    // not enabled
+    assert_eq!(stats.distinct_pcs, None);
+}
+
+#[test]
+fn rendering_enabled_frame_stats() {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/rom_singles/01-basics.nes").unwrap();
+
+    // the ROM starts with the screen off while initializing
+    nes.clock_for_frame();
+    let stats = nes.nes.frame_stats();
+    assert!(!stats.rendering_was_enabled);
+    assert!(stats.frame_is_black);
+
+    // then it enables rendering to show the text
+    for _ in 0..30 {
+        nes.clock_for_frame();
+    }
+    let stats = nes.nes.frame_stats();
+    assert!(stats.rendering_was_enabled);
+    assert!(!stats.frame_is_black);
+}
@@ -0,0 +1,105 @@
+use crate::tests::NesTester;
+
+/// Enables the NMI and the background, and the NMI handler increments `$00`
+/// and writes it to the backdrop color, so each frame has one color
+fn color_per_frame_program() -> Vec<u8> {
+    let mut prg = vec![
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000
+        0xA9, 0x0A, // LDA #$0A
+        0x8D, 0x01, 0x20, // STA $2001
+        0x4C, 0x0A, 0x80, // loop: JMP loop
+        // NMI ($800D)
+        0xE6, 0x00, // INC $00
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA5, 0x00, // LDA $00
+        0x29, 0x3F, // AND #$3F
+        0x8D, 0x07, 0x20, // STA $2007
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0x8D, 0x06, 0x20, // STA $2006
+        0x40, // RTI
+    ];
+    prg.resize(0x4000, 0);
+    prg[0x3FFA] = 0x0D;
+    prg[0x3FFB] = 0x80;
+    prg
+}
+
+#[test]
+fn each_step_is_one_whole_frame() {
+    let mut nes = NesTester::from_prg(&color_per_frame_program());
+
+    // let the PPU warm up and the program enable rendering
+    for _ in 0..4 {
+        nes.nes.clock_until_vblank();
+    }
+    assert!(nes.nes.frame_stats().rendering_was_enabled);
+
+    let mut counter = nes.cpu_read_address(0x00);
+    for frame in 5..=40 {
+        let result = nes.nes.clock_until_vblank();
+        assert!(result.frame_complete);
+        assert_eq!(nes.nes.frame_number(), frame);
+
+        // one NMI per step
+        let next_counter = nes.cpu_read_address(0x00);
+        assert_eq!(next_counter, counter.wrapping_add(1));
+        counter = next_counter;
+
+        // the backdrop changes only in vertical blank, so a whole frame is one color
+        let pixels = nes.pixel_buffer();
+        assert!(pixels.chunks_exact(3).all(|pixel| pixel == &pixels[..3]));
+    }
+}
+
+#[test]
+fn steps_follow_the_ppu_frame_length() {
+    // rendering enabled, odd frames are one dot shorter
+    let mut rendering = NesTester::from_prg(&color_per_frame_program());
+    // rendering disabled
+    let mut idle = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+
+    for _ in 0..4 {
+        rendering.nes.clock_until_vblank();
+        idle.nes.clock_until_vblank();
+    }
+
+    for _ in 0..10 {
+        // 6 frames of 89342 and 89341 dots, 3 dots per CPU cycle
+        let cycles = (0..6)
+            .map(|_| rendering.nes.clock_until_vblank().cycles_run)
+            .sum::<u32>();
+        assert_eq!(cycles, 89342 + 89341);
+
+        let cycles = (0..3)
+            .map(|_| {
+                let result = idle.nes.clock_until_vblank();
+                assert!((29780..=29781).contains(&result.cycles_run));
+                result.cycles_run
+            })
+            .sum::<u32>();
+        assert_eq!(cycles, 89342);
+    }
+}
+
+#[test]
+fn finishes_partial_frame_and_mixes_with_clock_for_frame() {
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+
+    nes.nes.clock_until_vblank();
+    let budget = nes.nes.clock_with_budget(1000);
+    assert!(!budget.frame_complete);
+
+    let result = nes.nes.clock_until_vblank();
+    assert_eq!(result.frame_cycles, budget.cycles_run + result.cycles_run);
+    assert_eq!(nes.nes.frame_stats().cpu_cycles, result.frame_cycles);
+    assert!((29780..=29781).contains(&result.frame_cycles));
+
+    nes.clock_for_frame();
+    nes.nes.clock_until_vblank();
+    assert_eq!(nes.nes.frame_number(), 4);
+}
@@ -0,0 +1,70 @@
+use crate::display::frame_hash;
+use crate::tests::NesTester;
+use crate::NES;
+use std::time::{Duration, Instant};
+
+#[test]
+fn idle_screen_animates() {
+    let mut nes = NES::new_without_file();
+
+    let mut hashes = Vec::new();
+    for _ in 0..16 {
+        assert!(nes.clock_with_budget(1000).frame_complete);
+        hashes.push(frame_hash(nes.pixel_buffer()));
+    }
+
+    // not black
+    assert!(nes.pixel_buffer().iter().any(|&byte| byte != 0));
+    // the border moves every 4 frames
+    assert_eq!(hashes[0], hashes[3]);
+    assert_ne!(hashes[0], hashes[4]);
+    assert_ne!(hashes[4], hashes[8]);
+}
+
+#[test]
+fn idle_screen_disabled() {
+    let mut nes = NES::new_without_file();
+    nes.clock_for_frame();
+    nes.set_idle_screen(false);
+
+    for _ in 0..8 {
+        // still completes, so frontends waiting for the end of the frame don't spin
+        assert!(nes.clock_with_budget(1000).frame_complete);
+        assert!(nes.pixel_buffer().iter().all(|&byte| byte == 0));
+    }
+    assert_eq!(nes.frame_number(), 9);
+}
+
+fn fastest_frame(nes: &mut NES) -> Duration {
+    (0..20)
+        .map(|_| {
+            let start = Instant::now();
+            nes.clock_for_frame();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn empty_console_is_cheap() {
+    let mut empty = NES::new_without_file();
+    let mut loaded = NesTester::from_prg(&[
+        0x4C, 0x00, 0x80, // JMP $8000
+    ]);
+
+    // drawing the idle screen converts the whole screen, which is not free in debug builds
+    for (idle_screen, min_ratio) in [(true, 4), (false, 10)] {
+        empty.set_idle_screen(idle_screen);
+        let frames = empty.frame_number();
+        let empty_time = fastest_frame(&mut empty);
+        let loaded_time = fastest_frame(loaded.nes_mut());
+
+        assert_eq!(empty.frame_number(), frames + 20);
+        assert!(
+            empty_time * min_ratio <= loaded_time,
+            "empty: {empty_time:?}, loaded: {loaded_time:?}"
+        );
+    }
+    assert_eq!(loaded.nes_mut().frame_number(), 40);
+}
@@ -0,0 +1,115 @@
+use crate::tests::NesTester;
+use crate::NESKey;
+
+const NMI_HANDLER: u16 = 0x8100;
+
+/// Enables the NMI and polls the controller once in the NMI handler, storing
+/// the buttons in `$10` in the order of [`NESKey`]
+fn polling_program() -> Vec<u8> {
+    let mut prg = vec![
+        // LDA #$80; STA $2000; JMP self
+        0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x05, 0x80,
+    ];
+
+    prg.resize((NMI_HANDLER - 0x8000) as usize, 0xEA);
+    prg.extend_from_slice(&[
+        // LDA #1; STA $4016; LDA #0; STA $4016
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+        // LDX #8; read: LDA $4016; LSR A; ROR $10; DEX; BNE read
+        0xA2, 0x08, 0xAD, 0x16, 0x40, 0x4A, 0x66, 0x10, 0xCA, 0xD0, 0xF7, // RTI
+        0x40,
+    ]);
+
+    prg.resize(0x4000, 0);
+    prg[0x3FFA] = NMI_HANDLER as u8;
+    prg[0x3FFB] = (NMI_HANDLER >> 8) as u8;
+    prg
+}
+
+#[test]
+fn poll_once_per_frame() {
+    let mut nes = NesTester::from_prg(&polling_program());
+    nes.nes.set_controller_state(NESKey::A, true);
+    nes.nes.set_controller_state(NESKey::Right, true);
+
+    for frame in 1..=3 {
+        nes.clock_for_frame();
+
+        assert_eq!(nes.nes.frame_stats().controller_polls, 1);
+        assert_eq!(nes.nes.last_polled_input(1), (0b1000_0001, frame));
+    }
+    assert_eq!(nes.cpu_read_address(0x10), 0b1000_0001);
+
+    // the game didn't see the new keys yet
+    nes.nes.set_controller_state(NESKey::A, false);
+    assert_eq!(nes.nes.last_polled_input(1), (0b1000_0001, 3));
+    nes.clock_for_frame();
+    assert_eq!(nes.nes.last_polled_input(1), (0b1000_0000, 4));
+
+    // player 2 is latched by the same strobe, with nothing pressed
+    assert_eq!(nes.nes.last_polled_input(2), (0, 4));
+}
+
+/// Same as [`polling_program`], reading both controllers, player 2 from `$4017` into `$11`
+pub(super) fn two_players_program() -> Vec<u8> {
+    let mut prg = polling_program();
+    let handler = NMI_HANDLER as usize - 0x8000;
+    prg[handler..handler + 27].copy_from_slice(&[
+        // LDA #1; STA $4016; LDA #0; STA $4016
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+        // LDX #8; read: LDA $4016; LSR A; ROR $10; LDA $4017; LSR A; ROR $11; DEX; BNE read
+        0xA2, 0x08, 0xAD, 0x16, 0x40, 0x4A, 0x66, 0x10, 0xAD, 0x17, 0x40, 0x4A, 0x66, 0x11, 0xCA,
+        0xD0, 0xF1,
+    ]);
+    // RTI
+    prg[handler + 27] = 0x40;
+    prg
+}
+
+#[test]
+fn player_2_from_second_port() {
+    let mut nes = NesTester::from_prg(&two_players_program());
+    nes.nes.set_controller_state(NESKey::Start, true);
+    nes.nes.set_controller2_state(NESKey::B, true);
+    nes.nes.set_controller2_state(NESKey::Left, true);
+
+    nes.clock_for_frame();
+    assert_eq!(nes.cpu_read_address(0x10), 0b0000_1000);
+    assert_eq!(nes.cpu_read_address(0x11), 0b0100_0010);
+    assert_eq!(nes.nes.last_polled_input(1), (0b0000_1000, 1));
+    assert_eq!(nes.nes.last_polled_input(2), (0b0100_0010, 1));
+    // both are latched by one strobe
+    assert_eq!(nes.nes.frame_stats().controller_polls, 1);
+
+    nes.nes.set_controller2_state(NESKey::B, false);
+    nes.nes.set_controller2_state(NESKey::Up, true);
+    nes.clock_for_frame();
+    assert_eq!(nes.cpu_read_address(0x10), 0b0000_1000);
+    assert_eq!(nes.cpu_read_address(0x11), 0b0101_0000);
+}
+
+#[test]
+fn no_polls_without_strobe() {
+    // JMP self
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    nes.nes.set_controller_state(NESKey::A, true);
+    nes.clock_for_frame();
+
+    assert_eq!(nes.nes.frame_stats().controller_polls, 0);
+    assert_eq!(nes.nes.last_polled_input(1), (0, 0));
+}
+
+#[test]
+fn last_polled_input_in_save_state() {
+    let mut nes = NesTester::from_prg(&polling_program());
+    nes.nes.set_controller_state(NESKey::Start, true);
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+
+    let mut state = Vec::new();
+    nes.nes.save_state(&mut state).unwrap();
+
+    let mut loaded = NesTester::from_prg(&polling_program());
+    loaded.nes.load_state(&mut state.as_slice()).unwrap();
+    assert_eq!(loaded.nes.last_polled_input(1), (0b1000, 2));
+}
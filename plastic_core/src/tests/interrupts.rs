@@ -0,0 +1,71 @@
+use crate::tests::NesTester;
+
+const IRQ_HANDLER: u16 = 0x8100;
+
+/// build a program that enables the APU frame IRQ with interrupts disabled, waits
+/// long enough for the IRQ to be pending, then runs `parts` followed by an infinite loop.
+///
+/// The IRQ handler stores `X` in `$10` and the low byte of the return address in `$11`.
+fn program(parts: &[&[u8]]) -> Vec<u8> {
+    let mut prg = vec![
+        // SEI; LDA #0; STA $4017
+        0x78, 0xA9, 0x00, 0x8D, 0x17, 0x40,
+        // LDX #0; LDY #0; loop: DEX; BNE loop; DEY; BNE loop
+        0xA2, 0x00, 0xA0, 0x00, 0xCA, 0xD0, 0xFD, 0x88, 0xD0, 0xFA,
+    ];
+    prg.extend_from_slice(&parts.concat());
+    let loop_address = 0x8000 + prg.len() as u16;
+    // JMP loop_address
+    prg.extend_from_slice(&[0x4C, loop_address as u8, (loop_address >> 8) as u8]);
+
+    prg.resize((IRQ_HANDLER - 0x8000) as usize, 0xEA);
+    prg.extend_from_slice(&[
+        // STX $10; TSX; LDA $0102,X; STA $11
+        0x86, 0x10, 0xBA, 0xBD, 0x02, 0x01, 0x85, 0x11,
+        // LDA $4015 (acknowledge); JMP self
+        0xAD, 0x15, 0x40, 0x4C, 0x0B, 0x81,
+    ]);
+
+    prg.resize(0x4000, 0);
+    prg[0x3FFE] = IRQ_HANDLER as u8;
+    prg[0x3FFF] = (IRQ_HANDLER >> 8) as u8;
+    prg
+}
+
+/// run the program and return the `X` value and the low byte of the return
+/// address as seen by the IRQ handler
+fn run_until_irq(parts: &[&[u8]]) -> (u8, u8) {
+    let mut nes = NesTester::from_prg(&program(parts));
+    nes.clock_until_infinite_loop();
+
+    (nes.cpu_read_address(0x10), nes.cpu_read_address(0x11))
+}
+
+#[test]
+fn cli_latency() {
+    // CLI; INX; INX; INX
+    let (x, return_address) = run_until_irq(&[&[0x58, 0xE8, 0xE8, 0xE8]]);
+
+    // one instruction runs after CLI before the IRQ
+    assert_eq!(x, 1);
+    assert_eq!(return_address, 0x12);
+}
+
+#[test]
+fn sei_after_cli_does_not_block_irq() {
+    // CLI; SEI; INX
+    let (x, return_address) = run_until_irq(&[&[0x58, 0x78, 0xE8]]);
+
+    // the IRQ is taken right after SEI
+    assert_eq!(x, 0);
+    assert_eq!(return_address, 0x12);
+}
+
+#[test]
+fn plp_latency() {
+    // LDA #0; PHA; PLP; INX; INX; INX
+    let (x, return_address) = run_until_irq(&[&[0xA9, 0x00, 0x48, 0x28, 0xE8, 0xE8, 0xE8]]);
+
+    assert_eq!(x, 1);
+    assert_eq!(return_address, 0x15);
+}
@@ -0,0 +1,97 @@
+use super::NesTester;
+use crate::cpu6502::CPUBusTrait;
+use crate::{Diagnostic, NESKey};
+
+/// `loop: JMP loop`
+const IDLE_LOOP: [u8; 3] = [0x4C, 0x00, 0x80];
+
+/// Put `data` on the CPU data bus by writing and reading it back from RAM
+fn set_open_bus(tester: &mut NesTester, data: u8) {
+    let bus = tester.nes.cpu_bus_mut();
+    bus.write(0x0000, data);
+    assert_eq!(bus.read(0x0000), data);
+}
+
+#[test]
+fn io_register_reads() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    tester.nes.set_controller_state(NESKey::A, true);
+    tester.nes.cpu_bus_mut().write(0x4016, 1);
+    tester.nes.cpu_bus_mut().write(0x4016, 0);
+
+    for address in 0x4000..=0x401F {
+        set_open_bus(&mut tester, 0xA5);
+        let data = tester.nes.cpu_bus_mut().read(address);
+
+        let expected = match address {
+            // the APU status, nothing is playing
+            0x4015 => 0x00,
+            // the controller drives bits 0-4, the first read is the A button
+            0x4016 => 0xA1,
+            // nothing is pressed on the controller of player 2
+            0x4017 => 0xA0,
+            // write-only APU registers, OAM DMA and the test mode registers
+            _ => 0xA5,
+        };
+        assert_eq!(data, expected, "read ${:04X}", address);
+    }
+}
+
+#[test]
+fn apu_status_read_keeps_open_bus() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+
+    set_open_bus(&mut tester, 0x5A);
+    let bus = tester.nes.cpu_bus_mut();
+    assert_eq!(bus.read(0x4015), 0x00);
+    // the status read is internal to the CPU, the data bus still has the last value
+    assert_eq!(bus.read(0x4018), 0x5A);
+}
+
+#[test]
+fn only_4016_writes_strobe_the_controller() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    tester.nes.set_controller_state(NESKey::A, true);
+
+    for address in (0x4000..=0x401F).filter(|&address| address != 0x4016 && address != 0x4014) {
+        let bus = tester.nes.cpu_bus_mut();
+        bus.write(address, 1);
+        bus.write(address, 0);
+        assert_eq!(bus.read(0x4016) & 1, 0, "strobe with ${:04X}", address);
+    }
+
+    let bus = tester.nes.cpu_bus_mut();
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+    assert_eq!(bus.read(0x4016) & 1, 1);
+}
+
+#[test]
+fn test_register_writes_ignored() {
+    let mut tester = NesTester::from_prg(&IDLE_LOOP);
+    tester.nes.set_homebrew_diagnostics(true);
+
+    for address in 0x4018..=0x401F {
+        set_open_bus(&mut tester, 0x00);
+        tester.nes.cpu_bus_mut().write(address, 0x55);
+    }
+
+    let addresses = tester
+        .nes
+        .take_diagnostics()
+        .into_iter()
+        .map(|diagnostic| match diagnostic {
+            Diagnostic::TestRegisterWrite { address, data, .. } => {
+                assert_eq!(data, 0x55);
+                address
+            }
+            diagnostic => panic!("unexpected diagnostic {:?}", diagnostic),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(addresses, (0x4018..=0x401F).collect::<Vec<_>>());
+
+    // without the diagnostics, the writes are silently ignored
+    tester.nes.set_homebrew_diagnostics(false);
+    tester.nes.cpu_bus_mut().write(0x401F, 0x55);
+    assert!(tester.nes.take_diagnostics().is_empty());
+}
@@ -0,0 +1,55 @@
+use crate::display::{
+    LAYER_SOURCE_BACKGROUND, LAYER_SOURCE_MASK, LAYER_SOURCE_SPRITE_BEHIND,
+    LAYER_SOURCE_SPRITE_FRONT, LAYER_SPRITE_0, TV_WIDTH,
+};
+use crate::tests::NesTester;
+
+/// returns the bounding box `(min_x, min_y, max_x, max_y)` of all pixels
+/// with the layer `source` and the number of these pixels
+fn layer_bounds(layer_map: &[u8], source: u8) -> Option<((usize, usize, usize, usize), usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    let mut count = 0;
+
+    for (i, layer) in layer_map.iter().enumerate() {
+        if layer & LAYER_SOURCE_MASK == source {
+            let (x, y) = (i % TV_WIDTH, i / TV_WIDTH);
+            let b = bounds.get_or_insert((x, y, x, y));
+            *b = (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y));
+            count += 1;
+        }
+    }
+
+    bounds.map(|b| (b, count))
+}
+
+#[test]
+fn layer_map_sprite_pixels() {
+    let mut nes = NesTester::new("../test_roms/sprite_hit_tests/01.basics.nes").unwrap();
+    assert!(nes.nes.layer_map().is_none());
+    nes.nes.set_layer_map_enabled(true);
+
+    // at this point, the test displays a solid 8x8 sprite 0 in front of the background
+    for _ in 0..9 {
+        nes.clock_for_frame();
+    }
+
+    // sprite 0 from the OAM DMA page
+    let sprite_y = nes.cpu_read_address(0x200) as usize;
+    let sprite_x = nes.cpu_read_address(0x203) as usize;
+
+    let layer_map = nes.nes.layer_map().unwrap();
+
+    let (bounds, count) = layer_bounds(layer_map, LAYER_SOURCE_SPRITE_FRONT).unwrap();
+    // sprites are rendered one scanline after their Y position
+    assert_eq!(bounds, (sprite_x, sprite_y + 1, sprite_x + 7, sprite_y + 8));
+    assert_eq!(count, 64);
+    assert!(layer_bounds(layer_map, LAYER_SOURCE_SPRITE_BEHIND).is_none());
+    assert!(layer_bounds(layer_map, LAYER_SOURCE_BACKGROUND).is_some());
+
+    let index = (sprite_y + 1) * TV_WIDTH + sprite_x;
+    assert_ne!(layer_map[index] & LAYER_SPRITE_0, 0);
+    assert_eq!(layer_map[0] & LAYER_SPRITE_0, 0);
+
+    nes.nes.set_layer_map_enabled(false);
+    assert!(nes.nes.layer_map().is_none());
+}
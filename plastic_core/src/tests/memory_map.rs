@@ -0,0 +1,131 @@
+use crate::{MemoryRegion, NES};
+
+/// Switch the PRG ROM bank at `$8000` to bank 3 with MMC1, which also enables
+/// PRG RAM, and then loop forever
+const SWITCH_PROGRAM: &[u8] = &[
+    0xA9, 0x03, // LDA #$03
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x4A, // LSR A
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x4A, // LSR A
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x4A, // LSR A
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x4A, // LSR A
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x4C, 0x15, 0xC0, // JMP $C015
+];
+
+const PRG_BANKS: usize = 8;
+
+/// MMC1 game with 128kb PRG ROM, running `SWITCH_PROGRAM` from the last bank
+fn mmc1_rom() -> Vec<u8> {
+    let mut rom = vec![
+        0x4E,
+        0x45,
+        0x53,
+        0x1A,
+        PRG_BANKS as u8,
+        1,
+        0x10,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ];
+    let mut prg = vec![0; PRG_BANKS * 0x4000];
+    let last_bank = (PRG_BANKS - 1) * 0x4000;
+    prg[last_bank..last_bank + SWITCH_PROGRAM.len()].copy_from_slice(SWITCH_PROGRAM);
+    // reset vector, $C000
+    prg[0x1FFFC] = 0x00;
+    prg[0x1FFFD] = 0xC0;
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0; 0x2000]);
+
+    rom
+}
+
+fn prg_rom(bank: u16) -> MemoryRegion {
+    MemoryRegion::PrgRom {
+        bank,
+        mapper_bank_size: 0x4000,
+    }
+}
+
+#[test]
+fn memory_region_labels() {
+    let nes = NES::new_from_bytes(&mmc1_rom()).unwrap();
+
+    for (address, region) in [
+        (0x0000, MemoryRegion::Ram),
+        (0x07FF, MemoryRegion::Ram),
+        (0x0800, MemoryRegion::RamMirror),
+        (0x1FFF, MemoryRegion::RamMirror),
+        (0x2000, MemoryRegion::PpuRegisters),
+        (0x2007, MemoryRegion::PpuRegisters),
+        (0x2008, MemoryRegion::PpuRegistersMirror),
+        (0x3FFF, MemoryRegion::PpuRegistersMirror),
+        (0x4000, MemoryRegion::ApuIo),
+        (0x4017, MemoryRegion::ApuIo),
+        (0x4018, MemoryRegion::ApuIoTest),
+        (0x4020, MemoryRegion::Unmapped),
+        (0x5FFF, MemoryRegion::Unmapped),
+        // PRG RAM is disabled at power-up
+        (0x6000, MemoryRegion::Unmapped),
+        // the power-up bank is the last one
+        (0x8000, prg_rom(7)),
+        (0xBFFF, prg_rom(7)),
+        (0xC000, prg_rom(7)),
+        (0xFFFF, prg_rom(7)),
+    ] {
+        assert_eq!(nes.memory_region(address), region, "${:04X}", address);
+    }
+}
+
+#[test]
+fn memory_map_after_bank_switch() {
+    let mut nes = NES::new_from_bytes(&mmc1_rom()).unwrap();
+
+    let fixed = [
+        (0x0000..=0x07FF, MemoryRegion::Ram),
+        (0x0800..=0x1FFF, MemoryRegion::RamMirror),
+        (0x2000..=0x2007, MemoryRegion::PpuRegisters),
+        (0x2008..=0x3FFF, MemoryRegion::PpuRegistersMirror),
+        (0x4000..=0x4017, MemoryRegion::ApuIo),
+        (0x4018..=0x401F, MemoryRegion::ApuIoTest),
+    ];
+
+    let mut expected = fixed.to_vec();
+    expected.extend([
+        (0x4020..=0x7FFF, MemoryRegion::Unmapped),
+        // the same bank in 2 ranges
+        (0x8000..=0xBFFF, prg_rom(7)),
+        (0xC000..=0xFFFF, prg_rom(7)),
+    ]);
+    assert_eq!(nes.memory_map(), expected);
+
+    nes.clock_for_frame();
+
+    assert_eq!(nes.memory_region(0x8000), prg_rom(3));
+    assert_eq!(nes.memory_region(0xC000), prg_rom(7));
+
+    let mut expected = fixed.to_vec();
+    expected.extend([
+        (0x4020..=0x5FFF, MemoryRegion::Unmapped),
+        (
+            0x6000..=0x7FFF,
+            MemoryRegion::PrgRam {
+                bank: 0,
+                battery: false,
+            },
+        ),
+        (0x8000..=0xBFFF, prg_rom(3)),
+        (0xC000..=0xFFFF, prg_rom(7)),
+    ]);
+    assert_eq!(nes.memory_map(), expected);
+}
@@ -0,0 +1,123 @@
+use super::{rom_from_prg_chr, NesTester};
+use crate::cpu6502::CPURunState;
+use crate::display::{COLORS, TV_HEIGHT, TV_WIDTH};
+use crate::ppu2c02::PpuBackend;
+
+/// Sets the IRQ latch of MMC3 to `LATCH_OFFSET`, with the background at `$0000` and the
+/// sprites at `$1000` so A12 rises once every scanline. The IRQ handler sets the
+/// red emphasis bit and the NMI handler clears it and reloads the counter, so the
+/// rows after the IRQ are red.
+const IRQ_PROGRAM: &[u8] = &[
+    0x78, // SEI
+    0x2C, 0x02, 0x20, // BIT $2002
+    0x10, 0xFB, // BPL $8001
+    0x2C, 0x02, 0x20, // BIT $2002
+    0x10, 0xFB, // BPL $8006
+    0xA9, 0x3F, // LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x21, // LDA #$21
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x88, // LDA #$88
+    0x8D, 0x00, 0x20, // STA $2000
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x05, 0x20, // STA $2005
+    0x8D, 0x05, 0x20, // STA $2005
+    0xA9, 0x00, // LDA #latch
+    0x8D, 0x00, 0xC0, // STA $C000
+    0x8D, 0x01, 0xC0, // STA $C001
+    0x8D, 0x01, 0xE0, // STA $E001
+    0xA9, 0x1E, // LDA #$1E
+    0x8D, 0x01, 0x20, // STA $2001
+    0x58, // CLI
+    0x4C, 0x38, 0x80, // JMP $8038
+    // IRQ
+    0x8D, 0x00, 0xE0, // STA $E000
+    0xA9, 0x3E, // LDA #$3E
+    0x8D, 0x01, 0x20, // STA $2001
+    0x40, // RTI
+    // NMI
+    0xA9, 0x1E, // LDA #$1E
+    0x8D, 0x01, 0x20, // STA $2001
+    0x8D, 0x01, 0xC0, // STA $C001
+    0x8D, 0x01, 0xE0, // STA $E001
+    0x40, // RTI
+];
+const LATCH_OFFSET: usize = 0x28;
+const IRQ_HANDLER: u16 = 0x803B;
+const NMI_HANDLER: u16 = 0x8044;
+
+fn irq_rom(latch: u8) -> Vec<u8> {
+    let mut prg = vec![0; 0x4000];
+    prg[..IRQ_PROGRAM.len()].copy_from_slice(IRQ_PROGRAM);
+    prg[LATCH_OFFSET] = latch;
+    prg[0x3FFA..0x3FFC].copy_from_slice(&NMI_HANDLER.to_le_bytes());
+    prg[0x3FFE..].copy_from_slice(&IRQ_HANDLER.to_le_bytes());
+
+    rom_from_prg_chr(&prg, &[], 0x40)
+}
+
+/// The first row of the frame with the emphasis set by the IRQ handler
+fn first_red_row(latch: u8) -> Option<usize> {
+    let mut nes = NesTester::from_rom(&irq_rom(latch));
+    for _ in 0..4 {
+        nes.clock_for_frame();
+    }
+
+    // the backdrop color `$21` without emphasis
+    let color = COLORS[0x21];
+    let blue = [color.r, color.g, color.b].repeat(TV_WIDTH);
+
+    let row = |y: usize| &nes.pixel_buffer()[y * TV_WIDTH * 3..(y + 1) * TV_WIDTH * 3];
+    let first = (0..TV_HEIGHT).find(|&y| row(y) != blue)?;
+
+    // whole rows are red, the IRQ is handled in the horizontal blank
+    assert!((first..TV_HEIGHT).all(|y| row(y) == row(first)));
+    Some(first)
+}
+
+#[test]
+fn irq_after_latch_scanlines() {
+    // the counter is reloaded on the pre-render scanline, and reaches 0 at the
+    // end of scanline `latch - 1`
+    assert_eq!(first_red_row(99), Some(99));
+    assert_eq!(first_red_row(20), Some(20));
+    assert_eq!(first_red_row(1), Some(1));
+}
+
+#[test]
+fn irq_with_zero_latch_on_pre_render() {
+    // the counter is reloaded with 0 on the pre-render scanline, and the IRQ is
+    // already there
+    assert_eq!(first_red_row(0), Some(0));
+}
+
+/// The CPU cycles at which the interrupts (the IRQ and the NMI) start in the first
+/// `frames` frames
+fn interrupt_cycles(latch: u8, backend: PpuBackend, frames: u64) -> Vec<u64> {
+    let mut nes = NesTester::from_rom(&irq_rom(latch));
+    nes.nes.set_ppu_backend(backend);
+    // the backend is applied on reset
+    nes.nes.reset();
+
+    (0..frames * 29781)
+        .filter(|_| matches!(nes.clock(), CPURunState::StartingInterrupt))
+        .collect()
+}
+
+#[test]
+fn scanline_backend_irq_timing() {
+    // both backends must toggle A12 at the same dots, so the IRQs happen at the
+    // same CPU cycles
+    for latch in (0..240).step_by(7) {
+        let dot_accurate = interrupt_cycles(latch, PpuBackend::DotAccurate, 6);
+        // the program starts after 2 frames, then there is an IRQ and an NMI every frame
+        assert!(dot_accurate.len() >= 6, "latch {latch}");
+        assert_eq!(
+            interrupt_cycles(latch, PpuBackend::Scanline, 6),
+            dot_accurate,
+            "latch {latch}"
+        );
+    }
+}
@@ -2,15 +2,68 @@ use crate::cartridge::CartridgeError;
 use crate::common::{Bus, Device};
 use crate::cpu6502::{CPUBusTrait, CPURunState};
 use crate::display::{COLORS, TV_WIDTH};
-use crate::nes::NES;
+use crate::nes::{NESBuilder, NES};
+use crate::ppu2c02::PpuBackend;
 use std::{
     convert::From,
     error::Error,
     fmt::{Debug, Display, Formatter, Result as fmtResult},
 };
 
+mod alignment;
+mod apu_register_log;
+mod apu_states;
+mod audio_ring;
+mod audio_sampling;
 mod blargg_tests;
+mod budget;
+mod channel_tap;
+#[cfg(feature = "compare")]
+mod compare;
+mod compat;
+mod conformance;
+mod cpu_halt;
+mod decimal_mode;
+mod diagnostics;
+mod dma;
+mod dmc_conflict;
+mod dmc_sample_log;
+mod expansion_port;
+mod four_screen;
+mod frame_delta;
+mod frame_stats;
+mod frame_step;
+mod idle_screen;
+mod input_polling;
+mod interrupts;
+mod io_registers;
+mod layer_map;
+mod memory_map;
+mod mmc3_irq;
+mod movie;
+mod nametable_view;
+mod output_delay;
+#[cfg(feature = "overrides")]
+mod overrides;
+mod palette_generator;
+mod pixel_output;
+#[cfg(feature = "debug")]
+mod ppu_snapshot;
+#[cfg(feature = "profiling")]
+mod profiling;
+#[cfg(feature = "rl")]
+mod rl;
+mod rom_data;
 mod save_state;
+mod scoreboard;
+mod shared_rom;
+mod sram_activity;
+mod sram_file;
+#[cfg(feature = "state-json")]
+mod state_json;
+mod state_sync;
+mod timing;
+mod timing_mode;
 
 pub enum TestError {
     CartridgeError(CartridgeError),
@@ -50,6 +103,28 @@ pub struct NesTester {
     nes: NES,
 }
 
+/// A ROM file with 16KB PRG and 8KB CHR ROM, `prg` is placed at `$8000` and
+/// the reset vector points to it, `chr` at the start of the CHR ROM.
+///
+/// `flags_6` is byte 6 of the header (mirroring, battery and the low nibble of the mapper)
+pub fn rom_from_prg_chr(prg: &[u8], chr: &[u8], flags_6: u8) -> Vec<u8> {
+    let mut rom = vec![
+        0x4E, 0x45, 0x53, 0x1A, 1, 1, flags_6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let mut prg_data = vec![0; 0x4000];
+    prg_data[..prg.len()].copy_from_slice(prg);
+    // reset vector
+    prg_data[0x3FFC] = 0x00;
+    prg_data[0x3FFD] = 0x80;
+    rom.extend_from_slice(&prg_data);
+    // CHR
+    let mut chr_data = vec![0; 0x2000];
+    chr_data[..chr.len()].copy_from_slice(chr);
+    rom.extend_from_slice(&chr_data);
+
+    rom
+}
+
 impl NesTester {
     pub fn new(filename: &str) -> Result<Self, CartridgeError> {
         let nes = NES::new(filename)?;
@@ -57,6 +132,36 @@ impl NesTester {
         Ok(Self { nes })
     }
 
+    /// Same as [`NesTester::new`], but rendering with the PPU `backend` from power-up
+    pub fn new_with_ppu_backend(
+        filename: &str,
+        backend: PpuBackend,
+    ) -> Result<Self, CartridgeError> {
+        let nes = NESBuilder::from_file(filename)
+            .ppu_backend(backend)
+            .build()?;
+
+        Ok(Self { nes })
+    }
+
+    /// Create an NROM cartridge with 16KB PRG, `prg` is placed at `$8000` and
+    /// the reset vector points to it
+    pub fn from_prg(prg: &[u8]) -> Self {
+        Self::from_prg_chr(prg, &[], false)
+    }
+
+    /// Same as [`NesTester::from_prg`], with `chr` at the start of the 8KB CHR ROM
+    pub fn from_prg_chr(prg: &[u8], chr: &[u8], vertical_mirroring: bool) -> Self {
+        Self::from_rom(&rom_from_prg_chr(prg, chr, vertical_mirroring as u8))
+    }
+
+    /// Load the iNES file in `rom`
+    pub fn from_rom(rom: &[u8]) -> Self {
+        let nes = NES::new_from_bytes(rom).unwrap();
+
+        Self { nes }
+    }
+
     pub fn cpu_read_address(&self, address: u16) -> u8 {
         self.nes.cpu_bus().read(address)
     }
@@ -69,6 +174,11 @@ impl NesTester {
         self.nes.pixel_buffer()
     }
 
+    /// The emulator, for tests outside of this module
+    pub fn nes_mut(&mut self) -> &mut NES {
+        &mut self.nes
+    }
+
     pub fn clock(&mut self) -> CPURunState {
         self.nes.clock().unwrap()
     }
@@ -0,0 +1,123 @@
+use crate::common::save_state::SaveError;
+use crate::cpu::CpuState;
+use crate::movie::Movie;
+use crate::tests::NesTester;
+
+const NMI_HANDLER: u16 = 0x8100;
+
+/// Enables the NMI, and in the NMI handler polls the controller into `$10`
+/// and uses it as the backdrop color, so the frames depend on the inputs
+fn input_color_program() -> Vec<u8> {
+    let mut prg = vec![
+        // LDA #$80; STA $2000; JMP self
+        0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x05, 0x80,
+    ];
+
+    prg.resize((NMI_HANDLER - 0x8000) as usize, 0xEA);
+    prg.extend_from_slice(&[
+        // LDA #1; STA $4016; LDA #0; STA $4016
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+        // LDX #8; read: LDA $4016; LSR A; ROR $10; DEX; BNE read
+        0xA2, 0x08, 0xAD, 0x16, 0x40, 0x4A, 0x66, 0x10, 0xCA, 0xD0, 0xF7,
+        // LDA #$3F; STA $2006; LDA #0; STA $2006
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20,
+        // LDA $10; AND #$3F; STA $2007
+        0xA5, 0x10, 0x29, 0x3F, 0x8D, 0x07, 0x20,
+        // LDA #$3F; STA $2006; LDA #0; STA $2006 (show the backdrop)
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, 0xA9, 0x00, 0x8D, 0x06, 0x20, // RTI
+        0x40,
+    ]);
+
+    prg.resize(0x4000, 0);
+    prg[0x3FFA] = NMI_HANDLER as u8;
+    prg[0x3FFB] = (NMI_HANDLER >> 8) as u8;
+    prg
+}
+
+fn input(frame: usize) -> u8 {
+    (frame * 73 + (frame >> 4)) as u8
+}
+
+/// The CPU registers and RAM, the whole state is not compared, as it contains the
+/// frame number of the last controller poll, which counts the frames of the session
+fn state(nes: &NesTester) -> (CpuState, Vec<u8>) {
+    let ram = (0..0x800)
+        .map(|address| nes.cpu_read_address(address))
+        .collect();
+    (nes.nes.cpu_state(), ram)
+}
+
+fn record(frames: usize, snapshot_interval: u32) -> Movie {
+    let mut nes = NesTester::from_prg(&input_color_program());
+    let mut movie = Movie::new(snapshot_interval);
+    for frame in 0..frames {
+        nes.nes
+            .record_movie_frame(&mut movie, input(frame))
+            .unwrap();
+    }
+    movie
+}
+
+#[test]
+fn seek_matches_straight_replay() {
+    let movie = record(2000, 500);
+    assert_eq!(
+        movie.snapshot_frames().collect::<Vec<_>>(),
+        [0, 500, 1000, 1500]
+    );
+    assert_eq!(movie.seek_index(1337), Some(1000));
+    assert_eq!(movie.seek_index(1000), Some(500));
+
+    let mut straight = NesTester::from_prg(&input_color_program());
+    straight.nes.play_movie_from_frame(&movie, 0).unwrap();
+    for &input in &movie.inputs()[..1337] {
+        for key in crate::NESKey::ALL {
+            straight
+                .nes
+                .set_controller_state(key, input & key as u8 != 0);
+        }
+        straight.clock_for_frame();
+    }
+
+    let mut seek = NesTester::from_prg(&input_color_program());
+    seek.nes.play_movie_from_frame(&movie, 1337).unwrap();
+
+    assert!(seek.pixel_buffer() == straight.pixel_buffer());
+    assert_eq!(state(&seek), state(&straight));
+    assert_eq!(seek.cpu_read_address(0x10), input(1336));
+}
+
+#[test]
+fn saved_movie_seek() {
+    let movie = record(120, 50);
+
+    let mut data = Vec::new();
+    movie.save_to(&mut data).unwrap();
+    let loaded = Movie::load_from(data.as_slice()).unwrap();
+    assert_eq!(loaded.inputs(), movie.inputs());
+    assert_eq!(loaded.snapshot_interval(), 50);
+
+    let mut expected = NesTester::from_prg(&input_color_program());
+    expected.nes.play_movie_from_frame(&movie, 0).unwrap();
+    for frame in 0..110 {
+        expected
+            .nes
+            .record_movie_frame(&mut Movie::new(0), input(frame))
+            .unwrap();
+    }
+
+    let mut nes = NesTester::from_prg(&input_color_program());
+    nes.nes.play_movie_from_frame(&loaded, 110).unwrap();
+    assert!(nes.pixel_buffer() == expected.pixel_buffer());
+    assert_eq!(state(&nes), state(&expected));
+
+    assert!(matches!(
+        nes.nes.play_movie_from_frame(&loaded, 121),
+        Err(SaveError::MovieTooShort {
+            frame: 121,
+            length: 120
+        })
+    ));
+    // truncated
+    assert!(Movie::load_from(&data[..data.len() - 1]).is_err());
+}
@@ -0,0 +1,101 @@
+use super::NesTester;
+use crate::NametableView;
+
+/// Sets the palette and one tile at the top left of nametable 0, and then
+/// scrolls to `(12, 34)` in nametable 1
+const SCROLL_PROGRAM: &[u8] = &[
+    0xA9, 0x3F, // LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x0F, // LDA #$0F
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x16, // LDA #$16
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x27, // LDA #$27
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x30, // LDA #$30
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x20, // LDA #$20
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x01, // LDA #$01
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x0C, // LDA #$0C
+    0x8D, 0x05, 0x20, // STA $2005
+    0xA9, 0x22, // LDA #$22
+    0x8D, 0x05, 0x20, // STA $2005
+    0xA9, 0x01, // LDA #$01
+    0x8D, 0x00, 0x20, // STA $2000
+    0xA9, 0x0A, // LDA #$0A
+    0x8D, 0x01, 0x20, // STA $2001
+    0x4C, 0x46, 0x80, // JMP $8046
+];
+
+fn run_scroll_program(vertical_mirroring: bool) -> NametableView {
+    // tile 1 is filled with color 3
+    let mut chr = [0; 32];
+    chr[16..].fill(0xFF);
+
+    let mut nes = NesTester::from_prg_chr(SCROLL_PROGRAM, &chr, vertical_mirroring).nes;
+    for _ in 0..3 {
+        nes.clock_for_frame();
+    }
+
+    nes.debug_nametable_arrangement()
+}
+
+#[test]
+fn vertical_mirroring_arrangement() {
+    let view = run_scroll_program(true);
+
+    assert_eq!(view.nametables.len(), 2);
+    assert_eq!(view.arrangement, [0, 1, 0, 1]);
+
+    for (x, y) in [(0, 0), (7, 7), (0, 240)] {
+        assert_eq!(view.pixel(x, y), 0x30, "({}, {})", x, y);
+    }
+    for (x, y) in [(8, 0), (0, 8), (256, 0), (256, 240)] {
+        assert_eq!(view.pixel(x, y), 0x0F, "({}, {})", x, y);
+    }
+    assert!(view.nametables[1].iter().all(|&color| color == 0x0F));
+}
+
+#[test]
+fn horizontal_mirroring_arrangement() {
+    let view = run_scroll_program(false);
+
+    assert_eq!(view.nametables.len(), 2);
+    assert_eq!(view.arrangement, [0, 0, 1, 1]);
+    assert_eq!(view.pixel(0, 0), 0x30);
+    assert_eq!(view.pixel(256, 0), 0x30);
+    assert_eq!(view.pixel(0, 240), 0x0F);
+}
+
+#[test]
+fn viewport_from_scroll() {
+    let view = run_scroll_program(true);
+
+    // nametable 1 is at the right
+    assert_eq!((view.scroll_x, view.scroll_y), (256 + 12, 34));
+    assert_eq!(
+        view.viewport_rects(),
+        vec![(268, 34, 244, 240), (0, 34, 12, 240)]
+    );
+}
+
+#[test]
+fn viewport_wraps_vertically() {
+    let view = NametableView {
+        nametables: vec![vec![0; 256 * 240]],
+        arrangement: [0; 4],
+        scroll_x: 0,
+        scroll_y: 400,
+    };
+
+    assert_eq!(
+        view.viewport_rects(),
+        vec![(0, 400, 256, 80), (0, 0, 256, 160)]
+    );
+}
@@ -0,0 +1,88 @@
+use super::NesTester;
+use crate::NES;
+
+/// Writes a new backdrop color and square wave period in a loop, so every frame
+/// has different pixels and audio
+const CHANGING_OUTPUT: &[u8] = &[
+    0xA9, 0x01, //       LDA #$01
+    0x8D, 0x15, 0x40, // STA $4015
+    0xA9, 0xBF, //       LDA #$BF
+    0x8D, 0x00, 0x40, // STA $4000
+    0xA9, 0x08, //       LDA #$08
+    0x8D, 0x03, 0x40, // STA $4003
+    // loop:
+    0xE8, //             INX
+    0xA9, 0x3F, //       LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, //       LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0x8E, 0x07, 0x20, // STX $2007
+    0x8E, 0x02, 0x40, // STX $4002
+    0x4C, 0x0F, 0x80, // JMP loop
+];
+
+fn new_nes() -> NES {
+    let mut nes = NesTester::from_prg(CHANGING_OUTPUT).nes;
+    nes.set_rendering_disabled_backdrop(true);
+    nes
+}
+
+#[test]
+fn delayed_output_matches_earlier_frames() {
+    let mut nes = new_nes();
+    let mut delayed = new_nes();
+    delayed.set_output_delay_frames(2);
+
+    let mut frames = Vec::new();
+    for frame in 0..20 {
+        nes.clock_for_frame();
+        delayed.clock_for_frame();
+        frames.push((nes.pixel_buffer().to_vec(), nes.audio_buffer()));
+
+        let audio = delayed.audio_buffer();
+        if frame >= 2 {
+            let (pixels, expected_audio) = &frames[frame - 2];
+            assert!(
+                delayed.pixel_buffer() == pixels.as_slice(),
+                "frame {}",
+                frame
+            );
+            assert_eq!(&audio, expected_audio, "frame {}", frame);
+        } else {
+            assert!(audio.is_empty());
+        }
+    }
+
+    // the frames are different, and the emulation is not affected
+    assert!(frames[10].0 != frames[11].0);
+    assert_eq!(nes.cpu_state(), delayed.cpu_state());
+    let (mut state, mut delayed_state) = (Vec::new(), Vec::new());
+    nes.save_state(&mut state).unwrap();
+    delayed.save_state(&mut delayed_state).unwrap();
+    assert!(state == delayed_state);
+}
+
+#[test]
+fn delay_refills_after_loading_state() {
+    let mut nes = new_nes();
+    nes.set_output_delay_frames(2);
+    for _ in 0..5 {
+        nes.clock_for_frame();
+    }
+    let state = nes.snapshot().unwrap();
+    nes.clock_for_frame();
+
+    nes.restore_snapshot(&state).unwrap();
+    let stale = nes.pixel_buffer().to_vec();
+    _ = nes.audio_buffer();
+
+    // the last presented frame stays until the delay is filled again
+    for _ in 0..2 {
+        nes.clock_for_frame();
+        assert!(nes.pixel_buffer() == stale.as_slice());
+        assert!(nes.audio_buffer().is_empty());
+    }
+    nes.clock_for_frame();
+    assert!(nes.pixel_buffer() != stale.as_slice());
+    assert!(!nes.audio_buffer().is_empty());
+}
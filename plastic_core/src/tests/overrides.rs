@@ -0,0 +1,162 @@
+use super::NesTester;
+use crate::overrides::{AppliedOverride, OverridesError};
+use crate::{ExpansionDevice, Region, NES};
+
+const ALL_INSTRS: &str = "../test_roms/instr_test-v5/all_instrs.nes";
+
+#[test]
+fn crc32_entry() {
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]).nes;
+    let overrides = format!(
+        r#"
+        [[game]]
+        crc32 = "{:08X}"
+        region = "PAL"
+        expansion_device = 4
+        bus_conflicts = true
+        submapper = 1
+
+        [[game]]
+        crc32 = "0x12345678"
+        region = "ntsc"
+        "#,
+        nes.rom_crc32()
+    );
+
+    assert_eq!(
+        nes.load_overrides(&overrides).unwrap(),
+        vec![
+            AppliedOverride::Region(Region::Pal),
+            AppliedOverride::ExpansionDevice(ExpansionDevice::VsSystem),
+            AppliedOverride::BusConflicts(true),
+            AppliedOverride::Submapper(1),
+        ]
+    );
+    assert_eq!(nes.region(), Region::Pal);
+    assert_eq!(nes.expansion_device(), ExpansionDevice::VsSystem);
+    assert_eq!(nes.submapper_id(), 1);
+}
+
+#[test]
+fn name_entry_and_priority() {
+    let mut nes = NES::new(ALL_INSTRS).unwrap();
+    let overrides = format!(
+        r#"
+        [[game]]
+        crc32 = "{:08X}"
+        region = "ntsc"
+
+        [[game]]
+        name = "ALL_INSTR?.*"
+        region = "pal"
+        expansion_device = 1
+
+        [[game]]
+        name = "other*"
+        bus_conflicts = true
+        "#,
+        nes.rom_crc32()
+    );
+
+    // CRC entries have priority over name entries, regardless of the order
+    assert_eq!(
+        nes.load_overrides(&overrides).unwrap(),
+        vec![
+            AppliedOverride::Region(Region::Ntsc),
+            AppliedOverride::ExpansionDevice(ExpansionDevice::StandardControllers),
+        ]
+    );
+}
+
+#[test]
+fn explicit_settings_have_priority() {
+    let mut nes = NES::new(ALL_INSTRS).unwrap();
+    nes.set_region(Region::Pal);
+
+    let applied = nes
+        .load_overrides(
+            r#"
+            [[game]]
+            name = "all_instrs.nes"
+            region = "ntsc"
+            "#,
+        )
+        .unwrap();
+
+    assert!(applied.is_empty());
+    assert_eq!(nes.region(), Region::Pal);
+}
+
+#[test]
+fn overrides_are_not_explicit() {
+    let mut nes = NES::new(ALL_INSTRS).unwrap();
+    let overrides = r#"
+        [[game]]
+        name = "*"
+        region = "pal"
+        "#;
+
+    nes.load_overrides(overrides).unwrap();
+    // loading again still applies them
+    assert_eq!(
+        nes.load_overrides(&overrides.replace("pal", "ntsc"))
+            .unwrap(),
+        vec![AppliedOverride::Region(Region::Ntsc)]
+    );
+}
+
+#[test]
+fn no_matching_entry() {
+    let mut nes = NES::new(ALL_INSTRS).unwrap();
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+    let cpu_state = nes.cpu_state();
+
+    let applied = nes
+        .load_overrides(
+            r#"
+            [[game]]
+            name = "*.unf"
+            region = "pal"
+            "#,
+        )
+        .unwrap();
+
+    assert!(applied.is_empty());
+    // not reset
+    assert_eq!(nes.cpu_state(), cpu_state);
+}
+
+#[test]
+fn invalid_documents() {
+    let mut nes = NES::new(ALL_INSTRS).unwrap();
+
+    assert!(matches!(
+        nes.load_overrides("[[game]\nname = 1"),
+        Err(OverridesError::Parse(_))
+    ));
+    assert!(matches!(
+        nes.load_overrides("[[game]]\nname = \"*\"\nmapper = 4"),
+        Err(OverridesError::Parse(_))
+    ));
+    assert!(matches!(
+        nes.load_overrides("[[game]]\nregion = \"pal\""),
+        Err(OverridesError::MissingKey { game: 0 })
+    ));
+    assert!(matches!(
+        nes.load_overrides("[[game]]\nname = \"a\"\n[[game]]\nname = \"*\"\nregion = \"dendy\""),
+        Err(OverridesError::InvalidValue {
+            game: 1,
+            field: "region",
+            ..
+        })
+    ));
+    assert!(matches!(
+        nes.load_overrides("[[game]]\ncrc32 = \"xyz\""),
+        Err(OverridesError::InvalidValue { field: "crc32", .. })
+    ));
+
+    // nothing was applied
+    assert_eq!(nes.region(), Region::Ntsc);
+}
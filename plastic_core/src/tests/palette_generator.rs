@@ -0,0 +1,141 @@
+use super::NesTester;
+use crate::display::{Color, PaletteGenerator, COLORS, TV_WIDTH};
+
+/// Entries of the built-in palette that are not what the hardware outputs
+const NON_HARDWARE_COLORS: [usize; 2] = [0x3C, 0x3D];
+
+fn difference(a: &Color, b: &Color) -> u32 {
+    (a.r.abs_diff(b.r) as u32 + a.g.abs_diff(b.g) as u32 + a.b.abs_diff(b.b) as u32) / 3
+}
+
+#[test]
+fn default_is_close_to_builtin_palette() {
+    let generated = PaletteGenerator::default().generate()[0];
+
+    let differences = (0..0x40)
+        .filter(|index| !NON_HARDWARE_COLORS.contains(index))
+        .map(|index| difference(&generated[index], &COLORS[index]))
+        .collect::<Vec<_>>();
+
+    let average = differences.iter().sum::<u32>() / differences.len() as u32;
+    assert!(average <= 16, "average difference {average}");
+    for (index, &difference) in differences.iter().enumerate() {
+        assert!(
+            difference <= 48,
+            "color {index:#04X} differs by {difference}"
+        );
+    }
+
+    // the blacks stay black
+    for index in [0x0D, 0x0E, 0x0F, 0x1D, 0x1E, 0x1F, 0x2E, 0x2F, 0x3E, 0x3F] {
+        assert_eq!(generated[index], Color { r: 0, g: 0, b: 0 });
+    }
+}
+
+/// The part of the color taken by the `channel`
+fn share(color: &Color, channel: usize) -> f32 {
+    let channels = [color.r, color.g, color.b];
+    let total = channels.iter().map(|&c| c as f32).sum::<f32>();
+    channels[channel] as f32 / total
+}
+
+#[test]
+fn emphasis_favors_its_color() {
+    let palettes = PaletteGenerator::default().generate();
+
+    // red, green and blue are bits 0, 1 and 2
+    for channel in 0..3 {
+        let emphasized = &palettes[1 << channel];
+        for gray in [0x00, 0x10, 0x20] {
+            assert!(share(&emphasized[gray], channel) > share(&palettes[0][gray], channel));
+        }
+    }
+
+    for index in (0x11..=0x1C).chain(0x21..=0x2C) {
+        let (normal, red) = (&palettes[0][index], &palettes[1][index]);
+        // no red to raise
+        if normal.r == 0 && red.r == 0 {
+            continue;
+        }
+        assert!(
+            share(red, 0) > share(normal, 0),
+            "color {index:#04X}: {normal:?} -> {red:?}"
+        );
+    }
+
+    // all emphasis bits darken everything
+    for (normal, dark) in palettes[0].iter().zip(palettes[7].iter()) {
+        assert!(dark.r <= normal.r && dark.g <= normal.g && dark.b <= normal.b);
+    }
+}
+
+#[test]
+fn parameters_change_the_palette() {
+    let default = PaletteGenerator::default().generate()[0];
+
+    let gray = PaletteGenerator {
+        saturation: 0.,
+        ..PaletteGenerator::default()
+    }
+    .generate()[0];
+    assert!(gray
+        .iter()
+        .all(|color| color.r == color.g && color.g == color.b));
+
+    let bright = PaletteGenerator {
+        brightness: 0.1,
+        ..PaletteGenerator::default()
+    }
+    .generate()[0];
+    assert!(bright[0x00].r > default[0x00].r);
+
+    let rotated = PaletteGenerator {
+        hue: 30.,
+        ..PaletteGenerator::default()
+    }
+    .generate()[0];
+    assert_ne!(rotated[0x16], default[0x16]);
+    // grays have no hue
+    assert_eq!(rotated[0x10], default[0x10]);
+}
+
+#[test]
+fn generated_palette_is_displayed() {
+    let mut nes = NesTester::from_prg(&[
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x16, // LDA #$16
+        0x8D, 0x07, 0x20, // STA $2007
+        0xA9, 0x2A, // LDA #$2A, red emphasis and background
+        0x8D, 0x01, 0x20, // STA $2001
+        0x4C, 0x14, 0x80, // JMP $8014
+    ]);
+    let pixel = |nes: &NesTester| {
+        let offset = (100 * TV_WIDTH + 100) * 3;
+        let pixel = &nes.pixel_buffer()[offset..offset + 3];
+        Color {
+            r: pixel[0],
+            g: pixel[1],
+            b: pixel[2],
+        }
+    };
+
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+    let builtin = pixel(&nes);
+
+    let generator = PaletteGenerator {
+        hue: 20.,
+        ..PaletteGenerator::default()
+    };
+    nes.nes_mut().set_generated_palette(Some(generator));
+    nes.clock_for_frame();
+    assert_eq!(pixel(&nes), generator.generate()[1][0x16]);
+    assert_ne!(pixel(&nes), builtin);
+
+    nes.nes_mut().set_generated_palette(None);
+    nes.clock_for_frame();
+    assert_eq!(pixel(&nes), builtin);
+}
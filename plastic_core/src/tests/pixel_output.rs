@@ -0,0 +1,222 @@
+use crate::display::{frame_hash, Color, COLORS, TV, TV_HEIGHT, TV_WIDTH};
+use crate::ppu2c02::PpuBackend;
+use crate::tests::NesTester;
+
+/// The hashes of the pixel buffer after running some frames, should not change
+/// unless there is a change in the rendering output
+const EXPECTED_HASHES: [(&str, u32, u64); 3] = [
+    (
+        "../test_roms/sprite_hit_tests/01.basics.nes",
+        60,
+        0xA3BDE71903A31271,
+    ),
+    (
+        "../test_roms/blargg_ppu_tests/palette_ram.nes",
+        60,
+        0x24D688820C189FE1,
+    ),
+    (
+        "../test_roms/instr_test-v5/rom_singles/01-basics.nes",
+        120,
+        0x757548A2E561D269,
+    ),
+];
+
+fn check_hashes(backend: PpuBackend) {
+    for (rom, frames, expected_hash) in EXPECTED_HASHES {
+        let mut nes = NesTester::new_with_ppu_backend(rom, backend).unwrap();
+        for _ in 0..frames {
+            nes.clock_for_frame();
+        }
+
+        assert_eq!(frame_hash(nes.pixel_buffer()), expected_hash, "{}", rom);
+    }
+}
+
+#[test]
+fn pixel_buffer_hashes() {
+    check_hashes(PpuBackend::DotAccurate);
+}
+
+/// These ROMs don't change the PPU state in the middle of scanlines, so the
+/// scanline backend renders the same output
+#[test]
+fn scanline_backend_pixel_buffer_hashes() {
+    check_hashes(PpuBackend::Scanline);
+}
+
+/// Set `$3F00` to `$0F` and `$3F05` to `$16` with rendering disabled, then point
+/// the VRAM address to `address_high:address_low` and loop
+fn palette_address_program(address_high: u8, address_low: u8) -> Vec<u8> {
+    vec![
+        0xA9,
+        0x3F, // LDA #$3F
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0xA9,
+        0x00, // LDA #$00
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0xA9,
+        0x0F, // LDA #$0F
+        0x8D,
+        0x07,
+        0x20, // STA $2007
+        0xA9,
+        0x3F, // LDA #$3F
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0xA9,
+        0x05, // LDA #$05
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0xA9,
+        0x16, // LDA #$16
+        0x8D,
+        0x07,
+        0x20, // STA $2007
+        0xA9,
+        address_high, // LDA #address_high
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0xA9,
+        address_low, // LDA #address_low
+        0x8D,
+        0x06,
+        0x20, // STA $2006
+        0x4C,
+        0x28,
+        0x80, // JMP $8028
+    ]
+}
+
+fn all_pixels_are(nes: &NesTester, color_code: u8) -> bool {
+    let color = &COLORS[color_code as usize];
+    nes.pixel_buffer()
+        .chunks_exact(3)
+        .all(|pixel| pixel == [color.r, color.g, color.b])
+}
+
+#[test]
+fn rendering_disabled_backdrop() {
+    let mut nes = NesTester::from_prg(&palette_address_program(0x20, 0x00));
+    nes.nes.set_rendering_disabled_backdrop(true);
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+
+    assert!(all_pixels_are(&nes, 0x0F));
+}
+
+#[test]
+fn rendering_disabled_palette_address() {
+    for backend in [PpuBackend::DotAccurate, PpuBackend::Scanline] {
+        let mut nes = NesTester::from_prg(&palette_address_program(0x3F, 0x05));
+        nes.nes.set_ppu_backend(backend);
+        nes.nes.set_rendering_disabled_backdrop(true);
+        nes.clock_for_frame();
+        nes.clock_for_frame();
+
+        // the entry at `v` leaks into the backdrop
+        assert!(all_pixels_are(&nes, 0x16), "{:?}", backend);
+
+        // disabled, the last frame stays
+        nes.nes.set_rendering_disabled_backdrop(false);
+        nes.clock_for_frame();
+        nes.clock_for_frame();
+        assert!(all_pixels_are(&nes, 0x16), "{:?}", backend);
+    }
+}
+
+#[test]
+fn palette_address_mid_scanline() {
+    // keep the palette setup, and switch `v` between the palettes and the nametables
+    let mut prg = palette_address_program(0x3F, 0x05);
+    prg.truncate(0x1E);
+    prg.extend_from_slice(&[
+        0xA9, 0x3F, // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x05, // LDA #$05
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x20, // LDA #$20
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0x4C, 0x1E, 0x80, // JMP $801E
+    ]);
+    let mut nes = NesTester::from_prg(&prg);
+    nes.nes.set_rendering_disabled_backdrop(true);
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+
+    let leaked = &COLORS[0x16];
+    let backdrop = &COLORS[0x0F];
+    // streaks of both colors in the same scanline
+    let scanline = &nes.pixel_buffer()[..TV_WIDTH * 3];
+    assert!(scanline
+        .chunks_exact(3)
+        .any(|pixel| pixel == [leaked.r, leaked.g, leaked.b]));
+    assert!(scanline
+        .chunks_exact(3)
+        .any(|pixel| pixel == [backdrop.r, backdrop.g, backdrop.b]));
+}
+
+#[test]
+fn emphasis_changes_mid_scanline() {
+    // changes in the middle of scanlines, and back to the same emphasis
+    let emphasis_at = |index: usize| ((index / 1000) % 8) as u8;
+    let color_at = |index: usize| (index % 0x40) as u8;
+
+    let mut tv = TV::new();
+    for index in 0..TV_WIDTH * TV_HEIGHT {
+        let (x, y) = (index % TV_WIDTH, index / TV_WIDTH);
+        tv.set_pixel(x as u32, y as u32, color_at(index), emphasis_at(index));
+    }
+    tv.signal_end_of_frame();
+    let mixed = tv.display_pixel_buffer().to_vec();
+
+    for emphasis in 0..8 {
+        for index in 0..TV_WIDTH * TV_HEIGHT {
+            let (x, y) = (index % TV_WIDTH, index / TV_WIDTH);
+            tv.set_pixel(x as u32, y as u32, color_at(index), emphasis);
+        }
+        tv.signal_end_of_frame();
+        let uniform = tv.display_pixel_buffer();
+
+        for (index, (mixed, uniform)) in mixed
+            .chunks_exact(3)
+            .zip(uniform.chunks_exact(3))
+            .enumerate()
+        {
+            if emphasis_at(index) == emphasis {
+                assert_eq!(mixed, uniform, "pixel {} emphasis {}", index, emphasis);
+            }
+        }
+    }
+}
+
+#[test]
+fn palette_change_applies_to_displayed_frame() {
+    let mut tv = TV::new();
+    for y in 0..TV_HEIGHT {
+        for x in 0..TV_WIDTH {
+            tv.set_pixel(x as u32, y as u32, x as u8, (y % 8) as u8);
+        }
+    }
+    tv.signal_end_of_frame();
+    let before = frame_hash(tv.display_pixel_buffer());
+
+    let color = Color { r: 1, g: 2, b: 3 };
+    tv.set_palette(Some(&[[color; 0x40]; 8]));
+    assert!(tv
+        .display_pixel_buffer()
+        .chunks_exact(3)
+        .all(|pixel| pixel == [1, 2, 3]));
+
+    tv.set_palette(None);
+    assert_eq!(frame_hash(tv.display_pixel_buffer()), before);
+}
@@ -0,0 +1,104 @@
+use super::NesTester;
+use crate::PpuSnapshot;
+
+/// Sets 2 palette colors, tile 1 at `$2005`, one sprite, and then reads `$2005`
+/// so the `$2007` read buffer is filled
+const SNAPSHOT_PROGRAM: &[u8] = &[
+    0xA9, 0x3F, // LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x0F, // LDA #$0F
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x16, // LDA #$16
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x20, // LDA #$20
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x05, // LDA #$05
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x01, // LDA #$01
+    0x8D, 0x07, 0x20, // STA $2007
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x03, 0x20, // STA $2003
+    0xA9, 0x10, // LDA #$10
+    0x8D, 0x04, 0x20, // STA $2004
+    0xA9, 0x01, // LDA #$01
+    0x8D, 0x04, 0x20, // STA $2004
+    0xA9, 0xE1, // LDA #$E1
+    0x8D, 0x04, 0x20, // STA $2004
+    0xA9, 0x20, // LDA #$20
+    0x8D, 0x04, 0x20, // STA $2004
+    0xA9, 0x20, // LDA #$20
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x05, // LDA #$05
+    0x8D, 0x06, 0x20, // STA $2006
+    0xAD, 0x07, 0x20, // LDA $2007
+    0x4C, 0x49, 0x80, // JMP $8049
+];
+
+fn run_snapshot_program(vertical_mirroring: bool) -> NesTester {
+    // tile 1 of the first pattern table is filled with color 1, and the top left
+    // pixel of tile `$11` of the second one has color 2
+    let mut chr = vec![0; 0x1119];
+    chr[0x10..0x18].fill(0xFF);
+    chr[0x1118] = 0x80;
+
+    let mut nes = NesTester::from_prg_chr(SNAPSHOT_PROGRAM, &chr, vertical_mirroring);
+    for _ in 0..3 {
+        nes.clock_for_frame();
+    }
+    nes
+}
+
+#[test]
+fn nametables_with_mirroring() {
+    let snapshot = run_snapshot_program(true).nes.debug_ppu_snapshot();
+    assert_eq!(snapshot.nametables[0][5], 1);
+    assert_eq!(snapshot.nametables[2][5], 1);
+    assert_eq!(snapshot.nametables[1][5], 0);
+    assert_eq!(snapshot.nametables[3][5], 0);
+
+    let snapshot = run_snapshot_program(false).nes.debug_ppu_snapshot();
+    assert_eq!(snapshot.nametables[0][5], 1);
+    assert_eq!(snapshot.nametables[1][5], 1);
+    assert_eq!(snapshot.nametables[2][5], 0);
+}
+
+#[test]
+fn pattern_tables_palettes_and_oam() {
+    let snapshot = run_snapshot_program(true).nes.debug_ppu_snapshot();
+    const SIZE: usize = PpuSnapshot::PATTERN_TABLE_SIZE;
+
+    for (table, x, y, color) in [(0, 8, 0, 1), (0, 15, 7, 1), (0, 16, 0, 0), (0, 8, 8, 0)] {
+        assert_eq!(snapshot.pattern_tables[table][y * SIZE + x], color);
+    }
+    assert_eq!(snapshot.pattern_tables[1][8 * SIZE + 8], 2);
+    assert_eq!(snapshot.pattern_tables[1][8 * SIZE + 9], 0);
+    assert!(snapshot.pattern_tables[1][..8 * SIZE]
+        .iter()
+        .all(|&c| c == 0));
+
+    assert_eq!(snapshot.palette_ram[..2], [0x0F, 0x16]);
+    // `$3F10` is a mirror of `$3F00`
+    assert_eq!(snapshot.palette_ram[0x10], 0x0F);
+
+    let sprite = snapshot.oam[0];
+    assert_eq!((sprite.y, sprite.tile, sprite.x), (0x10, 0x01, 0x20));
+    assert_eq!(sprite.palette, 1);
+    assert!(sprite.behind_background && sprite.flip_horizontal && sprite.flip_vertical);
+}
+
+#[test]
+fn snapshot_does_not_change_the_state() {
+    let nes = run_snapshot_program(true);
+
+    let mut before = Vec::new();
+    nes.nes.save_state(&mut before).unwrap();
+    nes.nes.debug_ppu_snapshot();
+    let mut after = Vec::new();
+    nes.nes.save_state(&mut after).unwrap();
+    assert_eq!(before, after);
+
+    // the read buffer still has the byte at `$2005`
+    assert_eq!(nes.cpu_read_address(0x2007), 1);
+}
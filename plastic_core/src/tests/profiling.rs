@@ -0,0 +1,69 @@
+use crate::profiler::FrameProfile;
+use crate::tests::NesTester;
+use std::time::{Duration, Instant};
+
+const ROM: &str = "../test_roms/sprite_hit_tests/01.basics.nes";
+
+#[test]
+fn profiling_measures_components() {
+    let mut nes = NesTester::new(ROM).unwrap();
+    nes.nes.set_profiling(true);
+
+    for _ in 0..10 {
+        let start = Instant::now();
+        nes.clock_for_frame();
+        let elapsed = start.elapsed().as_nanos() as u64;
+
+        let profile = nes.nes.profile();
+        assert_ne!(profile.cpu_ns, 0);
+        assert_ne!(profile.ppu_ns, 0);
+        assert_ne!(profile.apu_ns, 0);
+        // the components are measured inside the frame
+        assert!(profile.total_ns() <= elapsed);
+        assert!(profile.total_ns() >= elapsed / 2);
+    }
+
+    let average = nes.nes.profile_average();
+    assert_ne!(average.total_ns(), 0);
+}
+
+#[test]
+fn profiling_disabled_is_zero() {
+    let mut nes = NesTester::new(ROM).unwrap();
+    nes.clock_for_frame();
+    assert_eq!(nes.nes.profile(), FrameProfile::default());
+
+    nes.nes.set_profiling(true);
+    nes.clock_for_frame();
+    assert_ne!(nes.nes.profile(), FrameProfile::default());
+
+    nes.nes.set_profiling(false);
+    nes.clock_for_frame();
+    assert_eq!(nes.nes.profile(), FrameProfile::default());
+    assert_eq!(nes.nes.profile_average(), FrameProfile::default());
+}
+
+fn run_frames(profiling: bool) -> Duration {
+    let mut nes = NesTester::new(ROM).unwrap();
+    nes.nes.set_profiling(profiling);
+
+    let start = Instant::now();
+    for _ in 0..300 {
+        nes.clock_for_frame();
+    }
+    start.elapsed()
+}
+
+/// Timing depends on the machine, so this is only run manually with `--ignored`
+#[test]
+#[ignore]
+fn profiling_disabled_overhead() {
+    // warm up
+    run_frames(false);
+
+    let disabled = run_frames(false);
+    let enabled = run_frames(true);
+    println!("disabled: {:?}, enabled: {:?}", disabled, enabled);
+
+    assert!(disabled < enabled);
+}
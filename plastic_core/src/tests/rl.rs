@@ -0,0 +1,148 @@
+use crate::display::COLORS;
+use crate::nes_display::{frame_hash, TV_HEIGHT, TV_WIDTH};
+use crate::rl::{BatchOutput, RlConfig, RlObservation};
+use crate::tests::input_polling::two_players_program;
+use crate::tests::NesTester;
+use crate::NESKey;
+
+fn run_actions(nes: &mut NesTester, actions: &[u8]) -> Vec<RlObservation> {
+    actions
+        .iter()
+        .map(|&action| nes.nes.rl_step(action, 3))
+        .collect()
+}
+
+#[test]
+fn rl_step_deterministic() {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+    nes.nes.set_rl_config(RlConfig {
+        ram_addresses: vec![0x00, 0x10, 0x7FF, 0x0F00],
+        done_predicate: Some(Box::new(|ram| ram[0x10] == 0xFF)),
+        skip_intermediate_frames: true,
+        indexed_pixels: false,
+    });
+
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+    let snapshot = nes.nes.snapshot().unwrap();
+
+    let actions = [0x00, 0x01, 0x81, 0x08, 0xFF, 0x00, 0x42];
+    let first = run_actions(&mut nes, &actions);
+
+    nes.nes.rl_reset_to(&snapshot).unwrap();
+    let second = run_actions(&mut nes, &actions);
+
+    assert_eq!(first.len(), actions.len());
+    assert_eq!(first, second);
+    assert!(first.iter().all(|o| o.ram.len() == 4 && !o.done));
+    // the test is running, so the screen changes
+    assert_ne!(first[0].pixels, first[first.len() - 1].pixels);
+}
+
+#[test]
+fn rl_step_indexed_pixels() {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+    nes.nes.set_rl_config(RlConfig {
+        indexed_pixels: true,
+        ..Default::default()
+    });
+
+    let observation = nes.nes.rl_step(0, 60);
+    assert_eq!(observation.pixels.len(), TV_WIDTH * TV_HEIGHT);
+
+    // the same frame as the RGB buffer, the test doesn't use emphasis
+    let rgb = nes.pixel_buffer();
+    for (&index, pixel) in observation.pixels.iter().zip(rgb.chunks_exact(3)) {
+        let color = COLORS[index as usize];
+        assert_eq!(pixel, [color.r, color.g, color.b]);
+    }
+    assert!(observation
+        .pixels
+        .iter()
+        .any(|&index| index != observation.pixels[0]));
+}
+
+fn batch_inputs() -> Vec<[u8; 2]> {
+    (0..12u8).map(|i| [i.wrapping_mul(37), 0xFF]).collect()
+}
+
+/// Run `inputs` one frame at a time, in the same layout as [`BatchOutput`]
+fn run_single_frames(nes: &mut NesTester, inputs: &[[u8; 2]]) -> BatchOutput {
+    let mut out = BatchOutput {
+        audio_offsets: vec![0],
+        ..Default::default()
+    };
+    for &[player_1, player_2] in inputs {
+        for key in NESKey::ALL {
+            nes.nes.set_controller_state(key, player_1 & key as u8 != 0);
+            nes.nes
+                .set_controller2_state(key, player_2 & key as u8 != 0);
+        }
+        nes.clock_for_frame();
+        out.pixels.extend_from_slice(nes.nes.pixel_buffer());
+        out.audio.extend(nes.nes.audio_buffer());
+        out.audio_offsets.push(out.audio.len());
+        out.frames.push(crate::rl::BatchFrame {
+            hash: frame_hash(nes.nes.pixel_buffer()),
+            rendering_enabled: nes.nes.frame_stats().rendering_was_enabled,
+        });
+    }
+    out
+}
+
+fn new_batch_nes(delay: u8) -> NesTester {
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+    nes.nes.set_output_delay_frames(delay);
+    for _ in 0..10 {
+        nes.clock_for_frame();
+    }
+    nes.nes.audio_buffer();
+    nes
+}
+
+#[test]
+fn batch_matches_single_frames() {
+    let inputs = batch_inputs();
+    for delay in [0, 2] {
+        let expected = run_single_frames(&mut new_batch_nes(delay), &inputs);
+
+        let mut nes = new_batch_nes(delay);
+        // the previous content is replaced
+        let mut out = expected.clone();
+        assert_eq!(nes.nes.run_frames_batch(&inputs, &mut out), inputs.len());
+
+        assert_eq!(out, expected, "delay {delay}");
+        assert!(!out.frame_audio(inputs.len() - 1).is_empty());
+        assert_eq!(frame_hash(out.frame_pixels(5)), out.frames[5].hash);
+    }
+}
+
+#[test]
+fn batch_player_2_input() {
+    let mut nes = NesTester::from_prg(&two_players_program());
+    let mut out = BatchOutput::default();
+
+    for (frame, [player_1, player_2]) in [[0x08, 0x42], [0x01, 0x50], [0x00, 0x00]]
+        .into_iter()
+        .enumerate()
+    {
+        nes.nes.run_frames_batch(&[[player_1, player_2]], &mut out);
+
+        assert_eq!(nes.cpu_read_address(0x10), player_1);
+        assert_eq!(nes.cpu_read_address(0x11), player_2);
+        assert_eq!(nes.nes.last_polled_input(2), (player_2, frame as u64 + 1));
+    }
+}
+
+#[test]
+fn batch_stops_on_cpu_halt() {
+    // KIL
+    let mut nes = NesTester::from_prg(&[0x02]).nes;
+    let mut out = BatchOutput::default();
+
+    assert_eq!(nes.run_frames_batch(&[[0, 0]; 5], &mut out), 1);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out.audio_offsets.len(), 2);
+    assert!(nes.is_cpu_halted());
+}
@@ -0,0 +1,114 @@
+use crate::ppu2c02::PpuBackend;
+use crate::tests::{rom_from_prg_chr, NesTester};
+use crate::{NESBuilder, NES};
+
+const INES_HEADER_SIZE: usize = 16;
+
+#[test]
+fn rom_data_matches_file() {
+    let path = "../test_roms/instr_test-v5/rom_singles/01-basics.nes";
+    let file = std::fs::read(path).unwrap();
+    let nes = NES::new(path).unwrap();
+
+    let prg_size = file[4] as usize * 0x4000;
+    let chr_size = file[5] as usize * 0x2000;
+    let prg = &file[INES_HEADER_SIZE..INES_HEADER_SIZE + prg_size];
+    let chr = &file[INES_HEADER_SIZE + prg_size..INES_HEADER_SIZE + prg_size + chr_size];
+
+    assert_eq!(&*nes.prg_rom(), prg);
+    assert_eq!(nes.chr_rom().as_deref(), Some(chr));
+    assert!(nes.chr_ram().is_none());
+}
+
+#[test]
+fn chr_ram_rom_data() {
+    let nes = NES::new("../test_roms/holy-mapperel-bin-0.02/testroms/M0_P32K_CR8K_V.nes").unwrap();
+
+    assert_eq!(nes.prg_rom().len(), 0x8000);
+    assert!(nes.chr_rom().is_none());
+    assert_eq!(nes.chr_ram().map(|chr_ram| chr_ram.len()), Some(0x2000));
+}
+
+#[test]
+fn patched_prg_rom() {
+    let path = "../test_roms/cartridge_tests/test_creation.nes";
+    let patch_path = std::env::temp_dir().join("plastic_test_prg_rom_patch.ips");
+
+    // IPS patch writing `0x12 0x34` to the start of PRG
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0, 0, INES_HEADER_SIZE as u8, 0, 2, 0x12, 0x34]);
+    patch.extend_from_slice(b"EOF");
+    std::fs::write(&patch_path, patch).unwrap();
+
+    let nes = NES::new_with_patch(path, &patch_path);
+    std::fs::remove_file(&patch_path).unwrap();
+    let nes = nes.unwrap();
+
+    let file = std::fs::read(path).unwrap();
+    let prg_rom = nes.prg_rom();
+    assert_eq!(prg_rom[..2], [0x12, 0x34]);
+    assert_eq!(
+        prg_rom[2..],
+        file[INES_HEADER_SIZE + 2..INES_HEADER_SIZE + prg_rom.len()]
+    );
+}
+
+#[test]
+fn builder_patch() {
+    let path = "../test_roms/cartridge_tests/test_creation.nes";
+    let file = std::fs::read(path).unwrap();
+
+    // IPS patch writing `0x56` to the start of PRG
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0, 0, INES_HEADER_SIZE as u8, 0, 1, 0x56]);
+    patch.extend_from_slice(b"EOF");
+
+    let from_file = NESBuilder::from_file(path)
+        .patch_bytes(&patch)
+        .build()
+        .unwrap();
+    let from_bytes = NESBuilder::from_bytes(&file)
+        .patch_bytes(&patch)
+        .build()
+        .unwrap();
+    let unpatched = NESBuilder::from_bytes(&file).build().unwrap();
+
+    assert_eq!(from_file.prg_rom()[0], 0x56);
+    assert_eq!(*from_file.prg_rom(), *from_bytes.prg_rom());
+    assert_eq!(
+        unpatched.prg_rom()[..],
+        file[INES_HEADER_SIZE..INES_HEADER_SIZE + unpatched.prg_rom().len()]
+    );
+}
+
+#[test]
+fn builder_ppu_backend() {
+    #[rustfmt::skip]
+    let prg = [
+        // LDA #0; STA $2003; STA $2004; STA $2003
+        0xA9, 0x00, 0x8D, 0x03, 0x20, 0x8D, 0x04, 0x20, 0x8D, 0x03, 0x20,
+        // LDA #$08; STA $2001; LDY #4
+        0xA9, 0x08, 0x8D, 0x01, 0x20, 0xA0, 0x04,
+        // outer: LDX #0; loop: LDA $2004; CMP #$FF; BNE next; STA $10
+        0xA2, 0x00, 0xAD, 0x04, 0x20, 0xC9, 0xFF, 0xD0, 0x02, 0x85, 0x10,
+        // next: DEX; BNE loop; DEY; BNE outer; INC $11; JMP self
+        0xCA, 0xD0, 0xF4, 0x88, 0xD0, 0xEF, 0xE6, 0x11, 0x4C, 0x25, 0x80,
+    ];
+    let rom = rom_from_prg_chr(&prg, &[], 0);
+
+    // the dot accurate backend returns `$FF` from `$2004` while clearing the secondary
+    // OAM during rendering, the scanline backend returns the OAM, both in the first frame
+    for (backend, expected) in [(PpuBackend::DotAccurate, 0xFF), (PpuBackend::Scanline, 0)] {
+        let mut nes = NesTester {
+            nes: NESBuilder::from_bytes(&rom)
+                .ppu_backend(backend)
+                .build()
+                .unwrap(),
+        };
+        nes.clock_for_frame();
+
+        assert_eq!(nes.cpu_read_address(0x11), 1, "{:?}", backend);
+        assert_eq!(nes.cpu_read_address(0x10), expected, "{:?}", backend);
+        assert_eq!(nes.nes.ppu_backend(), backend);
+    }
+}
@@ -1,6 +1,8 @@
 use std::io::Cursor;
 
-use crate::tests::NesTester;
+use crate::common::save_state::Savable;
+use crate::tests::{rom_from_prg_chr, NesTester};
+use crate::{EmulatorConfig, PpuBackend, Region, SaveError};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TestState {
@@ -48,3 +50,341 @@ fn save_load_test() {
 
     assert_eq!(get_test_state(&nes), TestState::Passed);
 }
+
+fn pal_state(file_path: &str) -> Vec<u8> {
+    let mut nes = NesTester::new(file_path).unwrap();
+    nes.nes.set_region(Region::Pal);
+    nes.nes.set_cpu_ppu_alignment(2);
+    nes.nes.reset();
+    nes.clock_for_frame();
+
+    let mut buffer = Vec::new();
+    nes.nes.save_state(&mut buffer).unwrap();
+    buffer
+}
+
+#[test]
+fn state_config_applied() {
+    let file_path = "../test_roms/instr_test-v5/all_instrs.nes";
+    let buffer = pal_state(file_path);
+
+    let mut nes = NesTester::new(file_path).unwrap();
+    assert_eq!(nes.nes.config(), EmulatorConfig::default());
+    nes.nes.load_state(buffer.as_slice()).unwrap();
+
+    assert_eq!(
+        nes.nes.config(),
+        EmulatorConfig {
+            region: Region::Pal,
+            cpu_ppu_alignment: 2,
+        }
+    );
+    assert_eq!(nes.nes.region().frame_rate(), crate::FRAME_RATE_PAL);
+}
+
+#[test]
+fn state_config_mismatch_strict() {
+    let file_path = "../test_roms/instr_test-v5/all_instrs.nes";
+    let buffer = pal_state(file_path);
+
+    let mut nes = NesTester::new(file_path).unwrap();
+    nes.nes.set_strict_state_config(true);
+
+    match nes.nes.load_state(buffer.as_slice()) {
+        Err(SaveError::ConfigMismatch { saved, current }) => {
+            assert_eq!(saved.region, Region::Pal);
+            assert_eq!(saved.cpu_ppu_alignment, 2);
+            assert_eq!(current, EmulatorConfig::default());
+        }
+        other => panic!("expected config mismatch, got {:?}", other),
+    }
+    // nothing was changed
+    assert_eq!(nes.nes.config(), EmulatorConfig::default());
+}
+
+/// A component that can be swapped at runtime, which requires `Savable` to be object safe
+trait Component: Savable {
+    fn name(&self) -> &'static str;
+}
+
+impl Component for EmulatorConfig {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+}
+
+#[test]
+fn savable_trait_object() {
+    let saved: Box<dyn Component> = Box::new(EmulatorConfig {
+        region: Region::Pal,
+        cpu_ppu_alignment: 2,
+    });
+
+    let mut data = Vec::new();
+    saved.save(&mut data).unwrap();
+
+    let mut loaded = EmulatorConfig::default();
+    {
+        let component: &mut dyn Component = &mut loaded;
+        assert_eq!(component.name(), "config");
+        component.load(&mut data.as_slice()).unwrap();
+    }
+
+    assert_eq!(loaded.region, Region::Pal);
+    assert_eq!(loaded.cpu_ppu_alignment, 2);
+}
+
+/// Try to load `state` and make sure it fails without changing anything, by
+/// comparing the next frame with `reference` which didn't load anything
+fn assert_failed_load_untouched(nes: &mut NesTester, reference: &mut NesTester, state: &[u8]) {
+    assert!(nes.nes.load_state(state).is_err());
+    assert_eq!(nes.nes.config(), reference.nes.config());
+
+    nes.clock_for_frame();
+    reference.clock_for_frame();
+    assert_eq!(nes.nes.cpu_state(), reference.nes.cpu_state());
+    assert!(nes.pixel_buffer() == reference.pixel_buffer());
+    assert!(nes.nes.audio_buffer() == reference.nes.audio_buffer());
+}
+
+#[test]
+fn failed_load_keeps_state() {
+    let file_path = "../test_roms/instr_test-v5/all_instrs.nes";
+    // a state with a different config, so applying the config is rolled back as well
+    let mut saved = NesTester::new(file_path).unwrap();
+    saved.nes.set_region(Region::Pal);
+    saved.clock_for_frame();
+    let mut state = Vec::new();
+    saved.nes.save_state(&mut state).unwrap();
+
+    let mut nes = NesTester::new(file_path).unwrap();
+    let mut reference = NesTester::new(file_path).unwrap();
+    for _ in 0..10 {
+        nes.clock_for_frame();
+        reference.clock_for_frame();
+    }
+
+    let mut section_start = 0;
+    for section_length in saved.nes.state_section_lengths() {
+        assert!(section_length > 0);
+        // cut the state in the middle of the section
+        let end = section_start + section_length / 2;
+        assert_failed_load_untouched(&mut nes, &mut reference, &state[..end]);
+
+        section_start += section_length;
+    }
+    assert_eq!(section_start, state.len());
+
+    // all the sections are loaded before finding the extra data
+    let mut extra = state.clone();
+    extra.push(0);
+    assert_failed_load_untouched(&mut nes, &mut reference, &extra);
+
+    // and the state itself is fine
+    nes.nes.load_state(state.as_slice()).unwrap();
+    assert_eq!(nes.nes.region(), Region::Pal);
+}
+
+#[test]
+fn save_state_single_write() {
+    /// A writer that fails every write after the first one
+    struct OneWrite {
+        data: Vec<u8>,
+        writes: usize,
+    }
+
+    impl std::io::Write for OneWrite {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            if self.writes > 1 {
+                return Err(std::io::ErrorKind::Other.into());
+            }
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut nes = NesTester::new("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+    nes.clock_for_frame();
+
+    let mut writer = OneWrite {
+        data: Vec::new(),
+        writes: 0,
+    };
+    nes.nes.save_state(&mut writer).unwrap();
+
+    let mut expected = Vec::new();
+    nes.nes.save_state(&mut expected).unwrap();
+    assert_eq!(writer.data, expected);
+}
+
+/// A state of `all_instrs.nes` after a frame, and 2 emulators running the same ROM
+/// for 10 frames, to load into one and compare with the other
+fn state_and_running_pair() -> (Vec<u8>, NesTester, NesTester) {
+    let file_path = "../test_roms/instr_test-v5/all_instrs.nes";
+    let mut saved = NesTester::new(file_path).unwrap();
+    saved.clock_for_frame();
+    let mut state = Vec::new();
+    saved.nes.save_state(&mut state).unwrap();
+
+    let mut nes = NesTester::new(file_path).unwrap();
+    let mut reference = NesTester::new(file_path).unwrap();
+    for _ in 0..10 {
+        nes.clock_for_frame();
+        reference.clock_for_frame();
+    }
+
+    (state, nes, reference)
+}
+
+#[test]
+fn state_header_round_trip() {
+    let (state, mut nes, _) = state_and_running_pair();
+    assert_eq!(&state[..4], b"PLST");
+    assert_eq!(
+        state[4..6],
+        crate::common::save_state::STATE_FORMAT_VERSION.to_le_bytes()
+    );
+    // mapper 1
+    assert_eq!(state[6..8], [1, 0]);
+    assert_eq!(state[8..12], nes.nes.rom_crc32().to_le_bytes());
+
+    nes.nes.load_state(state.as_slice()).unwrap();
+    let mut loaded = Vec::new();
+    nes.nes.save_state(&mut loaded).unwrap();
+    assert_eq!(loaded, state);
+}
+
+#[test]
+fn state_header_truncated() {
+    let (state, mut nes, mut reference) = state_and_running_pair();
+
+    for end in [0, 3, 5, 11] {
+        assert!(matches!(
+            nes.nes.load_state(&state[..end]),
+            Err(SaveError::IoError(_))
+        ));
+    }
+    assert_failed_load_untouched(&mut nes, &mut reference, &state[..11]);
+}
+
+#[test]
+fn state_header_wrong_magic() {
+    let (mut state, mut nes, mut reference) = state_and_running_pair();
+    state[0] = b'X';
+
+    assert!(matches!(
+        nes.nes.load_state(state.as_slice()),
+        Err(SaveError::NotAState)
+    ));
+    assert_failed_load_untouched(&mut nes, &mut reference, &state);
+}
+
+#[test]
+fn state_header_future_version() {
+    let (mut state, mut nes, mut reference) = state_and_running_pair();
+    let version = crate::common::save_state::STATE_FORMAT_VERSION;
+    state[4..6].copy_from_slice(&(version + 1).to_le_bytes());
+
+    assert!(matches!(
+        nes.nes.load_state(state.as_slice()),
+        Err(SaveError::VersionMismatch { found, expected })
+            if found == version + 1 && expected == version
+    ));
+    assert_failed_load_untouched(&mut nes, &mut reference, &state);
+}
+
+#[test]
+fn state_of_another_rom_rejected() {
+    // same mapper and sizes, only the code differs
+    let mut saved = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    saved.clock_for_frame();
+    let mut state = Vec::new();
+    saved.nes.save_state(&mut state).unwrap();
+
+    let program = [0xEA, 0x4C, 0x00, 0x80];
+    let mut nes = NesTester::from_prg(&program);
+    let mut reference = NesTester::from_prg(&program);
+    nes.clock_for_frame();
+    reference.clock_for_frame();
+
+    assert!(matches!(
+        nes.nes.load_state(state.as_slice()),
+        Err(SaveError::RomMismatch)
+    ));
+    assert_failed_load_untouched(&mut nes, &mut reference, &state);
+
+    // the mapper is checked as well
+    let (state, mut nes, mut reference) = state_and_running_pair();
+    let mut other = state.clone();
+    other[6] = 0;
+    assert!(matches!(
+        nes.nes.load_state(other.as_slice()),
+        Err(SaveError::RomMismatch)
+    ));
+    assert_failed_load_untouched(&mut nes, &mut reference, &other);
+    nes.nes.load_state(state.as_slice()).unwrap();
+}
+
+/// Sprite 0 at `(200, 100)` over an opaque background, the CPU waits for the
+/// sprite 0 hit flag every frame, and stores the number of iterations it waited in `$00`
+const SPRITE_0_HIT_LOOP: [u8; 46] = [
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x03, 0x20, // STA $2003
+    0xA9, 100, // LDA #100
+    0x8D, 0x04, 0x20, // STA $2004 (Y)
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x04, 0x20, // STA $2004 (tile)
+    0x8D, 0x04, 0x20, // STA $2004 (attributes)
+    0xA9, 200, // LDA #200
+    0x8D, 0x04, 0x20, // STA $2004 (X)
+    0xA9, 0x1E, // LDA #$1E
+    0x8D, 0x01, 0x20, // STA $2001
+    0x2C, 0x02, 0x20, // BIT $2002
+    0x70, 0xFB, // BVS $801C
+    0xA2, 0x00, // LDX #$00
+    0xE8, // INX
+    0x2C, 0x02, 0x20, // BIT $2002
+    0x50, 0xFA, // BVC $8023
+    0x86, 0x00, // STX $00
+    0x4C, 0x1C, 0x80, // JMP $801C
+];
+
+/// States saved with the scanline backend in the middle of a scanline, after the
+/// line was rendered but before the sprite 0 hit dot, continue the same
+#[test]
+fn scanline_backend_state_mid_scanline() {
+    let rom = rom_from_prg_chr(&SPRITE_0_HIT_LOOP, &[0xFF; 16], 0);
+    let mut nes = NesTester::from_rom(&rom);
+    nes.nes.set_ppu_backend(PpuBackend::Scanline);
+    nes.nes.reset();
+    let mut loaded = NesTester::from_rom(&rom);
+
+    for _ in 0..3 {
+        nes.clock_for_frame();
+    }
+    assert_ne!(nes.cpu_read_address(0x00), 0);
+
+    // the save points move by a few dots every frame, to cover the whole frame
+    for _ in 0..3000 {
+        for _ in 0..7 {
+            nes.clock();
+        }
+
+        let mut state = Vec::new();
+        nes.nes.save_state(&mut state).unwrap();
+        loaded.nes.load_state(state.as_slice()).unwrap();
+        assert_eq!(loaded.nes.ppu_backend(), PpuBackend::Scanline);
+
+        for _ in 0..400 {
+            nes.clock();
+            loaded.clock();
+        }
+        assert_eq!(loaded.nes.cpu_state(), nes.nes.cpu_state());
+        assert_eq!(loaded.cpu_read_address(0x00), nes.cpu_read_address(0x00));
+    }
+}
@@ -0,0 +1,298 @@
+//! Runs all the test ROM suites in `test_roms` and reports a scoreboard.
+//!
+//! This is ignored by default as it takes a long time, run it with:
+//! ```text
+//! cargo test --release -p plastic_core scoreboard -- --ignored --nocapture
+//! ```
+//!
+//! The results are compared against [`EXPECTED_FILE`], any ROM that is expected to pass
+//! and doesn't is a regression and fails the test. ROMs that are not passing yet are
+//! only reported. The full results are written to [`OUTPUT_FILE`] (or the path in the
+//! `PLASTIC_SCOREBOARD_OUTPUT` environment variable), and setting
+//! `PLASTIC_SCOREBOARD_UPDATE=1` overwrites [`EXPECTED_FILE`] with the new results.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::NesTester;
+
+const TEST_ROMS_DIR: &str = "../test_roms";
+const EXPECTED_FILE: &str = "src/tests/scoreboard_expected.toml";
+const OUTPUT_FILE: &str = "../target/scoreboard.toml";
+
+/// stop the ROM if it didn't report a result after this many frames (1 minute)
+const MAX_FRAMES: u32 = 60 * 60;
+/// frames to wait before resetting when the ROM requests it, blargg asks for at least 100ms
+const RESET_DELAY_FRAMES: u32 = 10;
+
+const STATUS_ADDRESS: u16 = 0x6000;
+const STATUS_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const STATUS_TEXT_ADDRESS: u16 = 0x6004;
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+#[derive(Debug, Clone, Copy)]
+enum Protocol {
+    /// The status is written to `$6000`, `0x80` while running, `0x81` if the ROM needs
+    /// to be reset, otherwise it's the result code (`0` is pass), and the message
+    /// is a null terminated string at `$6004`.
+    /// `$6001-$6003` contain a signature to know the data is valid.
+    ///
+    /// NROM ROMs don't have RAM at `$6000`, so the result printed on the screen is used instead.
+    Status6000,
+    /// Older ROMs only print the result on the screen, either `PASSED`/`FAILED` or
+    /// the result code as `$XX` (`$01` is pass).
+    ScreenText,
+}
+
+const SUITES: &[(&str, Protocol)] = &[
+    ("blargg_apu_2005.07.30", Protocol::ScreenText),
+    ("blargg_ppu_tests", Protocol::ScreenText),
+    ("instr_test-v5", Protocol::Status6000),
+    ("instr_timing", Protocol::Status6000),
+    ("mmc3_test_2", Protocol::Status6000),
+    ("ppu_sprite_overflow", Protocol::Status6000),
+    ("ppu_vbl_nmi", Protocol::Status6000),
+    ("sprite_hit_tests", Protocol::ScreenText),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TestResult {
+    Pass,
+    Fail(String),
+    Timeout,
+}
+
+impl TestResult {
+    fn name(&self) -> &'static str {
+        match self {
+            TestResult::Pass => "pass",
+            TestResult::Fail(_) => "fail",
+            TestResult::Timeout => "timeout",
+        }
+    }
+}
+
+/// The ROMs of a suite, the single ROMs are used if present instead of the combined one
+fn suite_roms(suite: &str) -> Vec<String> {
+    let suite_dir = Path::new(TEST_ROMS_DIR).join(suite);
+    let singles_dir = suite_dir.join("rom_singles");
+    let dir = if singles_dir.is_dir() {
+        singles_dir
+    } else {
+        suite_dir
+    };
+
+    let mut roms = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "nes"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    roms.sort();
+    roms
+}
+
+/// Read a null terminated string from CPU memory
+fn read_memory_text(nes: &NesTester, address: u16) -> String {
+    (address..0x8000)
+        .map(|address| nes.cpu_read_address(address))
+        .take_while(|&c| c != 0)
+        .map(|c| c as char)
+        .collect()
+}
+
+/// Read the text shown in the first nametable.
+///
+/// The blargg ROMs font places each character at the tile index of its ASCII code,
+/// so a tile is recognized by its index, and anything else is treated as a space.
+fn read_screen_text(nes: &NesTester) -> String {
+    let mut text = String::new();
+
+    for y in 0..30 {
+        for x in 0..32 {
+            let tile = nes.ppu_read_address(0x2000 + y * 32 + x);
+            text.push(if (0x20..0x7F).contains(&tile) {
+                tile as char
+            } else {
+                ' '
+            });
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+fn check_status_6000(
+    nes: &mut NesTester,
+    reset_frame: &mut Option<u32>,
+    frame: u32,
+) -> Option<TestResult> {
+    let signature = [1, 2, 3].map(|i| nes.cpu_read_address(STATUS_ADDRESS + i));
+    if signature != STATUS_SIGNATURE {
+        return check_screen_text(nes, false);
+    }
+
+    match nes.cpu_read_address(STATUS_ADDRESS) {
+        STATUS_RUNNING => None,
+        STATUS_NEEDS_RESET => {
+            match *reset_frame {
+                Some(reset_at) if frame >= reset_at => {
+                    nes.nes.reset();
+                    *reset_frame = None;
+                }
+                Some(_) => {}
+                None => *reset_frame = Some(frame + RESET_DELAY_FRAMES),
+            }
+            None
+        }
+        0 => Some(TestResult::Pass),
+        code => Some(TestResult::Fail(format!(
+            "code {}: {}",
+            code,
+            read_memory_text(nes, STATUS_TEXT_ADDRESS).trim()
+        ))),
+    }
+}
+
+/// `with_code` also checks for the result code printed as `$XX`, which is only used in old ROMs
+fn check_screen_text(nes: &NesTester, with_code: bool) -> Option<TestResult> {
+    let text = read_screen_text(nes);
+
+    if text.contains("PASSED") || text.contains("Passed") {
+        return Some(TestResult::Pass);
+    }
+    if text.contains("FAILED") || text.contains("Failed") {
+        return Some(TestResult::Fail(
+            text.split_whitespace().collect::<Vec<_>>().join(" "),
+        ));
+    }
+
+    if !with_code {
+        return None;
+    }
+
+    let code = text.find('$').and_then(|index| {
+        let digits = text.get(index + 1..index + 3)?;
+        u8::from_str_radix(digits, 16).ok()
+    })?;
+
+    if code == 1 {
+        Some(TestResult::Pass)
+    } else {
+        Some(TestResult::Fail(format!("code {}", code)))
+    }
+}
+
+fn run_rom(path: &str, protocol: Protocol) -> TestResult {
+    let mut nes = match NesTester::new(path) {
+        Ok(nes) => nes,
+        Err(err) => return TestResult::Fail(format!("failed to load: {}", err)),
+    };
+    let mut reset_frame = None;
+
+    for frame in 0..MAX_FRAMES {
+        nes.clock_for_frame();
+
+        let result = match protocol {
+            Protocol::Status6000 => check_status_6000(&mut nes, &mut reset_frame, frame),
+            Protocol::ScreenText => check_screen_text(&nes, true),
+        };
+
+        if let Some(result) = result {
+            return result;
+        }
+    }
+
+    TestResult::Timeout
+}
+
+/// Parse the `"name" = "result"` lines of a scoreboard file, other lines are ignored
+fn parse_scoreboard(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (name, result) = line.split_once(" = ")?;
+            let name = name.trim().strip_prefix('"')?.strip_suffix('"')?;
+            // skip comments after the value
+            let result = result.split('#').next()?.trim();
+            let result = result.strip_prefix('"')?.strip_suffix('"')?;
+            Some((name.to_owned(), result.to_owned()))
+        })
+        .collect()
+}
+
+fn format_scoreboard(results: &BTreeMap<String, TestResult>, with_messages: bool) -> String {
+    let mut content =
+        String::from("# generated by the `scoreboard` test, see `src/tests/scoreboard.rs`\n");
+
+    let mut current_suite = "";
+    for (name, result) in results {
+        let suite = name.split('/').next().unwrap();
+        if suite != current_suite {
+            current_suite = suite;
+            writeln!(content, "\n# {}", suite).unwrap();
+        }
+
+        write!(content, "\"{}\" = \"{}\"", name, result.name()).unwrap();
+        if let (true, TestResult::Fail(message)) = (with_messages, result) {
+            write!(content, " # {}", message.replace('\n', " ")).unwrap();
+        }
+        content.push('\n');
+    }
+
+    content
+}
+
+#[test]
+#[ignore = "runs all test ROMs, takes a long time"]
+fn scoreboard() {
+    let mut results = BTreeMap::new();
+
+    for &(suite, protocol) in SUITES {
+        for path in suite_roms(suite) {
+            let rom_name = Path::new(&path).file_stem().unwrap().to_string_lossy();
+            let name = format!("{}/{}", suite, rom_name);
+
+            let result = run_rom(&path, protocol);
+            println!("{:<50} {:?}", name, result);
+            results.insert(name, result);
+        }
+    }
+
+    let output_path =
+        std::env::var("PLASTIC_SCOREBOARD_OUTPUT").unwrap_or_else(|_| OUTPUT_FILE.to_owned());
+    fs::write(&output_path, format_scoreboard(&results, true)).unwrap();
+
+    if std::env::var("PLASTIC_SCOREBOARD_UPDATE").is_ok_and(|value| value == "1") {
+        fs::write(EXPECTED_FILE, format_scoreboard(&results, false)).unwrap();
+        return;
+    }
+
+    let expected = parse_scoreboard(&fs::read_to_string(EXPECTED_FILE).unwrap());
+
+    let mut regressions = Vec::new();
+    for (name, result) in &results {
+        match expected.get(name).map(String::as_str) {
+            Some("pass") if *result != TestResult::Pass => regressions.push(name.as_str()),
+            Some("pass") => {}
+            _ if *result == TestResult::Pass => {
+                println!("{} is passing now, update the expected results", name)
+            }
+            _ => {}
+        }
+    }
+
+    let passed = results.values().filter(|r| **r == TestResult::Pass).count();
+    println!(
+        "passed {}/{}, results written to {}",
+        passed,
+        results.len(),
+        output_path
+    );
+
+    assert!(regressions.is_empty(), "regressions: {:?}", regressions);
+}
@@ -0,0 +1,88 @@
+use super::rom_from_prg_chr;
+use crate::cpu6502::CPUBusTrait;
+use crate::{RomData, NES};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+const INSTANCES: usize = 8;
+
+/// Polls the controller into `$10` in a loop, and copies the result to `$11`
+const POLL_INPUT: &[u8] = &[
+    0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #1; STA $4016
+    0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #0; STA $4016
+    0xA2, 0x08, //                   LDX #8
+    0xAD, 0x16, 0x40, //             LDA $4016
+    0x4A, //                         LSR A
+    0x66, 0x10, //                   ROR $10
+    0xCA, //                         DEX
+    0xD0, 0xF7, //                   BNE -9
+    0xA5, 0x10, 0x85, 0x11, //       LDA $10; STA $11
+    0x4C, 0x00, 0x80, //             JMP $8000
+];
+
+#[test]
+fn instances_share_rom_on_threads() {
+    let rom = Arc::new(RomData::parse(&rom_from_prg_chr(POLL_INPUT, &[], 0)).unwrap());
+    let barrier = Arc::new(Barrier::new(INSTANCES + 1));
+
+    let handles = (0..INSTANCES)
+        .map(|i| {
+            let rom = rom.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let mut nes = NES::from_shared_rom(rom.clone());
+                let shares_prg = nes.prg_rom().as_ptr() == rom.prg_rom().as_ptr();
+                drop(rom);
+
+                // all the instances are alive
+                barrier.wait();
+                barrier.wait();
+
+                // A, B and Select differ, without opposite directions
+                let input = 0x80 | i as u8;
+                for key in crate::NESKey::ALL {
+                    nes.set_controller_state(key, input & key as u8 != 0);
+                }
+                for _ in 0..5 {
+                    nes.clock_for_frame();
+                }
+
+                (shares_prg, input, nes.peek_memory(0x11))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    barrier.wait();
+    // the data is not copied for each instance
+    assert_eq!(Arc::strong_count(&rom.prg), INSTANCES + 1);
+    assert_eq!(Arc::strong_count(rom.chr.as_ref().unwrap()), INSTANCES + 1);
+    barrier.wait();
+
+    for handle in handles {
+        let (shares_prg, input, polled) = handle.join().unwrap();
+        assert!(shares_prg);
+        assert_eq!(polled, Some(input));
+    }
+    assert_eq!(Arc::strong_count(&rom.prg), 1);
+}
+
+#[test]
+fn chr_ram_is_per_instance() {
+    let rom =
+        std::fs::read("../test_roms/holy-mapperel-bin-0.02/testroms/M0_P32K_CR8K_V.nes").unwrap();
+    let rom = Arc::new(RomData::parse(&rom).unwrap());
+    assert!(rom.chr_rom().is_none());
+
+    let mut a = NES::from_shared_rom(rom.clone());
+    let b = NES::from_shared_rom(rom.clone());
+
+    // write the first byte of the pattern tables
+    let bus = a.cpu_bus_mut();
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0xAB);
+
+    assert_eq!(a.chr_ram().unwrap()[0], 0xAB);
+    assert_eq!(b.chr_ram().unwrap()[0], 0x00);
+    assert_eq!(*a.prg_rom(), *rom.prg_rom());
+}
@@ -0,0 +1,79 @@
+use super::rom_from_prg_chr;
+use crate::{SramActivity, NES};
+
+/// Enable PRG RAM on MMC1, write 16 bytes to `$6000` and then loop forever
+const SAVE_PROGRAM: &[u8] = &[
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x8D, 0x00, 0xE0, // STA $E000
+    0x8D, 0x00, 0xE0, // STA $E000 (PRG RAM enabled)
+    0xA2, 0x00, // LDX #$00
+    0x8E, 0x00, 0x60, // STX $6000
+    0xE8, // INX
+    0xE0, 0x10, // CPX #$10
+    0xD0, 0xF8, // BNE $8013
+    0x4C, 0x1B, 0x80, // JMP $801B
+];
+
+/// `flags_6` of mapper 1
+const MMC1: u8 = 0x10;
+/// `flags_6` bit 1, battery backed PRG RAM
+const BATTERY: u8 = 0b10;
+
+#[test]
+fn sram_activity_updates_and_quiesces() {
+    let mut nes =
+        NES::new_from_bytes(&rom_from_prg_chr(SAVE_PROGRAM, &[], MMC1 | BATTERY)).unwrap();
+    assert_eq!(nes.sram_write_activity(), SramActivity::default());
+
+    nes.clock_for_frame();
+    assert_eq!(
+        nes.sram_write_activity(),
+        SramActivity {
+            writes_last_frame: 16,
+            last_write_frame: 1,
+            dirty: true,
+        }
+    );
+
+    // no more writes
+    nes.clock_for_frame();
+    nes.clock_for_frame();
+    assert_eq!(
+        nes.sram_write_activity(),
+        SramActivity {
+            writes_last_frame: 0,
+            last_write_frame: 1,
+            dirty: true,
+        }
+    );
+
+    nes.clear_sram_dirty();
+    assert!(!nes.sram_write_activity().dirty);
+}
+
+#[test]
+fn sram_activity_without_battery() {
+    let mut nes = NES::new_from_bytes(&rom_from_prg_chr(SAVE_PROGRAM, &[], MMC1)).unwrap();
+    nes.clock_for_frame();
+
+    // writes to PRG RAM without a battery are not saves
+    assert_eq!(nes.cpu_read_u16(0x6000) & 0xFF, 0x0F);
+    assert_eq!(nes.sram_write_activity(), SramActivity::default());
+}
+
+#[test]
+fn sram_activity_not_in_state() {
+    let mut nes =
+        NES::new_from_bytes(&rom_from_prg_chr(SAVE_PROGRAM, &[], MMC1 | BATTERY)).unwrap();
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+
+    nes.clock_for_frame();
+    let activity = nes.sram_write_activity();
+    nes.load_state(state.as_slice()).unwrap();
+
+    assert_eq!(nes.sram_write_activity(), activity);
+}
@@ -0,0 +1,157 @@
+use super::rom_from_prg_chr;
+use crate::common::save_state::SaveError;
+use crate::diagnostics::Diagnostic;
+use crate::NES;
+use std::path::{Path, PathBuf};
+
+/// `flags_6` of mapper 1
+const MMC1: u8 = 0x10;
+/// `flags_6` bit 1, battery backed PRG RAM
+const BATTERY: u8 = 0b10;
+/// MMC1 cartridges have 8kb of save RAM
+const SRAM_SIZE: usize = 0x2000;
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
+/// A clean directory in the temp directory for the files of a test
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn exported_sram(nes: &NES, dir: &Path) -> Vec<u8> {
+    let path = dir.join("export.sav");
+    nes.export_sram_file(&path).unwrap();
+    std::fs::read(path).unwrap()
+}
+
+/// Import `data` into a new MMC1 cartridge with a battery, and return the save RAM
+/// after the import and the diagnostics
+fn import(dir_name: &str, data: &[u8]) -> (Vec<u8>, Vec<Diagnostic>) {
+    let dir = test_dir(dir_name);
+    let path = dir.join("import.srm");
+    std::fs::write(&path, data).unwrap();
+
+    let mut nes = NES::new_from_bytes(&rom_from_prg_chr(&[], &[], MMC1 | BATTERY)).unwrap();
+    nes.import_sram_file(&path).unwrap();
+    assert!(nes.sram_write_activity().dirty);
+
+    let sram = exported_sram(&nes, &dir);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    (sram, nes.take_diagnostics())
+}
+
+#[test]
+fn import_exact_size() {
+    let data = pattern(SRAM_SIZE);
+    assert_eq!(import("plastic_test_sram_exact", &data), (data, vec![]));
+}
+
+#[test]
+fn import_undersized_is_padded() {
+    let data = pattern(0x800);
+    let (sram, diagnostics) = import("plastic_test_sram_undersized", &data);
+
+    assert_eq!(sram.len(), SRAM_SIZE);
+    assert_eq!(sram[..0x800], data);
+    assert!(sram[0x800..].iter().all(|&b| b == 0));
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn import_oversized_is_truncated() {
+    // 32kb of save RAM padding from other emulators
+    let data = pattern(0x8000);
+    let (sram, diagnostics) = import("plastic_test_sram_oversized", &data);
+
+    assert_eq!(sram, data[..SRAM_SIZE]);
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::SramImportTruncated {
+            dropped_bytes: 0x6000
+        }]
+    );
+}
+
+#[test]
+fn oversized_sram_file_next_to_rom() {
+    let dir = test_dir("plastic_test_sram_oversized_load");
+    let rom_path = dir.join("game.nes");
+    std::fs::write(&rom_path, rom_from_prg_chr(&[], &[], MMC1 | BATTERY)).unwrap();
+
+    let data = pattern(0x8000);
+    std::fs::write(dir.join("game.nes.sav"), &data).unwrap();
+    {
+        let mut nes = NES::new(&rom_path).unwrap();
+        assert_eq!(exported_sram(&nes, &dir), data[..SRAM_SIZE]);
+        assert_eq!(
+            nes.take_diagnostics(),
+            vec![Diagnostic::SramImportTruncated {
+                dropped_bytes: 0x6000
+            }]
+        );
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn import_skips_header() {
+    let data = pattern(SRAM_SIZE);
+    let mut file = vec![0xAA; 0x200];
+    file.extend_from_slice(&data);
+
+    assert_eq!(import("plastic_test_sram_header", &file), (data, vec![]));
+}
+
+#[test]
+fn import_without_battery() {
+    let dir = test_dir("plastic_test_sram_no_battery");
+    let path = dir.join("import.sav");
+    std::fs::write(&path, pattern(SRAM_SIZE)).unwrap();
+
+    let mut nes = NES::new_from_bytes(&rom_from_prg_chr(&[], &[], MMC1)).unwrap();
+    assert!(matches!(
+        nes.import_sram_file(&path),
+        Err(SaveError::NoBatteryBackedRam)
+    ));
+    assert!(matches!(
+        nes.export_sram_file(dir.join("export.sav")),
+        Err(SaveError::NoBatteryBackedRam)
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn srm_fallback_lookup_order() {
+    let dir = test_dir("plastic_test_sram_fallback");
+    let rom_path = dir.join("game.nes");
+    std::fs::write(&rom_path, rom_from_prg_chr(&[], &[], MMC1 | BATTERY)).unwrap();
+
+    // only `.srm`, used as a fallback
+    std::fs::write(dir.join("game.srm"), vec![1; SRAM_SIZE]).unwrap();
+    {
+        let nes = NES::new(&rom_path).unwrap();
+        assert_eq!(exported_sram(&nes, &dir), vec![1; SRAM_SIZE]);
+    }
+    // the save RAM is always saved to `.nes.sav`
+    assert_eq!(
+        std::fs::read(dir.join("game.nes.sav")).unwrap(),
+        vec![1; SRAM_SIZE]
+    );
+
+    // `.nes.sav` has priority
+    std::fs::write(dir.join("game.nes.sav"), vec![2; SRAM_SIZE]).unwrap();
+    {
+        let nes = NES::new(&rom_path).unwrap();
+        assert_eq!(exported_sram(&nes, &dir), vec![2; SRAM_SIZE]);
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,91 @@
+use super::rom_from_prg_chr;
+use crate::NES;
+use serde_json::Value;
+
+/// Sets the backdrop color every frame from a counter in RAM, so the frames
+/// depend on the state
+const COLOR_CYCLE: &[u8] = &[
+    0xA9, 0x3F, // LDA #$3F
+    0x8D, 0x06, 0x20, // STA $2006
+    0xA9, 0x00, // LDA #$00
+    0x8D, 0x06, 0x20, // STA $2006
+    0xE6, 0x10, // INC $10
+    0xA5, 0x10, // LDA $10
+    0x29, 0x3F, // AND #$3F
+    0x8D, 0x07, 0x20, // STA $2007
+    0x4C, 0x00, 0x80, // JMP $8000
+];
+
+/// The paths of the values that differ between `a` and `b`
+fn diff_paths(a: &Value, b: &Value, path: String, diffs: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                match b.get(key) {
+                    Some(other) => diff_paths(value, other, format!("{path}.{key}"), diffs),
+                    None => diffs.push(format!("{path}.{key}")),
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (value, other)) in a.iter().zip(b).enumerate() {
+                diff_paths(value, other, format!("{path}[{i}]"), diffs);
+            }
+        }
+        _ if a != b => diffs.push(path),
+        _ => {}
+    }
+}
+
+#[test]
+fn import_exported_state() {
+    let mut nes = NES::new_from_bytes(&rom_from_prg_chr(COLOR_CYCLE, &[], 0)).unwrap();
+    for _ in 0..3 {
+        nes.clock_for_frame();
+    }
+    let json = nes.export_state_json();
+
+    let mut other = NES::new_from_bytes(&rom_from_prg_chr(COLOR_CYCLE, &[], 0)).unwrap();
+    other.import_state_json(&json).unwrap();
+    assert_eq!(other.export_state_json(), json);
+
+    for _ in 0..2 {
+        nes.clock_for_frame();
+        other.clock_for_frame();
+        assert_eq!(nes.pixel_buffer(), other.pixel_buffer());
+    }
+}
+
+#[test]
+fn invalid_state_does_not_modify_the_emulator() {
+    let mut nes = NES::new_from_bytes(&rom_from_prg_chr(COLOR_CYCLE, &[], 0)).unwrap();
+    nes.clock_for_frame();
+    let json = nes.export_state_json();
+
+    let mut state: Value = serde_json::from_str(&json).unwrap();
+    state["ppu"]["bus"]["vram"] = Value::Array(vec![]);
+
+    assert!(nes
+        .import_state_json(&serde_json::to_string(&state).unwrap())
+        .is_err());
+    assert!(nes.import_state_json("{}").is_err());
+    assert_eq!(nes.export_state_json(), json);
+}
+
+#[test]
+fn one_ram_byte_difference() {
+    // the same timing and registers, but only the first writes to RAM, as
+    // the RAM and `X` are 0 at power-up
+    let sta = [0xA9, 0x42, 0x85, 0x10, 0x4C, 0x04, 0x80]; // LDA #$42; STA $10
+    let stx = [0xA9, 0x42, 0x86, 0x10, 0x4C, 0x04, 0x80]; // LDA #$42; STX $10
+
+    let states = [sta, stx].map(|prg| {
+        let mut nes = NES::new_from_bytes(&rom_from_prg_chr(&prg, &[], 0)).unwrap();
+        nes.clock_for_frame();
+        serde_json::from_str::<Value>(&nes.export_state_json()).unwrap()
+    });
+
+    let mut diffs = Vec::new();
+    diff_paths(&states[0], &states[1], String::new(), &mut diffs);
+    assert_eq!(diffs, [".cpu.bus.ram[16]"]);
+}
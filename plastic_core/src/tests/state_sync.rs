@@ -0,0 +1,97 @@
+use super::NesTester;
+use crate::common::save_state::SaveError;
+use crate::display::frame_hash;
+use crate::SyncBaseline;
+
+const ROM: &str = "../test_roms/sprite_hit_tests/01.basics.nes";
+
+fn full_state(nes: &NesTester) -> Vec<u8> {
+    let mut state = Vec::new();
+    nes.nes.save_state(&mut state).unwrap();
+    state
+}
+
+#[test]
+fn delta_after_a_frame_is_small() {
+    let mut sender = NesTester::new(ROM).unwrap();
+    for _ in 0..60 {
+        sender.clock_for_frame();
+    }
+
+    let baseline = sender.nes.state_sync_baseline().unwrap();
+    assert_eq!(baseline.state(), full_state(&sender));
+    // nothing changed, a byte for each of the 6 sections
+    assert_eq!(sender.nes.state_sync_delta(&baseline).unwrap().len(), 6);
+
+    sender.clock_for_frame();
+    let delta = sender.nes.state_sync_delta(&baseline).unwrap();
+    let state_length = full_state(&sender).len();
+    assert!(
+        delta.len() * 20 < state_length,
+        "delta: {}, state: {state_length}",
+        delta.len()
+    );
+}
+
+#[test]
+fn delta_reproduces_the_frame() {
+    let mut sender = NesTester::new(ROM).unwrap();
+    let mut receiver = NesTester::new(ROM).unwrap();
+    for _ in 0..30 {
+        sender.clock_for_frame();
+    }
+
+    let baseline = sender.nes.state_sync_baseline().unwrap();
+    let received_baseline = SyncBaseline::from_bytes(&baseline.to_bytes()).unwrap();
+    assert_eq!(received_baseline, baseline);
+
+    for _ in 0..3 {
+        for _ in 0..10 {
+            sender.clock_for_frame();
+        }
+        let delta = sender.nes.state_sync_delta(&baseline).unwrap();
+        receiver
+            .nes
+            .apply_state_delta(&received_baseline, &delta)
+            .unwrap();
+        assert_eq!(full_state(&receiver), full_state(&sender));
+
+        // the pixels are not part of the state, run a frame on both
+        sender.clock_for_frame();
+        receiver.clock_for_frame();
+        assert_eq!(
+            frame_hash(receiver.pixel_buffer()),
+            frame_hash(sender.pixel_buffer())
+        );
+    }
+}
+
+#[test]
+fn corrupted_delta_is_rejected() {
+    let mut nes = NesTester::new(ROM).unwrap();
+    let baseline = nes.nes.state_sync_baseline().unwrap();
+    nes.clock_for_frame();
+    let delta = nes.nes.state_sync_delta(&baseline).unwrap();
+    let state = full_state(&nes);
+
+    // unknown section kind
+    let mut bad = delta.clone();
+    bad[0] = 7;
+    assert!(matches!(
+        nes.nes.apply_state_delta(&baseline, &bad),
+        Err(SaveError::SerializationError)
+    ));
+    assert!(nes
+        .nes
+        .apply_state_delta(&baseline, &delta[..delta.len() - 1])
+        .is_err());
+    let mut extra = delta.clone();
+    extra.push(0);
+    assert!(matches!(
+        nes.nes.apply_state_delta(&baseline, &extra),
+        Err(SaveError::ContainExtraData)
+    ));
+
+    // not modified on failure
+    assert_eq!(full_state(&nes), state);
+}
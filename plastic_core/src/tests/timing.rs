@@ -0,0 +1,72 @@
+use crate::nes_timing::{
+    CPU_FREQ_NTSC, CPU_FREQ_PAL, CYCLES_PER_FRAME_NTSC, CYCLES_PER_FRAME_NTSC_EVEN,
+    CYCLES_PER_FRAME_NTSC_ODD, CYCLES_PER_FRAME_PAL, NTSC_FPS, PAL_FPS,
+};
+use crate::tests::NesTester;
+use crate::Region;
+
+fn assert_close(a: f64, b: f64, tolerance: f64) {
+    assert!((a - b).abs() <= tolerance, "{} != {}", a, b);
+}
+
+#[test]
+fn timing_constants_are_consistent() {
+    assert_close(CYCLES_PER_FRAME_NTSC * NTSC_FPS, CPU_FREQ_NTSC, 1e-6);
+    assert_close(CYCLES_PER_FRAME_PAL * PAL_FPS, CPU_FREQ_PAL, 1e-6);
+
+    // the odd frame is one PPU dot shorter
+    assert_close(
+        CYCLES_PER_FRAME_NTSC_EVEN - CYCLES_PER_FRAME_NTSC_ODD,
+        1. / 3.,
+        1e-9,
+    );
+    assert_eq!(CYCLES_PER_FRAME_NTSC, 29780.5);
+    assert_eq!(CYCLES_PER_FRAME_PAL, 33247.5);
+
+    assert_close(CPU_FREQ_NTSC, 1_789_772.727, 1e-3);
+    assert_close(CPU_FREQ_PAL, 1_662_607.0, 1e-3);
+    assert_close(NTSC_FPS, 60.0988, 1e-4);
+    assert_close(PAL_FPS, 50.007, 1e-3);
+
+    assert_eq!(Region::Ntsc.frame_rate(), NTSC_FPS);
+    assert_eq!(Region::Pal.frame_rate(), PAL_FPS);
+}
+
+#[test]
+fn clock_for_frame_cycles() {
+    // JMP $8000
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    nes.clock_for_frame();
+
+    let frame_cycles = (0..100)
+        .map(|_| {
+            nes.clock_for_frame();
+            nes.nes.frame_stats().cpu_cycles
+        })
+        .collect::<Vec<_>>();
+
+    // the frames alternate between the two lengths
+    for pair in frame_cycles.chunks(2) {
+        let mut pair = pair.to_vec();
+        pair.sort();
+        assert_eq!(pair, [29780, 29781]);
+    }
+    assert_eq!(
+        frame_cycles.iter().sum::<u32>() as f64,
+        100. * CYCLES_PER_FRAME_NTSC
+    );
+}
+
+#[test]
+fn current_fps_follows_region() {
+    let mut nes = NesTester::from_prg(&[0x4C, 0x00, 0x80]);
+    assert_eq!(nes.nes.current_fps(), NTSC_FPS);
+
+    // the frames still have NTSC timing with PAL
+    nes.nes.set_region(Region::Pal);
+    assert_close(
+        nes.nes.current_fps(),
+        CPU_FREQ_PAL / CYCLES_PER_FRAME_NTSC,
+        1e-9,
+    );
+}
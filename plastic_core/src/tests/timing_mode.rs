@@ -0,0 +1,108 @@
+use super::rom_from_prg_chr;
+use crate::{Diagnostic, Region, RegionSource, TimingMode, NES};
+
+/// An infinite loop ROM, NES 2.0 with `byte_12` as the timing byte if `nes2`,
+/// otherwise iNES 1.0 with `byte_9` as the TV system byte
+fn rom_with_timing(nes2: bool, byte_9: u8, byte_12: u8) -> Vec<u8> {
+    let mut rom = rom_from_prg_chr(&[0x4C, 0x00, 0x80], &[], 0);
+    if nes2 {
+        rom[7] = 0x08;
+        rom[12] = byte_12;
+    } else {
+        rom[9] = byte_9;
+    }
+    rom
+}
+
+#[test]
+fn nes2_timing_values() {
+    let cases = [
+        (0, TimingMode::Ntsc, Region::Ntsc),
+        (1, TimingMode::Pal, Region::Pal),
+        (2, TimingMode::MultiRegion, Region::Ntsc),
+        (3, TimingMode::Dendy, Region::Pal),
+    ];
+
+    for (code, timing_mode, region) in cases {
+        let nes = NES::new_from_bytes(&rom_with_timing(true, 0, code)).unwrap();
+
+        assert_eq!(nes.timing_mode(), Some(timing_mode));
+        assert_eq!(nes.region(), region, "timing {:?}", timing_mode);
+        assert_eq!(nes.region_source(), RegionSource::Header);
+    }
+}
+
+#[test]
+fn ines1_guessing_is_opt_in() {
+    let mut nes = NES::new_from_bytes(&rom_with_timing(false, 1, 0)).unwrap();
+    assert_eq!(nes.timing_mode(), None);
+    assert_eq!(nes.region(), Region::Ntsc);
+    assert_eq!(nes.region_source(), RegionSource::Default);
+
+    nes.set_region_guessing(true);
+    assert_eq!(nes.region(), Region::Pal);
+    assert_eq!(nes.region_source(), RegionSource::Header);
+
+    nes.set_region_guessing(false);
+    assert_eq!(nes.region(), Region::Ntsc);
+    assert_eq!(nes.region_source(), RegionSource::Default);
+}
+
+#[test]
+fn ines1_guessing_from_file_name() {
+    let dir = std::env::temp_dir().join("plastic_test_timing_mode");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for (name, region) in [
+        ("Game (Europe).nes", Region::Pal),
+        ("Game (e) [!].nes", Region::Pal),
+        ("Game (U).nes", Region::Ntsc),
+    ] {
+        let path = dir.join(name);
+        std::fs::write(&path, rom_with_timing(false, 0, 0)).unwrap();
+
+        let mut nes = NES::new(&path).unwrap();
+        assert_eq!(nes.region(), Region::Ntsc);
+        nes.set_region_guessing(true);
+        assert_eq!(nes.region(), region, "{}", name);
+    }
+}
+
+#[test]
+fn api_has_priority_over_header() {
+    let mut nes = NES::new_from_bytes(&rom_with_timing(true, 0, 1)).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+    assert!(nes.take_diagnostics().is_empty());
+
+    nes.set_region(Region::Ntsc);
+    assert_eq!(nes.region(), Region::Ntsc);
+    assert_eq!(nes.region_source(), RegionSource::Api);
+    assert_eq!(nes.take_diagnostics(), vec![Diagnostic::PalRomOnNtsc]);
+
+    nes.set_region_guessing(true);
+    nes.reset();
+    assert_eq!(nes.region(), Region::Ntsc);
+}
+
+#[cfg(feature = "overrides")]
+#[test]
+fn overrides_priority() {
+    let mut nes = NES::new_from_bytes(&rom_with_timing(true, 0, 1)).unwrap();
+    let overrides = format!(
+        "[[game]]\ncrc32 = \"{:08X}\"\nregion = \"ntsc\"",
+        nes.rom_crc32()
+    );
+
+    // overrides > header
+    nes.load_overrides(&overrides).unwrap();
+    assert_eq!(nes.region(), Region::Ntsc);
+    assert_eq!(nes.region_source(), RegionSource::Overrides);
+    nes.set_region_guessing(true);
+    assert_eq!(nes.region(), Region::Ntsc);
+
+    // API > overrides
+    nes.set_region(Region::Pal);
+    nes.load_overrides(&overrides).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+    assert_eq!(nes.region_source(), RegionSource::Api);
+}
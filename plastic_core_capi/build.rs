@@ -0,0 +1,13 @@
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+        cbindgen::generate(&crate_dir)
+            .expect("Unable to generate C header")
+            .write_to_file(std::path::Path::new(&crate_dir).join("include/plastic.h"));
+    }
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,144 @@
+#ifndef PLASTIC_H
+#define PLASTIC_H
+
+/* This file is generated by cbindgen, do not modify it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Keys of the NES controller, used in [`plastic_set_button`]
+ */
+typedef enum PlasticKey {
+  PLASTIC_KEY_A = 0,
+  PLASTIC_KEY_B = 1,
+  PLASTIC_KEY_SELECT = 2,
+  PLASTIC_KEY_START = 3,
+  PLASTIC_KEY_UP = 4,
+  PLASTIC_KEY_DOWN = 5,
+  PLASTIC_KEY_LEFT = 6,
+  PLASTIC_KEY_RIGHT = 7,
+} PlasticKey;
+
+/**
+ * Result codes returned by the API functions
+ */
+typedef enum PlasticResult {
+  /**
+   * The operation succeeded
+   */
+  PLASTIC_RESULT_OK = 0,
+  /**
+   * A required pointer argument is null
+   */
+  PLASTIC_RESULT_NULL_POINTER = -1,
+  /**
+   * The provided buffer is too small
+   */
+  PLASTIC_RESULT_BUFFER_TOO_SMALL = -2,
+  /**
+   * An invalid key was provided to [`plastic_set_button`]
+   */
+  PLASTIC_RESULT_INVALID_KEY = -3,
+  /**
+   * Failed to save or load the state
+   */
+  PLASTIC_RESULT_STATE_ERROR = -4,
+  /**
+   * The emulator panicked during the call
+   */
+  PLASTIC_RESULT_PANIC = -5,
+  /**
+   * The emulator panicked in a previous call, and can't be used anymore
+   */
+  PLASTIC_RESULT_POISONED = -6,
+} PlasticResult;
+
+/**
+ * Opaque handle to an emulator instance
+ */
+typedef struct PlasticNes PlasticNes;
+
+/**
+ * Create a new emulator instance from the content of a `.nes` ROM file.
+ *
+ * Returns null if the ROM is invalid or not supported.
+ *
+ * # Safety
+ * `rom` must point to `len` readable bytes.
+ */
+struct PlasticNes *plastic_new(const uint8_t *rom, size_t len);
+
+/**
+ * Destroy an emulator instance created by [`plastic_new`], passing null does nothing.
+ *
+ * # Safety
+ * `handle` must be null or a handle returned by [`plastic_new`] that was not destroyed before.
+ */
+void plastic_destroy(struct PlasticNes *handle);
+
+/**
+ * Run the emulator for one video frame.
+ *
+ * # Safety
+ * `handle` must be null or a valid handle.
+ */
+enum PlasticResult plastic_clock_frame(struct PlasticNes *handle);
+
+/**
+ * Get the pixel buffer of the last frame in RGB format (3 bytes per pixel, 256x240 pixels).
+ *
+ * The length is written to `out_len` if it's not null.
+ * The buffer is valid until the next call that mutates the handle.
+ * Returns null on error.
+ *
+ * # Safety
+ * `handle` must be null or a valid handle, `out_len` must be null or valid for writes.
+ */
+const uint8_t *plastic_pixel_buffer(struct PlasticNes *handle, size_t *out_len);
+
+/**
+ * Read up to `max` audio samples into `out`, returns the number of samples written.
+ *
+ * Samples that don't fit are kept for the next call.
+ *
+ * # Safety
+ * `handle` must be null or a valid handle, `out` must be valid for `max` writes.
+ */
+size_t plastic_audio_buffer(struct PlasticNes *handle, float *out, size_t max);
+
+/**
+ * Set the state of a controller key, `key` is one of [`PlasticKey`].
+ *
+ * # Safety
+ * `handle` must be null or a valid handle.
+ */
+enum PlasticResult plastic_set_button(struct PlasticNes *handle, uint32_t key, bool pressed);
+
+/**
+ * Save the state of the emulator into `buffer` of size `capacity`.
+ *
+ * The size of the state is written to `out_len` (if not null) even when the buffer
+ * is too small, so it can be called with a null `buffer` to get the required size.
+ *
+ * # Safety
+ * `handle` must be null or a valid handle, `buffer` must be null or valid for `capacity`
+ * writes and `out_len` must be null or valid for writes.
+ */
+enum PlasticResult plastic_save_state(struct PlasticNes *handle,
+                                      uint8_t *buffer,
+                                      size_t capacity,
+                                      size_t *out_len);
+
+/**
+ * Load the state of the emulator from `buffer` of size `len`.
+ *
+ * # Safety
+ * `handle` must be null or a valid handle, `buffer` must point to `len` readable bytes.
+ */
+enum PlasticResult plastic_load_state(struct PlasticNes *handle, const uint8_t *buffer, size_t len);
+
+#endif /* PLASTIC_H */
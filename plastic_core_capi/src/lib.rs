@@ -0,0 +1,287 @@
+//! # Plastic NES Core C API
+//!
+//! C ABI bindings over [`plastic_core`], to be able to embed the emulator in other languages.
+//!
+//! All functions take an opaque [`PlasticNes`] handle created by [`plastic_new`] and destroyed
+//! by [`plastic_destroy`]. Panics are never propagated to the caller, instead, the function
+//! returns [`PlasticResult::Panic`] and the handle is marked as poisoned, any later call
+//! with it (other than [`plastic_destroy`]) will fail with [`PlasticResult::Poisoned`].
+//!
+//! The C header is in `include/plastic.h`, and can be regenerated by building with the `header` feature.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use plastic_core::{NESKey, NES};
+
+mod tests;
+
+/// Result codes returned by the API functions
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlasticResult {
+    /// The operation succeeded
+    Ok = 0,
+    /// A required pointer argument is null
+    NullPointer = -1,
+    /// The provided buffer is too small
+    BufferTooSmall = -2,
+    /// An invalid key was provided to [`plastic_set_button`]
+    InvalidKey = -3,
+    /// Failed to save or load the state
+    StateError = -4,
+    /// The emulator panicked during the call
+    Panic = -5,
+    /// The emulator panicked in a previous call, and can't be used anymore
+    Poisoned = -6,
+}
+
+/// Keys of the NES controller, used in [`plastic_set_button`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlasticKey {
+    A = 0,
+    B = 1,
+    Select = 2,
+    Start = 3,
+    Up = 4,
+    Down = 5,
+    Left = 6,
+    Right = 7,
+}
+
+impl PlasticKey {
+    fn from_raw(key: u32) -> Option<NESKey> {
+        let key = match key {
+            0 => NESKey::A,
+            1 => NESKey::B,
+            2 => NESKey::Select,
+            3 => NESKey::Start,
+            4 => NESKey::Up,
+            5 => NESKey::Down,
+            6 => NESKey::Left,
+            7 => NESKey::Right,
+            _ => return None,
+        };
+
+        Some(key)
+    }
+}
+
+/// Opaque handle to an emulator instance
+pub struct PlasticNes {
+    nes: NES,
+    /// audio samples taken from the emulator but not yet read by the caller
+    pending_audio: Vec<f32>,
+    poisoned: bool,
+}
+
+/// Run `f` on the handle, catching any panics and converting them to [`PlasticResult::Panic`]
+fn with_handle<F>(handle: *mut PlasticNes, f: F) -> PlasticResult
+where
+    F: FnOnce(&mut PlasticNes) -> PlasticResult,
+{
+    // SAFETY: the caller guarantees that the handle is either null or valid
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return PlasticResult::NullPointer;
+    };
+
+    if handle.poisoned {
+        return PlasticResult::Poisoned;
+    }
+
+    match catch_unwind(AssertUnwindSafe(|| f(handle))) {
+        Ok(result) => result,
+        Err(_) => {
+            handle.poisoned = true;
+            PlasticResult::Panic
+        }
+    }
+}
+
+/// Create a new emulator instance from the content of a `.nes` ROM file.
+///
+/// Returns null if the ROM is invalid or not supported.
+///
+/// # Safety
+/// `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_new(rom: *const u8, len: usize) -> *mut PlasticNes {
+    if rom.is_null() {
+        return ptr::null_mut();
+    }
+
+    let rom = slice::from_raw_parts(rom, len);
+
+    match catch_unwind(|| NES::new_from_bytes(rom)) {
+        Ok(Ok(nes)) => Box::into_raw(Box::new(PlasticNes {
+            nes,
+            pending_audio: Vec::new(),
+            poisoned: false,
+        })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Destroy an emulator instance created by [`plastic_new`], passing null does nothing.
+///
+/// # Safety
+/// `handle` must be null or a handle returned by [`plastic_new`] that was not destroyed before.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_destroy(handle: *mut PlasticNes) {
+    if !handle.is_null() {
+        let handle = Box::from_raw(handle);
+        // don't unwind into the caller if dropping (saving SRAM) panics
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(handle)));
+    }
+}
+
+/// Run the emulator for one video frame.
+///
+/// # Safety
+/// `handle` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_clock_frame(handle: *mut PlasticNes) -> PlasticResult {
+    with_handle(handle, |handle| {
+        handle.nes.clock_for_frame();
+        PlasticResult::Ok
+    })
+}
+
+/// Get the pixel buffer of the last frame in RGB format (3 bytes per pixel, 256x240 pixels).
+///
+/// The length is written to `out_len` if it's not null.
+/// The buffer is valid until the next call that mutates the handle.
+/// Returns null on error.
+///
+/// # Safety
+/// `handle` must be null or a valid handle, `out_len` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_pixel_buffer(
+    handle: *mut PlasticNes,
+    out_len: *mut usize,
+) -> *const u8 {
+    let mut result = ptr::null();
+    let mut len = 0;
+
+    with_handle(handle, |handle| {
+        let buffer = handle.nes.pixel_buffer();
+        result = buffer.as_ptr();
+        len = buffer.len();
+        PlasticResult::Ok
+    });
+
+    if !out_len.is_null() {
+        *out_len = len;
+    }
+
+    result
+}
+
+/// Read up to `max` audio samples into `out`, returns the number of samples written.
+///
+/// Samples that don't fit are kept for the next call.
+///
+/// # Safety
+/// `handle` must be null or a valid handle, `out` must be valid for `max` writes.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_audio_buffer(
+    handle: *mut PlasticNes,
+    out: *mut f32,
+    max: usize,
+) -> usize {
+    if out.is_null() {
+        return 0;
+    }
+    let out = slice::from_raw_parts_mut(out, max);
+    let mut written = 0;
+
+    with_handle(handle, |handle| {
+        let new_samples = handle.nes.audio_buffer();
+        handle.pending_audio.extend_from_slice(&new_samples);
+
+        written = handle.pending_audio.len().min(max);
+        out[..written].copy_from_slice(&handle.pending_audio[..written]);
+        handle.pending_audio.drain(..written);
+
+        PlasticResult::Ok
+    });
+
+    written
+}
+
+/// Set the state of a controller key, `key` is one of [`PlasticKey`].
+///
+/// # Safety
+/// `handle` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_set_button(
+    handle: *mut PlasticNes,
+    key: u32,
+    pressed: bool,
+) -> PlasticResult {
+    with_handle(handle, |handle| {
+        let Some(key) = PlasticKey::from_raw(key) else {
+            return PlasticResult::InvalidKey;
+        };
+
+        handle.nes.set_controller_state(key, pressed);
+        PlasticResult::Ok
+    })
+}
+
+/// Save the state of the emulator into `buffer` of size `capacity`.
+///
+/// The size of the state is written to `out_len` (if not null) even when the buffer
+/// is too small, so it can be called with a null `buffer` to get the required size.
+///
+/// # Safety
+/// `handle` must be null or a valid handle, `buffer` must be null or valid for `capacity`
+/// writes and `out_len` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_save_state(
+    handle: *mut PlasticNes,
+    buffer: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> PlasticResult {
+    with_handle(handle, |handle| {
+        let mut state = Vec::new();
+        if handle.nes.save_state(&mut state).is_err() {
+            return PlasticResult::StateError;
+        }
+
+        if !out_len.is_null() {
+            *out_len = state.len();
+        }
+
+        if buffer.is_null() || capacity < state.len() {
+            return PlasticResult::BufferTooSmall;
+        }
+
+        ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+        PlasticResult::Ok
+    })
+}
+
+/// Load the state of the emulator from `buffer` of size `len`.
+///
+/// # Safety
+/// `handle` must be null or a valid handle, `buffer` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plastic_load_state(
+    handle: *mut PlasticNes,
+    buffer: *const u8,
+    len: usize,
+) -> PlasticResult {
+    if buffer.is_null() {
+        return PlasticResult::NullPointer;
+    }
+    let state = slice::from_raw_parts(buffer, len);
+
+    with_handle(handle, |handle| match handle.nes.load_state(state) {
+        Ok(()) => PlasticResult::Ok,
+        Err(_) => PlasticResult::StateError,
+    })
+}
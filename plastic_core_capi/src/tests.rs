@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod capi_tests {
+    use super::super::*;
+    use std::ptr;
+
+    fn new_handle() -> *mut PlasticNes {
+        let rom = std::fs::read("../test_roms/instr_test-v5/all_instrs.nes").unwrap();
+        unsafe { plastic_new(rom.as_ptr(), rom.len()) }
+    }
+
+    #[test]
+    fn handle_lifecycle() {
+        let handle = new_handle();
+        assert!(!handle.is_null());
+
+        unsafe {
+            assert_eq!(
+                plastic_set_button(handle, PlasticKey::Start as u32, true),
+                PlasticResult::Ok
+            );
+            assert_eq!(
+                plastic_set_button(handle, 8, true),
+                PlasticResult::InvalidKey
+            );
+
+            for _ in 0..2 {
+                assert_eq!(plastic_clock_frame(handle), PlasticResult::Ok);
+            }
+
+            let mut len = 0;
+            let pixels = plastic_pixel_buffer(handle, &mut len);
+            assert!(!pixels.is_null());
+            assert_eq!(len, 256 * 240 * 3);
+
+            // read audio in small chunks, nothing should be lost
+            let mut audio = [0.; 100];
+            let mut total = 0;
+            loop {
+                let n = plastic_audio_buffer(handle, audio.as_mut_ptr(), audio.len());
+                total += n;
+                if n < audio.len() {
+                    break;
+                }
+            }
+            assert!(total > 100);
+
+            // query the size first
+            let mut state_len = 0;
+            assert_eq!(
+                plastic_save_state(handle, ptr::null_mut(), 0, &mut state_len),
+                PlasticResult::BufferTooSmall
+            );
+            let mut state = vec![0; state_len];
+            assert_eq!(
+                plastic_save_state(handle, state.as_mut_ptr(), state.len(), &mut state_len),
+                PlasticResult::Ok
+            );
+
+            let other = new_handle();
+            assert_eq!(
+                plastic_load_state(other, state.as_ptr(), state.len()),
+                PlasticResult::Ok
+            );
+            assert_eq!(
+                plastic_load_state(other, state.as_ptr(), state.len() - 1),
+                PlasticResult::StateError
+            );
+
+            plastic_destroy(other);
+            plastic_destroy(handle);
+            // no-op
+            plastic_destroy(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        unsafe {
+            assert!(plastic_new(ptr::null(), 0).is_null());
+            let garbage = [0u8; 32];
+            assert!(plastic_new(garbage.as_ptr(), garbage.len()).is_null());
+
+            assert_eq!(
+                plastic_clock_frame(ptr::null_mut()),
+                PlasticResult::NullPointer
+            );
+            let mut len = 1;
+            assert!(plastic_pixel_buffer(ptr::null_mut(), &mut len).is_null());
+            assert_eq!(len, 0);
+        }
+    }
+
+    #[test]
+    fn panic_containment() {
+        let handle = new_handle();
+
+        let result = with_handle(handle, |_| panic!("emulator panic"));
+        assert_eq!(result, PlasticResult::Panic);
+
+        unsafe {
+            // the handle can't be used anymore, but can be destroyed
+            assert_eq!(plastic_clock_frame(handle), PlasticResult::Poisoned);
+            assert!(plastic_pixel_buffer(handle, ptr::null_mut()).is_null());
+            plastic_destroy(handle);
+        }
+    }
+}
@@ -5,6 +5,7 @@ use plastic_core::{
     misc::{process_audio, Fps},
     nes_audio::SAMPLE_RATE,
     nes_display::{TV_HEIGHT, TV_WIDTH},
+    nes_timing::NTSC_FPS,
     NESKey, NES,
 };
 use ratatui::{
@@ -160,6 +161,15 @@ impl Ui {
         )
     }
 
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            self.nes.pause_audio();
+        } else {
+            self.nes.resume_audio();
+        }
+    }
+
     fn save_state(&mut self, slot: u8) {
         if let Some(path) = self.get_save_state_path(slot) {
             let file = fs::File::create(&path).unwrap();
@@ -336,7 +346,7 @@ impl Ui {
                     KeyCode::Char('A') | KeyCode::Char('a') => Some(NESKey::Left),
                     KeyCode::Char('D') | KeyCode::Char('d') => Some(NESKey::Right),
                     KeyCode::Char('P') | KeyCode::Char('p') if is_press => {
-                        self.paused = !self.paused;
+                        self.toggle_pause();
                         None
                     }
                     KeyCode::Enter if is_press => {
@@ -437,7 +447,7 @@ impl Ui {
                         self.is_file_explorer_open = true;
                     }
                     MenuEvent::FileReset => self.nes.reset(),
-                    MenuEvent::FilePause => self.paused = !self.paused,
+                    MenuEvent::FilePause => self.toggle_pause(),
                     MenuEvent::FileClose => self.nes = NES::new_without_file(),
                     MenuEvent::FileExit => return true,
                     MenuEvent::SaveState(i) => self.save_state(i),
@@ -501,7 +511,7 @@ impl Ui {
 
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend).unwrap();
-        let mut fps = Fps::new(61.0);
+        let mut fps = Fps::new(NTSC_FPS);
 
         loop {
             if let Some(ref mut player) = self.audio_player {
@@ -7,11 +7,12 @@ use plastic_core::{
     misc::{process_audio, Fps},
     nes_audio::SAMPLE_RATE,
     nes_display::{TV_HEIGHT, TV_WIDTH},
+    nes_timing::NTSC_FPS,
     NESKey, NES,
 };
 
 // 60 FPS gives audio glitches
-const TARGET_FPS: f64 = 61.;
+const TARGET_FPS: f64 = NTSC_FPS;
 
 const MIN_STATE_SLOT: u8 = 0;
 const MAX_STATE_SLOT: u8 = 9;
@@ -40,6 +41,27 @@ const PAUSE_SHORTCUT: egui::KeyboardShortcut =
 const CLOSE_SHORTCUT: egui::KeyboardShortcut =
     egui::KeyboardShortcut::new(egui::Modifiers::CTRL, egui::Key::Q);
 
+const PLAYER_1_KEYS: [(egui::Key, NESKey); 8] = [
+    (egui::Key::J, NESKey::B),
+    (egui::Key::K, NESKey::A),
+    (egui::Key::U, NESKey::Select),
+    (egui::Key::I, NESKey::Start),
+    (egui::Key::W, NESKey::Up),
+    (egui::Key::S, NESKey::Down),
+    (egui::Key::A, NESKey::Left),
+    (egui::Key::D, NESKey::Right),
+];
+const PLAYER_2_KEYS: [(egui::Key, NESKey); 8] = [
+    (egui::Key::Comma, NESKey::B),
+    (egui::Key::Period, NESKey::A),
+    (egui::Key::L, NESKey::Select),
+    (egui::Key::Semicolon, NESKey::Start),
+    (egui::Key::ArrowUp, NESKey::Up),
+    (egui::Key::ArrowDown, NESKey::Down),
+    (egui::Key::ArrowLeft, NESKey::Left),
+    (egui::Key::ArrowRight, NESKey::Right),
+];
+
 struct App {
     fps: Fps,
     nes: NES,
@@ -177,39 +199,36 @@ impl App {
                 self.nes.reset();
             }
             if i.consume_shortcut(&PAUSE_SHORTCUT) {
-                self.paused = !self.paused;
-                if !self.paused {
-                    // clear the audio buffer
-                    _ = self.nes.audio_buffer();
-                }
+                self.toggle_pause();
             }
             if i.consume_shortcut(&CLOSE_SHORTCUT) {
                 self.nes = NES::new_without_file();
             }
 
             if !self.nes.is_empty() {
-                self.nes
-                    .set_controller_state(NESKey::B, i.key_down(egui::Key::J));
-                self.nes
-                    .set_controller_state(NESKey::A, i.key_down(egui::Key::K));
-                self.nes
-                    .set_controller_state(NESKey::Select, i.key_down(egui::Key::U));
-                self.nes
-                    .set_controller_state(NESKey::Start, i.key_down(egui::Key::I));
-                self.nes
-                    .set_controller_state(NESKey::Up, i.key_down(egui::Key::W));
-                self.nes
-                    .set_controller_state(NESKey::Down, i.key_down(egui::Key::S));
-                self.nes
-                    .set_controller_state(NESKey::Left, i.key_down(egui::Key::A));
-                self.nes
-                    .set_controller_state(NESKey::Right, i.key_down(egui::Key::D));
+                for (key, nes_key) in PLAYER_1_KEYS {
+                    self.nes.set_controller_state(nes_key, i.key_down(key));
+                }
+                for (key, nes_key) in PLAYER_2_KEYS {
+                    self.nes.set_controller2_state(nes_key, i.key_down(key));
+                }
             }
         });
 
         self.handle_gamepad();
     }
 
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            self.nes.pause_audio();
+        } else {
+            // drop the old samples, the audio fades in again
+            _ = self.nes.audio_buffer();
+            self.nes.resume_audio();
+        }
+    }
+
     fn update_title(&mut self, ctx: &egui::Context) {
         let title = format!(
             "Plastic {} {}",
@@ -263,11 +282,7 @@ impl App {
                     )
                     .clicked()
                 {
-                    self.paused = !self.paused;
-                    if !self.paused {
-                        // clear the audio buffer
-                        _ = self.nes.audio_buffer();
-                    }
+                    self.toggle_pause();
                 }
                 if ui
                     .add(